@@ -51,6 +51,8 @@ fn main() {
 
     // Re-run build script if opencv4.pc changes
     println!("cargo:rerun-if-changed=build.rs");
+    // include_dir! can't tell the compiler about files added to the frontend
+    println!("cargo:rerun-if-changed=src/static");
     println!("cargo:rerun-if-env-changed=PKG_CONFIG_PATH");
 
     // Capture rustc version for metrics
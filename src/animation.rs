@@ -0,0 +1,75 @@
+//! Animated lolcommits (`animate = true`).
+//!
+//! The client captures a burst of frames from the open camera stream and uploads them
+//! as several `image` parts, in order. The server runs every frame through the
+//! post-processors, with the chyron on the last frame only unless
+//! `animation_chyron = "all"`, and saves a looping GIF with its metadata in a sidecar.
+
+use crate::error::Result;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, DynamicImage, Frame};
+use std::io::Write;
+
+/// Extension of animated gallery images.
+pub const EXTENSION: &str = "gif";
+
+/// Time each frame is shown when the upload doesn't say.
+pub const DEFAULT_FRAME_DELAY_MS: u64 = 250;
+
+/// Scale `image` down to at most `max_width` wide, keeping its aspect ratio. 0 leaves
+/// it alone.
+pub fn fit_width(image: DynamicImage, max_width: u32) -> DynamicImage {
+    if max_width == 0 || image.width() <= max_width {
+        return image;
+    }
+    image.resize(max_width, u32::MAX, image::imageops::FilterType::Triangle)
+}
+
+/// Encode `frames` as a GIF that loops forever, showing each for `delay_ms`.
+pub fn write_gif(writer: impl Write, frames: &[DynamicImage], delay_ms: u64) -> Result {
+    let delay = Delay::from_numer_denom_ms(delay_ms.clamp(1, u32::MAX as u64) as u32, 1);
+    let mut encoder = GifEncoder::new_with_speed(writer, 10);
+    encoder.set_repeat(Repeat::Infinite)?;
+    encoder.encode_frames(
+        frames
+            .iter()
+            .map(|frame| Frame::from_parts(frame.to_rgba8(), 0, 0, delay)),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::AnimationDecoder;
+    use image::codecs::gif::GifDecoder;
+    use test_case::test_case;
+
+    #[test_case(640, 480, 480, (480, 360) ; "wide frame is scaled down")]
+    #[test_case(320, 240, 480, (320, 240) ; "narrow frame is kept")]
+    #[test_case(640, 480, 0, (640, 480) ; "zero disables")]
+    fn test_fit_width(width: u32, height: u32, max_width: u32, expected: (u32, u32)) {
+        let fitted = fit_width(DynamicImage::new_rgb8(width, height), max_width);
+        assert_eq!((fitted.width(), fitted.height()), expected);
+    }
+
+    #[test]
+    fn test_write_gif_round_trip() -> Result {
+        let frames = vec![
+            DynamicImage::new_rgb8(8, 6),
+            DynamicImage::ImageRgb8(image::RgbImage::from_pixel(8, 6, image::Rgb([255, 0, 0]))),
+            DynamicImage::new_rgb8(8, 6),
+        ];
+        let mut gif = Vec::new();
+
+        write_gif(&mut gif, &frames, 200)?;
+
+        let decoded = GifDecoder::new(std::io::Cursor::new(gif))?
+            .into_frames()
+            .collect_frames()?;
+        assert_eq!(decoded.len(), 3);
+        assert_eq!(decoded[1].delay().numer_denom_ms(), (200, 1));
+        assert_eq!(decoded[1].buffer().get_pixel(0, 0).0, [255, 0, 0, 255]);
+        Ok(())
+    }
+}
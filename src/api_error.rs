@@ -0,0 +1,292 @@
+//! Error responses of the server's JSON API.
+//!
+//! Every failed API request is answered with a matching status and
+//! `{"error": {"code": ..., "message": ..., "details": {...}}}`: `code` is stable and
+//! meant for programs, `message` for people, and `details` holds whatever else the code
+//! calls for, e.g. the `filename` of a duplicate upload. Handlers return [`ApiError`],
+//! crate [`Error`]s convert into it, and clients read bodies back with
+//! [`ErrorBody::parse`].
+
+use crate::error::Error;
+use crate::server::{INVALID_METADATA_ERROR_CODE, LOW_DISK_SPACE_ERROR_CODE, READ_ONLY_ERROR_CODE};
+use axum::{
+    Json,
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+
+/// Code of errors with no more specific one, the server's own fault.
+pub const INTERNAL_ERROR_CODE: &str = "internal_error";
+
+/// Code of requests for an image, job or route that doesn't exist.
+pub const NOT_FOUND_ERROR_CODE: &str = "not_found";
+
+/// Code of errors caused by the server's config, e.g. on reloading it.
+pub const INVALID_CONFIG_ERROR_CODE: &str = "invalid_config";
+
+/// What went wrong, as sent inside `{"error": ...}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErrorBody {
+    pub code: String,
+    pub message: String,
+    #[serde(default)]
+    pub details: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Envelope<T> {
+    error: T,
+}
+
+/// Bodies of servers from before errors were nested, `{"error": code, "message": ...}`
+/// with any details alongside.
+#[derive(Deserialize)]
+struct FlatBody {
+    error: String,
+    #[serde(default)]
+    message: String,
+    #[serde(flatten)]
+    details: serde_json::Map<String, serde_json::Value>,
+}
+
+impl ErrorBody {
+    /// Read an error response body, `None` when it isn't one (a plain text error from a
+    /// proxy, say). Flat bodies from older servers are read too.
+    pub fn parse(body: &str) -> Option<Self> {
+        if let Ok(Envelope { error }) = serde_json::from_str::<Envelope<Self>>(body) {
+            return Some(error);
+        }
+        let flat: FlatBody = serde_json::from_str(body).ok()?;
+        Some(Self {
+            code: flat.error,
+            message: flat.message,
+            details: flat.details,
+        })
+    }
+
+    /// A detail as a string, `None` when it's missing or isn't one.
+    pub fn detail_str(&self, key: &str) -> Option<&str> {
+        self.details.get(key)?.as_str()
+    }
+}
+
+/// An error response from a handler.
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    body: ErrorBody,
+    /// Sent as `Retry-After`, for refusals that will pass.
+    retry_after_secs: Option<u64>,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, code: &str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            body: ErrorBody {
+                code: code.to_string(),
+                message: message.into(),
+                details: serde_json::Map::new(),
+            },
+            retry_after_secs: None,
+        }
+    }
+
+    pub fn bad_request(code: &str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, code, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, NOT_FOUND_ERROR_CODE, message)
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            INTERNAL_ERROR_CODE,
+            message,
+        )
+    }
+
+    /// Add `key` to the details. Values that can't be serialized are left out.
+    pub fn with_detail(mut self, key: &str, value: impl Serialize) -> Self {
+        if let Ok(value) = serde_json::to_value(value) {
+            self.body.details.insert(key.to_string(), value);
+        }
+        self
+    }
+
+    pub fn with_retry_after(self, secs: u64) -> Self {
+        Self {
+            retry_after_secs: Some(secs),
+            ..self
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn code(&self) -> &str {
+        &self.body.code
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = Json(Envelope { error: self.body });
+        match self.retry_after_secs {
+            Some(secs) => {
+                (self.status, [(header::RETRY_AFTER, secs.to_string())], body).into_response()
+            }
+            None => (self.status, body).into_response(),
+        }
+    }
+}
+
+impl From<Error> for ApiError {
+    fn from(error: Error) -> Self {
+        let message = error.to_string();
+        match error {
+            Error::LowDiskSpace {
+                available_mb,
+                min_free_mb,
+                ..
+            } => Self::new(
+                StatusCode::INSUFFICIENT_STORAGE,
+                LOW_DISK_SPACE_ERROR_CODE,
+                message,
+            )
+            .with_detail("available_mb", available_mb)
+            .with_detail("min_free_mb", min_free_mb),
+            Error::ServerReadOnly { .. } => Self::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                READ_ONLY_ERROR_CODE,
+                message,
+            ),
+            Error::InvalidConfig { problems } => Self::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                INVALID_CONFIG_ERROR_CODE,
+                message,
+            )
+            .with_detail("problems", problems),
+            Error::ConfigFileRead { .. }
+            | Error::TomlDeserialize(_)
+            | Error::UnknownPostProcessor { .. }
+            | Error::InvalidColor { .. }
+            | Error::UnknownConfigKey { .. }
+            | Error::InvalidConfigValue { .. }
+            | Error::InvalidEnvOverride { .. } => Self::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                INVALID_CONFIG_ERROR_CODE,
+                message,
+            ),
+            Error::Io(e) if e.kind() == std::io::ErrorKind::NotFound => Self::not_found(message),
+            Error::Image(_) | Error::PngDecoding(_) => {
+                Self::new(StatusCode::UNPROCESSABLE_ENTITY, "invalid_image", message)
+            }
+            Error::UnsafeFilename { field, .. } => Self::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                INVALID_METADATA_ERROR_CODE,
+                message,
+            )
+            .with_detail("field", field),
+            _ => Self::internal(message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use test_case::test_case;
+
+    async fn body(error: ApiError) -> (StatusCode, serde_json::Value) {
+        let response = error.into_response();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_response_shape() {
+        let error = ApiError::new(StatusCode::CONFLICT, "duplicate_revision", "already there")
+            .with_detail("filename", "existing.png");
+
+        assert_eq!(
+            body(error).await,
+            (
+                StatusCode::CONFLICT,
+                serde_json::json!({"error": {
+                    "code": "duplicate_revision",
+                    "message": "already there",
+                    "details": {"filename": "existing.png"},
+                }})
+            )
+        );
+        let (_, value) = body(ApiError::not_found("no such image")).await;
+        assert_eq!(value["error"]["details"], serde_json::json!({}));
+    }
+
+    #[test]
+    fn test_retry_after_header() {
+        let response = ApiError::new(StatusCode::SERVICE_UNAVAILABLE, "busy", "later")
+            .with_retry_after(10)
+            .into_response();
+        assert_eq!(response.headers()[header::RETRY_AFTER], "10");
+    }
+
+    #[test_case(Error::LowDiskSpace { path: PathBuf::from("/srv"), available_mb: 5, min_free_mb: 100 }, 507, "low_disk_space" ; "low disk space")]
+    #[test_case(Error::ServerReadOnly { url: "http://lol".to_string() }, 503, "read_only" ; "read only")]
+    #[test_case(Error::InvalidConfig { problems: vec!["server.images_dir: relative".to_string()] }, 500, "invalid_config" ; "invalid config")]
+    #[test_case(Error::UnknownPostProcessor { name: "sepia".to_string() }, 500, "invalid_config" ; "unknown post processor")]
+    #[test_case(Error::Io(std::io::Error::from(std::io::ErrorKind::NotFound)), 404, "not_found" ; "not found")]
+    #[test_case(Error::Io(std::io::Error::other("disk on fire")), 500, "internal_error" ; "other io")]
+    #[test_case(Error::UnsafeFilename { field: "revision", value: "..".to_string() }, 422, "invalid_metadata" ; "unsafe filename")]
+    #[test_case(Error::NoRepoName, 500, "internal_error" ; "anything else")]
+    fn test_error_codes(error: Error, status: u16, code: &str) {
+        let error = ApiError::from(error);
+        assert_eq!((error.status().as_u16(), error.code()), (status, code));
+    }
+
+    #[test]
+    fn test_low_disk_space_details() {
+        let error = ApiError::from(Error::LowDiskSpace {
+            path: PathBuf::from("/srv"),
+            available_mb: 5,
+            min_free_mb: 100,
+        });
+        assert_eq!(error.body.details["min_free_mb"], 100);
+    }
+
+    #[test_case(r#"{"error":{"code":"read_only","message":"read-only","details":{}}}"#, Some(("read_only", "read-only")) ; "nested")]
+    #[test_case(r#"{"error":{"code":"busy","message":"later"}}"#, Some(("busy", "later")) ; "nested without details")]
+    #[test_case(r#"{"error":"read_only","message":"read-only"}"#, Some(("read_only", "read-only")) ; "flat")]
+    #[test_case(r#"{"error":"unknown"}"#, Some(("unknown", "")) ; "flat without message")]
+    #[test_case("Service Unavailable", None ; "plain text")]
+    #[test_case(r#"{"status":"ok"}"#, None ; "not an error")]
+    fn test_parse(body: &str, expected: Option<(&str, &str)>) {
+        let parsed = ErrorBody::parse(body);
+        assert_eq!(
+            parsed
+                .as_ref()
+                .map(|body| (body.code.as_str(), body.message.as_str())),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_parse_details() {
+        let nested = ErrorBody::parse(
+            r#"{"error":{"code":"duplicate_revision","message":"","details":{"filename":"a.png"}}}"#,
+        );
+        let flat = ErrorBody::parse(r#"{"error":"duplicate_revision","filename":"a.png"}"#);
+        for body in [nested, flat] {
+            assert_eq!(body.unwrap().detail_str("filename"), Some("a.png"));
+        }
+    }
+}
@@ -0,0 +1,255 @@
+//! Commit type badges (`[burned_in_chyron.badges]`): an emoji or a small PNG stamped in
+//! a corner of a lolcommit, gitmoji style, e.g. 🐛 on fixes and ✨ on features.
+//!
+//! Stamped along with the chyron, so wherever it's drawn. A badge that can't be drawn,
+//! an unreadable PNG or an emoji the font has no glyph for, is left out with a warning
+//! rather than failing the capture.
+
+use crate::chyron::measure_text_width;
+use crate::config::{BadgeCorner, BurnedInChyronConfig};
+use ab_glyph::{Font, FontArc, PxScale};
+use image::{Rgba, RgbaImage, imageops::FilterType};
+use imageproc::drawing::draw_text_mut;
+use std::path::Path;
+
+/// Key of the badge for commit types without their own.
+pub const DEFAULT_BADGE: &str = "default";
+
+/// Space between a badge and the edges of its corner.
+const BADGE_MARGIN: i64 = 10;
+
+/// The badge configured for `commit_type`, falling back to the default one.
+fn badge_for<'a>(config: &'a BurnedInChyronConfig, commit_type: &str) -> Option<&'a str> {
+    config
+        .badges
+        .get(commit_type)
+        .or_else(|| config.badges.get(DEFAULT_BADGE))
+        .map(String::as_str)
+}
+
+/// Whether a badge names a PNG rather than being an emoji.
+fn is_image_path(badge: &str) -> bool {
+    badge.contains('/') || badge.to_ascii_lowercase().ends_with(".png")
+}
+
+/// Stamp the badge for `commit_type` onto `canvas`, if one is configured. Emoji are
+/// drawn in `color` with `font`.
+pub fn stamp(
+    config: &BurnedInChyronConfig,
+    font: &FontArc,
+    color: Rgba<u8>,
+    canvas: &mut RgbaImage,
+    commit_type: &str,
+) {
+    let Some(badge) = badge_for(config, commit_type) else {
+        return;
+    };
+    let image = if is_image_path(badge) {
+        load_image(Path::new(badge), config.badge_size_px)
+    } else {
+        render_emoji(badge, font, color, config.badge_size_px)
+    };
+    if let Some(image) = image {
+        let (x, y) = position(config.badge_corner, canvas.dimensions(), image.dimensions());
+        composite(canvas, &image, x, y, config.badge_opacity);
+    }
+}
+
+/// A PNG badge, scaled down or up to fit `size` square.
+fn load_image(path: &Path, size: u32) -> Option<RgbaImage> {
+    let image = match image::open(path) {
+        Ok(image) => image,
+        Err(e) => {
+            tracing::warn!(path = %path.display(), error = %e, "Failed to load badge, leaving it out");
+            return None;
+        }
+    };
+    if image.width().max(image.height()) == size {
+        return Some(image.into_rgba8());
+    }
+    Some(image.resize(size, size, FilterType::Lanczos3).into_rgba8())
+}
+
+/// `emoji` drawn `size` tall on a transparent canvas, `None` when `font` lacks a glyph
+/// for it. Variation selectors and joiners needn't have glyphs.
+fn render_emoji(emoji: &str, font: &FontArc, color: Rgba<u8>, size: u32) -> Option<RgbaImage> {
+    let missing = emoji
+        .chars()
+        .filter(|c| !matches!(c, '\u{FE0E}' | '\u{FE0F}' | '\u{200D}'))
+        .any(|c| font.glyph_id(c).0 == 0);
+    if missing || emoji.is_empty() {
+        tracing::warn!(
+            emoji,
+            "Badge font has no glyph for the emoji, leaving it out"
+        );
+        return None;
+    }
+    let scale = PxScale::from(size as f32);
+    let width = measure_text_width(font, scale, emoji).ceil().max(1.0) as u32;
+    let mut image = RgbaImage::new(width, size);
+    draw_text_mut(&mut image, color, 0, 0, scale, font, emoji);
+    Some(image)
+}
+
+/// Top left of a `badge` sized image in `corner` of a `canvas` sized one.
+fn position(corner: BadgeCorner, canvas: (u32, u32), badge: (u32, u32)) -> (i64, i64) {
+    let far = |canvas: u32, badge: u32| canvas as i64 - badge as i64 - BADGE_MARGIN;
+    match corner {
+        BadgeCorner::TopLeft => (BADGE_MARGIN, BADGE_MARGIN),
+        BadgeCorner::TopRight => (far(canvas.0, badge.0), BADGE_MARGIN),
+        BadgeCorner::BottomLeft => (BADGE_MARGIN, far(canvas.1, badge.1)),
+        BadgeCorner::BottomRight => (far(canvas.0, badge.0), far(canvas.1, badge.1)),
+    }
+}
+
+/// Draw `badge` over `canvas` with its top left at `x`, `y`, its alpha scaled by
+/// `opacity`. Whatever falls outside the canvas is cut off.
+fn composite(canvas: &mut RgbaImage, badge: &RgbaImage, x: i64, y: i64, opacity: f32) {
+    let (width, height) = canvas.dimensions();
+    for (bx, by, source) in badge.enumerate_pixels() {
+        let (cx, cy) = (x + bx as i64, y + by as i64);
+        if cx < 0 || cy < 0 || cx >= width as i64 || cy >= height as i64 {
+            continue;
+        }
+        let source_alpha = source[3] as f32 / 255.0 * opacity.clamp(0.0, 1.0);
+        if source_alpha == 0.0 {
+            continue;
+        }
+        let target = canvas.get_pixel_mut(cx as u32, cy as u32);
+        let target_alpha = target[3] as f32 / 255.0;
+        // Porter-Duff over, so badges land right on transparent overlays too
+        let alpha = source_alpha + target_alpha * (1.0 - source_alpha);
+        let blend = |s: u8, t: u8| {
+            ((s as f32 * source_alpha + t as f32 * target_alpha * (1.0 - source_alpha)) / alpha)
+                .round() as u8
+        };
+        *target = Rgba([
+            blend(source[0], target[0]),
+            blend(source[1], target[1]),
+            blend(source[2], target[2]),
+            (alpha * 255.0).round() as u8,
+        ]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Result;
+    use test_case::test_case;
+
+    const SANS: &[u8] = include_bytes!("../tests/fixtures/fonts/DejaVuSans.ttf");
+
+    fn font() -> FontArc {
+        FontArc::try_from_slice(SANS).unwrap()
+    }
+
+    /// A 16x16 badge, opaque red apart from a fully transparent top left quarter.
+    fn write_badge(path: &Path) -> Result {
+        RgbaImage::from_fn(16, 16, |x, y| {
+            if x < 8 && y < 8 {
+                Rgba([0, 0, 255, 0])
+            } else {
+                Rgba([255, 0, 0, 255])
+            }
+        })
+        .save(path)?;
+        Ok(())
+    }
+
+    fn config(badges: &[(&str, &str)], corner: BadgeCorner) -> BurnedInChyronConfig {
+        BurnedInChyronConfig {
+            badges: badges
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            badge_corner: corner,
+            badge_size_px: 16,
+            ..Default::default()
+        }
+    }
+
+    #[test_case(BadgeCorner::TopLeft, (10, 10) ; "top left")]
+    #[test_case(BadgeCorner::TopRight, (74, 10) ; "top right")]
+    #[test_case(BadgeCorner::BottomLeft, (10, 38) ; "bottom left")]
+    #[test_case(BadgeCorner::BottomRight, (74, 38) ; "bottom right")]
+    fn test_png_badge_lands_in_corner(corner: BadgeCorner, (x, y): (u32, u32)) -> Result {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("bug.png");
+        write_badge(&path)?;
+        let grey = Rgba([50, 50, 50, 255]);
+        let mut canvas = RgbaImage::from_pixel(100, 64, grey);
+
+        let config = config(&[("fix", path.to_str().unwrap())], corner);
+        stamp(&config, &font(), Rgba([255; 4]), &mut canvas, "fix");
+
+        for (cx, cy, pixel) in canvas.enumerate_pixels() {
+            let in_badge = (x..x + 16).contains(&cx) && (y..y + 16).contains(&cy);
+            let in_transparent_quarter = cx < x + 8 && cy < y + 8;
+            let expected = if in_badge && !in_transparent_quarter {
+                Rgba([255, 0, 0, 255])
+            } else {
+                grey
+            };
+            assert_eq!(*pixel, expected, "({cx}, {cy})");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_badge_opacity_and_transparent_canvas() -> Result {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("bug.png");
+        write_badge(&path)?;
+        let config = BurnedInChyronConfig {
+            badge_opacity: 0.5,
+            ..config(&[("fix", path.to_str().unwrap())], BadgeCorner::TopLeft)
+        };
+
+        let mut opaque = RgbaImage::from_pixel(40, 40, Rgba([0, 0, 0, 255]));
+        stamp(&config, &font(), Rgba([255; 4]), &mut opaque, "fix");
+        assert_eq!(*opaque.get_pixel(20, 20), Rgba([128, 0, 0, 255]));
+
+        let mut overlay = RgbaImage::new(40, 40);
+        stamp(&config, &font(), Rgba([255; 4]), &mut overlay, "fix");
+        assert_eq!(*overlay.get_pixel(20, 20), Rgba([255, 0, 0, 128]));
+        assert_eq!(*overlay.get_pixel(12, 12), Rgba([0, 0, 0, 0]));
+        Ok(())
+    }
+
+    #[test_case("fix", Some("F") ; "own badge")]
+    #[test_case("docs", Some("D") ; "default")]
+    fn test_badge_for(commit_type: &str, expected: Option<&str>) {
+        let with_default = config(&[("fix", "F"), ("default", "D")], BadgeCorner::TopLeft);
+        assert_eq!(badge_for(&with_default, commit_type), expected);
+        let without = config(&[("fix", "F")], BadgeCorner::TopLeft);
+        assert_eq!(badge_for(&without, "docs"), None);
+    }
+
+    #[test]
+    fn test_png_badge_is_scaled_to_size() -> Result {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("bug.png");
+        write_badge(&path)?;
+
+        let badge = load_image(&path, 32).unwrap();
+
+        assert_eq!(badge.dimensions(), (32, 32));
+        assert!(load_image(&dir.path().join("missing.png"), 32).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_emoji_badge_drawn_with_font() {
+        let badge = render_emoji("★", &font(), Rgba([255, 255, 0, 255]), 24).unwrap();
+        assert_eq!(badge.height(), 24);
+        assert!(badge.pixels().any(|pixel| pixel[3] > 0));
+
+        // DejaVu Sans has no 🐛, so the badge is left out
+        let mut canvas = RgbaImage::from_pixel(64, 64, Rgba([0, 0, 0, 255]));
+        let before = canvas.clone();
+        let config = config(&[("fix", "🐛")], BadgeCorner::TopLeft);
+        stamp(&config, &font(), Rgba([255; 4]), &mut canvas, "fix");
+        assert_eq!(canvas, before);
+    }
+}
@@ -0,0 +1,356 @@
+//! "Best of" selection: the most epic lolcommit within a date range.
+
+use crate::git::CommitMetadata;
+use chrono::NaiveDate;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// How many runner-up images accompany the winner.
+pub const RUNNERS_UP: usize = 5;
+
+/// Date format accepted for the window bounds.
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Metric {
+    /// Insertions plus deletions.
+    #[default]
+    Diff,
+    /// Number of reactions recorded for the revision.
+    Reactions,
+}
+
+impl Metric {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Metric::Diff => "diff",
+            Metric::Reactions => "reactions",
+        }
+    }
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "diff" => Ok(Metric::Diff),
+            "reactions" => Ok(Metric::Reactions),
+            other => Err(format!(
+                "unknown metric {other:?}, expected diff or reactions"
+            )),
+        }
+    }
+}
+
+/// Inclusive range of days, either end may be open.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimeWindow {
+    pub since: Option<NaiveDate>,
+    pub until: Option<NaiveDate>,
+}
+
+impl TimeWindow {
+    /// Parse optional `YYYY-MM-DD` bounds, rejecting bad dates and reversed ranges.
+    pub fn parse(since: Option<&str>, until: Option<&str>) -> std::result::Result<Self, String> {
+        let parse = |name: &str, value: Option<&str>| {
+            value
+                .map(|v| {
+                    NaiveDate::parse_from_str(v, DATE_FORMAT)
+                        .map_err(|_| format!("{name} must be a YYYY-MM-DD date, got {v:?}"))
+                })
+                .transpose()
+        };
+
+        let window = Self {
+            since: parse("since", since)?,
+            until: parse("until", until)?,
+        };
+
+        if let (Some(since), Some(until)) = (window.since, window.until)
+            && since > until
+        {
+            return Err(format!("since ({since}) is after until ({until})"));
+        }
+
+        Ok(window)
+    }
+
+    fn is_unbounded(&self) -> bool {
+        self.since.is_none() && self.until.is_none()
+    }
+
+    /// Whether an image taken at `timestamp` falls in the window. Images with an
+    /// unparseable timestamp only match an unbounded window.
+    pub fn contains(&self, timestamp: &str) -> bool {
+        if self.is_unbounded() {
+            return true;
+        }
+
+        let Some(taken) = crate::local_time(timestamp) else {
+            return false;
+        };
+        let day = taken.date();
+
+        self.since.is_none_or(|since| day >= since) && self.until.is_none_or(|until| day <= until)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Ranked {
+    pub score: u64,
+    pub metadata: CommitMetadata,
+}
+
+#[derive(Debug, Clone)]
+pub struct BestOf {
+    pub best: Ranked,
+    pub runners_up: Vec<Ranked>,
+}
+
+fn score(metric: Metric, metadata: &CommitMetadata, reactions: &HashMap<String, u32>) -> u64 {
+    match metric {
+        // Legacy images without stats parse as zero and simply rank last
+        Metric::Diff => u64::from(metadata.stats.insertions) + u64::from(metadata.stats.deletions),
+        Metric::Reactions => reactions
+            .get(&metadata.revision)
+            .copied()
+            .map(u64::from)
+            .unwrap_or(0),
+    }
+}
+
+/// Pick the top image in `window` by `metric`, plus up to [`RUNNERS_UP`] runners-up.
+///
+/// Ties go to the more recent image. `reactions` maps revisions to reaction counts and
+/// is only consulted for [`Metric::Reactions`].
+pub fn select_best(
+    images: Vec<CommitMetadata>,
+    window: &TimeWindow,
+    metric: Metric,
+    reactions: &HashMap<String, u32>,
+) -> Option<BestOf> {
+    let mut ranked: Vec<Ranked> = images
+        .into_iter()
+        .filter(|image| window.contains(&image.timestamp))
+        .map(|metadata| Ranked {
+            score: score(metric, &metadata, reactions),
+            metadata,
+        })
+        .collect();
+
+    ranked.sort_by(|a, b| {
+        b.score.cmp(&a.score).then_with(|| {
+            crate::parse_timestamp(&b.metadata.timestamp)
+                .cmp(&crate::parse_timestamp(&a.metadata.timestamp))
+        })
+    });
+    ranked.truncate(RUNNERS_UP + 1);
+
+    let mut ranked = ranked.into_iter();
+    let best = ranked.next()?;
+    Some(BestOf {
+        best,
+        runners_up: ranked.collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::DiffStats;
+    use test_case::test_case;
+
+    fn image(revision: &str, timestamp: &str, insertions: u32, deletions: u32) -> CommitMetadata {
+        CommitMetadata {
+            path: std::path::PathBuf::new(),
+            revision: revision.to_string(),
+            message: format!("feat: {revision}"),
+            commit_type: "feat".to_string(),
+            scope: String::new(),
+            timestamp: timestamp.to_string(),
+            repo_name: "repo".to_string(),
+            branch_name: "main".to_string(),
+            author_name: String::new(),
+            author_email: String::new(),
+            breaking: false,
+            co_authors: Vec::new(),
+            stats: DiffStats {
+                files_changed: u32::from(insertions + deletions > 0),
+                insertions,
+                deletions,
+            },
+        }
+    }
+
+    fn revisions(best: &BestOf) -> Vec<&str> {
+        std::iter::once(&best.best)
+            .chain(&best.runners_up)
+            .map(|r| r.metadata.revision.as_str())
+            .collect()
+    }
+
+    fn no_reactions() -> HashMap<String, u32> {
+        HashMap::new()
+    }
+
+    #[test]
+    fn test_empty_input() {
+        assert!(
+            select_best(
+                vec![],
+                &TimeWindow::default(),
+                Metric::Diff,
+                &no_reactions()
+            )
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn test_empty_window() {
+        let images = vec![image("a", "2024-01-10 10:00:00", 10, 0)];
+        let window = TimeWindow::parse(Some("2024-02-01"), Some("2024-02-29")).unwrap();
+
+        assert!(select_best(images, &window, Metric::Diff, &no_reactions()).is_none());
+    }
+
+    #[test]
+    fn test_largest_diff_wins() {
+        let images = vec![
+            image("small", "2024-01-10 10:00:00", 1, 1),
+            image("huge", "2024-01-09 10:00:00", 500, 200),
+            image("medium", "2024-01-11 10:00:00", 50, 5),
+        ];
+
+        let best = select_best(
+            images,
+            &TimeWindow::default(),
+            Metric::Diff,
+            &no_reactions(),
+        )
+        .unwrap();
+
+        assert_eq!(best.best.score, 700);
+        assert_eq!(revisions(&best), vec!["huge", "medium", "small"]);
+    }
+
+    #[test]
+    fn test_ties_break_by_recency() {
+        let images = vec![
+            image("older", "2024-01-10 10:00:00", 5, 5),
+            image("newer", "2024-01-10 11:00:00", 8, 2),
+            image("oldest", "2024-01-01 09:00:00", 10, 0),
+        ];
+
+        let best = select_best(
+            images,
+            &TimeWindow::default(),
+            Metric::Diff,
+            &no_reactions(),
+        )
+        .unwrap();
+        assert_eq!(revisions(&best), vec!["newer", "older", "oldest"]);
+    }
+
+    #[test]
+    fn test_legacy_images_without_stats_rank_last() {
+        let images = vec![
+            image("legacy", "2024-01-12 10:00:00", 0, 0),
+            image("modern", "2024-01-10 10:00:00", 3, 0),
+        ];
+
+        let best = select_best(
+            images,
+            &TimeWindow::default(),
+            Metric::Diff,
+            &no_reactions(),
+        )
+        .unwrap();
+        assert_eq!(revisions(&best), vec!["modern", "legacy"]);
+        assert_eq!(best.runners_up[0].score, 0);
+    }
+
+    #[test]
+    fn test_unparseable_timestamps_only_in_unbounded_window() {
+        let images = vec![image("legacy", "", 100, 0)];
+        assert!(
+            select_best(
+                images.clone(),
+                &TimeWindow::default(),
+                Metric::Diff,
+                &no_reactions()
+            )
+            .is_some()
+        );
+
+        let window = TimeWindow::parse(Some("2024-01-01"), None).unwrap();
+        assert!(select_best(images, &window, Metric::Diff, &no_reactions()).is_none());
+    }
+
+    #[test]
+    fn test_window_bounds_are_inclusive_days() {
+        let images = vec![
+            image("before", "2024-01-31 23:59:59", 1000, 0),
+            image("first", "2024-02-01 00:00:00", 1, 0),
+            image("last", "2024-02-29 23:59:59", 2, 0),
+            image("after", "2024-03-01 00:00:00", 1000, 0),
+        ];
+        let window = TimeWindow::parse(Some("2024-02-01"), Some("2024-02-29")).unwrap();
+
+        let best = select_best(images, &window, Metric::Diff, &no_reactions()).unwrap();
+        assert_eq!(revisions(&best), vec!["last", "first"]);
+    }
+
+    #[test]
+    fn test_runners_up_are_capped() {
+        let images = (0..10)
+            .map(|i| image(&format!("r{i}"), "2024-01-10 10:00:00", i, 0))
+            .collect();
+
+        let best = select_best(
+            images,
+            &TimeWindow::default(),
+            Metric::Diff,
+            &no_reactions(),
+        )
+        .unwrap();
+        assert_eq!(best.best.metadata.revision, "r9");
+        assert_eq!(best.runners_up.len(), RUNNERS_UP);
+        assert_eq!(best.runners_up.last().unwrap().metadata.revision, "r4");
+    }
+
+    #[test]
+    fn test_reactions_metric() {
+        let images = vec![
+            image("big-diff", "2024-01-10 10:00:00", 900, 0),
+            image("loved", "2024-01-09 10:00:00", 1, 0),
+            image("ignored", "2024-01-11 10:00:00", 1, 0),
+        ];
+        let reactions = HashMap::from([("loved".to_string(), 12), ("big-diff".to_string(), 3)]);
+
+        let best = select_best(
+            images,
+            &TimeWindow::default(),
+            Metric::Reactions,
+            &reactions,
+        )
+        .unwrap();
+        assert_eq!(revisions(&best), vec!["loved", "big-diff", "ignored"]);
+    }
+
+    #[test_case(Some("2024-13-01"), None ; "bad month")]
+    #[test_case(None, Some("yesterday") ; "not a date")]
+    #[test_case(Some("2024-01-10 10:00:00"), None ; "datetime")]
+    #[test_case(Some("2024-02-01"), Some("2024-01-01") ; "reversed")]
+    fn test_window_parse_rejects(since: Option<&str>, until: Option<&str>) {
+        assert!(TimeWindow::parse(since, until).is_err());
+    }
+
+    #[test_case("diff", Some(Metric::Diff) ; "diff")]
+    #[test_case("reactions", Some(Metric::Reactions) ; "reactions")]
+    #[test_case("likes", None ; "unknown")]
+    fn test_metric_from_str(input: &str, expected: Option<Metric>) {
+        assert_eq!(input.parse::<Metric>().ok(), expected);
+    }
+}
@@ -0,0 +1,232 @@
+use clap::{Parser, Subcommand};
+use owo_colors::OwoColorize;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use sw1nn_lolcommits_rs::{
+    camera::{self, DeviceListing},
+    config, error, git, inspect,
+    setup::{self, AssumeDefaults, StepReport, SystemProbe, TerminalPrompter},
+};
+
+#[derive(Parser, Debug)]
+#[command(name = "lolcommits")]
+#[command(about = "Set up lolcommits on this machine")]
+#[command(version)]
+struct Args {
+    #[arg(long, value_name = "FILE", help = "Path to config file")]
+    config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Configure the camera and server, install the post-commit hook and check it all works
+    Setup {
+        #[arg(long, short, action = clap::ArgAction::SetTrue, help = "Accept the defaults instead of prompting")]
+        yes: bool,
+    },
+    /// List cameras with their device ids, paths and supported formats
+    Devices {
+        #[arg(long, action = clap::ArgAction::SetTrue, help = "Print the list as JSON")]
+        json: bool,
+    },
+    /// Print the lolcommit metadata recorded in gallery images, warning about inconsistencies
+    Inspect {
+        #[arg(required = true, value_name = "FILE")]
+        files: Vec<PathBuf>,
+        #[arg(long, action = clap::ArgAction::SetTrue, help = "Print one JSON object per line")]
+        json: bool,
+    },
+}
+
+fn main() -> ExitCode {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("off")),
+        )
+        .init();
+
+    let args = Args::parse();
+
+    match args.command {
+        Command::Inspect { files, json } => {
+            let mut status = ExitCode::SUCCESS;
+            for file in &files {
+                let report = match inspect::inspect(file) {
+                    Ok(report) => report,
+                    Err(e) => {
+                        eprintln!(
+                            "{} {}: {}",
+                            "✗".red(),
+                            file.display(),
+                            error::report(&e).red()
+                        );
+                        status = ExitCode::FAILURE;
+                        continue;
+                    }
+                };
+                if report.is_empty() {
+                    status = ExitCode::FAILURE;
+                }
+
+                if json {
+                    match serde_json::to_string(&report) {
+                        Ok(json) => println!("{json}"),
+                        Err(e) => {
+                            eprintln!("{} {}", "✗".red(), error::report(&e).red());
+                            return ExitCode::FAILURE;
+                        }
+                    }
+                } else {
+                    print!("{}", inspect::render(&report));
+                }
+            }
+            status
+        }
+        Command::Devices { json } => {
+            let devices = match camera::list_devices() {
+                Ok(devices) => devices,
+                Err(e) => {
+                    eprintln!("{} {}", "✗".red(), error::report(&e).red());
+                    return ExitCode::FAILURE;
+                }
+            };
+
+            if json {
+                match serde_json::to_string_pretty(&devices) {
+                    Ok(json) => println!("{json}"),
+                    Err(e) => {
+                        eprintln!("{} {}", "✗".red(), error::report(&e).red());
+                        return ExitCode::FAILURE;
+                    }
+                }
+            } else {
+                print!("{}", render_devices(&devices));
+            }
+            ExitCode::SUCCESS
+        }
+        Command::Setup { yes } => {
+            let config_path = match config::Config::resolve_path(args.config) {
+                Ok(path) => path,
+                Err(e) => {
+                    eprintln!("{} {}", "✗".red(), error::report(&e).red());
+                    return ExitCode::FAILURE;
+                }
+            };
+
+            // Only offer the hook when run inside a repository
+            let hooks_dir = git::open_repo().ok().map(|repo| git::hooks_dir(&repo));
+
+            println!(
+                "Setting up lolcommits using {}",
+                config_path.display().to_string().magenta()
+            );
+
+            let reports = if yes {
+                setup::run_client(
+                    &config_path,
+                    hooks_dir.as_deref(),
+                    &SystemProbe,
+                    &mut AssumeDefaults,
+                    StepReport::print,
+                )
+            } else {
+                setup::run_client(
+                    &config_path,
+                    hooks_dir.as_deref(),
+                    &SystemProbe,
+                    &mut TerminalPrompter,
+                    StepReport::print,
+                )
+            };
+
+            if reports.iter().any(StepReport::is_failure) {
+                ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
+            }
+        }
+    }
+}
+
+/// Human readable listing: one line per camera, its formats indented beneath.
+fn render_devices(devices: &[DeviceListing]) -> String {
+    if devices.is_empty() {
+        return "No cameras found\n".to_string();
+    }
+
+    let device_width = devices.iter().map(|d| d.device.len()).max().unwrap_or(0);
+    let path_width = devices
+        .iter()
+        .map(|d| d.path.as_deref().unwrap_or("-").len())
+        .max()
+        .unwrap_or(0);
+
+    let mut out = String::new();
+    for device in devices {
+        out.push_str(&format!(
+            "{:<device_width$}  {:<path_width$}  {}\n",
+            device.device,
+            device.path.as_deref().unwrap_or("-"),
+            device.name,
+        ));
+        if let Some(error) = &device.error {
+            out.push_str(&format!("    {} could not open: {}\n", "✗".red(), error));
+        }
+        for format in &device.formats {
+            out.push_str(&format!(
+                "    {:<5} {:>4}x{:<4} @ {}fps\n",
+                format.format, format.width, format.height, format.fps
+            ));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sw1nn_lolcommits_rs::camera::DeviceFormat;
+
+    #[test]
+    fn test_render_devices_empty() {
+        assert_eq!(render_devices(&[]), "No cameras found\n");
+    }
+
+    #[test]
+    fn test_render_devices_lists_formats_and_errors() {
+        let devices = vec![
+            DeviceListing {
+                device: "0".to_string(),
+                path: Some("/dev/video0".to_string()),
+                name: "Integrated Camera".to_string(),
+                formats: vec![DeviceFormat {
+                    format: "YUYV".to_string(),
+                    width: 1280,
+                    height: 720,
+                    fps: 30,
+                }],
+                error: None,
+            },
+            DeviceListing {
+                device: "12".to_string(),
+                path: None,
+                name: "C920".to_string(),
+                formats: Vec::new(),
+                error: Some("device busy".to_string()),
+            },
+        ];
+
+        let rendered = render_devices(&devices);
+        let lines: Vec<&str> = rendered.lines().collect();
+
+        assert_eq!(lines[0], "0   /dev/video0  Integrated Camera");
+        assert_eq!(lines[1], "    YUYV  1280x720  @ 30fps");
+        assert_eq!(lines[2], "12  -            C920");
+        assert!(lines[3].contains("could not open: device busy"));
+    }
+}
@@ -473,23 +473,21 @@ fn apply_fix(
 ) -> Result<()> {
     let img = image::open(path)?;
 
-    let temp_file = tempfile::NamedTempFile::new_in(
-        path.parent()
-            .ok_or_else(|| std::io::Error::other("Invalid path"))?,
-    )?;
-
-    sw1nn_lolcommits_rs::image_metadata::save_png_with_metadata(&img, temp_file.path(), metadata)?;
+    let dir = new_path
+        .parent()
+        .ok_or_else(|| std::io::Error::other("Invalid path"))?;
+    let filename = new_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| std::io::Error::other("Invalid path"))?;
 
-    temp_file.persist(new_path).map_err(|e| e.error)?;
+    sw1nn_lolcommits_rs::storage::atomic_save(dir, filename, |temp_path| {
+        sw1nn_lolcommits_rs::image_metadata::save_png_with_metadata(&img, temp_path, metadata)
+    })?;
 
     // Set file mtime to the commit timestamp from metadata
-    if !metadata.timestamp.is_empty()
-        && let Ok(dt) = chrono::NaiveDateTime::parse_from_str(
-            &metadata.timestamp,
-            sw1nn_lolcommits_rs::TIMESTAMP_FORMAT,
-        )
-    {
-        let unix_secs: u64 = dt.and_utc().timestamp().try_into().unwrap_or(0);
+    if let Some(dt) = sw1nn_lolcommits_rs::parse_timestamp(&metadata.timestamp) {
+        let unix_secs: u64 = dt.timestamp().try_into().unwrap_or(0);
         let system_time = std::time::UNIX_EPOCH + std::time::Duration::from_secs(unix_secs);
         let times = std::fs::FileTimes::new().set_modified(system_time);
         std::fs::File::options()
@@ -1104,6 +1102,10 @@ mod tests {
             timestamp: "2026-03-01 12:00:00".to_owned(),
             repo_name: "old-repo".to_owned(),
             branch_name: "main".to_owned(),
+            author_name: String::new(),
+            author_email: String::new(),
+            breaking: false,
+            co_authors: Vec::new(),
             stats: sw1nn_lolcommits_rs::git::DiffStats {
                 files_changed: 0,
                 insertions: 0,
@@ -1175,6 +1177,10 @@ mod tests {
             timestamp: "2026-03-01 12:00:00".to_owned(),
             repo_name: "old-repo".to_owned(),
             branch_name: "main".to_owned(),
+            author_name: String::new(),
+            author_email: String::new(),
+            breaking: false,
+            co_authors: Vec::new(),
             stats: sw1nn_lolcommits_rs::git::DiffStats {
                 files_changed: 0,
                 insertions: 0,
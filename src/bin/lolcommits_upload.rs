@@ -1,17 +1,40 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use owo_colors::OwoColorize;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::{ExitCode, Stdio};
+use tracing_subscriber::prelude::*;
 
 use sw1nn_lolcommits_rs::{
-    capture, config,
-    error::{Error, Result},
+    api_error::ErrorBody,
+    capture::{self, FlushReport, Outcome},
+    config, config_edit, doctor,
+    error::{self, Error, Result},
+    git, hook, overrides,
 };
 
+/// Exit statuses, kept in step with `Error::exit_code`.
+const EXIT_CODES: &str = "\
+Exit codes:
+   0  success, or nothing to do
+   1  any other error
+   2  usage error (bad arguments, unknown revision, hook conflict)
+  10  camera or capture source not found
+  11  camera busy or not responding
+  20  not in a git repository
+  30  server connection failed
+  31  upload rejected or failed on the server
+  40  config error";
+
 #[derive(Parser, Debug)]
 #[command(name = "lolcommits_upload")]
 #[command(about = "Take a snapshot with your webcam when you commit")]
 #[command(version)]
+#[command(args_conflicts_with_subcommands = true)]
+#[command(after_long_help = EXIT_CODES)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     #[arg(
         default_value = "HEAD",
         help = "The commit revision (any git revision parameter)"
@@ -21,25 +44,153 @@ struct Args {
     #[arg(long, action = clap::ArgAction::SetTrue, help = "Force upload even if SHA already exists")]
     force: bool,
 
-    #[arg(long, short, action = clap::ArgAction::SetTrue, help = "Suppress camera busy errors (exit 0 instead)")]
+    #[arg(long, short, action = clap::ArgAction::SetTrue, help = "Suppress camera busy and timeout errors (exit 0 instead)")]
     quiet: bool,
 
     #[arg(long, value_name = "FILE", help = "Path to config file")]
     config: Option<PathBuf>,
+
+    #[arg(
+        long,
+        visible_alias = "image",
+        value_name = "PATH",
+        conflicts_with_all = ["quiet", "delay", "save_raw"],
+        help = "Upload this existing image (screenshot, phone photo) instead of capturing from the camera"
+    )]
+    from_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        value_parser = clap::value_parser!(u64).range(..=config::MAX_CAPTURE_DELAY_SECS),
+        help = "Wait this long with the camera ready before capturing, counting down"
+    )]
+    delay: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Also save the capture as taken, before any processing, in this directory"
+    )]
+    save_raw: Option<PathBuf>,
+
+    #[arg(long, action = clap::ArgAction::SetTrue, help = "Wait for the server to finish processing and report the outcome")]
+    wait: bool,
+
+    #[arg(long, action = clap::ArgAction::SetTrue, help = "Burn the chyron in and save the image locally instead of uploading it")]
+    local: bool,
+
+    #[arg(long, action = clap::ArgAction::SetTrue, overrides_with = "no_chyron", help = "Burn the chyron in, even if the server's config says not to")]
+    chyron: bool,
+
+    #[arg(long, action = clap::ArgAction::SetTrue, overrides_with = "chyron", help = "Leave the chyron off this lolcommit")]
+    no_chyron: bool,
+
+    #[arg(long, action = clap::ArgAction::SetTrue, help = "Upload captures spooled while the server was unreachable, without capturing")]
+    flush_spool: bool,
+
+    #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with_all = ["flush_spool", "local"], help = "Upload in the background once captured, so the commit isn't held up")]
+    background: bool,
+
+    /// Upload a capture spooled by --background, as its detached process
+    #[arg(long, value_name = "PATH", hide = true, conflicts_with = "background")]
+    upload_spooled: Option<PathBuf>,
+
+    #[arg(
+        long = "override",
+        value_name = "KEY=VALUE",
+        value_parser = overrides::parse_arg,
+        help = "Processing override for this upload, e.g. background=party (repeatable, must be allowed by the server)"
+    )]
+    overrides: Vec<(String, String)>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Install the post-commit hook that runs lolcommits_upload in this repository
+    Install {
+        #[arg(long, action = clap::ArgAction::SetTrue, help = "Replace an existing hook that isn't a shell script, keeping a backup")]
+        force: bool,
+
+        #[arg(long, action = clap::ArgAction::SetTrue, help = "Install into the global core.hooksPath, setting it if needed")]
+        global: bool,
+    },
+    /// Remove what install added to the post-commit hook
+    Uninstall {
+        #[arg(long, action = clap::ArgAction::SetTrue, help = "Uninstall from the global core.hooksPath")]
+        global: bool,
+    },
+    /// Show or change settings, by dotted key like client.camera_devices[0].device
+    Config {
+        #[arg(long, value_name = "FILE", help = "Path to config file")]
+        config: Option<PathBuf>,
+
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Check the config, cameras, repository and server, reporting each as pass or fail
+    Doctor {
+        #[arg(long, value_name = "FILE", help = "Path to config file")]
+        config: Option<PathBuf>,
+
+        #[arg(long, action = clap::ArgAction::SetTrue, help = "Print the checks as JSON")]
+        json: bool,
+
+        #[arg(long, action = clap::ArgAction::SetTrue, help = "Also send a test image to the server's /api/ping, which doesn't keep it")]
+        ping: bool,
+    },
 }
 
-fn main() -> Result<()> {
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("off")),
-        )
-        .init();
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    /// Print the effective config, with the repository's and the defaults filled in
+    Show,
+    /// Print which config files are loaded
+    Path,
+    /// Print one setting
+    Get { key: String },
+    /// Change one setting in the config file, keeping the others
+    Set { key: String, value: String },
+}
 
+fn main() -> ExitCode {
     let args = Args::parse();
+    init_tracing(args.upload_spooled.is_some());
+
+    // Failures are reported where they're handled, so only the exit status is left
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => std::process::exit(e.exit_code()),
+    }
+}
+
+/// Logging is off unless `RUST_LOG` asks for it, except in a detached upload, which has
+/// no terminal and logs to journald at info instead.
+fn init_tracing(detached: bool) {
+    let default = if detached { "info" } else { "off" };
+    let env_filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new(default));
+
+    match tracing_journald::layer() {
+        Ok(journald) if detached => tracing_subscriber::registry()
+            .with(env_filter)
+            .with(journald)
+            .init(),
+        _ => tracing_subscriber::fmt().with_env_filter(env_filter).init(),
+    }
+}
+
+fn run(args: Args) -> Result<()> {
+    match args.command {
+        Some(Command::Config { config, command }) => {
+            return run_config_command(config, command).inspect_err(print_error);
+        }
+        Some(Command::Doctor { config, json, ping }) => return run_doctor(config, json, ping),
+        Some(command) => return run_hook_command(command),
+        None => {}
+    }
 
-    // Load configuration
-    let config = config::Config::load_from(args.config)?;
+    let config = load_config(&args, git::open_repo().ok().as_ref()).inspect_err(print_error)?;
     tracing::debug!(?config, "Loaded configuration");
 
     let server_url = config
@@ -48,17 +199,96 @@ fn main() -> Result<()> {
         .map(|c| c.server_url.clone())
         .unwrap_or_else(|| "server".to_string());
 
+    if args.flush_spool {
+        let result = capture::flush_spool(&config.client.unwrap_or_default());
+        return handle_flush(result, &server_url);
+    }
+
+    if let Some(path) = &args.upload_spooled {
+        let result = capture::upload_spooled(&config.client.unwrap_or_default(), path);
+        match &result {
+            Ok(outcome) => tracing::info!(?outcome, "Background upload finished"),
+            Err(e) => tracing::error!(error = %error::report(e), "Background upload failed"),
+        }
+        return result.map(|_| ());
+    }
+
     let capture_args = capture::CaptureArgs {
         revision: args.revision,
         force: args.force,
+        overrides: args.overrides.into_iter().collect(),
+        background: args.background,
+        chyron: match (args.chyron, args.no_chyron) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        },
     };
 
-    if !tracing::enabled!(tracing::Level::INFO) {
+    let enabled = config.client.as_ref().is_none_or(|client| client.enabled);
+    if enabled && !tracing::enabled!(tracing::Level::INFO) {
         println!("📸 Capturing lolcommit...");
     }
 
-    match capture::capture_lolcommit(config, capture_args) {
-        Ok(()) => {
+    let result = match capture::capture_lolcommit(config, capture_args) {
+        Ok(Outcome::Queued { path }) => {
+            match detach_upload(&path, args.config.as_deref(), args.wait) {
+                Ok(()) => Ok(Outcome::Queued { path }),
+                Err(e) => {
+                    tracing::warn!(error = %e, "Failed to start the background upload");
+                    Ok(Outcome::Spooled {
+                        path,
+                        read_only: false,
+                    })
+                }
+            }
+        }
+        result => result,
+    };
+    handle_result(result, args.quiet, &server_url)
+}
+
+/// Start `lolcommits_upload --upload-spooled` for the capture at `path` without waiting
+/// for it. It gets no terminal and its own process group, so it outlives the hook and a
+/// Ctrl-C in the terminal doesn't reach it.
+fn detach_upload(path: &Path, config: Option<&Path>, wait: bool) -> Result<()> {
+    let mut command = std::process::Command::new(std::env::current_exe()?);
+    command.arg("--upload-spooled").arg(path);
+    if let Some(config) = config {
+        command.arg("--config").arg(config);
+    }
+    if wait {
+        command.arg("--wait");
+    }
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+
+    let child = command.spawn()?;
+    tracing::info!(pid = child.id(), path = %path.display(), "Uploading in the background");
+    Ok(())
+}
+
+/// Report the outcome of a capture to the user and decide the exit status.
+///
+/// A busy or unresponsive camera is only an error without `--quiet`; every other failure
+/// is reported and passed through.
+fn handle_result(result: Result<Outcome>, quiet: bool, server_url: &str) -> Result<()> {
+    match result {
+        Ok(Outcome::AlreadyCaptured { filename }) => {
+            tracing::info!(filename = ?filename, "Revision already captured");
+            if !tracing::enabled!(tracing::Level::INFO) {
+                println!(
+                    "{} Already captured on {}, use --force to capture again",
+                    "✓".green(),
+                    server_url.magenta()
+                );
+            }
+            Ok(())
+        }
+        Ok(Outcome::Uploaded) => {
             if !tracing::enabled!(tracing::Level::INFO) {
                 println!(
                     "{} Lolcommit uploaded successfully to {}",
@@ -68,7 +298,61 @@ fn main() -> Result<()> {
             }
             Ok(())
         }
-        Err(Error::CameraBusy { device }) if args.quiet => {
+        Ok(Outcome::Processed { filename }) => {
+            tracing::info!(filename, "Lolcommit processed");
+            if !tracing::enabled!(tracing::Level::INFO) {
+                println!(
+                    "{} Lolcommit saved on {} as {}",
+                    "✓".green(),
+                    server_url.magenta(),
+                    filename.cyan()
+                );
+            }
+            Ok(())
+        }
+        Ok(Outcome::Disabled) => Ok(()),
+        Ok(Outcome::Saved { path }) => {
+            tracing::info!(path = %path.display(), "Lolcommit saved locally");
+            if !tracing::enabled!(tracing::Level::INFO) {
+                println!(
+                    "{} Lolcommit saved to {}",
+                    "✓".green(),
+                    path.display().to_string().cyan()
+                );
+            }
+            Ok(())
+        }
+        Ok(Outcome::Spooled { path, read_only }) => {
+            tracing::info!(path = %path.display(), read_only, "Lolcommit spooled");
+            if !tracing::enabled!(tracing::Level::INFO) {
+                if read_only {
+                    println!(
+                        "{} Server {} is read-only, spooling locally",
+                        "⏳".yellow(),
+                        server_url.magenta()
+                    );
+                } else {
+                    println!(
+                        "{} Couldn't reach {}, lolcommit queued for the next upload",
+                        "⏳".yellow(),
+                        server_url.magenta()
+                    );
+                }
+            }
+            Ok(())
+        }
+        Ok(Outcome::Queued { path }) => {
+            tracing::info!(path = %path.display(), "Lolcommit queued for background upload");
+            if !tracing::enabled!(tracing::Level::INFO) {
+                println!(
+                    "{} Lolcommit captured, uploading to {} in the background",
+                    "✓".green(),
+                    server_url.magenta()
+                );
+            }
+            Ok(())
+        }
+        Err(Error::CameraBusy { device }) if quiet => {
             tracing::info!(device, "Camera busy, skipping lolcommit capture");
             Ok(())
         }
@@ -76,27 +360,556 @@ fn main() -> Result<()> {
             eprintln!("{} Camera {} is busy", "✗".red(), device.magenta());
             Err(Error::CameraBusy { device })
         }
-        Err(Error::ServerConnectionFailed { url, source }) => {
+        Err(Error::CameraTimeout { device }) if quiet => {
+            tracing::info!(device, "Camera not responding, skipping lolcommit capture");
+            Ok(())
+        }
+        Err(Error::CameraTimeout { device }) => {
             eprintln!(
-                "{} Failed to connect to lolcommitsd at {}: {}",
+                "{} Camera {} stopped responding",
+                "✗".red(),
+                device.magenta()
+            );
+            Err(Error::CameraTimeout { device })
+        }
+        Err(Error::ServerConnectionFailed {
+            url,
+            source,
+            attempts,
+        }) => {
+            eprintln!(
+                "{} Failed to connect to lolcommitsd at {}{}: {}",
                 "✗".red(),
                 url.magenta(),
+                error::after_attempts(attempts),
                 source.to_string().red()
             );
-            Err(Error::ServerConnectionFailed { url, source })
+            Err(Error::ServerConnectionFailed {
+                url,
+                source,
+                attempts,
+            })
         }
-        Err(Error::UploadFailed { status, body }) => {
+        Err(Error::ServerReadOnly { url }) => {
             eprintln!(
-                "{} Upload failed with status {}: {}",
+                "{} Server {} is read-only, lolcommit not uploaded",
+                "✗".yellow(),
+                url.magenta()
+            );
+            Err(Error::ServerReadOnly { url })
+        }
+        Err(Error::AlreadyCaptured { revision }) => {
+            eprintln!(
+                "{} Revision {} is already captured on {}, use --force to capture again",
+                "✗".yellow(),
+                revision.magenta(),
+                server_url.magenta()
+            );
+            Err(Error::AlreadyCaptured { revision })
+        }
+        Err(Error::RevisionNotFound { input }) => {
+            eprintln!("{} Revision {} not found", "✗".red(), input.magenta());
+            Err(Error::RevisionNotFound { input })
+        }
+        Err(Error::RevisionNotSingleCommit { input }) => {
+            eprintln!(
+                "{} {} is not a single commit, single commit required",
+                "✗".red(),
+                input.magenta()
+            );
+            Err(Error::RevisionNotSingleCommit { input })
+        }
+        Err(Error::ProcessingFailed { message }) => {
+            eprintln!(
+                "{} Server failed to process the lolcommit: {}",
+                "✗".red(),
+                message.red()
+            );
+            Err(Error::ProcessingFailed { message })
+        }
+        Err(Error::UploadFailed {
+            status,
+            body,
+            attempts,
+        }) => {
+            eprintln!(
+                "{} Upload failed with status {}{}: {}",
                 "✗".red(),
                 status.to_string().yellow(),
-                body.red()
+                error::after_attempts(attempts),
+                failure_message(&body).red()
             );
-            Err(Error::UploadFailed { status, body })
+            Err(Error::UploadFailed {
+                status,
+                body,
+                attempts,
+            })
         }
         Err(e) => {
-            eprintln!("{} {}", "✗".red(), e.to_string().red());
+            print_error(&e);
             Err(e)
         }
     }
 }
+
+/// Print an error with its cause chain.
+fn print_error(e: &Error) {
+    eprintln!("{} {}", "✗".red(), error::report(e).red());
+}
+
+/// The message of a failed upload's error body, or the body as is when it isn't a
+/// server error body (a proxy's error page, say).
+fn failure_message(body: &str) -> String {
+    ErrorBody::parse(body)
+        .map(|e| e.message)
+        .filter(|message| !message.is_empty())
+        .unwrap_or_else(|| body.to_string())
+}
+
+/// The user's config with the repository's `.lolcommits.toml` merged over it, then the
+/// command line flags applied, so flags win over both files.
+fn load_config(args: &Args, repo: Option<&git2::Repository>) -> Result<config::Config> {
+    let mut config = config::Config::load_for_repo(args.config.clone(), repo)?;
+    if let Some(path) = &args.from_file {
+        config.client.get_or_insert_default().capture_source = Some(path.clone());
+    }
+    if args.local {
+        config.client.get_or_insert_default().mode = config::CaptureMode::Local;
+    }
+    if args.wait {
+        config.client.get_or_insert_default().wait_for_processing = true;
+    }
+    if let Some(delay) = args.delay {
+        config.client.get_or_insert_default().capture_delay_secs = delay;
+    }
+    if let Some(dir) = &args.save_raw {
+        config.client.get_or_insert_default().save_raw_dir =
+            Some(dir.to_string_lossy().into_owned());
+    }
+    Ok(config)
+}
+
+/// Install or uninstall the post-commit hook and report what changed.
+fn run_hook_command(command: Command) -> Result<()> {
+    let result = match command {
+        Command::Install { force, global } => {
+            hooks_dir(global, true).and_then(|dir| hook::install(&dir, force))
+        }
+        Command::Uninstall { global } => {
+            hooks_dir(global, false).and_then(|dir| hook::uninstall(&dir))
+        }
+        Command::Config { .. } => unreachable!("handled by run_config_command"),
+        Command::Doctor { .. } => unreachable!("handled by run_doctor"),
+    };
+
+    match result {
+        Ok(change) => {
+            println!("{} {}", "✓".green(), change);
+            Ok(())
+        }
+        Err(e) => {
+            print_error(&e);
+            Err(e)
+        }
+    }
+}
+
+/// Run the doctor's checks, printing each as it finishes or all of them as JSON at the
+/// end, and fail if any did.
+fn run_doctor(config_path: Option<PathBuf>, json: bool, ping: bool) -> Result<()> {
+    let repo = git::open_repo();
+    let config = config::Config::load_for_repo(config_path, repo.as_ref().ok());
+    let checks = doctor::run(config, repo, &doctor::SystemProbe, ping, |check| {
+        if !json {
+            check.print();
+        }
+    });
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&checks)?);
+    }
+    match checks.iter().filter(|check| check.is_failure()).count() {
+        0 => Ok(()),
+        failed => Err(Error::ChecksFailed { failed }),
+    }
+}
+
+/// Show or change the config. `show` and `get` see the config a capture would use,
+/// `set` changes the user's config file.
+fn run_config_command(config_path: Option<PathBuf>, command: ConfigCommand) -> Result<()> {
+    let repo = git::open_repo().ok();
+    match command {
+        ConfigCommand::Show => {
+            let config = config::Config::load_for_repo(config_path, repo.as_ref())?;
+            print!("{}", toml::to_string_pretty(&config.with_defaults())?);
+        }
+        ConfigCommand::Path => {
+            println!("{}", config::Config::resolve_path(config_path)?.display());
+            let repo_config = repo
+                .as_ref()
+                .and_then(|repo| repo.workdir())
+                .map(|workdir| workdir.join(config::REPO_CONFIG_FILE_NAME))
+                .filter(|path| path.exists());
+            if let Some(path) = repo_config {
+                println!("{}", path.display());
+            }
+        }
+        ConfigCommand::Get { key } => {
+            let config = config::Config::load_for_repo(config_path, repo.as_ref())?;
+            match config_edit::get(&config, &key)? {
+                Some(value) => println!("{}", config_edit::display(&value)?),
+                None => println!("{} {} is not set", "!".yellow(), key.magenta()),
+            }
+        }
+        ConfigCommand::Set { key, value } => {
+            let path = config::Config::resolve_path(config_path)?;
+            config_edit::set_in_file(&path, &key, &value)?;
+            println!(
+                "{} Set {} in {}",
+                "✓".green(),
+                key.magenta(),
+                path.display().to_string().cyan()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// The current repository's hooks directory, or with `global` the global
+/// `core.hooksPath` (defaulting to `~/.config/git/hooks`, set when `configure`).
+fn hooks_dir(global: bool, configure: bool) -> Result<PathBuf> {
+    if !global {
+        return Ok(git::hooks_dir(&git::open_repo()?));
+    }
+
+    let default = xdg::BaseDirectories::new()
+        .get_config_home()
+        .ok_or(Error::NoHomeDirectory)?
+        .join("git")
+        .join("hooks");
+    let dir = hook::global_hooks_dir(&mut hook::open_global_config()?, &default, configure)?;
+    if configure {
+        println!(
+            "{} Global core.hooksPath is {}, repositories no longer run hooks from .git/hooks",
+            "!".yellow(),
+            dir.display().to_string().magenta()
+        );
+    }
+    Ok(dir)
+}
+
+/// Report a `--flush-spool` run. Failures are reported like those of a capture.
+fn handle_flush(result: Result<FlushReport>, server_url: &str) -> Result<()> {
+    let report = match result {
+        Ok(report) => report,
+        Err(e) => return handle_result(Err(e), false, server_url),
+    };
+    tracing::info!(
+        uploaded = report.uploaded,
+        skipped = report.skipped,
+        remaining = report.remaining,
+        "Flushed spool"
+    );
+    if !tracing::enabled!(tracing::Level::INFO) {
+        println!(
+            "{} Uploaded {} spooled lolcommit(s) to {}, {} left in the spool",
+            "✓".green(),
+            report.uploaded,
+            server_url.magenta(),
+            report.remaining
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    const SERVER: &str = "http://127.0.0.1:3000";
+
+    fn busy() -> Result<Outcome> {
+        Err(Error::CameraBusy {
+            device: "/dev/video0".to_string(),
+        })
+    }
+
+    #[test]
+    fn test_success_is_ok() {
+        assert!(handle_result(Ok(Outcome::Uploaded), false, SERVER).is_ok());
+    }
+
+    #[test]
+    fn test_already_captured_is_ok() {
+        let outcome = Outcome::AlreadyCaptured { filename: None };
+        assert!(handle_result(Ok(outcome), false, SERVER).is_ok());
+    }
+
+    #[test]
+    fn test_already_captured_error_passes_through() {
+        let result = handle_result(
+            Err(Error::AlreadyCaptured {
+                revision: "abc".to_string(),
+            }),
+            false,
+            SERVER,
+        );
+        assert!(matches!(result, Err(Error::AlreadyCaptured { revision }) if revision == "abc"));
+    }
+
+    #[test]
+    fn test_processed_is_ok() {
+        let outcome = Outcome::Processed {
+            filename: "repo-20240101-120000-abc.png".to_string(),
+        };
+        assert!(handle_result(Ok(outcome), false, SERVER).is_ok());
+    }
+
+    #[test]
+    fn test_cli_flags_win_over_repo_config() -> Result {
+        let dir = tempfile::tempdir()?;
+        let user_config = dir.path().join("config.toml");
+        std::fs::write(&user_config, "[client]\nwait_for_processing = false\n")?;
+        let repo = git2::Repository::init(dir.path().join("repo"))?;
+        std::fs::write(
+            dir.path().join("repo").join(config::REPO_CONFIG_FILE_NAME),
+            "[client]\nmode = \"server\"\ncapture_source = \"/repo/avatar.png\"\nserver_url = \"http://repo:3000\"\n",
+        )?;
+        let args = Args::parse_from([
+            "lolcommits_upload",
+            "--config",
+            &user_config.to_string_lossy(),
+            "--local",
+            "--wait",
+            "--delay",
+            "3",
+            "--save-raw",
+            "/tmp/raw",
+        ]);
+
+        let client = load_config(&args, Some(&repo))?.client.unwrap();
+
+        assert_eq!(client.mode, config::CaptureMode::Local);
+        assert!(client.wait_for_processing);
+        assert_eq!(client.capture_delay_secs, 3);
+        assert_eq!(client.save_raw_dir.as_deref(), Some("/tmp/raw"));
+        // Not given on the command line, so the repository's values stand
+        assert_eq!(
+            client.capture_source,
+            Some(PathBuf::from("/repo/avatar.png"))
+        );
+        assert_eq!(client.server_url, "http://repo:3000");
+        Ok(())
+    }
+
+    #[test_case("--from-file" ; "from file")]
+    #[test_case("--image" ; "image alias")]
+    fn test_image_replaces_the_camera(flag: &str) -> Result {
+        let dir = tempfile::tempdir()?;
+        let user_config = dir.path().join("config.toml");
+        std::fs::write(&user_config, "")?;
+        let args = Args::parse_from([
+            "lolcommits_upload",
+            "--config",
+            &user_config.to_string_lossy(),
+            flag,
+            "/tmp/phone.jpg",
+        ]);
+
+        let client = load_config(&args, None)?.client.unwrap();
+        assert_eq!(client.capture_source, Some(PathBuf::from("/tmp/phone.jpg")));
+        for camera_flag in [&["--delay", "3"][..], &["--quiet"], &["--save-raw", "/tmp"]] {
+            let args = [&["lolcommits_upload", flag, "a.png"][..], camera_flag].concat();
+            assert!(Args::try_parse_from(args).is_err(), "{camera_flag:?}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_delay_is_capped() {
+        assert!(Args::try_parse_from(["lolcommits_upload", "--delay", "30"]).is_ok());
+        assert!(Args::try_parse_from(["lolcommits_upload", "--delay", "31"]).is_err());
+    }
+
+    #[test]
+    fn test_background_conflicts_with_local_and_flush() {
+        assert!(Args::try_parse_from(["lolcommits_upload", "--background"]).is_ok());
+        assert!(Args::try_parse_from(["lolcommits_upload", "--background", "--local"]).is_err());
+        assert!(
+            Args::try_parse_from(["lolcommits_upload", "--background", "--flush-spool"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_doctor_subcommand() {
+        let args = Args::parse_from(["lolcommits_upload", "doctor", "--json", "--ping"]);
+        assert!(matches!(
+            args.command,
+            Some(Command::Doctor {
+                json: true,
+                ping: true,
+                ..
+            })
+        ));
+        assert!(Args::try_parse_from(["lolcommits_upload", "doctor", "--wait"]).is_err());
+    }
+
+    #[test]
+    fn test_last_chyron_flag_wins() {
+        let args = Args::parse_from(["lolcommits_upload", "--chyron", "--no-chyron"]);
+        assert!(!args.chyron && args.no_chyron);
+        let args = Args::parse_from(["lolcommits_upload", "--no-chyron", "--chyron"]);
+        assert!(args.chyron && !args.no_chyron);
+    }
+
+    #[test]
+    fn test_queued_is_ok() {
+        let outcome = Outcome::Queued {
+            path: PathBuf::from("/tmp/spool/0000000000001-abc.png"),
+        };
+        assert!(handle_result(Ok(outcome), false, SERVER).is_ok());
+    }
+
+    #[test]
+    fn test_disabled_is_ok() {
+        assert!(handle_result(Ok(Outcome::Disabled), false, SERVER).is_ok());
+    }
+
+    #[test]
+    fn test_saved_is_ok() {
+        let outcome = Outcome::Saved {
+            path: PathBuf::from("/tmp/images/repo-20240101-120000-abc.png"),
+        };
+        assert!(handle_result(Ok(outcome), false, SERVER).is_ok());
+    }
+
+    #[test_case(false ; "unreachable")]
+    #[test_case(true ; "read only")]
+    fn test_spooled_is_ok(read_only: bool) {
+        let outcome = Outcome::Spooled {
+            path: PathBuf::from("/tmp/spool/0000000000001-abc.png"),
+            read_only,
+        };
+        assert!(handle_result(Ok(outcome), false, SERVER).is_ok());
+    }
+
+    #[test]
+    fn test_flush_report_is_ok() {
+        assert!(handle_flush(Ok(FlushReport::default()), SERVER).is_ok());
+    }
+
+    #[test]
+    fn test_camera_busy_is_ok_when_quiet() {
+        assert!(handle_result(busy(), true, SERVER).is_ok());
+    }
+
+    #[test]
+    fn test_camera_busy_is_error_without_quiet() {
+        let result = handle_result(busy(), false, SERVER);
+        assert!(matches!(result, Err(Error::CameraBusy { device }) if device == "/dev/video0"));
+    }
+
+    #[test]
+    fn test_camera_timeout_is_ok_when_quiet() {
+        let timeout = || {
+            Err(Error::CameraTimeout {
+                device: "/dev/video0".to_string(),
+            })
+        };
+        assert!(handle_result(timeout(), true, SERVER).is_ok());
+        assert!(matches!(
+            handle_result(timeout(), false, SERVER),
+            Err(Error::CameraTimeout { .. })
+        ));
+    }
+
+    #[test]
+    fn test_connection_failure_passes_through() {
+        let source = reqwest::blocking::get("http://127.0.0.1:1/").unwrap_err();
+        let result = handle_result(
+            Err(Error::ServerConnectionFailed {
+                url: "http://127.0.0.1:1/api/upload".to_string(),
+                source,
+                attempts: 4,
+            }),
+            true,
+            SERVER,
+        );
+        assert!(matches!(result, Err(Error::ServerConnectionFailed { .. })));
+    }
+
+    #[test]
+    fn test_failure_message_prefers_the_error_message() {
+        assert_eq!(
+            failure_message(
+                r#"{"error":{"code":"invalid_override","message":"override \"x\" is not allowed","details":{}}}"#
+            ),
+            "override \"x\" is not allowed"
+        );
+        assert_eq!(
+            failure_message(r#"{"error":"busy","message":"try again later"}"#),
+            "try again later"
+        );
+        assert_eq!(failure_message("Bad Gateway"), "Bad Gateway");
+        assert_eq!(
+            failure_message(r#"{"error":"busy"}"#),
+            r#"{"error":"busy"}"#
+        );
+    }
+
+    #[test]
+    fn test_upload_failure_passes_through() {
+        let result = handle_result(
+            Err(Error::UploadFailed {
+                status: 500,
+                body: "boom".to_string(),
+                attempts: 4,
+            }),
+            true,
+            SERVER,
+        );
+        assert!(matches!(
+            result,
+            Err(Error::UploadFailed { status: 500, .. })
+        ));
+    }
+
+    #[test]
+    fn test_read_only_passes_through() {
+        let result = handle_result(
+            Err(Error::ServerReadOnly {
+                url: SERVER.to_string(),
+            }),
+            true,
+            SERVER,
+        );
+        assert!(matches!(result, Err(Error::ServerReadOnly { .. })));
+    }
+
+    #[test]
+    fn test_revision_not_found_passes_through() {
+        let result = handle_result(
+            Err(Error::RevisionNotFound {
+                input: "feature/typo".to_string(),
+            }),
+            true,
+            SERVER,
+        );
+        assert!(
+            matches!(result, Err(Error::RevisionNotFound { input }) if input == "feature/typo")
+        );
+    }
+
+    #[test]
+    fn test_other_errors_pass_through() {
+        let result = handle_result(
+            Err(Error::UnknownCameraFormat {
+                format: "H264".to_string(),
+            }),
+            true,
+            SERVER,
+        );
+        assert!(matches!(result, Err(Error::UnknownCameraFormat { .. })));
+    }
+}
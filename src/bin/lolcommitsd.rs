@@ -1,6 +1,20 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use std::net::SocketAddr;
 use std::path::PathBuf;
-use sw1nn_lolcommits_rs::{LogOutput, config, init_tracing_with_output, server};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+use sw1nn_lolcommits_rs::{
+    LogOutput, config, error, fsck,
+    image_processor::Background,
+    init_tracing_with_output,
+    segmentation::{self, SegmentationModel},
+    server,
+    setup::{self, StepReport},
+    systemd, tls,
+};
+use tokio::signal::unix::{SignalKind, signal};
+use tokio_util::task::TaskTracker;
 
 #[derive(Parser, Debug)]
 #[command(name = "lolcommitsd")]
@@ -12,33 +26,257 @@ struct Args {
 
     #[arg(long, value_enum, help = "Log output destination (overrides config)")]
     log: Option<LogOutput>,
+
+    #[arg(long, action = clap::ArgAction::SetTrue, help = "Check images_dir for inconsistencies and exit")]
+    fsck: bool,
+
+    #[arg(long, action = clap::ArgAction::SetTrue, requires = "fsck", help = "Apply the safe fixes found by --fsck (stop the daemon first)")]
+    repair: bool,
+
+    #[arg(long, action = clap::ArgAction::SetTrue, requires = "fsck", help = "Print the --fsck report as JSON")]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Create the server directories and download the segmentation model, then exit
+    Setup,
+    /// Verify the segmentation model's checksum, downloading it again if it's missing or
+    /// corrupt. Exits non-zero when no valid model could be had
+    Check,
 }
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Args::parse();
+async fn main() -> ExitCode {
+    match run(Args::parse()).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", error::report(e.as_ref()));
+            ExitCode::FAILURE
+        }
+    }
+}
 
+async fn run(args: Args) -> Result<(), Box<dyn std::error::Error>> {
     // Load config first to get log_output setting
-    let cfg = config::Config::load_from(args.config)?;
+    let cfg = config::Config::load_from(args.config.clone())?;
     let server_cfg = cfg.server.clone().unwrap_or_default();
 
     // CLI --log overrides config log_output
     let log_output = args.log.unwrap_or(server_cfg.log_output);
     init_tracing_with_output(log_output);
+
+    if let Some(Command::Setup) = args.command {
+        let reports = setup::run_server(
+            &server_cfg,
+            |models_dir, spec| segmentation::get_model_path(models_dir, spec),
+            StepReport::print,
+        );
+        if reports.iter().any(StepReport::is_failure) {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if let Some(Command::Check) = args.command {
+        let spec = segmentation::ModelSpec::from_config(&server_cfg.segmentation)?;
+        match segmentation::get_model_path(&server_cfg.models_dir, &spec) {
+            Ok(path) => println!("segmentation model ok: {}", path.display()),
+            Err(e) => {
+                eprintln!("{}", error::report(&e));
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
+    let images_dir = PathBuf::from(&server_cfg.images_dir);
+
+    if args.fsck {
+        let mut report = fsck::check(&images_dir)?;
+        if args.repair {
+            fsck::repair(&mut report);
+        }
+        if args.json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            report.print();
+        }
+        if report.unresolved() > 0 {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     let metrics_handle = sw1nn_lolcommits_rs::metrics::install_recorder();
 
     tracing::info!("Starting lolcommitsd({})", env!("CARGO_PKG_VERSION"));
     tracing::info!(config = ?cfg, "Parsed config");
 
-    let images_dir = PathBuf::from(&server_cfg.images_dir);
+    // Surface divergence early rather than as 404s and skipped uploads. Opt-in, as it
+    // rereads the whole gallery on top of the image index's own scan
+    if server_cfg.fsck_on_startup {
+        match fsck::check(&images_dir) {
+            Ok(report) if !report.issues.is_empty() => tracing::warn!(
+                issues = report.issues.len(),
+                images_dir = %images_dir.display(),
+                "images_dir is inconsistent, run `lolcommitsd --fsck` for details"
+            ),
+            Ok(_) => {}
+            Err(e) => tracing::warn!(error = %e, "Startup consistency check failed"),
+        }
+    }
+
+    // Load the certificate before anything slow, so a bad one stops startup straight away
+    let tls = match (&server_cfg.tls_cert_path, &server_cfg.tls_key_path) {
+        (Some(cert), Some(key)) => Some(tls::load(cert.as_ref(), key.as_ref()).await?),
+        _ => None,
+    };
 
-    let app = server::create_router(images_dir, metrics_handle);
+    // Load the segmentation model once rather than on every upload. Without it the
+    // server still runs, storing captures with their original background
+    let model_spec = segmentation::ModelSpec::from_config(&server_cfg.segmentation)?;
+    let models_dir = segmentation::resolve_models_dir(server_cfg.models_dir.as_ref(), &model_spec);
+    let segmentation_model = Arc::new(SegmentationModel::open(
+        &models_dir,
+        &model_spec,
+        server_cfg.dnn_backend,
+        server_cfg.dnn_target,
+    ));
+    if Background::resolve(&server_cfg.background_path) != Background::Disabled {
+        let model = segmentation_model.clone();
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = segmentation::get_model_path(&models_dir, &model_spec) {
+                tracing::error!(error = %e, "Failed to download segmentation model");
+            }
+            if !model.preload() {
+                tracing::error!(
+                    models_dir = %models_dir.display(),
+                    "Segmentation model unavailable, background replacement is disabled until the model file changes"
+                );
+            }
+        })
+        .await?;
+    }
 
-    let bind_addr = format!("{}:{}", server_cfg.bind_address, server_cfg.bind_port);
-    let listener = tokio::net::TcpListener::bind(&bind_addr).await?;
-    tracing::info!(address = %bind_addr, "Server running");
+    let shared_config = server::SharedConfig::new(cfg, args.config);
+    tokio::spawn(reload_on_sighup(shared_config.clone()));
 
-    axum::serve(listener, app).await?;
+    let tasks = TaskTracker::new();
+    let app = server::create_router(
+        images_dir,
+        metrics_handle,
+        segmentation_model,
+        shared_config,
+        tasks.clone(),
+    );
 
+    // A socket passed by systemd (lolcommitsd.socket) takes the place of bind_address
+    let listener = match systemd::listener() {
+        Some(listener) => {
+            listener.set_nonblocking(true)?;
+            tokio::net::TcpListener::from_std(listener)?
+        }
+        None => {
+            let bind_addr = format!("{}:{}", server_cfg.bind_address, server_cfg.bind_port);
+            tokio::net::TcpListener::bind(&bind_addr).await?
+        }
+    };
+    let address = listener.local_addr()?;
+
+    match tls {
+        Some(tls) => {
+            tracing::info!(%address, "Server running with HTTPS");
+            let handle = axum_server::Handle::new();
+            let stopping = handle.clone();
+            tokio::spawn(async move {
+                shutdown_signal().await;
+                stopping.graceful_shutdown(None);
+            });
+            axum_server::from_tcp_rustls(listener.into_std()?, tls)?
+                .handle(handle)
+                .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+                .await?;
+        }
+        None => {
+            tracing::info!(%address, "Server running");
+            // With the client's address, for the SSE connection logs
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .with_graceful_shutdown(shutdown_signal())
+            .await?;
+        }
+    }
+
+    drain(tasks, Duration::from_secs(server_cfg.shutdown_drain_secs)).await;
     Ok(())
 }
+
+/// Resolve on SIGTERM (systemctl stop or restart) or SIGINT (Ctrl-C).
+async fn shutdown_signal() {
+    let terminate = async {
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "Failed to install SIGTERM handler");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    tokio::select! {
+        _ = terminate => tracing::info!("Received SIGTERM, shutting down"),
+        _ = tokio::signal::ctrl_c() => tracing::info!("Received SIGINT, shutting down"),
+    }
+}
+
+/// Wait up to `timeout` for uploads still being processed, so a restart doesn't lose
+/// the ones already accepted.
+async fn drain(tasks: TaskTracker, timeout: Duration) {
+    tasks.close();
+    let pending = tasks.len();
+    if pending == 0 {
+        return;
+    }
+    tracing::info!(
+        pending,
+        timeout_secs = timeout.as_secs(),
+        "Waiting for uploads being processed"
+    );
+    let abandoned = match tokio::time::timeout(timeout, tasks.wait()).await {
+        Ok(()) => 0,
+        Err(_) => tasks.len(),
+    };
+    let drained = pending.saturating_sub(abandoned);
+    if abandoned > 0 {
+        tracing::warn!(
+            drained,
+            abandoned,
+            "Shut down with uploads still being processed"
+        );
+    } else {
+        tracing::info!(drained, abandoned, "Processed remaining uploads");
+    }
+}
+
+/// Re-read the config file on each SIGHUP.
+async fn reload_on_sighup(config: server::SharedConfig) {
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(e) => {
+            tracing::warn!(error = %e, "Failed to install SIGHUP handler, config reload is only available over HTTP");
+            return;
+        }
+    };
+    while hangup.recv().await.is_some() {
+        tracing::info!("Received SIGHUP, reloading config");
+        // Failures are logged by reload and the current config kept
+        let _ = config.reload();
+    }
+}
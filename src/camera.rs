@@ -1,13 +1,126 @@
-use crate::config::{CameraDeviceConfig, ClientConfig};
+use crate::config::{CameraDeviceConfig, CameraKind, ClientConfig, Crop, Rotation};
 use crate::error::{Error, Result};
 use image::DynamicImage;
 use nokhwa::Camera;
 use nokhwa::pixel_format::RgbFormat;
-use nokhwa::utils::{CameraIndex, FrameFormat, RequestedFormat, RequestedFormatType};
+use nokhwa::utils::{
+    CameraIndex, ControlValueDescription, ControlValueSetter, FrameFormat, RequestedFormat,
+    RequestedFormatType,
+};
+use serde::Serialize;
+use std::io::{IsTerminal, Write};
 use std::panic;
 use std::path::Path;
+use std::time::{Duration, Instant};
 
-fn parse_frame_format(format_str: &str) -> Option<FrameFormat> {
+/// A camera found on this machine by [`detect_cameras`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DetectedCamera {
+    /// Identifier usable as `device` in the client's `camera_devices`.
+    pub device: String,
+    pub name: String,
+}
+
+/// List the cameras the native backend can see.
+pub fn detect_cameras() -> Result<Vec<DetectedCamera>> {
+    let cameras = nokhwa::query(nokhwa::utils::ApiBackend::Auto)?;
+    tracing::debug!(count = cameras.len(), "Detected cameras");
+
+    Ok(cameras
+        .into_iter()
+        .map(|info| DetectedCamera {
+            device: device_id(info.index()),
+            name: info.human_name(),
+        })
+        .collect())
+}
+
+fn device_id(index: &CameraIndex) -> String {
+    match index {
+        CameraIndex::Index(index) => index.to_string(),
+        CameraIndex::String(device) => device.clone(),
+    }
+}
+
+/// A capture format a camera reports as supported.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct DeviceFormat {
+    pub format: String,
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+}
+
+/// A camera with everything needed to fill in `camera_devices`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DeviceListing {
+    /// Identifier usable as `device` in the client's `camera_devices`.
+    pub device: String,
+    /// `/dev/videoN` node for numeric indices, when it exists.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    pub name: String,
+    pub formats: Vec<DeviceFormat>,
+    /// Why the formats couldn't be read, e.g. the device is in use.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// List cameras with their supported formats. A camera that can't be opened is still
+/// listed, with the reason in `error`.
+pub fn list_devices() -> Result<Vec<DeviceListing>> {
+    let cameras = nokhwa::query(nokhwa::utils::ApiBackend::Auto)?;
+    tracing::debug!(count = cameras.len(), "Detected cameras");
+
+    Ok(cameras
+        .into_iter()
+        .map(|info| {
+            let (formats, error) = match query_formats(info.index()) {
+                Ok(formats) => (formats, None),
+                Err(e) => {
+                    tracing::debug!(device = %info.index(), error = %e, "Could not query camera formats");
+                    (Vec::new(), Some(e.to_string()))
+                }
+            };
+            DeviceListing {
+                device: device_id(info.index()),
+                path: device_node(info.index(), Path::new("/dev")),
+                name: info.human_name(),
+                formats,
+                error,
+            }
+        })
+        .collect())
+}
+
+fn device_node(index: &CameraIndex, dev_dir: &Path) -> Option<String> {
+    let CameraIndex::Index(index) = index else {
+        return None;
+    };
+    let path = dev_dir.join(format!("video{index}"));
+    path.exists().then(|| path.display().to_string())
+}
+
+fn query_formats(index: &CameraIndex) -> Result<Vec<DeviceFormat>> {
+    let requested = RequestedFormat::new::<RgbFormat>(RequestedFormatType::None);
+    let mut camera = Camera::new(index.clone(), requested)?;
+
+    let mut formats: Vec<DeviceFormat> = camera
+        .compatible_camera_formats()?
+        .into_iter()
+        .map(|format| DeviceFormat {
+            format: format.format().to_string(),
+            width: format.resolution().width(),
+            height: format.resolution().height(),
+            fps: format.frame_rate(),
+        })
+        .collect();
+    formats.sort();
+    formats.dedup();
+    Ok(formats)
+}
+
+pub(crate) fn parse_frame_format(format_str: &str) -> Option<FrameFormat> {
     match format_str.to_uppercase().as_str() {
         "YUYV" | "YUY2" => Some(FrameFormat::YUYV),
         "MJPEG" | "MJPG" => Some(FrameFormat::MJPEG),
@@ -17,7 +130,14 @@ fn parse_frame_format(format_str: &str) -> Option<FrameFormat> {
     }
 }
 
-fn parse_camera_device(device: &str) -> Result<CameraIndex> {
+/// Where the kernel describes each `/dev/videoN` node.
+const SYSFS_VIDEO_DIR: &str = "/sys/class/video4linux";
+
+fn parse_camera_device(device: &str, sysfs_dir: &Path) -> Result<CameraIndex> {
+    if let Some(selector) = Selector::parse(device) {
+        return selector.resolve(device, sysfs_dir).map(CameraIndex::Index);
+    }
+
     if device.chars().all(|c| c.is_ascii_digit()) {
         let index = device.parse().unwrap_or(0);
         tracing::debug!(index, "Using numeric camera index");
@@ -58,6 +178,112 @@ fn parse_camera_device(device: &str) -> Result<CameraIndex> {
     Ok(CameraIndex::String(device.to_string()))
 }
 
+/// A camera picked by what it is rather than its index, which can change between boots:
+/// `name:Logitech BRIO` or `serial:ABC123`, matched case-insensitively on a substring.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Selector<'a> {
+    Name(&'a str),
+    Serial(&'a str),
+}
+
+impl<'a> Selector<'a> {
+    fn parse(device: &'a str) -> Option<Self> {
+        if let Some(name) = device.strip_prefix("name:") {
+            Some(Selector::Name(name))
+        } else {
+            device.strip_prefix("serial:").map(Selector::Serial)
+        }
+    }
+
+    /// Index of the one camera under `sysfs_dir` that matches.
+    fn resolve(&self, device: &str, sysfs_dir: &Path) -> Result<u32> {
+        let matches: Vec<VideoNode> = video_nodes(sysfs_dir)
+            .into_iter()
+            .filter(|node| self.matches(node))
+            .collect();
+        match matches.as_slice() {
+            [node] => {
+                tracing::debug!(
+                    device,
+                    index = node.index,
+                    name = node.name,
+                    "Resolved camera"
+                );
+                Ok(node.index)
+            }
+            [] => Err(Error::CameraNotFound {
+                device: device.to_string(),
+            }),
+            _ => {
+                let candidates = matches
+                    .iter()
+                    .map(|node| format!("/dev/video{} ({})", node.index, node.name))
+                    .collect();
+                Err(Error::CameraAmbiguous {
+                    device: device.to_string(),
+                    candidates,
+                })
+            }
+        }
+    }
+
+    fn matches(&self, node: &VideoNode) -> bool {
+        let (value, wanted) = match self {
+            Selector::Name(name) => (Some(&node.name), name),
+            Selector::Serial(serial) => (node.serial.as_ref(), serial),
+        };
+        value.is_some_and(|value| value.to_lowercase().contains(&wanted.to_lowercase()))
+    }
+}
+
+/// A camera's capture node as sysfs describes it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct VideoNode {
+    index: u32,
+    name: String,
+    /// Serial number of the USB device, for USB cameras that report one.
+    serial: Option<String>,
+}
+
+/// The capture nodes under `sysfs_dir`, by index. A camera's extra nodes, such as the
+/// metadata node of UVC cameras, share its name and are left out.
+fn video_nodes(sysfs_dir: &Path) -> Vec<VideoNode> {
+    let read = |path: std::path::PathBuf| {
+        std::fs::read_to_string(path)
+            .ok()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+    };
+
+    let Ok(entries) = std::fs::read_dir(sysfs_dir) else {
+        tracing::debug!(dir = %sysfs_dir.display(), "No video devices to match against");
+        return Vec::new();
+    };
+    let mut nodes: Vec<VideoNode> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let dir = entry.path();
+            let index = entry
+                .file_name()
+                .to_str()?
+                .strip_prefix("video")?
+                .parse()
+                .ok()?;
+            if read(dir.join("index")).is_some_and(|node| node != "0") {
+                return None;
+            }
+            Some(VideoNode {
+                index,
+                name: read(dir.join("name"))?,
+                // `device` links to the USB interface, the serial is on the device above it
+                serial: read(dir.join("device/../serial")),
+            })
+        })
+        .collect();
+    nodes.sort_by_key(|node| node.index);
+    nodes
+}
+
 fn try_camera_with_device_config(
     index: &CameraIndex,
     device_config: &CameraDeviceConfig,
@@ -136,11 +362,195 @@ fn try_camera_formats(index: &CameraIndex) -> Result<Camera> {
         .unwrap_or_else(|| std::io::Error::other("No compatible camera format found").into()))
 }
 
-/// Try to capture an image from a single camera device.
-fn try_capture_from_device(device_config: &CameraDeviceConfig) -> Result<DynamicImage> {
-    tracing::debug!(device = device_config.device, "Trying camera device");
+/// Frames to throw away after opening the stream so exposure and white balance settle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Warmup {
+    pub frames: usize,
+    /// Keep discarding until at least this long has passed, whatever the frame count.
+    pub min_duration: Duration,
+}
+
+impl Warmup {
+    pub fn from_config(config: &ClientConfig) -> Self {
+        Self {
+            frames: config.camera_warmup_frames,
+            min_duration: Duration::from_millis(config.camera_warmup_ms),
+        }
+    }
+
+    /// Grab and discard frames until both the frame count and minimum duration are
+    /// reached, returning how many were grabbed. Failed grabs are logged and count
+    /// towards the total; only the final capture decides whether the capture works.
+    fn run<E: std::fmt::Display>(
+        &self,
+        mut grab: impl FnMut() -> std::result::Result<(), E>,
+    ) -> usize {
+        let started = Instant::now();
+        let mut discarded = 0;
+
+        while discarded < self.frames || started.elapsed() < self.min_duration {
+            match grab() {
+                Ok(()) => tracing::debug!(frame = discarded + 1, "Discarded warmup frame"),
+                Err(e) => {
+                    tracing::warn!(frame = discarded + 1, error = %e, "Warmup frame failed");
+                    // Don't spin on a camera that fails instantly while waiting on time
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+            }
+            discarded += 1;
+        }
+
+        discarded
+    }
+}
+
+/// A pause after warmup so the subject can straighten up, with the stream kept open so
+/// exposure stays settled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Countdown {
+    pub delay: Duration,
+    /// Print the seconds remaining to stdout.
+    pub show: bool,
+}
+
+impl Countdown {
+    pub fn from_config(config: &ClientConfig) -> Self {
+        let secs = config
+            .capture_delay_secs
+            .min(crate::config::MAX_CAPTURE_DELAY_SECS);
+        Self {
+            delay: Duration::from_secs(secs),
+            show: std::io::stdout().is_terminal() && !tracing::enabled!(tracing::Level::INFO),
+        }
+    }
+
+    /// Keep grabbing and discarding frames until the delay is up, so the capture that
+    /// follows is fresh rather than one buffered while waiting. Counts down to `out` as
+    /// "3… 2… 1… 📸" when shown.
+    fn run<E: std::fmt::Display>(
+        &self,
+        mut grab: impl FnMut() -> std::result::Result<(), E>,
+        out: &mut impl Write,
+    ) {
+        if self.delay.is_zero() {
+            return;
+        }
+
+        let started = Instant::now();
+        let mut shown = None;
+        loop {
+            let remaining = self.delay.saturating_sub(started.elapsed());
+            if remaining.is_zero() {
+                break;
+            }
+            let secs = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+            if self.show && shown != Some(secs) {
+                let _ = write!(out, "{secs}… ");
+                let _ = out.flush();
+                shown = Some(secs);
+            }
+            if let Err(e) = grab() {
+                tracing::debug!(error = %e, "Frame failed during capture delay");
+                std::thread::sleep(remaining.min(Duration::from_millis(10)));
+            }
+        }
+        if self.show {
+            let _ = writeln!(out, "📸");
+        }
+    }
+}
+
+/// Frames kept once the stream has warmed up: a single still, or an animated capture's
+/// frames spread over its duration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Burst {
+    pub frames: usize,
+    /// Time from the start of one kept frame to the next.
+    pub interval: Duration,
+}
+
+impl Burst {
+    pub const STILL: Burst = Burst {
+        frames: 1,
+        interval: Duration::ZERO,
+    };
+
+    pub fn from_config(config: &ClientConfig) -> Self {
+        let frames = config.animate_frames.max(1);
+        Self {
+            frames,
+            interval: Duration::from_millis(config.animate_duration_ms) / frames as u32,
+        }
+    }
+
+    /// Grab the frames, waiting out the rest of the interval after each so a slow
+    /// grab doesn't stretch the capture. The first failure aborts it.
+    fn run<T>(&self, mut grab: impl FnMut() -> Result<T>) -> Result<Vec<T>> {
+        let mut frames = Vec::with_capacity(self.frames);
+        for index in 0..self.frames {
+            let started = Instant::now();
+            frames.push(grab()?);
+            tracing::debug!(frame = index + 1, of = self.frames, "Captured frame");
+            if index + 1 < self.frames {
+                std::thread::sleep(self.interval.saturating_sub(started.elapsed()));
+            }
+        }
+        Ok(frames)
+    }
+}
+
+/// How often to retry a camera another application is briefly holding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BusyRetry {
+    pub retries: u32,
+    pub delay: Duration,
+}
+
+impl BusyRetry {
+    pub fn from_config(config: &ClientConfig) -> Self {
+        Self {
+            retries: config.camera_busy_retries,
+            delay: Duration::from_millis(config.camera_busy_retry_delay_ms),
+        }
+    }
+
+    /// Run `attempt` until it succeeds, fails with something other than
+    /// [`Error::CameraBusy`], or the retries run out.
+    fn run<T>(&self, device: &str, mut attempt: impl FnMut() -> Result<T>) -> Result<T> {
+        let mut retry = 0;
+        loop {
+            match attempt() {
+                Err(Error::CameraBusy { .. }) if retry < self.retries => {
+                    retry += 1;
+                    tracing::debug!(
+                        device,
+                        retry,
+                        max_retries = self.retries,
+                        delay_ms = self.delay.as_millis() as u64,
+                        "Camera busy, retrying"
+                    );
+                    std::thread::sleep(self.delay);
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Try to capture `burst`'s frames from a single camera device, reusing the open stream.
+fn try_capture_from_device(
+    device_config: &CameraDeviceConfig,
+    warmup: &Warmup,
+    countdown: &Countdown,
+    burst: &Burst,
+) -> Result<Vec<DynamicImage>> {
+    tracing::debug!(device = device_config.device, kind = ?device_config.kind, "Trying camera device");
+
+    if device_config.kind == CameraKind::HttpSnapshot {
+        return try_capture_from_snapshot_url(device_config, countdown, burst);
+    }
 
-    let index = parse_camera_device(&device_config.device)?;
+    let index = parse_camera_device(&device_config.device, Path::new(SYSFS_VIDEO_DIR))?;
 
     // Use device-specific format if all settings provided, otherwise auto-detect
     let mut camera = match try_camera_with_device_config(&index, device_config) {
@@ -183,8 +593,236 @@ fn try_capture_from_device(device_config: &CameraDeviceConfig) -> Result<Dynamic
         return Err(e.into());
     }
 
-    tracing::debug!("Capturing frame");
-    let frame = camera.frame()?;
+    apply_controls(&mut camera, device_config);
+
+    let discarded = warmup.run(|| camera.frame().map(drop));
+    tracing::debug!(discarded, "Camera warmed up");
+    countdown.run(|| camera.frame().map(drop), &mut std::io::stdout());
+
+    burst.run(|| {
+        tracing::debug!("Capturing frame");
+        decode_frame(&camera.frame()?).map(|image| transform_frame(image, device_config))
+    })
+}
+
+/// Set a device's `controls` on its open camera. A control the camera doesn't have, or
+/// won't take, is skipped with a warning rather than failing the capture.
+fn apply_controls(camera: &mut Camera, device_config: &CameraDeviceConfig) {
+    if device_config.controls.is_empty() {
+        return;
+    }
+    let device = &device_config.device;
+    let available = match camera.camera_controls() {
+        Ok(available) => available,
+        Err(e) => {
+            tracing::warn!(device, error = %e, "Could not list camera controls, leaving them as they are");
+            return;
+        }
+    };
+
+    for (name, &requested) in &device_config.controls {
+        let Some(control) = available
+            .iter()
+            .find(|control| control_key(control.name()) == control_key(name))
+        else {
+            tracing::warn!(
+                device,
+                control = name,
+                available = ?available.iter().map(|control| control_key(control.name())).collect::<Vec<_>>(),
+                "Camera has no such control, skipping it"
+            );
+            continue;
+        };
+        let setter = match control_setter(control.description(), requested) {
+            Ok(setter) => setter,
+            Err(reason) => {
+                tracing::warn!(
+                    device,
+                    control = name,
+                    requested,
+                    reason,
+                    "Skipping camera control"
+                );
+                continue;
+            }
+        };
+        if let Err(e) = camera.set_camera_control(control.control(), setter.clone()) {
+            tracing::warn!(device, control = name, requested, error = %e, "Camera refused control");
+            continue;
+        }
+        let accepted = camera
+            .camera_control(control.control())
+            .map(|control| control.value());
+        tracing::debug!(
+            device,
+            control = name,
+            requested,
+            set = ?setter,
+            accepted = ?accepted.ok(),
+            "Applied camera control"
+        );
+    }
+}
+
+/// A control name as `v4l2-ctl` spells it: "White Balance Temperature, Auto" becomes
+/// `white_balance_temperature_auto`.
+fn control_key(name: &str) -> String {
+    name.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_ascii_lowercase)
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// The value to set a control described by `description` to for a configured
+/// `requested`, clamped into its range. An error naming why for controls that don't take
+/// a number.
+fn control_setter(
+    description: &ControlValueDescription,
+    requested: i64,
+) -> std::result::Result<ControlValueSetter, String> {
+    match description {
+        ControlValueDescription::Integer { .. } => Ok(ControlValueSetter::Integer(requested)),
+        ControlValueDescription::IntegerRange { min, max, .. } => {
+            let clamped = requested.clamp(*min, (*max).max(*min));
+            if clamped != requested {
+                tracing::debug!(requested, min, max, clamped, "Clamped camera control");
+            }
+            Ok(ControlValueSetter::Integer(clamped))
+        }
+        ControlValueDescription::Float { .. } => Ok(ControlValueSetter::Float(requested as f64)),
+        ControlValueDescription::FloatRange { min, max, .. } => Ok(ControlValueSetter::Float(
+            (requested as f64).clamp(*min, max.max(*min)),
+        )),
+        ControlValueDescription::Boolean { .. } => Ok(ControlValueSetter::Boolean(requested != 0)),
+        ControlValueDescription::Enum { possible, .. } => {
+            if possible.contains(&requested) {
+                Ok(ControlValueSetter::EnumValue(requested))
+            } else {
+                Err(format!("expected one of {possible:?}"))
+            }
+        }
+        other => Err(format!("not a numeric control: {other}")),
+    }
+}
+
+/// Longest to wait for a network camera's snapshot.
+const SNAPSHOT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Try to capture `burst`'s frames from an `http-snapshot` camera, fetching a fresh
+/// snapshot for each. There's no stream to warm up, so only the countdown applies.
+fn try_capture_from_snapshot_url(
+    device_config: &CameraDeviceConfig,
+    countdown: &Countdown,
+    burst: &Burst,
+) -> Result<Vec<DynamicImage>> {
+    let client = reqwest::blocking::Client::builder()
+        .timeout(SNAPSHOT_TIMEOUT)
+        .build()?;
+
+    countdown.run(
+        || {
+            std::thread::sleep(Duration::from_millis(100));
+            Ok::<_, Error>(())
+        },
+        &mut std::io::stdout(),
+    );
+
+    burst.run(|| {
+        tracing::debug!(url = device_config.device, "Fetching snapshot");
+        fetch_snapshot(&client, device_config).map(|image| transform_frame(image, device_config))
+    })
+}
+
+fn fetch_snapshot(
+    client: &reqwest::blocking::Client,
+    device_config: &CameraDeviceConfig,
+) -> Result<DynamicImage> {
+    let mut request = client.get(&device_config.device);
+    if let Some(username) = &device_config.username {
+        request = request.basic_auth(username, device_config.password.as_deref());
+    }
+    let bytes = request.send()?.error_for_status()?.bytes()?;
+    tracing::debug!(bytes = bytes.len(), "Snapshot fetched");
+    Ok(crate::orientation::load_from_memory(&bytes)?)
+}
+
+/// Apply a device's `rotate`, then `mirror`, then `crop` to a decoded frame.
+pub fn transform_frame(image: DynamicImage, device_config: &CameraDeviceConfig) -> DynamicImage {
+    let image = match device_config.rotate {
+        Rotation::None => image,
+        Rotation::Quarter => image.rotate90(),
+        Rotation::Half => image.rotate180(),
+        Rotation::ThreeQuarters => image.rotate270(),
+    };
+    let image = if device_config.mirror {
+        image.fliph()
+    } else {
+        image
+    };
+
+    match device_config.crop {
+        Some(crop) => {
+            let (x, y, width, height) = crop_rect(image.width(), image.height(), crop);
+            image.crop_imm(x, y, width, height)
+        }
+        None => image,
+    }
+}
+
+/// The `(x, y, width, height)` to keep of a `width` x `height` frame. A rectangle reaching
+/// outside the frame is clamped to it.
+fn crop_rect(width: u32, height: u32, crop: Crop) -> (u32, u32, u32, u32) {
+    match crop {
+        Crop::Rect {
+            x,
+            y,
+            width: crop_width,
+            height: crop_height,
+        } => {
+            let clamped_x = x.min(width.saturating_sub(1));
+            let clamped_y = y.min(height.saturating_sub(1));
+            let clamped_width = crop_width.min(width - clamped_x).max(1);
+            let clamped_height = crop_height.min(height - clamped_y).max(1);
+            let clamped = (clamped_x, clamped_y, clamped_width, clamped_height);
+            if clamped != (x, y, crop_width, crop_height) {
+                tracing::warn!(
+                    frame = %format!("{width}x{height}"),
+                    crop = ?(x, y, crop_width, crop_height),
+                    clamped = ?clamped,
+                    "Camera crop is outside the frame, clamping it"
+                );
+            }
+            clamped
+        }
+        Crop::Aspect(ratio) => {
+            let (ratio_width, ratio_height) = (u64::from(ratio.width), u64::from(ratio.height));
+            // The frame is wider than the ratio: keep its full height
+            let (crop_width, crop_height) =
+                if u64::from(width) * ratio_height > u64::from(height) * ratio_width {
+                    (
+                        u64::from(height) * ratio_width / ratio_height,
+                        u64::from(height),
+                    )
+                } else {
+                    (
+                        u64::from(width),
+                        u64::from(width) * ratio_height / ratio_width,
+                    )
+                };
+            let crop_width = (crop_width as u32).max(1);
+            let crop_height = (crop_height as u32).max(1);
+            (
+                (width - crop_width) / 2,
+                (height - crop_height) / 2,
+                crop_width,
+                crop_height,
+            )
+        }
+    }
+}
+
+fn decode_frame(frame: &nokhwa::Buffer) -> Result<DynamicImage> {
     tracing::debug!(
         source_format = ?frame.source_frame_format(),
         buffer_len = frame.buffer().len(),
@@ -224,23 +862,83 @@ fn try_capture_from_device(device_config: &CameraDeviceConfig) -> Result<Dynamic
     Ok(DynamicImage::ImageRgb8(decoded))
 }
 
+/// Run `capture` on a thread of its own, giving up on it with [`Error::CameraTimeout`]
+/// once `timeout` has passed. A wedged driver call can't be interrupted, so an abandoned
+/// capture runs on until the camera lets go; it only owns copies of its settings and its
+/// result is dropped unseen, so nothing it does by then reaches the caller.
+fn with_timeout<T: Send + 'static>(
+    device: &str,
+    timeout: Option<Duration>,
+    capture: impl FnOnce() -> Result<T> + Send + 'static,
+) -> Result<T> {
+    let Some(timeout) = timeout else {
+        return capture();
+    };
+
+    let (sender, receiver) = std::sync::mpsc::sync_channel(1);
+    std::thread::Builder::new()
+        .name(format!("capture {device}"))
+        .spawn(move || {
+            // The receiver is gone if the capture was abandoned
+            let _ = sender.send(capture());
+        })?;
+
+    match receiver.recv_timeout(timeout) {
+        Ok(result) => result,
+        Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
+            tracing::warn!(
+                device,
+                timeout_ms = timeout.as_millis() as u64,
+                "Camera stopped responding, abandoning it"
+            );
+            Err(Error::CameraTimeout {
+                device: device.to_string(),
+            })
+        }
+        Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => {
+            Err(std::io::Error::other(format!("capture from {device} panicked")).into())
+        }
+    }
+}
+
 /// Capture an image from a camera.
 ///
 /// Tries each camera device in order from config until one successfully captures.
 pub fn capture_image(config: &ClientConfig) -> Result<DynamicImage> {
+    let mut frames = capture_frames(config, &Burst::STILL)?;
+    Ok(frames.remove(0))
+}
+
+/// Capture `burst`'s frames from a camera, trying devices like [`capture_image`].
+pub fn capture_frames(config: &ClientConfig, burst: &Burst) -> Result<Vec<DynamicImage>> {
     let devices = &config.camera_devices;
     tracing::debug!(device_count = devices.len(), "Camera devices to try");
+    let warmup = Warmup::from_config(config);
+    let countdown = Countdown::from_config(config);
+    let busy_retry = BusyRetry::from_config(config);
+    let timeout = (config.capture_timeout_secs > 0).then(|| {
+        Duration::from_secs(config.capture_timeout_secs)
+            + warmup.min_duration
+            + countdown.delay
+            + burst.interval * burst.frames as u32
+    });
 
     let mut last_error = None;
 
     for device_config in devices {
-        match try_capture_from_device(device_config) {
-            Ok(image) => {
+        match busy_retry.run(&device_config.device, || {
+            // The capture thread may outlive this call, so it gets its own copies
+            let (owned, burst) = (device_config.clone(), *burst);
+            with_timeout(&device_config.device, timeout, move || {
+                try_capture_from_device(&owned, &warmup, &countdown, &burst)
+            })
+        }) {
+            Ok(frames) => {
                 tracing::info!(
                     device = device_config.device,
                     "Successfully captured from camera"
                 );
-                return Ok(image);
+                return Ok(frames);
             }
             Err(e) => {
                 tracing::debug!(device = device_config.device, error = %e, "Camera failed, trying next");
@@ -252,3 +950,540 @@ pub fn capture_image(config: &ClientConfig) -> Result<DynamicImage> {
     // All cameras failed, return the last error
     Err(last_error.unwrap_or_else(|| std::io::Error::other("No camera devices configured").into()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AspectRatio;
+    use image::{Rgb, RgbImage};
+    use test_case::test_case;
+
+    const MARK: Rgb<u8> = Rgb([255, 0, 0]);
+
+    /// A 4x2 black frame with its top-left pixel marked.
+    fn marked_frame() -> DynamicImage {
+        let mut frame = RgbImage::new(4, 2);
+        frame.put_pixel(0, 0, MARK);
+        DynamicImage::ImageRgb8(frame)
+    }
+
+    fn device(mirror: bool, rotate: Rotation, crop: Option<Crop>) -> CameraDeviceConfig {
+        CameraDeviceConfig {
+            mirror,
+            rotate,
+            crop,
+            ..CameraDeviceConfig::new("0")
+        }
+    }
+
+    /// Dimensions of the transformed frame and where the mark ended up.
+    fn transformed(config: &CameraDeviceConfig) -> ((u32, u32), (u32, u32)) {
+        let frame = transform_frame(marked_frame(), config).to_rgb8();
+        let mark = frame
+            .enumerate_pixels()
+            .find(|(_, _, pixel)| **pixel == MARK)
+            .map(|(x, y, _)| (x, y))
+            .expect("mark cropped away");
+        (frame.dimensions(), mark)
+    }
+
+    #[test_case(false, Rotation::None, (4, 2), (0, 0) ; "untouched")]
+    #[test_case(true, Rotation::None, (4, 2), (3, 0) ; "mirrored")]
+    #[test_case(false, Rotation::Quarter, (2, 4), (1, 0) ; "rotated 90")]
+    #[test_case(false, Rotation::Half, (4, 2), (3, 1) ; "rotated 180")]
+    #[test_case(false, Rotation::ThreeQuarters, (2, 4), (0, 3) ; "rotated 270")]
+    #[test_case(true, Rotation::Half, (4, 2), (0, 1) ; "rotated then mirrored")]
+    fn test_transform_frame(
+        mirror: bool,
+        rotate: Rotation,
+        dimensions: (u32, u32),
+        mark: (u32, u32),
+    ) {
+        assert_eq!(
+            transformed(&device(mirror, rotate, None)),
+            (dimensions, mark)
+        );
+    }
+
+    #[test]
+    fn test_crop_keeps_the_rectangle() {
+        let crop = Crop::Rect {
+            x: 1,
+            y: 0,
+            width: 3,
+            height: 1,
+        };
+        // Mirroring first moves the mark into the kept rectangle
+        assert_eq!(
+            transformed(&device(true, Rotation::None, Some(crop))),
+            ((3, 1), (2, 0))
+        );
+    }
+
+    #[test_case(Crop::Rect { x: 2, y: 0, width: 10, height: 10 }, (2, 0, 2, 2) ; "too big")]
+    #[test_case(Crop::Rect { x: 9, y: 9, width: 1, height: 1 }, (3, 1, 1, 1) ; "outside")]
+    #[test_case(Crop::Aspect(AspectRatio { width: 1, height: 1 }), (1, 0, 2, 2) ; "square of wide frame")]
+    #[test_case(Crop::Aspect(AspectRatio { width: 4, height: 1 }), (0, 0, 4, 1) ; "wider than frame")]
+    fn test_crop_rect(crop: Crop, expected: (u32, u32, u32, u32)) {
+        assert_eq!(crop_rect(4, 2, crop), expected);
+    }
+
+    fn warmup(frames: usize, min_ms: u64) -> Warmup {
+        Warmup {
+            frames,
+            min_duration: Duration::from_millis(min_ms),
+        }
+    }
+
+    #[test]
+    fn test_with_timeout_returns_the_capture() {
+        let result = with_timeout("0", Some(Duration::from_secs(5)), || Ok(42));
+        assert_eq!(result.unwrap(), 42);
+    }
+
+    #[test]
+    fn test_with_timeout_abandons_a_wedged_capture() {
+        let (finished, wait_for_finish) = std::sync::mpsc::channel();
+        let started = Instant::now();
+
+        let result = with_timeout("/dev/video0", Some(Duration::from_millis(20)), move || {
+            std::thread::sleep(Duration::from_millis(200));
+            finished.send(()).unwrap();
+            Ok(42)
+        });
+
+        assert!(matches!(result, Err(Error::CameraTimeout { device }) if device == "/dev/video0"));
+        assert!(started.elapsed() < Duration::from_millis(200));
+        // The abandoned capture finishing later is harmless
+        wait_for_finish.recv().unwrap();
+    }
+
+    #[test]
+    fn test_with_timeout_reports_a_panicking_capture() {
+        let result: Result<()> =
+            with_timeout("0", Some(Duration::from_secs(5)), || panic!("driver bug"));
+        assert!(matches!(result, Err(Error::Io(_))));
+    }
+
+    #[test]
+    fn test_countdown_counts_down_while_grabbing() {
+        let countdown = Countdown {
+            delay: Duration::from_millis(1500),
+            show: true,
+        };
+        let mut out = Vec::new();
+        let mut grabbed = 0;
+        let started = Instant::now();
+
+        countdown.run(
+            || {
+                grabbed += 1;
+                std::thread::sleep(Duration::from_millis(5));
+                Ok::<_, String>(())
+            },
+            &mut out,
+        );
+
+        assert!(started.elapsed() >= Duration::from_millis(1500));
+        assert!(grabbed > 1, "stream not kept busy");
+        assert_eq!(String::from_utf8(out).unwrap(), "2… 1… 📸\n");
+    }
+
+    #[test]
+    fn test_countdown_silent_or_zero() {
+        let mut out = Vec::new();
+        Countdown {
+            delay: Duration::from_millis(20),
+            show: false,
+        }
+        .run(|| Ok::<_, String>(()), &mut out);
+        Countdown {
+            delay: Duration::ZERO,
+            show: true,
+        }
+        .run(
+            || -> std::result::Result<(), String> { panic!("no frames should be grabbed") },
+            &mut out,
+        );
+
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn test_warmup_discards_configured_frames() {
+        let mut grabbed = 0;
+        let discarded = warmup(3, 0).run(|| {
+            grabbed += 1;
+            Ok::<_, String>(())
+        });
+
+        assert_eq!((discarded, grabbed), (3, 3));
+    }
+
+    #[test]
+    fn test_warmup_zero_frames_grabs_nothing() {
+        let discarded = warmup(0, 0)
+            .run(|| -> std::result::Result<(), String> { panic!("no frames should be grabbed") });
+
+        assert_eq!(discarded, 0);
+    }
+
+    #[test]
+    fn test_warmup_failures_do_not_abort() {
+        let mut grabbed = 0;
+        let discarded = warmup(3, 0).run(|| {
+            grabbed += 1;
+            if grabbed == 2 {
+                Err("dropped frame")
+            } else {
+                Ok(())
+            }
+        });
+
+        assert_eq!(discarded, 3);
+    }
+
+    #[test]
+    fn test_warmup_waits_for_min_duration() {
+        let started = Instant::now();
+        let discarded = warmup(1, 50).run(|| {
+            std::thread::sleep(Duration::from_millis(5));
+            Ok::<_, String>(())
+        });
+
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        assert!(discarded > 1);
+    }
+
+    #[test]
+    fn test_burst_spreads_frames_over_interval() -> Result {
+        let burst = Burst {
+            frames: 3,
+            interval: Duration::from_millis(20),
+        };
+        let started = Instant::now();
+        let mut next = 0;
+
+        let frames = burst.run(|| {
+            next += 1;
+            Ok(next)
+        })?;
+
+        assert_eq!(frames, vec![1, 2, 3]);
+        // No wait after the last frame
+        assert!(started.elapsed() >= Duration::from_millis(40));
+        Ok(())
+    }
+
+    #[test]
+    fn test_burst_stops_at_first_failure() {
+        let mut grabbed = 0;
+        let result = Burst {
+            frames: 5,
+            interval: Duration::ZERO,
+        }
+        .run(|| -> Result<()> {
+            grabbed += 1;
+            if grabbed == 2 { Err(busy()) } else { Ok(()) }
+        });
+
+        assert!(result.is_err());
+        assert_eq!(grabbed, 2);
+    }
+
+    #[test]
+    fn test_burst_from_config() {
+        let config = ClientConfig {
+            animate_frames: 8,
+            animate_duration_ms: 2000,
+            ..Default::default()
+        };
+        assert_eq!(
+            Burst::from_config(&config),
+            Burst {
+                frames: 8,
+                interval: Duration::from_millis(250)
+            }
+        );
+        let config = ClientConfig {
+            animate_frames: 0,
+            ..config
+        };
+        assert_eq!(Burst::from_config(&config).frames, 1);
+    }
+
+    fn busy() -> Error {
+        Error::CameraBusy {
+            device: "/dev/video0".to_string(),
+        }
+    }
+
+    fn busy_retry(retries: u32) -> BusyRetry {
+        BusyRetry {
+            retries,
+            delay: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn test_busy_retry_succeeds_after_busy() {
+        let mut attempts = 0;
+        let result = busy_retry(2).run("/dev/video0", || {
+            attempts += 1;
+            if attempts < 3 {
+                Err(busy())
+            } else {
+                Ok(attempts)
+            }
+        });
+
+        assert_eq!(result.unwrap(), 3);
+    }
+
+    #[test]
+    fn test_busy_retry_gives_up_when_exhausted() {
+        let mut attempts = 0;
+        let result = busy_retry(2).run("/dev/video0", || -> Result<()> {
+            attempts += 1;
+            Err(busy())
+        });
+
+        assert!(matches!(result, Err(Error::CameraBusy { .. })));
+        assert_eq!(attempts, 3);
+    }
+
+    #[test]
+    fn test_busy_retry_does_not_retry_other_errors() {
+        let mut attempts = 0;
+        let result = busy_retry(2).run("/dev/video0", || -> Result<()> {
+            attempts += 1;
+            Err(Error::GitCommandFailed)
+        });
+
+        assert!(matches!(result, Err(Error::GitCommandFailed)));
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn test_busy_retry_zero_retries() {
+        let mut attempts = 0;
+        let _ = busy_retry(0).run("/dev/video0", || -> Result<()> {
+            attempts += 1;
+            Err(busy())
+        });
+
+        assert_eq!(attempts, 1);
+    }
+
+    /// Serve `jpeg` to each of `requests` requests on a local port, returning its URL and
+    /// a thread with the `Authorization` header of each.
+    fn snapshot_server(
+        jpeg: Vec<u8>,
+        requests: usize,
+    ) -> (String, std::thread::JoinHandle<Vec<Option<String>>>) {
+        use std::io::{BufRead, BufReader};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/snapshot.jpg", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            (0..requests)
+                .map(|_| {
+                    let (mut stream, _) = listener.accept().unwrap();
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    let mut authorization = None;
+                    loop {
+                        let mut line = String::new();
+                        reader.read_line(&mut line).unwrap();
+                        if line == "\r\n" {
+                            break;
+                        }
+                        if let Some((name, value)) = line.split_once(':')
+                            && name.eq_ignore_ascii_case("authorization")
+                        {
+                            authorization = Some(value.trim().to_string());
+                        }
+                    }
+                    write!(
+                        stream,
+                        "HTTP/1.1 200 OK\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        jpeg.len()
+                    )
+                    .unwrap();
+                    stream.write_all(&jpeg).unwrap();
+                    authorization
+                })
+                .collect()
+        });
+        (url, handle)
+    }
+
+    fn jpeg(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        DynamicImage::new_rgb8(width, height)
+            .write_to(
+                &mut std::io::Cursor::new(&mut bytes),
+                image::ImageFormat::Jpeg,
+            )
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_http_snapshot_capture() -> Result {
+        let (url, server) = snapshot_server(jpeg(8, 6), 2);
+        let device_config = CameraDeviceConfig {
+            kind: CameraKind::HttpSnapshot,
+            username: Some("ada".to_string()),
+            password: Some("hunter2".to_string()),
+            rotate: Rotation::Quarter,
+            ..CameraDeviceConfig::new(url)
+        };
+        let burst = Burst {
+            frames: 2,
+            interval: Duration::ZERO,
+        };
+
+        let frames = try_capture_from_device(
+            &device_config,
+            &warmup(0, 0),
+            &Countdown {
+                delay: Duration::ZERO,
+                show: false,
+            },
+            &burst,
+        )?;
+
+        assert_eq!(frames.len(), 2);
+        assert_eq!((frames[0].width(), frames[0].height()), (6, 8));
+        // "ada:hunter2"
+        assert_eq!(
+            server.join().unwrap(),
+            vec![Some("Basic YWRhOmh1bnRlcjI=".to_string()); 2]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_unreachable_snapshot_camera_falls_back_to_the_next() -> Result {
+        let unreachable = {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+            format!("http://{}/snapshot.jpg", listener.local_addr()?)
+        };
+        let (url, server) = snapshot_server(jpeg(4, 4), 1);
+        let config = ClientConfig {
+            camera_devices: vec![
+                CameraDeviceConfig {
+                    kind: CameraKind::HttpSnapshot,
+                    ..CameraDeviceConfig::new(unreachable)
+                },
+                CameraDeviceConfig {
+                    kind: CameraKind::HttpSnapshot,
+                    ..CameraDeviceConfig::new(url)
+                },
+            ],
+            ..Default::default()
+        };
+
+        let image = capture_image(&config)?;
+
+        assert_eq!((image.width(), image.height()), (4, 4));
+        assert_eq!(server.join().unwrap(), [None]);
+        Ok(())
+    }
+
+    #[test_case("White Balance Temperature, Auto", "white_balance_temperature_auto" ; "v4l2 name")]
+    #[test_case("Exposure (Absolute)", "exposure_absolute" ; "parenthesised")]
+    #[test_case("exposure_absolute", "exposure_absolute" ; "already a key")]
+    #[test_case("Gain", "gain" ; "one word")]
+    fn test_control_key(name: &str, expected: &str) {
+        assert_eq!(control_key(name), expected);
+    }
+
+    #[test_case(ControlValueDescription::IntegerRange { min: 2800, max: 6500, value: 4000, step: 1, default: 4000 }, 4600, Ok(ControlValueSetter::Integer(4600)) ; "in range")]
+    #[test_case(ControlValueDescription::IntegerRange { min: 3, max: 2047, value: 250, step: 1, default: 250 }, 5000, Ok(ControlValueSetter::Integer(2047)) ; "clamped")]
+    #[test_case(ControlValueDescription::Boolean { value: true, default: true }, 0, Ok(ControlValueSetter::Boolean(false)) ; "boolean")]
+    #[test_case(ControlValueDescription::Enum { value: 3, possible: vec![1, 3], default: 3 }, 1, Ok(ControlValueSetter::EnumValue(1)) ; "menu")]
+    #[test_case(ControlValueDescription::Enum { value: 3, possible: vec![1, 3], default: 3 }, 2, Err("expected one of [1, 3]".to_string()) ; "menu without the value")]
+    fn test_control_setter(
+        description: ControlValueDescription,
+        requested: i64,
+        expected: std::result::Result<ControlValueSetter, String>,
+    ) {
+        assert_eq!(control_setter(&description, requested), expected);
+    }
+
+    /// A sysfs `video4linux` directory with a USB camera of each `(name, serial)`, in
+    /// index order, each with a metadata node after its capture node.
+    fn sysfs(cameras: &[(&str, &str)]) -> Result<tempfile::TempDir> {
+        let dir = tempfile::tempdir()?;
+        for (i, (name, serial)) in cameras.iter().enumerate() {
+            let usb = dir.path().join(format!("usb/1-{i}"));
+            std::fs::create_dir_all(usb.join("interface"))?;
+            std::fs::write(usb.join("serial"), format!("{serial}\n"))?;
+            for node in 0..2 {
+                let video = dir.path().join(format!("video{}", i * 2 + node));
+                std::fs::create_dir(&video)?;
+                std::fs::write(video.join("name"), format!("{name}\n"))?;
+                std::fs::write(video.join("index"), format!("{node}\n"))?;
+                std::os::unix::fs::symlink(usb.join("interface"), video.join("device"))?;
+            }
+        }
+        Ok(dir)
+    }
+
+    #[test_case("2", CameraIndex::Index(2) ; "numeric")]
+    #[test_case("/dev/video3", CameraIndex::Index(3) ; "dev path")]
+    #[test_case("rtsp://cam.local/stream", CameraIndex::String("rtsp://cam.local/stream".to_string()) ; "device string")]
+    #[test_case("name:brio", CameraIndex::Index(2) ; "name substring")]
+    #[test_case("name:Elgato Cam Link", CameraIndex::Index(0) ; "full name")]
+    #[test_case("serial:abc", CameraIndex::Index(2) ; "serial")]
+    fn test_parse_camera_device(device: &str, expected: CameraIndex) -> Result {
+        let sysfs = sysfs(&[("Elgato Cam Link 4K", "0001"), ("Logitech BRIO", "ABC123")])?;
+
+        assert_eq!(parse_camera_device(device, sysfs.path())?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_camera_device_rejects() -> Result {
+        let sysfs = sysfs(&[("Logitech BRIO", "ABC123"), ("Logitech BRIO", "DEF456")])?;
+
+        assert!(matches!(
+            parse_camera_device("/dev/camera", sysfs.path()),
+            Err(Error::CameraInvalidDevicePath { .. })
+        ));
+        assert!(matches!(
+            parse_camera_device("name:C920", sysfs.path()),
+            Err(Error::CameraNotFound { .. })
+        ));
+        assert!(matches!(
+            parse_camera_device("serial:ABC123", Path::new("/nonexistent")),
+            Err(Error::CameraNotFound { .. })
+        ));
+        match parse_camera_device("name:logitech", sysfs.path()) {
+            Err(Error::CameraAmbiguous { candidates, .. }) => assert_eq!(
+                candidates,
+                ["/dev/video0 (Logitech BRIO)", "/dev/video2 (Logitech BRIO)"]
+            ),
+            other => panic!("expected an ambiguous match, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_device_node() -> Result {
+        let dev = tempfile::tempdir()?;
+        std::fs::write(dev.path().join("video2"), "")?;
+
+        assert_eq!(
+            device_node(&CameraIndex::Index(2), dev.path()),
+            Some(dev.path().join("video2").display().to_string())
+        );
+        assert_eq!(device_node(&CameraIndex::Index(0), dev.path()), None);
+        assert_eq!(
+            device_node(&CameraIndex::String("rtsp://cam".to_string()), dev.path()),
+            None
+        );
+        Ok(())
+    }
+}
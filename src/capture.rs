@@ -1,35 +1,96 @@
 //! Lolcommit capture and upload functionality.
 //!
-//! This module handles capturing webcam images and uploading them to the lolcommitsd server.
+//! This module handles capturing webcam images (or loading the configured `capture_source`
+//! still image) and uploading them to the lolcommitsd server, or in local mode
+//! (`mode = "local"` / `--local`) burning the chyron in and saving them to
+//! `local_images_dir` without a server.
 //!
 //! # Error Handling Requirements
 //!
 //! The following rules govern error handling for the upload client:
 //!
+//! - **Disabled** (`enabled = false`, usually in a repository's `.lolcommits.toml`): Log at
+//!   INFO level and exit 0 without touching the camera or server.
 //! - **Camera not available** (device does not exist): Exit with error.
-//! - **Camera busy** (device exists but in use): Exit with error, unless `--quiet` is passed.
-//!   With `--quiet`, log "camera busy" at INFO level and exit with return code 0.
+//! - **Capture source unusable** (`capture_source` / `--from-file` missing or not an image):
+//!   Exit with error.
+//! - **Camera busy** (device exists but in use): Retry per `camera_busy_retries`, then exit
+//!   with error, unless `--quiet` is passed. With `--quiet`, log "camera busy" at INFO level
+//!   and exit with return code 0.
 //! - **RUST_LOG**: When set, all logging should output at the appropriate level.
-//! - **Connection failure** (camera capture succeeds but cannot connect to server): Exit with error.
+//! - **Connection failure** (camera capture succeeds but cannot connect to server): Queue the
+//!   capture in the spool and exit 0, or exit with error when spooling is disabled
+//!   (`spool_max_entries = 0`) or the capture can't be written.
+//! - **Spooled captures**: Upload them oldest-first before every capture and on
+//!   `--flush-spool`, removing each once the server has it. A capture with an unreadable
+//!   sidecar or that the server rejects stays spooled with a warning and the flush moves on;
+//!   the flush stops at the first connection failure.
+//! - **Background upload** (`--background`): Spool the capture once the camera is released
+//!   and leave the upload, and the flush of older captures, to a detached
+//!   `lolcommits_upload --upload-spooled`, which logs to the journal. There is no duplicate
+//!   precheck. Whatever it can't upload stays spooled. Animations, and captures with
+//!   spooling disabled, are uploaded in the foreground instead.
+//! - **Server busy** (429 or 503 with Retry-After): Wait as asked, up to
+//!   `upload_max_retry_after_secs`, and retry up to `upload_retries` times.
+//! - **Transient failure** (can't connect, or any other 5xx): Retry up to `upload_retries`
+//!   times, waiting `upload_retry_base_ms` doubled for each retry, with jitter. A 4xx isn't
+//!   retried. All tries and these waits share `server_upload_timeout_secs`, and the final
+//!   error says how many attempts were made. Waits asked for with Retry-After don't count
+//!   against it.
+//! - **Over the upload limit**: A capture larger than `upload_max_dimension` whose PNG is
+//!   over the `max_upload_bytes` the server reports at `/api/config` is scaled down to
+//!   `upload_max_dimension` first. Any failure to get the limit uploads it as is.
+//! - **Payload too large** (413): Retry once with the image halved in size.
+//! - **Duplicate precheck** (`precheck_duplicates`): If the server already has the revision,
+//!   skip the camera and exit 0. Any failure of the check itself falls through to capturing.
+//! - **Duplicate upload** (server returns 409 with the duplicate revision error code): Report
+//!   that the revision is already captured and exit 0, or with error when
+//!   `fail_on_duplicate` is set. The same applies to a precheck hit.
 //! - **Upload error** (camera capture succeeds, connection succeeds, but server returns 4xx/5xx):
 //!   Log the error and exit with error.
+//! - **Server read-only** (server returns 503 with the read-only error code): Spool the
+//!   capture like an unreachable server's, reporting that the server is read-only. Without
+//!   a spool, report it and exit with error. Flushing the spool stops at a read-only server,
+//!   leaving the captures spooled.
 //! - **Upload success** (camera capture succeeds, server returns 2xx): Log the response body at
 //!   INFO level.
+//! - **Local mode**: Nothing is uploaded or spooled and background replacement is skipped,
+//!   its model lives on the server. A chyron or save failure exits with error.
+//! - **Waiting for processing** (`wait_for_processing` / `--wait`): Poll the upload's job
+//!   until the server has saved the image or failed to, and exit with error on failure or
+//!   after `wait_timeout_secs`. Servers that don't report a job are treated as success.
 
 use crate::{
-    camera, config,
+    animation,
+    api_error::ErrorBody,
+    camera, chyron, config,
     error::{Error, Result},
-    git,
+    git, image_metadata,
+    overrides::Overrides,
+    spool::{self, Spool},
+    storage,
 };
-use serde::Serialize;
+use image::DynamicImage;
+use image::codecs::png::{CompressionType, FilterType, PngEncoder};
+use reqwest::StatusCode;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use serde::{Deserialize, Serialize};
 use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 pub struct CaptureArgs {
     pub revision: String,
     pub force: bool,
+    /// Server-side processing overrides for this upload only.
+    pub overrides: Overrides,
+    /// Spool the capture for a detached upload instead of uploading it (`--background`).
+    pub background: bool,
+    /// Whether the chyron is burned in (`--chyron` / `--no-chyron`), over the config.
+    pub chyron: Option<bool>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct UploadMetadata {
     revision: String,
     message: String,
@@ -38,15 +99,112 @@ struct UploadMetadata {
     timestamp: String,
     repo_name: String,
     branch_name: String,
+    #[serde(default)]
+    author_name: String,
+    #[serde(default)]
+    author_email: String,
+    #[serde(default)]
+    breaking: bool,
+    #[serde(default)]
+    co_authors: Vec<String>,
     files_changed: u32,
     insertions: u32,
     deletions: u32,
     force: bool,
+    #[serde(default, skip_serializing_if = "Overrides::is_empty")]
+    processing_overrides: Overrides,
+    /// How long each frame of an animated capture is shown.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    frame_delay_ms: Option<u64>,
+    /// Whether the server burns the chyron in, over its config. `false` when it was burned
+    /// in here, so the server doesn't draw it again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    burned_in_chyron: Option<bool>,
 }
 
-pub fn capture_lolcommit(config: config::Config, args: CaptureArgs) -> Result<()> {
+impl UploadMetadata {
+    fn into_commit_metadata(self) -> git::CommitMetadata {
+        git::CommitMetadata {
+            path: PathBuf::new(),
+            revision: self.revision,
+            message: self.message,
+            commit_type: self.commit_type,
+            scope: self.scope,
+            timestamp: self.timestamp,
+            repo_name: self.repo_name,
+            branch_name: self.branch_name,
+            author_name: self.author_name,
+            author_email: self.author_email,
+            breaking: self.breaking,
+            co_authors: self.co_authors,
+            stats: git::DiffStats {
+                files_changed: self.files_changed,
+                insertions: self.insertions,
+                deletions: self.deletions,
+            },
+        }
+    }
+}
+
+/// What became of a capture.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// Capturing is disabled (`enabled = false`), so nothing was done.
+    Disabled,
+    Uploaded,
+    /// The server finished processing the upload, saving it as `filename`.
+    Processed {
+        filename: String,
+    },
+    /// The server already has this revision, so nothing was captured.
+    AlreadyCaptured {
+        filename: Option<String>,
+    },
+    /// Local mode saved the capture to `path`.
+    Saved {
+        path: PathBuf,
+    },
+    /// The server couldn't be reached, or is `read_only`, so the capture was queued at
+    /// `path` for later.
+    Spooled {
+        path: PathBuf,
+        read_only: bool,
+    },
+    /// The capture was queued at `path` for a detached upload (`--background`).
+    Queued {
+        path: PathBuf,
+    },
+}
+
+/// What a flush did with the spooled captures.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FlushReport {
+    pub uploaded: usize,
+    /// Unreadable or rejected by the server, and left in the spool.
+    pub skipped: usize,
+    pub remaining: usize,
+}
+
+pub fn capture_lolcommit(config: config::Config, args: CaptureArgs) -> Result<Outcome> {
     // Get client config, defaulting if not present in config file
     let client_config = config.client.clone().unwrap_or_default();
+    if !client_config.enabled {
+        tracing::info!("lolcommits is disabled for this repository, not capturing");
+        return Ok(Outcome::Disabled);
+    }
+
+    let background = args.background
+        && client_config.mode == config::CaptureMode::Server
+        && can_upload_in_background(&client_config);
+
+    // Older captures go first, and an unreachable server is spooled into below anyway. A
+    // background upload flushes them once it's done instead.
+    if client_config.mode == config::CaptureMode::Server
+        && !background
+        && let Err(e) = flush_spool(&client_config)
+    {
+        tracing::warn!(error = %e, "Failed to upload spooled captures");
+    }
 
     let repo = git::open_repo()?;
 
@@ -59,7 +217,8 @@ pub fn capture_lolcommit(config: config::Config, args: CaptureArgs) -> Result<()
 
     let repo_name = git::get_repo_name(&repo)?;
     let branch_name = git::get_branch_name(&repo)?;
-    let stats = git::get_diff_stats(&revision)?;
+    let (author_name, author_email) = git::get_commit_author(&repo, &revision)?;
+    let stats = git::get_diff_stats(&revision, &client_config.stats_exclude)?;
 
     tracing::info!(
         repo_name = %repo_name,
@@ -70,17 +229,14 @@ pub fn capture_lolcommit(config: config::Config, args: CaptureArgs) -> Result<()
         "Got git info"
     );
 
-    // Capture image from webcam
-    let image = camera::capture_image(&client_config)?;
-    tracing::info!("Captured image from webcam");
-
     // Parse commit message
     let commit_type = git::parse_commit_type(&message);
+    let breaking = git::is_breaking_change(&message);
+    let co_authors = git::parse_co_authors(&message);
     let first_line = message.lines().next().unwrap_or(&message);
     let scope = git::parse_commit_scope(first_line);
-    let timestamp = chrono::Local::now()
-        .format(crate::TIMESTAMP_FORMAT)
-        .to_string();
+    // The commit's own time, so a capture of an older revision is dated when it was committed
+    let timestamp = git::get_commit_time(&repo, &revision)?.to_rfc3339();
 
     // Create metadata for upload
     let metadata = UploadMetadata {
@@ -91,30 +247,714 @@ pub fn capture_lolcommit(config: config::Config, args: CaptureArgs) -> Result<()
         timestamp,
         repo_name,
         branch_name,
+        author_name,
+        author_email,
+        breaking,
+        co_authors,
         files_changed: stats.files_changed,
         insertions: stats.insertions,
         deletions: stats.deletions,
         force: args.force,
+        processing_overrides: args.overrides,
+        frame_delay_ms: client_config.animate.then(|| {
+            camera::Burst::from_config(&client_config)
+                .interval
+                .as_millis() as u64
+        }),
+        burned_in_chyron: match client_config.chyron_rendering {
+            config::ChyronRendering::Server => args.chyron,
+            config::ChyronRendering::Client => Some(false),
+        },
+    };
+    let chyron = (args.chyron != Some(false)).then(|| config.burned_in_chyron.unwrap_or_default());
+
+    if client_config.mode == config::CaptureMode::Local {
+        if client_config.animate {
+            tracing::warn!("Animated captures are processed by the server, saving a still");
+        }
+        return capture_and_save(
+            &client_config,
+            metadata,
+            || capture_frame(&client_config),
+            |image, commit_metadata| match &chyron {
+                Some(chyron) => chyron::burn_in_chyron(chyron, image, commit_metadata),
+                None => Ok(image),
+            },
+        );
+    }
+
+    let chyron =
+        chyron.filter(|_| client_config.chyron_rendering == config::ChyronRendering::Client);
+    let render = |frames: Vec<DynamicImage>, commit_metadata: &git::CommitMetadata| match &chyron {
+        Some(chyron) => burn_in_last(chyron, frames, commit_metadata),
+        None => Ok(frames),
+    };
+    if background {
+        return capture_and_spool(
+            &client_config,
+            metadata,
+            || capture_frames(&client_config),
+            render,
+        );
+    }
+    capture_and_upload(
+        &client_config,
+        metadata,
+        || capture_frames(&client_config),
+        render,
+    )
+}
+
+/// Whether captures can be handed to a detached upload, which goes through the spool.
+/// Otherwise they're uploaded in the foreground after all.
+fn can_upload_in_background(config: &config::ClientConfig) -> bool {
+    if Spool::from_config(config).is_none() {
+        tracing::warn!("Spooling is off (spool_max_entries = 0), uploading in the foreground");
+        return false;
+    }
+    if config.animate {
+        tracing::warn!("The spool holds stills only, uploading the animation in the foreground");
+        return false;
+    }
+    true
+}
+
+/// Burn the chyron into the last of `frames`, where the server draws it by default.
+fn burn_in_last(
+    chyron: &config::BurnedInChyronConfig,
+    mut frames: Vec<DynamicImage>,
+    commit_metadata: &git::CommitMetadata,
+) -> Result<Vec<DynamicImage>> {
+    if let Some(last) = frames.pop() {
+        frames.push(chyron::burn_in_chyron(chyron, last, commit_metadata)?);
+    }
+    Ok(frames)
+}
+
+/// Save a snapshot from `capture` to `local_images_dir` after `render` draws the chyron
+/// on it, named like the server names its images so it can be imported into a gallery.
+fn capture_and_save(
+    config: &config::ClientConfig,
+    metadata: UploadMetadata,
+    capture: impl FnOnce() -> Result<DynamicImage>,
+    render: impl FnOnce(DynamicImage, &git::CommitMetadata) -> Result<DynamicImage>,
+) -> Result<Outcome> {
+    if !metadata.processing_overrides.is_empty() {
+        tracing::warn!(overrides = ?metadata.processing_overrides, "Processing overrides are ignored in local mode");
+    }
+
+    let taken = image_metadata::taken_at(&metadata.timestamp);
+    let filename =
+        image_metadata::output_filename(&metadata.repo_name, &metadata.revision, taken, "png")?;
+    let commit_metadata = git::CommitMetadata {
+        timestamp: crate::format_timestamp(taken),
+        ..metadata.into_commit_metadata()
+    };
+    let image = capture()?;
+    save_raw(config, &filename, std::slice::from_ref(&image));
+    let image = render(image, &commit_metadata)?;
+
+    let path = storage::atomic_save(
+        Path::new(&config.local_images_dir),
+        &filename,
+        |temp_path| image_metadata::save_png_with_metadata(&image, temp_path, &commit_metadata),
+    )?;
+    tracing::info!(path = %path.display(), "Saved lolcommit locally");
+    Ok(Outcome::Saved { path })
+}
+
+/// Upload the frames from `capture` once `render` has drawn on them (if anything), unless
+/// the precheck finds the server already has the revision, in which case the camera is
+/// never touched.
+fn capture_and_upload(
+    config: &config::ClientConfig,
+    metadata: UploadMetadata,
+    capture: impl FnOnce() -> Result<Vec<DynamicImage>>,
+    render: impl FnOnce(Vec<DynamicImage>, &git::CommitMetadata) -> Result<Vec<DynamicImage>>,
+) -> Result<Outcome> {
+    if config.precheck_duplicates
+        && !metadata.force
+        && let Some(existing) = precheck(config, &metadata.repo_name, &metadata.revision)
+        && existing.exists
+    {
+        tracing::info!(revision = %metadata.revision, filename = ?existing.filename, "Revision already captured, skipping");
+        if config.fail_on_duplicate {
+            return Err(Error::AlreadyCaptured {
+                revision: metadata.revision,
+            });
+        }
+        return Ok(Outcome::AlreadyCaptured {
+            filename: existing.filename,
+        });
+    }
+
+    let revision = metadata.revision.clone();
+    let frames = capture_rendered(config, &metadata, capture, render)?;
+    // The spool holds a single image, so an animation is queued as its last frame
+    let spooled = match (Spool::from_config(config), frames.last()) {
+        (Some(spool), Some(image)) => {
+            Some((spool, image.clone(), serde_json::to_string(&metadata)?))
+        }
+        _ => None,
+    };
+
+    let outcome = match (upload_to_server(config, frames, metadata), spooled) {
+        (
+            Err(e @ (Error::ServerConnectionFailed { .. } | Error::ServerReadOnly { .. })),
+            Some((spool, image, metadata_json)),
+        ) => match encode_png(&image).and_then(|png| spool.push(&png, &metadata_json, &revision)) {
+            Ok(entry) => {
+                let read_only = matches!(e, Error::ServerReadOnly { .. });
+                if read_only {
+                    tracing::warn!(path = %entry.image.display(), "Server is read-only, spooled capture");
+                } else {
+                    tracing::warn!(error = %e, path = %entry.image.display(), "Server unreachable, spooled capture");
+                }
+                Outcome::Spooled {
+                    path: entry.image,
+                    read_only,
+                }
+            }
+            Err(spool_error) => {
+                tracing::error!(error = %spool_error, spool_dir = %spool.dir().display(), "Failed to spool capture");
+                return Err(e);
+            }
+        },
+        (result, _) => result?,
+    };
+
+    match outcome {
+        Outcome::AlreadyCaptured { .. } if config.fail_on_duplicate => {
+            Err(Error::AlreadyCaptured { revision })
+        }
+        outcome => Ok(outcome),
+    }
+}
+
+/// Capture the frames from `capture`, then spool them for the detached upload started by
+/// `--background`, so the commit doesn't wait on encoding or the server. The frame is
+/// spooled as a stored (uncompressed) PNG, which costs no more than copying its pixels;
+/// the detached upload encodes it properly. The camera is released by the time this
+/// returns.
+fn capture_and_spool(
+    config: &config::ClientConfig,
+    metadata: UploadMetadata,
+    capture: impl FnOnce() -> Result<Vec<DynamicImage>>,
+    render: impl FnOnce(Vec<DynamicImage>, &git::CommitMetadata) -> Result<Vec<DynamicImage>>,
+) -> Result<Outcome> {
+    let Some(spool) = Spool::from_config(config) else {
+        return capture_and_upload(config, metadata, capture, render);
+    };
+    let frames = capture_rendered(config, &metadata, capture, render)?;
+    let Some(image) = frames.last() else {
+        return Err(std::io::Error::other("capture returned no frames").into());
+    };
+    let entry = spool.push(
+        &encode_stored_png(image)?,
+        &serde_json::to_string(&metadata)?,
+        &metadata.revision,
+    )?;
+    Ok(Outcome::Queued { path: entry.image })
+}
+
+/// Frames from `capture`, kept raw if `save_raw_dir` asks for it, once `render` has drawn
+/// on them.
+fn capture_rendered(
+    config: &config::ClientConfig,
+    metadata: &UploadMetadata,
+    capture: impl FnOnce() -> Result<Vec<DynamicImage>>,
+    render: impl FnOnce(Vec<DynamicImage>, &git::CommitMetadata) -> Result<Vec<DynamicImage>>,
+) -> Result<Vec<DynamicImage>> {
+    let frames = capture()?;
+    let taken = image_metadata::taken_at(&metadata.timestamp);
+    save_raw(
+        config,
+        &image_metadata::output_filename(&metadata.repo_name, &metadata.revision, taken, "png")?,
+        &frames,
+    );
+    let commit_metadata = git::CommitMetadata {
+        timestamp: crate::format_timestamp(taken),
+        ..metadata.clone().into_commit_metadata()
+    };
+    render(frames, &commit_metadata)
+}
+
+/// Keep `frames` as captured in `save_raw_dir` as `filename`, or numbered after it for an
+/// animation, for telling a bad camera frame from bad processing. Failing to is only
+/// logged, as the capture itself is fine.
+fn save_raw(config: &config::ClientConfig, filename: &str, frames: &[DynamicImage]) {
+    let Some(dir) = &config.save_raw_dir else {
+        return;
+    };
+    let stem = filename.strip_suffix(".png").unwrap_or(filename);
+    for (i, frame) in frames.iter().enumerate() {
+        let name = match frames.len() {
+            1 => filename.to_string(),
+            _ => format!("{stem}-{i:02}.png"),
+        };
+        let saved = storage::atomic_save(Path::new(dir), &name, |path| {
+            frame.save_with_format(path, image::ImageFormat::Png)?;
+            Ok(())
+        });
+        match saved {
+            Ok(path) => tracing::info!(path = %path.display(), "Saved raw capture"),
+            Err(e) => tracing::warn!(dir, error = %e, "Failed to save raw capture"),
+        }
+    }
+}
+
+/// Upload spooled captures oldest-first, removing each once the server has it. Stops
+/// with the connection error when the server is still unreachable, and quietly when it's
+/// read-only, leaving the rest spooled.
+pub fn flush_spool(config: &config::ClientConfig) -> Result<FlushReport> {
+    let Some(spool) = Spool::from_config(config) else {
+        return Ok(FlushReport::default());
+    };
+    spool.prune();
+    let entries = spool.entries();
+    if entries.is_empty() {
+        return Ok(FlushReport::default());
+    }
+    tracing::info!(count = entries.len(), "Uploading spooled captures");
+
+    // Don't hold up the capture that triggered the flush waiting on old ones
+    let config = config::ClientConfig {
+        wait_for_processing: false,
+        ..config.clone()
+    };
+    let mut report = FlushReport::default();
+    for entry in &entries {
+        let (metadata, image) = match read_spooled(entry) {
+            Ok(spooled) => spooled,
+            Err(e) => {
+                tracing::warn!(path = %entry.metadata.display(), error = %e, "Skipping unreadable spooled capture");
+                report.skipped += 1;
+                continue;
+            }
+        };
+
+        match upload_to_server(&config, vec![image], metadata) {
+            Ok(_) => {
+                entry.remove();
+                report.uploaded += 1;
+            }
+            Err(e @ Error::ServerConnectionFailed { .. }) => {
+                tracing::warn!(
+                    uploaded = report.uploaded,
+                    remaining = spool.entries().len(),
+                    "Server still unreachable, keeping spooled captures"
+                );
+                return Err(e);
+            }
+            Err(Error::ServerReadOnly { .. }) => {
+                tracing::info!(
+                    uploaded = report.uploaded,
+                    "Server is read-only, keeping spooled captures"
+                );
+                break;
+            }
+            Err(e) => {
+                tracing::warn!(path = %entry.image.display(), error = %e, "Spooled capture rejected, keeping it");
+                report.skipped += 1;
+            }
+        }
+    }
+
+    report.remaining = spool.entries().len();
+    Ok(report)
+}
+
+/// Upload the capture spooled at `image` by `--background`, then any older ones. It stays
+/// spooled for the next capture or flush if the server can't be reached, is read-only or
+/// rejects it.
+pub fn upload_spooled(config: &config::ClientConfig, image: &Path) -> Result<Outcome> {
+    let entry = spool::Entry::for_image(image.to_path_buf());
+    let (metadata, frame) = read_spooled(&entry)?;
+
+    let outcome = match upload_to_server(config, vec![frame], metadata) {
+        Ok(outcome) => outcome,
+        Err(e @ Error::ServerConnectionFailed { .. }) => {
+            tracing::warn!(error = %e, path = %entry.image.display(), "Server unreachable, capture stays spooled");
+            return Ok(Outcome::Spooled {
+                path: entry.image,
+                read_only: false,
+            });
+        }
+        Err(Error::ServerReadOnly { .. }) => {
+            tracing::warn!(path = %entry.image.display(), "Server is read-only, capture stays spooled");
+            return Ok(Outcome::Spooled {
+                path: entry.image,
+                read_only: true,
+            });
+        }
+        Err(e) => return Err(e),
+    };
+    entry.remove();
+
+    match flush_spool(config) {
+        Ok(report) if report.uploaded > 0 => {
+            tracing::info!(?report, "Uploaded older spooled captures")
+        }
+        Ok(_) => {}
+        Err(e) => tracing::warn!(error = %e, "Failed to upload older spooled captures"),
+    }
+    Ok(outcome)
+}
+
+/// The upload metadata and image of a spooled capture.
+fn read_spooled(entry: &spool::Entry) -> Result<(UploadMetadata, DynamicImage)> {
+    let metadata = serde_json::from_str(&std::fs::read_to_string(&entry.metadata)?)?;
+    Ok((metadata, load_still_image(&entry.image)?))
+}
+
+#[derive(Debug, Deserialize)]
+struct ExistsResponse {
+    exists: bool,
+    #[serde(default)]
+    filename: Option<String>,
+}
+
+/// Ask the server whether it already has `revision`. Any failure gives `None` so the
+/// capture goes ahead as if there were no precheck.
+fn precheck(
+    config: &config::ClientConfig,
+    repo_name: &str,
+    revision: &str,
+) -> Option<ExistsResponse> {
+    let url = reqwest::Url::parse_with_params(
+        &format!("{}/api/exists", config.server_url),
+        &[("repo", repo_name), ("revision", revision)],
+    )
+    .ok()?;
+
+    let body = reqwest::blocking::Client::builder()
+        .timeout(PRECHECK_TIMEOUT)
+        .danger_accept_invalid_certs(config.tls_skip_verify)
+        .build()
+        .and_then(|client| client.get(url).send())
+        .and_then(|response| response.error_for_status())
+        .and_then(|response| response.text());
+
+    match body.map(|body| serde_json::from_str(&body)) {
+        Ok(Ok(response)) => Some(response),
+        Ok(Err(e)) => {
+            tracing::debug!(error = %e, "Unexpected duplicate precheck response, capturing anyway");
+            None
+        }
+        Err(e) => {
+            tracing::debug!(error = %e, "Duplicate precheck failed, capturing anyway");
+            None
+        }
+    }
+}
+
+/// Take the snapshot: the configured `capture_source` image if there is one, otherwise
+/// the webcam. Either way the rest of the upload is identical.
+fn capture_frame(config: &config::ClientConfig) -> Result<DynamicImage> {
+    match &config.capture_source {
+        Some(path) => load_capture_source(config, path),
+        None => {
+            let image = camera::capture_image(config)?;
+            tracing::info!("Captured image from webcam");
+            Ok(image)
+        }
+    }
+}
+
+/// The frames to upload: a burst from the webcam scaled down to `animate_max_width`
+/// with `animate`, otherwise the single snapshot from [`capture_frame`].
+fn capture_frames(config: &config::ClientConfig) -> Result<Vec<DynamicImage>> {
+    if !config.animate {
+        return Ok(vec![capture_frame(config)?]);
+    }
+    if config.capture_source.is_some() {
+        tracing::warn!(
+            "Animated captures need the webcam, uploading the capture source as a still"
+        );
+        return Ok(vec![capture_frame(config)?]);
+    }
+
+    let frames = camera::capture_frames(config, &camera::Burst::from_config(config))?;
+    tracing::info!(frames = frames.len(), "Captured animation from webcam");
+    Ok(frames
+        .into_iter()
+        .map(|frame| animation::fit_width(frame, config.animate_max_width))
+        .collect())
+}
+
+/// Load `capture_source` (or `--image`) in place of a webcam capture, refusing files
+/// over `capture_source_max_bytes` before decoding them and scaling it down to
+/// `capture_source_max_dimension`.
+pub(crate) fn load_capture_source(
+    config: &config::ClientConfig,
+    path: &Path,
+) -> Result<DynamicImage> {
+    if let Ok(metadata) = std::fs::metadata(path)
+        && metadata.len() > config.capture_source_max_bytes
+    {
+        return Err(Error::CaptureSourceTooLarge {
+            path: path.to_path_buf(),
+            size: metadata.len(),
+            limit: config.capture_source_max_bytes,
+        });
+    }
+    let mut image = load_still_image(path)?;
+    tracing::info!(path = %path.display(), width = image.width(), height = image.height(), "Loaded image from capture source");
+
+    if let Some(max_dimension) = config.capture_source_max_dimension
+        && image.width().max(image.height()) > max_dimension
+    {
+        image = image.resize(
+            max_dimension,
+            max_dimension,
+            image::imageops::FilterType::Triangle,
+        );
+        tracing::info!(
+            width = image.width(),
+            height = image.height(),
+            "Scaled capture source down"
+        );
+    }
+    Ok(image)
+}
+
+/// Load a still image in place of a webcam capture, as RGB like camera frames. JPEGs are
+/// turned upright according to their EXIF orientation.
+pub fn load_still_image(path: &Path) -> Result<DynamicImage> {
+    if !path.is_file() {
+        return Err(Error::CaptureSourceNotFound {
+            path: path.to_path_buf(),
+        });
+    }
+
+    let undecodable = |source| Error::CaptureSourceUndecodable {
+        path: path.to_path_buf(),
+        source,
     };
+    let bytes = std::fs::read(path)?;
+    let image = crate::orientation::load_from_memory(&bytes).map_err(undecodable)?;
+
+    Ok(DynamicImage::ImageRgb8(image.to_rgb8()))
+}
+
+/// The precheck only saves a camera activation, so don't wait long for it.
+const PRECHECK_TIMEOUT: Duration = Duration::from_secs(5);
 
-    // Encode image to PNG bytes
+pub(crate) fn encode_png(image: &DynamicImage) -> Result<Vec<u8>> {
     let mut png_bytes = Vec::new();
     image
         .write_to(&mut Cursor::new(&mut png_bytes), image::ImageFormat::Png)
         .map_err(|e| std::io::Error::other(e.to_string()))?;
-    tracing::debug!(bytes = png_bytes.len(), "Encoded image to PNG");
+    tracing::debug!(
+        bytes = png_bytes.len(),
+        width = image.width(),
+        height = image.height(),
+        "Encoded image to PNG"
+    );
+    Ok(png_bytes)
+}
+
+/// `image` as a PNG without compression or filtering, for handing over raw pixels in a
+/// format the spool and [`load_still_image`] already read.
+fn encode_stored_png(image: &DynamicImage) -> Result<Vec<u8>> {
+    let mut png_bytes = Vec::new();
+    let encoder = PngEncoder::new_with_quality(
+        &mut png_bytes,
+        CompressionType::Uncompressed,
+        FilterType::NoFilter,
+    );
+    image
+        .write_with_encoder(encoder)
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
+    Ok(png_bytes)
+}
+
+/// What to do once the server has answered an upload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UploadAction {
+    /// Done: the upload succeeded, or failed in a way retrying won't fix.
+    Finish,
+    /// The server is busy, send the same upload again after waiting.
+    RetryAfter(Duration),
+    /// The server failed, send the same upload again after backing off.
+    Backoff,
+    /// The image was too large, send a smaller one.
+    Shrink,
+}
+
+/// How the client cooperates with a server that pushes back.
+#[derive(Debug, Clone, Copy)]
+struct RetryPolicy {
+    retries: u32,
+    max_wait: Duration,
+    backoff_base: Duration,
+}
+
+impl RetryPolicy {
+    fn from_config(config: &config::ClientConfig) -> Self {
+        Self {
+            retries: config.upload_retries,
+            max_wait: Duration::from_secs(config.upload_max_retry_after_secs),
+            backoff_base: Duration::from_millis(config.upload_retry_base_ms),
+        }
+    }
+
+    /// How long to back off before the retry after the `attempt`th (0 for the first
+    /// send): the base doubled for each retry so far, then scaled by `jitter` (0 to 1)
+    /// into its upper half so clients that failed together don't retry together.
+    fn backoff(&self, attempt: u32, jitter: f64) -> Duration {
+        let delay = self.backoff_base.saturating_mul(1 << attempt.min(16));
+        delay.mul_f64(0.5 + jitter.clamp(0.0, 1.0) / 2.0)
+    }
+
+    /// Decide what to do about a response to the `attempt`th retry (0 for the first
+    /// send). 429 and 503 are retried when the server says when to come back, other
+    /// 5xx after backing off, and a 413 gets one smaller image, `shrunk` once that has
+    /// been sent.
+    fn action(
+        &self,
+        status: StatusCode,
+        headers: &HeaderMap,
+        attempt: u32,
+        shrunk: bool,
+    ) -> UploadAction {
+        match status {
+            StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE
+                if attempt < self.retries =>
+            {
+                match headers
+                    .get(RETRY_AFTER)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| parse_retry_after(value, chrono::Utc::now()))
+                {
+                    Some(wait) => UploadAction::RetryAfter(wait.min(self.max_wait)),
+                    None if status.is_server_error() => UploadAction::Backoff,
+                    None => UploadAction::Finish,
+                }
+            }
+            status if status.is_server_error() && attempt < self.retries => UploadAction::Backoff,
+            StatusCode::PAYLOAD_TOO_LARGE if !shrunk => UploadAction::Shrink,
+            _ => UploadAction::Finish,
+        }
+    }
+}
+
+/// A number from 0 to 1 that differs from call to call, for jittering retries.
+fn jitter() -> f64 {
+    use std::hash::{BuildHasher, Hasher};
+    let random = std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish();
+    (random >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Parse a Retry-After value, either delay-seconds or an HTTP date.
+fn parse_retry_after(value: &str, now: chrono::DateTime<chrono::Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    // A date in the past means "now"
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (at.with_timezone(&chrono::Utc) - now)
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
+
+#[derive(Debug, Deserialize)]
+struct ServerConfigResponse {
+    #[serde(default)]
+    max_upload_bytes: Option<usize>,
+}
+
+/// The server's `max_upload_bytes`, `None` when it can't be had, as from servers that
+/// predate the limit being reported.
+fn server_upload_limit(config: &config::ClientConfig) -> Option<usize> {
+    let body = reqwest::blocking::Client::builder()
+        .timeout(PRECHECK_TIMEOUT)
+        .danger_accept_invalid_certs(config.tls_skip_verify)
+        .build()
+        .and_then(|client| {
+            client
+                .get(format!("{}/api/config", config.server_url))
+                .send()
+        })
+        .and_then(|response| response.error_for_status())
+        .and_then(|response| response.text());
+
+    match body.map(|body| serde_json::from_str::<ServerConfigResponse>(&body)) {
+        Ok(Ok(response)) => response.max_upload_bytes,
+        Ok(Err(e)) => {
+            tracing::debug!(error = %e, "Unexpected server config, not checking the upload size");
+            None
+        }
+        Err(e) => {
+            tracing::debug!(error = %e, "Failed to get the server config, not checking the upload size");
+            None
+        }
+    }
+}
+
+/// Scale `frames` down to `upload_max_dimension` when they encode larger than the server
+/// accepts. Only frames already bigger than that could shrink, so the server is asked
+/// for its limit only then.
+fn fit_upload_limit(
+    config: &config::ClientConfig,
+    frames: Vec<DynamicImage>,
+    frame_bytes: Vec<Vec<u8>>,
+) -> Result<(Vec<DynamicImage>, Vec<Vec<u8>>)> {
+    let max_dimension = config.upload_max_dimension;
+    if !frames
+        .iter()
+        .any(|frame| frame.width().max(frame.height()) > max_dimension)
+    {
+        return Ok((frames, frame_bytes));
+    }
+    let size: usize = frame_bytes.iter().map(Vec::len).sum();
+    let Some(limit) = server_upload_limit(config).filter(|&limit| size > limit) else {
+        return Ok((frames, frame_bytes));
+    };
 
-    // Upload to server
-    upload_to_server(&client_config, png_bytes, metadata)?;
+    let frames: Vec<_> = frames
+        .iter()
+        .map(|frame| {
+            frame.resize(
+                max_dimension,
+                max_dimension,
+                image::imageops::FilterType::Triangle,
+            )
+        })
+        .collect();
+    let frame_bytes = frames.iter().map(encode_png).collect::<Result<Vec<_>>>()?;
+    tracing::info!(
+        limit,
+        size,
+        scaled_size = frame_bytes.iter().map(Vec::len).sum::<usize>(),
+        width = frames[0].width(),
+        height = frames[0].height(),
+        "Capture over the server's upload limit, scaled it down"
+    );
+    Ok((frames, frame_bytes))
+}
 
-    Ok(())
+/// Halve the image for servers that reject the full-size upload as too large.
+fn shrink(image: &DynamicImage) -> DynamicImage {
+    image.resize(
+        (image.width() / 2).max(1),
+        (image.height() / 2).max(1),
+        image::imageops::FilterType::Triangle,
+    )
 }
 
+/// Upload `frames` as one `image` part each, in order; several make an animation.
 fn upload_to_server(
     config: &config::ClientConfig,
-    image_bytes: Vec<u8>,
+    frames: Vec<DynamicImage>,
     metadata: UploadMetadata,
-) -> Result<()> {
+) -> Result<Outcome> {
     let url = format!("{}/api/upload", config.server_url);
     tracing::info!(url = %url, "Uploading to server");
 
@@ -122,49 +962,1606 @@ fn upload_to_server(
         .timeout(std::time::Duration::from_secs(
             config.server_upload_timeout_secs,
         ))
+        .danger_accept_invalid_certs(config.tls_skip_verify)
         .build()?;
 
     let metadata_json = serde_json::to_string(&metadata)?;
+    let policy = RetryPolicy::from_config(config);
+    let frame_bytes = frames.iter().map(encode_png).collect::<Result<Vec<_>>>()?;
+    let (mut frames, mut frame_bytes) = fit_upload_limit(config, frames, frame_bytes)?;
+    // Retries share the upload timeout rather than each getting their own. Waiting as
+    // the server asked doesn't count, as upload_max_retry_after_secs already limits it
+    let budget = Duration::from_secs(config.server_upload_timeout_secs);
+    let started = std::time::Instant::now();
+    let mut waited_as_asked = Duration::ZERO;
+    let mut attempt = 0;
+    let mut attempts = 0;
+    let mut shrunk = false;
 
-    let form = reqwest::blocking::multipart::Form::new()
-        .part(
+    let (status, body) = loop {
+        let mut form = reqwest::blocking::multipart::Form::new().part(
             "metadata",
-            reqwest::blocking::multipart::Part::text(metadata_json).mime_str("application/json")?,
-        )
-        .part(
-            "image",
-            reqwest::blocking::multipart::Part::bytes(image_bytes)
-                .file_name("image.png")
-                .mime_str("image/png")?,
+            reqwest::blocking::multipart::Part::text(metadata_json.clone())
+                .mime_str("application/json")?,
         );
+        for (index, bytes) in frame_bytes.iter().enumerate() {
+            let file_name = match frame_bytes.len() {
+                1 => "image.png".to_string(),
+                _ => format!("frame-{index}.png"),
+            };
+            form = form.part(
+                "image",
+                reqwest::blocking::multipart::Part::bytes(bytes.clone())
+                    .file_name(file_name)
+                    .mime_str("image/png")?,
+            );
+        }
 
-    let response =
-        client
+        attempts += 1;
+        let sent = client
             .post(&url)
+            .timeout(budget.saturating_sub(started.elapsed() - waited_as_asked))
             .multipart(form)
-            .send()
-            .map_err(|e| Error::ServerConnectionFailed {
-                url: url.clone(),
-                source: e,
-            })?;
+            .send();
+        let response = match sent {
+            Ok(response) => response,
+            Err(e) => {
+                let wait = policy.backoff(attempt, jitter());
+                let spent = started.elapsed() - waited_as_asked;
+                if attempt >= policy.retries || spent + wait >= budget {
+                    return Err(Error::ServerConnectionFailed {
+                        url,
+                        source: e,
+                        attempts,
+                    });
+                }
+                attempt += 1;
+                tracing::warn!(error = %e, wait_ms = wait.as_millis() as u64, attempt, "Couldn't reach the server, retrying upload");
+                std::thread::sleep(wait);
+                continue;
+            }
+        };
 
-    let status = response.status();
-    let body = response
-        .text()
-        .unwrap_or_else(|_| "Unknown response".to_string());
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response
+            .text()
+            .unwrap_or_else(|_| "Unknown response".to_string());
+        // Read-only lasts until an admin lifts it, so isn't worth retrying
+        let action = if is_read_only_response(status.as_u16(), &body) {
+            UploadAction::Finish
+        } else {
+            policy.action(status, &headers, attempt, shrunk)
+        };
+        // How much of the budget the retry would take, waiting as asked takes none
+        let budgeted_wait = match action {
+            UploadAction::RetryAfter(_) => Some(Duration::ZERO),
+            UploadAction::Backoff => Some(policy.backoff(attempt, jitter())),
+            _ => None,
+        };
+        if let Some(wait) = budgeted_wait
+            && started.elapsed() - waited_as_asked + wait >= budget
+        {
+            tracing::warn!(status = %status, attempts, "Out of time to retry upload");
+            break (status, body);
+        }
+
+        match action {
+            UploadAction::Finish => break (status, body),
+            UploadAction::RetryAfter(wait) => {
+                attempt += 1;
+                tracing::warn!(status = %status, wait_secs = wait.as_secs_f32(), attempt, "Server busy, retrying upload");
+                std::thread::sleep(wait);
+                waited_as_asked += wait;
+            }
+            UploadAction::Backoff => {
+                let wait = budgeted_wait.unwrap_or_default();
+                attempt += 1;
+                tracing::warn!(status = %status, wait_ms = wait.as_millis() as u64, attempt, "Server error, retrying upload");
+                std::thread::sleep(wait);
+            }
+            UploadAction::Shrink => {
+                shrunk = true;
+                frames = frames.iter().map(shrink).collect();
+                frame_bytes = frames.iter().map(encode_png).collect::<Result<Vec<_>>>()?;
+                tracing::warn!(
+                    width = frames[0].width(),
+                    height = frames[0].height(),
+                    frames = frames.len(),
+                    "Upload too large, retrying with a smaller image"
+                );
+            }
+        }
+    };
 
     if status.is_success() {
-        let message = serde_json::from_str::<serde_json::Value>(&body)
-            .ok()
-            .and_then(|v| v.get("message").and_then(|m| m.as_str()).map(String::from))
-            .unwrap_or_else(|| body.clone());
+        let response = serde_json::from_str::<serde_json::Value>(&body).ok();
+        let field = |name: &str| {
+            response
+                .as_ref()
+                .and_then(|v| v.get(name).and_then(|m| m.as_str()).map(String::from))
+        };
+        let message = field("message").unwrap_or_else(|| body.clone());
         tracing::info!(status = %status, message = %message, "Upload successful");
-        Ok(())
+
+        match field("job_id") {
+            Some(job_id) if config.wait_for_processing => wait_for_job(config, &client, &job_id),
+            None if config.wait_for_processing => {
+                tracing::warn!("Server did not report a job, not waiting for processing");
+                Ok(Outcome::Uploaded)
+            }
+            _ => Ok(Outcome::Uploaded),
+        }
+    } else if let Some(filename) = duplicate_filename(status.as_u16(), &body) {
+        tracing::info!(filename = %filename, "Server already has this revision");
+        Ok(Outcome::AlreadyCaptured {
+            filename: Some(filename),
+        })
+    } else if is_read_only_response(status.as_u16(), &body) {
+        tracing::warn!(url = %url, "Server is in read-only mode");
+        Err(Error::ServerReadOnly { url })
     } else {
-        tracing::error!(status = %status, body = %body, "Upload failed");
+        tracing::error!(status = %status, body = %body, attempts, "Upload failed");
         Err(Error::UploadFailed {
             status: status.as_u16(),
             body,
+            attempts,
         })
     }
 }
+
+/// How often to ask the server whether an upload has been processed.
+const JOB_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Deserialize)]
+struct JobResponse {
+    status: String,
+    #[serde(default)]
+    error: Option<String>,
+    #[serde(default)]
+    filename: Option<String>,
+}
+
+/// Poll `/api/jobs/{job_id}` until the server has finished with the upload.
+fn wait_for_job(
+    config: &config::ClientConfig,
+    client: &reqwest::blocking::Client,
+    job_id: &str,
+) -> Result<Outcome> {
+    let url = format!("{}/api/jobs/{job_id}", config.server_url);
+    let timeout = Duration::from_secs(config.wait_timeout_secs);
+    let started = std::time::Instant::now();
+    tracing::info!(job_id, "Waiting for the server to process the upload");
+
+    loop {
+        let response = client
+            .get(&url)
+            .send()
+            .map_err(|e| Error::ServerConnectionFailed {
+                url: url.clone(),
+                source: e,
+                attempts: 1,
+            })?;
+        let status = response.status();
+        let body = response.text().unwrap_or_default();
+        if !status.is_success() {
+            return Err(Error::ProcessingFailed {
+                message: format!("job status request failed with status {status}: {body}"),
+            });
+        }
+
+        let job: JobResponse = serde_json::from_str(&body)?;
+        tracing::debug!(job_id, status = %job.status, "Polled upload job");
+        match job.status.as_str() {
+            "done" => {
+                return Ok(Outcome::Processed {
+                    filename: job.filename.unwrap_or_default(),
+                });
+            }
+            "failed" => {
+                return Err(Error::ProcessingFailed {
+                    message: job.error.unwrap_or_else(|| "unknown error".to_string()),
+                });
+            }
+            _ if started.elapsed() >= timeout => {
+                return Err(Error::ProcessingTimedOut {
+                    job_id: job_id.to_string(),
+                    waited_secs: config.wait_timeout_secs,
+                });
+            }
+            _ => std::thread::sleep(JOB_POLL_INTERVAL),
+        }
+    }
+}
+
+/// The existing file named by a 409 for an upload of a revision the server already has.
+fn duplicate_filename(status: u16, body: &str) -> Option<String> {
+    if status != 409 {
+        return None;
+    }
+    let body = ErrorBody::parse(body)?;
+    if body.code != crate::server::DUPLICATE_REVISION_ERROR_CODE {
+        return None;
+    }
+    Some(body.detail_str("filename")?.to_string())
+}
+
+/// Whether a failed upload response is the server refusing uploads in read-only mode.
+fn is_read_only_response(status: u16, body: &str) -> bool {
+    status == 503
+        && ErrorBody::parse(body).is_some_and(|e| e.code == crate::server::READ_ONLY_ERROR_CODE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use test_case::test_case;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            retries: 2,
+            max_wait: Duration::from_secs(60),
+            backoff_base: Duration::from_millis(500),
+        }
+    }
+
+    fn retry_after(value: Option<&'static str>) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Some(value) = value {
+            headers.insert(RETRY_AFTER, HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    #[test_case(202, None, 0, false, UploadAction::Finish ; "accepted")]
+    #[test_case(200, Some("5"), 0, false, UploadAction::Finish ; "success ignores retry after")]
+    #[test_case(429, Some("5"), 0, false, UploadAction::RetryAfter(Duration::from_secs(5)) ; "too many requests")]
+    #[test_case(503, Some("5"), 1, false, UploadAction::RetryAfter(Duration::from_secs(5)) ; "unavailable")]
+    #[test_case(429, Some("5"), 0, true, UploadAction::RetryAfter(Duration::from_secs(5)) ; "retry after shrinking")]
+    #[test_case(429, Some("3600"), 0, false, UploadAction::RetryAfter(Duration::from_secs(60)) ; "wait capped")]
+    #[test_case(429, Some("0"), 0, false, UploadAction::RetryAfter(Duration::ZERO) ; "immediate")]
+    #[test_case(429, Some("5"), 2, false, UploadAction::Finish ; "budget exhausted")]
+    #[test_case(429, None, 0, false, UploadAction::Finish ; "too many requests without retry after")]
+    #[test_case(503, None, 0, false, UploadAction::Backoff ; "unavailable without retry after")]
+    #[test_case(429, Some("soon"), 0, false, UploadAction::Finish ; "unparseable retry after")]
+    #[test_case(500, Some("5"), 0, false, UploadAction::Backoff ; "server error")]
+    #[test_case(502, None, 1, true, UploadAction::Backoff ; "bad gateway after shrinking")]
+    #[test_case(500, None, 2, false, UploadAction::Finish ; "server error budget exhausted")]
+    #[test_case(413, None, 0, false, UploadAction::Shrink ; "too large")]
+    #[test_case(413, None, 2, false, UploadAction::Shrink ; "too large after retries")]
+    #[test_case(413, None, 0, true, UploadAction::Finish ; "too large after shrinking")]
+    #[test_case(400, None, 0, false, UploadAction::Finish ; "bad request")]
+    fn test_retry_policy(
+        status: u16,
+        header: Option<&'static str>,
+        attempt: u32,
+        shrunk: bool,
+        expected: UploadAction,
+    ) {
+        let status = StatusCode::from_u16(status).unwrap();
+        assert_eq!(
+            policy().action(status, &retry_after(header), attempt, shrunk),
+            expected
+        );
+    }
+
+    #[test_case(0, 0.0, 250 ; "first retry, least jitter")]
+    #[test_case(0, 1.0, 500 ; "first retry, most jitter")]
+    #[test_case(2, 0.5, 1500 ; "third retry")]
+    #[test_case(1, 7.0, 1000 ; "jitter clamped")]
+    fn test_backoff_doubles_with_jitter(attempt: u32, jitter: f64, expected_ms: u64) {
+        assert_eq!(
+            policy().backoff(attempt, jitter),
+            Duration::from_millis(expected_ms)
+        );
+    }
+
+    #[test]
+    fn test_jitter_is_a_fraction() {
+        assert!((0..100).map(|_| jitter()).all(|j| (0.0..1.0).contains(&j)));
+    }
+
+    #[test_case("120", Some(120) ; "seconds")]
+    #[test_case(" 7 ", Some(7) ; "padded seconds")]
+    #[test_case("Mon, 15 Jan 2024 12:01:00 GMT", Some(60) ; "http date")]
+    #[test_case("Mon, 15 Jan 2024 11:00:00 GMT", Some(0) ; "http date in the past")]
+    #[test_case("-1", None ; "negative")]
+    #[test_case("tomorrow", None ; "garbage")]
+    fn test_parse_retry_after(value: &str, expected: Option<u64>) {
+        let now = chrono::DateTime::parse_from_rfc3339("2024-01-15T12:00:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        assert_eq!(
+            parse_retry_after(value, now),
+            expected.map(Duration::from_secs)
+        );
+    }
+
+    #[test]
+    fn test_shrink_halves_image() {
+        let image = shrink(&DynamicImage::new_rgb8(640, 480));
+        assert_eq!((image.width(), image.height()), (320, 240));
+    }
+
+    /// A request received by [`stub_server`].
+    #[derive(Debug)]
+    struct StubRequest {
+        /// e.g. "POST /api/upload HTTP/1.1"
+        line: String,
+        /// Width of the uploaded PNG, if there was one.
+        png_width: Option<u32>,
+        /// Number of PNGs uploaded, one per frame.
+        pngs: usize,
+    }
+
+    /// Answer one request per entry in `responses`, in order, on a local port. The
+    /// thread returns the requests it received.
+    fn stub_server(
+        responses: Vec<&'static str>,
+    ) -> (String, std::thread::JoinHandle<Vec<StubRequest>>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let handle = std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let mut content_length = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header == "\r\n" {
+                        break;
+                    }
+                    if let Some((name, value)) = header.split_once(':')
+                        && name.eq_ignore_ascii_case("content-length")
+                    {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+
+                // The PNG is the last part, find its header to read the width
+                let png_width =
+                    body.windows(8)
+                        .position(|w| w == b"\x89PNG\r\n\x1a\n")
+                        .map(|start| {
+                            let width = &body[start + 16..start + 20];
+                            u32::from_be_bytes(width.try_into().unwrap())
+                        });
+                let pngs = body
+                    .windows(8)
+                    .filter(|w| w == b"\x89PNG\r\n\x1a\n")
+                    .count();
+                requests.push(StubRequest {
+                    line: line.trim_end().to_string(),
+                    png_width,
+                    pngs,
+                });
+
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+            requests
+        });
+
+        (url, handle)
+    }
+
+    fn uploaded_widths(requests: &[StubRequest]) -> Vec<u32> {
+        requests.iter().filter_map(|r| r.png_width).collect()
+    }
+
+    fn upload_metadata() -> UploadMetadata {
+        UploadMetadata {
+            revision: "abc".to_string(),
+            message: "feat: test".to_string(),
+            commit_type: "feat".to_string(),
+            scope: String::new(),
+            timestamp: "2024-01-01 00:00:00".to_string(),
+            repo_name: "repo".to_string(),
+            branch_name: "main".to_string(),
+            author_name: "Test User".to_string(),
+            author_email: "test@example.com".to_string(),
+            breaking: false,
+            co_authors: Vec::new(),
+            files_changed: 0,
+            insertions: 0,
+            deletions: 0,
+            force: false,
+            processing_overrides: Overrides::new(),
+            frame_delay_ms: None,
+            burned_in_chyron: None,
+        }
+    }
+
+    const TOO_MANY_REQUESTS: &str = "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 0\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+    const TOO_LARGE: &str =
+        "HTTP/1.1 413 Payload Too Large\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+    const ACCEPTED: &str = "HTTP/1.1 202 Accepted\r\nContent-Type: application/json\r\nContent-Length: 35\r\nConnection: close\r\n\r\n{\"status\":\"ok\",\"message\":\"queued\"}";
+    const SERVER_ERROR: &str =
+        "HTTP/1.1 500 Internal Server Error\r\nContent-Length: 4\r\nConnection: close\r\n\r\nboom";
+    const BAD_REQUEST: &str =
+        "HTTP/1.1 400 Bad Request\r\nContent-Length: 3\r\nConnection: close\r\n\r\nbad";
+
+    #[test]
+    fn test_upload_retries_after_too_many_requests() {
+        let (url, server) = stub_server(vec![TOO_MANY_REQUESTS, ACCEPTED]);
+        let config = config::ClientConfig {
+            server_url: url,
+            ..Default::default()
+        };
+
+        let result = upload_to_server(
+            &config,
+            vec![DynamicImage::new_rgb8(8, 8)],
+            upload_metadata(),
+        );
+
+        assert!(result.is_ok(), "{result:?}");
+        assert_eq!(uploaded_widths(&server.join().unwrap()), vec![8, 8]);
+    }
+
+    #[test]
+    fn test_upload_gives_up_when_retries_exhausted() {
+        let (url, server) = stub_server(vec![TOO_MANY_REQUESTS, TOO_MANY_REQUESTS]);
+        let config = config::ClientConfig {
+            server_url: url,
+            upload_retries: 1,
+            ..Default::default()
+        };
+
+        let result = upload_to_server(
+            &config,
+            vec![DynamicImage::new_rgb8(8, 8)],
+            upload_metadata(),
+        );
+
+        assert!(
+            matches!(result, Err(Error::UploadFailed { status: 429, .. })),
+            "{result:?}"
+        );
+        assert_eq!(server.join().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_upload_backs_off_after_server_errors() {
+        let (url, server) = stub_server(vec![SERVER_ERROR, SERVER_ERROR, ACCEPTED]);
+        let config = config::ClientConfig {
+            server_url: url,
+            upload_retry_base_ms: 1,
+            ..Default::default()
+        };
+
+        let result = upload_to_server(
+            &config,
+            vec![DynamicImage::new_rgb8(8, 8)],
+            upload_metadata(),
+        );
+
+        assert!(result.is_ok(), "{result:?}");
+        assert_eq!(server.join().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_upload_failure_counts_attempts() {
+        let (url, server) = stub_server(vec![SERVER_ERROR, SERVER_ERROR]);
+        let config = config::ClientConfig {
+            server_url: url,
+            upload_retries: 1,
+            upload_retry_base_ms: 1,
+            ..Default::default()
+        };
+
+        let result = upload_to_server(
+            &config,
+            vec![DynamicImage::new_rgb8(8, 8)],
+            upload_metadata(),
+        );
+
+        assert!(
+            matches!(
+                &result,
+                Err(Error::UploadFailed { status: 500, body, attempts: 2 }) if body == "boom"
+            ),
+            "{result:?}"
+        );
+        assert_eq!(server.join().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_upload_does_not_retry_client_errors() {
+        let (url, server) = stub_server(vec![BAD_REQUEST]);
+        let config = config::ClientConfig {
+            server_url: url,
+            upload_retry_base_ms: 1,
+            ..Default::default()
+        };
+
+        let result = upload_to_server(
+            &config,
+            vec![DynamicImage::new_rgb8(8, 8)],
+            upload_metadata(),
+        );
+
+        assert!(
+            matches!(
+                result,
+                Err(Error::UploadFailed {
+                    status: 400,
+                    attempts: 1,
+                    ..
+                })
+            ),
+            "{result:?}"
+        );
+        assert_eq!(server.join().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_upload_retries_stop_at_the_timeout() {
+        let (url, server) = stub_server(vec![SERVER_ERROR]);
+        let config = config::ClientConfig {
+            server_url: url,
+            server_upload_timeout_secs: 1,
+            upload_retry_base_ms: 5_000,
+            ..Default::default()
+        };
+
+        let started = std::time::Instant::now();
+        let result = upload_to_server(
+            &config,
+            vec![DynamicImage::new_rgb8(8, 8)],
+            upload_metadata(),
+        );
+
+        assert!(
+            matches!(
+                result,
+                Err(Error::UploadFailed {
+                    status: 500,
+                    attempts: 1,
+                    ..
+                })
+            ),
+            "{result:?}"
+        );
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(server.join().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_upload_waits_as_asked_past_the_timeout() {
+        const BUSY: &str = "HTTP/1.1 503 Service Unavailable\r\nRetry-After: 2\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+        let (url, server) = stub_server(vec![BUSY, ACCEPTED]);
+        let config = config::ClientConfig {
+            server_url: url,
+            server_upload_timeout_secs: 1,
+            upload_max_retry_after_secs: 5,
+            ..Default::default()
+        };
+
+        let started = std::time::Instant::now();
+        let result = upload_to_server(
+            &config,
+            vec![DynamicImage::new_rgb8(8, 8)],
+            upload_metadata(),
+        );
+
+        assert!(result.is_ok(), "{result:?}");
+        assert!(started.elapsed() >= Duration::from_secs(2));
+        assert_eq!(server.join().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_upload_shrinks_once_when_too_large() {
+        let (url, server) = stub_server(vec![TOO_LARGE, TOO_LARGE]);
+        let config = config::ClientConfig {
+            server_url: url,
+            ..Default::default()
+        };
+
+        let result = upload_to_server(
+            &config,
+            vec![DynamicImage::new_rgb8(8, 8)],
+            upload_metadata(),
+        );
+
+        assert!(
+            matches!(result, Err(Error::UploadFailed { status: 413, .. })),
+            "{result:?}"
+        );
+        assert_eq!(uploaded_widths(&server.join().unwrap()), vec![8, 4]);
+    }
+
+    #[test]
+    fn test_upload_sends_each_frame_of_an_animation() {
+        let (url, server) = stub_server(vec![TOO_LARGE, ACCEPTED]);
+        let config = config::ClientConfig {
+            server_url: url,
+            ..Default::default()
+        };
+        let metadata = UploadMetadata {
+            frame_delay_ms: Some(250),
+            ..upload_metadata()
+        };
+
+        let result = upload_to_server(&config, vec![DynamicImage::new_rgb8(8, 8); 3], metadata);
+
+        assert!(result.is_ok(), "{result:?}");
+        let requests = server.join().unwrap();
+        // Every frame is shrunk for the retry
+        assert_eq!(
+            requests.iter().map(|r| r.pngs).collect::<Vec<_>>(),
+            vec![3, 3]
+        );
+        assert_eq!(uploaded_widths(&requests), vec![8, 4]);
+    }
+
+    /// Noise, so its PNG is about as large as its pixels.
+    fn noise(width: u32, height: u32) -> DynamicImage {
+        let mut seed = 0x9e37_79b9_u32;
+        DynamicImage::ImageRgb8(image::RgbImage::from_fn(width, height, |_, _| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            image::Rgb([seed as u8, (seed >> 8) as u8, (seed >> 16) as u8])
+        }))
+    }
+
+    fn limit_config(url: String) -> config::ClientConfig {
+        config::ClientConfig {
+            server_url: url,
+            upload_max_dimension: 32,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_upload_over_the_server_limit_is_scaled_down() {
+        let (url, server) = stub_server(vec![
+            json_response(r#"{"gallery_title":"Lols","max_upload_bytes":1024}"#),
+            ACCEPTED,
+        ]);
+
+        let result = upload_to_server(&limit_config(url), vec![noise(128, 64)], upload_metadata());
+
+        assert!(result.is_ok(), "{result:?}");
+        let requests = server.join().unwrap();
+        assert_eq!(requests[0].line, "GET /api/config HTTP/1.1");
+        assert_eq!(uploaded_widths(&requests), vec![32]);
+    }
+
+    #[test]
+    fn test_upload_within_the_server_limit_is_untouched() {
+        let (url, server) = stub_server(vec![
+            json_response(r#"{"gallery_title":"Lols","max_upload_bytes":4194304}"#),
+            ACCEPTED,
+        ]);
+
+        let result = upload_to_server(&limit_config(url), vec![noise(128, 64)], upload_metadata());
+
+        assert!(result.is_ok(), "{result:?}");
+        assert_eq!(uploaded_widths(&server.join().unwrap()), vec![128]);
+    }
+
+    #[test]
+    fn test_upload_without_a_reported_limit_is_untouched() {
+        let (url, server) =
+            stub_server(vec![json_response(r#"{"gallery_title":"Lols"}"#), ACCEPTED]);
+
+        let result = upload_to_server(&limit_config(url), vec![noise(128, 64)], upload_metadata());
+
+        assert!(result.is_ok(), "{result:?}");
+        assert_eq!(uploaded_widths(&server.join().unwrap()), vec![128]);
+    }
+
+    fn accepted_job(job_id: &str) -> &'static str {
+        let body = format!(r#"{{"status":"accepted","message":"queued","job_id":"{job_id}"}}"#);
+        format!(
+            "HTTP/1.1 202 Accepted\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+        .leak()
+    }
+
+    fn wait_config(url: String) -> config::ClientConfig {
+        config::ClientConfig {
+            server_url: url,
+            wait_for_processing: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_wait_polls_job_until_done() {
+        let (url, server) = stub_server(vec![
+            accepted_job("42"),
+            json_response(r#"{"id":"42","status":"processing"}"#),
+            json_response(r#"{"id":"42","status":"done","filename":"repo-1-abc.png"}"#),
+        ]);
+
+        let outcome = upload_to_server(
+            &wait_config(url),
+            vec![DynamicImage::new_rgb8(8, 8)],
+            upload_metadata(),
+        );
+
+        assert_eq!(
+            outcome.unwrap(),
+            Outcome::Processed {
+                filename: "repo-1-abc.png".to_string()
+            }
+        );
+        let requests = server.join().unwrap();
+        assert!(
+            requests[1].line.starts_with("GET /api/jobs/42 "),
+            "{requests:?}"
+        );
+    }
+
+    #[test]
+    fn test_wait_reports_failed_job() {
+        let (url, server) = stub_server(vec![
+            accepted_job("42"),
+            json_response(r#"{"id":"42","status":"failed","error":"disk full"}"#),
+        ]);
+
+        let outcome = upload_to_server(
+            &wait_config(url),
+            vec![DynamicImage::new_rgb8(8, 8)],
+            upload_metadata(),
+        );
+
+        assert!(
+            matches!(&outcome, Err(Error::ProcessingFailed { message }) if message == "disk full"),
+            "{outcome:?}"
+        );
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_wait_gives_up_after_timeout() {
+        let (url, server) = stub_server(vec![
+            accepted_job("42"),
+            json_response(r#"{"id":"42","status":"queued"}"#),
+        ]);
+        let config = config::ClientConfig {
+            wait_timeout_secs: 0,
+            ..wait_config(url)
+        };
+
+        let outcome = upload_to_server(
+            &config,
+            vec![DynamicImage::new_rgb8(8, 8)],
+            upload_metadata(),
+        );
+
+        assert!(
+            matches!(&outcome, Err(Error::ProcessingTimedOut { job_id, .. }) if job_id == "42"),
+            "{outcome:?}"
+        );
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_wait_without_job_id_is_uploaded() {
+        let (url, server) = stub_server(vec![ACCEPTED]);
+
+        let outcome = upload_to_server(
+            &wait_config(url),
+            vec![DynamicImage::new_rgb8(8, 8)],
+            upload_metadata(),
+        );
+
+        assert_eq!(outcome.unwrap(), Outcome::Uploaded);
+        assert_eq!(server.join().unwrap().len(), 1);
+    }
+
+    const CONFLICT: &str = "HTTP/1.1 409 Conflict\r\nContent-Type: application/json\r\nContent-Length: 73\r\nConnection: close\r\n\r\n{\"error\":\"duplicate_revision\",\"revision\":\"abc\",\"filename\":\"existing.png\"}";
+
+    #[test]
+    fn test_duplicate_upload_is_already_captured() {
+        let (url, server) = stub_server(vec![CONFLICT]);
+        let config = config::ClientConfig {
+            server_url: url,
+            ..Default::default()
+        };
+
+        let outcome = capture_and_upload(
+            &config,
+            upload_metadata(),
+            || Ok(vec![DynamicImage::new_rgb8(8, 8)]),
+            |frames, _| Ok(frames),
+        );
+
+        assert_eq!(
+            outcome.unwrap(),
+            Outcome::AlreadyCaptured {
+                filename: Some("existing.png".to_string())
+            }
+        );
+        assert_eq!(server.join().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_duplicate_upload_fails_when_configured() {
+        let (url, server) = stub_server(vec![CONFLICT]);
+        let config = config::ClientConfig {
+            server_url: url,
+            fail_on_duplicate: true,
+            ..Default::default()
+        };
+
+        let outcome = capture_and_upload(
+            &config,
+            upload_metadata(),
+            || Ok(vec![DynamicImage::new_rgb8(8, 8)]),
+            |frames, _| Ok(frames),
+        );
+
+        assert!(
+            matches!(&outcome, Err(Error::AlreadyCaptured { revision }) if revision == "abc"),
+            "{outcome:?}"
+        );
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_other_conflicts_are_upload_failures() {
+        assert_eq!(duplicate_filename(409, r#"{"error":"locked"}"#), None);
+        assert_eq!(duplicate_filename(409, "conflict"), None);
+        assert_eq!(
+            duplicate_filename(
+                400,
+                r#"{"error":"duplicate_revision","filename":"existing.png"}"#
+            ),
+            None
+        );
+    }
+
+    #[test_case(r#"{"error":{"code":"read_only","message":"Server is in read-only mode","details":{}}}"# ; "nested")]
+    #[test_case(r#"{"error":"read_only","message":"Server is in read-only mode"}"# ; "flat")]
+    fn test_read_only_response_detected(body: &str) {
+        assert!(is_read_only_response(503, body));
+    }
+
+    #[test_case(r#"{"error":{"code":"duplicate_revision","message":"","details":{"filename":"existing.png"}}}"# ; "nested")]
+    #[test_case(r#"{"error":"duplicate_revision","filename":"existing.png"}"# ; "flat")]
+    fn test_duplicate_filename_read_from_conflict(body: &str) {
+        assert_eq!(
+            duplicate_filename(409, body).as_deref(),
+            Some("existing.png")
+        );
+    }
+
+    #[test]
+    fn test_other_503_is_not_read_only() {
+        assert!(!is_read_only_response(503, "Service Unavailable"));
+        assert!(!is_read_only_response(
+            503,
+            r#"{"error":"overloaded","message":"busy"}"#
+        ));
+    }
+
+    #[test]
+    fn test_send_failure_is_connection_failure() {
+        // Nothing listens on port 1, so the send itself fails
+        let config = config::ClientConfig {
+            server_url: "http://127.0.0.1:1".to_string(),
+            server_upload_timeout_secs: 5,
+            upload_retry_base_ms: 1,
+            ..Default::default()
+        };
+
+        let result = upload_to_server(
+            &config,
+            vec![DynamicImage::new_rgb8(4, 4)],
+            upload_metadata(),
+        );
+        assert!(
+            matches!(&result, Err(Error::ServerConnectionFailed { url, attempts: 4, .. }) if url == "http://127.0.0.1:1/api/upload"),
+            "{result:?}"
+        );
+    }
+
+    #[test]
+    fn test_raw_capture_is_saved_before_rendering() -> Result {
+        let dir = tempfile::tempdir()?;
+        let config = config::ClientConfig {
+            mode: config::CaptureMode::Local,
+            local_images_dir: dir.path().join("images").to_string_lossy().to_string(),
+            save_raw_dir: Some(dir.path().join("raw").to_string_lossy().to_string()),
+            ..Default::default()
+        };
+
+        let outcome = capture_and_save(
+            &config,
+            upload_metadata(),
+            || Ok(DynamicImage::new_rgb8(8, 6)),
+            |image, _| {
+                let mut image = image.to_rgb8();
+                image.fill(255);
+                Ok(DynamicImage::ImageRgb8(image))
+            },
+        )?;
+
+        let Outcome::Saved { path } = outcome else {
+            panic!("expected a local save, got {outcome:?}");
+        };
+        let raw = dir.path().join("raw").join(path.file_name().unwrap());
+        assert_eq!(image::open(&raw)?.to_rgb8().get_pixel(0, 0).0, [0, 0, 0]);
+        assert_eq!(image::open(&path)?.to_rgb8().get_pixel(0, 0).0, [255; 3]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_raw_capture_failure_does_not_abort() -> Result {
+        let dir = tempfile::tempdir()?;
+        let not_a_dir = dir.path().join("raw");
+        std::fs::write(&not_a_dir, b"in the way")?;
+        let config = config::ClientConfig {
+            mode: config::CaptureMode::Local,
+            local_images_dir: dir.path().join("images").to_string_lossy().to_string(),
+            save_raw_dir: Some(not_a_dir.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+
+        let outcome = capture_and_save(
+            &config,
+            upload_metadata(),
+            || Ok(DynamicImage::new_rgb8(8, 6)),
+            |image, _| Ok(image),
+        )?;
+
+        assert!(matches!(outcome, Outcome::Saved { .. }));
+        Ok(())
+    }
+
+    #[test]
+    fn test_raw_animation_frames_are_numbered() -> Result {
+        let dir = tempfile::tempdir()?;
+        let config = config::ClientConfig {
+            save_raw_dir: Some(dir.path().to_string_lossy().to_string()),
+            ..Default::default()
+        };
+
+        save_raw(
+            &config,
+            "repo-20240101-000000-abc.png",
+            &[DynamicImage::new_rgb8(4, 4), DynamicImage::new_rgb8(4, 4)],
+        );
+
+        let mut names: Vec<_> = std::fs::read_dir(dir.path())?
+            .map(|entry| entry.map(|entry| entry.file_name()))
+            .collect::<std::io::Result<_>>()?;
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                "repo-20240101-000000-abc-00.png",
+                "repo-20240101-000000-abc-01.png"
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_local_mode_saves_rendered_capture() -> Result {
+        let dir = tempfile::tempdir()?;
+        let config = config::ClientConfig {
+            mode: config::CaptureMode::Local,
+            local_images_dir: dir.path().join("images").to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        let rendered_revision = std::cell::RefCell::new(String::new());
+
+        let outcome = capture_and_save(
+            &config,
+            upload_metadata(),
+            || Ok(DynamicImage::new_rgb8(8, 6)),
+            |image, commit_metadata| {
+                *rendered_revision.borrow_mut() = commit_metadata.revision.clone();
+                Ok(image)
+            },
+        )?;
+
+        let Outcome::Saved { path } = outcome else {
+            panic!("expected a local save, got {outcome:?}");
+        };
+        assert_eq!(*rendered_revision.borrow(), "abc");
+        assert_eq!(path.parent(), Some(dir.path().join("images").as_path()));
+        let parsed = image_metadata::parse_filename(&path).expect("filename should parse");
+        assert_eq!(
+            (parsed.repo_name.as_str(), parsed.revision.as_str()),
+            ("repo", "abc")
+        );
+        // Named and dated by the commit, not by when it was saved
+        assert_eq!(parsed.timestamp, "2024-01-01 00:00:00");
+        let saved = image_metadata::read_png_metadata(&path)?.expect("metadata should be saved");
+        assert_eq!(
+            chrono::DateTime::parse_from_rfc3339(&saved.timestamp).ok(),
+            crate::parse_timestamp("2024-01-01 00:00:00")
+        );
+        assert_eq!(saved.message, "feat: test");
+        assert_eq!(saved.branch_name, "main");
+        assert_eq!(saved.author_name, "Test User");
+        assert_eq!(image::open(&path)?.width(), 8);
+        Ok(())
+    }
+
+    #[test]
+    fn test_local_mode_render_failure_saves_nothing() -> Result {
+        let dir = tempfile::tempdir()?;
+        let config = config::ClientConfig {
+            local_images_dir: dir.path().to_string_lossy().to_string(),
+            ..Default::default()
+        };
+
+        let result = capture_and_save(
+            &config,
+            upload_metadata(),
+            || Ok(DynamicImage::new_rgb8(8, 6)),
+            |_, _| Err(Error::GitCommandFailed),
+        );
+
+        assert!(matches!(result, Err(Error::GitCommandFailed)), "{result:?}");
+        assert_eq!(std::fs::read_dir(dir.path())?.count(), 0);
+        Ok(())
+    }
+
+    fn spool_config(url: &str, spool_dir: &Path) -> config::ClientConfig {
+        config::ClientConfig {
+            server_url: url.to_string(),
+            server_upload_timeout_secs: 5,
+            upload_retry_base_ms: 1,
+            spool_dir: spool_dir.to_string_lossy().to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Spool a `width`-pixel capture of `revision`.
+    fn spool_capture(config: &config::ClientConfig, revision: &str, width: u32) -> Result {
+        let metadata = UploadMetadata {
+            revision: revision.to_string(),
+            ..upload_metadata()
+        };
+        Spool::from_config(config).unwrap().push(
+            &encode_png(&DynamicImage::new_rgb8(width, 4))?,
+            &serde_json::to_string(&metadata)?,
+            revision,
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_connection_failure_is_spooled() -> Result {
+        let dir = tempfile::tempdir()?;
+        let config = spool_config("http://127.0.0.1:1", dir.path());
+
+        let outcome = capture_and_upload(
+            &config,
+            upload_metadata(),
+            || Ok(vec![DynamicImage::new_rgb8(8, 8)]),
+            |frames, _| Ok(frames),
+        )?;
+
+        let entries = Spool::from_config(&config).unwrap().entries();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            outcome,
+            Outcome::Spooled {
+                path: entries[0].image.clone(),
+                read_only: false,
+            }
+        );
+        let sidecar: UploadMetadata =
+            serde_json::from_str(&std::fs::read_to_string(&entries[0].metadata)?)?;
+        assert_eq!(sidecar.revision, "abc");
+        assert_eq!(load_still_image(&entries[0].image)?.width(), 8);
+        Ok(())
+    }
+
+    const READ_ONLY: &str = "HTTP/1.1 503 Service Unavailable\r\nContent-Type: application/json\r\nContent-Length: 61\r\nConnection: close\r\n\r\n{\"error\":\"read_only\",\"message\":\"Server is in read-only mode\"}";
+
+    #[test]
+    fn test_read_only_server_is_spooled() -> Result {
+        let dir = tempfile::tempdir()?;
+        let (url, server) = stub_server(vec![READ_ONLY]);
+        let config = spool_config(&url, dir.path());
+
+        let outcome = capture_and_upload(
+            &config,
+            upload_metadata(),
+            || Ok(vec![DynamicImage::new_rgb8(8, 8)]),
+            |frames, _| Ok(frames),
+        )?;
+
+        assert_eq!(server.join().unwrap().len(), 1, "not retried");
+        let entries = Spool::from_config(&config).unwrap().entries();
+        assert_eq!(
+            outcome,
+            Outcome::Spooled {
+                path: entries[0].image.clone(),
+                read_only: true,
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_only_server_without_spool_is_error() -> Result {
+        let dir = tempfile::tempdir()?;
+        let (url, server) = stub_server(vec![READ_ONLY]);
+        let config = config::ClientConfig {
+            spool_max_entries: 0,
+            ..spool_config(&url, dir.path())
+        };
+
+        let result = capture_and_upload(
+            &config,
+            upload_metadata(),
+            || Ok(vec![DynamicImage::new_rgb8(8, 8)]),
+            |frames, _| Ok(frames),
+        );
+
+        server.join().unwrap();
+        assert!(
+            matches!(result, Err(Error::ServerReadOnly { .. })),
+            "{result:?}"
+        );
+        assert_eq!(std::fs::read_dir(dir.path())?.count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_client_chyron_is_burned_into_the_last_frame() -> Result {
+        let dir = tempfile::tempdir()?;
+        let config = spool_config("http://127.0.0.1:1", dir.path());
+        let metadata = UploadMetadata {
+            burned_in_chyron: Some(false),
+            ..upload_metadata()
+        };
+        let chyron = config::BurnedInChyronConfig::default();
+        let rendered = std::cell::RefCell::new(Vec::new());
+
+        capture_and_upload(
+            &config,
+            metadata,
+            || Ok(vec![DynamicImage::new_rgb8(320, 240); 2]),
+            |frames, commit_metadata| {
+                let frames = burn_in_last(&chyron, frames, commit_metadata)?;
+                rendered.replace(frames.clone());
+                Ok(frames)
+            },
+        )?;
+
+        let rendered = rendered.into_inner();
+        assert_eq!(rendered[0], DynamicImage::new_rgb8(320, 240));
+        assert!(
+            rendered[1]
+                .to_rgba8()
+                .pixels()
+                .any(|p| p.0[..3] != [0, 0, 0])
+        );
+        let entries = Spool::from_config(&config).unwrap().entries();
+        let sidecar: UploadMetadata =
+            serde_json::from_str(&std::fs::read_to_string(&entries[0].metadata)?)?;
+        assert_eq!(sidecar.burned_in_chyron, Some(false));
+        Ok(())
+    }
+
+    #[test]
+    fn test_upload_metadata_leaves_chyron_to_the_server_by_default() -> Result {
+        let json = serde_json::to_value(upload_metadata())?;
+        assert!(json.get("burned_in_chyron").is_none());
+
+        let metadata = UploadMetadata {
+            burned_in_chyron: Some(true),
+            ..upload_metadata()
+        };
+        assert_eq!(serde_json::to_value(metadata)?["burned_in_chyron"], true);
+        Ok(())
+    }
+
+    #[test]
+    fn test_connection_failure_without_spool_is_error() -> Result {
+        let dir = tempfile::tempdir()?;
+        let config = config::ClientConfig {
+            spool_max_entries: 0,
+            ..spool_config("http://127.0.0.1:1", dir.path())
+        };
+
+        let result = capture_and_upload(
+            &config,
+            upload_metadata(),
+            || Ok(vec![DynamicImage::new_rgb8(8, 8)]),
+            |frames, _| Ok(frames),
+        );
+
+        assert!(
+            matches!(result, Err(Error::ServerConnectionFailed { .. })),
+            "{result:?}"
+        );
+        assert_eq!(std::fs::read_dir(dir.path())?.count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_flush_spool_uploads_oldest_first() -> Result {
+        let dir = tempfile::tempdir()?;
+        let (url, server) = stub_server(vec![ACCEPTED, ACCEPTED]);
+        let config = spool_config(&url, dir.path());
+        spool_capture(&config, "aaa", 4)?;
+        spool_capture(&config, "bbb", 6)?;
+
+        let report = flush_spool(&config)?;
+
+        assert_eq!(
+            report,
+            FlushReport {
+                uploaded: 2,
+                skipped: 0,
+                remaining: 0
+            }
+        );
+        assert_eq!(uploaded_widths(&server.join().unwrap()), vec![4, 6]);
+        assert_eq!(std::fs::read_dir(dir.path())?.count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_background_capture_is_spooled_without_uploading() -> Result {
+        let dir = tempfile::tempdir()?;
+        // Nothing listens here, so any attempt to upload would fail the test
+        let config = spool_config("http://127.0.0.1:1", dir.path());
+
+        let outcome = capture_and_spool(
+            &config,
+            upload_metadata(),
+            || Ok(vec![DynamicImage::new_rgb8(8, 8)]),
+            |frames, _| Ok(frames),
+        )?;
+
+        let entries = Spool::from_config(&config).unwrap().entries();
+        assert_eq!(
+            outcome,
+            Outcome::Queued {
+                path: entries[0].image.clone()
+            }
+        );
+        assert_eq!(load_still_image(&entries[0].image)?.width(), 8);
+        // Left uncompressed for the detached upload to encode
+        assert!(std::fs::metadata(&entries[0].image)?.len() > 8 * 8 * 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_upload_spooled_uploads_it_then_older_captures() -> Result {
+        let dir = tempfile::tempdir()?;
+        let (url, server) = stub_server(vec![ACCEPTED, ACCEPTED]);
+        let config = spool_config(&url, dir.path());
+        spool_capture(&config, "aaa", 4)?;
+        spool_capture(&config, "bbb", 6)?;
+        let queued = Spool::from_config(&config).unwrap().entries()[1]
+            .image
+            .clone();
+
+        let outcome = upload_spooled(&config, &queued)?;
+
+        assert_eq!(outcome, Outcome::Uploaded);
+        assert_eq!(uploaded_widths(&server.join().unwrap()), vec![6, 4]);
+        assert_eq!(std::fs::read_dir(dir.path())?.count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_upload_spooled_keeps_it_while_unreachable() -> Result {
+        let dir = tempfile::tempdir()?;
+        let config = spool_config("http://127.0.0.1:1", dir.path());
+        spool_capture(&config, "abc", 4)?;
+        let queued = Spool::from_config(&config).unwrap().entries()[0]
+            .image
+            .clone();
+
+        let outcome = upload_spooled(&config, &queued)?;
+
+        assert_eq!(
+            outcome,
+            Outcome::Spooled {
+                path: queued,
+                read_only: false
+            }
+        );
+        assert_eq!(Spool::from_config(&config).unwrap().entries().len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_flush_spool_skips_corrupt_sidecar() -> Result {
+        let dir = tempfile::tempdir()?;
+        let (url, server) = stub_server(vec![ACCEPTED]);
+        let config = spool_config(&url, dir.path());
+        std::fs::write(dir.path().join("0000000000001-bad.json"), "{not json")?;
+        std::fs::write(dir.path().join("0000000000001-bad.png"), "png")?;
+        spool_capture(&config, "abc", 4)?;
+
+        let report = flush_spool(&config)?;
+
+        assert_eq!(
+            report,
+            FlushReport {
+                uploaded: 1,
+                skipped: 1,
+                remaining: 1
+            }
+        );
+        assert_eq!(uploaded_widths(&server.join().unwrap()), vec![4]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_flush_spool_keeps_captures_while_unreachable() -> Result {
+        let dir = tempfile::tempdir()?;
+        let config = spool_config("http://127.0.0.1:1", dir.path());
+        spool_capture(&config, "aaa", 4)?;
+        spool_capture(&config, "bbb", 4)?;
+
+        let result = flush_spool(&config);
+
+        assert!(
+            matches!(result, Err(Error::ServerConnectionFailed { .. })),
+            "{result:?}"
+        );
+        assert_eq!(Spool::from_config(&config).unwrap().entries().len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_flush_spool_stops_quietly_while_read_only() -> Result {
+        let dir = tempfile::tempdir()?;
+        let (url, server) = stub_server(vec![READ_ONLY]);
+        let config = spool_config(&url, dir.path());
+        spool_capture(&config, "aaa", 4)?;
+        spool_capture(&config, "bbb", 4)?;
+
+        let report = flush_spool(&config)?;
+
+        assert_eq!(
+            report,
+            FlushReport {
+                uploaded: 0,
+                skipped: 0,
+                remaining: 2
+            }
+        );
+        assert_eq!(server.join().unwrap().len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_upload_spooled_keeps_it_while_read_only() -> Result {
+        let dir = tempfile::tempdir()?;
+        let (url, server) = stub_server(vec![READ_ONLY]);
+        let config = spool_config(&url, dir.path());
+        spool_capture(&config, "abc", 4)?;
+        let queued = Spool::from_config(&config).unwrap().entries()[0]
+            .image
+            .clone();
+
+        let outcome = upload_spooled(&config, &queued)?;
+
+        server.join().unwrap();
+        assert_eq!(
+            outcome,
+            Outcome::Spooled {
+                path: queued,
+                read_only: true
+            }
+        );
+        assert_eq!(Spool::from_config(&config).unwrap().entries().len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_still_image_converts_to_rgb() -> Result {
+        let dir = tempfile::tempdir()?;
+        // Extension doesn't match the content, the format is sniffed
+        let path = dir.path().join("avatar.jpg");
+        DynamicImage::new_rgba8(4, 3).save_with_format(&path, image::ImageFormat::Png)?;
+
+        let image = load_still_image(&path)?;
+
+        assert!(matches!(image, DynamicImage::ImageRgb8(_)));
+        assert_eq!((image.width(), image.height()), (4, 3));
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_still_image_missing() {
+        let result = load_still_image(Path::new("/nonexistent/avatar.png"));
+        assert!(
+            matches!(&result, Err(Error::CaptureSourceNotFound { path }) if path == Path::new("/nonexistent/avatar.png")),
+            "{result:?}"
+        );
+    }
+
+    #[test]
+    fn test_load_still_image_not_an_image() -> Result {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("avatar.png");
+        std::fs::write(&path, "not an image")?;
+
+        let result = load_still_image(&path);
+
+        assert!(
+            matches!(&result, Err(Error::CaptureSourceUndecodable { .. })),
+            "{result:?}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_capture_source_is_scaled_down() -> Result {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("phone.png");
+        DynamicImage::new_rgb8(400, 300).save(&path)?;
+        let config = config::ClientConfig {
+            capture_source: Some(path.clone()),
+            capture_source_max_dimension: Some(100),
+            ..Default::default()
+        };
+
+        let image = capture_frame(&config)?;
+
+        assert_eq!((image.width(), image.height()), (100, 75));
+        let small = config::ClientConfig {
+            capture_source_max_dimension: Some(1000),
+            ..config
+        };
+        assert_eq!(capture_frame(&small)?.width(), 400);
+        Ok(())
+    }
+
+    #[test]
+    fn test_capture_source_over_the_size_limit_is_refused() -> Result {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("huge.png");
+        DynamicImage::new_rgb8(64, 64).save(&path)?;
+        let config = config::ClientConfig {
+            capture_source: Some(path.clone()),
+            capture_source_max_bytes: 10,
+            ..Default::default()
+        };
+
+        let result = capture_frame(&config);
+
+        assert!(
+            matches!(&result, Err(Error::CaptureSourceTooLarge { limit: 10, .. })),
+            "{result:?}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_only_code_requires_503() {
+        let body = r#"{"error":"read_only","message":"Server is in read-only mode"}"#;
+        assert!(!is_read_only_response(500, body));
+    }
+
+    fn json_response(body: &str) -> &'static str {
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+        .leak()
+    }
+
+    fn precheck_config(url: String) -> config::ClientConfig {
+        config::ClientConfig {
+            server_url: url,
+            precheck_duplicates: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_precheck_hit_skips_camera() {
+        let (url, server) = stub_server(vec![json_response(
+            r#"{"exists":true,"filename":"repo-20240101-000000-abc.png"}"#,
+        )]);
+        let camera_used = std::cell::Cell::new(false);
+
+        let outcome = capture_and_upload(
+            &precheck_config(url),
+            upload_metadata(),
+            || {
+                camera_used.set(true);
+                Ok(vec![DynamicImage::new_rgb8(8, 8)])
+            },
+            |frames, _| Ok(frames),
+        );
+
+        assert_eq!(
+            outcome.unwrap(),
+            Outcome::AlreadyCaptured {
+                filename: Some("repo-20240101-000000-abc.png".to_string())
+            }
+        );
+        assert!(!camera_used.get());
+        let requests = server.join().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert!(
+            requests[0]
+                .line
+                .starts_with("GET /api/exists?repo=repo&revision=abc "),
+            "{requests:?}"
+        );
+    }
+
+    #[test]
+    fn test_precheck_miss_captures_and_uploads() {
+        let (url, server) = stub_server(vec![json_response(r#"{"exists":false}"#), ACCEPTED]);
+        let camera_used = std::cell::Cell::new(false);
+
+        let outcome = capture_and_upload(
+            &precheck_config(url),
+            upload_metadata(),
+            || {
+                camera_used.set(true);
+                Ok(vec![DynamicImage::new_rgb8(8, 8)])
+            },
+            |frames, _| Ok(frames),
+        );
+
+        assert_eq!(outcome.unwrap(), Outcome::Uploaded);
+        assert!(camera_used.get());
+        assert_eq!(uploaded_widths(&server.join().unwrap()), vec![8]);
+    }
+
+    #[test]
+    fn test_precheck_failure_falls_through_to_capture() {
+        // An older server without the endpoint
+        let not_found = "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+        let (url, server) = stub_server(vec![not_found, ACCEPTED]);
+
+        let outcome = capture_and_upload(
+            &precheck_config(url),
+            upload_metadata(),
+            || Ok(vec![DynamicImage::new_rgb8(8, 8)]),
+            |frames, _| Ok(frames),
+        );
+
+        assert_eq!(outcome.unwrap(), Outcome::Uploaded);
+        assert_eq!(server.join().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_precheck_skipped_when_forcing() {
+        let (url, server) = stub_server(vec![ACCEPTED]);
+        let metadata = UploadMetadata {
+            force: true,
+            ..upload_metadata()
+        };
+
+        let outcome = capture_and_upload(
+            &precheck_config(url),
+            metadata,
+            || Ok(vec![DynamicImage::new_rgb8(8, 8)]),
+            |frames, _| Ok(frames),
+        );
+
+        assert_eq!(outcome.unwrap(), Outcome::Uploaded);
+        assert!(
+            server.join().unwrap()[0]
+                .line
+                .starts_with("POST /api/upload ")
+        );
+    }
+}
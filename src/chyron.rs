@@ -0,0 +1,1135 @@
+//! The chyron: the band across a lolcommit with its commit message, repo, branch,
+//! revision and diff stats, and the fonts it's drawn in.
+//!
+//! Kept apart from background replacement so rendering it needs no OpenCV or
+//! segmentation model, which lets the client draw it itself (`chyron_rendering =
+//! "client"` and local mode).
+
+use crate::config::{ChyronColors, ChyronPosition, ChyronStyle, MessageOverflow, TextStyle};
+use crate::error::Result;
+use crate::git::CommitMetadata;
+use crate::locale::Locale;
+use ab_glyph::{Font, FontArc, PxScale, ScaleFont};
+use image::{DynamicImage, Rgba, RgbaImage};
+use imageproc::drawing::draw_text_mut;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{LazyLock, Mutex};
+
+/// Parsed fonts keyed by their resolved file path, so each font file is read and parsed
+/// once however many uploads and font names use it.
+#[derive(Default)]
+pub struct FontCache {
+    fonts: Mutex<HashMap<PathBuf, FontArc>>,
+}
+
+/// Fonts shared by every chyron render in the process.
+static FONT_CACHE: LazyLock<FontCache> = LazyLock::new(FontCache::default);
+
+impl FontCache {
+    /// Resolve `font_name` with fontconfig (falling back to monospace) and load it, or
+    /// reuse the font already loaded from that path.
+    pub fn load(&self, font_name: &str) -> Result<FontArc> {
+        let font_path = resolve_font_path(font_name)?;
+        let mut fonts = self.fonts.lock().expect("font cache lock poisoned");
+        if let Some(font) = fonts.get(&font_path) {
+            return Ok(font.clone());
+        }
+
+        tracing::debug!(font_name = %font_name, font_path = %font_path.display(), "Loading font");
+        let font_data = std::fs::read(&font_path).map_err(|e| {
+            std::io::Error::other(format!(
+                "Failed to read font from {}: {}",
+                font_path.display(),
+                e
+            ))
+        })?;
+        let font = FontArc::try_from_vec(font_data)
+            .map_err(|e| std::io::Error::other(format!("Failed to parse font: {}", e)))?;
+
+        fonts.insert(font_path, font.clone());
+        Ok(font)
+    }
+
+    /// Number of distinct font files loaded.
+    pub fn len(&self) -> usize {
+        self.fonts.lock().expect("font cache lock poisoned").len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Resolve font name to font file path using fontconfig
+///
+/// Uses fontconfig to find the font file for the given font name.
+/// Falls back to monospace if the requested font is not found.
+fn resolve_font_path(font_name: &str) -> Result<PathBuf> {
+    let fc = fontconfig::Fontconfig::new()
+        .ok_or_else(|| std::io::Error::other("Failed to initialize fontconfig"))?;
+
+    // Try to find the requested font
+    let font = fc.find(font_name, None);
+
+    if let Some(font) = font {
+        let path = &font.path;
+        tracing::debug!(font_name = %font_name, path = %path.display(), "Found font via fontconfig");
+        return Ok(path.clone());
+    }
+
+    // Fallback to monospace (universally available)
+    tracing::warn!(font_name = %font_name, "Font not found, trying fallback: monospace");
+    let fallback = fc.find("monospace", None);
+
+    if let Some(font) = fallback {
+        let path = &font.path;
+        tracing::info!(path = %path.display(), "Using fallback font");
+        return Ok(path.clone());
+    }
+
+    Err(std::io::Error::other(format!(
+        "Font '{}' not found and monospace fallback unavailable",
+        font_name
+    ))
+    .into())
+}
+
+/// Fonts for each text element of the chyron.
+pub struct ChyronFonts {
+    pub message: FontArc,
+    pub info: FontArc,
+    pub sha: FontArc,
+    pub stats: FontArc,
+    /// For `style = "meme"` captions.
+    pub meme: FontArc,
+}
+
+impl ChyronFonts {
+    /// Resolve fonts using fontconfig (with fallback to default_font_name)
+    pub fn from_config(config: &crate::config::BurnedInChyronConfig) -> Result<Self> {
+        Self::from_cache(config, &FONT_CACHE)
+    }
+
+    fn from_cache(config: &crate::config::BurnedInChyronConfig, cache: &FontCache) -> Result<Self> {
+        let message = cache.load(config.get_message_font_name())?;
+        // Only looked up when it will be drawn with
+        let meme = match config.style {
+            ChyronStyle::Chyron => message.clone(),
+            ChyronStyle::Meme => cache.load(config.get_meme_font_name())?,
+        };
+        Ok(Self {
+            message,
+            info: cache.load(config.get_info_font_name())?,
+            sha: cache.load(config.get_sha_font_name())?,
+            stats: cache.load(config.get_stats_font_name())?,
+            meme,
+        })
+    }
+
+    /// Use the same font for every text element.
+    pub fn uniform(font: FontArc) -> Self {
+        Self {
+            message: font.clone(),
+            info: font.clone(),
+            sha: font.clone(),
+            stats: font.clone(),
+            meme: font,
+        }
+    }
+}
+
+impl ChyronFonts {
+    fn get(&self, font: ChyronFont) -> &FontArc {
+        match font {
+            ChyronFont::Message => &self.message,
+            ChyronFont::Info => &self.info,
+            ChyronFont::Sha => &self.sha,
+            ChyronFont::Stats => &self.stats,
+        }
+    }
+}
+
+/// Height of the chyron band on images up to 480px tall, unless configured otherwise.
+pub const CHYRON_HEIGHT: u32 = 80;
+
+/// Space between the band's edges and the text.
+const LEFT_MARGIN: i32 = 15;
+const RIGHT_MARGIN: i32 = 30;
+/// Space between the message and the revision/stats column.
+const COLUMN_GAP: i32 = 20;
+/// Space between the entries of the stats block.
+const STATS_GAP: i32 = 10;
+/// Distance between wrapped message lines, relative to the title font size.
+const MESSAGE_LINE_SPACING: f32 = 1.2;
+/// Smallest a shrunk message gets, relative to the title font size.
+const MIN_SHRINK_FACTOR: f32 = 0.5;
+
+/// Rendered width of `text` in pixels: the sum of the glyph advances plus kerning.
+pub fn measure_text_width(font: &impl Font, scale: PxScale, text: &str) -> f32 {
+    let font = font.as_scaled(scale);
+    let mut width = 0.0;
+    let mut previous = None;
+    for c in text.chars() {
+        let glyph = font.glyph_id(c);
+        if let Some(previous) = previous {
+            width += font.kern(previous, glyph);
+        }
+        width += font.h_advance(glyph);
+        previous = Some(glyph);
+    }
+    width
+}
+
+/// `text` shortened with an ellipsis so it renders no wider than `max_width`.
+pub fn truncate_to_width(font: &impl Font, scale: PxScale, text: &str, max_width: f32) -> String {
+    if measure_text_width(font, scale, text) <= max_width {
+        return text.to_string();
+    }
+
+    let mut truncated: String = text.to_string();
+    while truncated.pop().is_some() {
+        let candidate = format!("{}…", truncated.trim_end());
+        if measure_text_width(font, scale, &candidate) <= max_width {
+            return candidate;
+        }
+    }
+    String::new()
+}
+
+/// `text` split into at most two lines no wider than `max_width`, breaking at the last
+/// space that fits (mid-word when there is none). The second line is truncated.
+pub fn wrap_to_width(font: &impl Font, scale: PxScale, text: &str, max_width: f32) -> Vec<String> {
+    if measure_text_width(font, scale, text) <= max_width {
+        return vec![text.to_string()];
+    }
+
+    // End of the longest prefix that fits, always on a char boundary
+    let mut fits = 0;
+    for (i, c) in text.char_indices() {
+        let end = i + c.len_utf8();
+        if measure_text_width(font, scale, &text[..end]) > max_width {
+            break;
+        }
+        fits = end;
+    }
+    let split = if text[fits..].starts_with(char::is_whitespace) {
+        fits
+    } else {
+        match text[..fits].rfind(char::is_whitespace) {
+            Some(space) if space > 0 => space,
+            _ => fits,
+        }
+    };
+    if split == 0 {
+        return vec![truncate_to_width(font, scale, text, max_width)];
+    }
+
+    vec![
+        text[..split].trim_end().to_string(),
+        truncate_to_width(font, scale, text[split..].trim_start(), max_width),
+    ]
+}
+
+/// The scale at which `text` fits in `max_width`, no smaller than [`MIN_SHRINK_FACTOR`]
+/// of `scale`.
+pub fn shrink_to_width(font: &impl Font, scale: PxScale, text: &str, max_width: f32) -> PxScale {
+    let width = measure_text_width(font, scale, text);
+    if width <= max_width {
+        return scale;
+    }
+
+    let factor = (max_width / width).max(MIN_SHRINK_FACTOR);
+    // Round down so the text can't end up a fraction of a pixel too wide
+    let shrink = |size: f32| (size * factor * 10.0).floor() / 10.0;
+    PxScale {
+        x: shrink(scale.x),
+        y: shrink(scale.y),
+    }
+}
+
+/// The first line of a commit message without its conventional commit prefix, as shown.
+pub fn display_message(message: &str) -> &str {
+    let first_line = message.lines().next().unwrap_or(message);
+    if let Some(colon_pos) = first_line.find(':') {
+        first_line[colon_pos + 1..].trim()
+    } else {
+        first_line
+    }
+}
+
+/// The first 7 characters of a revision, as shown.
+pub fn short_revision(revision: &str) -> &str {
+    if revision.len() > 7 {
+        &revision[..7]
+    } else {
+        revision
+    }
+}
+
+/// Which of the [`ChyronFonts`] a piece of text is drawn with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChyronFont {
+    Message,
+    Info,
+    Sha,
+    Stats,
+}
+
+/// A piece of chyron text and where it goes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChyronText {
+    pub text: String,
+    pub color: Rgba<u8>,
+    pub x: i32,
+    pub y: i32,
+    pub scale: PxScale,
+    pub font: ChyronFont,
+}
+
+/// Height of the chyron band before any wrapped message lines: `height_px`, else
+/// `height_fraction` of the image, else [`CHYRON_HEIGHT`] growing to a sixth of images
+/// taller than 480px so it doesn't become a sliver on high resolution captures.
+pub fn chyron_height(config: &crate::config::BurnedInChyronConfig, image_height: u32) -> u32 {
+    match (config.height_px, config.height_fraction) {
+        (Some(px), _) => px,
+        (None, Some(fraction)) => (image_height as f32 * fraction.clamp(0.0, 1.0)).round() as u32,
+        (None, None) => CHYRON_HEIGHT.max(image_height / 6),
+    }
+}
+
+/// Where everything in the chyron goes for an image of a given size, independent of
+/// what it is drawn onto.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChyronLayout {
+    /// First row of the band.
+    pub band_top: u32,
+    /// Row after the band's last.
+    pub band_bottom: u32,
+    /// Band color, blended with `chyron_opacity`.
+    pub background: Rgba<u8>,
+    pub texts: Vec<ChyronText>,
+    /// Offsets the text is drawn at in `shadow` before its own color, per `text_style`.
+    pub shadow_offsets: Vec<(i32, i32)>,
+    pub shadow: Rgba<u8>,
+}
+
+impl ChyronLayout {
+    pub fn new(
+        config: &crate::config::BurnedInChyronConfig,
+        fonts: &ChyronFonts,
+        width: u32,
+        height: u32,
+        metadata: &CommitMetadata,
+    ) -> Self {
+        let locale = Locale::resolve(config.locale.as_deref());
+        let mut texts = Vec::new();
+
+        // Colors are checked when the config is loaded
+        let colors = config.colors().unwrap_or_else(|e| {
+            tracing::warn!(error = %e, "Using the default chyron colors");
+            ChyronColors::default()
+        });
+        let yellow = Rgba([255u8, 255u8, 0u8, 255u8]);
+        let red = Rgba([255u8, 0u8, 0u8, 255u8]);
+        let left_margin = config.margin_px.map_or(LEFT_MARGIN, |margin| margin as i32);
+        let right_margin = config
+            .margin_px
+            .map_or(RIGHT_MARGIN, |margin| margin as i32);
+
+        let title_scale = PxScale::from(config.title_font_size);
+        let info_scale = PxScale::from(config.info_font_size);
+
+        // Stats format is: (N) +X -Y with k/M suffixes for large numbers, where
+        // N=files changed (yellow), X=insertions (green), Y=deletions (red) unless
+        // stats_color sets one color for all three
+        let mut stats = Vec::new();
+        if metadata.stats.files_changed > 0 {
+            let files_str = format!(
+                "({})",
+                locale.format_stat_number(metadata.stats.files_changed)
+            );
+            stats.push((files_str, colors.stats.unwrap_or(yellow)));
+        }
+        if metadata.stats.insertions > 0 {
+            let insert_str = format!("+{}", locale.format_stat_number(metadata.stats.insertions));
+            stats.push((
+                insert_str,
+                colors.stats.unwrap_or(Rgba([0u8, 255u8, 0u8, 255u8])),
+            ));
+        }
+        if metadata.stats.deletions > 0 {
+            let delete_str = format!("-{}", locale.format_stat_number(metadata.stats.deletions));
+            stats.push((delete_str, colors.stats.unwrap_or(red)));
+        }
+        let stats_widths: Vec<i32> = stats
+            .iter()
+            .map(|(text, _)| measure_text_width(&fonts.stats, info_scale, text).ceil() as i32)
+            .collect();
+        let stats_width =
+            stats_widths.iter().sum::<i32>() + STATS_GAP * (stats.len() as i32 - 1).max(0);
+
+        let revision_short = short_revision(&metadata.revision);
+        let revision_width =
+            measure_text_width(&fonts.sha, title_scale, revision_short).ceil() as i32;
+
+        // The revision and stats share a left edge, placed so the wider of the two
+        // ends at the right margin
+        let column_x = width as i32 - right_margin - stats_width.max(revision_width);
+
+        let display_message = display_message(&metadata.message);
+        let message_right = if revision_short.is_empty() {
+            width as i32 - right_margin
+        } else {
+            column_x - COLUMN_GAP
+        };
+        let message_width = (message_right - left_margin).max(0) as f32;
+        let (message_lines, message_scale) = match config.message_overflow {
+            MessageOverflow::Truncate => (
+                vec![truncate_to_width(
+                    &fonts.message,
+                    title_scale,
+                    display_message,
+                    message_width,
+                )],
+                title_scale,
+            ),
+            MessageOverflow::Wrap => (
+                wrap_to_width(&fonts.message, title_scale, display_message, message_width),
+                title_scale,
+            ),
+            MessageOverflow::Shrink => {
+                let scale =
+                    shrink_to_width(&fonts.message, title_scale, display_message, message_width);
+                (
+                    vec![truncate_to_width(
+                        &fonts.message,
+                        scale,
+                        display_message,
+                        message_width,
+                    )],
+                    scale,
+                )
+            }
+        };
+
+        // Wrapped lines push the info row down and grow the band upwards
+        let line_height = (config.title_font_size * MESSAGE_LINE_SPACING).ceil() as i32;
+        let extra_height = line_height * (message_lines.len() as i32 - 1);
+        let band_height = (chyron_height(config, height) + extra_height as u32).min(height);
+        let band_top = match config.position {
+            ChyronPosition::Bottom => height - band_height,
+            ChyronPosition::Top => 0,
+        };
+        // The text keeps its arrangement for an 80px band, centred in a taller one
+        let padding = (band_height as i32 - (CHYRON_HEIGHT as i32 + extra_height)).max(0) / 2;
+        let title_y = band_top as i32 + 10 + padding;
+        let info_y = band_top as i32 + 45 + extra_height + padding;
+
+        // A shrunk message stays vertically centred on the title row
+        let message_y = title_y + ((title_scale.y - message_scale.y) / 2.0).round() as i32;
+        for (line, text) in message_lines.into_iter().enumerate() {
+            texts.push(ChyronText {
+                text,
+                color: colors.message,
+                x: left_margin,
+                y: message_y + line as i32 * line_height,
+                scale: message_scale,
+                font: ChyronFont::Message,
+            });
+        }
+
+        let commit_type = if metadata.breaking {
+            format!("{}!", metadata.commit_type.to_uppercase())
+        } else {
+            metadata.commit_type.to_uppercase()
+        };
+        let mut info_text = if metadata.scope.is_empty() {
+            format!("{} • {}", commit_type, metadata.repo_name)
+        } else {
+            format!(
+                "{} • {} • {}",
+                commit_type, metadata.scope, metadata.repo_name
+            )
+        };
+        if config.show_author && !metadata.author_name.is_empty() {
+            info_text.push_str(" • ");
+            info_text.push_str(&metadata.author_name);
+        }
+        match metadata.co_authors.len() {
+            0 => {}
+            1 => info_text.push_str(" • +1 co-author"),
+            n => info_text.push_str(&format!(" • +{n} co-authors")),
+        }
+        // Only locales with a date format add the timestamp
+        if let Some(timestamp) = locale.format_timestamp(&metadata.timestamp) {
+            info_text.push_str(" • ");
+            info_text.push_str(&timestamp);
+        }
+        // A breaking change has its type picked out in red, the rest of the line following on
+        let info_x = if metadata.breaking {
+            let rest = info_text.split_off(commit_type.len());
+            let type_width = measure_text_width(&fonts.info, info_scale, &info_text);
+            texts.push(ChyronText {
+                text: std::mem::replace(&mut info_text, rest),
+                color: red,
+                x: left_margin,
+                y: info_y,
+                scale: info_scale,
+                font: ChyronFont::Info,
+            });
+            left_margin + type_width.round() as i32
+        } else {
+            left_margin
+        };
+        texts.push(ChyronText {
+            text: info_text,
+            color: colors.info,
+            x: info_x,
+            y: info_y,
+            scale: info_scale,
+            font: ChyronFont::Info,
+        });
+
+        if !revision_short.is_empty() {
+            texts.push(ChyronText {
+                text: revision_short.to_string(),
+                color: colors.sha,
+                x: column_x,
+                y: title_y,
+                scale: title_scale,
+                font: ChyronFont::Sha,
+            });
+        }
+
+        let mut x_offset = column_x;
+        for ((text, color), text_width) in stats.into_iter().zip(stats_widths) {
+            texts.push(ChyronText {
+                text,
+                color,
+                x: x_offset,
+                y: info_y,
+                scale: info_scale,
+                font: ChyronFont::Stats,
+            });
+            x_offset += text_width + STATS_GAP;
+        }
+
+        let offset = config.shadow_offset_px as i32;
+        let shadow_offsets = match config.text_style {
+            TextStyle::Plain => Vec::new(),
+            TextStyle::Shadow => vec![(offset, offset)],
+            TextStyle::Outline => [-offset, 0, offset]
+                .into_iter()
+                .flat_map(|dx| [-offset, 0, offset].map(|dy| (dx, dy)))
+                .filter(|&offset| offset != (0, 0))
+                .collect(),
+        };
+
+        Self {
+            band_top,
+            band_bottom: band_top + band_height,
+            background: colors.background,
+            texts,
+            shadow_offsets,
+            shadow: colors.shadow,
+        }
+    }
+
+    /// Draw every piece of text onto `canvas`, shadows first so no text is covered by
+    /// another's.
+    fn draw_text(&self, fonts: &ChyronFonts, canvas: &mut RgbaImage) {
+        for &(dx, dy) in &self.shadow_offsets {
+            for text in &self.texts {
+                draw_text_mut(
+                    canvas,
+                    self.shadow,
+                    text.x + dx,
+                    text.y + dy,
+                    text.scale,
+                    fonts.get(text.font),
+                    &text.text,
+                );
+            }
+        }
+        for text in &self.texts {
+            draw_text_mut(
+                canvas,
+                text.color,
+                text.x,
+                text.y,
+                text.scale,
+                fonts.get(text.font),
+                &text.text,
+            );
+        }
+    }
+}
+
+pub fn burn_in_chyron(
+    config: &crate::config::BurnedInChyronConfig,
+    image: DynamicImage,
+    metadata: &CommitMetadata,
+) -> Result<DynamicImage> {
+    let fonts = ChyronFonts::from_config(config)?;
+    overlay_chyron(config, &fonts, image, metadata)
+}
+
+/// Draw the chyron onto `image` using already loaded fonts, or the captions of
+/// `style = "meme"`.
+pub fn overlay_chyron(
+    config: &crate::config::BurnedInChyronConfig,
+    fonts: &ChyronFonts,
+    image: DynamicImage,
+    metadata: &CommitMetadata,
+) -> Result<DynamicImage> {
+    // Work directly with RGBA if already RGBA, otherwise convert
+    let mut rgba_image = match image {
+        DynamicImage::ImageRgba8(img) => img,
+        other => other.to_rgba8(),
+    };
+    if config.style == ChyronStyle::Meme {
+        crate::meme::draw(&fonts.meme, &mut rgba_image, metadata);
+        stamp_badge(config, fonts, &mut rgba_image, metadata);
+        return Ok(DynamicImage::ImageRgba8(rgba_image));
+    }
+    let (width, height) = rgba_image.dimensions();
+    let layout = ChyronLayout::new(config, fonts, width, height, metadata);
+
+    // Manually apply the semi-transparent band color with proper alpha blending
+    let overlay_alpha = config.chyron_opacity;
+    let [br, bg, bb, _] = layout.background.0.map(f32::from);
+    for y in layout.band_top..layout.band_bottom {
+        for x in 0..width {
+            let pixel = rgba_image.get_pixel_mut(x, y);
+            let [r, g, b, a] = pixel.0;
+
+            // Blend: result = overlay * overlay_alpha + background * (1 - overlay_alpha)
+            pixel.0 = [
+                (br * overlay_alpha + r as f32 * (1.0 - overlay_alpha)) as u8,
+                (bg * overlay_alpha + g as f32 * (1.0 - overlay_alpha)) as u8,
+                (bb * overlay_alpha + b as f32 * (1.0 - overlay_alpha)) as u8,
+                a, // Keep original alpha
+            ];
+        }
+    }
+
+    layout.draw_text(fonts, &mut rgba_image);
+    stamp_badge(config, fonts, &mut rgba_image, metadata);
+
+    Ok(DynamicImage::ImageRgba8(rgba_image))
+}
+
+/// Render only the chyron for an image of `width` x `height`: the band and its text (or
+/// the meme captions) on an otherwise fully transparent canvas, for compositing elsewhere.
+pub fn render_chyron_overlay(
+    config: &crate::config::BurnedInChyronConfig,
+    fonts: &ChyronFonts,
+    width: u32,
+    height: u32,
+    metadata: &CommitMetadata,
+) -> RgbaImage {
+    let mut canvas = RgbaImage::new(width, height);
+    if config.style == ChyronStyle::Meme {
+        crate::meme::draw(&fonts.meme, &mut canvas, metadata);
+        stamp_badge(config, fonts, &mut canvas, metadata);
+        return canvas;
+    }
+    let layout = ChyronLayout::new(config, fonts, width, height, metadata);
+
+    let band_alpha = (config.chyron_opacity.clamp(0.0, 1.0) * 255.0).round() as u8;
+    let [r, g, b, _] = layout.background.0;
+    for y in layout.band_top..layout.band_bottom {
+        for x in 0..width {
+            canvas.put_pixel(x, y, Rgba([r, g, b, band_alpha]));
+        }
+    }
+
+    layout.draw_text(fonts, &mut canvas);
+    stamp_badge(config, fonts, &mut canvas, metadata);
+    canvas
+}
+
+/// Stamp the commit type's badge, see [`crate::badge`]. Emoji are drawn like the message.
+fn stamp_badge(
+    config: &crate::config::BurnedInChyronConfig,
+    fonts: &ChyronFonts,
+    canvas: &mut RgbaImage,
+    metadata: &CommitMetadata,
+) {
+    let color = config.colors().unwrap_or_default().message;
+    crate::badge::stamp(config, &fonts.message, color, canvas, &metadata.commit_type);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test]
+    fn test_resolve_font_path_monospace() {
+        // Monospace should always be available
+        let result = resolve_font_path("monospace");
+        assert!(result.is_ok());
+        let path = result.unwrap();
+        assert!(path.exists());
+        assert!(path.is_file());
+    }
+
+    #[test]
+    fn test_resolve_font_path_nonexistent_falls_back() {
+        // Nonexistent font should fall back to monospace
+        let result = resolve_font_path("ThisFontDefinitelyDoesNotExist12345");
+        assert!(result.is_ok());
+        let path = result.unwrap();
+        assert!(path.exists());
+    }
+
+    const MONO: &[u8] = include_bytes!("../tests/fixtures/fonts/DejaVuSansMono.ttf");
+    const SANS: &[u8] = include_bytes!("../tests/fixtures/fonts/DejaVuSans.ttf");
+
+    fn font(data: &'static [u8]) -> FontArc {
+        FontArc::try_from_slice(data).unwrap()
+    }
+
+    #[test]
+    fn test_measure_text_width_monospace_depends_on_length_only() {
+        let scale = PxScale::from(24.0);
+        let narrow = measure_text_width(&font(MONO), scale, "iiii");
+        let wide = measure_text_width(&font(MONO), scale, "MMMM");
+        assert!((narrow - wide).abs() < 0.01, "{narrow} vs {wide}");
+    }
+
+    #[test]
+    fn test_measure_text_width_proportional_depends_on_glyphs() {
+        let scale = PxScale::from(24.0);
+        let narrow = measure_text_width(&font(SANS), scale, "iiii");
+        let wide = measure_text_width(&font(SANS), scale, "MMMM");
+        assert!(narrow * 2.0 < wide, "{narrow} vs {wide}");
+        // Same number of characters, so the old chars * 10 estimate couldn't tell
+        assert_eq!("iiii".len(), "MMMM".len());
+    }
+
+    #[test]
+    fn test_measure_text_width_scales() {
+        let small = measure_text_width(&font(SANS), PxScale::from(12.0), "+1234");
+        let large = measure_text_width(&font(SANS), PxScale::from(24.0), "+1234");
+        assert!((large - 2.0 * small).abs() < 0.5, "{small} vs {large}");
+    }
+
+    #[test_case("short", "short" ; "fits")]
+    #[test_case("a much longer commit message than fits", "a much…" ; "truncated")]
+    #[test_case("", "" ; "empty")]
+    fn test_truncate_to_width(text: &str, expected: &str) {
+        let font = font(MONO);
+        let scale = PxScale::from(20.0);
+        let max_width = measure_text_width(&font, scale, "a much…");
+        assert_eq!(truncate_to_width(&font, scale, text, max_width), expected);
+    }
+
+    fn layout_metadata(message: &str, stats: (u32, u32, u32)) -> CommitMetadata {
+        CommitMetadata {
+            path: PathBuf::new(),
+            revision: "abcdef0123".to_string(),
+            message: message.to_string(),
+            commit_type: "feat".to_string(),
+            scope: String::new(),
+            timestamp: "2024-01-15 12:34:56".to_string(),
+            repo_name: "repo".to_string(),
+            branch_name: "main".to_string(),
+            author_name: String::new(),
+            author_email: String::new(),
+            breaking: false,
+            co_authors: Vec::new(),
+            stats: crate::git::DiffStats {
+                files_changed: stats.0,
+                insertions: stats.1,
+                deletions: stats.2,
+            },
+        }
+    }
+
+    fn text_extent(layout: &ChyronLayout, fonts: &ChyronFonts, font: ChyronFont) -> (i32, i32) {
+        let texts: Vec<_> = layout.texts.iter().filter(|t| t.font == font).collect();
+        let left = texts.iter().map(|t| t.x).min().unwrap();
+        let right = texts
+            .iter()
+            .map(|t| t.x + measure_text_width(fonts.get(font), t.scale, &t.text).ceil() as i32)
+            .max()
+            .unwrap();
+        (left, right)
+    }
+
+    #[test_case(MONO ; "monospace")]
+    #[test_case(SANS ; "proportional")]
+    fn test_layout_right_column_fits(data: &'static [u8]) {
+        let fonts = ChyronFonts::uniform(font(data));
+        let config = crate::config::BurnedInChyronConfig::default();
+        let metadata = layout_metadata("feat: wide stats", (12, 4567, 890));
+
+        let layout = ChyronLayout::new(&config, &fonts, 640, 480, &metadata);
+
+        let (sha_left, sha_right) = text_extent(&layout, &fonts, ChyronFont::Sha);
+        let (stats_left, stats_right) = text_extent(&layout, &fonts, ChyronFont::Stats);
+        assert_eq!(sha_left, stats_left);
+        assert_eq!(sha_right.max(stats_right), 640 - RIGHT_MARGIN);
+    }
+
+    #[test_case(MONO ; "monospace")]
+    #[test_case(SANS ; "proportional")]
+    fn test_layout_truncates_message_before_revision(data: &'static [u8]) {
+        let fonts = ChyronFonts::uniform(font(data));
+        let config = crate::config::BurnedInChyronConfig::default();
+        let metadata = layout_metadata(
+            "feat: a commit message far too long to fit next to the revision in the chyron",
+            (1, 2, 3),
+        );
+
+        let layout = ChyronLayout::new(&config, &fonts, 640, 480, &metadata);
+
+        let (_, message_right) = text_extent(&layout, &fonts, ChyronFont::Message);
+        let (sha_left, _) = text_extent(&layout, &fonts, ChyronFont::Sha);
+        assert!(message_right <= sha_left - COLUMN_GAP);
+        assert!(layout.texts[0].text.ends_with('…'));
+    }
+
+    #[test_case("short", &["short"] ; "fits")]
+    #[test_case("wrap this message", &["wrap this", "message"] ; "breaks at space")]
+    #[test_case("wrap this long message onto lines", &["wrap this", "long mes…"] ; "second line truncated")]
+    #[test_case("unbreakablewords", &["unbreakab", "lewords"] ; "breaks mid word")]
+    #[test_case("çàfé ünï ✓✓✓✓", &["çàfé ünï", "✓✓✓✓"] ; "multibyte")]
+    #[test_case("", &[""] ; "empty")]
+    fn test_wrap_to_width(text: &str, expected: &[&str]) {
+        let font = font(MONO);
+        let scale = PxScale::from(20.0);
+        let max_width = measure_text_width(&font, scale, "wrap this");
+        assert_eq!(wrap_to_width(&font, scale, text, max_width), expected);
+    }
+
+    #[test]
+    fn test_shrink_to_width() {
+        let font = font(SANS);
+        let scale = PxScale::from(28.0);
+        let text = "a message a little too long";
+        let max_width = measure_text_width(&font, scale, text) * 0.8;
+
+        let shrunk = shrink_to_width(&font, scale, text, max_width);
+
+        assert!(
+            shrunk.y < scale.y && shrunk.y > scale.y * 0.75,
+            "{shrunk:?}"
+        );
+        assert!(measure_text_width(&font, shrunk, text) <= max_width);
+        assert_eq!(shrink_to_width(&font, scale, "short", max_width), scale);
+        // Never below the minimum, however long the text
+        let huge = "x".repeat(500);
+        assert_eq!(
+            shrink_to_width(&font, scale, &huge, max_width).y,
+            scale.y * MIN_SHRINK_FACTOR
+        );
+    }
+
+    fn overflow_layout(overflow: MessageOverflow, message: &str) -> (ChyronLayout, ChyronFonts) {
+        let fonts = ChyronFonts::uniform(font(SANS));
+        let config = crate::config::BurnedInChyronConfig {
+            message_overflow: overflow,
+            ..Default::default()
+        };
+        let metadata = layout_metadata(message, (1, 2, 3));
+        let layout = ChyronLayout::new(&config, &fonts, 640, 480, &metadata);
+        (layout, fonts)
+    }
+
+    const LONG_MESSAGE: &str =
+        "feat: a commit message far too long to fit next to the revision in the chyron";
+
+    #[test]
+    fn test_layout_wrap_grows_band() {
+        let (layout, fonts) = overflow_layout(MessageOverflow::Wrap, LONG_MESSAGE);
+
+        let lines: Vec<_> = layout
+            .texts
+            .iter()
+            .filter(|t| t.font == ChyronFont::Message)
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].y > lines[0].y);
+        assert!(layout.band_top < 480 - CHYRON_HEIGHT);
+        let info = layout.texts.iter().find(|t| t.font == ChyronFont::Info);
+        assert!(info.unwrap().y > lines[1].y);
+        let (_, message_right) = text_extent(&layout, &fonts, ChyronFont::Message);
+        let (sha_left, _) = text_extent(&layout, &fonts, ChyronFont::Sha);
+        assert!(message_right <= sha_left - COLUMN_GAP);
+    }
+
+    #[test]
+    fn test_layout_wrap_short_message_keeps_band() {
+        let (layout, _) = overflow_layout(MessageOverflow::Wrap, "feat: short");
+        assert_eq!(layout.band_top, 480 - CHYRON_HEIGHT);
+    }
+
+    #[test]
+    fn test_layout_shrink_fits_message() {
+        let message = "feat: a message slightly too long for the chyron space";
+        let (layout, fonts) = overflow_layout(MessageOverflow::Shrink, message);
+
+        let text = &layout.texts[0];
+        assert_eq!(text.font, ChyronFont::Message);
+        assert_eq!(
+            text.text,
+            "a message slightly too long for the chyron space"
+        );
+        assert!(text.scale.y < crate::config::BurnedInChyronConfig::default().title_font_size);
+        assert_eq!(layout.band_top, 480 - CHYRON_HEIGHT);
+        let (_, message_right) = text_extent(&layout, &fonts, ChyronFont::Message);
+        let (sha_left, _) = text_extent(&layout, &fonts, ChyronFont::Sha);
+        assert!(message_right <= sha_left - COLUMN_GAP);
+    }
+
+    #[test_case(true, "Zoë Example", "FEAT • repo • Zoë Example" ; "shown")]
+    #[test_case(false, "Zoë Example", "FEAT • repo" ; "hidden")]
+    #[test_case(true, "", "FEAT • repo" ; "unknown author")]
+    fn test_layout_info_line_author(show_author: bool, author_name: &str, expected: &str) {
+        let fonts = ChyronFonts::uniform(font(SANS));
+        let config = crate::config::BurnedInChyronConfig {
+            show_author,
+            ..Default::default()
+        };
+        let metadata = CommitMetadata {
+            author_name: author_name.to_string(),
+            ..layout_metadata("feat: authored", (1, 2, 3))
+        };
+
+        let layout = ChyronLayout::new(&config, &fonts, 640, 480, &metadata);
+
+        let info = layout.texts.iter().find(|t| t.font == ChyronFont::Info);
+        assert_eq!(info.unwrap().text, expected);
+    }
+
+    #[test_case(0, "FEAT • repo" ; "none")]
+    #[test_case(1, "FEAT • repo • +1 co-author" ; "one")]
+    #[test_case(3, "FEAT • repo • +3 co-authors" ; "several")]
+    fn test_layout_info_line_co_authors(count: usize, expected: &str) {
+        let fonts = ChyronFonts::uniform(font(SANS));
+        let metadata = CommitMetadata {
+            co_authors: vec!["Sam Pair <sam@example.com>".to_string(); count],
+            ..layout_metadata("feat: paired", (1, 2, 3))
+        };
+
+        let layout = ChyronLayout::new(&Default::default(), &fonts, 640, 480, &metadata);
+
+        let info = layout.texts.iter().find(|t| t.font == ChyronFont::Info);
+        assert_eq!(info.unwrap().text, expected);
+    }
+
+    #[test]
+    fn test_layout_breaking_change_type_in_red() {
+        let fonts = ChyronFonts::uniform(font(SANS));
+        let metadata = CommitMetadata {
+            breaking: true,
+            ..layout_metadata("feat!: drop v1 API", (1, 2, 3))
+        };
+
+        let layout = ChyronLayout::new(&Default::default(), &fonts, 640, 480, &metadata);
+
+        let info: Vec<_> = layout
+            .texts
+            .iter()
+            .filter(|t| t.font == ChyronFont::Info)
+            .collect();
+        assert_eq!(info.len(), 2);
+        assert_eq!(
+            (info[0].text.as_str(), info[0].color),
+            ("FEAT!", Rgba([255, 0, 0, 255]))
+        );
+        assert_eq!(info[1].text, " • repo");
+        assert_eq!(info[0].y, info[1].y);
+        assert!(info[1].x > info[0].x);
+    }
+
+    #[test_case(None, None, 480, 80 ; "default")]
+    #[test_case(None, None, 2160, 360 ; "default grows with tall images")]
+    #[test_case(None, None, 120, 80 ; "default on tiny images")]
+    #[test_case(None, Some(0.25), 480, 120 ; "fraction")]
+    #[test_case(Some(50), Some(0.25), 480, 50 ; "pixels win")]
+    fn test_chyron_height(
+        height_px: Option<u32>,
+        height_fraction: Option<f32>,
+        image_height: u32,
+        expected: u32,
+    ) {
+        let config = crate::config::BurnedInChyronConfig {
+            height_px,
+            height_fraction,
+            ..Default::default()
+        };
+        assert_eq!(chyron_height(&config, image_height), expected);
+    }
+
+    #[test]
+    fn test_layout_top_position_with_margins_and_colors() {
+        let fonts = ChyronFonts::uniform(font(SANS));
+        let config = crate::config::BurnedInChyronConfig {
+            position: ChyronPosition::Top,
+            height_px: Some(120),
+            margin_px: Some(40),
+            message_color: "#00ff00".to_string(),
+            stats_color: Some("#123456".to_string()),
+            background_color: "#003366".to_string(),
+            ..Default::default()
+        };
+
+        let layout = ChyronLayout::new(
+            &config,
+            &fonts,
+            640,
+            480,
+            &layout_metadata("feat: top", (1, 2, 3)),
+        );
+
+        assert_eq!((layout.band_top, layout.band_bottom), (0, 120));
+        assert_eq!(layout.background, Rgba([0x00, 0x33, 0x66, 255]));
+        let message = &layout.texts[0];
+        assert_eq!((message.x, message.color), (40, Rgba([0, 255, 0, 255])));
+        // Centred in the taller band
+        assert_eq!(message.y, 10 + 20);
+        let stats: Vec<_> = layout
+            .texts
+            .iter()
+            .filter(|t| t.font == ChyronFont::Stats)
+            .collect();
+        assert_eq!(stats.len(), 3);
+        assert!(
+            stats
+                .iter()
+                .all(|t| t.color == Rgba([0x12, 0x34, 0x56, 255]))
+        );
+        let right = stats
+            .last()
+            .map(|t| t.x + measure_text_width(&fonts.stats, t.scale, &t.text).ceil() as i32);
+        let sha = layout
+            .texts
+            .iter()
+            .find(|t| t.font == ChyronFont::Sha)
+            .unwrap();
+        let sha_right = sha.x + measure_text_width(&fonts.sha, sha.scale, &sha.text).ceil() as i32;
+        assert_eq!(right.unwrap().max(sha_right), 640 - 40);
+    }
+
+    #[test_case(TextStyle::Plain ; "plain")]
+    #[test_case(TextStyle::Shadow ; "shadow")]
+    #[test_case(TextStyle::Outline ; "outline")]
+    fn test_text_style_draws_dark_pixels_beside_glyphs(style: TextStyle) -> Result {
+        let fonts = ChyronFonts::uniform(font(SANS));
+        let red = "#ff0000".to_string();
+        // A transparent band leaves the canvas white, so only the text and its
+        // shadow show up
+        let config = crate::config::BurnedInChyronConfig {
+            text_style: style,
+            chyron_opacity: 0.0,
+            message_color: red.clone(),
+            info_color: red.clone(),
+            sha_color: red.clone(),
+            stats_color: Some(red),
+            ..Default::default()
+        };
+        let white = DynamicImage::ImageRgba8(RgbaImage::from_pixel(320, 120, Rgba([255; 4])));
+
+        let rendered = overlay_chyron(
+            &config,
+            &fonts,
+            white,
+            &layout_metadata("feat: readable", (1, 2, 3)),
+        )?
+        .to_rgba8();
+
+        let is_dark = |p: &Rgba<u8>| p.0[..3].iter().all(|&c| c < 64);
+        let is_glyph = |p: &Rgba<u8>| p.0[0] > 192 && p.0[1] < 64;
+        let near_glyph = |x: u32, y: u32| {
+            (x.saturating_sub(2)..=x + 2).any(|gx| {
+                (y.saturating_sub(2)..=y + 2)
+                    .any(|gy| rendered.get_pixel_checked(gx, gy).is_some_and(is_glyph))
+            })
+        };
+        let dark: Vec<(u32, u32)> = rendered
+            .enumerate_pixels()
+            .filter(|(_, _, p)| is_dark(p))
+            .map(|(x, y, _)| (x, y))
+            .collect();
+        let leftmost_glyph = rendered
+            .enumerate_pixels()
+            .filter(|(_, _, p)| is_glyph(p))
+            .map(|(x, _, _)| x)
+            .min()
+            .unwrap();
+        let leftmost_dark = dark.iter().map(|&(x, _)| x).min();
+
+        match style {
+            TextStyle::Plain => assert!(dark.is_empty()),
+            TextStyle::Shadow => {
+                assert!(dark.iter().any(|&(x, y)| near_glyph(x, y)));
+                // Only below and to the right of the text
+                assert!(leftmost_dark.unwrap() > leftmost_glyph);
+            }
+            TextStyle::Outline => {
+                assert!(dark.iter().any(|&(x, y)| near_glyph(x, y)));
+                assert!(leftmost_dark.unwrap() < leftmost_glyph);
+            }
+        }
+        Ok(())
+    }
+
+    #[test_case(MessageOverflow::Truncate ; "truncate")]
+    #[test_case(MessageOverflow::Wrap ; "wrap")]
+    #[test_case(MessageOverflow::Shrink ; "shrink")]
+    fn test_layout_empty_message(overflow: MessageOverflow) {
+        let (layout, _) = overflow_layout(overflow, "");
+        assert_eq!(layout.texts[0].text, "");
+        assert_eq!(layout.band_top, 480 - CHYRON_HEIGHT);
+    }
+
+    #[test]
+    fn test_load_font_monospace() {
+        // Test loading monospace font
+        let result = FontCache::default().load("monospace");
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_font_cache_loads_each_font_once() -> Result {
+        let cache = FontCache::default();
+        let config = crate::config::BurnedInChyronConfig::default();
+
+        // Every chyron element uses monospace by default
+        for _ in 0..5 {
+            ChyronFonts::from_cache(&config, &cache)?;
+        }
+        assert_eq!(cache.len(), 1);
+
+        let mixed = crate::config::BurnedInChyronConfig {
+            message_font_name: Some("sans-serif".to_string()),
+            ..Default::default()
+        };
+        ChyronFonts::from_cache(&mixed, &cache)?;
+        ChyronFonts::from_cache(&mixed, &cache)?;
+        let expected = if resolve_font_path("sans-serif")? == resolve_font_path("monospace")? {
+            1
+        } else {
+            2
+        };
+        assert_eq!(cache.len(), expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_font_cache_falls_back_for_unknown_fonts() -> Result {
+        let cache = FontCache::default();
+
+        cache.load("ThisFontDefinitelyDoesNotExist12345")?;
+        cache.load("ThisFontDefinitelyDoesNotExist12345")?;
+
+        // Whatever fontconfig substitutes is loaded once
+        assert_eq!(cache.len(), 1);
+        Ok(())
+    }
+}
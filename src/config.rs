@@ -1,5 +1,6 @@
 use crate::error::{Error, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use xdg::BaseDirectories;
 
@@ -9,12 +10,48 @@ const XDG_PREFIX: &str = "lolcommits";
 /// Default configuration file name within the config directory.
 const CONFIG_FILE_NAME: &str = "config.toml";
 
+/// Per-repository config at the top of the working tree, see [`Config::load_for_repo`].
+pub const REPO_CONFIG_FILE_NAME: &str = ".lolcommits.toml";
+
+/// Prefix of the environment variables that override settings, see [`Config::apply_env`].
+pub const ENV_PREFIX: &str = "LOLCOMMITS_";
+
+/// Shorthand environment variables (after [`ENV_PREFIX`]) and the keys they set.
+const ENV_SHORTHANDS: &[(&str, &str)] = &[
+    ("SERVER_URL", "client.server_url"),
+    ("IMAGES_DIR", "server.images_dir"),
+    ("MODELS_DIR", "server.models_dir"),
+];
+
+/// Sections (and keys) of the repository config that are merged, the rest are ignored.
+const REPO_CONFIG_KEYS: &[&str] = &["enabled", "client", "burned_in_chyron"];
+
+/// Most warmup frames `validate` accepts, several seconds of video at any frame rate.
+const MAX_CAMERA_WARMUP_FRAMES: usize = 300;
+
+/// Longest `capture_delay_secs` (and `lolcommits_upload --delay`) allowed, so a hook can't
+/// be left waiting on a typo.
+pub const MAX_CAPTURE_DELAY_SECS: u64 = 30;
+
 /// Configuration for a single camera device.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CameraDeviceConfig {
-    /// Device path or index (e.g., "/dev/video0", "0", "/dev/video-ugreen")
+    /// Device path or index (e.g., "/dev/video0", "0", "/dev/video-ugreen"), or the URL
+    /// of a network camera
     pub device: String,
 
+    /// How to capture from `device`.
+    #[serde(default)]
+    pub kind: CameraKind,
+
+    /// Basic auth user for an `http-snapshot` camera.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+
+    /// Basic auth password for an `http-snapshot` camera.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+
     /// Camera pixel format: "YUYV", "MJPEG", "NV12", "GRAY". If not set, auto-detects.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub format: Option<String>,
@@ -30,6 +67,123 @@ pub struct CameraDeviceConfig {
     /// Camera frame rate. If not set, auto-detects.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub fps: Option<u32>,
+
+    /// Flip frames left to right, for a "selfie view". Applied after `rotate`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub mirror: bool,
+
+    /// Clockwise rotation of frames, for cameras mounted sideways or upside down.
+    #[serde(default, skip_serializing_if = "Rotation::is_none")]
+    pub rotate: Rotation,
+
+    /// Part of the rotated, mirrored frame to keep.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crop: Option<Crop>,
+
+    /// Camera controls to set once the stream is open, by name as `v4l2-ctl --list-ctrls`
+    /// shows them, e.g. `white_balance_temperature_auto = 0`. Values persist on the
+    /// device after capture; they aren't restored.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub controls: BTreeMap<String, i64>,
+}
+
+/// The kinds of camera lolcommits can capture from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CameraKind {
+    /// A local camera, by path or index, or any device string the camera backend
+    /// understands.
+    #[default]
+    V4l2,
+    /// A network camera serving a JPEG snapshot at the `device` URL.
+    HttpSnapshot,
+    /// An RTSP stream. Reserved: the camera backend can't open stream URLs, so configs
+    /// asking for it are rejected when loaded.
+    Rtsp,
+}
+
+/// Clockwise rotation of a camera's frames, configured as `0`, `90`, `180` or `270`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(try_from = "u16", into = "u16")]
+pub enum Rotation {
+    #[default]
+    None,
+    Quarter,
+    Half,
+    ThreeQuarters,
+}
+
+impl Rotation {
+    pub fn is_none(&self) -> bool {
+        *self == Rotation::None
+    }
+}
+
+impl TryFrom<u16> for Rotation {
+    type Error = String;
+
+    fn try_from(degrees: u16) -> std::result::Result<Self, Self::Error> {
+        match degrees {
+            0 => Ok(Rotation::None),
+            90 => Ok(Rotation::Quarter),
+            180 => Ok(Rotation::Half),
+            270 => Ok(Rotation::ThreeQuarters),
+            _ => Err(format!("rotate must be 0, 90, 180 or 270, got {degrees}")),
+        }
+    }
+}
+
+impl From<Rotation> for u16 {
+    fn from(rotation: Rotation) -> Self {
+        match rotation {
+            Rotation::None => 0,
+            Rotation::Quarter => 90,
+            Rotation::Half => 180,
+            Rotation::ThreeQuarters => 270,
+        }
+    }
+}
+
+/// Part of a camera's frame to keep: a rectangle in pixels, or the largest centred
+/// rectangle of an aspect ratio such as `"4:3"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Crop {
+    Rect {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+    Aspect(AspectRatio),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct AspectRatio {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl TryFrom<String> for AspectRatio {
+    type Error = String;
+
+    fn try_from(ratio: String) -> std::result::Result<Self, Self::Error> {
+        let parsed = ratio.split_once(':').and_then(|(width, height)| {
+            let width: u32 = width.trim().parse().ok()?;
+            let height: u32 = height.trim().parse().ok()?;
+            (width > 0 && height > 0).then_some(AspectRatio { width, height })
+        });
+        parsed.ok_or_else(|| {
+            format!("crop must be a rectangle or a ratio like \"4:3\", got {ratio:?}")
+        })
+    }
+}
+
+impl From<AspectRatio> for String {
+    fn from(ratio: AspectRatio) -> Self {
+        format!("{}:{}", ratio.width, ratio.height)
+    }
 }
 
 impl CameraDeviceConfig {
@@ -40,14 +194,164 @@ impl CameraDeviceConfig {
     {
         Self {
             device: device.into(),
+            kind: CameraKind::default(),
+            username: None,
+            password: None,
             format: None,
             width: None,
             height: None,
             fps: None,
+            mirror: false,
+            rotate: Rotation::None,
+            crop: None,
+            controls: BTreeMap::new(),
+        }
+    }
+}
+
+/// How the chyron fits a commit message too long for the space beside the revision.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageOverflow {
+    /// Cut the message short with an ellipsis.
+    #[default]
+    Truncate,
+    /// Continue on a second line, making the chyron taller.
+    Wrap,
+    /// Draw the message in a smaller font, truncating if it still doesn't fit.
+    Shrink,
+}
+
+/// What is drawn behind the chyron's text to keep it readable over a bright photo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TextStyle {
+    #[default]
+    Plain,
+    /// The text in `shadow_color`, offset down and right by `shadow_offset_px`.
+    Shadow,
+    /// The text in `shadow_color` at the 8 surrounding offsets of `shadow_offset_px`.
+    Outline,
+}
+
+/// Which edge of the image the chyron band is drawn along.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChyronPosition {
+    Top,
+    #[default]
+    Bottom,
+}
+
+/// How a burned-in caption looks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChyronStyle {
+    /// A news style band along one edge, see [`crate::chyron`].
+    #[default]
+    Chyron,
+    /// Outlined captions across the top and bottom, see [`crate::meme`].
+    Meme,
+}
+
+/// Which corner of the image a commit type badge is stamped in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BadgeCorner {
+    TopLeft,
+    #[default]
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// OpenCV DNN backend running the segmentation model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DnnBackend {
+    #[default]
+    Opencv,
+    Cuda,
+}
+
+/// Device the segmentation model runs on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DnnTarget {
+    #[default]
+    Cpu,
+    Opencl,
+    Cuda,
+}
+
+/// Built-in segmentation models, see [`crate::segmentation::ModelSpec`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SegmentationModelKind {
+    /// U2Net, 170MB.
+    #[default]
+    U2net,
+    /// The 4MB portable U2Net, less precise around hair and hands.
+    U2netp,
+}
+
+/// File format the server saves processed uploads in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Lossless, with the commit metadata embedded.
+    #[default]
+    Png,
+    /// Lossy at `jpeg_quality`, with the metadata in a JSON sidecar.
+    Jpeg,
+    /// Lossless WebP, with the metadata in a JSON sidecar.
+    Webp,
+}
+
+impl OutputFormat {
+    /// Extension of the saved files.
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Png => "png",
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::Webp => "webp",
         }
     }
 }
 
+/// Which frames of an animated upload get the chyron.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnimationChyron {
+    /// Only the last frame, so the animation ends on the commit.
+    #[default]
+    Last,
+    All,
+}
+
+/// Where `lolcommits_upload` sends captures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptureMode {
+    /// Upload to lolcommitsd, which processes and stores them.
+    #[default]
+    Server,
+    /// Burn the chyron in and save to `local_images_dir`, without a server.
+    Local,
+}
+
+/// Who burns the chyron into uploaded captures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChyronRendering {
+    /// lolcommitsd, per its own config.
+    #[default]
+    Server,
+    /// `lolcommits_upload`, using the `[burned_in_chyron]` settings, for servers that
+    /// don't draw one.
+    Client,
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Config {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -62,6 +366,9 @@ pub struct Config {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BurnedInChyronConfig {
+    #[serde(default)]
+    pub style: ChyronStyle,
+
     #[serde(default = "default_font_name")]
     pub default_font_name: String,
 
@@ -77,6 +384,10 @@ pub struct BurnedInChyronConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stats_font_name: Option<String>,
 
+    /// Font of `style = "meme"` captions, e.g. "Impact".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meme_font_name: Option<String>,
+
     #[serde(default = "default_chyron_opacity")]
     pub chyron_opacity: f32,
 
@@ -85,10 +396,87 @@ pub struct BurnedInChyronConfig {
 
     #[serde(default = "default_info_font_size")]
     pub info_font_size: f32,
+
+    /// Locale for chyron dates and numbers (e.g. "en-US", "de-DE"). Unset or unknown
+    /// locales keep the default rendering: '.' decimals and no timestamp.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+
+    #[serde(default)]
+    pub message_overflow: MessageOverflow,
+
+    /// Add the commit author's name to the info line, for galleries shared by a team.
+    #[serde(default)]
+    pub show_author: bool,
+
+    #[serde(default)]
+    pub position: ChyronPosition,
+
+    /// Band height in pixels. Takes precedence over `height_fraction`; with neither set
+    /// the band is 80px, growing with images taller than 480px.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height_px: Option<u32>,
+
+    /// Band height as a fraction of the image height.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height_fraction: Option<f32>,
+
+    /// Space between the band's sides and the text. Unset keeps 15px on the left and
+    /// 30px on the right.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub margin_px: Option<u32>,
+
+    /// Hex colors (`#rrggbb`), checked when the config is loaded.
+    #[serde(default = "default_background_color")]
+    pub background_color: String,
+
+    #[serde(default = "default_message_color")]
+    pub message_color: String,
+
+    #[serde(default = "default_info_color")]
+    pub info_color: String,
+
+    #[serde(default = "default_sha_color")]
+    pub sha_color: String,
+
+    /// One color for all the stats. Unset keeps yellow files, green insertions and red
+    /// deletions.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats_color: Option<String>,
+
+    #[serde(default)]
+    pub text_style: TextStyle,
+
+    /// Distance in pixels of the shadow or outline from the text.
+    #[serde(default = "default_shadow_offset_px")]
+    pub shadow_offset_px: u32,
+
+    #[serde(default = "default_shadow_color")]
+    pub shadow_color: String,
+
+    /// Badge stamped on the image by commit type, gitmoji style: an emoji, drawn with
+    /// the message font, or the path of a PNG. `default` is used for types without one.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub badges: BTreeMap<String, String>,
+
+    #[serde(default)]
+    pub badge_corner: BadgeCorner,
+
+    /// Height of an emoji badge, and the square a PNG badge is scaled to fit.
+    #[serde(default = "default_badge_size_px")]
+    pub badge_size_px: u32,
+
+    #[serde(default = "default_badge_opacity")]
+    pub badge_opacity: f32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClientConfig {
+    /// Capture at all. Set `enabled = false` in a repository's `.lolcommits.toml` to opt
+    /// that repository out.
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+
     /// List of camera devices to try in order. First working camera is used.
     /// Each camera can have its own format/resolution settings.
     #[serde(default = "default_camera_devices")]
@@ -97,11 +485,143 @@ pub struct ClientConfig {
     #[serde(default = "default_camera_warmup_frames")]
     pub camera_warmup_frames: usize,
 
+    /// Minimum time to keep discarding frames before the real capture, for cameras
+    /// whose auto-exposure settles slowly. Applies on top of `camera_warmup_frames`.
+    #[serde(default)]
+    pub camera_warmup_ms: u64,
+
+    /// Seconds to wait after warmup, with the stream open, before taking the capture, to
+    /// straighten up. A countdown is shown when run in a terminal. At most
+    /// [`MAX_CAPTURE_DELAY_SECS`]; `lolcommits_upload --delay` overrides it.
+    #[serde(default)]
+    pub capture_delay_secs: u64,
+
+    /// How many more times to try a busy camera before moving on to the next device.
+    #[serde(default = "default_camera_busy_retries")]
+    pub camera_busy_retries: u32,
+
+    #[serde(default = "default_camera_busy_retry_delay_ms")]
+    pub camera_busy_retry_delay_ms: u64,
+
+    /// Give up on a camera that hasn't delivered its frames after this many seconds, on
+    /// top of the warmup and animation time, and move on to the next device. 0 waits
+    /// forever.
+    #[serde(default = "default_capture_timeout_secs")]
+    pub capture_timeout_secs: u64,
+
+    /// Still image (PNG, JPEG, ...) to upload instead of capturing from a camera, for
+    /// headless machines. `lolcommits_upload --from-file` overrides it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capture_source: Option<PathBuf>,
+
+    /// Largest `capture_source` file accepted, in bytes.
+    #[serde(default = "default_capture_source_max_bytes")]
+    pub capture_source_max_bytes: u64,
+
+    /// Scale a `capture_source` image down to at most this many pixels on its longest
+    /// side, e.g. for phone photos. Uploaded at its own size when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capture_source_max_dimension: Option<u32>,
+
+    /// Upload captures, or process and save them locally. Also set by `--local`.
+    #[serde(default)]
+    pub mode: CaptureMode,
+
+    /// Whether uploads get their chyron from the server or already have it burned in.
+    /// Local mode always burns it in.
+    #[serde(default)]
+    pub chyron_rendering: ChyronRendering,
+
+    /// Where captures are saved in local mode.
+    #[serde(default = "default_local_images_dir")]
+    pub local_images_dir: String,
+
+    /// Also keep each capture as the camera took it, before any processing, in this
+    /// directory. Off when unset. Also set by `--save-raw`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub save_raw_dir: Option<String>,
+
     #[serde(default = "default_server_url")]
     pub server_url: String,
 
+    /// Accept any certificate from an `https://` server_url, for self-signed ones.
+    #[serde(default)]
+    pub tls_skip_verify: bool,
+
     #[serde(default = "default_server_upload_timeout_secs")]
     pub server_upload_timeout_secs: u64,
+
+    /// Ask the server whether the revision is already in the gallery before using the
+    /// camera, skipping the capture when it is. Ignored with `--force`.
+    #[serde(default)]
+    pub precheck_duplicates: bool,
+
+    /// Exit with an error when the server already has the revision, rather than
+    /// reporting it and exiting 0.
+    #[serde(default)]
+    pub fail_on_duplicate: bool,
+
+    /// Wait for the server to finish processing the upload and report how it went,
+    /// rather than returning once it is accepted. Also set by `--wait`.
+    #[serde(default)]
+    pub wait_for_processing: bool,
+
+    /// How long to wait for processing before giving up.
+    #[serde(default = "default_wait_timeout_secs")]
+    pub wait_timeout_secs: u64,
+
+    /// How many times to retry an upload that couldn't reach the server, got a 5xx, or
+    /// that the server asked to come back later (429/503 with Retry-After).
+    #[serde(default = "default_upload_retries")]
+    pub upload_retries: u32,
+
+    /// First wait before retrying a failed upload, doubling with each retry and jittered.
+    /// Retry-After waits are taken as the server asks instead.
+    #[serde(default = "default_upload_retry_base_ms")]
+    pub upload_retry_base_ms: u64,
+
+    /// Upper bound on a single Retry-After wait, however long the server asks for.
+    #[serde(default = "default_upload_max_retry_after_secs")]
+    pub upload_max_retry_after_secs: u64,
+
+    /// A capture encoding larger than the server's `max_upload_bytes` is scaled down
+    /// to at most this many pixels on its longest side before uploading.
+    #[serde(default = "default_upload_max_dimension")]
+    pub upload_max_dimension: u32,
+
+    /// Where captures that couldn't reach the server are queued for a later upload.
+    #[serde(default = "default_spool_dir")]
+    pub spool_dir: String,
+
+    /// Most captures kept in the spool, dropping the oldest beyond it. 0 disables spooling.
+    #[serde(default = "default_spool_max_entries")]
+    pub spool_max_entries: usize,
+
+    /// Spooled captures older than this are dropped rather than uploaded.
+    #[serde(default = "default_spool_max_age_days")]
+    pub spool_max_age_days: u64,
+
+    /// Glob patterns, relative to the repository root, for files left out of the diff
+    /// stats (lockfiles and generated code). Empty counts every file.
+    #[serde(default = "default_stats_exclude")]
+    pub stats_exclude: Vec<String>,
+
+    /// Capture a burst of frames for an animated GIF instead of a single still.
+    #[serde(default)]
+    pub animate: bool,
+
+    /// Frames in an animated capture.
+    #[serde(default = "default_animate_frames")]
+    pub animate_frames: usize,
+
+    /// How long an animated capture lasts, spread evenly over its frames.
+    #[serde(default = "default_animate_duration_ms")]
+    pub animate_duration_ms: u64,
+
+    /// Frames wider than this are scaled down before uploading, keeping the upload
+    /// and the GIF small.
+    #[serde(default = "default_animate_max_width")]
+    pub animate_max_width: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -109,29 +629,241 @@ pub struct ServerConfig {
     #[serde(default = "default_background_path")]
     pub background_path: String,
 
+    /// Background path specs by conventional commit type (e.g. `fix = "extinguisher"`),
+    /// resolved like `background_path`, which is used for other types and for any of
+    /// these that can't be found.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub backgrounds: BTreeMap<String, String>,
+
     #[serde(default = "default_center_person")]
     pub center_person: bool,
 
+    /// Largest fraction of the person that centering may push out of frame.
+    #[serde(default = "default_center_person_max_off_frame")]
+    pub center_person_max_off_frame: f32,
+
     #[serde(default = "default_gallery_title")]
     pub gallery_title: String,
 
+    /// How many of the newest lolcommits `/feed.xml` lists.
+    #[serde(default = "default_feed_entries")]
+    pub feed_entries: usize,
+
+    /// Most image data accepted in one upload, summed over its frames. Read at startup.
+    #[serde(default = "default_max_upload_bytes")]
+    pub max_upload_bytes: usize,
+
+    /// Uploads processed at once. Read at startup.
+    #[serde(default = "default_processing_concurrency")]
+    pub processing_concurrency: usize,
+
+    /// Uploads that may wait for a processing slot before more are refused with a 503.
+    /// Read at startup.
+    #[serde(default = "default_processing_backlog")]
+    pub processing_backlog: usize,
+
+    /// Longest an upload may take to process before it is given up on and kept in
+    /// `images_dir/failed` (see [`crate::failed_uploads`]).
+    #[serde(default = "default_processing_timeout_secs")]
+    pub processing_timeout_secs: u64,
+
     #[serde(default = "default_images_dir")]
     pub images_dir: String,
 
     #[serde(default = "default_models_dir")]
     pub models_dir: String,
 
+    /// Gallery frontend files served in place of the built-in ones, when the directory
+    /// has an `index.html`. Files it lacks are still served built in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub static_dir: Option<String>,
+
+    /// Backend and target for segmentation inference. Falls back to the CPU when the
+    /// requested combination fails to load or run. Read at startup.
+    #[serde(default)]
+    pub dnn_backend: DnnBackend,
+
+    #[serde(default)]
+    pub dnn_target: DnnTarget,
+
     #[serde(default = "default_bind_address")]
     pub bind_address: String,
 
     #[serde(default = "default_bind_port")]
     pub bind_port: u16,
 
+    /// PEM certificate chain and private key to serve HTTPS with. Both or neither;
+    /// without them the server speaks plain HTTP.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls_cert_path: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls_key_path: Option<String>,
+
     #[serde(default)]
     pub log_output: crate::LogOutput,
 
     #[serde(default = "default_burned_in_chyron")]
     pub burned_in_chyron: bool,
+
+    /// Absolute URL the gallery is publicly reachable at (e.g. "https://lol.example.com/gallery").
+    /// When unset, image URLs are root-relative and respect `mount_prefix`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_base_url: Option<String>,
+
+    /// Path prefix the gallery is mounted under behind a reverse proxy (e.g. "/lolcommits").
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub mount_prefix: String,
+
+    /// Start in read-only mode (serve the gallery, refuse uploads). Can be toggled at
+    /// runtime via the admin API, which persists its choice in `state_dir`.
+    #[serde(default)]
+    pub read_only: bool,
+
+    #[serde(default = "default_state_dir")]
+    pub state_dir: String,
+
+    /// Format new gallery images are saved in. Existing images keep theirs.
+    #[serde(default)]
+    pub output_format: OutputFormat,
+
+    /// JPEG quality from 1 to 100, with `output_format = "jpeg"`.
+    #[serde(default = "default_jpeg_quality")]
+    pub jpeg_quality: u8,
+
+    /// Which frames of an animated upload get the chyron. Every frame gets the background.
+    #[serde(default)]
+    pub animation_chyron: AnimationChyron,
+
+    /// Animated uploads wider than this are scaled down before processing.
+    #[serde(default = "default_animation_max_width")]
+    pub animation_max_width: u32,
+
+    /// Brighten uploads darker than `low_light_threshold` before processing. Dark uploads
+    /// are logged either way.
+    #[serde(default)]
+    pub auto_exposure_correction: bool,
+
+    /// Mean luminance from 0 to 255 below which an upload counts as taken in low light.
+    #[serde(default = "default_low_light_threshold")]
+    pub low_light_threshold: f32,
+
+    /// Keep each upload as received in `state_dir/originals`, so
+    /// `POST /api/images/{filename}/reprocess` can redo its processing later.
+    #[serde(default)]
+    pub keep_originals: bool,
+
+    /// Run the `--fsck` consistency check over `images_dir` at startup and warn about
+    /// what it finds. Off by default, since it reads every image a second time.
+    #[serde(default)]
+    pub fsck_on_startup: bool,
+
+    /// Bearer token required by the admin API. Admin endpoints are disabled when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub admin_token: Option<String>,
+
+    /// Size of the in-memory cache for served images in MiB, 0 disables it.
+    #[serde(default)]
+    pub image_cache_mb: u64,
+
+    /// Refuse uploads while the images_dir filesystem has less than this much free
+    /// space in MiB, 0 disables the check.
+    #[serde(default = "default_min_free_space_mb")]
+    pub min_free_space_mb: u64,
+
+    /// How long the status of a finished upload job stays available at `/api/jobs/{id}`.
+    #[serde(default = "default_job_ttl_secs")]
+    pub job_ttl_secs: u64,
+
+    /// On SIGTERM or SIGINT, how long to wait for uploads still being processed before
+    /// exiting without them.
+    #[serde(default = "default_shutdown_drain_secs")]
+    pub shutdown_drain_secs: u64,
+
+    /// Post-processing stages applied to uploads, in order (see [`crate::post_processor::STAGES`]).
+    #[serde(default = "crate::post_processor::default_post_processors")]
+    pub post_processors: Vec<String>,
+
+    /// Keys clients may override per upload (see [`crate::overrides::KEYS`]), none by default.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow_overrides: Vec<String>,
+
+    #[serde(default)]
+    pub segmentation: SegmentationConfig,
+}
+
+/// Cleanup of the segmentation mask before compositing. The defaults use the mask as
+/// the model produced it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SegmentationConfig {
+    /// Mask values at or below this become background.
+    #[serde(default)]
+    pub mask_floor: f32,
+
+    /// Mask values at or above this become the person, those between the floor and
+    /// ceiling are stretched to fill the range.
+    #[serde(default = "default_mask_ceiling")]
+    pub mask_ceiling: f32,
+
+    /// Standard deviation in pixels of a Gaussian blur softening the mask's edges,
+    /// 0 for none.
+    #[serde(default)]
+    pub feather_px: f32,
+
+    /// Radius in pixels of a morphological open then close, removing specks and filling
+    /// holes up to about twice as wide, 0 for none.
+    #[serde(default)]
+    pub cleanup_radius_px: u8,
+
+    /// Which built-in model to download and run. Ignored when `model_url` is set.
+    #[serde(default)]
+    pub model: SegmentationModelKind,
+
+    /// A custom ONNX model with a U2Net-style output, instead of a built-in one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model_url: Option<String>,
+
+    /// MD5 of the file at `model_url`, required with it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model_checksum: Option<String>,
+
+    /// Side of the square input the model at `model_url` expects, 320 when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model_input_size: Option<u32>,
+}
+
+impl Default for SegmentationConfig {
+    fn default() -> Self {
+        Self {
+            mask_floor: 0.0,
+            mask_ceiling: default_mask_ceiling(),
+            feather_px: 0.0,
+            cleanup_radius_px: 0,
+            model: SegmentationModelKind::default(),
+            model_url: None,
+            model_checksum: None,
+            model_input_size: None,
+        }
+    }
+}
+
+fn default_mask_ceiling() -> f32 {
+    1.0
+}
+
+fn section(value: impl Serialize) -> Result<toml::Table> {
+    into_section(toml::Value::try_from(value)?)
+}
+
+fn into_section(value: toml::Value) -> Result<toml::Table> {
+    match value {
+        toml::Value::Table(table) => Ok(table),
+        other => Err(<toml::de::Error as serde::de::Error>::custom(format!(
+            "expected a table, found {}",
+            other.type_str()
+        ))
+        .into()),
+    }
 }
 
 fn default_font_name() -> String {
@@ -147,6 +879,10 @@ fn default_background_path() -> String {
         .to_string()
 }
 
+fn default_enabled() -> bool {
+    true
+}
+
 fn default_camera_devices() -> Vec<CameraDeviceConfig> {
     vec![CameraDeviceConfig::new("0")]
 }
@@ -155,10 +891,54 @@ fn default_camera_warmup_frames() -> usize {
     3
 }
 
+fn default_camera_busy_retries() -> u32 {
+    2
+}
+
+fn default_camera_busy_retry_delay_ms() -> u64 {
+    500
+}
+
+fn default_capture_timeout_secs() -> u64 {
+    10
+}
+
 fn default_chyron_opacity() -> f32 {
     0.75
 }
 
+fn default_background_color() -> String {
+    "#000000".to_string()
+}
+
+fn default_message_color() -> String {
+    "#ffffff".to_string()
+}
+
+fn default_info_color() -> String {
+    "#b4b4b4".to_string()
+}
+
+fn default_sha_color() -> String {
+    "#ffff00".to_string()
+}
+
+fn default_shadow_offset_px() -> u32 {
+    2
+}
+
+fn default_badge_size_px() -> u32 {
+    48
+}
+
+fn default_badge_opacity() -> f32 {
+    1.0
+}
+
+fn default_shadow_color() -> String {
+    "#000000".to_string()
+}
+
 fn default_title_font_size() -> f32 {
     28.0
 }
@@ -171,6 +951,10 @@ fn default_center_person() -> bool {
     true
 }
 
+fn default_center_person_max_off_frame() -> f32 {
+    0.25
+}
+
 fn default_burned_in_chyron() -> bool {
     true
 }
@@ -179,6 +963,28 @@ fn default_gallery_title() -> String {
     "Lolcommits Gallery".to_string()
 }
 
+fn default_max_upload_bytes() -> usize {
+    4 * 1024 * 1024
+}
+
+fn default_processing_concurrency() -> usize {
+    std::thread::available_parallelism()
+        .map(std::num::NonZeroUsize::get)
+        .unwrap_or(2)
+}
+
+fn default_processing_backlog() -> usize {
+    16
+}
+
+fn default_processing_timeout_secs() -> u64 {
+    120
+}
+
+fn default_feed_entries() -> usize {
+    20
+}
+
 fn default_server_url() -> String {
     "http://127.0.0.1:3000".to_string()
 }
@@ -187,14 +993,115 @@ fn default_server_upload_timeout_secs() -> u64 {
     30
 }
 
+fn default_wait_timeout_secs() -> u64 {
+    120
+}
+
+fn default_min_free_space_mb() -> u64 {
+    100
+}
+
+fn default_job_ttl_secs() -> u64 {
+    3600
+}
+
+fn default_shutdown_drain_secs() -> u64 {
+    30
+}
+
+fn default_upload_retries() -> u32 {
+    3
+}
+
+fn default_upload_retry_base_ms() -> u64 {
+    500
+}
+
+fn default_upload_max_retry_after_secs() -> u64 {
+    60
+}
+
+fn default_capture_source_max_bytes() -> u64 {
+    25 * 1024 * 1024
+}
+
+fn default_upload_max_dimension() -> u32 {
+    1920
+}
+
+fn default_local_images_dir() -> String {
+    BaseDirectories::with_prefix(XDG_PREFIX)
+        .get_data_home()
+        .expect("XDG not configured")
+        .join("images")
+        .to_string_lossy()
+        .to_string()
+}
+
+fn default_spool_dir() -> String {
+    BaseDirectories::with_prefix(XDG_PREFIX)
+        .get_cache_home()
+        .expect("XDG not configured")
+        .join("spool")
+        .to_string_lossy()
+        .to_string()
+}
+
+fn default_spool_max_entries() -> usize {
+    50
+}
+
+fn default_spool_max_age_days() -> u64 {
+    30
+}
+
+fn default_stats_exclude() -> Vec<String> {
+    [
+        "**/Cargo.lock",
+        "**/package-lock.json",
+        "**/yarn.lock",
+        "**/*.min.js",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
+fn default_animate_frames() -> usize {
+    8
+}
+
+fn default_animate_duration_ms() -> u64 {
+    2000
+}
+
+fn default_animate_max_width() -> u32 {
+    480
+}
+
+fn default_animation_max_width() -> u32 {
+    480
+}
+
 fn default_images_dir() -> String {
     "/var/lib/lolcommits/images".to_string()
 }
 
+fn default_low_light_threshold() -> f32 {
+    60.0
+}
+
+fn default_jpeg_quality() -> u8 {
+    85
+}
+
 fn default_models_dir() -> String {
     "/var/lib/lolcommits/models".to_string()
 }
 
+fn default_state_dir() -> String {
+    "/var/lib/lolcommits/state".to_string()
+}
+
 fn default_bind_address() -> String {
     "0.0.0.0".to_string()
 }
@@ -206,14 +1113,35 @@ fn default_bind_port() -> u16 {
 impl Default for BurnedInChyronConfig {
     fn default() -> Self {
         Self {
+            style: ChyronStyle::default(),
             default_font_name: default_font_name(),
             message_font_name: None,
             info_font_name: None,
             sha_font_name: None,
             stats_font_name: None,
+            meme_font_name: None,
             chyron_opacity: default_chyron_opacity(),
             title_font_size: default_title_font_size(),
             info_font_size: default_info_font_size(),
+            locale: None,
+            message_overflow: MessageOverflow::default(),
+            show_author: false,
+            position: ChyronPosition::default(),
+            height_px: None,
+            height_fraction: None,
+            margin_px: None,
+            background_color: default_background_color(),
+            message_color: default_message_color(),
+            info_color: default_info_color(),
+            sha_color: default_sha_color(),
+            stats_color: None,
+            text_style: TextStyle::default(),
+            shadow_offset_px: default_shadow_offset_px(),
+            shadow_color: default_shadow_color(),
+            badges: BTreeMap::new(),
+            badge_corner: BadgeCorner::default(),
+            badge_size_px: default_badge_size_px(),
+            badge_opacity: default_badge_opacity(),
         }
     }
 }
@@ -221,10 +1149,40 @@ impl Default for BurnedInChyronConfig {
 impl Default for ClientConfig {
     fn default() -> Self {
         Self {
+            enabled: default_enabled(),
             camera_devices: default_camera_devices(),
             camera_warmup_frames: default_camera_warmup_frames(),
+            camera_warmup_ms: 0,
+            capture_delay_secs: 0,
+            camera_busy_retries: default_camera_busy_retries(),
+            camera_busy_retry_delay_ms: default_camera_busy_retry_delay_ms(),
+            capture_timeout_secs: default_capture_timeout_secs(),
+            capture_source: None,
+            capture_source_max_bytes: default_capture_source_max_bytes(),
+            capture_source_max_dimension: None,
+            mode: CaptureMode::default(),
+            chyron_rendering: ChyronRendering::default(),
+            local_images_dir: default_local_images_dir(),
+            save_raw_dir: None,
             server_url: default_server_url(),
+            tls_skip_verify: false,
             server_upload_timeout_secs: default_server_upload_timeout_secs(),
+            precheck_duplicates: false,
+            fail_on_duplicate: false,
+            wait_for_processing: false,
+            wait_timeout_secs: default_wait_timeout_secs(),
+            upload_retries: default_upload_retries(),
+            upload_retry_base_ms: default_upload_retry_base_ms(),
+            upload_max_retry_after_secs: default_upload_max_retry_after_secs(),
+            upload_max_dimension: default_upload_max_dimension(),
+            spool_dir: default_spool_dir(),
+            spool_max_entries: default_spool_max_entries(),
+            spool_max_age_days: default_spool_max_age_days(),
+            stats_exclude: default_stats_exclude(),
+            animate: false,
+            animate_frames: default_animate_frames(),
+            animate_duration_ms: default_animate_duration_ms(),
+            animate_max_width: default_animate_max_width(),
         }
     }
 }
@@ -233,14 +1191,46 @@ impl Default for ServerConfig {
     fn default() -> Self {
         Self {
             background_path: default_background_path(),
+            backgrounds: BTreeMap::new(),
             center_person: default_center_person(),
+            center_person_max_off_frame: default_center_person_max_off_frame(),
             gallery_title: default_gallery_title(),
+            feed_entries: default_feed_entries(),
+            max_upload_bytes: default_max_upload_bytes(),
+            processing_concurrency: default_processing_concurrency(),
+            processing_backlog: default_processing_backlog(),
+            processing_timeout_secs: default_processing_timeout_secs(),
             images_dir: default_images_dir(),
             models_dir: default_models_dir(),
+            static_dir: None,
+            dnn_backend: DnnBackend::default(),
+            dnn_target: DnnTarget::default(),
             bind_address: default_bind_address(),
             bind_port: default_bind_port(),
+            tls_cert_path: None,
+            tls_key_path: None,
             log_output: crate::LogOutput::default(),
             burned_in_chyron: default_burned_in_chyron(),
+            public_base_url: None,
+            mount_prefix: String::new(),
+            read_only: false,
+            state_dir: default_state_dir(),
+            output_format: OutputFormat::default(),
+            jpeg_quality: default_jpeg_quality(),
+            animation_chyron: AnimationChyron::default(),
+            animation_max_width: default_animation_max_width(),
+            auto_exposure_correction: false,
+            low_light_threshold: default_low_light_threshold(),
+            keep_originals: false,
+            fsck_on_startup: false,
+            admin_token: None,
+            image_cache_mb: 0,
+            min_free_space_mb: default_min_free_space_mb(),
+            job_ttl_secs: default_job_ttl_secs(),
+            shutdown_drain_secs: default_shutdown_drain_secs(),
+            post_processors: crate::post_processor::default_post_processors(),
+            allow_overrides: Vec::new(),
+            segmentation: SegmentationConfig::default(),
         }
     }
 }
@@ -253,6 +1243,13 @@ impl BurnedInChyronConfig {
             .unwrap_or(&self.default_font_name)
     }
 
+    /// Get the font name for meme captions, falling back to default_font_name
+    pub fn get_meme_font_name(&self) -> &str {
+        self.meme_font_name
+            .as_deref()
+            .unwrap_or(&self.default_font_name)
+    }
+
     /// Get the font name for info, falling back to default_font_name
     pub fn get_info_font_name(&self) -> &str {
         self.info_font_name
@@ -267,42 +1264,149 @@ impl BurnedInChyronConfig {
             .unwrap_or(&self.default_font_name)
     }
 
-    /// Get the font name for stats, falling back to default_font_name
-    pub fn get_stats_font_name(&self) -> &str {
-        self.stats_font_name
-            .as_deref()
-            .unwrap_or(&self.default_font_name)
+    /// Get the font name for stats, falling back to default_font_name
+    pub fn get_stats_font_name(&self) -> &str {
+        self.stats_font_name
+            .as_deref()
+            .unwrap_or(&self.default_font_name)
+    }
+
+    /// The configured colors, or an error naming the first that isn't a hex color.
+    pub fn colors(&self) -> Result<ChyronColors> {
+        Ok(ChyronColors {
+            background: parse_hex_color("background_color", &self.background_color)?,
+            message: parse_hex_color("message_color", &self.message_color)?,
+            info: parse_hex_color("info_color", &self.info_color)?,
+            sha: parse_hex_color("sha_color", &self.sha_color)?,
+            shadow: parse_hex_color("shadow_color", &self.shadow_color)?,
+            stats: self
+                .stats_color
+                .as_deref()
+                .map(|color| parse_hex_color("stats_color", color))
+                .transpose()?,
+        })
+    }
+}
+
+/// [`BurnedInChyronConfig`]'s colors, parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChyronColors {
+    pub background: image::Rgba<u8>,
+    pub message: image::Rgba<u8>,
+    pub info: image::Rgba<u8>,
+    pub sha: image::Rgba<u8>,
+    pub shadow: image::Rgba<u8>,
+    pub stats: Option<image::Rgba<u8>>,
+}
+
+impl Default for ChyronColors {
+    fn default() -> Self {
+        Self {
+            background: image::Rgba([0, 0, 0, 255]),
+            message: image::Rgba([255, 255, 255, 255]),
+            info: image::Rgba([180, 180, 180, 255]),
+            sha: image::Rgba([255, 255, 0, 255]),
+            shadow: image::Rgba([0, 0, 0, 255]),
+            stats: None,
+        }
+    }
+}
+
+/// Parse `#rrggbb` (the `#` is optional) as an opaque color.
+fn parse_hex_color(field: &'static str, value: &str) -> Result<image::Rgba<u8>> {
+    let invalid = || Error::InvalidColor {
+        field,
+        value: value.to_string(),
+    };
+    let hex = value.strip_prefix('#').unwrap_or(value);
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid());
+    Ok(image::Rgba([channel(0)?, channel(2)?, channel(4)?, 255]))
+}
+
+/// Write a config file, creating its parent directory if needed.
+pub(crate) fn write_file(config_path: &std::path::Path, contents: &str) -> Result {
+    if let Some(parent) = config_path.parent() {
+        std::fs::create_dir_all(parent).map_err(|source| Error::ConfigFileWrite {
+            path: config_path.to_path_buf(),
+            source,
+        })?;
+    }
+
+    std::fs::write(config_path, contents).map_err(|source| Error::ConfigFileWrite {
+        path: config_path.to_path_buf(),
+        source,
+    })?;
+
+    tracing::info!(path = %config_path.display(), "Config saved successfully");
+    Ok(())
+}
+
+/// Deserialize a config along with the dotted paths of the keys it has no setting
+/// for, most likely misspellings, which serde otherwise ignores.
+pub(crate) fn deserialize_with_unknown_keys<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<(Config, Vec<String>), D::Error> {
+    let mut unknown = Vec::new();
+    let config = serde_ignored::deserialize(deserializer, |path| unknown.push(key_path(&path)))?;
+    Ok((config, unknown))
+}
+
+/// `path` the way it's written in a config file, e.g. `client.camera_devices[0].format`.
+fn key_path(path: &serde_ignored::Path) -> String {
+    use serde_ignored::Path;
+    match path {
+        Path::Root => String::new(),
+        Path::Seq { parent, index } => format!("{}[{index}]", key_path(parent)),
+        Path::Map { parent, key } => match key_path(parent) {
+            parent if parent.is_empty() => key.clone(),
+            parent => format!("{parent}.{key}"),
+        },
+        Path::Some { parent }
+        | Path::NewtypeStruct { parent }
+        | Path::NewtypeVariant { parent } => key_path(parent),
     }
 }
 
 impl Config {
-    /// Load configuration from the specified path, or search in hierarchical order:
+    /// Resolve the config file to use: the specified path, or the first found in
+    /// hierarchical order:
     /// 1. /etc/sw1nn/lolcommits/config.toml (system-wide)
     /// 2. XDG_CONFIG_HOME/lolcommits/config.toml (user-specific)
-    pub fn load_from(config_path: Option<PathBuf>) -> Result<Self> {
-        let config_path = if let Some(path) = config_path {
+    pub fn resolve_path(config_path: Option<PathBuf>) -> Result<PathBuf> {
+        if let Some(path) = config_path {
             // Use explicit path if provided
-            path
+            return Ok(path);
+        }
+
+        // Search in hierarchical order
+        let system_config = PathBuf::from("/etc/sw1nn/lolcommits/config.toml");
+
+        if system_config.exists() {
+            tracing::debug!(path = %system_config.display(), "Using system config");
+            Ok(system_config)
         } else {
-            // Search in hierarchical order
-            let system_config = PathBuf::from("/etc/sw1nn/lolcommits/config.toml");
-
-            if system_config.exists() {
-                tracing::debug!(path = %system_config.display(), "Using system config");
-                system_config
-            } else {
-                // Fall back to user config
-                let user_config =
-                    BaseDirectories::with_prefix(XDG_PREFIX).place_config_file(CONFIG_FILE_NAME)?;
-                tracing::debug!(path = %user_config.display(), "Using user config");
-                user_config
-            }
-        };
+            // Fall back to user config
+            let user_config =
+                BaseDirectories::with_prefix(XDG_PREFIX).place_config_file(CONFIG_FILE_NAME)?;
+            tracing::debug!(path = %user_config.display(), "Using user config");
+            Ok(user_config)
+        }
+    }
+
+    /// Load configuration from the specified path, or search in hierarchical order
+    /// (see [`Config::resolve_path`]).
+    pub fn load_from(config_path: Option<PathBuf>) -> Result<Self> {
+        let config_path = Self::resolve_path(config_path)?;
 
         if !config_path.exists() {
             tracing::info!(path = %config_path.display(), "Config file not found, creating default");
-            let default_config = Config::default();
+            let mut default_config = Config::default();
             default_config.save()?;
+            default_config.apply_env(std::env::vars())?;
+            default_config.validate()?;
             return Ok(default_config);
         }
 
@@ -313,12 +1417,264 @@ impl Config {
                 source,
             })?;
 
-        let config: Config = toml::from_str(&contents)?;
+        let (mut config, unknown) =
+            deserialize_with_unknown_keys(toml::Deserializer::parse(&contents)?)?;
+        for key in unknown {
+            tracing::warn!(path = %config_path.display(), key, "Ignoring unknown config setting");
+        }
+        config.apply_env(std::env::vars())?;
+        config.validate()?;
+        if let Some(server) = &config.server {
+            crate::post_processor::validate(&server.post_processors)?;
+            crate::segmentation::ModelSpec::from_config(&server.segmentation)?;
+        }
+        if let Some(chyron) = &config.burned_in_chyron {
+            chyron.colors()?;
+        }
 
         tracing::debug!(?config, "Config loaded successfully");
         Ok(config)
     }
 
+    /// [`Config::load_from`], with the `.lolcommits.toml` at the top of `repo`'s working
+    /// tree merged over it (see [`Config::merge_repo_config`]). Command line flags are
+    /// applied to the result, so they still take precedence.
+    pub fn load_for_repo(
+        config_path: Option<PathBuf>,
+        repo: Option<&git2::Repository>,
+    ) -> Result<Self> {
+        let mut config = Self::load_from(config_path)?;
+        if let Some(workdir) = repo.and_then(|repo| repo.workdir()) {
+            config.merge_repo_config(&workdir.join(REPO_CONFIG_FILE_NAME))?;
+            // The environment wins over the repository's config too
+            config.apply_env(std::env::vars())?;
+            config.validate()?;
+        }
+        Ok(config)
+    }
+
+    /// Apply the `LOLCOMMITS_` variables among `vars` over this config. Any setting can
+    /// be set as `LOLCOMMITS_<SECTION>__<KEY>` (e.g. `LOLCOMMITS_CLIENT__SERVER_URL`),
+    /// a few have shorthands like `LOLCOMMITS_SERVER_URL`, and `LOLCOMMITS_CAMERA_DEVICE`
+    /// replaces `camera_devices` with that one device. Values are typed like
+    /// `lolcommits_upload config set`'s, a bad one is an error naming its variable.
+    pub fn apply_env(&mut self, vars: impl IntoIterator<Item = (String, String)>) -> Result {
+        let mut vars: Vec<_> = vars
+            .into_iter()
+            .filter(|(variable, _)| variable.starts_with(ENV_PREFIX))
+            .collect();
+        if vars.is_empty() {
+            return Ok(());
+        }
+        vars.sort();
+
+        let mut file = toml::Table::try_from(&*self)?;
+        for (variable, value) in vars {
+            let name = &variable[ENV_PREFIX.len()..];
+            if name == "CAMERA_DEVICE" {
+                let device = toml::Table::from_iter([("device".to_string(), value.into())]);
+                if let Some(client) = file
+                    .entry("client")
+                    .or_insert_with(|| toml::Table::new().into())
+                    .as_table_mut()
+                {
+                    client.insert("camera_devices".to_string(), vec![device].into());
+                }
+                continue;
+            }
+            let key = match ENV_SHORTHANDS
+                .iter()
+                .find(|(shorthand, _)| *shorthand == name)
+            {
+                Some((_, key)) => key.to_string(),
+                None if name.contains("__") => name.to_lowercase().replace("__", "."),
+                None => {
+                    tracing::warn!(variable, "Ignoring unknown environment variable");
+                    continue;
+                }
+            };
+            tracing::debug!(variable, key, "Applying setting from the environment");
+            crate::config_edit::assign(&mut file, &key, &value).map_err(|e| {
+                Error::InvalidEnvOverride {
+                    variable: variable.clone(),
+                    reason: e.to_string(),
+                }
+            })?;
+        }
+        *self = toml::Value::Table(file).try_into()?;
+        Ok(())
+    }
+
+    /// Check the values serde accepts but that can't work, such as an opacity of 7.5 or
+    /// a relative `images_dir`. Every problem is reported, each with its key.
+    pub fn validate(&self) -> Result {
+        let mut problems = Vec::new();
+
+        if let Some(chyron) = &self.burned_in_chyron {
+            if !(0.0..=1.0).contains(&chyron.chyron_opacity) {
+                problems.push(format!(
+                    "burned_in_chyron.chyron_opacity: {} is not between 0 and 1",
+                    chyron.chyron_opacity
+                ));
+            }
+            if !(0.0..=1.0).contains(&chyron.badge_opacity) {
+                problems.push(format!(
+                    "burned_in_chyron.badge_opacity: {} is not between 0 and 1",
+                    chyron.badge_opacity
+                ));
+            }
+            if chyron.badge_size_px == 0 {
+                problems.push("burned_in_chyron.badge_size_px: must be above 0".to_string());
+            }
+            for (key, size) in [
+                ("title_font_size", chyron.title_font_size),
+                ("info_font_size", chyron.info_font_size),
+            ] {
+                if !(size.is_finite() && size > 0.0) {
+                    problems.push(format!("burned_in_chyron.{key}: {size} must be above 0"));
+                }
+            }
+        }
+
+        if let Some(client) = &self.client {
+            for (i, camera) in client.camera_devices.iter().enumerate() {
+                if let Some(format) = &camera.format
+                    && crate::camera::parse_frame_format(format).is_none()
+                {
+                    problems.push(format!(
+                        "client.camera_devices[{i}].format: unknown format {format:?}, expected one of YUYV, MJPEG, NV12, GRAY"
+                    ));
+                }
+                if camera.kind == CameraKind::HttpSnapshot
+                    && !reqwest::Url::parse(&camera.device)
+                        .is_ok_and(|url| matches!(url.scheme(), "http" | "https"))
+                {
+                    problems.push(format!(
+                        "client.camera_devices[{i}].device: {:?} is not an http(s) URL",
+                        camera.device
+                    ));
+                }
+                if camera.kind == CameraKind::Rtsp {
+                    problems.push(format!(
+                        "client.camera_devices[{i}].kind: \"rtsp\" is not supported yet, use \"http-snapshot\" if the camera serves JPEG snapshots"
+                    ));
+                }
+                if let Some(Crop::Rect { width, height, .. }) = camera.crop
+                    && (width == 0 || height == 0)
+                {
+                    problems.push(format!(
+                        "client.camera_devices[{i}].crop: {width}x{height} is empty"
+                    ));
+                }
+            }
+            match reqwest::Url::parse(&client.server_url) {
+                Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+                _ => problems.push(format!(
+                    "client.server_url: {:?} is not an http(s) URL",
+                    client.server_url
+                )),
+            }
+            if client.camera_warmup_frames > MAX_CAMERA_WARMUP_FRAMES {
+                problems.push(format!(
+                    "client.camera_warmup_frames: {} is more than {MAX_CAMERA_WARMUP_FRAMES}, use camera_warmup_ms for slow cameras",
+                    client.camera_warmup_frames
+                ));
+            }
+            if client.capture_source_max_dimension == Some(0) {
+                problems.push("client.capture_source_max_dimension: must be above 0".to_string());
+            }
+            if client.capture_delay_secs > MAX_CAPTURE_DELAY_SECS {
+                problems.push(format!(
+                    "client.capture_delay_secs: {} is more than {MAX_CAPTURE_DELAY_SECS}",
+                    client.capture_delay_secs
+                ));
+            }
+        }
+
+        if let Some(server) = &self.server {
+            for (key, dir) in [
+                ("images_dir", &server.images_dir),
+                ("models_dir", &server.models_dir),
+            ] {
+                if !std::path::Path::new(dir).is_absolute() {
+                    problems.push(format!("server.{key}: {dir:?} is not an absolute path"));
+                }
+            }
+            if let Some(dir) = &server.static_dir
+                && !std::path::Path::new(dir).is_absolute()
+            {
+                problems.push(format!(
+                    "server.static_dir: {dir:?} is not an absolute path"
+                ));
+            }
+            if server.tls_cert_path.is_some() != server.tls_key_path.is_some() {
+                problems.push(
+                    "server.tls_cert_path, server.tls_key_path: set both to serve HTTPS, or neither"
+                        .to_string(),
+                );
+            }
+            if !(0.0..=255.0).contains(&server.low_light_threshold) {
+                problems.push(format!(
+                    "server.low_light_threshold: {} is not between 0 and 255",
+                    server.low_light_threshold
+                ));
+            }
+            if server.processing_timeout_secs == 0 {
+                problems.push("server.processing_timeout_secs: must be at least 1".to_string());
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::InvalidConfig { problems })
+        }
+    }
+
+    /// Merge a repository's config file over this config, if it exists. Only client-side
+    /// settings apply: a top-level `enabled` and keys in the `[client]` and
+    /// `[burned_in_chyron]` sections, each replacing the user's value. Anything else,
+    /// such as `[server]`, is ignored with a warning.
+    pub fn merge_repo_config(&mut self, path: &std::path::Path) -> Result {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(source) => {
+                return Err(Error::ConfigFileRead {
+                    path: path.to_path_buf(),
+                    source,
+                });
+            }
+        };
+        let mut repo: toml::Table = toml::from_str(&contents)?;
+        tracing::info!(path = %path.display(), "Merging repository config");
+
+        for key in repo
+            .keys()
+            .filter(|key| !REPO_CONFIG_KEYS.contains(&key.as_str()))
+        {
+            tracing::warn!(path = %path.display(), key, "Ignoring setting that can't be set per repository");
+        }
+
+        let mut client = section(self.client.clone().unwrap_or_default())?;
+        if let Some(enabled) = repo.remove("enabled") {
+            client.insert("enabled".to_string(), enabled);
+        }
+        if let Some(overrides) = repo.remove("client") {
+            client.extend(into_section(overrides)?);
+        }
+        self.client = Some(toml::Value::Table(client).try_into()?);
+
+        if let Some(overrides) = repo.remove("burned_in_chyron") {
+            let mut chyron = section(self.burned_in_chyron.clone().unwrap_or_default())?;
+            chyron.extend(into_section(overrides)?);
+            let chyron: BurnedInChyronConfig = toml::Value::Table(chyron).try_into()?;
+            chyron.colors()?;
+            self.burned_in_chyron = Some(chyron);
+        }
+        Ok(())
+    }
+
     /// Load configuration using hierarchical search
     pub fn load() -> Result<Self> {
         Self::load_from(None)
@@ -329,15 +1685,22 @@ impl Config {
         let config_path =
             BaseDirectories::with_prefix(XDG_PREFIX).place_config_file(CONFIG_FILE_NAME)?;
 
-        let contents = toml::to_string_pretty(self)?;
+        self.save_to(&config_path)
+    }
 
-        std::fs::write(&config_path, contents).map_err(|source| Error::ConfigFileWrite {
-            path: config_path.clone(),
-            source,
-        })?;
+    /// Save configuration to a specific file, creating its parent directory if needed.
+    pub fn save_to(&self, config_path: &std::path::Path) -> Result {
+        write_file(config_path, &toml::to_string_pretty(self)?)
+    }
 
-        tracing::info!(path = %config_path.display(), "Config saved successfully");
-        Ok(())
+    /// This config with every missing section filled in with its defaults, as it
+    /// behaves.
+    pub fn with_defaults(self) -> Self {
+        Self {
+            client: Some(self.client.unwrap_or_default()),
+            server: Some(self.server.unwrap_or_default()),
+            burned_in_chyron: Some(self.burned_in_chyron.unwrap_or_default()),
+        }
     }
 
     /// Get the path to the config file
@@ -351,6 +1714,7 @@ impl Config {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use test_case::test_case;
 
     #[test]
     fn test_default_config() {
@@ -373,6 +1737,11 @@ mod tests {
         assert_eq!(client.camera_devices.len(), 1);
         assert_eq!(client.camera_devices[0].device, "0");
         assert_eq!(client.camera_warmup_frames, 3);
+        assert_eq!(client.camera_warmup_ms, 0);
+        assert_eq!(client.capture_delay_secs, 0);
+        assert_eq!(client.camera_busy_retries, 2);
+        assert_eq!(client.camera_busy_retry_delay_ms, 500);
+        assert_eq!(client.capture_timeout_secs, 10);
     }
 
     #[test]
@@ -416,6 +1785,7 @@ mod tests {
         assert_eq!(chyron.get_info_font_name(), "DejaVu Sans");
         assert_eq!(chyron.get_sha_font_name(), "DejaVu Sans");
         assert_eq!(chyron.get_stats_font_name(), "DejaVu Sans");
+        assert_eq!(chyron.get_meme_font_name(), "DejaVu Sans");
     }
 
     #[test]
@@ -435,6 +1805,166 @@ mod tests {
         assert_eq!(chyron.get_stats_font_name(), "monospace");
     }
 
+    #[test_case("", MessageOverflow::Truncate ; "default")]
+    #[test_case("message_overflow = \"wrap\"", MessageOverflow::Wrap ; "wrap")]
+    #[test_case("message_overflow = \"shrink\"", MessageOverflow::Shrink ; "shrink")]
+    fn test_message_overflow(line: &str, expected: MessageOverflow) {
+        let config: Config = toml::from_str(&format!("[burned_in_chyron]\n{line}")).unwrap();
+        assert_eq!(config.burned_in_chyron.unwrap().message_overflow, expected);
+    }
+
+    #[test_case("", CaptureMode::Server ; "default")]
+    #[test_case("mode = \"server\"", CaptureMode::Server ; "server")]
+    #[test_case("mode = \"local\"", CaptureMode::Local ; "local")]
+    fn test_capture_mode(line: &str, expected: CaptureMode) {
+        let config: Config = toml::from_str(&format!("[client]\n{line}")).unwrap();
+        assert_eq!(config.client.unwrap().mode, expected);
+    }
+
+    #[test_case("", TextStyle::Plain, 2 ; "default")]
+    #[test_case("text_style = \"shadow\"", TextStyle::Shadow, 2 ; "shadow")]
+    #[test_case("text_style = \"outline\"\nshadow_offset_px = 1", TextStyle::Outline, 1 ; "outline")]
+    fn test_text_style(lines: &str, style: TextStyle, offset: u32) {
+        let config: Config = toml::from_str(&format!("[burned_in_chyron]\n{lines}")).unwrap();
+        let chyron = config.burned_in_chyron.unwrap();
+        assert_eq!(
+            (chyron.text_style, chyron.shadow_offset_px),
+            (style, offset)
+        );
+    }
+
+    #[test_case("", ChyronStyle::Chyron ; "default")]
+    #[test_case("style = \"meme\"", ChyronStyle::Meme ; "meme")]
+    fn test_chyron_style(line: &str, expected: ChyronStyle) {
+        let config: Config = toml::from_str(&format!("[burned_in_chyron]\n{line}")).unwrap();
+        assert_eq!(config.burned_in_chyron.unwrap().style, expected);
+    }
+
+    #[test]
+    fn test_badges() {
+        let config: Config = toml::from_str(
+            "[burned_in_chyron]
+badge_corner = \"bottom-left\"
+
+[burned_in_chyron.badges]
+fix = \"🐛\"
+default = \"/usr/share/lolcommits/star.png\"
+",
+        )
+        .unwrap();
+        let chyron = config.burned_in_chyron.unwrap();
+        assert_eq!(chyron.badges["fix"], "🐛");
+        assert_eq!(chyron.badges["default"], "/usr/share/lolcommits/star.png");
+        assert_eq!(
+            (
+                chyron.badge_corner,
+                chyron.badge_size_px,
+                chyron.badge_opacity
+            ),
+            (BadgeCorner::BottomLeft, 48, 1.0)
+        );
+    }
+
+    #[test_case("#1a2B3c", [0x1a, 0x2b, 0x3c, 255] ; "with hash")]
+    #[test_case("ffcc00", [0xff, 0xcc, 0x00, 255] ; "without hash")]
+    fn test_parse_hex_color(value: &str, expected: [u8; 4]) {
+        assert_eq!(parse_hex_color("info_color", value).unwrap().0, expected);
+    }
+
+    #[test_case("red" ; "name")]
+    #[test_case("#fff" ; "short")]
+    #[test_case("#ggffff" ; "not hex")]
+    #[test_case("#ffccÿ" ; "non ascii")]
+    fn test_parse_hex_color_rejects(value: &str) {
+        let error = parse_hex_color("info_color", value).unwrap_err();
+        assert!(
+            matches!(&error, Error::InvalidColor { field: "info_color", value: v } if v == value),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn test_default_chyron_colors_parse() {
+        let colors = BurnedInChyronConfig::default().colors().unwrap();
+        assert_eq!(colors, ChyronColors::default());
+    }
+
+    #[test]
+    fn test_load_rejects_invalid_chyron_color() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            "[burned_in_chyron]
+sha_color = \"#zzzzzz\"\n",
+        )
+        .unwrap();
+
+        let error = Config::load_from(Some(path)).unwrap_err();
+        assert!(
+            matches!(
+                error,
+                Error::InvalidColor {
+                    field: "sha_color",
+                    ..
+                }
+            ),
+            "{error}"
+        );
+    }
+
+    #[test_case("position = \"top\"", ChyronPosition::Top ; "top")]
+    #[test_case("", ChyronPosition::Bottom ; "default")]
+    fn test_chyron_position(line: &str, expected: ChyronPosition) {
+        let config: Config = toml::from_str(&format!("[burned_in_chyron]\n{line}")).unwrap();
+        assert_eq!(config.burned_in_chyron.unwrap().position, expected);
+    }
+
+    #[test_case("", OutputFormat::Png, 85 ; "default")]
+    #[test_case("output_format = \"jpeg\"\njpeg_quality = 70", OutputFormat::Jpeg, 70 ; "jpeg")]
+    #[test_case("output_format = \"webp\"", OutputFormat::Webp, 85 ; "webp")]
+    fn test_server_output_format(lines: &str, format: OutputFormat, jpeg_quality: u8) {
+        let config: Config = toml::from_str(&format!("[server]\n{lines}")).unwrap();
+        let server = config.server.unwrap();
+        assert_eq!(server.output_format, format);
+        assert_eq!(server.jpeg_quality, jpeg_quality);
+    }
+
+    #[test_case("", AnimationChyron::Last ; "default")]
+    #[test_case("animation_chyron = \"all\"", AnimationChyron::All ; "all")]
+    fn test_server_animation_chyron(line: &str, expected: AnimationChyron) {
+        let config: Config = toml::from_str(&format!("[server]\n{line}")).unwrap();
+        assert_eq!(config.server.unwrap().animation_chyron, expected);
+    }
+
+    #[test]
+    fn test_client_animation_defaults() {
+        let config: Config = toml::from_str("[client]\nanimate = true").unwrap();
+        let client = config.client.unwrap();
+        assert!(client.animate);
+        assert_eq!(client.animate_frames, 8);
+        assert_eq!(client.animate_duration_ms, 2000);
+        assert_eq!(client.animate_max_width, 480);
+        assert!(!ClientConfig::default().animate);
+    }
+
+    #[test_case("", DnnBackend::Opencv, DnnTarget::Cpu ; "default")]
+    #[test_case("dnn_backend = \"cuda\"\ndnn_target = \"cuda\"", DnnBackend::Cuda, DnnTarget::Cuda ; "cuda")]
+    #[test_case("dnn_target = \"opencl\"", DnnBackend::Opencv, DnnTarget::Opencl ; "opencl")]
+    fn test_server_dnn_settings(lines: &str, backend: DnnBackend, target: DnnTarget) {
+        let config: Config = toml::from_str(&format!("[server]\n{lines}")).unwrap();
+        let server = config.server.unwrap();
+        assert_eq!(server.dnn_backend, backend);
+        assert_eq!(server.dnn_target, target);
+    }
+
+    #[test]
+    fn test_message_overflow_rejects_unknown() {
+        let result: std::result::Result<Config, _> =
+            toml::from_str("[burned_in_chyron]\nmessage_overflow = \"scroll\"");
+        assert!(result.is_err());
+    }
+
     #[test]
     fn test_default_font_name_is_monospace() {
         let chyron = BurnedInChyronConfig::default();
@@ -532,6 +2062,76 @@ mod tests {
         assert_eq!(server.bind_port, 8080);
     }
 
+    #[test]
+    fn test_server_backgrounds_table() {
+        let toml_str = r#"
+            [server]
+            background_path = "office"
+
+            [server.backgrounds]
+            fix = "extinguisher"
+            feat = "/srv/backgrounds/fireworks.png"
+        "#;
+
+        let config: Config = toml::from_str(toml_str).unwrap();
+        let server = config.server.unwrap();
+        assert_eq!(server.background_path, "office");
+        assert_eq!(server.backgrounds["fix"], "extinguisher");
+        assert_eq!(server.backgrounds["feat"], "/srv/backgrounds/fireworks.png");
+    }
+
+    #[test]
+    fn test_server_backgrounds_round_trip() {
+        let config = Config {
+            server: Some(ServerConfig {
+                backgrounds: [("fix".to_string(), "extinguisher".to_string())].into(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let toml_str = toml::to_string(&config).unwrap();
+        let parsed: Config = toml::from_str(&toml_str).unwrap();
+        assert_eq!(
+            parsed.server.unwrap().backgrounds,
+            config.server.unwrap().backgrounds
+        );
+
+        // Left out entirely when empty
+        let toml_str = toml::to_string(&Config {
+            server: Some(ServerConfig::default()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(!toml_str.contains("backgrounds"), "{toml_str}");
+    }
+
+    #[test]
+    fn test_server_segmentation_section() {
+        let toml_str = r#"
+            [server.segmentation]
+            mask_floor = 0.2
+            feather_px = 1.5
+            cleanup_radius_px = 3
+            model = "u2netp"
+        "#;
+
+        let config: Config = toml::from_str(toml_str).unwrap();
+        let segmentation = config.server.unwrap().segmentation;
+        assert_eq!(segmentation.mask_floor, 0.2);
+        assert_eq!(segmentation.mask_ceiling, 1.0);
+        assert_eq!(segmentation.feather_px, 1.5);
+        assert_eq!(segmentation.cleanup_radius_px, 3);
+        assert_eq!(segmentation.model, SegmentationModelKind::U2netp);
+        assert_eq!(segmentation.model_url, None);
+
+        let config: Config = toml::from_str("[server]").unwrap();
+        assert_eq!(
+            config.server.unwrap().segmentation,
+            SegmentationConfig::default()
+        );
+    }
+
     #[test]
     fn test_server_burned_in_chyron_false() {
         let toml_str = r#"
@@ -544,6 +2144,254 @@ mod tests {
         assert!(!server.burned_in_chyron);
     }
 
+    #[test]
+    fn test_post_processors_default_order() {
+        let config: Config = toml::from_str("[server]").unwrap();
+        assert_eq!(
+            config.server.unwrap().post_processors,
+            vec!["background", "chyron"]
+        );
+    }
+
+    #[test]
+    fn test_load_rejects_unknown_post_processor() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "[server]\npost_processors = [\"chyron\", \"qr\"]\n").unwrap();
+
+        let error = Config::load_from(Some(path)).unwrap_err();
+        assert!(matches!(error, Error::UnknownPostProcessor { name } if name == "qr"));
+    }
+
+    fn full_config() -> Config {
+        Config {
+            client: Some(ClientConfig::default()),
+            server: Some(ServerConfig::default()),
+            burned_in_chyron: Some(BurnedInChyronConfig::default()),
+        }
+    }
+
+    #[test]
+    fn test_default_config_is_valid() {
+        full_config().validate().unwrap();
+    }
+
+    #[test_case("[burned_in_chyron]\nchyron_opacity = 7.5", "burned_in_chyron.chyron_opacity: 7.5 is not between 0 and 1" ; "opacity")]
+    #[test_case("[burned_in_chyron]\nbadge_opacity = 2.0", "burned_in_chyron.badge_opacity: 2 is not between 0 and 1" ; "badge opacity")]
+    #[test_case("[burned_in_chyron]\nbadge_size_px = 0", "burned_in_chyron.badge_size_px: must be above 0" ; "badge size")]
+    #[test_case("[burned_in_chyron]\ninfo_font_size = 0.0", "burned_in_chyron.info_font_size: 0 must be above 0" ; "font size")]
+    #[test_case("[[client.camera_devices]]\ndevice = \"/dev/video0\"\nformat = \"YUV\"", "client.camera_devices[0].format: unknown format \"YUV\", expected one of YUYV, MJPEG, NV12, GRAY" ; "camera format")]
+    #[test_case("[[client.camera_devices]]\ndevice = \"0\"\ncrop = { x = 0, y = 0, width = 0, height = 480 }", "client.camera_devices[0].crop: 0x480 is empty" ; "empty crop")]
+    #[test_case("[[client.camera_devices]]\ndevice = \"camera.local/snapshot.jpg\"\nkind = \"http-snapshot\"", "client.camera_devices[0].device: \"camera.local/snapshot.jpg\" is not an http(s) URL" ; "snapshot url")]
+    #[test_case("[[client.camera_devices]]\ndevice = \"rtsp://camera.local/stream\"\nkind = \"rtsp\"", "client.camera_devices[0].kind: \"rtsp\" is not supported yet, use \"http-snapshot\" if the camera serves JPEG snapshots" ; "rtsp")]
+    #[test_case("[client]\nserver_url = \"localhost:3000\"", "client.server_url: \"localhost:3000\" is not an http(s) URL" ; "server url")]
+    #[test_case("[client]\ncamera_warmup_frames = 1000", "client.camera_warmup_frames: 1000 is more than 300, use camera_warmup_ms for slow cameras" ; "warmup frames")]
+    #[test_case("[client]\ncapture_source_max_dimension = 0", "client.capture_source_max_dimension: must be above 0" ; "capture source max dimension")]
+    #[test_case("[client]\ncapture_delay_secs = 60", "client.capture_delay_secs: 60 is more than 30" ; "capture delay")]
+    #[test_case("[server]\nimages_dir = \"images\"", "server.images_dir: \"images\" is not an absolute path" ; "images dir")]
+    #[test_case("[server]\nstatic_dir = \"gallery\"", "server.static_dir: \"gallery\" is not an absolute path" ; "static dir")]
+    #[test_case("[server]\ntls_cert_path = \"/etc/lolcommits/cert.pem\"", "server.tls_cert_path, server.tls_key_path: set both to serve HTTPS, or neither" ; "tls cert without key")]
+    #[test_case("[server]\nlow_light_threshold = 300.0", "server.low_light_threshold: 300 is not between 0 and 255" ; "low light threshold")]
+    #[test_case("[server]\nprocessing_timeout_secs = 0", "server.processing_timeout_secs: must be at least 1" ; "processing timeout")]
+    fn test_validate_rejects(toml_str: &str, expected: &str) {
+        let config: Config = toml::from_str(toml_str).unwrap();
+
+        let error = config.validate().unwrap_err();
+
+        assert!(
+            matches!(&error, Error::InvalidConfig { problems } if problems == &[expected]),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn test_validate_lists_every_problem() {
+        let mut config = full_config();
+        config.burned_in_chyron.as_mut().unwrap().chyron_opacity = -1.0;
+        config.server.as_mut().unwrap().models_dir = "models".to_string();
+
+        let error = config.validate().unwrap_err();
+
+        assert!(
+            matches!(&error, Error::InvalidConfig { problems } if problems.len() == 2),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn test_camera_transforms() {
+        let toml_str = r#"
+            [[client.camera_devices]]
+            device = "/dev/video0"
+            mirror = true
+            rotate = 180
+            crop = "4:3"
+
+            [[client.camera_devices]]
+            device = "1"
+            crop = { x = 10, y = 20, width = 640, height = 480 }
+        "#;
+        let config: Config = toml::from_str(toml_str).unwrap();
+        let devices = &config.client.unwrap().camera_devices;
+
+        assert!(devices[0].mirror);
+        assert_eq!(devices[0].rotate, Rotation::Half);
+        assert_eq!(
+            devices[0].crop,
+            Some(Crop::Aspect(AspectRatio {
+                width: 4,
+                height: 3
+            }))
+        );
+        assert!(!devices[1].mirror);
+        assert_eq!(devices[1].rotate, Rotation::None);
+        assert_eq!(
+            devices[1].crop,
+            Some(Crop::Rect {
+                x: 10,
+                y: 20,
+                width: 640,
+                height: 480
+            })
+        );
+    }
+
+    #[test_case("rotate = 45" ; "rotation")]
+    #[test_case("crop = \"wide\"" ; "aspect ratio")]
+    #[test_case("crop = \"16:0\"" ; "zero ratio")]
+    fn test_camera_transforms_reject(setting: &str) {
+        let toml_str = format!("[[client.camera_devices]]\ndevice = \"0\"\n{setting}");
+        assert!(toml::from_str::<Config>(&toml_str).is_err());
+    }
+
+    #[test]
+    fn test_load_rejects_invalid_values() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "[burned_in_chyron]\nchyron_opacity = 7.5\n").unwrap();
+
+        let error = Config::load_from(Some(path)).unwrap_err();
+        assert!(matches!(error, Error::InvalidConfig { .. }), "{error}");
+    }
+
+    #[test]
+    fn test_unknown_keys() {
+        let toml_str = r#"
+            colour = "red"
+
+            [client]
+            server_ulr = "http://lol:3000"
+
+            [[client.camera_devices]]
+            device = "/dev/video0"
+            fromat = "MJPEG"
+            mirror = false
+            rotate = 0
+
+            [server]
+            mount_prefix = ""
+            [server.backgrounds]
+            fix = "extinguisher"
+
+            [burned_in_chyron]
+            shw_branch = false
+            badge_sise_px = 0
+        "#;
+        let (config, unknown) =
+            deserialize_with_unknown_keys(toml::Deserializer::parse(toml_str).unwrap()).unwrap();
+
+        assert_eq!(
+            unknown,
+            vec![
+                "burned_in_chyron.badge_sise_px",
+                "burned_in_chyron.shw_branch",
+                "client.camera_devices[0].fromat",
+                "client.server_ulr",
+                "colour",
+            ]
+        );
+        assert_eq!(
+            config.client.unwrap().camera_devices[0].device,
+            "/dev/video0"
+        );
+    }
+
+    fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_apply_env_overrides_file() {
+        let mut config: Config =
+            toml::from_str("[client]\nserver_url = \"http://file:3000\"\nanimate = true\n")
+                .unwrap();
+
+        config
+            .apply_env(env(&[
+                ("LOLCOMMITS_SERVER_URL", "http://env:3000"),
+                ("LOLCOMMITS_CAMERA_DEVICE", "/dev/video2"),
+                ("LOLCOMMITS_IMAGES_DIR", "/srv/lolcommits"),
+                ("LOLCOMMITS_MODELS_DIR", "/srv/models"),
+                ("LOLCOMMITS_CLIENT__WAIT_FOR_PROCESSING", "true"),
+                ("LOLCOMMITS_SERVER__BIND_PORT", "8080"),
+                ("LOLCOMMITS_BURNED_IN_CHYRON__CHYRON_OPACITY", "0.5"),
+                ("PATH", "/usr/bin"),
+            ]))
+            .unwrap();
+
+        let client = config.client.unwrap();
+        assert_eq!(client.server_url, "http://env:3000");
+        assert!(client.animate);
+        assert!(client.wait_for_processing);
+        assert_eq!(client.camera_devices.len(), 1);
+        assert_eq!(client.camera_devices[0].device, "/dev/video2");
+        let server = config.server.unwrap();
+        assert_eq!(server.images_dir, "/srv/lolcommits");
+        assert_eq!(server.models_dir, "/srv/models");
+        assert_eq!(server.bind_port, 8080);
+        assert_eq!(config.burned_in_chyron.unwrap().chyron_opacity, 0.5);
+    }
+
+    #[test_case("LOLCOMMITS_SERVER__BIND_PORT", "http" ; "non numeric port")]
+    #[test_case("LOLCOMMITS_SERVER__BIND_PORT", "99999" ; "port out of range")]
+    #[test_case("LOLCOMMITS_CLIENT__SERVER_ULR", "http://env:3000" ; "unknown key")]
+    fn test_apply_env_rejects(variable: &str, value: &str) {
+        let mut config = Config::default();
+
+        let error = config.apply_env(env(&[(variable, value)])).unwrap_err();
+
+        assert!(
+            matches!(&error, Error::InvalidEnvOverride { variable: v, .. } if v == variable),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn test_apply_env_ignores_unknown_variables() {
+        let mut config = Config::default();
+
+        config
+            .apply_env(env(&[("LOLCOMMITS_UNRELATED", "1")]))
+            .unwrap();
+
+        assert!(config.client.is_none());
+    }
+
+    #[test]
+    fn test_load_applies_env_over_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "[server]\njob_ttl_secs = 60\n").unwrap();
+
+        let config = temp_env::with_var("LOLCOMMITS_SERVER__JOB_TTL_SECS", Some("90"), || {
+            Config::load_from(Some(path)).unwrap()
+        });
+
+        assert_eq!(config.server.unwrap().job_ttl_secs, 90);
+    }
+
     #[test]
     fn test_server_burned_in_chyron_defaults_to_true() {
         let toml_str = r#"
@@ -555,4 +2403,80 @@ mod tests {
         let server = config.server.unwrap();
         assert!(server.burned_in_chyron);
     }
+
+    /// A user config and a repository with `repo_config` as its `.lolcommits.toml`.
+    fn repo_with_config(
+        dir: &std::path::Path,
+        repo_config: &str,
+    ) -> Result<(PathBuf, git2::Repository)> {
+        let user_config = dir.join("config.toml");
+        std::fs::write(
+            &user_config,
+            "[client]\nserver_url = \"http://user:3000\"\ncamera_warmup_frames = 5\n\n[server]\nbind_port = 8080\n",
+        )?;
+        let repo = git2::Repository::init(dir.join("repo"))?;
+        std::fs::write(dir.join("repo").join(REPO_CONFIG_FILE_NAME), repo_config)?;
+        Ok((user_config, repo))
+    }
+
+    #[test]
+    fn test_load_for_repo_merges_client_settings() -> Result {
+        let dir = tempfile::tempdir()?;
+        let (user_config, repo) = repo_with_config(
+            dir.path(),
+            r#"
+                enabled = false
+
+                [client]
+                server_url = "http://repo:3000"
+
+                [burned_in_chyron]
+                chyron_opacity = 0.5
+
+                [server]
+                bind_port = 9999
+            "#,
+        )?;
+
+        let config = Config::load_for_repo(Some(user_config), Some(&repo))?;
+
+        let client = config.client.unwrap();
+        assert!(!client.enabled);
+        assert_eq!(client.server_url, "http://repo:3000");
+        // Settings the repository doesn't mention are the user's
+        assert_eq!(client.camera_warmup_frames, 5);
+        let chyron = config.burned_in_chyron.unwrap();
+        assert_eq!(chyron.chyron_opacity, 0.5);
+        assert_eq!(chyron.default_font_name, "monospace");
+        // Server settings can't be set per repository
+        assert_eq!(config.server.unwrap().bind_port, 8080);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_for_repo_without_repo_config() -> Result {
+        let dir = tempfile::tempdir()?;
+        let (user_config, repo) = repo_with_config(dir.path(), "")?;
+        std::fs::remove_file(dir.path().join("repo").join(REPO_CONFIG_FILE_NAME))?;
+
+        let config = Config::load_for_repo(Some(user_config.clone()), Some(&repo))?;
+        let client = config.client.unwrap();
+        assert!(client.enabled);
+        assert_eq!(client.server_url, "http://user:3000");
+
+        let config = Config::load_for_repo(Some(user_config), None)?;
+        assert_eq!(config.client.unwrap().server_url, "http://user:3000");
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_for_repo_rejects_invalid_values() -> Result {
+        let dir = tempfile::tempdir()?;
+        let (user_config, repo) =
+            repo_with_config(dir.path(), "[client]\ncamera_warmup_frames = \"many\"\n")?;
+
+        let error = Config::load_for_repo(Some(user_config), Some(&repo)).unwrap_err();
+        assert!(matches!(error, Error::TomlDeserialize(_)), "{error}");
+        Ok(())
+    }
 }
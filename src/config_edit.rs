@@ -0,0 +1,328 @@
+//! `lolcommits_upload config`: reading and changing single settings by their dotted key
+//! (e.g. `client.camera_devices[0].device`).
+//!
+//! `set` edits the config file as a TOML table, so settings it doesn't touch, unknown
+//! keys included, are written back as they were. The new value takes the type of the
+//! setting it replaces, and the result has to load as a valid [`Config`] before it is
+//! written.
+
+use crate::config::{self, Config};
+use crate::error::{Error, Result};
+use std::path::Path;
+
+/// One step of a dotted key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// Split `client.camera_devices[0].device` into its keys and indices.
+fn parse_key(key: &str) -> Result<Vec<Segment>> {
+    let unknown = || Error::UnknownConfigKey {
+        key: key.to_string(),
+    };
+    let mut segments = Vec::new();
+    for part in key.split('.') {
+        let (name, mut indices) = part.split_at(part.find('[').unwrap_or(part.len()));
+        if name.is_empty() {
+            return Err(unknown());
+        }
+        segments.push(Segment::Key(name.to_string()));
+        while let Some(rest) = indices.strip_prefix('[') {
+            let (index, rest) = rest.split_once(']').ok_or_else(unknown)?;
+            segments.push(Segment::Index(index.parse().map_err(|_| unknown())?));
+            indices = rest;
+        }
+        if !indices.is_empty() {
+            return Err(unknown());
+        }
+    }
+    Ok(segments)
+}
+
+fn child<'a>(value: &'a toml::Value, segment: &Segment) -> Option<&'a toml::Value> {
+    match segment {
+        Segment::Key(key) => value.as_table()?.get(key),
+        Segment::Index(index) => value.as_array()?.get(*index),
+    }
+}
+
+fn lookup<'a>(value: &'a toml::Value, segments: &[Segment]) -> Option<&'a toml::Value> {
+    segments
+        .iter()
+        .try_fold(value, |value, segment| child(value, segment))
+}
+
+/// The value of `key` in `config` with its defaults filled in, `None` when it's unset.
+pub fn get(config: &Config, key: &str) -> Result<Option<toml::Value>> {
+    let segments = parse_key(key)?;
+    let effective = toml::Value::try_from(config.clone().with_defaults())?;
+    Ok(lookup(&effective, &segments).cloned())
+}
+
+/// A value as `lolcommits_upload config get` prints it: strings without quotes, tables
+/// as TOML.
+pub fn display(value: &toml::Value) -> Result<String> {
+    Ok(match value {
+        toml::Value::String(s) => s.clone(),
+        toml::Value::Table(table) => toml::to_string_pretty(table)?,
+        value => value.to_string(),
+    })
+}
+
+/// Parse `value` as the type of `current`, the setting's present value. Settings
+/// without one (unset optional settings) take the value as a TOML literal, falling back
+/// to a string.
+fn parse_value(key: &str, value: &str, current: Option<&toml::Value>) -> Result<toml::Value> {
+    let invalid = |reason: &str| Error::InvalidConfigValue {
+        key: key.to_string(),
+        value: value.to_string(),
+        reason: reason.to_string(),
+    };
+    match current {
+        Some(toml::Value::String(_)) => Ok(toml::Value::String(value.to_string())),
+        Some(toml::Value::Boolean(_)) => value
+            .parse()
+            .map(toml::Value::Boolean)
+            .map_err(|_| invalid("expected true or false")),
+        Some(toml::Value::Integer(_)) => value
+            .parse()
+            .map(toml::Value::Integer)
+            .map_err(|_| invalid("expected a whole number")),
+        Some(toml::Value::Float(_)) => value
+            .parse()
+            .map(toml::Value::Float)
+            .map_err(|_| invalid("expected a number")),
+        Some(toml::Value::Array(_) | toml::Value::Table(_)) => Err(invalid(
+            "lists and sections can't be set whole, set one of their keys or edit the file",
+        )),
+        Some(toml::Value::Datetime(_)) | None => {
+            Ok(toml::from_str::<toml::Table>(&format!("value = {value}"))
+                .ok()
+                .and_then(|mut table| table.remove("value"))
+                .unwrap_or_else(|| toml::Value::String(value.to_string())))
+        }
+    }
+}
+
+/// The entry `segments` leads to in `file`, creating what's missing on the way. Lists
+/// the file doesn't have are copied from `effective`, so an index into a default list
+/// works.
+fn entry<'a>(
+    key: &str,
+    file: &'a mut toml::Value,
+    effective: Option<&toml::Value>,
+    segments: &[Segment],
+) -> Result<&'a mut toml::Value> {
+    let Some((segment, rest)) = segments.split_first() else {
+        return Ok(file);
+    };
+    let unknown = || Error::UnknownConfigKey {
+        key: key.to_string(),
+    };
+    let effective = effective.and_then(|effective| child(effective, segment));
+    let next = match segment {
+        Segment::Key(name) => {
+            let table = file.as_table_mut().ok_or_else(unknown)?;
+            table
+                .entry(name.clone())
+                .or_insert_with(|| match effective {
+                    Some(toml::Value::Array(array)) => toml::Value::Array(array.clone()),
+                    Some(toml::Value::Table(_)) => toml::Value::Table(toml::Table::new()),
+                    _ if rest.is_empty() => toml::Value::Boolean(false),
+                    _ => toml::Value::Table(toml::Table::new()),
+                })
+        }
+        Segment::Index(index) => file
+            .as_array_mut()
+            .and_then(|array| array.get_mut(*index))
+            .ok_or_else(unknown)?,
+    };
+    entry(key, next, effective, rest)
+}
+
+/// Set `key` to `value` in `file`, the contents of a config file, checking that the
+/// key is a setting, the value has its type and the result is a valid config.
+pub fn set(file: &mut toml::Table, key: &str, value: &str) -> Result {
+    let mut updated = file.clone();
+    assign(&mut updated, key, value)?.validate()?;
+    *file = updated;
+    Ok(())
+}
+
+/// [`set`] without [`Config::validate`], returning the config `file` now holds.
+pub(crate) fn assign(file: &mut toml::Table, key: &str, value: &str) -> Result<Config> {
+    let segments = parse_key(key)?;
+    let current: Config = toml::Value::Table(file.clone()).try_into()?;
+    let effective = toml::Value::try_from(current.with_defaults())?;
+    let parsed = parse_value(key, value, lookup(&effective, &segments))?;
+
+    let mut updated = toml::Value::Table(file.clone());
+    *entry(key, &mut updated, Some(&effective), &segments)? = parsed;
+    let toml::Value::Table(updated) = updated else {
+        unreachable!("the config file is a table");
+    };
+
+    let (config, unknown) = config::deserialize_with_unknown_keys(toml::Value::Table(
+        updated.clone(),
+    ))
+    .map_err(|e: toml::de::Error| Error::InvalidConfigValue {
+        key: key.to_string(),
+        value: value.to_string(),
+        reason: e.message().to_string(),
+    })?;
+    // An unknown section is reported whole, so look for the key or one of its parents
+    let is_key_or_parent = |unknown: &String| {
+        key.strip_prefix(unknown.as_str())
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(['.', '[']))
+    };
+    if unknown.iter().any(is_key_or_parent) {
+        return Err(Error::UnknownConfigKey {
+            key: key.to_string(),
+        });
+    }
+
+    *file = updated;
+    Ok(config)
+}
+
+/// [`set`] in the config file at `path`, which is created if it doesn't exist.
+pub fn set_in_file(path: &Path, key: &str, value: &str) -> Result {
+    let mut file: toml::Table = match std::fs::read_to_string(path) {
+        Ok(contents) => toml::from_str(&contents)?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => toml::Table::new(),
+        Err(source) => {
+            return Err(Error::ConfigFileRead {
+                path: path.to_path_buf(),
+                source,
+            });
+        }
+    };
+    set(&mut file, key, value)?;
+    config::write_file(path, &toml::to_string_pretty(&file)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    fn table(toml_str: &str) -> toml::Table {
+        toml::from_str(toml_str).unwrap()
+    }
+
+    #[test_case("client.server_url", vec![Segment::Key("client".into()), Segment::Key("server_url".into())] ; "dotted")]
+    #[test_case("client.camera_devices[0].device", vec![Segment::Key("client".into()), Segment::Key("camera_devices".into()), Segment::Index(0), Segment::Key("device".into())] ; "indexed")]
+    fn test_parse_key(key: &str, expected: Vec<Segment>) {
+        assert_eq!(parse_key(key).unwrap(), expected);
+    }
+
+    #[test_case("client..server_url" ; "empty segment")]
+    #[test_case("client.camera_devices[x]" ; "bad index")]
+    #[test_case("client.camera_devices[0" ; "unclosed index")]
+    fn test_parse_key_rejects(key: &str) {
+        assert!(matches!(
+            parse_key(key),
+            Err(Error::UnknownConfigKey { .. })
+        ));
+    }
+
+    #[test]
+    fn test_get_fills_in_defaults() {
+        let config: Config = toml::from_str("[client]\nanimate = true\n").unwrap();
+
+        assert_eq!(
+            get(&config, "client.animate").unwrap(),
+            Some(toml::Value::Boolean(true))
+        );
+        assert_eq!(
+            get(&config, "server.bind_port").unwrap(),
+            Some(toml::Value::Integer(3000))
+        );
+        assert_eq!(get(&config, "client.nope").unwrap(), None);
+    }
+
+    #[test]
+    fn test_set_keeps_other_settings() {
+        let mut file = table(
+            "[client]\nserver_url = \"http://lol:3000\"\nanimate = true\n\n[server]\nbind_port = 8080\n",
+        );
+
+        set(&mut file, "client.server_url", "https://lol.example.com").unwrap();
+
+        assert_eq!(
+            file,
+            table(
+                "[client]\nserver_url = \"https://lol.example.com\"\nanimate = true\n\n[server]\nbind_port = 8080\n"
+            )
+        );
+    }
+
+    #[test_case("client.animate", "true", "[client]\nanimate = true" ; "bool")]
+    #[test_case("client.animate_frames", "12", "[client]\nanimate_frames = 12" ; "integer")]
+    #[test_case("burned_in_chyron.chyron_opacity", "0.5", "[burned_in_chyron]\nchyron_opacity = 0.5" ; "float")]
+    #[test_case("burned_in_chyron.locale", "de-DE", "[burned_in_chyron]\nlocale = \"de-DE\"" ; "unset optional string")]
+    #[test_case("burned_in_chyron.height_px", "120", "[burned_in_chyron]\nheight_px = 120" ; "unset optional number")]
+    #[test_case("client.camera_devices[0].format", "MJPEG", "[client]\ncamera_devices = [{ device = \"0\", kind = \"v4l2\", format = \"MJPEG\" }]" ; "default list entry")]
+    fn test_set(key: &str, value: &str, expected: &str) {
+        let mut file = toml::Table::new();
+
+        set(&mut file, key, value).unwrap();
+
+        assert_eq!(file, table(expected));
+    }
+
+    #[test_case("client.server_ulr", "http://lol" ; "misspelled key")]
+    #[test_case("client.animat", "false" ; "misspelled key set to false")]
+    #[test_case("clinet.animate", "true" ; "misspelled section")]
+    #[test_case("client.camera_devices[3].device", "/dev/video1" ; "index out of range")]
+    fn test_set_rejects_unknown_keys(key: &str, value: &str) {
+        let mut file = toml::Table::new();
+
+        let error = set(&mut file, key, value).unwrap_err();
+
+        assert!(matches!(error, Error::UnknownConfigKey { .. }), "{error}");
+        assert!(file.is_empty());
+    }
+
+    #[test_case("client.animate", "yes" ; "bool")]
+    #[test_case("client.animate_frames", "many" ; "integer")]
+    #[test_case("burned_in_chyron.height_px", "tall" ; "unset optional number")]
+    #[test_case("client.camera_devices", "/dev/video1" ; "list")]
+    fn test_set_rejects_wrong_type(key: &str, value: &str) {
+        let mut file = toml::Table::new();
+
+        let error = set(&mut file, key, value).unwrap_err();
+
+        assert!(matches!(error, Error::InvalidConfigValue { .. }), "{error}");
+    }
+
+    #[test]
+    fn test_set_rejects_invalid_config() {
+        let mut file = toml::Table::new();
+
+        let error = set(&mut file, "burned_in_chyron.chyron_opacity", "7.5").unwrap_err();
+
+        assert!(matches!(error, Error::InvalidConfig { .. }), "{error}");
+    }
+
+    #[test]
+    fn test_set_in_file_round_trip() -> Result {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            "custom_key = 1\n\n[client]\nserver_url = \"http://lol:3000\"\n",
+        )?;
+
+        set_in_file(&path, "client.animate", "true")?;
+
+        let file: toml::Table = toml::from_str(&std::fs::read_to_string(&path)?)?;
+        assert_eq!(
+            file,
+            table("custom_key = 1\n\n[client]\nserver_url = \"http://lol:3000\"\nanimate = true\n")
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,263 @@
+//! Free space guard and size accounting for the gallery in `images_dir`.
+//!
+//! Uploads are refused while the filesystem holding `images_dir` has less than
+//! `min_free_space_mb` available, so a full disk can't leave a truncated PNG behind.
+
+use crate::config::ServerConfig;
+use crate::error::{Error, Result};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const MB: u64 = 1024 * 1024;
+
+/// Reports the space available on a filesystem.
+pub trait SpaceProbe: Send + Sync {
+    /// Bytes available to unprivileged users on the filesystem holding `path`.
+    fn available_bytes(&self, path: &Path) -> std::io::Result<u64>;
+}
+
+/// [`SpaceProbe`] backed by `statvfs(2)`.
+pub struct Statvfs;
+
+impl SpaceProbe for Statvfs {
+    fn available_bytes(&self, path: &Path) -> std::io::Result<u64> {
+        let stat = rustix::fs::statvfs(path)?;
+        Ok(stat.f_bavail.saturating_mul(stat.f_frsize))
+    }
+}
+
+pub struct DiskSpace {
+    images_dir: PathBuf,
+    min_free_mb: u64,
+    probe: Box<dyn SpaceProbe>,
+    gallery_bytes: AtomicU64,
+}
+
+impl DiskSpace {
+    pub fn new(config: &ServerConfig) -> Self {
+        Self::with_probe(config, Box::new(Statvfs))
+    }
+
+    pub fn with_probe(config: &ServerConfig, probe: Box<dyn SpaceProbe>) -> Self {
+        let images_dir = PathBuf::from(&config.images_dir);
+        let gallery_bytes = gallery_bytes(&images_dir).unwrap_or_else(|e| {
+            tracing::warn!(images_dir = %images_dir.display(), error = %e, "Failed to measure gallery size");
+            0
+        });
+        crate::metrics::set_gallery_bytes(gallery_bytes);
+
+        Self {
+            images_dir,
+            min_free_mb: config.min_free_space_mb,
+            probe,
+            gallery_bytes: AtomicU64::new(gallery_bytes),
+        }
+    }
+
+    /// Fail with [`Error::LowDiskSpace`] when less than `min_free_space_mb` is available.
+    /// Passes when the guard is off (0) or the space can't be determined.
+    pub fn check(&self) -> Result {
+        if self.min_free_mb == 0 {
+            return Ok(());
+        }
+
+        // images_dir is only created by the first upload
+        let path = self
+            .images_dir
+            .ancestors()
+            .find(|path| path.exists())
+            .unwrap_or(&self.images_dir);
+        let available = match self.probe.available_bytes(path) {
+            Ok(available) => available,
+            Err(e) => {
+                tracing::warn!(path = %path.display(), error = %e, "Failed to check free space");
+                return Ok(());
+            }
+        };
+        crate::metrics::set_disk_free_bytes(available);
+
+        if available < self.min_free_mb * MB {
+            return Err(Error::LowDiskSpace {
+                path: self.images_dir.clone(),
+                available_mb: available / MB,
+                min_free_mb: self.min_free_mb,
+            });
+        }
+        Ok(())
+    }
+
+    /// Total size of the images in the gallery.
+    pub fn gallery_bytes(&self) -> u64 {
+        self.gallery_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Account for a newly saved image.
+    pub fn record_saved(&self, path: &Path) {
+        match std::fs::metadata(path) {
+            Ok(metadata) => {
+                let total = self
+                    .gallery_bytes
+                    .fetch_add(metadata.len(), Ordering::Relaxed)
+                    + metadata.len();
+                crate::metrics::set_gallery_bytes(total);
+            }
+            Err(e) => {
+                tracing::warn!(path = %path.display(), error = %e, "Failed to read saved image size")
+            }
+        }
+    }
+
+    /// Account for an image of `bytes` removed from the gallery.
+    pub fn record_deleted(&self, bytes: u64) {
+        let previous = self
+            .gallery_bytes
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |total| {
+                Some(total.saturating_sub(bytes))
+            })
+            .unwrap_or_default();
+        crate::metrics::set_gallery_bytes(previous.saturating_sub(bytes));
+    }
+}
+
+/// Sum of the sizes of the images directly in `images_dir`, 0 when it doesn't exist yet.
+pub fn gallery_bytes(images_dir: &Path) -> std::io::Result<u64> {
+    let entries = match std::fs::read_dir(images_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+
+    let mut total = 0;
+    for entry in entries {
+        let entry = entry?;
+        if crate::image_metadata::is_image_file(&entry.path()) && entry.file_type()?.is_file() {
+            total += entry.metadata()?.len();
+        }
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FixedProbe(std::io::Result<u64>);
+
+    impl SpaceProbe for FixedProbe {
+        fn available_bytes(&self, _path: &Path) -> std::io::Result<u64> {
+            match &self.0 {
+                Ok(bytes) => Ok(*bytes),
+                Err(e) => Err(std::io::Error::new(e.kind(), e.to_string())),
+            }
+        }
+    }
+
+    fn disk_space(images_dir: &Path, min_free_mb: u64, available: u64) -> DiskSpace {
+        let config = ServerConfig {
+            images_dir: images_dir.display().to_string(),
+            min_free_space_mb: min_free_mb,
+            ..Default::default()
+        };
+        DiskSpace::with_probe(&config, Box::new(FixedProbe(Ok(available))))
+    }
+
+    #[test]
+    fn test_check_threshold() -> Result {
+        let dir = tempfile::tempdir()?;
+
+        assert!(disk_space(dir.path(), 100, 101 * MB).check().is_ok());
+        assert!(disk_space(dir.path(), 100, 100 * MB).check().is_ok());
+
+        let error = disk_space(dir.path(), 100, 100 * MB - 1)
+            .check()
+            .unwrap_err();
+        assert!(
+            matches!(
+                error,
+                Error::LowDiskSpace {
+                    available_mb: 99,
+                    min_free_mb: 100,
+                    ..
+                }
+            ),
+            "{error:?}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_disabled_at_zero() -> Result {
+        let dir = tempfile::tempdir()?;
+        assert!(disk_space(dir.path(), 0, 0).check().is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_passes_when_probe_fails() -> Result {
+        let dir = tempfile::tempdir()?;
+        let config = ServerConfig {
+            images_dir: dir.path().display().to_string(),
+            min_free_space_mb: 100,
+            ..Default::default()
+        };
+        let probe = FixedProbe(Err(std::io::Error::from(
+            std::io::ErrorKind::PermissionDenied,
+        )));
+
+        assert!(
+            DiskSpace::with_probe(&config, Box::new(probe))
+                .check()
+                .is_ok()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_statvfs_probe_reads_real_filesystem() -> Result {
+        let dir = tempfile::tempdir()?;
+        assert!(Statvfs.available_bytes(dir.path())? > 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_gallery_bytes_counts_only_pngs() -> Result {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("a.png"), vec![0u8; 100])?;
+        std::fs::write(dir.path().join("b.png"), vec![0u8; 50])?;
+        std::fs::write(dir.path().join("notes.txt"), vec![0u8; 1000])?;
+        std::fs::create_dir(dir.path().join(".chyron"))?;
+        std::fs::write(dir.path().join(".chyron").join("a.png"), vec![0u8; 1000])?;
+
+        assert_eq!(gallery_bytes(dir.path())?, 150);
+        assert_eq!(gallery_bytes(&dir.path().join("missing"))?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_saved_adds_to_total() -> Result {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("a.png"), vec![0u8; 100])?;
+        let disk_space = disk_space(dir.path(), 0, 0);
+        assert_eq!(disk_space.gallery_bytes(), 100);
+
+        std::fs::write(dir.path().join("b.png"), vec![0u8; 25])?;
+        disk_space.record_saved(&dir.path().join("b.png"));
+
+        assert_eq!(disk_space.gallery_bytes(), 125);
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_deleted_subtracts_without_underflow() -> Result {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("a.png"), vec![0u8; 100])?;
+        let disk_space = disk_space(dir.path(), 0, 0);
+
+        disk_space.record_deleted(40);
+        assert_eq!(disk_space.gallery_bytes(), 60);
+
+        disk_space.record_deleted(1000);
+        assert_eq!(disk_space.gallery_bytes(), 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,561 @@
+//! `lolcommits_upload doctor`: what works and what doesn't, one check at a time.
+//!
+//! Runs through everything a capture needs, in the order a capture needs it: the config,
+//! each camera (or the capture source), the git repository and the server, optionally
+//! ending with a test upload to `/api/ping`, which the server reads and throws away.
+//! Nothing is uploaded to the gallery. Like [`crate::setup`], the camera and network
+//! are behind a [`Probe`] so the checks can run without either.
+
+use crate::{
+    camera, capture,
+    config::{CaptureMode, ClientConfig, Config},
+    error::{Error, Result},
+    git,
+    setup::{self, ClientProbe},
+};
+use owo_colors::OwoColorize;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Pass,
+    Fail,
+    /// The check doesn't apply, or depends on one that failed.
+    Skip,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub status: Status,
+    /// What was found, or the error text of a failure.
+    pub detail: String,
+    /// Round trip of a check that talks to the server.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub elapsed_ms: Option<u64>,
+}
+
+impl Check {
+    fn new(name: impl Into<String>, status: Status, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status,
+            detail: detail.into(),
+            elapsed_ms: None,
+        }
+    }
+
+    fn timed(self, elapsed: Duration) -> Self {
+        Self {
+            elapsed_ms: Some(elapsed.as_millis() as u64),
+            ..self
+        }
+    }
+
+    pub fn is_failure(&self) -> bool {
+        self.status == Status::Fail
+    }
+
+    /// Print the check as a ✓/✗ line.
+    pub fn print(&self) {
+        match self.status {
+            Status::Pass => println!("{} {}: {}", "✓".green(), self.name, self.detail),
+            Status::Skip => println!("{} {}: {}", "-".yellow(), self.name, self.detail.dimmed()),
+            Status::Fail => println!("{} {}: {}", "✗".red(), self.name, self.detail.red()),
+        }
+    }
+}
+
+/// What `/api/ping` says about a test upload.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Ping {
+    pub width: u32,
+    pub height: u32,
+    /// Real uploads would be refused.
+    pub read_only: bool,
+}
+
+/// The checks that need a camera or the network.
+pub trait Probe {
+    /// Capture a frame with the cameras in `config`, returning its dimensions.
+    fn capture(&self, config: &ClientConfig) -> Result<(u32, u32)>;
+
+    /// Fetch `/api/config`, returning the gallery title.
+    fn server_config(&self, config: &ClientConfig) -> Result<String>;
+
+    /// Send a tiny test image to `/api/ping`.
+    fn ping(&self, config: &ClientConfig) -> Result<Ping>;
+}
+
+/// [`Probe`] backed by the real camera and HTTP client.
+pub struct SystemProbe;
+
+impl Probe for SystemProbe {
+    fn capture(&self, config: &ClientConfig) -> Result<(u32, u32)> {
+        let image = camera::capture_image(config)?;
+        Ok((image.width(), image.height()))
+    }
+
+    fn server_config(&self, config: &ClientConfig) -> Result<String> {
+        setup::SystemProbe.check_server(config)
+    }
+
+    fn ping(&self, config: &ClientConfig) -> Result<Ping> {
+        let url = format!("{}/api/ping", config.server_url);
+        let png = capture::encode_png(&image::DynamicImage::new_rgb8(1, 1))?;
+        let form = reqwest::blocking::multipart::Form::new().part(
+            "image",
+            reqwest::blocking::multipart::Part::bytes(png)
+                .file_name("ping.png")
+                .mime_str("image/png")?,
+        );
+
+        let client = reqwest::blocking::Client::builder()
+            .timeout(Duration::from_secs(config.server_upload_timeout_secs))
+            .danger_accept_invalid_certs(config.tls_skip_verify)
+            .build()?;
+        let response = client.post(&url).multipart(form).send().map_err(|source| {
+            Error::ServerConnectionFailed {
+                url: url.clone(),
+                source,
+                attempts: 1,
+            }
+        })?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(Error::HttpError {
+                status: status.as_u16(),
+            });
+        }
+        Ok(serde_json::from_str(&response.text()?)?)
+    }
+}
+
+/// Run every check in order, calling `on_check` as each one finishes.
+///
+/// `config` and `repo` are as loaded by the caller, so their errors are reported rather
+/// than stopping the run. The cameras and server are only checked with a config to
+/// check them with, and `/api/ping` only when `ping` is set.
+pub fn run(
+    config: Result<Config>,
+    repo: Result<git2::Repository>,
+    probe: &impl Probe,
+    ping: bool,
+    mut on_check: impl FnMut(&Check),
+) -> Vec<Check> {
+    let mut checks = Vec::new();
+    let mut record = |check: Check| {
+        on_check(&check);
+        checks.push(check);
+    };
+
+    let client = match config {
+        Ok(config) => {
+            record(Check::new("config", Status::Pass, "loaded and valid"));
+            Some(config.client.unwrap_or_default())
+        }
+        Err(e) => {
+            record(Check::new("config", Status::Fail, e.to_string()));
+            None
+        }
+    };
+
+    match &client {
+        Some(client) => {
+            for check in check_cameras(client, probe) {
+                record(check);
+            }
+        }
+        None => record(Check::new("camera", Status::Skip, "no valid config")),
+    }
+
+    record(check_git(repo));
+
+    match &client {
+        Some(client) if client.mode == CaptureMode::Local => {
+            record(Check::new("server", Status::Skip, "local mode, no server"));
+        }
+        Some(client) => {
+            let server = check_server(client, probe);
+            let reachable = !server.is_failure();
+            record(server);
+            if !ping {
+                record(Check::new(
+                    "test upload",
+                    Status::Skip,
+                    "run with --ping to send one",
+                ));
+            } else if reachable {
+                record(check_ping(client, probe));
+            } else {
+                record(Check::new(
+                    "test upload",
+                    Status::Skip,
+                    "server unreachable",
+                ));
+            }
+        }
+        None => record(Check::new("server", Status::Skip, "no valid config")),
+    }
+
+    checks
+}
+
+/// One check per configured camera, each capturing from that camera alone, or one for
+/// the capture source that replaces them.
+fn check_cameras(client: &ClientConfig, probe: &impl Probe) -> Vec<Check> {
+    if let Some(path) = &client.capture_source {
+        let name = "capture source";
+        return vec![match capture::load_capture_source(client, path) {
+            Ok(image) => Check::new(
+                name,
+                Status::Pass,
+                format!(
+                    "{} is a {}x{} image",
+                    path.display(),
+                    image.width(),
+                    image.height()
+                ),
+            ),
+            Err(e) => Check::new(name, Status::Fail, e.to_string()),
+        }];
+    }
+
+    if client.camera_devices.is_empty() {
+        return vec![Check::new(
+            "camera",
+            Status::Fail,
+            "no camera_devices configured",
+        )];
+    }
+
+    client
+        .camera_devices
+        .iter()
+        .map(|device| {
+            let name = format!("camera {}", device.device);
+            let only = ClientConfig {
+                camera_devices: vec![device.clone()],
+                ..client.clone()
+            };
+            match probe.capture(&only) {
+                Ok((width, height)) => Check::new(
+                    name,
+                    Status::Pass,
+                    format!("captured a {width}x{height} frame"),
+                ),
+                Err(e) => Check::new(name, Status::Fail, e.to_string()),
+            }
+        })
+        .collect()
+}
+
+fn check_git(repo: Result<git2::Repository>) -> Check {
+    const NAME: &str = "git";
+
+    let head = repo.and_then(|repo| {
+        let sha = git::resolve_revision(&repo, "HEAD")?;
+        let name = git::get_repo_name(&repo)?;
+        Ok((name, sha))
+    });
+    match head {
+        Ok((name, sha)) => Check::new(
+            NAME,
+            Status::Pass,
+            format!("{name} at {}", &sha[..sha.len().min(7)]),
+        ),
+        Err(e) => Check::new(NAME, Status::Fail, e.to_string()),
+    }
+}
+
+fn check_server(client: &ClientConfig, probe: &impl Probe) -> Check {
+    const NAME: &str = "server";
+
+    let started = Instant::now();
+    let result = probe.server_config(client);
+    let elapsed = started.elapsed();
+    match result {
+        Ok(title) => Check::new(
+            NAME,
+            Status::Pass,
+            format!(
+                "{} is serving {:?} ({} ms)",
+                client.server_url,
+                title,
+                elapsed.as_millis()
+            ),
+        )
+        .timed(elapsed),
+        Err(e) => Check::new(NAME, Status::Fail, format!("{}: {}", client.server_url, e)),
+    }
+}
+
+fn check_ping(client: &ClientConfig, probe: &impl Probe) -> Check {
+    const NAME: &str = "test upload";
+
+    let started = Instant::now();
+    let result = probe.ping(client);
+    let elapsed = started.elapsed();
+    let check = match result {
+        Ok(ping) if ping.read_only => Check::new(
+            NAME,
+            Status::Fail,
+            "server is read-only, uploads would be refused",
+        ),
+        Ok(ping) => Check::new(
+            NAME,
+            Status::Pass,
+            format!(
+                "server read a {}x{} image ({} ms)",
+                ping.width,
+                ping.height,
+                elapsed.as_millis()
+            ),
+        ),
+        Err(e) => Check::new(NAME, Status::Fail, e.to_string()),
+    };
+    check.timed(elapsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CameraDeviceConfig;
+
+    struct StubProbe {
+        /// Devices that capture, the others are busy.
+        working: Vec<&'static str>,
+        server_ok: bool,
+        read_only: bool,
+    }
+
+    impl Default for StubProbe {
+        fn default() -> Self {
+            Self {
+                working: vec!["0"],
+                server_ok: true,
+                read_only: false,
+            }
+        }
+    }
+
+    impl Probe for StubProbe {
+        fn capture(&self, config: &ClientConfig) -> Result<(u32, u32)> {
+            let device = &config.camera_devices[0].device;
+            if self.working.contains(&device.as_str()) {
+                Ok((640, 480))
+            } else {
+                Err(Error::CameraBusy {
+                    device: device.clone(),
+                })
+            }
+        }
+
+        fn server_config(&self, _config: &ClientConfig) -> Result<String> {
+            if self.server_ok {
+                Ok("Lolcommits Gallery".to_string())
+            } else {
+                Err(Error::HttpError { status: 502 })
+            }
+        }
+
+        fn ping(&self, _config: &ClientConfig) -> Result<Ping> {
+            Ok(Ping {
+                width: 1,
+                height: 1,
+                read_only: self.read_only,
+            })
+        }
+    }
+
+    fn config(devices: &[&str]) -> Result<Config> {
+        Ok(Config {
+            client: Some(ClientConfig {
+                camera_devices: devices
+                    .iter()
+                    .map(|&d| CameraDeviceConfig::new(d))
+                    .collect(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+    }
+
+    /// A repository with one commit.
+    fn repo(dir: &std::path::Path) -> Result<git2::Repository> {
+        let repo = git2::Repository::init(dir)?;
+        let signature = git2::Signature::now("Test", "test@example.com")?;
+        let tree = repo.find_tree(repo.index()?.write_tree()?)?;
+        repo.commit(Some("HEAD"), &signature, &signature, "initial", &tree, &[])?;
+        drop(tree);
+        Ok(repo)
+    }
+
+    fn statuses(checks: &[Check]) -> Vec<(&str, Status)> {
+        checks
+            .iter()
+            .map(|check| (check.name.as_str(), check.status))
+            .collect()
+    }
+
+    #[test]
+    fn test_everything_passes() -> Result {
+        let dir = tempfile::tempdir()?;
+        let mut seen = 0;
+
+        let checks = run(
+            config(&["0"]),
+            repo(dir.path()),
+            &StubProbe::default(),
+            true,
+            |_| seen += 1,
+        );
+
+        assert_eq!(
+            statuses(&checks),
+            [
+                ("config", Status::Pass),
+                ("camera 0", Status::Pass),
+                ("git", Status::Pass),
+                ("server", Status::Pass),
+                ("test upload", Status::Pass),
+            ]
+        );
+        assert_eq!(seen, checks.len());
+        assert!(checks[3].elapsed_ms.is_some());
+        assert!(checks[3].detail.contains("\"Lolcommits Gallery\""));
+        Ok(())
+    }
+
+    #[test]
+    fn test_each_camera_is_checked_alone() -> Result {
+        let dir = tempfile::tempdir()?;
+
+        let checks = run(
+            config(&["0", "2"]),
+            repo(dir.path()),
+            &StubProbe::default(),
+            false,
+            |_| {},
+        );
+
+        assert_eq!(checks[1].status, Status::Pass);
+        assert_eq!(
+            (checks[2].name.as_str(), checks[2].status),
+            ("camera 2", Status::Fail)
+        );
+        assert_eq!(checks[2].detail, "camera 2 is busy");
+        assert_eq!(checks.last().map(|check| check.status), Some(Status::Skip));
+        Ok(())
+    }
+
+    #[test]
+    fn test_failures_carry_the_error() {
+        let probe = StubProbe {
+            server_ok: false,
+            ..Default::default()
+        };
+
+        let checks = run(
+            config(&["0"]),
+            Err(Error::NotInGitRepo),
+            &probe,
+            true,
+            |_| {},
+        );
+
+        assert_eq!(
+            statuses(&checks)[2..],
+            [
+                ("git", Status::Fail),
+                ("server", Status::Fail),
+                ("test upload", Status::Skip),
+            ]
+        );
+        assert_eq!(checks[2].detail, Error::NotInGitRepo.to_string());
+        assert!(
+            checks[3].detail.ends_with("status 502"),
+            "{}",
+            checks[3].detail
+        );
+    }
+
+    #[test]
+    fn test_invalid_config_skips_what_needs_it() {
+        let invalid = Err(Error::InvalidConfig {
+            problems: vec!["client.server_url: \"nope\" is not an http(s) URL".to_string()],
+        });
+
+        let checks = run(
+            invalid,
+            Err(Error::NotInGitRepo),
+            &StubProbe::default(),
+            true,
+            |_| {},
+        );
+
+        assert_eq!(
+            statuses(&checks),
+            [
+                ("config", Status::Fail),
+                ("camera", Status::Skip),
+                ("git", Status::Fail),
+                ("server", Status::Skip),
+            ]
+        );
+        assert!(checks[0].detail.contains("client.server_url"));
+    }
+
+    #[test]
+    fn test_read_only_server_fails_the_test_upload() -> Result {
+        let dir = tempfile::tempdir()?;
+        let probe = StubProbe {
+            read_only: true,
+            ..Default::default()
+        };
+
+        let checks = run(config(&["0"]), repo(dir.path()), &probe, true, |_| {});
+
+        assert!(checks[4].is_failure());
+        assert!(checks[4].detail.contains("read-only"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_local_mode_has_no_server_to_check() -> Result {
+        let dir = tempfile::tempdir()?;
+        let mut local = config(&["0"])?;
+        local.client.as_mut().unwrap().mode = CaptureMode::Local;
+
+        let checks = run(
+            Ok(local),
+            repo(dir.path()),
+            &StubProbe::default(),
+            true,
+            |_| {},
+        );
+
+        assert_eq!(checks.last().map(|check| check.status), Some(Status::Skip));
+        assert!(!checks.iter().any(Check::is_failure));
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_shape() {
+        let check = Check::new("server", Status::Pass, "ok").timed(Duration::from_millis(12));
+        assert_eq!(
+            serde_json::to_value(&check).unwrap(),
+            serde_json::json!({"name": "server", "status": "pass", "detail": "ok", "elapsed_ms": 12})
+        );
+        let check = Check::new("git", Status::Skip, "");
+        assert!(
+            serde_json::to_value(&check)
+                .unwrap()
+                .get("elapsed_ms")
+                .is_none()
+        );
+    }
+}
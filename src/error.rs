@@ -41,6 +41,9 @@ pub enum Error {
     #[from]
     SerdeJson(serde_json::Error),
 
+    #[from]
+    Zip(zip::result::ZipError),
+
     NotInGitRepo,
     NoHomeDirectory,
     NoRepoName,
@@ -74,6 +77,9 @@ pub enum Error {
         path: PathBuf,
         source: std::io::Error,
     },
+    ModelChecksumRequired {
+        url: String,
+    },
 
     CameraSymlinkResolution {
         path: PathBuf,
@@ -82,29 +88,549 @@ pub enum Error {
     CameraInvalidDevicePath {
         path: PathBuf,
     },
+    /// A `name:` or `serial:` device matching no camera.
+    CameraNotFound {
+        device: String,
+    },
+    /// A `name:` or `serial:` device matching several cameras, each listed as
+    /// `/dev/videoN (name)`.
+    CameraAmbiguous {
+        device: String,
+        candidates: Vec<String>,
+    },
     CameraBusy {
         device: String,
     },
+    CameraTimeout {
+        device: String,
+    },
+    CaptureSourceNotFound {
+        path: PathBuf,
+    },
+    CaptureSourceUndecodable {
+        path: PathBuf,
+        source: image::ImageError,
+    },
+    CaptureSourceTooLarge {
+        path: PathBuf,
+        size: u64,
+        limit: u64,
+    },
 
     ServerConnectionFailed {
         url: String,
         source: reqwest::Error,
+        /// Requests sent, counting retries.
+        attempts: u32,
     },
 
     UploadFailed {
         status: u16,
         body: String,
+        /// Requests sent, counting retries.
+        attempts: u32,
+    },
+
+    ServerReadOnly {
+        url: String,
+    },
+
+    AlreadyCaptured {
+        revision: String,
+    },
+
+    ProcessingFailed {
+        message: String,
+    },
+
+    ProcessingTimedOut {
+        job_id: String,
+        waited_secs: u64,
+    },
+    /// The server gave up processing an upload after `processing_timeout_secs`.
+    ProcessingDeadlineExceeded {
+        secs: u64,
     },
 
     UnknownCameraFormat {
         format: String,
     },
+
+    UnknownPostProcessor {
+        name: String,
+    },
+
+    InvalidColor {
+        field: &'static str,
+        value: String,
+    },
+
+    InvalidConfig {
+        problems: Vec<String>,
+    },
+
+    UnknownConfigKey {
+        key: String,
+    },
+
+    InvalidConfigValue {
+        key: String,
+        value: String,
+        reason: String,
+    },
+
+    InvalidEnvOverride {
+        variable: String,
+        reason: String,
+    },
+
+    TlsFileRead {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    TlsConfig {
+        reason: String,
+    },
+
+    LowDiskSpace {
+        path: PathBuf,
+        available_mb: u64,
+        min_free_mb: u64,
+    },
+
+    RevisionNotFound {
+        input: String,
+    },
+    HookConflict {
+        path: PathBuf,
+    },
+    RevisionNotSingleCommit {
+        input: String,
+    },
+    /// `lolcommits_upload doctor` found problems, each already reported.
+    ChecksFailed {
+        failed: usize,
+    },
+    /// Commit metadata with nothing left of a repo name or revision to put in an image's
+    /// filename.
+    UnsafeFilename {
+        field: &'static str,
+        value: String,
+    },
+    PathOutsideDir {
+        path: PathBuf,
+        dir: PathBuf,
+    },
 }
 
 impl std::fmt::Display for Error {
     fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::result::Result<(), std::fmt::Error> {
-        write!(fmt, "{self:?}")
+        match self {
+            Error::Git(e) => write!(fmt, "git error: {e}"),
+            Error::Io(e) => write!(fmt, "I/O error: {e}"),
+            Error::Image(e) => write!(fmt, "image error: {e}"),
+            Error::Camera(e) => write!(fmt, "camera error: {e}"),
+            Error::OpenCV(e) => write!(fmt, "OpenCV error: {e}"),
+            Error::Xdg(e) => write!(fmt, "XDG directory error: {e}"),
+            Error::TomlDeserialize(e) => write!(fmt, "invalid config: {e}"),
+            Error::TomlSerialize(e) => write!(fmt, "failed to serialize config: {e}"),
+            Error::Reqwest(e) => write!(fmt, "HTTP client error: {e}"),
+            Error::PngEncoding(e) => write!(fmt, "PNG encoding error: {e}"),
+            Error::PngDecoding(e) => write!(fmt, "PNG decoding error: {e}"),
+            Error::Zip(e) => write!(fmt, "ZIP error: {e}"),
+            Error::SerdeJson(e) => write!(fmt, "JSON error: {e}"),
+            Error::NotInGitRepo => write!(fmt, "not in a git repository"),
+            Error::NoHomeDirectory => write!(fmt, "could not determine home directory"),
+            Error::NoRepoName => write!(fmt, "could not determine repository name"),
+            Error::GitCommandFailed => write!(fmt, "git command failed"),
+            Error::ConfigFileRead { path, source } => {
+                write!(fmt, "failed to read config {}: {source}", path.display())
+            }
+            Error::ConfigFileWrite { path, source } => {
+                write!(fmt, "failed to write config {}: {source}", path.display())
+            }
+            Error::HttpError { status } => write!(fmt, "HTTP request failed with status {status}"),
+            Error::ModelFileTooSmall { size } => {
+                write!(fmt, "downloaded model is too small ({size} bytes)")
+            }
+            Error::ModelChecksumMismatch { expected, actual } => write!(
+                fmt,
+                "model checksum mismatch (expected {expected}, got {actual})"
+            ),
+            Error::ModelDirectoryCreate { path, source } => write!(
+                fmt,
+                "failed to create models directory {}: {source}",
+                path.display()
+            ),
+            Error::ModelFileWrite { path, source } => {
+                write!(fmt, "failed to write model {}: {source}", path.display())
+            }
+            Error::ModelChecksumRequired { url } => write!(
+                fmt,
+                "segmentation.model_url {url} needs a segmentation.model_checksum (the file's MD5)"
+            ),
+            Error::CameraSymlinkResolution { path, source } => write!(
+                fmt,
+                "failed to resolve camera symlink {}: {source}",
+                path.display()
+            ),
+            Error::CameraInvalidDevicePath { path } => {
+                write!(fmt, "invalid camera device path {}", path.display())
+            }
+            Error::CameraNotFound { device } => write!(fmt, "no camera matches {device}"),
+            Error::CameraAmbiguous { device, candidates } => write!(
+                fmt,
+                "{device} matches several cameras: {}",
+                candidates.join(", ")
+            ),
+            Error::CameraBusy { device } => write!(fmt, "camera {device} is busy"),
+            Error::CameraTimeout { device } => {
+                write!(fmt, "camera {device} stopped responding")
+            }
+            Error::CaptureSourceNotFound { path } => {
+                write!(fmt, "capture source {} does not exist", path.display())
+            }
+            Error::CaptureSourceUndecodable { path, source } => write!(
+                fmt,
+                "capture source {} is not a readable image: {source}",
+                path.display()
+            ),
+            Error::CaptureSourceTooLarge { path, size, limit } => write!(
+                fmt,
+                "capture source {} is {size} bytes, more than the {limit} allowed (capture_source_max_bytes)",
+                path.display()
+            ),
+            Error::ServerConnectionFailed {
+                url,
+                source,
+                attempts,
+            } => write!(
+                fmt,
+                "failed to connect to {url}{}: {source}",
+                after_attempts(*attempts)
+            ),
+            Error::UploadFailed {
+                status,
+                body,
+                attempts,
+            } => write!(
+                fmt,
+                "upload failed with status {status}{}: {body}",
+                after_attempts(*attempts)
+            ),
+            Error::ServerReadOnly { url } => write!(fmt, "server {url} is read-only"),
+            Error::ProcessingFailed { message } => {
+                write!(fmt, "server failed to process the upload: {message}")
+            }
+            Error::ProcessingTimedOut {
+                job_id,
+                waited_secs,
+            } => write!(
+                fmt,
+                "upload still processing after {waited_secs}s (job {job_id})"
+            ),
+            Error::AlreadyCaptured { revision } => write!(
+                fmt,
+                "revision {revision} is already captured, use --force to capture it again"
+            ),
+            Error::UnknownCameraFormat { format } => {
+                write!(fmt, "unknown camera format {format:?}")
+            }
+            Error::RevisionNotFound { input } => write!(fmt, "revision {input:?} not found"),
+            Error::HookConflict { path } => write!(
+                fmt,
+                "{} is not a shell script lolcommits can add to, `lolcommits_upload install --force` replaces it (keeping a backup)",
+                path.display()
+            ),
+            Error::RevisionNotSingleCommit { input } => {
+                write!(
+                    fmt,
+                    "{input:?} is not a single commit, single commit required"
+                )
+            }
+            Error::LowDiskSpace {
+                path,
+                available_mb,
+                min_free_mb,
+            } => write!(
+                fmt,
+                "only {available_mb} MiB free for {}, need at least {min_free_mb} MiB (min_free_space_mb); free up space or remove old images",
+                path.display()
+            ),
+            Error::UnknownPostProcessor { name } => write!(
+                fmt,
+                "unknown post-processor {name:?}, expected one of: {}",
+                crate::post_processor::STAGES.join(", ")
+            ),
+            Error::InvalidColor { field, value } => write!(
+                fmt,
+                "invalid color {value:?} for burned_in_chyron.{field}, expected a hex color like \"#ffcc00\""
+            ),
+            Error::InvalidConfig { problems } => {
+                write!(fmt, "invalid config:")?;
+                for problem in problems {
+                    write!(fmt, "\n  {problem}")?;
+                }
+                Ok(())
+            }
+            Error::UnknownConfigKey { key } => write!(fmt, "unknown config key {key:?}"),
+            Error::InvalidConfigValue { key, value, reason } => {
+                write!(fmt, "invalid value {value:?} for {key}: {reason}")
+            }
+            Error::InvalidEnvOverride { variable, reason } => {
+                write!(fmt, "environment variable {variable}: {reason}")
+            }
+            Error::TlsFileRead { path, source } => {
+                write!(fmt, "failed to read TLS file {}: {source}", path.display())
+            }
+            Error::TlsConfig { reason } => {
+                write!(fmt, "invalid TLS certificate or key: {reason}")
+            }
+            Error::ChecksFailed { failed: 1 } => write!(fmt, "1 check failed"),
+            Error::ChecksFailed { failed } => write!(fmt, "{failed} checks failed"),
+            Error::ProcessingDeadlineExceeded { secs } => write!(
+                fmt,
+                "processing took longer than {secs}s (processing_timeout_secs)"
+            ),
+            Error::UnsafeFilename { field, value } => {
+                write!(fmt, "{field} {value:?} can't be used in an image filename")
+            }
+            Error::PathOutsideDir { path, dir } => write!(
+                fmt,
+                "refusing to write {} outside {}",
+                path.display(),
+                dir.display()
+            ),
+        }
+    }
+}
+
+/// ` after N attempts` when a request was retried, for the messages of errors that count
+/// them.
+pub fn after_attempts(attempts: u32) -> String {
+    match attempts {
+        0 | 1 => String::new(),
+        n => format!(" after {n} attempts"),
+    }
+}
+
+impl Error {
+    /// The process exit status `lolcommits_upload` uses for this error, so hooks can tell
+    /// failure classes apart. Listed in `lolcommits_upload --help`; scripts depend on
+    /// these numbers, so don't change them.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::RevisionNotFound { .. }
+            | Error::RevisionNotSingleCommit { .. }
+            | Error::HookConflict { .. } => 2,
+            Error::Camera(_)
+            | Error::CameraInvalidDevicePath { .. }
+            | Error::CameraNotFound { .. }
+            | Error::CameraAmbiguous { .. }
+            | Error::CameraSymlinkResolution { .. }
+            | Error::CaptureSourceNotFound { .. }
+            | Error::CaptureSourceUndecodable { .. }
+            | Error::CaptureSourceTooLarge { .. } => 10,
+            Error::CameraBusy { .. } | Error::CameraTimeout { .. } => 11,
+            Error::NotInGitRepo | Error::NoRepoName => 20,
+            Error::ServerConnectionFailed { .. } => 30,
+            Error::UploadFailed { .. }
+            | Error::ServerReadOnly { .. }
+            | Error::AlreadyCaptured { .. }
+            | Error::ProcessingFailed { .. }
+            | Error::ProcessingTimedOut { .. } => 31,
+            Error::ConfigFileRead { .. }
+            | Error::ConfigFileWrite { .. }
+            | Error::TomlDeserialize(_)
+            | Error::TomlSerialize(_)
+            | Error::NoHomeDirectory
+            | Error::UnknownCameraFormat { .. }
+            | Error::UnknownPostProcessor { .. }
+            | Error::InvalidColor { .. }
+            | Error::InvalidConfig { .. }
+            | Error::UnknownConfigKey { .. }
+            | Error::InvalidConfigValue { .. }
+            | Error::InvalidEnvOverride { .. }
+            | Error::TlsFileRead { .. }
+            | Error::TlsConfig { .. } => 40,
+            _ => 1,
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Git(e) => Some(e),
+            Error::Io(e) => Some(e),
+            Error::Image(e) => Some(e),
+            Error::Camera(e) => Some(e),
+            Error::OpenCV(e) => Some(e),
+            Error::Xdg(e) => Some(e),
+            Error::TomlDeserialize(e) => Some(e),
+            Error::TomlSerialize(e) => Some(e),
+            Error::Reqwest(e) => Some(e),
+            Error::PngEncoding(e) => Some(e),
+            Error::PngDecoding(e) => Some(e),
+            Error::SerdeJson(e) => Some(e),
+            Error::Zip(e) => Some(e),
+            Error::ConfigFileRead { source, .. }
+            | Error::ConfigFileWrite { source, .. }
+            | Error::ModelDirectoryCreate { source, .. }
+            | Error::ModelFileWrite { source, .. }
+            | Error::CameraSymlinkResolution { source, .. }
+            | Error::TlsFileRead { source, .. } => Some(source),
+            Error::ServerConnectionFailed { source, .. } => Some(source),
+            Error::CaptureSourceUndecodable { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// `error: {error}` then a `caused by:` line for each error down its source chain, for
+/// the binaries to print. Causes the message above already quotes are skipped, as most
+/// variants include their immediate source.
+pub fn report(error: &(dyn std::error::Error + 'static)) -> String {
+    let mut report = format!("error: {error}");
+    let mut previous = error.to_string();
+    let mut cause = error.source();
+    while let Some(error) = cause {
+        let message = error.to_string();
+        if !previous.contains(&message) {
+            report.push_str(&format!("\ncaused by: {message}"));
+        }
+        previous = message;
+        cause = error.source();
     }
+    report
 }
 
-impl std::error::Error for Error {}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(Error::CameraBusy { device: "/dev/video0".to_string() }, "camera /dev/video0 is busy" ; "camera busy")]
+    #[test_case(Error::CameraTimeout { device: "/dev/video0".to_string() }, "camera /dev/video0 stopped responding" ; "camera timeout")]
+    #[test_case(Error::CameraAmbiguous { device: "name:brio".to_string(), candidates: vec!["/dev/video0 (Logitech BRIO)".to_string(), "/dev/video4 (Logitech BRIO)".to_string()] }, "name:brio matches several cameras: /dev/video0 (Logitech BRIO), /dev/video4 (Logitech BRIO)" ; "camera ambiguous")]
+    #[test_case(Error::CaptureSourceNotFound { path: PathBuf::from("/tmp/avatar.png") }, "capture source /tmp/avatar.png does not exist" ; "capture source not found")]
+    #[test_case(Error::CaptureSourceTooLarge { path: PathBuf::from("/tmp/raw.tiff"), size: 90_000_000, limit: 26_214_400 }, "capture source /tmp/raw.tiff is 90000000 bytes, more than the 26214400 allowed (capture_source_max_bytes)" ; "capture source too large")]
+    #[test_case(Error::UploadFailed { status: 500, body: "boom".to_string(), attempts: 1 }, "upload failed with status 500: boom" ; "upload failed")]
+    #[test_case(Error::UploadFailed { status: 502, body: "bad gateway".to_string(), attempts: 4 }, "upload failed with status 502 after 4 attempts: bad gateway" ; "upload failed after retries")]
+    #[test_case(Error::ServerReadOnly { url: "http://lol".to_string() }, "server http://lol is read-only" ; "server read only")]
+    #[test_case(Error::AlreadyCaptured { revision: "abc1234".to_string() }, "revision abc1234 is already captured, use --force to capture it again" ; "already captured")]
+    #[test_case(Error::ProcessingFailed { message: "disk full".to_string() }, "server failed to process the upload: disk full" ; "processing failed")]
+    #[test_case(Error::ProcessingTimedOut { job_id: "42".to_string(), waited_secs: 120 }, "upload still processing after 120s (job 42)" ; "processing timed out")]
+    #[test_case(Error::UnknownCameraFormat { format: "H264".to_string() }, "unknown camera format \"H264\"" ; "unknown camera format")]
+    #[test_case(Error::UnknownPostProcessor { name: "qr".to_string() }, "unknown post-processor \"qr\", expected one of: background, chyron" ; "unknown post processor")]
+    #[test_case(Error::LowDiskSpace { path: PathBuf::from("/srv/lolcommits"), available_mb: 12, min_free_mb: 100 }, "only 12 MiB free for /srv/lolcommits, need at least 100 MiB (min_free_space_mb); free up space or remove old images" ; "low disk space")]
+    #[test_case(Error::RevisionNotFound { input: "feature/typo".to_string() }, "revision \"feature/typo\" not found" ; "revision not found")]
+    #[test_case(Error::RevisionNotSingleCommit { input: "a..b".to_string() }, "\"a..b\" is not a single commit, single commit required" ; "revision range")]
+    #[test_case(Error::InvalidColor { field: "sha_color", value: "red".to_string() }, "invalid color \"red\" for burned_in_chyron.sha_color, expected a hex color like \"#ffcc00\"" ; "invalid color")]
+    #[test_case(Error::InvalidConfig { problems: vec!["client.server_url: bad".to_string(), "server.images_dir: worse".to_string()] }, "invalid config:\n  client.server_url: bad\n  server.images_dir: worse" ; "invalid config")]
+    #[test_case(Error::UnknownConfigKey { key: "client.server_ulr".to_string() }, "unknown config key \"client.server_ulr\"" ; "unknown config key")]
+    #[test_case(Error::InvalidConfigValue { key: "client.animate".to_string(), value: "yes".to_string(), reason: "expected true or false".to_string() }, "invalid value \"yes\" for client.animate: expected true or false" ; "invalid config value")]
+    #[test_case(Error::InvalidEnvOverride { variable: "LOLCOMMITS_SERVER__BIND_PORT".to_string(), reason: "not a port".to_string() }, "environment variable LOLCOMMITS_SERVER__BIND_PORT: not a port" ; "invalid env override")]
+    #[test_case(Error::TlsConfig { reason: "key does not match certificate".to_string() }, "invalid TLS certificate or key: key does not match certificate" ; "tls config")]
+    #[test_case(Error::HookConflict { path: PathBuf::from(".git/hooks/post-commit") }, ".git/hooks/post-commit is not a shell script lolcommits can add to, `lolcommits_upload install --force` replaces it (keeping a backup)" ; "hook conflict")]
+    #[test_case(Error::HttpError { status: 404 }, "HTTP request failed with status 404" ; "http error")]
+    #[test_case(Error::ModelChecksumRequired { url: "https://example.com/m.onnx".to_string() }, "segmentation.model_url https://example.com/m.onnx needs a segmentation.model_checksum (the file's MD5)" ; "model checksum required")]
+    #[test_case(Error::NotInGitRepo, "not in a git repository" ; "not in git repo")]
+    #[test_case(Error::ChecksFailed { failed: 1 }, "1 check failed" ; "one check failed")]
+    #[test_case(Error::ChecksFailed { failed: 3 }, "3 checks failed" ; "checks failed")]
+    #[test_case(Error::ProcessingDeadlineExceeded { secs: 120 }, "processing took longer than 120s (processing_timeout_secs)" ; "processing deadline exceeded")]
+    #[test_case(Error::UnsafeFilename { field: "revision", value: "../x".to_string() }, "revision \"../x\" can't be used in an image filename" ; "unsafe filename")]
+    #[test_case(Error::PathOutsideDir { path: PathBuf::from("/srv/images/../x.png"), dir: PathBuf::from("/srv/images") }, "refusing to write /srv/images/../x.png outside /srv/images" ; "path outside dir")]
+    fn test_display(error: Error, expected: &str) {
+        assert_eq!(error.to_string(), expected);
+    }
+
+    #[test_case(Error::RevisionNotFound { input: "nope".to_string() }, 2 ; "usage")]
+    #[test_case(Error::CameraInvalidDevicePath { path: PathBuf::from("/dev/video9") }, 10 ; "camera not found")]
+    #[test_case(Error::CameraNotFound { device: "serial:ABC123".to_string() }, 10 ; "camera selector not found")]
+    #[test_case(Error::CaptureSourceNotFound { path: PathBuf::from("/tmp/avatar.png") }, 10 ; "capture source not found")]
+    #[test_case(Error::CaptureSourceTooLarge { path: PathBuf::from("/tmp/raw.tiff"), size: 2, limit: 1 }, 10 ; "capture source too large")]
+    #[test_case(Error::CameraBusy { device: "/dev/video0".to_string() }, 11 ; "camera busy")]
+    #[test_case(Error::CameraTimeout { device: "/dev/video0".to_string() }, 11 ; "camera timeout")]
+    #[test_case(Error::NotInGitRepo, 20 ; "not in git repo")]
+    #[test_case(Error::UploadFailed { status: 500, body: "boom".to_string(), attempts: 1 }, 31 ; "upload failed")]
+    #[test_case(Error::ServerReadOnly { url: "http://lol".to_string() }, 31 ; "server read only")]
+    #[test_case(Error::AlreadyCaptured { revision: "abc1234".to_string() }, 31 ; "already captured")]
+    #[test_case(Error::ConfigFileRead { path: PathBuf::from("/etc/lolcommits.toml"), source: std::io::Error::other("denied") }, 40 ; "config read")]
+    #[test_case(Error::InvalidColor { field: "sha_color", value: "red".to_string() }, 40 ; "invalid color")]
+    #[test_case(Error::Io(std::io::Error::other("disk on fire")), 1 ; "other")]
+    #[test_case(Error::ChecksFailed { failed: 2 }, 1 ; "checks failed")]
+    #[test_case(Error::ProcessingDeadlineExceeded { secs: 120 }, 1 ; "processing deadline exceeded")]
+    #[test_case(Error::UnsafeFilename { field: "repo_name", value: "..".to_string() }, 1 ; "unsafe filename")]
+    fn test_exit_code(error: Error, expected: i32) {
+        assert_eq!(error.exit_code(), expected);
+    }
+
+    #[test]
+    fn test_server_connection_failed_keeps_source() {
+        // Nothing listens on port 1, so the send fails without leaving the machine
+        let source = reqwest::blocking::get("http://127.0.0.1:1/").unwrap_err();
+        let error = Error::ServerConnectionFailed {
+            url: "http://127.0.0.1:1/api/upload".to_string(),
+            source,
+            attempts: 3,
+        };
+
+        assert!(
+            error.to_string().starts_with(
+                "failed to connect to http://127.0.0.1:1/api/upload after 3 attempts: "
+            )
+        );
+        assert!(std::error::Error::source(&error).is_some());
+        assert_eq!(error.exit_code(), 30);
+    }
+
+    #[test]
+    fn test_io_error_converts_via_from() {
+        let error: Error = std::io::Error::other("disk on fire").into();
+        assert!(matches!(error, Error::Io(_)));
+        assert_eq!(error.to_string(), "I/O error: disk on fire");
+    }
+
+    #[derive(Debug)]
+    struct Wrapped(Error);
+
+    impl std::fmt::Display for Wrapped {
+        fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(fmt, "setup failed")
+        }
+    }
+
+    impl std::error::Error for Wrapped {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            Some(&self.0)
+        }
+    }
+
+    #[test]
+    fn test_report_skips_quoted_source() {
+        let error = Error::ConfigFileRead {
+            path: PathBuf::from("/etc/lolcommits.toml"),
+            source: std::io::Error::other("permission denied"),
+        };
+
+        assert_eq!(
+            report(&error),
+            "error: failed to read config /etc/lolcommits.toml: permission denied"
+        );
+    }
+
+    #[test]
+    fn test_report_lists_causes() {
+        let error = Wrapped(Error::ConfigFileRead {
+            path: PathBuf::from("/etc/lolcommits.toml"),
+            source: std::io::Error::other("permission denied"),
+        });
+
+        assert_eq!(
+            report(&error),
+            "error: setup failed\ncaused by: failed to read config /etc/lolcommits.toml: permission denied"
+        );
+    }
+}
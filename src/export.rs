@@ -0,0 +1,167 @@
+//! ZIP export of gallery images, for archiving a project's lolcommits.
+//!
+//! The archive is written front to back to any [`Write`], so the server can stream it
+//! to the client as it is produced instead of holding a gallery's worth of images in
+//! memory.
+
+use crate::error::Result;
+use crate::git::CommitMetadata;
+use chrono::{Datelike, Timelike};
+use serde::Serialize;
+use std::io::Write;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// Name of the archive entry listing the metadata of every included image.
+pub const MANIFEST_NAME: &str = "manifest.json";
+
+#[derive(Debug, Serialize)]
+struct ManifestEntry<'a> {
+    filename: &'a str,
+    #[serde(flatten)]
+    metadata: &'a CommitMetadata,
+}
+
+/// Write a ZIP of `images`, each under its filename and dated by its commit, followed
+/// by a [`MANIFEST_NAME`] with their metadata. Images are stored rather than compressed,
+/// as they already are. An image that has gone since it was listed is left out of the
+/// archive and the manifest. Returns how many images were included.
+pub fn write_archive<W: Write>(out: W, images: &[CommitMetadata]) -> Result<usize> {
+    let mut zip = ZipWriter::new_stream(out);
+    let stored = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Stored)
+        .large_file(true);
+
+    let mut included = Vec::with_capacity(images.len());
+    for image in images {
+        let Some(filename) = image.path.file_name().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        let mut file = match std::fs::File::open(&image.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                tracing::warn!(filename, "Image deleted during export, leaving it out");
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let options = match zip_time(&image.timestamp) {
+            Some(time) => stored.last_modified_time(time),
+            None => stored,
+        };
+        zip.start_file(filename, options)?;
+        std::io::copy(&mut file, &mut zip)?;
+        included.push(ManifestEntry {
+            filename,
+            metadata: image,
+        });
+    }
+
+    zip.start_file(
+        MANIFEST_NAME,
+        SimpleFileOptions::default().compression_method(CompressionMethod::Stored),
+    )?;
+    serde_json::to_writer_pretty(&mut zip, &included)?;
+    zip.finish()?.flush()?;
+    Ok(included.len())
+}
+
+/// An image's commit time as a ZIP timestamp, `None` outside the years ZIP can record.
+fn zip_time(timestamp: &str) -> Option<zip::DateTime> {
+    let taken = crate::local_time(timestamp)?;
+    zip::DateTime::from_date_and_time(
+        u16::try_from(taken.year()).ok()?,
+        taken.month() as u8,
+        taken.day() as u8,
+        taken.hour() as u8,
+        taken.minute() as u8,
+        taken.second() as u8,
+    )
+    .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_metadata;
+    use std::io::{Cursor, Read};
+    use std::path::Path;
+
+    fn image(dir: &Path, filename: &str, contents: &[u8]) -> CommitMetadata {
+        let path = dir.join(filename);
+        std::fs::write(&path, contents).unwrap();
+        image_metadata::parse_filename(&path).unwrap()
+    }
+
+    #[test]
+    fn test_archive_holds_images_and_manifest() -> Result {
+        let dir = tempfile::tempdir()?;
+        let images = [
+            image(dir.path(), "app-20240301-120000-bbb2222.png", b"second"),
+            image(dir.path(), "app-20240201-093000-aaa1111.png", b"first"),
+        ];
+
+        let mut archive = Vec::new();
+        assert_eq!(write_archive(&mut archive, &images)?, 2);
+
+        let mut zip = zip::ZipArchive::new(Cursor::new(archive))?;
+        let names: Vec<_> = zip.file_names().map(str::to_owned).collect();
+        assert_eq!(
+            names,
+            [
+                "app-20240301-120000-bbb2222.png",
+                "app-20240201-093000-aaa1111.png",
+                MANIFEST_NAME
+            ]
+        );
+
+        let mut contents = String::new();
+        let mut entry = zip.by_name("app-20240201-093000-aaa1111.png")?;
+        assert_eq!(entry.compression(), CompressionMethod::Stored);
+        let modified = entry.last_modified().unwrap();
+        assert_eq!(
+            (modified.year(), modified.month(), modified.hour()),
+            (2024, 2, 9)
+        );
+        entry.read_to_string(&mut contents)?;
+        assert_eq!(contents, "first");
+        drop(entry);
+
+        let manifest: serde_json::Value = serde_json::from_reader(zip.by_name(MANIFEST_NAME)?)?;
+        assert_eq!(manifest[0]["filename"], "app-20240301-120000-bbb2222.png");
+        assert_eq!(manifest[1]["revision"], "aaa1111");
+        assert_eq!(manifest[1]["repo_name"], "app");
+        Ok(())
+    }
+
+    #[test]
+    fn test_vanished_images_are_left_out() -> Result {
+        let dir = tempfile::tempdir()?;
+        let images = [
+            image(dir.path(), "app-20240301-120000-bbb2222.png", b"kept"),
+            image(dir.path(), "app-20240201-093000-aaa1111.png", b"deleted"),
+        ];
+        std::fs::remove_file(&images[1].path)?;
+
+        let mut archive = Vec::new();
+        assert_eq!(write_archive(&mut archive, &images)?, 1);
+
+        let mut zip = zip::ZipArchive::new(Cursor::new(archive))?;
+        assert_eq!(zip.len(), 2);
+        let manifest: serde_json::Value = serde_json::from_reader(zip.by_name(MANIFEST_NAME)?)?;
+        assert_eq!(manifest.as_array().map(Vec::len), Some(1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_empty_export_has_an_empty_manifest() -> Result {
+        let mut archive = Vec::new();
+        assert_eq!(write_archive(&mut archive, &[])?, 0);
+
+        let mut zip = zip::ZipArchive::new(Cursor::new(archive))?;
+        let manifest: serde_json::Value = serde_json::from_reader(zip.by_name(MANIFEST_NAME)?)?;
+        assert_eq!(manifest, serde_json::json!([]));
+        Ok(())
+    }
+}
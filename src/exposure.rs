@@ -0,0 +1,210 @@
+//! Brightening of dark uploads (`auto_exposure_correction`).
+//!
+//! A capture whose mean luminance is below `low_light_threshold` gets a linear stretch
+//! of its levels, from the darkest to the brightest percent of its pixels, before any
+//! other processing. Frames of an animation all get the stretch planned from the first,
+//! so the correction doesn't flicker. Well exposed captures are left exactly as they are.
+
+use image::{DynamicImage, RgbaImage};
+
+/// Fraction of pixels at each end of the histogram treated as outliers when planning
+/// the stretch, so a few hot or dead pixels don't cancel it out.
+const CLIP_FRACTION: f64 = 0.01;
+
+/// Most a stretch brightens by, so noise in near-black frames isn't blown up.
+const MAX_GAIN: f32 = 4.0;
+
+/// Mean luminance from 0 to 255 (Rec. 601 weights) of `image`, 0 when it's empty.
+pub fn mean_luminance(image: &DynamicImage) -> f32 {
+    mean(&histogram(&image.to_rgba8())).unwrap_or(0.0)
+}
+
+/// Brighten `frames` when the first is darker than `threshold`, returning whether they
+/// were. Logs a warning about dark frames whether or not `enabled` allows fixing them.
+pub fn correct(frames: &mut [DynamicImage], threshold: f32, enabled: bool) -> bool {
+    let Some(first) = frames.first() else {
+        return false;
+    };
+    let first = first.to_rgba8();
+    let histogram = histogram(&first);
+    let Some(mean) = mean(&histogram).filter(|&mean| mean < threshold) else {
+        return false;
+    };
+    if !enabled {
+        tracing::warn!(
+            mean_luminance = mean,
+            threshold,
+            "Low light capture, enable auto_exposure_correction to brighten it"
+        );
+        return false;
+    }
+
+    let Some(stretch) = Stretch::plan(&histogram) else {
+        tracing::warn!(
+            mean_luminance = mean,
+            threshold,
+            "Low light capture too flat to correct"
+        );
+        return false;
+    };
+    for (i, frame) in frames.iter_mut().enumerate() {
+        let mut rgba = if i == 0 {
+            first.clone()
+        } else {
+            frame.to_rgba8()
+        };
+        stretch.apply(&mut rgba);
+        *frame = DynamicImage::ImageRgba8(rgba);
+    }
+    tracing::info!(
+        mean_luminance = mean,
+        threshold,
+        low = stretch.low,
+        gain = stretch.gain,
+        frames = frames.len(),
+        "Corrected low light capture"
+    );
+    true
+}
+
+/// How many pixels of `image` have each luminance.
+fn histogram(image: &RgbaImage) -> [u64; 256] {
+    let mut histogram = [0u64; 256];
+    for pixel in image.pixels() {
+        histogram[luminance(pixel.0) as usize] += 1;
+    }
+    histogram
+}
+
+fn mean(histogram: &[u64; 256]) -> Option<f32> {
+    let count: u64 = histogram.iter().sum();
+    let total: u64 = histogram
+        .iter()
+        .enumerate()
+        .map(|(level, n)| level as u64 * n)
+        .sum();
+    (count > 0).then(|| total as f32 / count as f32)
+}
+
+fn luminance([r, g, b, _]: [u8; 4]) -> u8 {
+    (0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32).round() as u8
+}
+
+/// Levels mapped as `(level - low) * gain`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Stretch {
+    low: f32,
+    gain: f32,
+}
+
+impl Stretch {
+    /// Stretch the histogram's middle levels across the full range, `None` when there's
+    /// nothing to stretch.
+    fn plan(histogram: &[u64; 256]) -> Option<Self> {
+        let count: u64 = histogram.iter().sum();
+        let clip = (count as f64 * CLIP_FRACTION) as u64;
+        let low = percentile(histogram.iter().copied().enumerate(), clip)?;
+        let high = percentile(histogram.iter().copied().enumerate().rev(), clip)?;
+        if high <= low {
+            return None;
+        }
+        let gain = (255.0 / (high - low) as f32).min(MAX_GAIN);
+        (gain > 1.0).then_some(Self {
+            low: low as f32,
+            gain,
+        })
+    }
+
+    /// Stretch the colour channels of every pixel, leaving alpha alone.
+    fn apply(&self, image: &mut RgbaImage) {
+        let lut: [u8; 256] = std::array::from_fn(|level| {
+            ((level as f32 - self.low) * self.gain)
+                .round()
+                .clamp(0.0, 255.0) as u8
+        });
+        for pixel in image.pixels_mut() {
+            for channel in &mut pixel.0[..3] {
+                *channel = lut[*channel as usize];
+            }
+        }
+    }
+}
+
+/// The first level after `skip` pixels, counting from whichever end `levels` starts at.
+fn percentile(levels: impl Iterator<Item = (usize, u64)>, skip: u64) -> Option<usize> {
+    let mut seen = 0;
+    for (level, n) in levels {
+        seen += n;
+        if seen > skip {
+            return Some(level);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    /// A horizontal ramp from `from` to `to` grey.
+    fn ramp(from: u8, to: u8) -> DynamicImage {
+        let width = 64u32;
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(width, 4, |x, _| {
+            let level = from as u32 + (to - from) as u32 * x / (width - 1);
+            Rgba([level as u8, level as u8, level as u8, 200])
+        }))
+    }
+
+    #[test]
+    fn test_dark_frames_are_brightened() {
+        let mut frames = vec![ramp(5, 60), ramp(5, 60)];
+        let before = mean_luminance(&frames[0]);
+
+        assert!(correct(&mut frames, 60.0, true));
+
+        let after = mean_luminance(&frames[0]);
+        assert!(after > before * 2.0, "{before} -> {after}");
+        assert_eq!(frames[0], frames[1], "frames stretched differently");
+        assert_eq!(frames[0].to_rgba8().get_pixel(0, 0)[3], 200);
+    }
+
+    #[test]
+    fn test_well_exposed_frames_are_untouched() {
+        let original = ramp(40, 220);
+        let mut frames = vec![original.clone()];
+
+        assert!(!correct(&mut frames, 60.0, true));
+        assert_eq!(frames[0], original);
+    }
+
+    #[test]
+    fn test_disabled_correction_leaves_dark_frames() {
+        let original = ramp(5, 60);
+        let mut frames = vec![original.clone()];
+
+        assert!(!correct(&mut frames, 60.0, false));
+        assert_eq!(frames[0], original);
+    }
+
+    #[test]
+    fn test_flat_frames_are_not_stretched() {
+        let black = DynamicImage::new_rgb8(8, 8);
+        let mut frames = vec![black.clone()];
+
+        assert!(!correct(&mut frames, 60.0, true));
+        assert_eq!(frames[0], black);
+        assert!(!correct(&mut [], 60.0, true));
+    }
+
+    #[test]
+    fn test_gain_is_capped() {
+        let mut histogram = [0u64; 256];
+        histogram[10..20].fill(100);
+
+        let stretch = Stretch::plan(&histogram).unwrap();
+
+        assert_eq!(stretch.gain, MAX_GAIN);
+        assert_eq!(stretch.low, 10.0);
+    }
+}
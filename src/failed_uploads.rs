@@ -0,0 +1,94 @@
+//! Uploads the server couldn't process, kept in `images_dir/failed`.
+//!
+//! When processing an upload fails or runs past `processing_timeout_secs`, its frames
+//! are moved here as received, in a directory named after its job, beside the metadata
+//! it was sent with and the error. An admin can look into them and replay one by posting
+//! its files back to `/api/upload`. Nothing here is served or indexed, and nothing clears
+//! it out.
+
+use crate::error::Result;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// Directory within images_dir that failed uploads are kept in.
+pub const FAILED_DIR: &str = "failed";
+
+/// The upload's metadata, as sent in its `metadata` part.
+pub const METADATA_NAME: &str = "metadata.json";
+
+/// Why processing failed.
+pub const ERROR_NAME: &str = "error.txt";
+
+/// Move the frames of the failed upload `job_id` into `images_dir/failed/{job_id}` as
+/// `frame-0.png` and so on, with its metadata and error. Returns the directory.
+pub fn keep(
+    images_dir: &Path,
+    job_id: &str,
+    frames: &[impl AsRef<Path>],
+    metadata: &impl Serialize,
+    error: &str,
+) -> Result<PathBuf> {
+    let dir = images_dir.join(FAILED_DIR).join(job_id);
+    std::fs::create_dir_all(&dir)?;
+
+    for (index, frame) in frames.iter().enumerate() {
+        let frame = frame.as_ref();
+        let target = dir.join(format!("frame-{index}.{}", extension(frame)));
+        // Uploads are streamed into images_dir, so this is normally a rename within one
+        // filesystem
+        if let Err(e) = std::fs::rename(frame, &target) {
+            tracing::debug!(frame = %frame.display(), error = %e, "Rename failed, copying instead");
+            std::fs::copy(frame, &target)?;
+        }
+    }
+    crate::storage::atomic_write(&dir, METADATA_NAME, serde_json::to_vec_pretty(metadata)?)?;
+    crate::storage::atomic_write(&dir, ERROR_NAME, error)?;
+    Ok(dir)
+}
+
+/// The extension of the image format `path` holds, `bin` when it isn't one.
+fn extension(path: &Path) -> &'static str {
+    image::ImageReader::open(path)
+        .and_then(|reader| reader.with_guessed_format())
+        .ok()
+        .and_then(|reader| reader.format())
+        .and_then(|format| format.extensions_str().first().copied())
+        .unwrap_or("bin")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frames_are_moved_with_metadata_and_error() -> Result {
+        let dir = tempfile::tempdir()?;
+        let images_dir = dir.path().join("images");
+        std::fs::create_dir_all(&images_dir)?;
+        let png = images_dir.join(".upload-png");
+        image::DynamicImage::new_rgb8(2, 2).save_with_format(&png, image::ImageFormat::Png)?;
+        let garbage = images_dir.join(".upload-garbage");
+        std::fs::write(&garbage, b"not an image")?;
+
+        let kept = keep(
+            &images_dir,
+            "job-1",
+            &[&png, &garbage],
+            &serde_json::json!({"revision": "abc1234"}),
+            "decoding failed",
+        )?;
+
+        assert_eq!(kept, images_dir.join(FAILED_DIR).join("job-1"));
+        assert!(!png.exists() && !garbage.exists());
+        assert!(kept.join("frame-0.png").exists());
+        assert_eq!(std::fs::read(kept.join("frame-1.bin"))?, b"not an image");
+        let metadata: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(kept.join(METADATA_NAME))?)?;
+        assert_eq!(metadata["revision"], "abc1234");
+        assert_eq!(
+            std::fs::read_to_string(kept.join(ERROR_NAME))?,
+            "decoding failed"
+        );
+        Ok(())
+    }
+}
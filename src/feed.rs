@@ -0,0 +1,323 @@
+//! Atom feed of the newest lolcommits, served at `/feed.xml`.
+//!
+//! Built from the image index rather than the files, so a poll never reads the images
+//! themselves. The server caches the rendered feed until the index changes.
+
+use crate::config::ServerConfig;
+use crate::git::CommitMetadata;
+use crate::urls::{self, ImageUrls};
+use chrono::SecondsFormat;
+use std::fmt::Write;
+
+/// Route the feed is served under.
+pub const FEED_ROUTE: &str = "/feed.xml";
+
+pub const CONTENT_TYPE: &str = "application/atom+xml; charset=utf-8";
+
+/// `updated` of a feed with no entries.
+const EPOCH: &str = "1970-01-01T00:00:00Z";
+
+/// Render an Atom feed of `images`, which are expected newest first.
+pub fn render(config: &ServerConfig, images: &[CommitMetadata]) -> String {
+    let base = urls::base_url(config);
+    let self_url = format!("{base}{FEED_ROUTE}");
+    // Atom ids must be absolute, which links aren't without public_base_url
+    let feed_id = if is_absolute(&base) {
+        self_url.clone()
+    } else {
+        "urn:lolcommits:feed".to_string()
+    };
+    let updated = images
+        .first()
+        .and_then(|image| updated(&image.timestamp))
+        .unwrap_or_else(|| EPOCH.to_string());
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    let _ = writeln!(xml, "  <id>{}</id>", escape(&feed_id));
+    let _ = writeln!(xml, "  <title>{}</title>", escape(&config.gallery_title));
+    let _ = writeln!(xml, "  <updated>{updated}</updated>");
+    let _ = writeln!(xml, "  <link rel=\"self\" href=\"{}\"/>", escape(&self_url));
+    let _ = writeln!(
+        xml,
+        "  <link rel=\"alternate\" type=\"text/html\" href=\"{}/\"/>",
+        escape(&base)
+    );
+    let _ = writeln!(xml, "  <generator>lolcommitsd</generator>");
+    for image in images {
+        entry(&mut xml, config, image);
+    }
+    xml.push_str("</feed>\n");
+    xml
+}
+
+fn entry(xml: &mut String, config: &ServerConfig, image: &CommitMetadata) {
+    let filename = image
+        .path
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or("");
+    let url = ImageUrls::for_metadata(config, image).url;
+    let title = title(image);
+    let author = if image.author_name.is_empty() {
+        &image.repo_name
+    } else {
+        &image.author_name
+    };
+
+    xml.push_str("  <entry>\n");
+    let _ = writeln!(
+        xml,
+        "    <id>urn:lolcommits:image:{}</id>",
+        escape(filename)
+    );
+    let _ = writeln!(xml, "    <title>{}</title>", escape(&title));
+    if let Some(updated) = updated(&image.timestamp) {
+        let _ = writeln!(xml, "    <updated>{updated}</updated>");
+    }
+    xml.push_str("    <author>\n");
+    let _ = writeln!(xml, "      <name>{}</name>", escape(author));
+    if !image.author_email.is_empty() {
+        let _ = writeln!(xml, "      <email>{}</email>", escape(&image.author_email));
+    }
+    xml.push_str("    </author>\n");
+    let _ = writeln!(
+        xml,
+        "    <link rel=\"alternate\" href=\"{}\"/>",
+        escape(&url)
+    );
+    let _ = writeln!(
+        xml,
+        "    <link rel=\"enclosure\" type=\"{}\" href=\"{}\"/>",
+        mime_type(filename),
+        escape(&url)
+    );
+    let _ = writeln!(xml, "    <category term=\"{}\"/>", escape(&image.repo_name));
+    // The content is escaped HTML, so the image markup is escaped twice
+    let img = format!("<img src=\"{}\" alt=\"{}\"/>", escape(&url), escape(&title));
+    let _ = writeln!(xml, "    <content type=\"html\">{}</content>", escape(&img));
+    xml.push_str("  </entry>\n");
+}
+
+/// "{type}({scope}): {subject}", leaving out what the commit didn't have.
+fn title(image: &CommitMetadata) -> String {
+    let subject = image.message.lines().next().unwrap_or("").trim();
+    let breaking = if image.breaking { "!" } else { "" };
+    match (image.commit_type.as_str(), image.scope.as_str()) {
+        ("", _) => subject.to_string(),
+        (commit_type, "") => format!("{commit_type}{breaking}: {subject}"),
+        (commit_type, scope) => format!("{commit_type}({scope}){breaking}: {subject}"),
+    }
+}
+
+/// The metadata timestamp as RFC 3339.
+fn updated(timestamp: &str) -> Option<String> {
+    let taken = crate::parse_timestamp(timestamp)?;
+    Some(taken.to_rfc3339_opts(SecondsFormat::Secs, true))
+}
+
+fn mime_type(filename: &str) -> &'static str {
+    match filename.rsplit_once('.').map(|(_, ext)| ext) {
+        Some("jpg") => "image/jpeg",
+        Some("webp") => "image/webp",
+        Some(crate::animation::EXTENSION) => "image/gif",
+        _ => "image/png",
+    }
+}
+
+fn is_absolute(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+/// Escapes `text` for XML character data and attribute values. Characters XML 1.0 can't
+/// represent at all, such as control characters pasted into a commit message, become U+FFFD
+/// so a single bad commit can't make the whole feed unparseable.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            '\u{0}'..='\u{1f}' | '\u{fffe}' | '\u{ffff}' => {
+                escaped.push(char::REPLACEMENT_CHARACTER)
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, NaiveDateTime};
+    use test_case::test_case;
+
+    fn image(filename: &str, commit_type: &str, scope: &str, message: &str) -> CommitMetadata {
+        CommitMetadata {
+            path: filename.into(),
+            revision: "abc1234".to_string(),
+            message: message.to_string(),
+            commit_type: commit_type.to_string(),
+            scope: scope.to_string(),
+            timestamp: "2024-03-01 12:30:00".to_string(),
+            repo_name: "repo".to_string(),
+            branch_name: "main".to_string(),
+            author_name: "Ada".to_string(),
+            author_email: "ada@example.com".to_string(),
+            breaking: false,
+            co_authors: Vec::new(),
+            stats: crate::git::DiffStats {
+                files_changed: 1,
+                insertions: 2,
+                deletions: 0,
+            },
+        }
+    }
+
+    fn public_config() -> ServerConfig {
+        ServerConfig {
+            public_base_url: Some("https://lol.example.com/gallery/".to_string()),
+            gallery_title: "Team <Lols>".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test_case("feat", "server", "add feed", false, "feat(server): add feed" ; "type and scope")]
+    #[test_case("fix", "", "typo", false, "fix: typo" ; "no scope")]
+    #[test_case("", "", "Initial commit", false, "Initial commit" ; "not conventional")]
+    #[test_case("feat", "api", "drop v1", true, "feat(api)!: drop v1" ; "breaking")]
+    #[test_case("docs", "", "readme\n\nmore detail", false, "docs: readme" ; "first line only")]
+    fn test_title(commit_type: &str, scope: &str, message: &str, breaking: bool, expected: &str) {
+        let mut image = image("x.png", commit_type, scope, message);
+        image.breaking = breaking;
+        assert_eq!(title(&image), expected);
+    }
+
+    #[test_case("repo-20240301-123000-abc1234.png", "image/png")]
+    #[test_case("repo-20240301-123000-abc1234.jpg", "image/jpeg")]
+    #[test_case("repo-20240301-123000-abc1234.webp", "image/webp")]
+    #[test_case("repo-20240301-123000-abc1234.gif", "image/gif")]
+    fn test_mime_type(filename: &str, expected: &str) {
+        assert_eq!(mime_type(filename), expected);
+    }
+
+    #[test]
+    fn test_updated_is_rfc3339_of_the_local_time() {
+        let rfc3339 = updated("2024-03-01 12:30:00").unwrap();
+        let parsed = chrono::DateTime::parse_from_rfc3339(&rfc3339).unwrap();
+        assert_eq!(
+            parsed.with_timezone(&Local).naive_local(),
+            NaiveDateTime::parse_from_str("2024-03-01 12:30:00", crate::TIMESTAMP_FORMAT).unwrap()
+        );
+        assert_eq!(updated("not a timestamp"), None);
+    }
+
+    #[test]
+    fn test_updated_keeps_the_recorded_offset() {
+        assert_eq!(
+            updated("2024-03-01T12:30:00+05:00").as_deref(),
+            Some("2024-03-01T12:30:00+05:00")
+        );
+    }
+
+    #[test]
+    fn test_links_are_absolute_with_public_base_url() {
+        let feed = render(
+            &public_config(),
+            &[image(
+                "repo-20240301-123000-abc1234.png",
+                "feat",
+                "",
+                "add feed",
+            )],
+        );
+
+        assert!(feed.contains("<id>https://lol.example.com/gallery/feed.xml</id>"));
+        assert!(
+            feed.contains("<link rel=\"self\" href=\"https://lol.example.com/gallery/feed.xml\"/>")
+        );
+        assert!(feed.contains(
+            "<link rel=\"enclosure\" type=\"image/png\" href=\"https://lol.example.com/gallery/images/repo-20240301-123000-abc1234.png\"/>"
+        ));
+        assert!(feed.contains("<id>urn:lolcommits:image:repo-20240301-123000-abc1234.png</id>"));
+    }
+
+    #[test]
+    fn test_feed_id_is_a_urn_without_public_base_url() {
+        let config = ServerConfig {
+            mount_prefix: "/lolcommits".to_string(),
+            ..Default::default()
+        };
+        let feed = render(&config, &[]);
+
+        assert!(feed.contains("<id>urn:lolcommits:feed</id>"));
+        assert!(feed.contains("<link rel=\"self\" href=\"/lolcommits/feed.xml\"/>"));
+        assert!(feed.contains(&format!("<updated>{EPOCH}</updated>")));
+        assert!(!feed.contains("<entry>"));
+    }
+
+    #[test]
+    fn test_entries_are_escaped() {
+        let mut image = image(
+            "repo-20240301-123000-abc1234.png",
+            "fix",
+            "",
+            "handle <T> & \"quotes\"",
+        );
+        image.author_name = String::new();
+        image.author_email = String::new();
+        let feed = render(&public_config(), &[image]);
+
+        assert!(feed.contains("<title>Team &lt;Lols&gt;</title>"));
+        assert!(feed.contains("<title>fix: handle &lt;T&gt; &amp; &quot;quotes&quot;</title>"));
+        assert!(feed.contains("<name>repo</name>"), "falls back to the repo");
+        assert!(!feed.contains("<email>"));
+        assert!(feed.contains("alt=&quot;fix: handle &amp;lt;T&amp;gt;"));
+    }
+
+    #[test_case("bell\u{7}", "bell\u{fffd}" ; "bell")]
+    #[test_case("nul\u{0}", "nul\u{fffd}" ; "nul")]
+    #[test_case("tab\u{b}\u{c}", "tab\u{fffd}\u{fffd}" ; "vertical tab and form feed")]
+    #[test_case("esc\u{1b}[31m", "esc\u{fffd}[31m" ; "ansi escape")]
+    #[test_case("not\u{fffe}", "not\u{fffd}" ; "noncharacter")]
+    #[test_case("a\tb\r\nc", "a\tb\r\nc" ; "whitespace is kept")]
+    fn test_escape_replaces_characters_xml_cannot_hold(text: &str, expected: &str) {
+        assert_eq!(escape(text), expected);
+    }
+
+    #[test]
+    fn test_entries_with_control_characters_are_replaced() {
+        let image = image(
+            "repo-20240301-123000-abc1234.png",
+            "fix",
+            "",
+            "oops\u{1b}[0m\u{8}",
+        );
+        let feed = render(&public_config(), &[image]);
+
+        assert!(feed.contains("<title>fix: oops\u{fffd}[0m\u{fffd}</title>"));
+        assert!(
+            !feed
+                .chars()
+                .any(|c| c.is_control() && !matches!(c, '\t' | '\n' | '\r'))
+        );
+    }
+
+    #[test]
+    fn test_entries_follow_the_given_order() {
+        let images = [
+            image("repo-20240301-123000-bbb2222.png", "feat", "", "newer"),
+            image("repo-20240201-123000-aaa1111.png", "feat", "", "older"),
+        ];
+        let feed = render(&public_config(), &images);
+
+        assert_eq!(feed.matches("<entry>").count(), 2);
+        assert!(feed.find("newer").unwrap() < feed.find("older").unwrap());
+    }
+}
@@ -0,0 +1,722 @@
+//! Consistency check for `images_dir` (`lolcommitsd --fsck`).
+//!
+//! The gallery, the revision cache and the duplicate-upload check are all derived from
+//! the images on disk and their metadata, embedded in PNGs or in the JSON sidecar beside
+//! a JPEG or WebP, so that is what gets checked. The server's index snapshot in
+//! `state_dir` only caches that metadata and is reconciled with the files at startup, so
+//! every issue reported here is one the server would otherwise act on silently.
+
+use crate::error::Result;
+use crate::git::CommitMetadata;
+use crate::image_metadata;
+use owo_colors::OwoColorize;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Where `--repair` moves files that can't be read.
+pub const QUARANTINE_DIR: &str = ".quarantine";
+
+/// Prefixes of the temporary files written before being renamed into place: images being
+/// saved, and uploads being streamed to disk.
+const TEMP_FILE_PREFIXES: [&str; 2] = [".tmp", crate::server::UPLOAD_TEMP_PREFIX];
+
+/// What a single image in `images_dir` yielded.
+#[derive(Debug, Clone)]
+pub enum Scanned {
+    /// Metadata read from the embedded chunks or the sidecar.
+    Embedded(CommitMetadata),
+    /// No stored metadata, guessed from the filename.
+    FilenameOnly(CommitMetadata),
+    /// No stored metadata and a filename that doesn't parse either.
+    Unidentified,
+    /// The image couldn't be decoded.
+    Corrupt(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub scanned: Scanned,
+}
+
+impl Entry {
+    fn metadata(&self) -> Option<&CommitMetadata> {
+        match &self.scanned {
+            Scanned::Embedded(metadata) | Scanned::FilenameOnly(metadata) => Some(metadata),
+            Scanned::Unidentified | Scanned::Corrupt(_) => None,
+        }
+    }
+}
+
+/// Contents of `images_dir`: the images plus any other top-level files.
+#[derive(Debug, Default)]
+pub struct Scan {
+    pub images: Vec<Entry>,
+    pub other_files: Vec<PathBuf>,
+}
+
+/// Read every top-level file in `images_dir`, sorted by path.
+pub fn scan(images_dir: &Path) -> Result<Scan> {
+    let mut scan = Scan::default();
+    if !images_dir.exists() {
+        return Ok(scan);
+    }
+
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(images_dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        if !image_metadata::is_image_file(&path) {
+            scan.other_files.push(path);
+            continue;
+        }
+
+        let scanned = match image_metadata::read_metadata(&path) {
+            Ok(Some(mut metadata)) => {
+                metadata.path = path.clone();
+                Scanned::Embedded(metadata)
+            }
+            Ok(None) => match image_metadata::parse_filename(&path) {
+                Some(metadata) => Scanned::FilenameOnly(metadata),
+                None => Scanned::Unidentified,
+            },
+            Err(e) => Scanned::Corrupt(e.to_string()),
+        };
+        scan.images.push(Entry { path, scanned });
+    }
+
+    Ok(scan)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    /// PNG that fails to decode: the gallery skips it and its revision is re-uploadable.
+    Unparseable,
+    /// PNG without embedded metadata.
+    MissingMetadata,
+    /// Several files claim the same revision.
+    DuplicateRevision,
+    /// Leftover temporary file from an interrupted upload.
+    Orphan,
+    /// Metadata sidecar whose JPEG, WebP or GIF is gone.
+    OrphanSidecar,
+}
+
+impl IssueKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IssueKind::Unparseable => "unparseable",
+            IssueKind::MissingMetadata => "missing_metadata",
+            IssueKind::DuplicateRevision => "duplicate_revision",
+            IssueKind::Orphan => "orphan",
+            IssueKind::OrphanSidecar => "orphan_sidecar",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Issue {
+    pub kind: IssueKind,
+    pub path: PathBuf,
+    pub detail: String,
+    pub hint: &'static str,
+}
+
+pub fn check_unparseable(images: &[Entry]) -> Vec<Issue> {
+    images
+        .iter()
+        .filter_map(|entry| match &entry.scanned {
+            Scanned::Corrupt(error) => Some(Issue {
+                kind: IssueKind::Unparseable,
+                path: entry.path.clone(),
+                detail: error.clone(),
+                hint: "--repair moves it to .quarantine; restore it from another backup",
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+pub fn check_missing_metadata(images: &[Entry]) -> Vec<Issue> {
+    images
+        .iter()
+        .filter_map(|entry| match &entry.scanned {
+            Scanned::FilenameOnly(metadata) => Some(Issue {
+                kind: IssueKind::MissingMetadata,
+                path: entry.path.clone(),
+                detail: format!(
+                    "no embedded metadata, revision {} taken from the filename",
+                    metadata.revision
+                ),
+                hint: "--repair embeds the filename metadata; run lolcommits_fixup to recover the commit message",
+            }),
+            Scanned::Unidentified => Some(Issue {
+                kind: IssueKind::MissingMetadata,
+                path: entry.path.clone(),
+                detail: "no embedded metadata and the filename isn't {repo}-{timestamp}-{sha}.png"
+                    .to_string(),
+                hint: "rename it to {repo}-{timestamp}-{sha}.png or remove it",
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// One issue for every file after the first (by path) that shares a revision.
+pub fn check_duplicate_revisions(images: &[Entry]) -> Vec<Issue> {
+    let mut by_revision: BTreeMap<&str, Vec<&Path>> = BTreeMap::new();
+    for entry in images {
+        if let Some(metadata) = entry.metadata()
+            && !metadata.revision.is_empty()
+        {
+            by_revision
+                .entry(&metadata.revision)
+                .or_default()
+                .push(&entry.path);
+        }
+    }
+
+    by_revision
+        .into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .flat_map(|(revision, paths)| {
+            let first = paths[0];
+            paths.into_iter().skip(1).map(move |path| Issue {
+                kind: IssueKind::DuplicateRevision,
+                path: path.to_path_buf(),
+                detail: format!("revision {revision} is also in {}", first.display()),
+                hint: "keep one copy; force re-uploads leave the older image behind",
+            })
+        })
+        .collect()
+}
+
+pub fn check_orphans(other_files: &[PathBuf]) -> Vec<Issue> {
+    other_files
+        .iter()
+        .filter(|path| {
+            path.file_name()
+                .and_then(|s| s.to_str())
+                .is_some_and(|name| {
+                    TEMP_FILE_PREFIXES
+                        .iter()
+                        .any(|prefix| name.starts_with(prefix))
+                })
+        })
+        .map(|path| Issue {
+            kind: IssueKind::Orphan,
+            path: path.clone(),
+            detail: "temporary file left by an interrupted upload".to_string(),
+            hint: "--repair removes it",
+        })
+        .collect()
+}
+
+/// Sidecars that no image in `images` would read its metadata from.
+pub fn check_orphan_sidecars(images: &[Entry], other_files: &[PathBuf]) -> Vec<Issue> {
+    let sidecars: HashSet<PathBuf> = images
+        .iter()
+        .map(|entry| image_metadata::sidecar_path(&entry.path))
+        .collect();
+    other_files
+        .iter()
+        .filter(|path| path.extension().and_then(|s| s.to_str()) == Some("json"))
+        .filter(|path| !sidecars.contains(*path))
+        .map(|path| Issue {
+            kind: IssueKind::OrphanSidecar,
+            path: path.clone(),
+            detail: "metadata sidecar without an image beside it".to_string(),
+            hint: "--repair removes it; restore the image first if it should still be there",
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RepairAction {
+    Quarantined,
+    EmbeddedMetadata,
+    RemovedOrphan,
+}
+
+impl RepairAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RepairAction::Quarantined => "quarantined",
+            RepairAction::EmbeddedMetadata => "embedded metadata",
+            RepairAction::RemovedOrphan => "removed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Repair {
+    pub action: RepairAction,
+    pub path: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub images_dir: PathBuf,
+    pub images: usize,
+    /// Distinct revisions the server's revision cache is seeded with.
+    pub revisions: usize,
+    pub issues: Vec<Issue>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub repairs: Vec<Repair>,
+}
+
+/// Run every check over `images_dir`.
+pub fn check(images_dir: &Path) -> Result<Report> {
+    let scan = scan(images_dir)?;
+
+    let revisions = scan
+        .images
+        .iter()
+        .filter_map(Entry::metadata)
+        .map(|metadata| metadata.revision.as_str())
+        .collect::<HashSet<_>>()
+        .len();
+
+    let mut issues = check_unparseable(&scan.images);
+    issues.extend(check_missing_metadata(&scan.images));
+    issues.extend(check_duplicate_revisions(&scan.images));
+    issues.extend(check_orphans(&scan.other_files));
+    issues.extend(check_orphan_sidecars(&scan.images, &scan.other_files));
+
+    Ok(Report {
+        images_dir: images_dir.to_path_buf(),
+        images: scan.images.len(),
+        revisions,
+        issues,
+        repairs: Vec::new(),
+    })
+}
+
+/// Move an unreadable file into [`QUARANTINE_DIR`] so it stops being scanned.
+pub fn quarantine(images_dir: &Path, path: &Path) -> Result {
+    let quarantine_dir = images_dir.join(QUARANTINE_DIR);
+    std::fs::create_dir_all(&quarantine_dir)?;
+    let name = path
+        .file_name()
+        .ok_or_else(|| std::io::Error::other("path has no file name"))?;
+    std::fs::rename(path, quarantine_dir.join(name))?;
+    Ok(())
+}
+
+/// Rewrite a PNG with the metadata parsed from its filename embedded in it, or write
+/// the sidecar for a JPEG or WebP.
+pub fn embed_filename_metadata(path: &Path) -> Result {
+    let metadata = image_metadata::parse_filename(path)
+        .ok_or_else(|| std::io::Error::other("filename doesn't carry metadata"))?;
+    let image = image::open(path)?;
+    if path.extension().and_then(|s| s.to_str()) != Some("png") {
+        return image_metadata::write_sidecar(path, &metadata, &Default::default());
+    }
+
+    let dir = path
+        .parent()
+        .ok_or_else(|| std::io::Error::other("Invalid image path"))?;
+    let filename = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| std::io::Error::other("Invalid image path"))?;
+    crate::storage::atomic_save(dir, filename, |temp_path| {
+        image_metadata::save_png_with_metadata(&image, temp_path, &metadata)
+    })?;
+    Ok(())
+}
+
+/// Apply the safe fixes for `report.issues`. Duplicates and unidentified files need a
+/// human and are left alone. Run with the daemon stopped: an in-flight upload looks
+/// like an orphan.
+pub fn repair(report: &mut Report) {
+    let mut repairs = Vec::new();
+
+    for issue in &report.issues {
+        let (action, result) = match issue.kind {
+            IssueKind::Unparseable => (
+                RepairAction::Quarantined,
+                quarantine(&report.images_dir, &issue.path),
+            ),
+            IssueKind::MissingMetadata if image_metadata::parse_filename(&issue.path).is_some() => {
+                (
+                    RepairAction::EmbeddedMetadata,
+                    embed_filename_metadata(&issue.path),
+                )
+            }
+            IssueKind::Orphan | IssueKind::OrphanSidecar => (
+                RepairAction::RemovedOrphan,
+                std::fs::remove_file(&issue.path).map_err(Into::into),
+            ),
+            IssueKind::MissingMetadata | IssueKind::DuplicateRevision => continue,
+        };
+
+        if let Err(e) = &result {
+            tracing::warn!(path = %issue.path.display(), ?action, error = %e, "Repair failed");
+        }
+        repairs.push(Repair {
+            action,
+            path: issue.path.clone(),
+            error: result.err().map(|e| e.to_string()),
+        });
+    }
+
+    report.repairs = repairs;
+}
+
+impl Report {
+    /// Issues not fixed by a successful repair.
+    pub fn unresolved(&self) -> usize {
+        let repaired: HashSet<&Path> = self
+            .repairs
+            .iter()
+            .filter(|repair| repair.error.is_none())
+            .map(|repair| repair.path.as_path())
+            .collect();
+        self.issues
+            .iter()
+            .filter(|issue| !repaired.contains(issue.path.as_path()))
+            .count()
+    }
+
+    /// Print the report as human readable text.
+    pub fn print(&self) {
+        println!(
+            "Checked {} images ({} revisions) in {}",
+            self.images,
+            self.revisions,
+            self.images_dir.display().to_string().magenta()
+        );
+
+        for issue in &self.issues {
+            println!(
+                "{} {} {}: {}",
+                "✗".red(),
+                issue.kind.as_str().yellow(),
+                issue.path.display(),
+                issue.detail
+            );
+            println!("  {}", issue.hint.dimmed());
+        }
+
+        for repair in &self.repairs {
+            match &repair.error {
+                None => println!(
+                    "{} {} {}",
+                    "✓".green(),
+                    repair.action.as_str(),
+                    repair.path.display()
+                ),
+                Some(e) => println!(
+                    "{} {} {}: {}",
+                    "✗".red(),
+                    repair.action.as_str(),
+                    repair.path.display(),
+                    e.red()
+                ),
+            }
+        }
+
+        match self.unresolved() {
+            0 => println!("{} images_dir is consistent", "✓".green()),
+            n => println!("{} {} issue(s) need attention", "✗".red(), n),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::DiffStats;
+    use image::{DynamicImage, RgbaImage};
+
+    fn metadata(revision: &str) -> CommitMetadata {
+        CommitMetadata {
+            path: PathBuf::new(),
+            revision: revision.to_string(),
+            message: "feat: fsck".to_string(),
+            commit_type: "feat".to_string(),
+            scope: String::new(),
+            timestamp: "2024-01-15 12:34:56".to_string(),
+            repo_name: "repo".to_string(),
+            branch_name: "main".to_string(),
+            author_name: String::new(),
+            author_email: String::new(),
+            breaking: false,
+            co_authors: Vec::new(),
+            stats: DiffStats {
+                files_changed: 1,
+                insertions: 2,
+                deletions: 3,
+            },
+        }
+    }
+
+    fn image() -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::new(2, 2))
+    }
+
+    fn write_with_metadata(dir: &Path, name: &str, revision: &str) -> PathBuf {
+        let path = dir.join(name);
+        image_metadata::save_png_with_metadata(&image(), &path, &metadata(revision)).unwrap();
+        path
+    }
+
+    fn write_plain(dir: &Path, name: &str) -> PathBuf {
+        let path = dir.join(name);
+        image().save(&path).unwrap();
+        path
+    }
+
+    fn write_bytes(dir: &Path, name: &str, bytes: &[u8]) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    fn kinds(issues: &[Issue]) -> Vec<IssueKind> {
+        issues.iter().map(|issue| issue.kind).collect()
+    }
+
+    #[test]
+    fn test_scan_classifies_files() -> Result {
+        let dir = tempfile::tempdir()?;
+        write_with_metadata(dir.path(), "repo-20240115-123456-aaa.png", "aaa");
+        write_plain(dir.path(), "repo-20240115-123456-bbb.png");
+        write_plain(dir.path(), "holiday.png");
+        write_bytes(dir.path(), "repo-20240115-123456-ccc.png", b"not a png");
+        write_bytes(dir.path(), "notes.txt", b"hello");
+        std::fs::create_dir(dir.path().join(QUARANTINE_DIR))?;
+
+        let scan = scan(dir.path())?;
+
+        let scanned: Vec<&str> = scan
+            .images
+            .iter()
+            .map(|entry| match entry.scanned {
+                Scanned::Embedded(_) => "embedded",
+                Scanned::FilenameOnly(_) => "filename",
+                Scanned::Unidentified => "unidentified",
+                Scanned::Corrupt(_) => "corrupt",
+            })
+            .collect();
+        assert_eq!(
+            scanned,
+            vec!["unidentified", "embedded", "filename", "corrupt"]
+        );
+        assert_eq!(scan.other_files, vec![dir.path().join("notes.txt")]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_missing_dir_is_empty() -> Result {
+        let dir = tempfile::tempdir()?;
+        let scan = scan(&dir.path().join("missing"))?;
+        assert!(scan.images.is_empty() && scan.other_files.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_unparseable() -> Result {
+        let dir = tempfile::tempdir()?;
+        write_with_metadata(dir.path(), "repo-20240115-123456-aaa.png", "aaa");
+        let corrupt = write_bytes(dir.path(), "repo-20240115-123456-bbb.png", b"\x89PNG junk");
+
+        let issues = check_unparseable(&scan(dir.path())?.images);
+
+        assert_eq!(kinds(&issues), vec![IssueKind::Unparseable]);
+        assert_eq!(issues[0].path, corrupt);
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_missing_metadata() -> Result {
+        let dir = tempfile::tempdir()?;
+        write_with_metadata(dir.path(), "repo-20240115-123456-aaa.png", "aaa");
+        let legacy = write_plain(dir.path(), "repo-20240115-123456-bbb.png");
+        let stray = write_plain(dir.path(), "holiday.png");
+
+        let issues = check_missing_metadata(&scan(dir.path())?.images);
+
+        let paths: Vec<&Path> = issues.iter().map(|issue| issue.path.as_path()).collect();
+        assert_eq!(paths, vec![stray.as_path(), legacy.as_path()]);
+        assert!(issues[1].detail.contains("bbb"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_duplicate_revisions() -> Result {
+        let dir = tempfile::tempdir()?;
+        let first = write_with_metadata(dir.path(), "repo-20240115-123456-aaa.png", "aaa");
+        let second = write_with_metadata(dir.path(), "repo-20240116-090000-aaa.png", "aaa");
+        // Legacy file whose filename revision collides with an embedded one
+        let third = write_plain(dir.path(), "repo-20240117-090000-aaa.png");
+        write_with_metadata(dir.path(), "repo-20240115-123456-bbb.png", "bbb");
+
+        let issues = check_duplicate_revisions(&scan(dir.path())?.images);
+
+        let paths: Vec<&Path> = issues.iter().map(|issue| issue.path.as_path()).collect();
+        assert_eq!(paths, vec![second.as_path(), third.as_path()]);
+        assert!(issues[0].detail.contains(&first.display().to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_orphans() -> Result {
+        let dir = tempfile::tempdir()?;
+        let orphan = write_bytes(dir.path(), ".tmpAbC123", b"partial");
+        let upload = write_bytes(dir.path(), ".uploadXyZ789", b"partial");
+        write_bytes(dir.path(), "README.txt", b"mine");
+
+        let issues = check_orphans(&scan(dir.path())?.other_files);
+
+        assert_eq!(kinds(&issues), vec![IssueKind::Orphan, IssueKind::Orphan]);
+        assert_eq!(issues[0].path, orphan);
+        assert_eq!(issues[1].path, upload);
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_orphan_sidecars() -> Result {
+        let dir = tempfile::tempdir()?;
+        let jpeg = dir.path().join("repo-20240115-123456-aaa.jpg");
+        DynamicImage::new_rgb8(2, 2).save(&jpeg)?;
+        image_metadata::write_sidecar(&jpeg, &metadata("aaa"), &Default::default())?;
+        let orphan = write_bytes(dir.path(), "repo-20240115-123456-bbb.json", b"{}");
+        write_bytes(dir.path(), "README.txt", b"mine");
+
+        let scan = scan(dir.path())?;
+        let issues = check_orphan_sidecars(&scan.images, &scan.other_files);
+
+        assert_eq!(kinds(&issues), vec![IssueKind::OrphanSidecar]);
+        assert_eq!(issues[0].path, orphan);
+        Ok(())
+    }
+
+    #[test]
+    fn test_repair_removes_orphan_sidecars() -> Result {
+        let dir = tempfile::tempdir()?;
+        let orphan = write_bytes(dir.path(), "repo-20240115-123456-bbb.json", b"{}");
+
+        let mut report = check(dir.path())?;
+        repair(&mut report);
+
+        assert_eq!(report.repairs[0].action, RepairAction::RemovedOrphan);
+        assert_eq!(report.unresolved(), 0);
+        assert!(!orphan.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_consistent_dir() -> Result {
+        let dir = tempfile::tempdir()?;
+        write_with_metadata(dir.path(), "repo-20240115-123456-aaa.png", "aaa");
+        write_with_metadata(dir.path(), "repo-20240115-123456-bbb.png", "bbb");
+
+        let report = check(dir.path())?;
+
+        assert_eq!((report.images, report.revisions), (2, 2));
+        assert!(report.issues.is_empty());
+        assert_eq!(report.unresolved(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_repair_applies_safe_fixes() -> Result {
+        let dir = tempfile::tempdir()?;
+        write_with_metadata(dir.path(), "repo-20240115-123456-aaa.png", "aaa");
+        write_with_metadata(dir.path(), "repo-20240116-123456-aaa.png", "aaa");
+        let legacy = write_plain(dir.path(), "repo-20240115-123456-bbb.png");
+        let corrupt = write_bytes(dir.path(), "repo-20240115-123456-ccc.png", b"junk");
+        let orphan = write_bytes(dir.path(), ".tmpXyZ", b"partial");
+        let upload = write_bytes(dir.path(), ".uploadAbC", b"partial");
+
+        let mut report = check(dir.path())?;
+        repair(&mut report);
+
+        let actions: Vec<RepairAction> = report.repairs.iter().map(|r| r.action).collect();
+        assert_eq!(
+            actions,
+            vec![
+                RepairAction::Quarantined,
+                RepairAction::EmbeddedMetadata,
+                RepairAction::RemovedOrphan,
+                RepairAction::RemovedOrphan
+            ]
+        );
+        assert!(report.repairs.iter().all(|r| r.error.is_none()));
+        // The duplicate needs a human
+        assert_eq!(report.unresolved(), 1);
+
+        assert!(!corrupt.exists());
+        assert!(
+            dir.path()
+                .join(QUARANTINE_DIR)
+                .join("repo-20240115-123456-ccc.png")
+                .exists()
+        );
+        assert!(!orphan.exists());
+        assert!(!upload.exists());
+        let embedded = image_metadata::read_png_metadata(&legacy)?.expect("metadata embedded");
+        assert_eq!(embedded.revision, "bbb");
+
+        let after = check(dir.path())?;
+        assert_eq!(kinds(&after.issues), vec![IssueKind::DuplicateRevision]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_repair_writes_sidecar_for_jpeg() -> Result {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("repo-20240115-123456-aaa.jpg");
+        DynamicImage::new_rgb8(2, 2).save(&path)?;
+
+        let mut report = check(dir.path())?;
+        assert_eq!(kinds(&report.issues), vec![IssueKind::MissingMetadata]);
+        repair(&mut report);
+
+        assert!(report.repairs.iter().all(|r| r.error.is_none()));
+        assert!(image_metadata::sidecar_path(&path).exists());
+        let rescanned = scan(dir.path())?;
+        assert!(matches!(rescanned.images[0].scanned, Scanned::Embedded(_)));
+        // The sidecar is not itself an image or an orphan
+        assert!(check(dir.path())?.issues.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_repair_leaves_unidentified_files() -> Result {
+        let dir = tempfile::tempdir()?;
+        let stray = write_plain(dir.path(), "holiday.png");
+
+        let mut report = check(dir.path())?;
+        repair(&mut report);
+
+        assert!(report.repairs.is_empty());
+        assert_eq!(report.unresolved(), 1);
+        assert!(stray.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_report_json() -> Result {
+        let dir = tempfile::tempdir()?;
+        write_bytes(dir.path(), ".tmpAbC", b"partial");
+
+        let json = serde_json::to_value(check(dir.path())?).unwrap();
+
+        assert_eq!(json["images"], 0);
+        assert_eq!(json["issues"][0]["kind"], "orphan");
+        assert!(json.get("repairs").is_none());
+        Ok(())
+    }
+}
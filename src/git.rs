@@ -1,7 +1,6 @@
 use crate::error::{Error::*, Result};
-use git2::Repository;
+use git2::{ErrorCode, Repository, RevparseMode};
 use serde::{Deserialize, Serialize};
-use std::process::Command;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct DiffStats {
@@ -27,6 +26,17 @@ pub struct CommitMetadata {
     pub timestamp: String,
     pub repo_name: String,
     pub branch_name: String,
+    /// Empty for images saved before authors were recorded.
+    #[serde(default)]
+    pub author_name: String,
+    #[serde(default)]
+    pub author_email: String,
+    /// A conventional commit breaking change (`feat!:` or a `BREAKING CHANGE:` footer).
+    #[serde(default)]
+    pub breaking: bool,
+    /// `Name <email>` of each `Co-authored-by:` trailer.
+    #[serde(default)]
+    pub co_authors: Vec<String>,
     pub stats: DiffStats,
 }
 
@@ -118,71 +128,183 @@ pub fn repo_name_from_url(url: &str) -> Option<String> {
     }
 }
 
-/// Get diff stats for a commit using git show --numstat
-pub fn get_diff_stats(sha: &str) -> Result<DiffStats> {
-    get_diff_stats_in_dir(sha, None)
+/// Files changed and lines added and removed by `sha` in the repository found from the
+/// environment, like `git show --numstat`.
+/// Files matching one of the `exclude` globs (relative to the repository root) aren't counted.
+pub fn get_diff_stats(sha: &str, exclude: &[String]) -> Result<DiffStats> {
+    diff_stats(&open_repo()?, sha, exclude)
 }
 
-fn get_diff_stats_in_dir(sha: &str, repo_path: Option<&std::path::Path>) -> Result<DiffStats> {
-    let mut cmd = Command::new("git");
-
-    if let Some(path) = repo_path {
-        // Clear GIT_DIR/GIT_WORK_TREE so -C takes effect
-        cmd.env_remove("GIT_DIR")
-            .env_remove("GIT_WORK_TREE")
-            .arg("-C")
-            .arg(path);
+/// Compares against the first parent only for merges, and against an empty tree for a
+/// root commit. Binary files count as changed files without lines, and renames are
+/// detected so a moved file counts once.
+fn diff_stats(repo: &Repository, sha: &str, exclude: &[String]) -> Result<DiffStats> {
+    let commit = repo.revparse_single(sha)?.peel_to_commit()?;
+    let parent_tree = match commit.parents().next() {
+        Some(parent) => Some(parent.tree()?),
+        None => None,
+    };
+
+    let mut diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?;
+    diff.find_similar(None)?;
+
+    if exclude.is_empty() {
+        let stats = diff.stats()?;
+        return Ok(DiffStats {
+            files_changed: stats.files_changed() as u32,
+            insertions: stats.insertions() as u32,
+            deletions: stats.deletions() as u32,
+        });
     }
 
-    let output = cmd.args(["show", "--numstat", "--format=", sha]).output()?;
+    let mut stats = DiffStats {
+        files_changed: 0,
+        insertions: 0,
+        deletions: 0,
+    };
+    for (index, delta) in diff.deltas().enumerate() {
+        let path = delta.new_file().path().or_else(|| delta.old_file().path());
+        let path = path.and_then(|p| p.to_str()).unwrap_or_default();
+        if exclude
+            .iter()
+            .any(|pattern| glob_match::glob_match(pattern, path))
+        {
+            tracing::debug!(path, "Excluded from diff stats");
+            continue;
+        }
 
-    if !output.status.success() {
-        return Err(GitCommandFailed);
+        stats.files_changed += 1;
+        if let Some(patch) = git2::Patch::from_diff(&diff, index)? {
+            let (_, insertions, deletions) = patch.line_stats()?;
+            stats.insertions += insertions as u32;
+            stats.deletions += deletions as u32;
+        }
     }
+    Ok(stats)
+}
+
+/// The branch being committed to. HEAD is detached mid-rebase, mid-bisect and in CI
+/// checkouts, so then this is the branch being rebased or bisected, else the nearest
+/// branch or tag containing the commit, else its short SHA.
+pub fn get_branch_name(repo: &Repository) -> Result<String> {
+    let head = repo.head()?;
+    let (branch_name, strategy) = if head.is_branch() {
+        (head.shorthand().unwrap_or("HEAD").to_string(), "head")
+    } else if let Some(branch_name) = in_progress_branch(repo) {
+        (branch_name, "in_progress")
+    } else {
+        let commit = head.peel_to_commit()?;
+        match nearest_ref_containing(repo, commit.id()) {
+            Some(name) => (name, "containing_ref"),
+            None => (commit.id().to_string()[..7].to_string(), "short_sha"),
+        }
+    };
+
+    tracing::debug!(branch = %branch_name, strategy, "Resolved branch name");
+    Ok(branch_name)
+}
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let mut files_changed = 0;
-    let mut total_insertions = 0;
-    let mut total_deletions = 0;
+/// The branch a rebase or bisect started from, read from the state files git leaves in
+/// the repository while it runs.
+fn in_progress_branch(repo: &Repository) -> Option<String> {
+    [
+        "rebase-merge/head-name",
+        "rebase-apply/head-name",
+        "BISECT_START",
+    ]
+    .iter()
+    .filter_map(|file| std::fs::read_to_string(repo.path().join(file)).ok())
+    .map(|contents| contents.trim().to_string())
+    // A rebase of a detached HEAD records "detached HEAD"
+    .find_map(|name| {
+        let name = name.strip_prefix("refs/heads/").unwrap_or(&name);
+        (!name.is_empty() && !name.contains(' ') && !is_sha(name)).then(|| name.to_string())
+    })
+}
 
-    for line in stdout.lines() {
-        let line = line.trim();
-        if line.is_empty() {
+fn is_sha(name: &str) -> bool {
+    name.len() == 40 && name.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// The branch (local, then remote) or tag fewest commits ahead of `commit` among those
+/// containing it.
+fn nearest_ref_containing(repo: &Repository, commit: git2::Oid) -> Option<String> {
+    let mut nearest: Option<(usize, usize, String)> = None;
+    for reference in repo.references().ok()?.flatten() {
+        let Some(name) = reference.name() else {
+            continue;
+        };
+        let kind = if name.starts_with("refs/heads/") {
+            0
+        } else if name.starts_with("refs/remotes/") && !name.ends_with("/HEAD") {
+            1
+        } else if name.starts_with("refs/tags/") {
+            2
+        } else {
+            continue;
+        };
+        let Ok(tip) = reference.peel_to_commit().map(|c| c.id()) else {
+            continue;
+        };
+        let contains = tip == commit || repo.graph_descendant_of(tip, commit).unwrap_or(false);
+        if !contains {
             continue;
         }
-
-        let parts: Vec<&str> = line.split('\t').collect();
-        if parts.len() >= 2 {
-            // Handle binary files (marked as "-")
-            if parts[0] != "-"
-                && let Ok(insertions) = parts[0].parse::<u32>()
-            {
-                total_insertions += insertions;
-            }
-            if parts[1] != "-"
-                && let Ok(deletions) = parts[1].parse::<u32>()
-            {
-                total_deletions += deletions;
-            }
-            files_changed += 1;
+        let Ok((ahead, _)) = repo.graph_ahead_behind(tip, commit) else {
+            continue;
+        };
+        let candidate = (ahead, kind, reference.shorthand()?.to_string());
+        if nearest.as_ref().is_none_or(|best| candidate < *best) {
+            nearest = Some(candidate);
         }
     }
+    nearest.map(|(_, _, name)| name)
+}
 
-    Ok(DiffStats {
-        files_changed,
-        insertions: total_insertions,
-        deletions: total_deletions,
-    })
+/// Name and email of the commit's author, empty when git doesn't have them as UTF-8.
+pub fn get_commit_author(repo: &Repository, sha: &str) -> Result<(String, String)> {
+    let commit = repo.revparse_single(sha)?.peel_to_commit()?;
+    let author = commit.author();
+    Ok((
+        author.name().unwrap_or_default().to_string(),
+        author.email().unwrap_or_default().to_string(),
+    ))
 }
 
-pub fn get_branch_name(repo: &Repository) -> Result<String> {
-    let head = repo.head()?;
+/// When `sha` was committed, in the committer's timezone. The committer time rather
+/// than the author time, so an amended or rebased commit is dated when it was remade.
+pub fn get_commit_time(
+    repo: &Repository,
+    sha: &str,
+) -> Result<chrono::DateTime<chrono::FixedOffset>> {
+    let time = repo.revparse_single(sha)?.peel_to_commit()?.time();
+    chrono::FixedOffset::east_opt(time.offset_minutes() * 60)
+        .and_then(|offset| {
+            chrono::DateTime::from_timestamp(time.seconds(), 0).map(|t| t.with_timezone(&offset))
+        })
+        .ok_or(GitCommandFailed)
+}
 
-    if let Some(branch_name) = head.shorthand() {
-        Ok(branch_name.to_string())
-    } else {
-        Ok("HEAD".to_string())
-    }
+/// The `Co-authored-by:` trailers in the message's final paragraph, as `Name <email>`.
+/// Trailers without both a name and an email are skipped.
+pub fn parse_co_authors(message: &str) -> Vec<String> {
+    let trailers = message
+        .trim_end()
+        .rsplit_once("\n\n")
+        .map_or("", |(_, last)| last);
+
+    trailers
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            if !key.trim().eq_ignore_ascii_case("co-authored-by") {
+                return None;
+            }
+            let value = value.trim();
+            let (name, email) = value.strip_suffix('>')?.split_once('<')?;
+            (!name.trim().is_empty() && email.contains('@')).then(|| value.to_string())
+        })
+        .collect()
 }
 
 /// Get the commit message for a given SHA (supports both short and long SHAs)
@@ -197,12 +319,12 @@ pub fn get_commit_message(repo: &Repository, sha: &str) -> Result<String> {
 }
 
 /// Parse the commit type from a conventional commit message
-/// Example: "feat(scope): message" -> "feat"
+/// Example: "feat(scope): message" -> "feat", "feat!: message" -> "feat"
 pub fn parse_commit_type(message: &str) -> String {
     let first_line = message.lines().next().unwrap_or(message);
 
     if let Some(colon_pos) = first_line.find(':') {
-        let prefix = &first_line[..colon_pos];
+        let prefix = first_line[..colon_pos].trim_end().trim_end_matches('!');
 
         if let Some(paren_pos) = prefix.find('(') {
             prefix[..paren_pos].trim().to_string()
@@ -214,6 +336,21 @@ pub fn parse_commit_type(message: &str) -> String {
     }
 }
 
+/// Whether a conventional commit message marks a breaking change, with a `!` before the
+/// colon ("feat!: ...", "feat(scope)!: ...") or a `BREAKING CHANGE:` footer in the body.
+pub fn is_breaking_change(message: &str) -> bool {
+    let mut lines = message.lines();
+    let first_line = lines.next().unwrap_or_default();
+    let marked = first_line
+        .find(':')
+        .is_some_and(|colon_pos| first_line[..colon_pos].trim_end().ends_with('!'));
+
+    marked
+        || lines.any(|line| {
+            line.starts_with("BREAKING CHANGE:") || line.starts_with("BREAKING-CHANGE:")
+        })
+}
+
 /// Strip the conventional commit prefix from a message
 /// Example: "feat(scope): message" -> "message"
 pub fn strip_commit_prefix(message: &str) -> String {
@@ -244,9 +381,45 @@ pub fn open_repo() -> Result<Repository> {
     Repository::open_from_env().map_err(|_| NotInGitRepo)
 }
 
+/// Directory git runs hooks from, honouring `core.hooksPath`.
+pub fn hooks_dir(repo: &Repository) -> std::path::PathBuf {
+    let configured = repo
+        .config()
+        .ok()
+        .and_then(|config| config.get_path("core.hooksPath").ok());
+
+    match configured {
+        Some(path) if path.is_absolute() => path,
+        // Relative hook paths are resolved from the top of the working tree
+        Some(path) => repo.workdir().unwrap_or_else(|| repo.path()).join(path),
+        None => repo.path().join("hooks"),
+    }
+}
+
+/// Resolve any revspec naming a single commit (`feature/foo`, `origin/main~2`, a tag, a
+/// short SHA) to its full 40 character SHA, the only form passed on downstream.
 pub fn resolve_revision(repo: &Repository, revision: &str) -> Result<String> {
-    let obj = repo.revparse_single(revision)?;
-    Ok(obj.id().to_string())
+    let spec = repo.revparse(revision).map_err(|e| match e.code() {
+        ErrorCode::NotFound | ErrorCode::InvalidSpec | ErrorCode::Ambiguous => RevisionNotFound {
+            input: revision.to_string(),
+        },
+        _ => Git(e),
+    })?;
+
+    let single_commit = || RevisionNotSingleCommit {
+        input: revision.to_string(),
+    };
+    if !spec.mode().contains(RevparseMode::SINGLE) {
+        return Err(single_commit());
+    }
+
+    // Annotated tags peel to their commit, trees and blobs aren't commits at all
+    let commit = spec
+        .from()
+        .ok_or_else(single_commit)?
+        .peel_to_commit()
+        .map_err(|_| single_commit())?;
+    Ok(commit.id().to_string())
 }
 
 #[cfg(test)]
@@ -323,6 +496,235 @@ mod tests {
         assert_eq!(repo_name_from_url(url), expected.map(|s| s.to_owned()));
     }
 
+    #[test]
+    fn test_hooks_dir() -> Result<()> {
+        let temp_dir = create_test_repo()?;
+        let repo = Repository::open(temp_dir.path())?;
+        assert_eq!(hooks_dir(&repo), repo.path().join("hooks"));
+
+        repo.config()?.set_str("core.hooksPath", ".githooks")?;
+        assert_eq!(hooks_dir(&repo), repo.workdir().unwrap().join(".githooks"));
+        Ok(())
+    }
+
+    fn commit_id(repo: &Repository, spec: &str) -> Result<String> {
+        Ok(repo
+            .revparse_single(spec)?
+            .peel_to_commit()?
+            .id()
+            .to_string())
+    }
+
+    #[test_case("HEAD" ; "head")]
+    #[test_case("HEAD~1" ; "ancestor")]
+    #[test_case("feature/foo-bar" ; "branch with slash")]
+    #[test_case("origin/main~1" ; "remote branch ancestor")]
+    #[test_case("v1.0" ; "annotated tag")]
+    fn test_resolve_revision(spec: &str) -> Result<()> {
+        let temp_dir = create_test_repo()?;
+        let repo = Repository::open(temp_dir.path())?;
+        let head = repo.head()?.peel_to_commit()?;
+        repo.branch("feature/foo-bar", &head, false)?;
+        repo.reference("refs/remotes/origin/main", head.id(), false, "test")?;
+        repo.tag(
+            "v1.0",
+            head.as_object(),
+            &repo.signature()?,
+            "release",
+            false,
+        )?;
+
+        let resolved = resolve_revision(&repo, spec)?;
+
+        assert_eq!(resolved.len(), 40);
+        assert!(resolved.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_eq!(resolved, commit_id(&repo, spec)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_revision_short_sha_expands() -> Result<()> {
+        let temp_dir = create_test_repo()?;
+        let repo = Repository::open(temp_dir.path())?;
+        let full = commit_id(&repo, "HEAD")?;
+
+        assert_eq!(resolve_revision(&repo, &full[..7])?, full);
+        Ok(())
+    }
+
+    #[test_case("feature/does-not-exist" ; "missing branch")]
+    #[test_case("HEAD~5" ; "beyond root")]
+    fn test_resolve_revision_not_found(spec: &str) -> Result<()> {
+        let temp_dir = create_test_repo()?;
+        let repo = Repository::open(temp_dir.path())?;
+
+        let error = resolve_revision(&repo, spec).unwrap_err();
+        assert!(matches!(error, RevisionNotFound { input } if input == spec));
+        Ok(())
+    }
+
+    #[test_case("HEAD~1..HEAD" ; "range")]
+    #[test_case("HEAD:test.txt" ; "blob")]
+    fn test_resolve_revision_requires_single_commit(spec: &str) -> Result<()> {
+        let temp_dir = create_test_repo()?;
+        let repo = Repository::open(temp_dir.path())?;
+
+        let error = resolve_revision(&repo, spec).unwrap_err();
+        assert!(matches!(error, RevisionNotSingleCommit { input } if input == spec));
+        Ok(())
+    }
+
+    /// Commit `files` (path, contents) on top of `parents`, returning the commit's id.
+    fn commit_files(
+        repo: &Repository,
+        files: &[(&str, &[u8])],
+        parents: &[&git2::Commit],
+    ) -> Result<git2::Oid> {
+        let workdir = repo.workdir().unwrap();
+        let mut index = repo.index()?;
+        for (path, contents) in files {
+            let full_path = workdir.join(path);
+            fs::create_dir_all(full_path.parent().unwrap())?;
+            fs::write(full_path, contents)?;
+            index.add_path(std::path::Path::new(path))?;
+        }
+        index.write()?;
+        let tree = repo.find_tree(index.write_tree()?)?;
+        let sig = repo.signature()?;
+        Ok(repo.commit(None, &sig, &sig, "commit", &tree, parents)?)
+    }
+
+    #[test]
+    fn test_get_branch_name_on_branch() -> Result<()> {
+        let temp_dir = create_test_repo()?;
+        let repo = Repository::open(temp_dir.path())?;
+        repo.branch("feature", &repo.head()?.peel_to_commit()?, false)?;
+        repo.set_head("refs/heads/feature")?;
+
+        assert_eq!(get_branch_name(&repo)?, "feature");
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_branch_name_mid_rebase() -> Result<()> {
+        let temp_dir = create_test_repo()?;
+        let repo = Repository::open(temp_dir.path())?;
+        repo.set_head_detached(repo.head()?.peel_to_commit()?.id())?;
+        fs::create_dir(repo.path().join("rebase-merge"))?;
+        fs::write(
+            repo.path().join("rebase-merge/head-name"),
+            "refs/heads/topic\n",
+        )?;
+
+        assert_eq!(get_branch_name(&repo)?, "topic");
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_branch_name_detached_uses_nearest_branch() -> Result<()> {
+        let temp_dir = create_test_repo()?;
+        let repo = Repository::open(temp_dir.path())?;
+        let branch = repo.head()?.shorthand().unwrap().to_string();
+        let head = repo.head()?.peel_to_commit()?;
+        // A further-ahead branch loses to the one the commit is closest to
+        let sig = repo.signature()?;
+        let ahead = repo.commit(None, &sig, &sig, "Ahead", &head.tree()?, &[&head])?;
+        repo.branch("later", &repo.find_commit(ahead)?, false)?;
+        repo.set_head_detached(head.parent_id(0)?)?;
+
+        assert_eq!(get_branch_name(&repo)?, branch);
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_branch_name_detached_uses_tag() -> Result<()> {
+        let temp_dir = create_test_repo()?;
+        let repo = Repository::open(temp_dir.path())?;
+        let head = repo.head()?.peel_to_commit()?;
+        let sig = repo.signature()?;
+        let orphan = repo.commit(None, &sig, &sig, "Tagged", &head.tree()?, &[])?;
+        repo.tag_lightweight("v1.0.0", repo.find_commit(orphan)?.as_object(), false)?;
+        repo.set_head_detached(orphan)?;
+
+        assert_eq!(get_branch_name(&repo)?, "v1.0.0");
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_branch_name_detached_unreachable_uses_short_sha() -> Result<()> {
+        let temp_dir = create_test_repo()?;
+        let repo = Repository::open(temp_dir.path())?;
+        let head = repo.head()?.peel_to_commit()?;
+        let sig = repo.signature()?;
+        let orphan = repo.commit(None, &sig, &sig, "Dangling", &head.tree()?, &[])?;
+        repo.set_head_detached(orphan)?;
+
+        assert_eq!(get_branch_name(&repo)?, orphan.to_string()[..7]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_commit_author() -> Result<()> {
+        let temp_dir = create_test_repo()?;
+        let repo = Repository::open(temp_dir.path())?;
+
+        let (name, email) = get_commit_author(&repo, "HEAD")?;
+
+        assert_eq!(name, "Test User");
+        assert_eq!(email, "test@example.com");
+        Ok(())
+    }
+
+    #[test_case("feat: add thing", "feat", "", false ; "plain")]
+    #[test_case("feat(api): add thing", "feat", "api", false ; "scoped")]
+    #[test_case("feat!: drop v1 API", "feat", "", true ; "bang")]
+    #[test_case("feat(api)!: drop v1 API", "feat", "api", true ; "scoped bang")]
+    #[test_case("fix: tidy\n\nBREAKING CHANGE: config keys renamed", "fix", "", true ; "footer")]
+    #[test_case("fix: tidy\n\nBREAKING-CHANGE: config keys renamed", "fix", "", true ; "hyphenated footer")]
+    #[test_case("fix: tidy\n\nNot a BREAKING CHANGE: mid-line", "fix", "", false ; "footer mid line")]
+    #[test_case("Update README", "commit", "", false ; "not conventional")]
+    #[test_case("Wow! it works: finally", "Wow! it works", "", false ; "bang before colon not at end")]
+    fn test_parse_conventional_commit(
+        message: &str,
+        commit_type: &str,
+        scope: &str,
+        breaking: bool,
+    ) {
+        let first_line = message.lines().next().unwrap();
+        assert_eq!(parse_commit_type(message), commit_type);
+        assert_eq!(parse_commit_scope(first_line), scope);
+        assert_eq!(is_breaking_change(message), breaking);
+    }
+
+    #[test_case("feat: solo", &[] ; "no trailers")]
+    #[test_case("feat: pair\n\nCo-authored-by: Sam Pair <sam@example.com>", &["Sam Pair <sam@example.com>"] ; "one")]
+    #[test_case("feat: mob\n\nBody.\n\nco-authored-by: Sam <sam@example.com>\nCo-Authored-By: Kim <kim@example.com>\nSigned-off-by: Zoë <zoe@example.com>\n", &["Sam <sam@example.com>", "Kim <kim@example.com>"] ; "several any case")]
+    #[test_case("feat: pair\n\nCo-authored-by: Sam Pair\nCo-authored-by: <anon@example.com>\nCo-authored-by: Kim <kim@example.com>", &["Kim <kim@example.com>"] ; "malformed skipped")]
+    #[test_case("feat: pair\n\nCo-authored-by: Sam <sam@example.com>\n\nTrailing paragraph", &[] ; "not in final paragraph")]
+    #[test_case("Co-authored-by: Sam <sam@example.com>", &[] ; "subject is not a trailer")]
+    fn test_parse_co_authors(message: &str, expected: &[&str]) {
+        assert_eq!(parse_co_authors(message), expected);
+    }
+
+    #[test]
+    fn test_get_commit_time() -> Result<()> {
+        let temp_dir = create_test_repo()?;
+        let repo = Repository::open(temp_dir.path())?;
+        let sig = git2::Signature::new(
+            "Test User",
+            "test@example.com",
+            &git2::Time::new(1_705_322_096, 120),
+        )?;
+        let tree = repo.head()?.peel_to_tree()?;
+        let parent = repo.head()?.peel_to_commit()?;
+        repo.commit(Some("HEAD"), &sig, &sig, "Dated commit", &tree, &[&parent])?;
+
+        let time = get_commit_time(&repo, "HEAD")?;
+
+        assert_eq!(time.to_rfc3339(), "2024-01-15T14:34:56+02:00");
+        Ok(())
+    }
+
     #[test]
     fn test_get_diff_stats() -> Result<()> {
         let temp_dir = create_test_repo()?;
@@ -332,7 +734,7 @@ mod tests {
         let commit = head.peel_to_commit()?;
         let sha = commit.id().to_string();
 
-        let stats = get_diff_stats_in_dir(&sha, Some(temp_dir.path()))?;
+        let stats = diff_stats(&repo, &sha, &[])?;
 
         // The second commit added a file, so we should have:
         // - 1 file changed
@@ -343,4 +745,128 @@ mod tests {
         assert_eq!(stats.deletions, 0);
         Ok(())
     }
+
+    #[test]
+    fn test_diff_stats_initial_commit() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo = Repository::init(temp_dir.path())?;
+        repo.config()?.set_str("user.name", "Test User")?;
+        repo.config()?.set_str("user.email", "test@example.com")?;
+
+        let root = commit_files(
+            &repo,
+            &[("a.txt", b"one\ntwo\n"), ("b.txt", b"three\n")],
+            &[],
+        )?;
+
+        let stats = diff_stats(&repo, &root.to_string(), &[])?;
+        assert_eq!(
+            stats,
+            DiffStats {
+                files_changed: 2,
+                insertions: 3,
+                deletions: 0
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_stats_excludes_matching_paths() -> Result<()> {
+        let temp_dir = create_test_repo()?;
+        let repo = Repository::open(temp_dir.path())?;
+        let head = repo.head()?.peel_to_commit()?;
+        let id = commit_files(
+            &repo,
+            &[
+                ("Cargo.lock", b"a\nb\nc\nd\n"),
+                ("crates/core/Cargo.lock", b"a\nb\n"),
+                ("web/app.min.js", b"x\n"),
+                ("src/Cargo.lock.rs", b"fn main() {}\n"),
+                ("image.bin", b"\x89PNG\0\0\x01\x02"),
+            ],
+            &[&head],
+        )?;
+        let exclude = crate::config::ClientConfig::default().stats_exclude;
+
+        let all = diff_stats(&repo, &id.to_string(), &[])?;
+        let filtered = diff_stats(&repo, &id.to_string(), &exclude)?;
+
+        assert_eq!(
+            all,
+            DiffStats {
+                files_changed: 5,
+                insertions: 8,
+                deletions: 0
+            }
+        );
+        assert_eq!(
+            filtered,
+            DiffStats {
+                files_changed: 2,
+                insertions: 1,
+                deletions: 0
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_stats_counts_binary_files_without_lines() -> Result<()> {
+        let temp_dir = create_test_repo()?;
+        let repo = Repository::open(temp_dir.path())?;
+        let head = repo.head()?.peel_to_commit()?;
+
+        let id = commit_files(
+            &repo,
+            &[
+                ("image.bin", b"\x89PNG\0\0\x01\x02"),
+                ("test.txt", b"changed\n"),
+            ],
+            &[&head],
+        )?;
+
+        let stats = diff_stats(&repo, &id.to_string(), &[])?;
+        assert_eq!(
+            stats,
+            DiffStats {
+                files_changed: 2,
+                insertions: 1,
+                deletions: 1
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_stats_merge_uses_first_parent() -> Result<()> {
+        let temp_dir = create_test_repo()?;
+        let repo = Repository::open(temp_dir.path())?;
+        let base = repo.head()?.peel_to_commit()?;
+
+        let main = repo.find_commit(commit_files(&repo, &[("main.txt", b"main\n")], &[&base])?)?;
+        // Branch off base again
+        let mut index = repo.index()?;
+        index.read_tree(&base.tree()?)?;
+        index.write()?;
+        let feature = repo.find_commit(commit_files(
+            &repo,
+            &[("feature.txt", b"one\ntwo\n")],
+            &[&base],
+        )?)?;
+
+        // The merge brings in the feature branch's file relative to main
+        let merge = commit_files(&repo, &[("main.txt", b"main\n")], &[&main, &feature])?;
+
+        let stats = diff_stats(&repo, &merge.to_string(), &[])?;
+        assert_eq!(
+            stats,
+            DiffStats {
+                files_changed: 1,
+                insertions: 2,
+                deletions: 0
+            }
+        );
+        Ok(())
+    }
 }
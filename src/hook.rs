@@ -0,0 +1,366 @@
+//! The post-commit hook that runs `lolcommits_upload`, behind `lolcommits_upload
+//! install` / `uninstall` and `lolcommits setup`.
+//!
+//! The hook's command sits between marker comments, so it can be appended to an existing
+//! shell hook and later removed without touching the rest of the file.
+
+use crate::error::{Error, Result};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+const BEGIN_MARKER: &str = "# >>> lolcommits >>>";
+const END_MARKER: &str = "# <<< lolcommits <<<";
+
+/// Command the hook runs, in the background so the commit doesn't wait for the camera.
+const HOOK_COMMAND: &str = "lolcommits_upload --quiet HEAD &";
+
+/// What `lolcommits setup` added before the markers existed.
+const LEGACY_COMMENT: &str = "# Added by lolcommits setup";
+const LEGACY_COMMAND: &str = "lolcommits_upload --quiet";
+
+/// Shells whose hooks the stanza can be appended to.
+const SHELLS: &[&str] = &["sh", "bash", "dash", "zsh", "ksh"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Created(PathBuf),
+    Appended(PathBuf),
+    /// An unrelated hook was replaced, after moving it to `backup`.
+    Replaced {
+        path: PathBuf,
+        backup: PathBuf,
+    },
+    AlreadyInstalled(PathBuf),
+    /// The hook only ran lolcommits and was deleted.
+    Removed(PathBuf),
+    /// The stanza was removed, leaving the rest of the hook.
+    Stripped(PathBuf),
+    NotInstalled(PathBuf),
+}
+
+impl std::fmt::Display for Change {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Change::Created(path) => write!(fmt, "wrote {}", path.display()),
+            Change::Appended(path) => write!(fmt, "appended to {}", path.display()),
+            Change::Replaced { path, backup } => write!(
+                fmt,
+                "replaced {}, the previous hook is in {}",
+                path.display(),
+                backup.display()
+            ),
+            Change::AlreadyInstalled(path) => {
+                write!(fmt, "{} already runs lolcommits", path.display())
+            }
+            Change::Removed(path) => write!(fmt, "removed {}", path.display()),
+            Change::Stripped(path) => write!(fmt, "removed lolcommits from {}", path.display()),
+            Change::NotInstalled(path) => {
+                write!(fmt, "{} doesn't run lolcommits", path.display())
+            }
+        }
+    }
+}
+
+/// Whether a hook's contents already run lolcommits.
+pub fn is_installed(contents: &str) -> bool {
+    contents.contains(BEGIN_MARKER)
+        || contents
+            .lines()
+            .any(|line| line.trim_start().starts_with(LEGACY_COMMAND))
+}
+
+/// Install the post-commit hook in `hooks_dir`, appending to an existing shell hook.
+/// Any other existing hook is only replaced with `force`, keeping it as a backup.
+pub fn install(hooks_dir: &Path, force: bool) -> Result<Change> {
+    let path = hooks_dir.join("post-commit");
+    let stanza = format!("{BEGIN_MARKER}\n{HOOK_COMMAND}\n{END_MARKER}\n");
+
+    let change = match read_hook(&path)? {
+        None => {
+            write_hook(&path, &format!("#!/bin/sh\n\n{stanza}"))?;
+            Change::Created(path)
+        }
+        Some(Some(contents)) if is_installed(&contents) => Change::AlreadyInstalled(path),
+        Some(Some(mut contents)) if is_shell_script(&contents) => {
+            if !contents.ends_with('\n') {
+                contents.push('\n');
+            }
+            write_hook(&path, &format!("{contents}\n{stanza}"))?;
+            Change::Appended(path)
+        }
+        Some(_) if force => {
+            let backup = path.with_extension("lolcommits-backup");
+            std::fs::rename(&path, &backup)?;
+            write_hook(&path, &format!("#!/bin/sh\n\n{stanza}"))?;
+            Change::Replaced { path, backup }
+        }
+        Some(_) => return Err(Error::HookConflict { path }),
+    };
+    tracing::info!(change = %change, "Installed post-commit hook");
+    Ok(change)
+}
+
+/// Remove what [`install`] (or `lolcommits setup`) added to the post-commit hook in
+/// `hooks_dir`, deleting the hook if nothing else is left in it.
+pub fn uninstall(hooks_dir: &Path) -> Result<Change> {
+    let path = hooks_dir.join("post-commit");
+    let Some(Some(contents)) = read_hook(&path)? else {
+        return Ok(Change::NotInstalled(path));
+    };
+
+    let mut kept = Vec::new();
+    let mut in_stanza = false;
+    let mut removed = false;
+    for line in contents.lines() {
+        match line.trim() {
+            BEGIN_MARKER => in_stanza = true,
+            END_MARKER if in_stanza => in_stanza = false,
+            _ if in_stanza => {}
+            LEGACY_COMMENT | LEGACY_COMMAND => {}
+            _ => {
+                kept.push(line);
+                continue;
+            }
+        }
+        removed = true;
+    }
+    if !removed {
+        return Ok(Change::NotInstalled(path));
+    }
+
+    while kept.last().is_some_and(|line| line.trim().is_empty()) {
+        kept.pop();
+    }
+    let change = if kept.iter().all(|line| line.starts_with("#!")) {
+        std::fs::remove_file(&path)?;
+        Change::Removed(path)
+    } else {
+        write_hook(&path, &format!("{}\n", kept.join("\n")))?;
+        Change::Stripped(path)
+    };
+    tracing::info!(change = %change, "Uninstalled post-commit hook");
+    Ok(change)
+}
+
+/// The global `core.hooksPath` that every repository runs hooks from. When unset and
+/// `configure` is true, it is set to `default`.
+pub fn global_hooks_dir(
+    config: &mut git2::Config,
+    default: &Path,
+    configure: bool,
+) -> Result<PathBuf> {
+    if let Ok(path) = config.get_path("core.hooksPath") {
+        return Ok(path);
+    }
+    if configure {
+        config.set_str("core.hooksPath", &default.to_string_lossy())?;
+        tracing::info!(path = %default.display(), "Set global core.hooksPath");
+    }
+    Ok(default.to_path_buf())
+}
+
+/// The user's global git config (`~/.gitconfig` or `$XDG_CONFIG_HOME/git/config`).
+pub fn open_global_config() -> Result<git2::Config> {
+    let path = git2::Config::find_global()
+        .or_else(|_| git2::Config::find_xdg())
+        .or_else(|_| {
+            std::env::var_os("HOME")
+                .map(|home| PathBuf::from(home).join(".gitconfig"))
+                .ok_or(Error::NoHomeDirectory)
+        })?;
+    Ok(git2::Config::open(&path)?)
+}
+
+/// `None` when there's no hook, `Some(None)` when it isn't text.
+fn read_hook(path: &Path) -> Result<Option<Option<String>>> {
+    match std::fs::read(path) {
+        Ok(bytes) => Ok(Some(String::from_utf8(bytes).ok())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn write_hook(path: &Path, contents: &str) -> Result {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, contents)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))?;
+    Ok(())
+}
+
+/// Whether the hook's shebang names a POSIX-ish shell, directly or through `env`.
+fn is_shell_script(contents: &str) -> bool {
+    let Some(shebang) = contents.lines().next().and_then(|l| l.strip_prefix("#!")) else {
+        return false;
+    };
+    let mut words = shebang.split_whitespace();
+    let interpreter = match words.next() {
+        Some(program) if program.ends_with("/env") => words.next(),
+        program => program,
+    };
+    interpreter
+        .and_then(|program| program.rsplit('/').next())
+        .is_some_and(|name| SHELLS.contains(&name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    /// Hooks dir of a fresh repository in `dir`.
+    fn repo_hooks_dir(dir: &Path) -> Result<PathBuf> {
+        let repo = git2::Repository::init(dir)?;
+        Ok(crate::git::hooks_dir(&repo))
+    }
+
+    #[test]
+    fn test_install_fresh_and_rerun() -> Result {
+        let dir = tempfile::tempdir()?;
+        let hooks_dir = repo_hooks_dir(dir.path())?;
+        let hook = hooks_dir.join("post-commit");
+
+        assert_eq!(install(&hooks_dir, false)?, Change::Created(hook.clone()));
+
+        let contents = std::fs::read_to_string(&hook)?;
+        assert!(contents.starts_with("#!/bin/sh\n"));
+        assert!(contents.contains(&format!("{BEGIN_MARKER}\n{HOOK_COMMAND}\n{END_MARKER}\n")));
+        assert_eq!(
+            std::fs::metadata(&hook)?.permissions().mode() & 0o777,
+            0o755
+        );
+
+        assert_eq!(
+            install(&hooks_dir, false)?,
+            Change::AlreadyInstalled(hook.clone())
+        );
+        assert_eq!(std::fs::read_to_string(&hook)?, contents);
+        Ok(())
+    }
+
+    #[test]
+    fn test_install_appends_and_uninstall_restores_shell_hook() -> Result {
+        let dir = tempfile::tempdir()?;
+        let hooks_dir = repo_hooks_dir(dir.path())?;
+        let hook = hooks_dir.join("post-commit");
+        let original = "#!/usr/bin/env bash\necho committed\n";
+        write_hook(&hook, original)?;
+
+        assert_eq!(install(&hooks_dir, false)?, Change::Appended(hook.clone()));
+        let contents = std::fs::read_to_string(&hook)?;
+        assert!(contents.starts_with(original));
+        assert!(contents.ends_with(&format!("{END_MARKER}\n")));
+
+        assert_eq!(uninstall(&hooks_dir)?, Change::Stripped(hook.clone()));
+        assert_eq!(std::fs::read_to_string(&hook)?, original);
+        Ok(())
+    }
+
+    #[test]
+    fn test_install_refuses_unrelated_hook_without_force() -> Result {
+        let dir = tempfile::tempdir()?;
+        let hooks_dir = repo_hooks_dir(dir.path())?;
+        let hook = hooks_dir.join("post-commit");
+        let original = "#!/usr/bin/env python3\nprint('committed')\n";
+        write_hook(&hook, original)?;
+
+        let result = install(&hooks_dir, false);
+        assert!(
+            matches!(&result, Err(Error::HookConflict { path }) if *path == hook),
+            "{result:?}"
+        );
+        assert_eq!(std::fs::read_to_string(&hook)?, original);
+
+        let backup = hooks_dir.join("post-commit.lolcommits-backup");
+        assert_eq!(
+            install(&hooks_dir, true)?,
+            Change::Replaced {
+                path: hook.clone(),
+                backup: backup.clone()
+            }
+        );
+        assert_eq!(std::fs::read_to_string(&backup)?, original);
+        assert!(is_installed(&std::fs::read_to_string(&hook)?));
+        Ok(())
+    }
+
+    #[test]
+    fn test_uninstall_removes_hook_it_created() -> Result {
+        let dir = tempfile::tempdir()?;
+        let hooks_dir = repo_hooks_dir(dir.path())?;
+        let hook = hooks_dir.join("post-commit");
+
+        assert_eq!(uninstall(&hooks_dir)?, Change::NotInstalled(hook.clone()));
+
+        install(&hooks_dir, false)?;
+        assert_eq!(uninstall(&hooks_dir)?, Change::Removed(hook.clone()));
+        assert!(!hook.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_uninstall_leaves_unrelated_hook() -> Result {
+        let dir = tempfile::tempdir()?;
+        let hook = dir.path().join("post-commit");
+        write_hook(&hook, "#!/bin/sh\necho committed\n")?;
+
+        assert_eq!(uninstall(dir.path())?, Change::NotInstalled(hook.clone()));
+        assert_eq!(
+            std::fs::read_to_string(&hook)?,
+            "#!/bin/sh\necho committed\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_uninstall_removes_legacy_setup_lines() -> Result {
+        let dir = tempfile::tempdir()?;
+        let hook = dir.path().join("post-commit");
+        write_hook(
+            &hook,
+            &format!("#!/bin/sh\necho committed\n\n{LEGACY_COMMENT}\n{LEGACY_COMMAND}\n"),
+        )?;
+        assert!(is_installed(&std::fs::read_to_string(&hook)?));
+
+        assert_eq!(uninstall(dir.path())?, Change::Stripped(hook.clone()));
+        assert_eq!(
+            std::fs::read_to_string(&hook)?,
+            "#!/bin/sh\necho committed\n"
+        );
+        Ok(())
+    }
+
+    #[test_case("#!/bin/sh", true ; "sh")]
+    #[test_case("#!/bin/bash -e", true ; "bash with flags")]
+    #[test_case("#!/usr/bin/env zsh", true ; "env zsh")]
+    #[test_case("#!/usr/bin/env node", false ; "node")]
+    #[test_case("#!/usr/bin/python3", false ; "python")]
+    #[test_case("echo no shebang", false ; "no shebang")]
+    fn test_is_shell_script(first_line: &str, expected: bool) {
+        assert_eq!(
+            is_shell_script(&format!("{first_line}\necho hi\n")),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_global_hooks_dir() -> Result {
+        let dir = tempfile::tempdir()?;
+        let mut config = git2::Config::open(&dir.path().join("gitconfig"))?;
+        let default = dir.path().join("hooks");
+
+        assert_eq!(global_hooks_dir(&mut config, &default, false)?, default);
+        assert!(config.get_path("core.hooksPath").is_err());
+
+        assert_eq!(global_hooks_dir(&mut config, &default, true)?, default);
+        assert_eq!(config.get_path("core.hooksPath")?, default);
+
+        config.set_str("core.hooksPath", "/srv/git-hooks")?;
+        assert_eq!(
+            global_hooks_dir(&mut config, &default, true)?,
+            PathBuf::from("/srv/git-hooks")
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,253 @@
+//! Size-bounded in-memory LRU of recently served image bytes.
+//!
+//! Entries are keyed by filename and remember the file's mtime, so a file that changes
+//! on disk is never served stale: a lookup with a different mtime is a miss. Paths that
+//! delete or rewrite images should still call [`ImageCache::invalidate`] to release the
+//! memory straight away.
+
+use axum::body::Bytes;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// Largest share of the cache a single file may take; bigger files are served from disk.
+const MAX_ENTRY_FRACTION: u64 = 4;
+
+#[derive(Debug)]
+struct Entry {
+    mtime: SystemTime,
+    bytes: Bytes,
+    last_used: u64,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    entries: HashMap<String, Entry>,
+    /// Access tick -> filename, oldest first.
+    recency: BTreeMap<u64, String>,
+    used_bytes: u64,
+    tick: u64,
+}
+
+impl Inner {
+    fn touch(&mut self, filename: &str) {
+        self.tick += 1;
+        let tick = self.tick;
+        if let Some(entry) = self.entries.get_mut(filename) {
+            self.recency.remove(&entry.last_used);
+            entry.last_used = tick;
+            self.recency.insert(tick, filename.to_owned());
+        }
+    }
+
+    fn remove(&mut self, filename: &str) -> bool {
+        match self.entries.remove(filename) {
+            Some(entry) => {
+                self.recency.remove(&entry.last_used);
+                self.used_bytes -= entry.bytes.len() as u64;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ImageCache {
+    capacity_bytes: u64,
+    inner: Mutex<Inner>,
+}
+
+impl ImageCache {
+    pub fn new(capacity_bytes: u64) -> Self {
+        Self {
+            capacity_bytes,
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    /// Build a cache from the `image_cache_mb` setting, `None` when caching is off.
+    pub fn from_megabytes(megabytes: u64) -> Option<Self> {
+        (megabytes > 0).then(|| Self::new(megabytes * 1024 * 1024))
+    }
+
+    /// Whether a file of this size is small enough to be worth caching.
+    pub fn accepts(&self, len: u64) -> bool {
+        len <= self.capacity_bytes / MAX_ENTRY_FRACTION
+    }
+
+    /// Look up `filename`, only returning bytes cached for the same `mtime`.
+    pub fn get(&self, filename: &str, mtime: SystemTime) -> Option<Bytes> {
+        let mut inner = self.inner.lock().expect("image cache lock poisoned");
+
+        let bytes = match inner.entries.get(filename) {
+            Some(entry) if entry.mtime == mtime => entry.bytes.clone(),
+            Some(_) => {
+                // File changed on disk since it was cached
+                inner.remove(filename);
+                crate::metrics::set_image_cache_bytes(inner.used_bytes);
+                crate::metrics::record_image_cache_lookup("miss");
+                return None;
+            }
+            None => {
+                crate::metrics::record_image_cache_lookup("miss");
+                return None;
+            }
+        };
+
+        inner.touch(filename);
+        crate::metrics::record_image_cache_lookup("hit");
+        Some(bytes)
+    }
+
+    /// Cache `bytes` for `filename`, evicting least recently used entries to make room.
+    pub fn insert(&self, filename: &str, mtime: SystemTime, bytes: Bytes) {
+        let len = bytes.len() as u64;
+        if !self.accepts(len) {
+            return;
+        }
+
+        let mut inner = self.inner.lock().expect("image cache lock poisoned");
+        inner.remove(filename);
+
+        while inner.used_bytes + len > self.capacity_bytes {
+            let Some((_, oldest)) = inner.recency.pop_first() else {
+                break;
+            };
+            if let Some(entry) = inner.entries.remove(&oldest) {
+                inner.used_bytes -= entry.bytes.len() as u64;
+                tracing::debug!(filename = %oldest, "Evicted image from cache");
+                crate::metrics::record_image_cache_eviction();
+            }
+        }
+
+        inner.tick += 1;
+        let tick = inner.tick;
+        inner.recency.insert(tick, filename.to_owned());
+        inner.entries.insert(
+            filename.to_owned(),
+            Entry {
+                mtime,
+                bytes,
+                last_used: tick,
+            },
+        );
+        inner.used_bytes += len;
+        crate::metrics::set_image_cache_bytes(inner.used_bytes);
+    }
+
+    /// Drop any cached bytes for `filename`.
+    pub fn invalidate(&self, filename: &str) {
+        let mut inner = self.inner.lock().expect("image cache lock poisoned");
+        if inner.remove(filename) {
+            tracing::debug!(filename, "Invalidated cached image");
+            crate::metrics::set_image_cache_bytes(inner.used_bytes);
+        }
+    }
+
+    pub fn used_bytes(&self) -> u64 {
+        self.inner
+            .lock()
+            .expect("image cache lock poisoned")
+            .used_bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn bytes(len: usize) -> Bytes {
+        Bytes::from(vec![0u8; len])
+    }
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn test_from_megabytes_zero_is_disabled() {
+        assert!(ImageCache::from_megabytes(0).is_none());
+        assert_eq!(
+            ImageCache::from_megabytes(2).unwrap().capacity_bytes,
+            2 * 1024 * 1024
+        );
+    }
+
+    #[test]
+    fn test_hit_and_miss() {
+        let cache = ImageCache::new(100);
+        assert!(cache.get("a.png", at(1)).is_none());
+
+        cache.insert("a.png", at(1), bytes(10));
+        assert_eq!(cache.get("a.png", at(1)).unwrap().len(), 10);
+        assert!(cache.get("b.png", at(1)).is_none());
+    }
+
+    #[test]
+    fn test_changed_mtime_is_a_miss() {
+        let cache = ImageCache::new(100);
+        cache.insert("a.png", at(1), bytes(10));
+
+        assert!(cache.get("a.png", at(2)).is_none());
+        // The stale entry is dropped rather than kept around
+        assert_eq!(cache.used_bytes(), 0);
+    }
+
+    #[test]
+    fn test_invalidate() {
+        let cache = ImageCache::new(100);
+        cache.insert("a.png", at(1), bytes(10));
+        cache.insert("b.png", at(1), bytes(10));
+
+        cache.invalidate("a.png");
+        cache.invalidate("missing.png");
+
+        assert!(cache.get("a.png", at(1)).is_none());
+        assert!(cache.get("b.png", at(1)).is_some());
+        assert_eq!(cache.used_bytes(), 10);
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let cache = ImageCache::new(100);
+        cache.insert("a.png", at(1), bytes(25));
+        cache.insert("b.png", at(1), bytes(25));
+        cache.insert("c.png", at(1), bytes(25));
+        cache.insert("d.png", at(1), bytes(25));
+
+        // Touch a so b becomes the oldest
+        assert!(cache.get("a.png", at(1)).is_some());
+        cache.insert("e.png", at(1), bytes(25));
+
+        assert!(cache.get("b.png", at(1)).is_none());
+        for name in ["a.png", "c.png", "d.png", "e.png"] {
+            assert!(cache.get(name, at(1)).is_some(), "{name} should be cached");
+        }
+        assert_eq!(cache.used_bytes(), 100);
+    }
+
+    #[test]
+    fn test_oversized_files_are_not_cached() {
+        let cache = ImageCache::new(100);
+        cache.insert("small.png", at(1), bytes(25));
+        cache.insert("big.png", at(1), bytes(26));
+
+        assert!(cache.get("big.png", at(1)).is_none());
+        assert!(cache.get("small.png", at(1)).is_some());
+        assert_eq!(cache.used_bytes(), 25);
+    }
+
+    #[test]
+    fn test_reinsert_replaces_entry() {
+        let cache = ImageCache::new(100);
+        cache.insert("a.png", at(1), bytes(10));
+        cache.insert("a.png", at(2), bytes(20));
+
+        assert!(cache.get("a.png", at(1)).is_none());
+        cache.insert("a.png", at(2), bytes(20));
+        assert_eq!(cache.get("a.png", at(2)).unwrap().len(), 20);
+        assert_eq!(cache.used_bytes(), 20);
+    }
+}
@@ -0,0 +1,670 @@
+//! In-memory index of the metadata of the images in `images_dir`.
+//!
+//! Reading the metadata chunks of every PNG takes seconds once the gallery holds a few
+//! thousand images, so the server reads each file once, records uploads as they are
+//! saved and rescans the directory when the index is older than its rescan interval.
+//! A rescan drops files deleted by hand and only parses files it hasn't seen before or
+//! that changed since they were indexed.
+//!
+//! The server keeps a snapshot of the index in `state_dir`, so after a restart only
+//! the images that changed while it was down are parsed again. A thread of its own
+//! saves it shortly after the index changes, so nothing waits on the write.
+
+use crate::error::Result;
+use crate::git::CommitMetadata;
+use crate::image_metadata;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How long the index is trusted before the next listing rescans `images_dir`.
+pub const RESCAN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Name of the index snapshot within `state_dir`.
+pub const SNAPSHOT_FILE_NAME: &str = "image-index.json";
+
+/// How long the snapshot waits for further changes before it is saved, so a burst of
+/// uploads or deletions costs one write.
+const SNAPSHOT_DELAY: Duration = Duration::from_secs(1);
+
+pub struct ImageIndex {
+    images_dir: PathBuf,
+    rescan_interval: Duration,
+    state: Arc<RwLock<State>>,
+    /// Persists the index, if anything does.
+    snapshot: Option<SnapshotWriter>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Keyed by filename.
+    entries: HashMap<String, Entry>,
+    scanned_at: Option<Instant>,
+    /// Modification time of `images_dir` itself when it was last scanned.
+    dir_modified: Option<SystemTime>,
+    /// Bumped whenever the indexed images change.
+    generation: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    #[serde(flatten)]
+    metadata: CommitMetadata,
+    modified: Option<SystemTime>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    /// A snapshot of some other directory is ignored.
+    images_dir: PathBuf,
+    entries: HashMap<String, Entry>,
+}
+
+impl ImageIndex {
+    /// Index `images_dir` now. A failed scan is logged and retried on the next listing.
+    pub fn open(images_dir: impl Into<PathBuf>) -> Self {
+        Self::with_rescan_interval(images_dir, RESCAN_INTERVAL)
+    }
+
+    /// [`ImageIndex::open`], starting from the snapshot in `state_dir` and keeping it up
+    /// to date.
+    pub fn open_persistent(images_dir: impl Into<PathBuf>, state_dir: impl AsRef<Path>) -> Self {
+        Self::build(
+            images_dir.into(),
+            RESCAN_INTERVAL,
+            Some(state_dir.as_ref().join(SNAPSHOT_FILE_NAME)),
+        )
+    }
+
+    pub fn with_rescan_interval(images_dir: impl Into<PathBuf>, rescan_interval: Duration) -> Self {
+        Self::build(images_dir.into(), rescan_interval, None)
+    }
+
+    fn build(
+        images_dir: PathBuf,
+        rescan_interval: Duration,
+        snapshot_path: Option<PathBuf>,
+    ) -> Self {
+        let entries = snapshot_path
+            .as_deref()
+            .map(|path| load_snapshot(path, &images_dir))
+            .unwrap_or_default();
+        let state = Arc::new(RwLock::new(State {
+            entries,
+            ..State::default()
+        }));
+        let snapshot = snapshot_path
+            .map(|path| SnapshotWriter::spawn(path, images_dir.clone(), state.clone()));
+        let index = Self {
+            images_dir,
+            rescan_interval,
+            state,
+            snapshot,
+        };
+        match index.rescan() {
+            Ok(count) => {
+                tracing::info!(count, images_dir = %index.images_dir.display(), "Indexed images")
+            }
+            Err(e) => {
+                tracing::warn!(images_dir = %index.images_dir.display(), error = %e, "Failed to index images")
+            }
+        }
+        index
+    }
+
+    /// Every indexed image, newest first. Rescans first when the index is stale.
+    pub fn list(&self) -> Vec<CommitMetadata> {
+        if self.is_stale()
+            && let Err(e) = self.rescan()
+        {
+            tracing::warn!(images_dir = %self.images_dir.display(), error = %e, "Failed to rescan images");
+        }
+
+        let state = self.state.read().expect("image index lock poisoned");
+        let mut images: Vec<CommitMetadata> = state
+            .entries
+            .values()
+            .map(|entry| entry.metadata.clone())
+            .collect();
+        // Newest first; images with unparseable timestamps last
+        images.sort_by_cached_key(|image| {
+            std::cmp::Reverse(crate::parse_timestamp(&image.timestamp))
+        });
+        images
+    }
+
+    pub fn len(&self) -> usize {
+        self.state
+            .read()
+            .expect("image index lock poisoned")
+            .entries
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// A counter that changes whenever an image is added, replaced or dropped, so
+    /// anything derived from the listing can tell when it is out of date.
+    pub fn generation(&self) -> u64 {
+        self.state
+            .read()
+            .expect("image index lock poisoned")
+            .generation
+    }
+
+    /// Index a file just saved into `images_dir`, replacing any entry of the same name.
+    pub fn insert(&self, path: &Path) {
+        let Some(filename) = path.file_name().and_then(|s| s.to_str()) else {
+            return;
+        };
+        let Some(metadata) = image_metadata::parse_image_file(path) else {
+            tracing::warn!(path = %path.display(), "Saved image has no readable metadata, not indexing");
+            return;
+        };
+
+        let entry = Entry {
+            metadata,
+            modified: modified(path),
+        };
+        let mut state = self.state.write().expect("image index lock poisoned");
+        state.entries.insert(filename.to_string(), entry);
+        state.generation += 1;
+        crate::metrics::set_images_total(state.entries.len());
+        drop(state);
+        self.snapshot_changed();
+    }
+
+    /// Forget `filename`, returning its metadata if it was indexed.
+    pub fn remove(&self, filename: &str) -> Option<CommitMetadata> {
+        let mut state = self.state.write().expect("image index lock poisoned");
+        let entry = state.entries.remove(filename)?;
+        state.generation += 1;
+        crate::metrics::set_images_total(state.entries.len());
+        drop(state);
+        self.snapshot_changed();
+        Some(entry.metadata)
+    }
+
+    /// Rescan if files have been added to or removed from `images_dir` since the last
+    /// scan, going by the directory's modification time. Cheap enough to call before
+    /// every lookup that must not miss files added or deleted by hand.
+    pub fn rescan_if_changed(&self) {
+        let dir_modified = modified(&self.images_dir);
+        let scanned = self.state.read().expect("image index lock poisoned");
+        if scanned.scanned_at.is_some() && scanned.dir_modified == dir_modified {
+            return;
+        }
+        drop(scanned);
+
+        if let Err(e) = self.rescan() {
+            tracing::warn!(images_dir = %self.images_dir.display(), error = %e, "Failed to rescan images");
+        }
+    }
+
+    /// Re-read `images_dir`, returning how many images are indexed.
+    pub fn rescan(&self) -> Result<usize> {
+        // Before reading, so changes made during the scan trigger another
+        let dir_modified = modified(&self.images_dir);
+        let paths: Vec<PathBuf> = match std::fs::read_dir(&self.images_dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| image_metadata::is_image_file(path))
+                .collect(),
+            // images_dir is only created by the first upload
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        // Parse outside the lock so listings aren't held up, reusing entries for files
+        // that haven't changed
+        let known: HashMap<String, Option<SystemTime>> = self
+            .state
+            .read()
+            .expect("image index lock poisoned")
+            .entries
+            .iter()
+            .map(|(filename, entry)| (filename.clone(), entry.modified))
+            .collect();
+        let mut scanned = HashMap::with_capacity(paths.len());
+        for path in paths {
+            let Some(filename) = path.file_name().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            let modified = modified(&path);
+            if known.get(filename) == Some(&modified) {
+                scanned.insert(filename.to_string(), None);
+            } else if let Some(metadata) = image_metadata::parse_image_file(&path) {
+                scanned.insert(filename.to_string(), Some(Entry { metadata, modified }));
+            }
+        }
+
+        let mut state = self.state.write().expect("image index lock poisoned");
+        let mut previous = std::mem::take(&mut state.entries);
+        let mut changed = 0;
+        for (filename, entry) in scanned {
+            if entry.is_some() {
+                changed += 1;
+            }
+            if let Some(entry) = entry.or_else(|| previous.remove(&filename)) {
+                state.entries.insert(filename, entry);
+            }
+        }
+        // Whatever is left was deleted, unless it was inserted while the scan ran
+        let mut removed = 0;
+        for (filename, entry) in previous {
+            if known.contains_key(&filename) {
+                removed += 1;
+            } else {
+                state.entries.entry(filename).or_insert(entry);
+            }
+        }
+        if removed > 0 {
+            tracing::info!(removed, "Dropped deleted images from the index");
+        }
+        if changed + removed > 0 {
+            state.generation += 1;
+        }
+
+        state.scanned_at = Some(Instant::now());
+        state.dir_modified = dir_modified;
+        crate::metrics::set_images_total(state.entries.len());
+        let count = state.entries.len();
+        drop(state);
+        if changed + removed > 0 {
+            self.snapshot_changed();
+        }
+        Ok(count)
+    }
+
+    /// Have the snapshot, if there is one, saved soon.
+    fn snapshot_changed(&self) {
+        if let Some(snapshot) = &self.snapshot {
+            snapshot.changed();
+        }
+    }
+
+    fn is_stale(&self) -> bool {
+        self.state
+            .read()
+            .expect("image index lock poisoned")
+            .scanned_at
+            .is_none_or(|scanned_at| scanned_at.elapsed() >= self.rescan_interval)
+    }
+}
+
+/// Saves the index's snapshot on a thread of its own, [`SNAPSHOT_DELAY`] after it
+/// changes, so saves are never made on the request path and never land out of order.
+/// Dropping it saves any change still pending.
+struct SnapshotWriter {
+    changes: Option<Sender<()>>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl SnapshotWriter {
+    fn spawn(path: PathBuf, images_dir: PathBuf, state: Arc<RwLock<State>>) -> Self {
+        let (changes, changed) = std::sync::mpsc::channel();
+        let thread = std::thread::Builder::new()
+            .name("image-index-snapshot".to_string())
+            .spawn(move || write_snapshots(&path, &images_dir, &state, changed));
+        let thread = match thread {
+            Ok(thread) => Some(thread),
+            Err(e) => {
+                tracing::warn!(error = %e, "Failed to start image index writer, not saving it");
+                None
+            }
+        };
+        Self {
+            changes: Some(changes),
+            thread,
+        }
+    }
+
+    fn changed(&self) {
+        if let Some(changes) = &self.changes {
+            let _ = changes.send(());
+        }
+    }
+}
+
+impl Drop for SnapshotWriter {
+    fn drop(&mut self) {
+        // Disconnecting has the thread save what's pending and stop
+        self.changes.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Save the snapshot once changes stop arriving for [`SNAPSHOT_DELAY`], until the index
+/// is dropped.
+fn write_snapshots(path: &Path, images_dir: &Path, state: &RwLock<State>, changed: Receiver<()>) {
+    while changed.recv().is_ok() {
+        let deadline = Instant::now() + SNAPSHOT_DELAY;
+        while changed
+            .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            .is_ok()
+            && Instant::now() < deadline
+        {}
+        // Whatever these announce is about to be saved anyway
+        while changed.try_recv().is_ok() {}
+        save_snapshot(path, images_dir, state);
+    }
+}
+
+/// Write the index to its snapshot. A failure only costs parsing again after the next
+/// restart, so it is logged and otherwise ignored.
+fn save_snapshot(path: &Path, images_dir: &Path, state: &RwLock<State>) {
+    let state = state.read().expect("image index lock poisoned");
+    let json = serde_json::to_vec(&SnapshotRef {
+        images_dir,
+        entries: &state.entries,
+    });
+    drop(state);
+
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name().and_then(|s| s.to_str())) else {
+        return;
+    };
+    match json
+        .map_err(Into::into)
+        .and_then(|json| crate::storage::atomic_write(dir, name, json))
+    {
+        Ok(_) => tracing::debug!(path = %path.display(), "Saved image index"),
+        Err(e) => {
+            tracing::warn!(path = %path.display(), error = %e, "Failed to save image index")
+        }
+    }
+}
+
+/// [`Snapshot`] borrowed from the index, for saving without copying every entry.
+#[derive(Serialize)]
+struct SnapshotRef<'a> {
+    images_dir: &'a Path,
+    entries: &'a HashMap<String, Entry>,
+}
+
+/// The entries of the snapshot at `path`, with their paths restored. Empty when there
+/// is no usable snapshot, which only means every image is parsed again.
+fn load_snapshot(path: &Path, images_dir: &Path) -> HashMap<String, Entry> {
+    let snapshot = match std::fs::read(path) {
+        Ok(json) => match serde_json::from_slice::<Snapshot>(&json) {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                tracing::warn!(path = %path.display(), error = %e, "Ignoring unreadable image index");
+                return HashMap::new();
+            }
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return HashMap::new(),
+        Err(e) => {
+            tracing::warn!(path = %path.display(), error = %e, "Failed to read image index");
+            return HashMap::new();
+        }
+    };
+    if snapshot.images_dir != images_dir {
+        tracing::info!(path = %path.display(), "Image index is of another images_dir, ignoring it");
+        return HashMap::new();
+    }
+
+    let mut entries = snapshot.entries;
+    for (filename, entry) in &mut entries {
+        entry.metadata.path = images_dir.join(filename);
+    }
+    tracing::debug!(path = %path.display(), count = entries.len(), "Loaded image index");
+    entries
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An image without metadata chunks, indexed from its filename.
+    fn touch(dir: &Path, repo: &str, timestamp: &str, revision: &str) -> Result<PathBuf> {
+        let path = dir.join(format!("{repo}-{timestamp}-{revision}.png"));
+        std::fs::write(&path, b"not really a png")?;
+        Ok(path)
+    }
+
+    fn revisions(index: &ImageIndex) -> Vec<String> {
+        index
+            .list()
+            .into_iter()
+            .map(|image| image.revision)
+            .collect()
+    }
+
+    #[test]
+    fn test_indexes_existing_images_newest_first() -> Result {
+        let dir = tempfile::tempdir()?;
+        touch(dir.path(), "repo", "20240101-120000", "aaa1111")?;
+        touch(dir.path(), "repo", "20240301-120000", "ccc3333")?;
+        touch(dir.path(), "repo", "20240201-120000", "bbb2222")?;
+        std::fs::write(dir.path().join("notes.txt"), b"ignored")?;
+
+        let index = ImageIndex::open(dir.path());
+
+        assert_eq!(index.len(), 3);
+        assert_eq!(revisions(&index), ["ccc3333", "bbb2222", "aaa1111"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_mixed_timestamp_formats_sort_by_instant() -> Result {
+        let dir = tempfile::tempdir()?;
+        let image = image::DynamicImage::new_rgb8(1, 1);
+        for (revision, timestamp) in [
+            ("old1111", "2024-02-28 12:00:00"),
+            ("tokyo22", "2024-03-01T10:00:00+09:00"),
+            ("newyork", "2024-03-01T03:00:00-05:00"),
+            ("old3333", "2024-03-02 00:00:00"),
+        ] {
+            let path = dir
+                .path()
+                .join(format!("repo-20240101-000000-{revision}.png"));
+            let mut metadata = crate::image_metadata::parse_filename(&path).unwrap();
+            metadata.timestamp = timestamp.to_string();
+            crate::image_metadata::save_png_with_metadata(&image, &path, &metadata)?;
+        }
+
+        let index = ImageIndex::open(dir.path());
+
+        // As strings Tokyo's 10:00 would sort after New York's 03:00, which is later
+        assert_eq!(
+            revisions(&index),
+            ["old3333", "newyork", "tokyo22", "old1111"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_images_dir_is_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = ImageIndex::open(dir.path().join("images"));
+        assert!(index.is_empty());
+        assert!(index.list().is_empty());
+    }
+
+    #[test]
+    fn test_insert_is_listed_without_rescan() -> Result {
+        let dir = tempfile::tempdir()?;
+        let index = ImageIndex::open(dir.path());
+
+        let path = touch(dir.path(), "repo", "20240101-120000", "aaa1111")?;
+        index.insert(&path);
+
+        assert_eq!(revisions(&index), ["aaa1111"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove() -> Result {
+        let dir = tempfile::tempdir()?;
+        touch(dir.path(), "repo", "20240101-120000", "aaa1111")?;
+        touch(dir.path(), "repo", "20240201-120000", "bbb2222")?;
+        let index = ImageIndex::open(dir.path());
+
+        let removed = index.remove("repo-20240101-120000-aaa1111.png");
+
+        assert_eq!(
+            removed.map(|image| image.revision).as_deref(),
+            Some("aaa1111")
+        );
+        assert!(index.remove("repo-20240101-120000-aaa1111.png").is_none());
+        assert_eq!(revisions(&index), ["bbb2222"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rescan_drops_deleted_and_adds_new_images() -> Result {
+        let dir = tempfile::tempdir()?;
+        let deleted = touch(dir.path(), "repo", "20240101-120000", "aaa1111")?;
+        touch(dir.path(), "repo", "20240201-120000", "bbb2222")?;
+        let index = ImageIndex::open(dir.path());
+
+        std::fs::remove_file(deleted)?;
+        touch(dir.path(), "repo", "20240301-120000", "ccc3333")?;
+        // Still serving the indexed list until the next rescan
+        assert_eq!(revisions(&index), ["bbb2222", "aaa1111"]);
+
+        assert_eq!(index.rescan()?, 2);
+        assert_eq!(revisions(&index), ["ccc3333", "bbb2222"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_generation_changes_with_the_images() -> Result {
+        let dir = tempfile::tempdir()?;
+        touch(dir.path(), "repo", "20240101-120000", "aaa1111")?;
+        let index = ImageIndex::open(dir.path());
+        let opened = index.generation();
+
+        index.rescan()?;
+        assert_eq!(index.generation(), opened, "unchanged directory");
+
+        let path = touch(dir.path(), "repo", "20240201-120000", "bbb2222")?;
+        index.insert(&path);
+        let inserted = index.generation();
+        assert_ne!(inserted, opened);
+
+        assert!(index.remove("missing.png").is_none());
+        assert_eq!(index.generation(), inserted);
+        index.remove("repo-20240101-120000-aaa1111.png");
+        assert_ne!(index.generation(), inserted);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stale_index_rescans_on_list() -> Result {
+        let dir = tempfile::tempdir()?;
+        let deleted = touch(dir.path(), "repo", "20240101-120000", "aaa1111")?;
+        let index = ImageIndex::with_rescan_interval(dir.path(), Duration::ZERO);
+
+        std::fs::remove_file(deleted)?;
+
+        assert!(index.list().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_spares_parsing_unchanged_images() -> Result {
+        let dir = tempfile::tempdir()?;
+        let images_dir = dir.path().join("images");
+        std::fs::create_dir(&images_dir)?;
+        touch(&images_dir, "repo", "20240101-120000", "aaa1111")?;
+        let deleted = touch(&images_dir, "repo", "20240201-120000", "bbb2222")?;
+        ImageIndex::open_persistent(&images_dir, dir.path());
+
+        // Only a reused entry can carry this message
+        let snapshot_path = dir.path().join(SNAPSHOT_FILE_NAME);
+        let mut snapshot: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&snapshot_path)?)?;
+        snapshot["entries"]["repo-20240101-120000-aaa1111.png"]["message"] =
+            "from the snapshot".into();
+        std::fs::write(&snapshot_path, serde_json::to_vec(&snapshot)?)?;
+        std::fs::remove_file(deleted)?;
+        touch(&images_dir, "repo", "20240301-120000", "ccc3333")?;
+
+        let index = ImageIndex::open_persistent(&images_dir, dir.path());
+
+        let images = index.list();
+        assert_eq!(
+            images
+                .iter()
+                .map(|image| image.revision.as_str())
+                .collect::<Vec<_>>(),
+            ["ccc3333", "aaa1111"]
+        );
+        assert_eq!(images[1].message, "from the snapshot");
+        assert_eq!(
+            images[1].path,
+            images_dir.join("repo-20240101-120000-aaa1111.png")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_pending_snapshot_is_saved_on_drop() -> Result {
+        let dir = tempfile::tempdir()?;
+        let images_dir = dir.path().join("images");
+        std::fs::create_dir(&images_dir)?;
+        let index = ImageIndex::open_persistent(&images_dir, dir.path());
+
+        let path = touch(&images_dir, "repo", "20240101-120000", "aaa1111")?;
+        index.insert(&path);
+        drop(index);
+
+        let snapshot: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(dir.path().join(SNAPSHOT_FILE_NAME))?)?;
+        assert_eq!(
+            snapshot["entries"]["repo-20240101-120000-aaa1111.png"]["revision"],
+            "aaa1111"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_of_another_directory_is_ignored() -> Result {
+        let dir = tempfile::tempdir()?;
+        let first = dir.path().join("first");
+        let second = dir.path().join("second");
+        std::fs::create_dir(&first)?;
+        std::fs::create_dir(&second)?;
+        touch(&first, "repo", "20240101-120000", "aaa1111")?;
+        ImageIndex::open_persistent(&first, dir.path());
+
+        let index = ImageIndex::open_persistent(&second, dir.path());
+
+        assert!(index.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rescan_if_changed_notices_files_added_and_deleted() -> Result {
+        let dir = tempfile::tempdir()?;
+        let deleted = touch(dir.path(), "repo", "20240101-120000", "aaa1111")?;
+        let index = ImageIndex::open(dir.path());
+        let opened = index.generation();
+
+        index.rescan_if_changed();
+        assert_eq!(index.generation(), opened, "nothing changed");
+
+        std::fs::remove_file(deleted)?;
+        touch(dir.path(), "repo", "20240201-120000", "bbb2222")?;
+        // Directory mtimes can be as coarse as a second
+        let later = SystemTime::now() + Duration::from_secs(2);
+        std::fs::File::open(dir.path())?.set_modified(later)?;
+        index.rescan_if_changed();
+
+        assert_eq!(revisions(&index), ["bbb2222"]);
+        Ok(())
+    }
+}
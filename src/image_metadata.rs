@@ -1,16 +1,230 @@
-use crate::error::Result;
+use crate::error::{Error, Result};
 use crate::git::{CommitMetadata, DiffStats};
-use image::DynamicImage;
+use crate::overrides::Overrides;
+use chrono::{DateTime, FixedOffset, Local};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::{DynamicImage, ExtendedColorType, ImageEncoder, ImageFormat, ImageReader};
 use png::Encoder;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
-use std::io::BufWriter;
-use std::path::Path;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Chunk holding the whole [`CommitMetadata`] as one [`VersionedMetadata`] JSON object,
+/// for tools that would rather parse one blob than a chunk per field.
+pub const JSON_KEY: &str = "lolcommit:json";
+
+/// Version of the [`JSON_KEY`] chunk's layout, bumped when it changes incompatibly.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Chunk recording the processing overrides applied to an upload, as a JSON object.
+const OVERRIDES_KEY: &str = "lolcommit:Processing_overrides";
+
+/// Chunk present, as `true`, when an upload was brightened by
+/// [`crate::exposure::correct`].
+pub const CORRECTED_KEY: &str = "lolcommit:corrected";
+
+/// Chunk holding `true` or `false` when the upload chose whether the server burns the
+/// chyron in, rather than leaving it to the config.
+pub const CHYRON_KEY: &str = "lolcommit:burned_in_chyron";
+
+/// Extensions of the gallery's image files, one per [`crate::config::OutputFormat`]
+/// plus animated GIFs.
+pub const IMAGE_EXTENSIONS: [&str; 4] = ["png", "jpg", "webp", crate::animation::EXTENSION];
+
+/// Whether `path` names a gallery image by its extension.
+pub fn is_image_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|s| s.to_str())
+        .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext))
+}
+
+/// Whether `path` is an animated lolcommit.
+pub fn is_animated(path: &Path) -> bool {
+    path.extension().and_then(|s| s.to_str()) == Some(crate::animation::EXTENSION)
+}
+
+pub(crate) fn is_png(path: &Path) -> bool {
+    path.extension().and_then(|s| s.to_str()) == Some("png")
+}
+
+/// The JSON file beside a JPEG, WebP or GIF image holding its metadata, which those formats
+/// can't carry the way PNG's iTXt chunks do.
+pub fn sidecar_path(path: &Path) -> PathBuf {
+    path.with_extension("json")
+}
+
+/// Contents of the [`JSON_KEY`] chunk.
+#[derive(Serialize, Deserialize)]
+struct VersionedMetadata<M> {
+    schema_version: u32,
+    #[serde(flatten)]
+    metadata: M,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Sidecar {
+    #[serde(flatten)]
+    metadata: CommitMetadata,
+    #[serde(default, skip_serializing_if = "Overrides::is_empty")]
+    processing_overrides: Overrides,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    exposure_corrected: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    burned_in_chyron: Option<bool>,
+}
+
+/// How an upload was processed, recorded alongside its metadata.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProcessingInfo {
+    pub overrides: Overrides,
+    /// Whether it was brightened for being taken in low light.
+    pub exposure_corrected: bool,
+    /// The upload's choice of whether the server burns the chyron in, over the config's,
+    /// kept for reprocessing. `false` when the client burned it in itself.
+    pub burned_in_chyron: Option<bool>,
+}
+
+/// Save `image` as `dir/filename` atomically in the format its extension names,
+/// returning the final path. PNGs get the metadata embedded; JPEG and WebP images get a
+/// sidecar, written first so the image is never listed without it.
+pub fn save_gallery_image(
+    dir: &Path,
+    filename: &str,
+    image: &DynamicImage,
+    metadata: &CommitMetadata,
+    processing: &ProcessingInfo,
+    jpeg_quality: u8,
+) -> Result<PathBuf> {
+    let path = dir.join(filename);
+    let format = match path.extension().and_then(|s| s.to_str()) {
+        Some("jpg") => ImageFormat::Jpeg,
+        Some("webp") => ImageFormat::WebP,
+        Some(crate::animation::EXTENSION) => {
+            return save_gallery_animation(
+                dir,
+                filename,
+                std::slice::from_ref(image),
+                crate::animation::DEFAULT_FRAME_DELAY_MS,
+                metadata,
+                processing,
+            );
+        }
+        _ => {
+            return crate::storage::atomic_save(dir, filename, |temp_path| {
+                save_png_with_processing_info(image, temp_path, metadata, processing)
+            });
+        }
+    };
+
+    write_sidecar(&path, metadata, processing)?;
+    crate::storage::atomic_save(dir, filename, |temp_path| {
+        let mut writer = BufWriter::new(File::create(temp_path)?);
+        let rgb_image = image.to_rgb8();
+        match format {
+            ImageFormat::Jpeg => {
+                JpegEncoder::new_with_quality(&mut writer, jpeg_quality.clamp(1, 100)).write_image(
+                    &rgb_image,
+                    rgb_image.width(),
+                    rgb_image.height(),
+                    ExtendedColorType::Rgb8,
+                )?
+            }
+            _ => WebPEncoder::new_lossless(&mut writer).write_image(
+                &rgb_image,
+                rgb_image.width(),
+                rgb_image.height(),
+                ExtendedColorType::Rgb8,
+            )?,
+        }
+        writer.flush()?;
+        Ok(())
+    })
+}
+
+/// Save `frames` as the animated GIF `dir/filename` atomically, each shown for
+/// `delay_ms`, with its metadata in a sidecar written first.
+pub fn save_gallery_animation(
+    dir: &Path,
+    filename: &str,
+    frames: &[DynamicImage],
+    delay_ms: u64,
+    metadata: &CommitMetadata,
+    processing: &ProcessingInfo,
+) -> Result<PathBuf> {
+    write_sidecar(&dir.join(filename), metadata, processing)?;
+    crate::storage::atomic_save(dir, filename, |temp_path| {
+        let mut writer = BufWriter::new(File::create(temp_path)?);
+        crate::animation::write_gif(&mut writer, frames, delay_ms)?;
+        writer.flush()?;
+        Ok(())
+    })
+}
+
+/// Record the metadata of the JPEG, WebP or GIF image at `path` in its sidecar.
+pub fn write_sidecar(
+    path: &Path,
+    metadata: &CommitMetadata,
+    processing: &ProcessingInfo,
+) -> Result {
+    let sidecar = sidecar_path(path);
+    let (Some(dir), Some(name)) = (
+        sidecar.parent(),
+        sidecar.file_name().and_then(|s| s.to_str()),
+    ) else {
+        return Err(std::io::Error::other(format!("invalid image path {}", path.display())).into());
+    };
+    let json = serde_json::to_vec_pretty(&Sidecar {
+        metadata: metadata.clone(),
+        processing_overrides: processing.overrides.clone(),
+        exposure_corrected: processing.exposure_corrected,
+        burned_in_chyron: processing.burned_in_chyron,
+    })?;
+    crate::storage::atomic_write(dir, name, json)?;
+    Ok(())
+}
+
+fn read_sidecar(path: &Path) -> Result<Option<Sidecar>> {
+    match std::fs::read(sidecar_path(path)) {
+        Ok(json) => Ok(Some(serde_json::from_slice(&json)?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Metadata of a gallery image in any of the [`IMAGE_EXTENSIONS`] formats: embedded in a
+/// PNG, otherwise from its sidecar. `None` when it has none; an error when the image
+/// itself can't be read.
+pub fn read_metadata<P: AsRef<Path>>(path: P) -> Result<Option<CommitMetadata>> {
+    let path = path.as_ref();
+    if is_png(path) {
+        return read_png_metadata(path);
+    }
+
+    // Check the image is readable, as decoding a PNG's chunks does
+    ImageReader::open(path)?
+        .with_guessed_format()?
+        .into_dimensions()?;
+    Ok(read_sidecar(path)?.map(|sidecar| sidecar.metadata))
+}
 
 pub fn save_png_with_metadata<P: AsRef<Path>>(
     image: &DynamicImage,
     path: P,
     metadata: &CommitMetadata,
+) -> Result {
+    save_png_with_processing_info(image, path, metadata, &ProcessingInfo::default())
+}
+
+/// Like [`save_png_with_metadata`], also recording the overrides the upload was
+/// processed with (omitted when empty) and whether it was exposure corrected.
+pub fn save_png_with_processing_info<P: AsRef<Path>>(
+    image: &DynamicImage,
+    path: P,
+    metadata: &CommitMetadata,
+    processing: &ProcessingInfo,
 ) -> Result {
     let file = File::create(path.as_ref())?;
     let writer = BufWriter::new(file);
@@ -37,6 +251,21 @@ pub fn save_png_with_metadata<P: AsRef<Path>>(
     )?;
     encoder.add_itxt_chunk("lolcommit:Repo".to_string(), metadata.repo_name.clone())?;
     encoder.add_itxt_chunk("lolcommit:Branch".to_string(), metadata.branch_name.clone())?;
+    if !metadata.author_name.is_empty() {
+        encoder.add_itxt_chunk("lolcommit:Author".to_string(), metadata.author_name.clone())?;
+    }
+    if !metadata.author_email.is_empty() {
+        encoder.add_itxt_chunk("lolcommit:Email".to_string(), metadata.author_email.clone())?;
+    }
+    if metadata.breaking {
+        encoder.add_itxt_chunk("lolcommit:Breaking".to_string(), "true".to_string())?;
+    }
+    if !metadata.co_authors.is_empty() {
+        encoder.add_itxt_chunk(
+            "lolcommit:Co_authors".to_string(),
+            serde_json::to_string(&metadata.co_authors)?,
+        )?;
+    }
     encoder.add_itxt_chunk("lolcommit:Diff".to_string(), metadata.diff_stats_string())?;
     encoder.add_itxt_chunk(
         "lolcommit:Files_changed".to_string(),
@@ -51,6 +280,27 @@ pub fn save_png_with_metadata<P: AsRef<Path>>(
         metadata.stats.deletions.to_string(),
     )?;
 
+    encoder.add_itxt_chunk(
+        JSON_KEY.to_string(),
+        serde_json::to_string(&VersionedMetadata {
+            schema_version: SCHEMA_VERSION,
+            metadata,
+        })?,
+    )?;
+
+    if !processing.overrides.is_empty() {
+        encoder.add_itxt_chunk(
+            OVERRIDES_KEY.to_string(),
+            serde_json::to_string(&processing.overrides)?,
+        )?;
+    }
+    if processing.exposure_corrected {
+        encoder.add_itxt_chunk(CORRECTED_KEY.to_string(), "true".to_string())?;
+    }
+    if let Some(burned_in_chyron) = processing.burned_in_chyron {
+        encoder.add_itxt_chunk(CHYRON_KEY.to_string(), burned_in_chyron.to_string())?;
+    }
+
     let mut writer = encoder.write_header()?;
     writer.write_image_data(&rgb_image)?;
 
@@ -66,15 +316,8 @@ fn remove_key(chunks: &mut HashMap<&str, String>, new_key: &str, old_key: &str)
         .unwrap_or_default()
 }
 
-pub fn read_png_metadata<P: AsRef<Path>>(path: P) -> Result<Option<CommitMetadata>> {
-    let file = File::open(path.as_ref())?;
-    let reader = std::io::BufReader::new(file);
-    let decoder = png::Decoder::new(reader);
-    let reader = decoder.read_info()?;
-
-    let info = reader.info();
-
-    // Build HashMaps for O(1) lookup - tEXt first, then iTXt overwrites (iTXt takes priority)
+/// Text chunks of a PNG by keyword, tEXt first, then iTXt overwrites (iTXt takes priority).
+fn text_chunks<'a>(info: &'a png::Info<'_>) -> HashMap<&'a str, String> {
     let mut chunks: HashMap<&str, String> = info
         .uncompressed_latin1_text
         .iter()
@@ -86,16 +329,70 @@ pub fn read_png_metadata<P: AsRef<Path>>(path: P) -> Result<Option<CommitMetadat
             chunks.insert(&chunk.keyword, text);
         }
     }
+    chunks
+}
+
+fn read_png_info<P: AsRef<Path>>(path: P) -> Result<png::Reader<std::io::BufReader<File>>> {
+    let file = File::open(path.as_ref())?;
+    let decoder = png::Decoder::new(std::io::BufReader::new(file));
+    Ok(decoder.read_info()?)
+}
+
+/// Width and height of an image, read from its header without decoding it.
+pub fn read_dimensions<P: AsRef<Path>>(path: P) -> Result<(u32, u32)> {
+    Ok(ImageReader::open(path)?
+        .with_guessed_format()?
+        .into_dimensions()?)
+}
+
+/// Every text chunk embedded in a gallery image, for debugging what was recorded.
+/// Empty for formats that keep their metadata in a sidecar.
+pub fn read_text_chunks<P: AsRef<Path>>(path: P) -> Result<BTreeMap<String, String>> {
+    if !is_png(path.as_ref()) {
+        return Ok(BTreeMap::new());
+    }
+    let reader = read_png_info(path)?;
+    Ok(text_chunks(reader.info())
+        .into_iter()
+        .map(|(keyword, text)| (keyword.to_string(), text))
+        .collect())
+}
+
+pub fn read_png_metadata<P: AsRef<Path>>(path: P) -> Result<Option<CommitMetadata>> {
+    let reader = read_png_info(path)?;
+    let mut chunks = text_chunks(reader.info());
 
     tracing::debug!(?chunks, "Loaded PNG metadata chunks");
 
+    if let Some(metadata) = chunks.get(JSON_KEY).and_then(|json| parse_json_chunk(json)) {
+        return Ok(Some(metadata));
+    }
+
     let revision = remove_key(&mut chunks, "lolcommit:Revision", "lolcommit:revision");
     let message = remove_key(&mut chunks, "lolcommit:Message", "lolcommit:message");
-    let commit_type = remove_key(&mut chunks, "lolcommit:Type", "lolcommit:type");
+    let mut commit_type = remove_key(&mut chunks, "lolcommit:Type", "lolcommit:type");
     let scope = remove_key(&mut chunks, "lolcommit:Scope", "lolcommit:scope");
     let timestamp = remove_key(&mut chunks, "lolcommit:Timestamp", "lolcommit:timestamp");
     let repo_name = remove_key(&mut chunks, "lolcommit:Repo", "lolcommit:repo");
     let branch_name = remove_key(&mut chunks, "lolcommit:Branch", "lolcommit:branch");
+    let author_name = remove_key(&mut chunks, "lolcommit:Author", "lolcommit:author");
+    let author_email = remove_key(&mut chunks, "lolcommit:Email", "lolcommit:email");
+    let mut breaking =
+        remove_key(&mut chunks, "lolcommit:Breaking", "lolcommit:breaking") == "true";
+    // Images saved before breaking changes were recorded kept the `!` in the type
+    if let Some(stripped) = commit_type.strip_suffix('!') {
+        commit_type = stripped.to_string();
+        breaking = true;
+    }
+    let co_authors = remove_key(&mut chunks, "lolcommit:Co_authors", "lolcommit:co_authors");
+    let co_authors = if co_authors.is_empty() {
+        Vec::new()
+    } else {
+        serde_json::from_str(&co_authors).unwrap_or_else(|e| {
+            tracing::warn!(error = %e, "Ignoring malformed co-authors metadata");
+            Vec::new()
+        })
+    };
     let files_changed = remove_key(
         &mut chunks,
         "lolcommit:Files_changed",
@@ -122,6 +419,10 @@ pub fn read_png_metadata<P: AsRef<Path>>(path: P) -> Result<Option<CommitMetadat
             timestamp,
             repo_name,
             branch_name,
+            author_name,
+            author_email,
+            breaking,
+            co_authors,
             stats: DiffStats {
                 files_changed,
                 insertions,
@@ -133,34 +434,168 @@ pub fn read_png_metadata<P: AsRef<Path>>(path: P) -> Result<Option<CommitMetadat
     }
 }
 
+/// The metadata in a [`JSON_KEY`] chunk, `None` when it can't be used and the individual
+/// chunks should be read instead.
+fn parse_json_chunk(json: &str) -> Option<CommitMetadata> {
+    let value: serde_json::Value = serde_json::from_str(json)
+        .inspect_err(|e| tracing::warn!(error = %e, "Ignoring malformed JSON metadata chunk"))
+        .ok()?;
+    let version = value.get("schema_version").and_then(|v| v.as_u64());
+    match version {
+        Some(version) if version == u64::from(SCHEMA_VERSION) => serde_json::from_value::<
+            VersionedMetadata<CommitMetadata>,
+        >(value)
+        .inspect_err(|e| tracing::warn!(error = %e, "Ignoring malformed JSON metadata chunk"))
+        .ok()
+        .map(|versioned| versioned.metadata),
+        _ => {
+            tracing::warn!(
+                ?version,
+                supported = SCHEMA_VERSION,
+                "Ignoring JSON metadata chunk of an unknown schema version"
+            );
+            None
+        }
+    }
+}
+
+/// Read how a gallery image was processed, the default if nothing was recorded.
+pub fn read_processing_info<P: AsRef<Path>>(path: P) -> Result<ProcessingInfo> {
+    if !is_png(path.as_ref()) {
+        return Ok(read_sidecar(path.as_ref())?
+            .map(|sidecar| ProcessingInfo {
+                overrides: sidecar.processing_overrides,
+                exposure_corrected: sidecar.exposure_corrected,
+                burned_in_chyron: sidecar.burned_in_chyron,
+            })
+            .unwrap_or_default());
+    }
+
+    let file = File::open(path.as_ref())?;
+    let reader = png::Decoder::new(std::io::BufReader::new(file)).read_info()?;
+
+    let mut processing = ProcessingInfo::default();
+    for chunk in &reader.info().utf8_text {
+        match chunk.keyword.as_str() {
+            OVERRIDES_KEY => processing.overrides = serde_json::from_str(&chunk.get_text()?)?,
+            CORRECTED_KEY => processing.exposure_corrected = chunk.get_text()? == "true",
+            CHYRON_KEY => processing.burned_in_chyron = chunk.get_text()?.parse().ok(),
+            _ => {}
+        }
+    }
+    Ok(processing)
+}
+
 pub fn parse_image_file(path: &Path) -> Option<CommitMetadata> {
     let filename = path.file_name()?.to_str()?;
 
-    // Try to read metadata from PNG file first
-    if let Ok(Some(mut metadata)) = read_png_metadata(path) {
-        tracing::debug!(filename, "Read metadata from PNG");
+    // Try to read embedded or sidecar metadata first
+    if let Ok(Some(mut metadata)) = read_metadata(path) {
+        tracing::debug!(filename, "Read metadata from image");
         metadata.path = path.to_path_buf();
         return Some(metadata);
     }
 
     // Fallback: parse filename for old images without metadata
-    // Expected format: {repo_name}-{timestamp}-{commit_sha}.png
-    // timestamp format: %Y%m%d-%H%M%S
     tracing::debug!(filename, "Falling back to filename parsing");
-    let name = filename.strip_suffix(".png")?;
-    let parts: Vec<&str> = name.rsplitn(3, '-').collect();
+    parse_filename(path)
+}
+
+/// When an uploaded capture was committed. Clients send the commit time as RFC 3339;
+/// older clients sent their wall-clock time in [`crate::TIMESTAMP_FORMAT`], read as the
+/// server's local time. Anything unparseable is treated as taken now.
+pub fn taken_at(timestamp: &str) -> DateTime<FixedOffset> {
+    crate::parse_timestamp(timestamp).unwrap_or_else(|| {
+        tracing::debug!(
+            timestamp,
+            "Unparseable upload timestamp, using the current time"
+        );
+        Local::now().fixed_offset()
+    })
+}
+
+/// Longest repo name or revision put in a filename. SHA-256 object names are 64 hex
+/// digits.
+const MAX_FILENAME_COMPONENT_LEN: usize = 64;
+
+/// Filename for a capture of `revision` taken at `taken`, in the format [`parse_filename`]
+/// reads, with the given extension. The filename has no room for an offset, so it carries
+/// the server's local time of the same instant. Both come from clients, so the repo name
+/// goes through [`filename_repo_name`] and the revision has to be a hex commit hash.
+pub fn output_filename(
+    repo_name: &str,
+    revision: &str,
+    taken: DateTime<FixedOffset>,
+    extension: &str,
+) -> Result<String> {
+    let safe_repo_name = filename_repo_name(repo_name);
+    if safe_repo_name.is_empty() {
+        return Err(Error::UnsafeFilename {
+            field: "repo_name",
+            value: repo_name.to_string(),
+        });
+    }
+    if !is_revision(revision) {
+        return Err(Error::UnsafeFilename {
+            field: "revision",
+            value: revision.to_string(),
+        });
+    }
+    let timestamp = taken.with_timezone(&Local).format("%Y%m%d-%H%M%S");
+    Ok(format!(
+        "{}-{}-{}.{}",
+        safe_repo_name, timestamp, revision, extension
+    ))
+}
+
+/// `repo_name` as it appears in filenames: anything but `[A-Za-z0-9._-]` replaced with
+/// `_`, leading dots dropped so it can be neither `..` nor hidden, and cut to 64
+/// characters. Empty when nothing is left.
+pub fn filename_repo_name(repo_name: &str) -> String {
+    repo_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .skip_while(|&c| c == '.')
+        .take(MAX_FILENAME_COMPONENT_LEN)
+        .collect()
+}
+
+/// Whether `revision` is a commit hash, full or abbreviated.
+pub fn is_revision(revision: &str) -> bool {
+    !revision.is_empty()
+        && revision.len() <= MAX_FILENAME_COMPONENT_LEN
+        && revision.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Derive metadata from the filename alone, for images without embedded chunks.
+/// Expected format: {repo_name}-{timestamp}-{commit_sha}.png (or another of
+/// [`IMAGE_EXTENSIONS`])
+/// timestamp format: %Y%m%d-%H%M%S
+pub fn parse_filename(path: &Path) -> Option<CommitMetadata> {
+    if !is_image_file(path) {
+        return None;
+    }
+    let name = path.file_stem()?.to_str()?;
+    // The timestamp has a '-' of its own: {repo_name}-{date}-{time}-{commit_sha}
+    let parts: Vec<&str> = name.rsplitn(4, '-').collect();
 
-    if parts.len() != 3 {
+    if parts.len() != 4 {
         return None;
     }
 
     let revision = parts[0].to_string();
-    let time_part = parts[1];
-    let repo_name = parts[2].to_string();
+    let time_part = format!("{}-{}", parts[2], parts[1]);
+    let repo_name = parts[3].to_string();
 
     // Parse timestamp for display
     let timestamp =
-        parse_timestamp(time_part).unwrap_or_else(|| format!("{}-{}", repo_name, time_part));
+        parse_timestamp(&time_part).unwrap_or_else(|| format!("{}-{}", repo_name, time_part));
 
     Some(CommitMetadata {
         path: path.to_path_buf(),
@@ -171,6 +606,10 @@ pub fn parse_image_file(path: &Path) -> Option<CommitMetadata> {
         timestamp,
         repo_name,
         branch_name: String::new(),
+        author_name: String::new(),
+        author_email: String::new(),
+        breaking: false,
+        co_authors: Vec::new(),
         stats: DiffStats {
             files_changed: 0,
             insertions: 0,
@@ -202,6 +641,7 @@ mod tests {
     use super::*;
     use crate::error::Result;
     use crate::git::DiffStats;
+    use test_case::test_case;
 
     #[test]
     fn test_round_trip_new_keys() -> Result {
@@ -212,12 +652,16 @@ mod tests {
         let metadata = CommitMetadata {
             path: std::path::PathBuf::new(),
             revision: "abc1234".to_owned(),
-            message: "feat: add something".to_owned(),
+            message: "feat(core)!: add something".to_owned(),
             commit_type: "feat".to_owned(),
             scope: "core".to_owned(),
             timestamp: "2024-01-15 12:34:56".to_owned(),
             repo_name: "my-repo".to_owned(),
             branch_name: "main".to_owned(),
+            author_name: "Zoë Example".to_owned(),
+            author_email: "zoe@example.com".to_owned(),
+            breaking: true,
+            co_authors: vec!["Sam Pair <sam@example.com>".to_owned()],
             stats: DiffStats {
                 files_changed: 3,
                 insertions: 42,
@@ -236,6 +680,10 @@ mod tests {
         assert_eq!(read_back.timestamp, metadata.timestamp);
         assert_eq!(read_back.repo_name, metadata.repo_name);
         assert_eq!(read_back.branch_name, metadata.branch_name);
+        assert_eq!(read_back.author_name, metadata.author_name);
+        assert_eq!(read_back.author_email, metadata.author_email);
+        assert!(read_back.breaking);
+        assert_eq!(read_back.co_authors, metadata.co_authors);
         assert_eq!(read_back.stats.files_changed, metadata.stats.files_changed);
         assert_eq!(read_back.stats.insertions, metadata.stats.insertions);
         assert_eq!(read_back.stats.deletions, metadata.stats.deletions);
@@ -243,6 +691,112 @@ mod tests {
         Ok(())
     }
 
+    fn sample_metadata(message: String) -> CommitMetadata {
+        let mut metadata = parse_filename(Path::new("repo-20240115-123456-abc1234.png")).unwrap();
+        metadata.path = std::path::PathBuf::new();
+        metadata.message = message;
+        metadata.commit_type = "feat".to_owned();
+        metadata.author_name = "Zoë Example".to_owned();
+        metadata
+    }
+
+    #[test_case("feat: 日本語のコミット 🎉\n\nÜnïcödé bödy, with \"quotes\" and \\ slashes" ; "unicode")]
+    #[test_case(&"a very long body line\n".repeat(200_000) ; "long body")]
+    fn test_json_chunk_round_trip(message: &str) -> Result {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("test.png");
+        let image = image::DynamicImage::ImageRgba8(image::RgbaImage::new(1, 1));
+        let metadata = sample_metadata(message.to_owned());
+
+        save_png_with_metadata(&image, &path, &metadata)?;
+
+        let chunks = read_text_chunks(&path)?;
+        let json: serde_json::Value = serde_json::from_str(&chunks[JSON_KEY])?;
+        assert_eq!(json["schema_version"], SCHEMA_VERSION);
+        assert_eq!(json["message"], message);
+        assert!(json.get("path").is_none());
+
+        let read_back = read_png_metadata(&path)?.expect("metadata should be present");
+        assert_eq!(read_back.message, message);
+        assert_eq!(read_back.author_name, "Zoë Example");
+        assert_eq!(read_back.revision, "abc1234");
+        Ok(())
+    }
+
+    fn write_chunks(path: &Path, chunks: &[(&str, String)]) -> Result {
+        let mut encoder = Encoder::new(BufWriter::new(File::create(path)?), 1, 1);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        for (keyword, text) in chunks {
+            encoder.add_itxt_chunk(keyword.to_string(), text.clone())?;
+        }
+        encoder.write_header()?.write_image_data(&[0, 0, 0, 0])?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_chunk_is_preferred_over_keys() -> Result {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("both.png");
+        let json = serde_json::to_string(&VersionedMetadata {
+            schema_version: SCHEMA_VERSION,
+            metadata: &sample_metadata("feat: from json".to_owned()),
+        })?;
+        write_chunks(
+            &path,
+            &[
+                ("lolcommit:Revision", "def5678".to_owned()),
+                ("lolcommit:Message", "feat: from keys".to_owned()),
+                (JSON_KEY, json),
+            ],
+        )?;
+
+        let read_back = read_png_metadata(&path)?.expect("metadata should be present");
+        assert_eq!(read_back.message, "feat: from json");
+        assert_eq!(read_back.revision, "abc1234");
+        Ok(())
+    }
+
+    #[test_case(r#"{"schema_version": 99, "revision": "abc1234"}"# ; "newer schema")]
+    #[test_case(r#"{"revision": "abc1234"}"# ; "unversioned")]
+    #[test_case("not json" ; "malformed")]
+    fn test_unusable_json_chunk_falls_back_to_keys(json: &str) -> Result {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("fallback.png");
+        write_chunks(
+            &path,
+            &[
+                ("lolcommit:Revision", "def5678".to_owned()),
+                ("lolcommit:Message", "feat: from keys".to_owned()),
+                (JSON_KEY, json.to_owned()),
+            ],
+        )?;
+
+        let read_back = read_png_metadata(&path)?.expect("metadata should be present");
+        assert_eq!(read_back.message, "feat: from keys");
+        assert_eq!(read_back.revision, "def5678");
+        Ok(())
+    }
+
+    #[test]
+    fn test_reads_old_bang_commit_type_as_breaking() -> Result {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("old_breaking.png");
+
+        let mut encoder = Encoder::new(BufWriter::new(File::create(&path)?), 1, 1);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.add_itxt_chunk("lolcommit:Revision".to_owned(), "def5678".to_owned())?;
+        encoder.add_itxt_chunk("lolcommit:Type".to_owned(), "feat!".to_owned())?;
+        encoder.write_header()?.write_image_data(&[0, 0, 0, 0])?;
+
+        let read_back = read_png_metadata(&path)?.expect("metadata should be present");
+
+        assert_eq!(read_back.commit_type, "feat");
+        assert!(read_back.breaking);
+        Ok(())
+    }
+
     #[test]
     fn test_reads_old_lowercase_keys() -> Result {
         let dir = tempfile::tempdir()?;
@@ -277,6 +831,11 @@ mod tests {
         let read_back = read_back.expect("metadata should be present for old-style keys");
 
         assert_eq!(read_back.revision, "def5678");
+        // Written before authors were recorded
+        assert_eq!(read_back.author_name, "");
+        assert_eq!(read_back.author_email, "");
+        assert!(!read_back.breaking);
+        assert!(read_back.co_authors.is_empty());
         assert_eq!(read_back.message, "fix: old style commit");
         assert_eq!(read_back.commit_type, "fix");
         assert_eq!(read_back.repo_name, "old-repo");
@@ -288,4 +847,243 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_processing_overrides_round_trip() -> Result {
+        let dir = tempfile::tempdir()?;
+        let image = image::DynamicImage::ImageRgba8(image::RgbaImage::new(1, 1));
+        let metadata = parse_filename(Path::new("repo-20240115-123456-abc1234.png")).unwrap();
+
+        let plain = dir.path().join("plain.png");
+        save_png_with_metadata(&image, &plain, &metadata)?;
+        assert_eq!(read_processing_info(&plain)?, ProcessingInfo::default());
+
+        let overridden = dir.path().join("overridden.png");
+        let overrides = Overrides::from([
+            ("background".to_string(), "party".to_string()),
+            ("center_person".to_string(), "false".to_string()),
+        ]);
+        let processing = ProcessingInfo {
+            overrides,
+            exposure_corrected: true,
+            burned_in_chyron: Some(false),
+        };
+        save_png_with_processing_info(&image, &overridden, &metadata, &processing)?;
+
+        assert_eq!(read_processing_info(&overridden)?, processing);
+        // The commit metadata itself is unaffected
+        let read_back = read_png_metadata(&overridden)?.expect("metadata should be present");
+        assert_eq!(read_back.revision, "abc1234");
+
+        assert_eq!(read_text_chunks(&overridden)?[CORRECTED_KEY], "true");
+        assert!(!read_text_chunks(&plain)?.contains_key(CORRECTED_KEY));
+        Ok(())
+    }
+
+    #[test_case("jpg", ImageFormat::Jpeg ; "jpeg")]
+    #[test_case("webp", ImageFormat::WebP ; "webp")]
+    #[test_case("gif", ImageFormat::Gif ; "gif")]
+    fn test_gallery_image_with_sidecar_round_trip(extension: &str, format: ImageFormat) -> Result {
+        let dir = tempfile::tempdir()?;
+        let image = image::DynamicImage::new_rgb8(16, 12);
+        let filename = format!("repo-20240115-123456-abc1234.{extension}");
+        let mut metadata = parse_filename(Path::new(&filename)).unwrap();
+        metadata.message = "feat: smaller gallery".to_owned();
+        let processing = ProcessingInfo {
+            overrides: Overrides::from([("background".to_string(), "party".to_string())]),
+            exposure_corrected: true,
+            burned_in_chyron: None,
+        };
+
+        let path = save_gallery_image(dir.path(), &filename, &image, &metadata, &processing, 80)?;
+
+        assert_eq!(path, dir.path().join(&filename));
+        assert_eq!(
+            ImageReader::open(&path)?.with_guessed_format()?.format(),
+            Some(format)
+        );
+        assert!(sidecar_path(&path).exists());
+        let read_back = parse_image_file(&path).expect("metadata should be present");
+        assert_eq!(read_back.message, "feat: smaller gallery");
+        assert_eq!(read_back.path, path);
+        assert_eq!(read_processing_info(&path)?, processing);
+        Ok(())
+    }
+
+    #[test]
+    fn test_gallery_animation_is_listed_as_animated() -> Result {
+        let dir = tempfile::tempdir()?;
+        let filename = "repo-20240115-123456-abc1234.gif";
+        let metadata = parse_filename(Path::new(filename)).unwrap();
+        let frames = vec![image::DynamicImage::new_rgb8(8, 6); 3];
+
+        let path = save_gallery_animation(
+            dir.path(),
+            filename,
+            &frames,
+            250,
+            &metadata,
+            &ProcessingInfo::default(),
+        )?;
+
+        assert!(is_animated(&path));
+        assert!(is_image_file(&path));
+        assert!(!is_animated(&path.with_extension("png")));
+        let read_back = parse_image_file(&path).expect("metadata should be present");
+        assert_eq!(read_back.revision, "abc1234");
+        Ok(())
+    }
+
+    #[test]
+    fn test_gallery_png_embeds_metadata() -> Result {
+        let dir = tempfile::tempdir()?;
+        let filename = "repo-20240115-123456-abc1234.png";
+        let metadata = parse_filename(Path::new(filename)).unwrap();
+
+        let path = save_gallery_image(
+            dir.path(),
+            filename,
+            &image::DynamicImage::new_rgb8(4, 3),
+            &metadata,
+            &ProcessingInfo::default(),
+            80,
+        )?;
+
+        assert!(!sidecar_path(&path).exists());
+        assert!(read_png_metadata(&path)?.is_some());
+        let chunks = read_text_chunks(&path)?;
+        assert_eq!(chunks["lolcommit:Revision"], "abc1234");
+        assert_eq!(chunks["lolcommit:Repo"], "repo");
+        Ok(())
+    }
+
+    #[test]
+    fn test_image_without_sidecar_falls_back_to_filename() -> Result {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("repo-20240115-123456-abc1234.jpg");
+        image::DynamicImage::new_rgb8(4, 3).save(&path)?;
+
+        assert!(read_metadata(&path)?.is_none());
+        assert!(read_text_chunks(&path)?.is_empty());
+        assert_eq!(read_dimensions(&path)?, (4, 3));
+        let parsed = parse_image_file(&path).expect("filename should parse");
+        assert_eq!(parsed.revision, "abc1234");
+        Ok(())
+    }
+
+    #[test_case("png" ; "png")]
+    #[test_case("jpg" ; "jpeg")]
+    #[test_case("webp" ; "webp")]
+    fn test_output_filename_parses_back(extension: &str) {
+        let taken = taken_at("2024-01-15 12:34:56");
+        let filename = output_filename("my-repo", "abc1234", taken, extension).unwrap();
+
+        assert_eq!(
+            filename,
+            format!("my-repo-20240115-123456-abc1234.{extension}")
+        );
+        let parsed = parse_filename(Path::new(&filename)).expect("filename should parse");
+        assert_eq!(parsed.repo_name, "my-repo");
+        assert_eq!(parsed.revision, "abc1234");
+        assert_eq!(parsed.timestamp, "2024-01-15 12:34:56");
+    }
+
+    #[test_case("../../etc/cron.d/x", "_.._etc_cron.d_x" ; "traversal")]
+    #[test_case("..", "" ; "dots")]
+    #[test_case(".hidden", "hidden" ; "dotfile")]
+    #[test_case("my repo", "my_repo" ; "space")]
+    #[test_case("café-app", "caf_-app" ; "unicode")]
+    #[test_case("a\\b", "a_b" ; "backslash")]
+    fn test_filename_repo_name(repo_name: &str, expected: &str) {
+        assert_eq!(filename_repo_name(repo_name), expected);
+    }
+
+    #[test_case("..", "abc1234", "repo_name" ; "nothing left of repo name")]
+    #[test_case("repo", "", "revision" ; "empty revision")]
+    #[test_case("repo", "../abc", "revision" ; "path in revision")]
+    #[test_case("repo", "main", "revision" ; "not hex")]
+    fn test_output_filename_rejects_unsafe_components(
+        repo_name: &str,
+        revision: &str,
+        expected: &str,
+    ) {
+        let taken = taken_at("2024-01-15 12:34:56");
+
+        let result = output_filename(repo_name, revision, taken, "png");
+
+        assert!(
+            matches!(&result, Err(Error::UnsafeFilename { field, .. }) if *field == expected),
+            "{result:?}"
+        );
+    }
+
+    #[test]
+    fn test_sanitized_repo_name_parses_back() -> Result {
+        let dir = tempfile::tempdir()?;
+        let taken = taken_at("2024-01-15 12:34:56");
+        let filename = output_filename("../my repo/ünï", "abc1234", taken, "png")?;
+        let path = dir.path().join(&filename);
+        DynamicImage::new_rgb8(2, 2).save(&path)?;
+
+        assert_eq!(Path::new(&filename).parent(), Some(Path::new("")));
+        let parsed = parse_image_file(&path).expect("filename should parse");
+        assert_eq!(parsed.repo_name, "_my_repo__n_");
+        assert_eq!(parsed.revision, "abc1234");
+        assert_eq!(parsed.timestamp, "2024-01-15 12:34:56");
+        Ok(())
+    }
+
+    #[test]
+    fn test_taken_at_keeps_the_commit_offset() {
+        let taken = taken_at("2024-01-15T12:34:56+05:00");
+
+        assert_eq!(taken.offset().local_minus_utc(), 5 * 3600);
+        assert_eq!(crate::format_timestamp(taken), "2024-01-15T12:34:56+05:00");
+    }
+
+    #[test]
+    fn test_filename_is_the_same_instant_in_local_time() {
+        let taken = taken_at("2024-01-15T12:34:56+05:00");
+        let filename = output_filename("repo", "abc1234", taken, "png").unwrap();
+
+        let parsed = parse_filename(Path::new(&filename)).expect("filename should parse");
+        assert_eq!(crate::parse_timestamp(&parsed.timestamp), Some(taken));
+    }
+
+    #[test_case("2024-01-15 12:34:56", "2024-01-15 12:34:56" ; "old local format")]
+    #[test_case("2024-01-15T12:34:56+05:00", "2024-01-15T12:34:56+05:00" ; "rfc3339")]
+    #[test_case("2024-01-15T07:34:56Z", "2024-01-15T12:34:56+05:00" ; "same instant in utc")]
+    fn test_parse_timestamp_reads_both_formats(timestamp: &str, same_as: &str) {
+        let expected = chrono::DateTime::parse_from_rfc3339(same_as)
+            .ok()
+            .or_else(|| {
+                let naive =
+                    chrono::NaiveDateTime::parse_from_str(same_as, crate::TIMESTAMP_FORMAT).ok()?;
+                Some(naive.and_local_timezone(Local).earliest()?.fixed_offset())
+            })
+            .unwrap();
+
+        assert_eq!(crate::parse_timestamp(timestamp), Some(expected));
+    }
+
+    #[test_case("" ; "empty")]
+    #[test_case("yesterday" ; "garbage")]
+    fn test_taken_at_falls_back_to_now(timestamp: &str) {
+        let before = Local::now() - chrono::Duration::seconds(1);
+
+        let taken = taken_at(timestamp);
+
+        assert!(taken >= before && taken <= Local::now());
+    }
+
+    #[test]
+    fn test_parse_filename() {
+        let parsed = parse_filename(Path::new("my-repo-20240115-123456-abc1234.png")).unwrap();
+        assert_eq!(parsed.repo_name, "my-repo");
+        assert_eq!(parsed.timestamp, "2024-01-15 12:34:56");
+        assert_eq!(parsed.revision, "abc1234");
+
+        assert!(parse_filename(Path::new("abc1234.png")).is_none());
+        assert!(parse_filename(Path::new("repo-20240115-123456-abc1234.txt")).is_none());
+    }
 }
@@ -1,11 +1,9 @@
 use crate::error::Result;
-use crate::git::CommitMetadata;
-use crate::segmentation;
-use ab_glyph::{FontRef, PxScale};
-use image::{DynamicImage, Rgba};
-use imageproc::drawing::draw_text_mut;
+use crate::segmentation::{SegmentationModel, SegmentationNet};
+use image::{DynamicImage, GrayImage, ImageBuffer, Luma};
+use imageproc::distance_transform::Norm;
 use opencv::core::{CV_32F, Mat, Scalar, Size, Vec3b};
-use opencv::dnn::{DNN_BACKEND_OPENCV, DNN_TARGET_CPU, read_net_from_onnx};
+use opencv::dnn::Net;
 use opencv::imgproc::{COLOR_BGR2RGB, COLOR_RGB2BGR, INTER_LINEAR, cvt_color, resize};
 use opencv::prelude::*;
 use std::env;
@@ -37,74 +35,72 @@ where
     )
 }
 
-/// Format a number with k/M suffix for numbers over 999
-/// Examples: 42 -> "42", 1234 -> "1.2k", 1567890 -> "1.6M"
-fn format_stat_number(n: u32) -> String {
-    if n <= 999 {
-        n.to_string()
-    } else if n < 1_000_000 {
-        let k = n as f32 / 1000.0;
-        format!("{:.1}k", k)
-    } else {
-        let m = n as f32 / 1_000_000.0;
-        format!("{:.1}M", m)
-    }
+/// `background_path` value that turns background replacement off.
+pub const NO_BACKGROUND: &str = "none";
+
+/// Background bundled into the binary, used when the configured one can't be found.
+#[cfg(feature = "embedded-background")]
+const EMBEDDED_BACKGROUND: &[u8] = include_bytes!("../assets/default_background.png");
+
+/// Where the background for compositing comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Background {
+    /// `background_path = "none"`: uploads are stored without replacing the background.
+    Disabled,
+    File(PathBuf),
+    /// The configured background can't be found, the bundled default stands in.
+    Embedded,
+    /// The configured background can't be found and there is no bundled default.
+    Missing(String),
 }
 
-/// Load a font by name using fontconfig and return a FontRef
-///
-/// The font data is leaked to satisfy FontRef's lifetime requirements.
-fn load_font(font_name: &str) -> Result<FontRef<'static>> {
-    let font_path = resolve_font_path(font_name)?;
-    tracing::debug!(font_name = %font_name, font_path = %font_path.display(), "Loading font");
-
-    let font_data = std::fs::read(&font_path).map_err(|e| {
-        std::io::Error::other(format!(
-            "Failed to read font from {}: {}",
-            font_path.display(),
-            e
-        ))
-    })?;
-
-    let font_data_static: &'static [u8] = Box::leak(font_data.into_boxed_slice());
-    let font = FontRef::try_from_slice(font_data_static)
-        .map_err(|e| std::io::Error::other(format!("Failed to parse font: {}", e)))?;
-
-    Ok(font)
-}
-
-/// Resolve font name to font file path using fontconfig
-///
-/// Uses fontconfig to find the font file for the given font name.
-/// Falls back to monospace if the requested font is not found.
-fn resolve_font_path(font_name: &str) -> Result<PathBuf> {
-    let fc = fontconfig::Fontconfig::new()
-        .ok_or_else(|| std::io::Error::other("Failed to initialize fontconfig"))?;
-
-    // Try to find the requested font
-    let font = fc.find(font_name, None);
+impl Background {
+    /// Resolve `background_path`, falling back to the bundled default when enabled.
+    pub fn resolve(path_spec: &str) -> Background {
+        if path_spec.eq_ignore_ascii_case(NO_BACKGROUND) {
+            return Background::Disabled;
+        }
 
-    if let Some(font) = font {
-        let path = &font.path;
-        tracing::debug!(font_name = %font_name, path = %path.display(), "Found font via fontconfig");
-        return Ok(path.clone());
+        match resolve_background_path(path_spec) {
+            Ok(path) => Background::File(path),
+            Err(e) if cfg!(feature = "embedded-background") => {
+                tracing::warn!(
+                    background_path = path_spec,
+                    error = %e,
+                    "Configured background not found, using the built-in default background"
+                );
+                Background::Embedded
+            }
+            Err(e) => Background::Missing(e.to_string()),
+        }
     }
 
-    // Fallback to monospace (universally available)
-    tracing::warn!(font_name = %font_name, "Font not found, trying fallback: monospace");
-    let fallback = fc.find("monospace", None);
-
-    if let Some(font) = fallback {
-        let path = &font.path;
-        tracing::info!(path = %path.display(), "Using fallback font");
-        return Ok(path.clone());
+    /// Short status for the health endpoint.
+    pub fn status(&self) -> &'static str {
+        match self {
+            Background::Disabled => "disabled",
+            Background::File(_) => "configured",
+            Background::Embedded => "embedded_default",
+            Background::Missing(_) => "missing",
+        }
     }
 
-    Err(std::io::Error::other(format!(
-        "Font '{}' not found and monospace fallback unavailable",
-        font_name
-    ))
-    .into())
+    fn load(&self) -> Result<DynamicImage> {
+        match self {
+            Background::File(path) => {
+                tracing::debug!(path = %path.display(), "Loading background image");
+                Ok(image::open(path)?)
+            }
+            #[cfg(feature = "embedded-background")]
+            Background::Embedded => Ok(image::load_from_memory(EMBEDDED_BACKGROUND)?),
+            #[cfg(not(feature = "embedded-background"))]
+            Background::Embedded => {
+                Err(std::io::Error::other("built without the embedded background").into())
+            }
+            Background::Missing(reason) => Err(std::io::Error::other(reason.clone()).into()),
+            Background::Disabled => Err(std::io::Error::other("background disabled").into()),
+        }
+    }
 }
 
 /// Resolve background image path according to XDG Base Directory specification
@@ -112,7 +108,7 @@ fn resolve_font_path(font_name: &str) -> Result<PathBuf> {
 /// If the path starts with '/', treat it as an absolute path.
 /// Otherwise, treat it as a basename and search for {basename}.png in XDG_DATA_DIRS
 /// (typically /usr/local/share:/usr/share) and XDG_DATA_HOME.
-fn resolve_background_path(path_spec: &str) -> Result<PathBuf> {
+pub(crate) fn resolve_background_path(path_spec: &str) -> Result<PathBuf> {
     // If it starts with '/', it's an absolute path
     if path_spec.starts_with('/') {
         let path = PathBuf::from(path_spec);
@@ -176,49 +172,17 @@ fn resolve_background_path(path_spec: &str) -> Result<PathBuf> {
     .into())
 }
 
-pub fn replace_background(
-    config: &crate::config::ServerConfig,
-    image: DynamicImage,
-) -> Result<DynamicImage> {
-    let rgb_image = image.to_rgb8();
-    let (width, height) = rgb_image.dimensions();
-    let image_data = rgb_image.into_raw();
+/// Run the model on a BGR image, retrying once on the CPU when the configured backend
+/// fails. Returns the mask and its side length, the model's input size.
+fn segment(model: &mut SegmentationNet, bgr_mat: &Mat) -> Result<(Vec<f32>, i32)> {
+    let size = model.input_size;
 
-    // Convert Vec<u8> to Vec<Vec3b> for opencv - keep RGB order initially
-    let vec3b_data: Vec<Vec3b> = image_data
-        .chunks_exact(3)
-        .map(|chunk| Vec3b::from([chunk[0], chunk[1], chunk[2]])) // R, G, B as-is
-        .collect();
-
-    let temp_mat = Mat::from_slice(&vec3b_data)?;
-    let rgb_mat = temp_mat.reshape(3, height as i32)?;
-
-    tracing::debug!(
-        "Input RGB mat size: {:?}, type: {}",
-        rgb_mat.size()?,
-        rgb_mat.typ()
-    );
-
-    // Use OpenCV's cvt_color to properly convert RGB to BGR
-    let mut bgr_mat = Mat::default();
-    convert_color(&rgb_mat, &mut bgr_mat, COLOR_RGB2BGR, 0)?;
-
-    tracing::debug!("After RGB->BGR conversion, mat type: {}", bgr_mat.typ());
-
-    // Get segmentation model
-    let model_path = segmentation::get_model_path(&config.models_dir)?;
-    tracing::debug!(path = %model_path.display(), "Loading segmentation model");
-
-    let mut net = read_net_from_onnx(model_path.to_str().unwrap())?;
-    net.set_preferable_backend(DNN_BACKEND_OPENCV)?;
-    net.set_preferable_target(DNN_TARGET_CPU)?;
-
-    // Prepare input: resize to 320x320 and normalize for U2Net
+    // Prepare input: resize to the model's input size and normalize for U2Net
     let mut resized = Mat::default();
     resize(
-        &bgr_mat,
+        bgr_mat,
         &mut resized,
-        Size::new(320, 320),
+        Size::new(size, size),
         0.0,
         0.0,
         INTER_LINEAR,
@@ -231,14 +195,39 @@ pub fn replace_background(
     let blob = opencv::dnn::blob_from_image(
         &input_float,
         1.0,
-        Size::new(320, 320),
+        Size::new(size, size),
         Scalar::default(),
         true, // swapRB: true to convert BGR to RGB for model
         false,
         CV_32F,
     )?;
 
-    net.set_input(&blob, "", 1.0, Scalar::default())?;
+    let started = std::time::Instant::now();
+    let mask = match run_segmentation(&mut model.net, &blob, size) {
+        Err(e) if !model.on_cpu() => {
+            tracing::warn!(
+                backend = ?model.backend,
+                target = ?model.target,
+                error = %e,
+                "Segmentation failed on the configured DNN backend, falling back to CPU"
+            );
+            model.fall_back_to_cpu()?;
+            run_segmentation(&mut model.net, &blob, size)
+        }
+        result => result,
+    }?;
+    tracing::info!(
+        backend = ?model.backend,
+        target = ?model.target,
+        duration_ms = started.elapsed().as_millis() as u64,
+        "Segmentation inference finished"
+    );
+    Ok((mask, size))
+}
+
+/// Run U2Net on a `size` x `size` input blob, returning the main mask.
+fn run_segmentation(net: &mut Net, blob: &Mat, size: i32) -> Result<Vec<f32>> {
+    net.set_input(blob, "", 1.0, Scalar::default())?;
 
     // Run inference
     tracing::debug!("Running segmentation inference");
@@ -257,15 +246,23 @@ pub fn replace_background(
         return Err(std::io::Error::other("No output from model").into());
     }
 
-    // Use the first output (main segmentation mask) - shape is [1, 1, 320, 320]
+    // Use the first output (main segmentation mask) - shape is [1, 1, size, size]
     let output = outputs.get(0)?;
     tracing::debug!(shape = ?output.mat_size(), "Output shape");
 
-    // The output is [1, 1, 320, 320], we need to extract the 320x320 data
+    // The output is [1, 1, size, size], we need to extract the size x size data
     // Use data_bytes to get raw bytes, then convert to f32
     let output_bytes = output.data_bytes()?;
-    let mut data_vec = Vec::with_capacity(320 * 320);
-    for i in 0..(320 * 320) {
+    let pixels = (size * size) as usize;
+    if output_bytes.len() < pixels * 4 {
+        return Err(std::io::Error::other(format!(
+            "Model output has {} bytes, expected a {size}x{size} mask",
+            output_bytes.len()
+        ))
+        .into());
+    }
+    let mut data_vec = Vec::with_capacity(pixels);
+    for i in 0..pixels {
         let idx = i * 4;
         let val = f32::from_le_bytes([
             output_bytes[idx],
@@ -275,14 +272,65 @@ pub fn replace_background(
         ]);
         data_vec.push(val);
     }
-    let mask_320 = Mat::new_rows_cols_with_data(320, 320, &data_vec)?.try_clone()?;
+    Ok(data_vec)
+}
 
-    tracing::debug!("Mask 320 type: {}, min/max checking", mask_320.typ());
+/// Segment the person with `model` and composite them onto the configured background.
+/// The image is returned unchanged while no model can be loaded.
+pub fn replace_background(
+    config: &crate::config::ServerConfig,
+    model: &SegmentationModel,
+    image: DynamicImage,
+) -> Result<DynamicImage> {
+    // Resolve before segmenting so a missing background fails fast
+    let background = Background::resolve(&config.background_path);
+    if background == Background::Disabled {
+        tracing::debug!("Background replacement disabled");
+        return Ok(image);
+    }
+    let bg_dynamic = background.load()?;
+
+    let rgb_image = image.to_rgb8();
+    let (width, height) = rgb_image.dimensions();
+    let image_data = rgb_image.into_raw();
+
+    // Convert Vec<u8> to Vec<Vec3b> for opencv - keep RGB order initially
+    let vec3b_data: Vec<Vec3b> = image_data
+        .chunks_exact(3)
+        .map(|chunk| Vec3b::from([chunk[0], chunk[1], chunk[2]])) // R, G, B as-is
+        .collect();
+
+    let temp_mat = Mat::from_slice(&vec3b_data)?;
+    let rgb_mat = temp_mat.reshape(3, height as i32)?;
+
+    tracing::debug!(
+        "Input RGB mat size: {:?}, type: {}",
+        rgb_mat.size()?,
+        rgb_mat.typ()
+    );
+
+    // Use OpenCV's cvt_color to properly convert RGB to BGR
+    let mut bgr_mat = Mat::default();
+    convert_color(&rgb_mat, &mut bgr_mat, COLOR_RGB2BGR, 0)?;
+
+    tracing::debug!("After RGB->BGR conversion, mat type: {}", bgr_mat.typ());
+
+    let Some((data_vec, input_size)) = model.with(|net| segment(net, &bgr_mat)).transpose()? else {
+        tracing::warn!("Segmentation model unavailable, skipping background replacement");
+        return Ok(image);
+    };
+    let mask_small =
+        Mat::new_rows_cols_with_data(input_size, input_size, &data_vec)?.try_clone()?;
+
+    tracing::debug!(
+        "Mask {input_size} type: {}, min/max checking",
+        mask_small.typ()
+    );
 
     // Resize mask back to original size
     let mut mask_full = Mat::default();
     resize(
-        &mask_320,
+        &mask_small,
         &mut mask_full,
         Size::new(width as i32, height as i32),
         0.0,
@@ -306,53 +354,15 @@ pub fn replace_background(
         })
         .collect();
 
-    // Calculate center of mass of the mask to find person's center
-    let mut sum_x = 0.0_f32;
-    let mut sum_y = 0.0_f32;
-    let mut total_weight = 0.0_f32;
-
-    for y in 0..height {
-        for x in 0..width {
-            let idx = (y * width + x) as usize;
-            let weight = mask_values[idx];
-            if weight > 0.1 {
-                // Only consider pixels that are likely person
-                sum_x += x as f32 * weight;
-                sum_y += y as f32 * weight;
-                total_weight += weight;
-            }
-        }
-    }
-
-    let (person_center_x, person_center_y) = if total_weight > 0.0 {
-        (sum_x / total_weight, sum_y / total_weight)
-    } else {
-        (width as f32 / 2.0, height as f32 / 2.0) // Default to image center if no person detected
-    };
-
-    let image_center_x = width as f32 / 2.0;
-    let image_center_y = height as f32 / 2.0;
-
-    let offset_x = (image_center_x - person_center_x) as i32;
-    let offset_y = (image_center_y - person_center_y) as i32;
-
-    tracing::debug!(
-        person_center_x = person_center_x,
-        person_center_y = person_center_y,
-        offset_x = offset_x,
-        offset_y = offset_y,
-        "Calculated person center and offset"
-    );
+    // Clean up before centering so stray blobs don't pull the person off center
+    let mask_values = refine_mask(&config.segmentation, mask_values, width, height);
+    let (offset_x, offset_y) = person_offset(config, &mask_values, width, height);
 
     // Convert BGR back to RGB
     let mut rgb_mat = Mat::default();
     convert_color(&bgr_mat, &mut rgb_mat, COLOR_BGR2RGB, 0)?;
     let rgb_bytes: Vec<u8> = rgb_mat.data_bytes()?.to_vec();
 
-    // Load background image using image crate
-    let bg_image_path = resolve_background_path(&config.background_path)?;
-    tracing::debug!(path = %bg_image_path.display(), "Loading background image");
-    let bg_dynamic = image::open(&bg_image_path)?;
     let bg_resized = bg_dynamic.resize_exact(width, height, image::imageops::FilterType::Lanczos3);
     let bg_rgb = bg_resized.to_rgb8();
     let bg_bytes = bg_rgb.as_raw();
@@ -403,228 +413,136 @@ pub fn replace_background(
     Ok(DynamicImage::ImageRgb8(result_image))
 }
 
-pub fn burn_in_chyron(
-    config: &crate::config::BurnedInChyronConfig,
-    image: DynamicImage,
-    metadata: &CommitMetadata,
-) -> Result<DynamicImage> {
-    // Resolve fonts using fontconfig (with fallback to default_font_name)
-    let message_font = load_font(config.get_message_font_name())?;
-    let info_font = load_font(config.get_info_font_name())?;
-    let sha_font = load_font(config.get_sha_font_name())?;
-    let stats_font = load_font(config.get_stats_font_name())?;
-
-    // Work directly with RGBA if already RGBA, otherwise convert
-    let mut rgba_image = match image {
-        DynamicImage::ImageRgba8(img) => img,
-        other => other.to_rgba8(),
-    };
-    let (width, height) = rgba_image.dimensions();
-
-    let chyron_height = 80;
-    let y_start = height - chyron_height;
-
-    // Manually apply semi-transparent black with proper alpha blending
-    let overlay_alpha = config.chyron_opacity;
-    for y in y_start..height {
-        for x in 0..width {
-            let pixel = rgba_image.get_pixel_mut(x, y);
-            let [r, g, b, a] = pixel.0;
-
-            // Blend: result = overlay * overlay_alpha + background * (1 - overlay_alpha)
-            pixel.0 = [
-                (0.0 * overlay_alpha + r as f32 * (1.0 - overlay_alpha)) as u8,
-                (0.0 * overlay_alpha + g as f32 * (1.0 - overlay_alpha)) as u8,
-                (0.0 * overlay_alpha + b as f32 * (1.0 - overlay_alpha)) as u8,
-                a, // Keep original alpha
-            ];
+/// Mask weight above which a pixel counts as part of the person.
+const PERSON_THRESHOLD: f32 = 0.1;
+
+/// Apply the `[server.segmentation]` cleanup to a `width` x `height` mask of 0-1
+/// weights: the soft threshold, then the open/close, then the feathering.
+fn refine_mask(
+    config: &crate::config::SegmentationConfig,
+    mut mask: Vec<f32>,
+    width: u32,
+    height: u32,
+) -> Vec<f32> {
+    let (floor, ceiling) = (
+        config.mask_floor,
+        config.mask_ceiling.max(config.mask_floor),
+    );
+    if floor > 0.0 || ceiling < 1.0 {
+        for value in &mut mask {
+            *value = if *value <= floor {
+                0.0
+            } else if *value >= ceiling {
+                1.0
+            } else {
+                (*value - floor) / (ceiling - floor)
+            };
         }
     }
 
-    let white = Rgba([255u8, 255u8, 255u8, 255u8]);
-    let yellow = Rgba([255u8, 255u8, 0u8, 255u8]);
-    let grey = Rgba([180u8, 180u8, 180u8, 255u8]);
-
-    let title_scale = PxScale::from(config.title_font_size);
-    let info_scale = PxScale::from(config.info_font_size);
-
-    // Extract first line and strip conventional commit prefix for display
-    let first_line = metadata.message.lines().next().unwrap_or(&metadata.message);
-    let display_message = if let Some(colon_pos) = first_line.find(':') {
-        first_line[colon_pos + 1..].trim()
-    } else {
-        first_line
-    };
+    if config.cleanup_radius_px > 0 {
+        let binary = GrayImage::from_fn(width, height, |x, y| {
+            let weight = mask[(y * width + x) as usize];
+            Luma([if weight >= 0.5 { 255 } else { 0 }])
+        });
+        let radius = config.cleanup_radius_px;
+        let cleaned = imageproc::morphology::close(
+            &imageproc::morphology::open(&binary, Norm::LInf, radius),
+            Norm::LInf,
+            radius,
+        );
+        for ((value, before), after) in mask.iter_mut().zip(binary.pixels()).zip(cleaned.pixels()) {
+            match (before.0[0], after.0[0]) {
+                // A speck opened away
+                (255, 0) => *value = 0.0,
+                // A hole closed up
+                (0, 255) => *value = 1.0,
+                _ => {}
+            }
+        }
+    }
 
-    let title_y = y_start as i32 + 10;
-    draw_text_mut(
-        &mut rgba_image,
-        white,
-        15,
-        title_y,
-        title_scale,
-        &message_font,
-        display_message,
-    );
+    if config.feather_px > 0.0 {
+        let image = ImageBuffer::<Luma<f32>, Vec<f32>>::from_raw(width, height, mask)
+            .expect("mask has one weight per pixel");
+        mask = imageproc::filter::gaussian_blur_f32(&image, config.feather_px).into_raw();
+    }
 
-    let info_y = y_start as i32 + 45;
-    let info_text = if metadata.scope.is_empty() {
-        format!(
-            "{} • {}",
-            metadata.commit_type.to_uppercase(),
-            metadata.repo_name
-        )
-    } else {
-        format!(
-            "{} • {} • {}",
-            metadata.commit_type.to_uppercase(),
-            metadata.scope,
-            metadata.repo_name
-        )
-    };
-    draw_text_mut(
-        &mut rgba_image,
-        grey,
-        15,
-        info_y,
-        info_scale,
-        &info_font,
-        &info_text,
-    );
+    mask
+}
 
-    // Calculate stats width first to determine left-aligned starting position
-    // Format is: (N) +X -Y with k/M suffixes for large numbers
-    let has_stats = !metadata.stats.is_empty();
-    let stats_start_x = if has_stats {
-        let mut total_width = 0;
-
-        // Files changed: (N)
-        if metadata.stats.files_changed > 0 {
-            let files_str = format!("({})", format_stat_number(metadata.stats.files_changed));
-            total_width += (files_str.len() as f32 * 10.0) as i32; // (N) width
-            total_width += 10; // small gap
-        }
+/// Translation that moves the person's center of mass to the center of the frame, or
+/// none when `center_person` is off. The offset is clamped so at most
+/// `center_person_max_off_frame` of the person's extent is pushed out of frame, which
+/// keeps stray mask blobs (a bright lamp in a corner) from dragging them out of shot.
+fn person_offset(
+    config: &crate::config::ServerConfig,
+    mask_values: &[f32],
+    width: u32,
+    height: u32,
+) -> (i32, i32) {
+    if !config.center_person {
+        tracing::debug!("Person centering disabled");
+        return (0, 0);
+    }
 
-        // Insertions: +X
-        if metadata.stats.insertions > 0 {
-            let insert_str = format!("+{}", format_stat_number(metadata.stats.insertions));
-            total_width += (insert_str.len() as f32 * 10.0) as i32; // +X width
-            total_width += 10; // small gap
-        }
+    // Calculate center of mass and extent of the mask to find the person
+    let mut sum_x = 0.0_f32;
+    let mut sum_y = 0.0_f32;
+    let mut total_weight = 0.0_f32;
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (width, height, 0, 0);
 
-        // Deletions: -Y
-        if metadata.stats.deletions > 0 {
-            let delete_str = format!("-{}", format_stat_number(metadata.stats.deletions));
-            total_width += (delete_str.len() as f32 * 10.0) as i32; // -Y width
+    for y in 0..height {
+        for x in 0..width {
+            let idx = (y * width + x) as usize;
+            let weight = mask_values[idx];
+            if weight > PERSON_THRESHOLD {
+                sum_x += x as f32 * weight;
+                sum_y += y as f32 * weight;
+                total_weight += weight;
+                min_x = min_x.min(x);
+                min_y = min_y.min(y);
+                max_x = max_x.max(x);
+                max_y = max_y.max(y);
+            }
         }
+    }
 
-        (width as i32) - 30 - total_width
-    } else {
-        (width as i32) - 150 // default position if no stats
-    };
-
-    // Draw revision on the right side of the title line, left-aligned with stats
-    if !metadata.revision.is_empty() {
-        let revision_short = if metadata.revision.len() > 7 {
-            &metadata.revision[..7]
-        } else {
-            &metadata.revision
-        };
-        draw_text_mut(
-            &mut rgba_image,
-            yellow,
-            stats_start_x,
-            title_y,
-            title_scale,
-            &sha_font,
-            revision_short,
-        );
+    if total_weight <= 0.0 {
+        tracing::debug!("No person detected, not centering");
+        return (0, 0);
     }
 
-    // Draw colorized stats on the right side, left-aligned with SHA
-    // Format: (N) +X -Y where N=files changed (yellow), X=insertions (green), Y=deletions (red)
-    // Numbers over 999 are formatted with k/M suffixes (e.g., 1.2k, 1.5M)
-    if has_stats {
-        let yellow = Rgba([255u8, 255u8, 0u8, 255u8]);
-        let green = Rgba([0u8, 255u8, 0u8, 255u8]);
-        let red = Rgba([255u8, 0u8, 0u8, 255u8]);
-
-        let mut x_offset = stats_start_x;
-
-        // Draw files changed in parentheses (yellow)
-        if metadata.stats.files_changed > 0 {
-            let files_str = format!("({})", format_stat_number(metadata.stats.files_changed));
-            draw_text_mut(
-                &mut rgba_image,
-                yellow,
-                x_offset,
-                info_y,
-                info_scale,
-                &stats_font,
-                &files_str,
-            );
-            let text_width = (files_str.len() as f32 * 10.0) as i32;
-            x_offset += text_width;
-            x_offset += 10; // small gap
-        }
+    let (person_center_x, person_center_y) = (sum_x / total_weight, sum_y / total_weight);
+    let image_center_x = width as f32 / 2.0;
+    let image_center_y = height as f32 / 2.0;
 
-        // Draw insertions (green)
-        if metadata.stats.insertions > 0 {
-            let insert_str = format!("+{}", format_stat_number(metadata.stats.insertions));
-            draw_text_mut(
-                &mut rgba_image,
-                green,
-                x_offset,
-                info_y,
-                info_scale,
-                &stats_font,
-                &insert_str,
-            );
-            let text_width = (insert_str.len() as f32 * 10.0) as i32;
-            x_offset += text_width;
-            x_offset += 10; // small gap
-        }
+    let max_off_frame = config.center_person_max_off_frame.clamp(0.0, 1.0);
+    let clamp_axis = |offset: f32, min: u32, max: u32, size: u32| {
+        // The person's original position is in frame, so 0 is always within bounds
+        let allowed = ((max - min + 1) as f32 * max_off_frame) as i32;
+        let lowest = -(min as i32) - allowed;
+        let highest = (size as i32 - 1 - max as i32) + allowed;
+        (offset as i32).clamp(lowest, highest)
+    };
+    let offset_x = clamp_axis(image_center_x - person_center_x, min_x, max_x, width);
+    let offset_y = clamp_axis(image_center_y - person_center_y, min_y, max_y, height);
 
-        // Draw deletions (red)
-        if metadata.stats.deletions > 0 {
-            let delete_str = format!("-{}", format_stat_number(metadata.stats.deletions));
-            draw_text_mut(
-                &mut rgba_image,
-                red,
-                x_offset,
-                info_y,
-                info_scale,
-                &stats_font,
-                &delete_str,
-            );
-        }
-    }
+    tracing::debug!(
+        person_center_x = person_center_x,
+        person_center_y = person_center_y,
+        offset_x = offset_x,
+        offset_y = offset_y,
+        "Calculated person center and offset"
+    );
 
-    Ok(DynamicImage::ImageRgba8(rgba_image))
+    (offset_x, offset_y)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-
-    #[test]
-    fn test_resolve_font_path_monospace() {
-        // Monospace should always be available
-        let result = resolve_font_path("monospace");
-        assert!(result.is_ok());
-        let path = result.unwrap();
-        assert!(path.exists());
-        assert!(path.is_file());
-    }
-
-    #[test]
-    fn test_resolve_font_path_nonexistent_falls_back() {
-        // Nonexistent font should fall back to monospace
-        let result = resolve_font_path("ThisFontDefinitelyDoesNotExist12345");
-        assert!(result.is_ok());
-        let path = result.unwrap();
-        assert!(path.exists());
-    }
+    use test_case::test_case;
 
     #[test]
     fn test_resolve_background_path_absolute() {
@@ -736,10 +654,197 @@ mod tests {
         fs::remove_dir_all(&test_data_dir2).ok();
     }
 
+    /// A `width` x `height` mask with each `(x0, x1, weight)` column band filled in
+    /// over the full height.
+    fn column_mask(width: u32, height: u32, bands: &[(u32, u32, f32)]) -> Vec<f32> {
+        let mut mask = vec![0.0; (width * height) as usize];
+        for y in 0..height {
+            for &(x0, x1, weight) in bands {
+                for x in x0..=x1 {
+                    mask[(y * width + x) as usize] = weight;
+                }
+            }
+        }
+        mask
+    }
+
+    fn centering(center_person: bool, max_off_frame: f32) -> crate::config::ServerConfig {
+        crate::config::ServerConfig {
+            center_person,
+            center_person_max_off_frame: max_off_frame,
+            ..Default::default()
+        }
+    }
+
+    #[test_case(&[(10, 19, 1.0)], (35, 0) ; "moves person to center")]
+    #[test_case(&[(45, 54, 1.0)], (0, 0) ; "already centered")]
+    #[test_case(&[], (0, 0) ; "no person")]
+    #[test_case(&[(0, 9, 1.0), (90, 99, 0.2)], (25, 0) ; "faint blob limits shift")]
+    fn test_person_offset(bands: &[(u32, u32, f32)], expected: (i32, i32)) {
+        let mask = column_mask(100, 100, bands);
+        assert_eq!(
+            person_offset(&centering(true, 0.25), &mask, 100, 100),
+            expected
+        );
+    }
+
     #[test]
-    fn test_load_font_monospace() {
-        // Test loading monospace font
-        let result = load_font("monospace");
-        assert!(result.is_ok());
+    fn test_person_offset_disabled() {
+        let mask = column_mask(100, 100, &[(10, 19, 1.0)]);
+        assert_eq!(
+            person_offset(&centering(false, 0.25), &mask, 100, 100),
+            (0, 0)
+        );
+    }
+
+    #[test]
+    fn test_person_offset_zero_fraction_keeps_person_in_frame() {
+        let mask = column_mask(100, 100, &[(0, 9, 1.0), (90, 99, 0.2)]);
+        assert_eq!(
+            person_offset(&centering(true, 0.0), &mask, 100, 100),
+            (0, 0)
+        );
+    }
+
+    fn refine(config: crate::config::SegmentationConfig, mask: &[f32]) -> Vec<f32> {
+        refine_mask(&config, mask.to_vec(), 100, 100)
+    }
+
+    /// A 100x100 mask with a 40x40 person in the middle, a 3x3 speck in a corner and a
+    /// 2x2 hole in the person, all at full weight.
+    fn blobby_mask() -> Vec<f32> {
+        let mut mask = vec![0.0; 100 * 100];
+        for y in 30..70 {
+            for x in 30..70 {
+                mask[y * 100 + x] = 1.0;
+            }
+        }
+        for y in 5..8 {
+            for x in 5..8 {
+                mask[y * 100 + x] = 1.0;
+            }
+        }
+        for y in 49..51 {
+            for x in 49..51 {
+                mask[y * 100 + x] = 0.0;
+            }
+        }
+        mask
+    }
+
+    #[test]
+    fn test_refine_mask_defaults_leave_mask_alone() {
+        let mask: Vec<f32> = (0..100 * 100).map(|i| (i % 97) as f32 / 96.0).collect();
+        assert_eq!(refine(Default::default(), &mask), mask);
+    }
+
+    #[test_case(0.05, 0.0 ; "below floor")]
+    #[test_case(0.5, 0.5 ; "between is stretched")]
+    #[test_case(0.95, 1.0 ; "above ceiling")]
+    fn test_refine_mask_soft_threshold(value: f32, expected: f32) {
+        let config = crate::config::SegmentationConfig {
+            mask_floor: 0.1,
+            mask_ceiling: 0.9,
+            ..Default::default()
+        };
+        let refined = refine(config, &vec![value; 100 * 100]);
+        assert!((refined[0] - expected).abs() < 1e-6, "{}", refined[0]);
+    }
+
+    #[test]
+    fn test_refine_mask_cleanup_removes_specks_and_fills_holes() {
+        let config = crate::config::SegmentationConfig {
+            cleanup_radius_px: 2,
+            ..Default::default()
+        };
+
+        let refined = refine(config, &blobby_mask());
+
+        assert_eq!(refined[6 * 100 + 6], 0.0, "speck kept");
+        assert_eq!(refined[49 * 100 + 49], 1.0, "hole kept");
+        // The person's outline, corners included, survives
+        assert_eq!(refined[30 * 100 + 30], 1.0);
+        assert_eq!(refined[69 * 100 + 69], 1.0);
+        assert_eq!(refined[29 * 100 + 50], 0.0);
+    }
+
+    #[test]
+    fn test_refine_mask_feathers_edges() {
+        let config = crate::config::SegmentationConfig {
+            feather_px: 2.0,
+            ..Default::default()
+        };
+
+        let refined = refine(config, &blobby_mask());
+
+        // Soft on both sides of the edge, untouched well inside and outside
+        let (outside, inside) = (refined[50 * 100 + 29], refined[50 * 100 + 30]);
+        assert!(outside > 0.05 && outside < 0.5, "{outside}");
+        assert!(inside > 0.5 && inside < 0.95, "{inside}");
+        assert!(refined[40 * 100 + 40] > 0.99);
+        assert!(refined[90 * 100 + 90] < 0.01);
+    }
+
+    #[test_case("none" ; "lowercase")]
+    #[test_case("None" ; "capitalised")]
+    fn test_background_none_is_disabled(spec: &str) {
+        assert_eq!(Background::resolve(spec), Background::Disabled);
+    }
+
+    #[test]
+    fn test_background_none_skips_replacement() -> Result {
+        let config = crate::config::ServerConfig {
+            background_path: NO_BACKGROUND.to_string(),
+            ..Default::default()
+        };
+        let image =
+            DynamicImage::ImageRgb8(image::RgbImage::from_pixel(4, 3, image::Rgb([1, 2, 3])));
+
+        let model = SegmentationModel::open(
+            "/nonexistent/models",
+            &Default::default(),
+            Default::default(),
+            Default::default(),
+        );
+
+        let result = replace_background(&config, &model, image.clone())?;
+
+        assert_eq!(result, image);
+        Ok(())
+    }
+
+    #[test]
+    fn test_background_existing_file_is_used() -> Result {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("bg.png");
+        image::RgbImage::new(2, 2).save(&path)?;
+
+        let background = Background::resolve(path.to_str().unwrap());
+
+        assert_eq!(background, Background::File(path));
+        assert_eq!(background.status(), "configured");
+        assert_eq!(background.load()?.width(), 2);
+        Ok(())
+    }
+
+    #[cfg(feature = "embedded-background")]
+    #[test]
+    fn test_background_missing_falls_back_to_embedded() -> Result {
+        let background = Background::resolve("/this/path/definitely/does/not/exist.png");
+
+        assert_eq!(background, Background::Embedded);
+        assert_eq!(background.status(), "embedded_default");
+        let image = background.load()?;
+        assert!(image.width() > 0 && image.height() > 0);
+        Ok(())
+    }
+
+    #[cfg(not(feature = "embedded-background"))]
+    #[test]
+    fn test_background_missing_without_embedded_default() {
+        let background = Background::resolve("/this/path/definitely/does/not/exist.png");
+
+        assert!(matches!(background, Background::Missing(_)));
+        assert!(background.load().is_err());
     }
 }
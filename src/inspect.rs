@@ -0,0 +1,303 @@
+//! `lolcommits inspect`: what a gallery image says about itself.
+//!
+//! Reads the `lolcommit:*` chunks embedded in a PNG, or the sidecar of other formats,
+//! falling back to the filename the way [`image_metadata::parse_image_file`] does, and
+//! points out where the filename and the recorded metadata disagree.
+
+use crate::error::Result;
+use crate::git::CommitMetadata;
+use crate::image_metadata;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Prefix of the text chunks lolcommits embeds in its PNGs.
+const CHUNK_PREFIX: &str = "lolcommit:";
+
+/// Where an image's metadata came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    /// `lolcommit:*` chunks in the PNG itself.
+    Embedded,
+    /// The JSON file beside a JPEG, WebP or GIF.
+    Sidecar,
+    /// Only the filename, for images saved before metadata was embedded.
+    Filename,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub path: PathBuf,
+    /// `None` when neither the image nor its filename identify the commit.
+    pub source: Option<Source>,
+    /// Every `lolcommit:*` chunk by keyword, as stored.
+    pub chunks: BTreeMap<String, String>,
+    pub metadata: Option<CommitMetadata>,
+    pub warnings: Vec<String>,
+}
+
+impl Report {
+    pub fn is_empty(&self) -> bool {
+        self.source.is_none()
+    }
+}
+
+/// Inspect one image. An error when the file can't be read at all.
+pub fn inspect(path: &Path) -> Result<Report> {
+    let chunks: BTreeMap<String, String> = image_metadata::read_text_chunks(path)?
+        .into_iter()
+        .filter(|(keyword, _)| keyword.starts_with(CHUNK_PREFIX))
+        .collect();
+    let recorded = image_metadata::read_metadata(path)?;
+    let from_filename = image_metadata::parse_filename(path);
+
+    let source = match (&recorded, &from_filename) {
+        (Some(_), _) if image_metadata::is_png(path) => Some(Source::Embedded),
+        (Some(_), _) => Some(Source::Sidecar),
+        (None, Some(_)) => Some(Source::Filename),
+        (None, None) => None,
+    };
+    let warnings = match (&recorded, &from_filename) {
+        (Some(recorded), Some(from_filename)) => inconsistencies(recorded, from_filename),
+        (Some(recorded), None) => {
+            let mut warnings = inconsistencies(recorded, recorded);
+            warnings.push("filename isn't {repo}-{timestamp}-{revision}".to_string());
+            warnings
+        }
+        (None, Some(_)) => vec!["no metadata recorded, derived from the filename".to_string()],
+        (None, None) => Vec::new(),
+    };
+
+    let metadata = recorded.or(from_filename).map(|mut metadata| {
+        metadata.path = path.to_path_buf();
+        metadata
+    });
+    Ok(Report {
+        path: path.to_path_buf(),
+        source,
+        chunks,
+        metadata,
+        warnings,
+    })
+}
+
+/// Where the recorded metadata disagrees with the filename, or doesn't make sense on its
+/// own.
+fn inconsistencies(recorded: &CommitMetadata, from_filename: &CommitMetadata) -> Vec<String> {
+    let mut warnings = Vec::new();
+
+    if recorded.revision.is_empty() {
+        warnings.push("no revision recorded".to_string());
+    } else if !same_revision(&recorded.revision, &from_filename.revision) {
+        warnings.push(format!(
+            "filename revision {} differs from recorded revision {}",
+            from_filename.revision, recorded.revision
+        ));
+    }
+    if !recorded.repo_name.is_empty() && recorded.repo_name != from_filename.repo_name {
+        warnings.push(format!(
+            "filename repo {} differs from recorded repo {}",
+            from_filename.repo_name, recorded.repo_name
+        ));
+    }
+    if crate::parse_timestamp(&recorded.timestamp).is_none() {
+        warnings.push(format!(
+            "recorded timestamp {:?} isn't RFC 3339 or {}",
+            recorded.timestamp,
+            crate::TIMESTAMP_FORMAT
+        ));
+    }
+    warnings
+}
+
+/// Revisions match when one abbreviates the other.
+fn same_revision(a: &str, b: &str) -> bool {
+    a.starts_with(b) || b.starts_with(a)
+}
+
+/// Human readable report: the path, then one row per chunk (or metadata field when there
+/// are no chunks), then any warnings.
+pub fn render(report: &Report) -> String {
+    let mut out = format!("{}\n", report.path.display());
+
+    let Some(source) = report.source else {
+        out.push_str("    no lolcommit metadata\n");
+        return out;
+    };
+
+    let rows: Vec<(String, String)> = if report.chunks.is_empty() {
+        report.metadata.as_ref().map(fields).unwrap_or_default()
+    } else {
+        report
+            .chunks
+            .iter()
+            .map(|(keyword, text)| (keyword.clone(), text.clone()))
+            .collect()
+    };
+    let width = rows.iter().map(|(key, _)| key.len()).max().unwrap_or(0);
+
+    out.push_str(&format!("    {:<width$}  {:?}\n", "source", source));
+    for (key, value) in &rows {
+        let mut lines = value.lines();
+        out.push_str(&format!(
+            "    {key:<width$}  {}\n",
+            lines.next().unwrap_or_default()
+        ));
+        for line in lines {
+            out.push_str(&format!("    {:<width$}  {line}\n", ""));
+        }
+    }
+    for warning in &report.warnings {
+        out.push_str(&format!("    ! {warning}\n"));
+    }
+    out
+}
+
+/// The non-empty fields of `metadata`, for images without chunks to list.
+fn fields(metadata: &CommitMetadata) -> Vec<(String, String)> {
+    let stats = &metadata.stats;
+    [
+        ("revision", metadata.revision.clone()),
+        ("repo", metadata.repo_name.clone()),
+        ("branch", metadata.branch_name.clone()),
+        ("timestamp", metadata.timestamp.clone()),
+        ("type", metadata.commit_type.clone()),
+        ("scope", metadata.scope.clone()),
+        ("author", metadata.author_name.clone()),
+        ("email", metadata.author_email.clone()),
+        ("co_authors", metadata.co_authors.join(", ")),
+        ("message", metadata.message.clone()),
+        (
+            "stats",
+            if stats.is_empty() {
+                String::new()
+            } else {
+                format!(
+                    "{} files, +{} -{}",
+                    stats.files_changed, stats.insertions, stats.deletions
+                )
+            },
+        ),
+    ]
+    .into_iter()
+    .filter(|(_, value)| !value.is_empty())
+    .map(|(key, value)| (key.to_string(), value))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::DiffStats;
+    use image::DynamicImage;
+
+    fn metadata(revision: &str, repo_name: &str) -> CommitMetadata {
+        CommitMetadata {
+            path: PathBuf::new(),
+            revision: revision.to_string(),
+            message: "feat: inspect\n\nWith a body".to_string(),
+            commit_type: "feat".to_string(),
+            scope: String::new(),
+            timestamp: "2024-03-01 09:15:00".to_string(),
+            repo_name: repo_name.to_string(),
+            branch_name: "main".to_string(),
+            author_name: "Ada".to_string(),
+            author_email: String::new(),
+            breaking: false,
+            co_authors: Vec::new(),
+            stats: DiffStats {
+                files_changed: 1,
+                insertions: 2,
+                deletions: 3,
+            },
+        }
+    }
+
+    fn save(dir: &Path, filename: &str, metadata: &CommitMetadata) -> Result<PathBuf> {
+        let path = dir.join(filename);
+        image_metadata::save_png_with_metadata(&DynamicImage::new_rgb8(2, 2), &path, metadata)?;
+        Ok(path)
+    }
+
+    #[test]
+    fn test_embedded_chunks_are_listed() -> Result {
+        let dir = tempfile::tempdir()?;
+        let path = save(
+            dir.path(),
+            "app-20240301-091500-abc1234.png",
+            &metadata("abc1234", "app"),
+        )?;
+
+        let report = inspect(&path)?;
+
+        assert_eq!(report.source, Some(Source::Embedded));
+        assert_eq!(report.chunks["lolcommit:Revision"], "abc1234");
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+
+        let rendered = render(&report);
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert!(
+            lines
+                .iter()
+                .any(|line| line.starts_with("    lolcommit:Repo ") && line.ends_with(" app"))
+        );
+        let message = lines
+            .iter()
+            .position(|line| line.ends_with(" feat: inspect"))
+            .unwrap();
+        assert_eq!(lines[message + 2].trim(), "With a body");
+        Ok(())
+    }
+
+    #[test]
+    fn test_warns_when_filename_disagrees() -> Result {
+        let dir = tempfile::tempdir()?;
+        let path = save(
+            dir.path(),
+            "other-20240301-091500-def5678.png",
+            &metadata("abc1234", "app"),
+        )?;
+
+        let report = inspect(&path)?;
+
+        assert_eq!(
+            report.warnings,
+            [
+                "filename revision def5678 differs from recorded revision abc1234",
+                "filename repo other differs from recorded repo app",
+            ]
+        );
+        assert!(render(&report).contains("! filename revision def5678"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_falls_back_to_the_filename() -> Result {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("app-20240301-091500-abc1234.png");
+        DynamicImage::new_rgb8(2, 2).save(&path)?;
+
+        let report = inspect(&path)?;
+
+        assert_eq!(report.source, Some(Source::Filename));
+        assert!(report.chunks.is_empty());
+        assert_eq!(report.metadata.unwrap().revision, "abc1234");
+        assert_eq!(report.warnings.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_nothing_to_go_on() -> Result {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("holiday.png");
+        DynamicImage::new_rgb8(2, 2).save(&path)?;
+
+        let report = inspect(&path)?;
+
+        assert!(report.is_empty());
+        assert!(render(&report).ends_with("no lolcommit metadata\n"));
+        assert!(inspect(&dir.path().join("missing.png")).is_err());
+        Ok(())
+    }
+}
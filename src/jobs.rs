@@ -0,0 +1,166 @@
+//! Status of uploads being processed in the background.
+//!
+//! `/api/upload` answers 202 before the image is processed, so each upload gets a job
+//! that clients can poll at `/api/jobs/{id}` to learn whether it was saved. Finished
+//! jobs are forgotten `job_ttl_secs` after they finish.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Processing,
+    Done,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Job {
+    pub id: String,
+    pub revision: String,
+    pub status: JobStatus,
+    /// Why the job failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The saved image, once done.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filename: Option<String>,
+    #[serde(skip)]
+    finished_at: Option<Instant>,
+}
+
+pub struct Jobs {
+    ttl: Duration,
+    jobs: Mutex<HashMap<String, Job>>,
+}
+
+impl Jobs {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            jobs: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Queue a job for an upload of `revision`, returning its id.
+    pub fn create(&self, revision: &str) -> String {
+        let id = uuid::Uuid::new_v4().to_string();
+        let mut jobs = self.lock();
+        self.evict_expired(&mut jobs);
+        jobs.insert(
+            id.clone(),
+            Job {
+                id: id.clone(),
+                revision: revision.to_string(),
+                status: JobStatus::Queued,
+                error: None,
+                filename: None,
+                finished_at: None,
+            },
+        );
+        id
+    }
+
+    pub fn start(&self, id: &str) {
+        self.update(id, |job| job.status = JobStatus::Processing);
+    }
+
+    pub fn finish(&self, id: &str, filename: String) {
+        self.update(id, |job| {
+            job.status = JobStatus::Done;
+            job.filename = Some(filename);
+            job.finished_at = Some(Instant::now());
+        });
+    }
+
+    pub fn fail(&self, id: &str, error: String) {
+        self.update(id, |job| {
+            job.status = JobStatus::Failed;
+            job.error = Some(error);
+            job.finished_at = Some(Instant::now());
+        });
+    }
+
+    pub fn get(&self, id: &str) -> Option<Job> {
+        let mut jobs = self.lock();
+        self.evict_expired(&mut jobs);
+        jobs.get(id).cloned()
+    }
+
+    fn update(&self, id: &str, f: impl FnOnce(&mut Job)) {
+        if let Some(job) = self.lock().get_mut(id) {
+            f(job);
+        }
+    }
+
+    fn evict_expired(&self, jobs: &mut HashMap<String, Job>) {
+        jobs.retain(|_, job| {
+            job.finished_at
+                .is_none_or(|finished_at| finished_at.elapsed() < self.ttl)
+        });
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Job>> {
+        self.jobs.lock().expect("jobs lock poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_job_lifecycle() {
+        let jobs = Jobs::new(Duration::from_secs(60));
+        let id = jobs.create("abc1234");
+        assert_eq!(jobs.get(&id).unwrap().status, JobStatus::Queued);
+
+        jobs.start(&id);
+        assert_eq!(jobs.get(&id).unwrap().status, JobStatus::Processing);
+
+        jobs.finish(&id, "repo-20240101-120000-abc1234.png".to_string());
+        let job = jobs.get(&id).unwrap();
+        assert_eq!(job.status, JobStatus::Done);
+        assert_eq!(
+            job.filename.as_deref(),
+            Some("repo-20240101-120000-abc1234.png")
+        );
+        assert_eq!(job.revision, "abc1234");
+    }
+
+    #[test]
+    fn test_failed_job_keeps_error() {
+        let jobs = Jobs::new(Duration::from_secs(60));
+        let id = jobs.create("abc1234");
+
+        jobs.fail(&id, "disk full".to_string());
+
+        let job = serde_json::to_value(jobs.get(&id).unwrap()).unwrap();
+        assert_eq!(job["status"], "failed");
+        assert_eq!(job["error"], "disk full");
+        assert!(job.get("filename").is_none());
+    }
+
+    #[test]
+    fn test_finished_jobs_expire() {
+        let jobs = Jobs::new(Duration::ZERO);
+        let running = jobs.create("aaa1111");
+        let finished = jobs.create("bbb2222");
+        jobs.start(&running);
+        jobs.finish(&finished, "done.png".to_string());
+
+        assert!(jobs.get(&finished).is_none());
+        assert!(jobs.get(&running).is_some());
+    }
+
+    #[test]
+    fn test_ids_are_unique() {
+        let jobs = Jobs::new(Duration::from_secs(60));
+        assert_ne!(jobs.create("abc1234"), jobs.create("abc1234"));
+        assert!(jobs.get("not-a-job").is_none());
+    }
+}
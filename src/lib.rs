@@ -1,18 +1,80 @@
+pub mod animation;
+pub mod api_error;
+pub mod badge;
+pub mod best_of;
 pub mod camera;
 pub mod capture;
+pub mod chyron;
 pub mod config;
+pub mod config_edit;
+pub mod disk_space;
+pub mod doctor;
 pub mod error;
+pub mod export;
+pub mod exposure;
+pub mod failed_uploads;
+pub mod feed;
+pub mod fsck;
 pub mod git;
+pub mod hook;
+pub mod image_cache;
+pub mod image_index;
 pub mod image_metadata;
 pub mod image_processor;
+pub mod inspect;
+pub mod jobs;
+pub mod locale;
+pub mod meme;
 pub mod metrics;
+pub mod model_cache;
+pub mod orientation;
+pub mod overrides;
+pub mod post_processor;
+pub mod processing_queue;
+pub mod read_only;
+pub mod revision_cache;
 pub mod segmentation;
 pub mod server;
+pub mod setup;
+pub mod spool;
+pub mod static_files;
+pub mod stats;
+pub mod storage;
+pub mod systemd;
+pub mod tls;
+pub mod urls;
 
 use std::io::IsTerminal;
 
-/// Timestamp format used in commit metadata (both writing and parsing)
+/// Format of metadata timestamps recorded before they carried an offset, in the server's
+/// local time. Still read; new metadata is written with [`format_timestamp`].
 pub const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// A metadata timestamp as recorded: RFC 3339 with the commit's offset.
+pub fn format_timestamp(taken: chrono::DateTime<chrono::FixedOffset>) -> String {
+    taken.to_rfc3339_opts(chrono::SecondsFormat::Secs, false)
+}
+
+/// When a metadata timestamp was taken, from RFC 3339 or, for older images, local time in
+/// [`TIMESTAMP_FORMAT`]. Compare these rather than the strings, which don't sort
+/// chronologically across offsets.
+pub fn parse_timestamp(timestamp: &str) -> Option<chrono::DateTime<chrono::FixedOffset>> {
+    use chrono::TimeZone;
+
+    chrono::DateTime::parse_from_rfc3339(timestamp)
+        .ok()
+        .or_else(|| {
+            let naive = chrono::NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT).ok()?;
+            let local = chrono::Local.from_local_datetime(&naive).earliest()?;
+            Some(local.fixed_offset())
+        })
+}
+
+/// The server-local wall-clock time of a metadata timestamp, for grouping by day and
+/// display.
+pub fn local_time(timestamp: &str) -> Option<chrono::NaiveDateTime> {
+    parse_timestamp(timestamp).map(|taken| taken.with_timezone(&chrono::Local).naive_local())
+}
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 /// Log output destination
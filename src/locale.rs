@@ -0,0 +1,152 @@
+//! Locale-specific rendering of dates and numbers in the chyron.
+//!
+//! A small built-in table rather than full ICU: it only needs a decimal separator and a
+//! short date format per locale. The API deliberately stays locale-neutral.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Locale {
+    pub tag: &'static str,
+    decimal_separator: char,
+    /// chrono format for the chyron timestamp, `None` to leave it out.
+    date_format: Option<&'static str>,
+}
+
+/// Behaviour when no (or an unknown) locale is configured: '.' decimals, no timestamp.
+pub const DEFAULT: Locale = Locale {
+    tag: "",
+    decimal_separator: '.',
+    date_format: None,
+};
+
+const LOCALES: &[Locale] = &[
+    Locale {
+        tag: "en-US",
+        decimal_separator: '.',
+        date_format: Some("%m/%d %-I:%M %p"),
+    },
+    Locale {
+        tag: "en-GB",
+        decimal_separator: '.',
+        date_format: Some("%d/%m %H:%M"),
+    },
+    Locale {
+        tag: "de-DE",
+        decimal_separator: ',',
+        date_format: Some("%d.%m. %H:%M"),
+    },
+    Locale {
+        tag: "fr-FR",
+        decimal_separator: ',',
+        date_format: Some("%d/%m %H:%M"),
+    },
+    Locale {
+        tag: "nl-NL",
+        decimal_separator: ',',
+        date_format: Some("%d-%m %H:%M"),
+    },
+    Locale {
+        tag: "ja-JP",
+        decimal_separator: '.',
+        date_format: Some("%m/%d %H:%M"),
+    },
+];
+
+impl Locale {
+    /// Look up a BCP 47 style tag ("de-DE", "de_de" or just "de"), falling back to
+    /// [`DEFAULT`] for anything not in the table.
+    pub fn resolve(tag: Option<&str>) -> Locale {
+        let Some(tag) = tag.map(|t| t.trim().replace('_', "-")) else {
+            return DEFAULT;
+        };
+
+        let exact = LOCALES.iter().find(|l| l.tag.eq_ignore_ascii_case(&tag));
+        let language = || {
+            let language = tag.split('-').next().unwrap_or_default();
+            LOCALES.iter().find(|l| {
+                l.tag
+                    .split('-')
+                    .next()
+                    .is_some_and(|l| l.eq_ignore_ascii_case(language))
+            })
+        };
+
+        match exact.or_else(language) {
+            Some(locale) => *locale,
+            None => {
+                tracing::debug!(tag, "Unknown locale, using default formatting");
+                DEFAULT
+            }
+        }
+    }
+
+    /// Format a number with k/M suffix for numbers over 999
+    /// Examples: 42 -> "42", 1234 -> "1.2k" ("1,2k" in de-DE), 1567890 -> "1.6M"
+    pub fn format_stat_number(&self, n: u32) -> String {
+        if n <= 999 {
+            return n.to_string();
+        }
+
+        let formatted = if n < 1_000_000 {
+            format!("{:.1}k", n as f32 / 1000.0)
+        } else {
+            format!("{:.1}M", n as f32 / 1_000_000.0)
+        };
+
+        formatted.replace('.', &self.decimal_separator.to_string())
+    }
+
+    /// Render a metadata timestamp for the chyron, `None` when this locale shows no
+    /// timestamp or it can't be parsed.
+    pub fn format_timestamp(&self, timestamp: &str) -> Option<String> {
+        let format = self.date_format?;
+        let parsed = crate::local_time(timestamp)?;
+        Some(parsed.format(format).to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(None,          ""      ; "unset")]
+    #[test_case(Some("de-DE"), "de-DE" ; "exact")]
+    #[test_case(Some("de_de"), "de-DE" ; "underscore lowercase")]
+    #[test_case(Some("de-AT"), "de-DE" ; "language fallback")]
+    #[test_case(Some("en"),    "en-US" ; "language only")]
+    #[test_case(Some("xx-YY"), ""      ; "unknown")]
+    fn test_resolve(tag: Option<&str>, expected: &str) {
+        assert_eq!(Locale::resolve(tag).tag, expected);
+    }
+
+    #[test_case(None,          42,        "42"   ; "default small")]
+    #[test_case(None,          999,       "999"  ; "default boundary")]
+    #[test_case(None,          1234,      "1.2k" ; "default thousands")]
+    #[test_case(None,          1_567_890, "1.6M" ; "default millions")]
+    #[test_case(Some("en-US"), 1234,      "1.2k" ; "en-US thousands")]
+    #[test_case(Some("de-DE"), 42,        "42"   ; "de-DE small")]
+    #[test_case(Some("de-DE"), 1234,      "1,2k" ; "de-DE thousands")]
+    #[test_case(Some("de-DE"), 1_567_890, "1,6M" ; "de-DE millions")]
+    #[test_case(Some("fr-FR"), 48_213,    "48,2k" ; "fr-FR thousands")]
+    #[test_case(Some("xx-YY"), 1234,      "1.2k" ; "unknown falls back")]
+    fn test_format_stat_number(tag: Option<&str>, n: u32, expected: &str) {
+        assert_eq!(Locale::resolve(tag).format_stat_number(n), expected);
+    }
+
+    #[test_case(None,          None                     ; "default has no timestamp")]
+    #[test_case(Some("de-DE"), Some("15.01. 14:05")     ; "de-DE 24h")]
+    #[test_case(Some("en-GB"), Some("15/01 14:05")      ; "en-GB")]
+    #[test_case(Some("en-US"), Some("01/15 2:05 PM")    ; "en-US 12h")]
+    #[test_case(Some("xx-YY"), None                     ; "unknown has no timestamp")]
+    fn test_format_timestamp(tag: Option<&str>, expected: Option<&str>) {
+        assert_eq!(
+            Locale::resolve(tag).format_timestamp("2024-01-15 14:05:09"),
+            expected.map(str::to_string)
+        );
+    }
+
+    #[test]
+    fn test_format_timestamp_unparseable() {
+        assert_eq!(Locale::resolve(Some("de-DE")).format_timestamp(""), None);
+    }
+}
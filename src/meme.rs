@@ -0,0 +1,261 @@
+//! Meme captions (`style = "meme"`), the look of classic lolcommits: the commit message
+//! across the top and its type, repo and revision across the bottom, centred, in white
+//! with a black outline, in place of the chyron band.
+//!
+//! Text starts at a tenth of the image height and shrinks, to half that at most, so
+//! every line fits within 90% of the image width. A message still too long wraps onto
+//! a second line and is then cut short with an ellipsis.
+
+use crate::chyron::{
+    display_message, measure_text_width, short_revision, shrink_to_width, truncate_to_width,
+    wrap_to_width,
+};
+use crate::git::CommitMetadata;
+use ab_glyph::{Font, FontArc, PxScale, ScaleFont};
+use image::{Rgba, RgbaImage};
+use imageproc::drawing::draw_text_mut;
+
+/// Starting text size, relative to the image height.
+const TEXT_HEIGHT_FRACTION: f32 = 0.1;
+/// Widest a line may be, relative to the image width.
+const MAX_WIDTH_FRACTION: f32 = 0.9;
+/// Space above the top caption and below the bottom one, relative to the image height.
+const MARGIN_FRACTION: f32 = 0.03;
+/// Distance between the message's lines, relative to their height.
+const LINE_SPACING: f32 = 1.1;
+/// Outline thickness, relative to the text size.
+const OUTLINE_FRACTION: f32 = 1.0 / 16.0;
+
+const FILL: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const OUTLINE: Rgba<u8> = Rgba([0, 0, 0, 255]);
+
+/// A line of caption and where it goes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Caption {
+    pub text: String,
+    pub x: i32,
+    pub y: i32,
+}
+
+/// Where the captions go on an image of a given size.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemeLayout {
+    /// Shared by every line, so the captions match.
+    pub scale: PxScale,
+    pub captions: Vec<Caption>,
+    pub outline_px: i32,
+}
+
+impl MemeLayout {
+    pub fn new(font: &FontArc, width: u32, height: u32, metadata: &CommitMetadata) -> Self {
+        let max_width = width as f32 * MAX_WIDTH_FRACTION;
+        let base = PxScale::from((height as f32 * TEXT_HEIGHT_FRACTION).max(1.0));
+        let message = display_message(&metadata.message);
+        let footer = footer(metadata);
+
+        // As big as lets the footer fit on one line and the message on two
+        let footer_scale = shrink_to_width(font, base, &footer, max_width);
+        let message_scale = shrink_to_width(font, base, message, 2.0 * max_width);
+        let scale = if footer_scale.y < message_scale.y {
+            footer_scale
+        } else {
+            message_scale
+        };
+
+        let line_height = font.as_scaled(scale).height();
+        let margin = height as f32 * MARGIN_FRACTION;
+        let centred = |text: String, y: f32| Caption {
+            x: ((width as f32 - measure_text_width(font, scale, &text)) / 2.0).round() as i32,
+            y: y.round() as i32,
+            text,
+        };
+
+        let mut captions: Vec<Caption> = wrap_to_width(font, scale, message, max_width)
+            .into_iter()
+            .enumerate()
+            .filter(|(_, line)| !line.is_empty())
+            .map(|(i, line)| centred(line, margin + i as f32 * line_height * LINE_SPACING))
+            .collect();
+        let footer = truncate_to_width(font, scale, &footer, max_width);
+        if !footer.is_empty() {
+            captions.push(centred(footer, height as f32 - margin - line_height));
+        }
+
+        Self {
+            scale,
+            captions,
+            outline_px: (scale.y * OUTLINE_FRACTION).round().max(1.0) as i32,
+        }
+    }
+}
+
+/// `{type} • {repo} • {revision}`, leaving out whatever is empty.
+fn footer(metadata: &CommitMetadata) -> String {
+    [
+        metadata.commit_type.as_str(),
+        metadata.repo_name.as_str(),
+        short_revision(&metadata.revision),
+    ]
+    .into_iter()
+    .filter(|part| !part.is_empty())
+    .collect::<Vec<_>>()
+    .join(" • ")
+}
+
+/// Caption `canvas` with `metadata` in `font`.
+pub fn draw(font: &FontArc, canvas: &mut RgbaImage, metadata: &CommitMetadata) {
+    let (width, height) = canvas.dimensions();
+    let layout = MemeLayout::new(font, width, height, metadata);
+    let r = layout.outline_px;
+
+    // Every outline first, so no line's outline covers another's fill
+    for caption in &layout.captions {
+        for dx in -r..=r {
+            for dy in -r..=r {
+                if (dx, dy) != (0, 0) && dx * dx + dy * dy <= r * r + 1 {
+                    draw_text_mut(
+                        canvas,
+                        OUTLINE,
+                        caption.x + dx,
+                        caption.y + dy,
+                        layout.scale,
+                        font,
+                        &caption.text,
+                    );
+                }
+            }
+        }
+    }
+    for caption in &layout.captions {
+        draw_text_mut(
+            canvas,
+            FILL,
+            caption.x,
+            caption.y,
+            layout.scale,
+            font,
+            &caption.text,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::DiffStats;
+    use std::path::PathBuf;
+
+    const SANS: &[u8] = include_bytes!("../tests/fixtures/fonts/DejaVuSans.ttf");
+
+    fn font() -> FontArc {
+        FontArc::try_from_slice(SANS).unwrap()
+    }
+
+    fn metadata(message: &str, repo_name: &str) -> CommitMetadata {
+        CommitMetadata {
+            path: PathBuf::new(),
+            revision: "abcdef0123".to_string(),
+            message: message.to_string(),
+            commit_type: "feat".to_string(),
+            scope: String::new(),
+            timestamp: "2024-01-15 12:34:56".to_string(),
+            repo_name: repo_name.to_string(),
+            branch_name: "main".to_string(),
+            author_name: String::new(),
+            author_email: String::new(),
+            breaking: false,
+            co_authors: Vec::new(),
+            stats: DiffStats {
+                files_changed: 1,
+                insertions: 2,
+                deletions: 3,
+            },
+        }
+    }
+
+    fn texts(layout: &MemeLayout) -> Vec<&str> {
+        layout
+            .captions
+            .iter()
+            .map(|caption| caption.text.as_str())
+            .collect()
+    }
+
+    fn assert_fits(layout: &MemeLayout, width: u32) {
+        for caption in &layout.captions {
+            let text_width = measure_text_width(&font(), layout.scale, &caption.text);
+            assert!(
+                text_width <= width as f32 * MAX_WIDTH_FRACTION,
+                "{:?} is {text_width} wide",
+                caption.text
+            );
+            // Centred, give or take rounding
+            let right = width as f32 - (caption.x as f32 + text_width);
+            assert!((caption.x as f32 - right).abs() <= 1.0, "{caption:?}");
+        }
+    }
+
+    #[test]
+    fn test_short_message_at_full_size() {
+        let layout = MemeLayout::new(&font(), 640, 480, &metadata("feat: add memes", "repo"));
+
+        assert_eq!(texts(&layout), ["add memes", "feat • repo • abcdef0"]);
+        assert_eq!(layout.scale, PxScale::from(48.0));
+        assert_fits(&layout, 640);
+        let line_height = font().as_scaled(layout.scale).height();
+        assert_eq!(
+            layout.captions[0].y,
+            (480.0 * MARGIN_FRACTION).round() as i32
+        );
+        assert_eq!(
+            layout.captions[1].y,
+            (480.0 - 480.0 * MARGIN_FRACTION - line_height).round() as i32
+        );
+    }
+
+    #[test]
+    fn test_long_message_wraps_then_truncates() {
+        let message = "feat: this commit message goes on and on about everything it changed, \
+                       far longer than two lines of any caption could ever hold at all";
+
+        let layout = MemeLayout::new(&font(), 640, 480, &metadata(message, "repo"));
+
+        let texts = texts(&layout);
+        assert_eq!(texts.len(), 3, "{texts:?}");
+        assert!(texts[0].starts_with("this commit"), "{texts:?}");
+        assert!(texts[1].ends_with('…'), "{texts:?}");
+        assert_eq!(layout.scale.y, 24.0, "shrunk as far as it goes");
+        assert!(layout.captions[1].y > layout.captions[0].y);
+        assert_fits(&layout, 640);
+    }
+
+    #[test]
+    fn test_long_footer_shrinks_text() {
+        let layout = MemeLayout::new(
+            &font(),
+            640,
+            480,
+            &metadata("feat: add memes", "a-repository-with-a-rather-long-name"),
+        );
+
+        assert!(layout.scale.y < 48.0, "{:?}", layout.scale);
+        assert_eq!(
+            texts(&layout)[1],
+            "feat • a-repository-with-a-rather-long-name • abcdef0"
+        );
+        assert_fits(&layout, 640);
+    }
+
+    #[test]
+    fn test_draw_outlines_white_text() {
+        let mut canvas = RgbaImage::from_pixel(320, 240, Rgba([0, 0, 255, 255]));
+
+        draw(&font(), &mut canvas, &metadata("feat: add memes", "repo"));
+
+        let count = |color: [u8; 4]| canvas.pixels().filter(|pixel| pixel.0 == color).count();
+        assert!(count([255, 255, 255, 255]) > 100);
+        assert!(count([0, 0, 0, 255]) > 100);
+        // Nothing in the middle of the image
+        assert!((100..140).all(|y| (0..320).all(|x| canvas.get_pixel(x, y).0 == [0, 0, 255, 255])));
+    }
+}
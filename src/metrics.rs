@@ -40,12 +40,37 @@ fn register_descriptions() {
         "lolcommits_sse_connections_active",
         "Number of active SSE connections"
     );
+    describe_gauge!(
+        "lolcommits_image_cache_bytes",
+        "Bytes held by the in-memory image cache"
+    );
+    describe_gauge!(
+        "lolcommits_gallery_bytes",
+        "Total size of the images in the gallery"
+    );
+    describe_gauge!(
+        "lolcommits_disk_free_bytes",
+        "Free space on the images_dir filesystem at the last check"
+    );
+    describe_gauge!(
+        "lolcommits_processing_queue_depth",
+        "Uploads accepted and waiting for a processing slot"
+    );
+    describe_gauge!("lolcommits_processing_active", "Uploads being processed");
 
     // Counters
     describe_counter!("lolcommits_http_requests_total", "Total HTTP requests");
     describe_counter!(
         "lolcommits_uploads_total",
-        "Total uploads by status (accepted, duplicate_skipped, processed, failed)"
+        "Total uploads by status (accepted, duplicate_rejected, duplicate_skipped, rejected_read_only, rejected_low_disk_space, rejected_busy, rejected_too_large, processed, failed)"
+    );
+    describe_counter!(
+        "lolcommits_image_cache_lookups_total",
+        "Image cache lookups by result (hit, miss)"
+    );
+    describe_counter!(
+        "lolcommits_image_cache_evictions_total",
+        "Images evicted from the in-memory cache to make room"
     );
 
     // Histograms
@@ -115,20 +140,41 @@ pub fn record_upload(status: &str) {
     counter!("lolcommits_uploads_total", "status" => status.to_owned()).increment(1);
 }
 
+pub fn record_image_cache_lookup(result: &str) {
+    counter!("lolcommits_image_cache_lookups_total", "result" => result.to_owned()).increment(1);
+}
+
+pub fn record_image_cache_eviction() {
+    counter!("lolcommits_image_cache_evictions_total").increment(1);
+}
+
 // -- Gauge helpers --
 
 pub fn set_images_total(count: usize) {
     gauge!("lolcommits_images_total").set(count as f64);
 }
 
-pub fn increment_images_total() {
-    gauge!("lolcommits_images_total").increment(1.0);
-}
-
 pub fn set_revision_cache_size(count: usize) {
     gauge!("lolcommits_revision_cache_size").set(count as f64);
 }
 
+pub fn set_image_cache_bytes(bytes: u64) {
+    gauge!("lolcommits_image_cache_bytes").set(bytes as f64);
+}
+
+pub fn set_gallery_bytes(bytes: u64) {
+    gauge!("lolcommits_gallery_bytes").set(bytes as f64);
+}
+
+pub fn set_disk_free_bytes(bytes: u64) {
+    gauge!("lolcommits_disk_free_bytes").set(bytes as f64);
+}
+
+pub fn set_processing_queue(waiting: usize, active: usize) {
+    gauge!("lolcommits_processing_queue_depth").set(waiting as f64);
+    gauge!("lolcommits_processing_active").set(active as f64);
+}
+
 pub fn increment_sse_connections() {
     gauge!("lolcommits_sse_connections_active").increment(1.0);
 }
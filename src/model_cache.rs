@@ -0,0 +1,176 @@
+//! A model loaded from disk once and shared by every upload.
+//!
+//! Loading the segmentation net takes most of a second, so the server keeps it in
+//! memory and only reloads it when the model file's modification time changes.
+
+use crate::error::Result;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+type Loader<T> = Box<dyn Fn(&Path) -> Result<T> + Send + Sync>;
+
+pub struct ModelCache<T> {
+    path: PathBuf,
+    load: Loader<T>,
+    state: Mutex<State<T>>,
+}
+
+struct State<T> {
+    model: Option<T>,
+    /// Modification time of the file at the last load attempt, successful or not.
+    modified: Option<SystemTime>,
+}
+
+impl<T> ModelCache<T> {
+    /// Nothing is loaded until the first [`ModelCache::with`] or [`ModelCache::preload`].
+    pub fn new(
+        path: impl Into<PathBuf>,
+        load: impl Fn(&Path) -> Result<T> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            path: path.into(),
+            load: Box::new(load),
+            state: Mutex::new(State {
+                model: None,
+                modified: None,
+            }),
+        }
+    }
+
+    /// Load the model now. A failure is logged and `false` returned; the next attempt is
+    /// made once the file changes.
+    pub fn preload(&self) -> bool {
+        let mut state = self.state.lock().expect("model cache lock poisoned");
+        self.reload(&mut state);
+        state.model.is_some()
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.state
+            .lock()
+            .expect("model cache lock poisoned")
+            .model
+            .is_some()
+    }
+
+    /// Run `f` with the model, reloading it first if the file changed since it was
+    /// loaded. `None` when no model could be loaded. Callers are serialised.
+    pub fn with<R>(&self, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        let mut state = self.state.lock().expect("model cache lock poisoned");
+        if modified(&self.path) != state.modified {
+            self.reload(&mut state);
+        }
+        state.model.as_mut().map(f)
+    }
+
+    /// Keeps the previous model when loading the new file fails.
+    fn reload(&self, state: &mut State<T>) {
+        state.modified = modified(&self.path);
+        match (self.load)(&self.path) {
+            Ok(model) => {
+                tracing::info!(path = %self.path.display(), "Loaded model");
+                state.model = Some(model);
+            }
+            Err(e) => {
+                tracing::error!(
+                    path = %self.path.display(),
+                    error = %e,
+                    kept_previous = state.model.is_some(),
+                    "Failed to load model"
+                );
+            }
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use std::time::Duration;
+
+    /// "Model" that is the file's contents, failing on "broken".
+    fn load_contents(path: &Path) -> Result<String> {
+        let contents = std::fs::read_to_string(path)?;
+        if contents == "broken" {
+            return Err(Error::GitCommandFailed);
+        }
+        Ok(contents)
+    }
+
+    /// Write `contents` with an explicit mtime, so the tests don't depend on the
+    /// filesystem's timestamp resolution.
+    fn write(path: &Path, contents: &str, mtime_secs: u64) -> Result {
+        std::fs::write(path, contents)?;
+        std::fs::File::options()
+            .write(true)
+            .open(path)?
+            .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(mtime_secs))?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_loads_lazily_and_once() -> Result {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("model.onnx");
+        write(&path, "v1", 1000)?;
+        let cache = ModelCache::new(&path, load_contents);
+        assert!(!cache.is_loaded());
+
+        assert_eq!(cache.with(|m| m.clone()), Some("v1".to_string()));
+
+        // Same mtime, so the file isn't read again
+        write(&path, "v2", 1000)?;
+        assert_eq!(cache.with(|m| m.clone()), Some("v1".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_reloads_when_file_changes() -> Result {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("model.onnx");
+        write(&path, "v1", 1000)?;
+        let cache = ModelCache::new(&path, load_contents);
+        assert!(cache.preload());
+
+        write(&path, "v2", 2000)?;
+
+        assert_eq!(cache.with(|m| m.clone()), Some("v2".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_failed_reload_keeps_previous_model() -> Result {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("model.onnx");
+        write(&path, "v1", 1000)?;
+        let cache = ModelCache::new(&path, load_contents);
+        assert!(cache.preload());
+
+        write(&path, "broken", 2000)?;
+
+        assert_eq!(cache.with(|m| m.clone()), Some("v1".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_model_is_unavailable_until_it_appears() -> Result {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("model.onnx");
+        let cache = ModelCache::new(&path, load_contents);
+
+        assert!(!cache.preload());
+        assert_eq!(cache.with(|m| m.clone()), None);
+
+        write(&path, "downloaded", 1000)?;
+
+        assert_eq!(cache.with(|m| m.clone()), Some("downloaded".to_string()));
+        assert!(cache.is_loaded());
+        Ok(())
+    }
+}
@@ -0,0 +1,209 @@
+//! EXIF orientation for JPEG inputs.
+//!
+//! Phone cameras store photos as shot and record how to display them in the EXIF
+//! Orientation tag, which the `image` crate ignores. Still images loaded by the client
+//! and JPEG uploads decoded by the server are rotated upright here. The decoded pixels
+//! carry no EXIF, so nothing downstream can apply the rotation a second time.
+
+use image::{DynamicImage, ImageFormat, ImageReader, ImageResult};
+use std::io::{BufRead, BufReader, Cursor, Seek};
+use std::path::Path;
+
+/// Decode `bytes`, rotating/flipping JPEGs as their EXIF Orientation tag asks.
+pub fn load_from_memory(bytes: &[u8]) -> ImageResult<DynamicImage> {
+    let format = image::guess_format(bytes)?;
+    let image = image::load_from_memory_with_format(bytes, format)?;
+
+    if format != ImageFormat::Jpeg {
+        return Ok(image);
+    }
+    Ok(upright(image, read_orientation(bytes)))
+}
+
+/// [`load_from_memory`] for an image file, decoded without reading it into memory first.
+pub fn load_from_file(path: &Path) -> ImageResult<DynamicImage> {
+    let reader = ImageReader::open(path)?.with_guessed_format()?;
+    let format = reader.format();
+    let image = reader.decode()?;
+
+    if format != Some(ImageFormat::Jpeg) {
+        return Ok(image);
+    }
+    let orientation = std::fs::File::open(path)
+        .ok()
+        .and_then(|file| orientation_of(&mut BufReader::new(file)));
+    Ok(upright(image, orientation))
+}
+
+fn upright(image: DynamicImage, orientation: Option<u32>) -> DynamicImage {
+    match orientation {
+        Some(orientation) => {
+            tracing::debug!(orientation, "Applying EXIF orientation");
+            apply(image, orientation)
+        }
+        None => image,
+    }
+}
+
+/// The EXIF Orientation (1-8) of a JPEG, `None` when it has no readable tag.
+pub fn read_orientation(bytes: &[u8]) -> Option<u32> {
+    orientation_of(&mut Cursor::new(bytes))
+}
+
+fn orientation_of(reader: &mut (impl BufRead + Seek)) -> Option<u32> {
+    let exif = exif::Reader::new().read_from_container(reader).ok()?;
+    exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)?
+        .value
+        .get_uint(0)
+}
+
+/// Transform `image` so it displays upright for EXIF `orientation`. Unknown values
+/// leave it untouched.
+pub fn apply(image: DynamicImage, orientation: u32) -> DynamicImage {
+    match orientation {
+        2 => image.fliph(),
+        3 => image.rotate180(),
+        4 => image.flipv(),
+        5 => image.rotate90().fliph(),
+        6 => image.rotate90(),
+        7 => image.rotate270().fliph(),
+        8 => image.rotate270(),
+        _ => image,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GenericImageView, Rgb, RgbImage};
+    use test_case::test_case;
+
+    const RED: Rgb<u8> = Rgb([255, 0, 0]);
+    const GREEN: Rgb<u8> = Rgb([0, 255, 0]);
+    const BLUE: Rgb<u8> = Rgb([0, 0, 255]);
+    const WHITE: Rgb<u8> = Rgb([255, 255, 255]);
+
+    /// 32x16 with a distinct colour in each 16x8 quadrant, as stored by the camera.
+    fn stored() -> RgbImage {
+        RgbImage::from_fn(32, 16, |x, y| match (x < 16, y < 8) {
+            (true, true) => RED,
+            (false, true) => GREEN,
+            (true, false) => BLUE,
+            (false, false) => WHITE,
+        })
+    }
+
+    /// APP1 segment holding a big-endian TIFF with just the Orientation tag.
+    fn exif_segment(orientation: u16) -> Vec<u8> {
+        let mut payload = b"Exif\0\0MM\0\x2a\0\0\0\x08".to_vec();
+        payload.extend_from_slice(&1u16.to_be_bytes()); // entries
+        payload.extend_from_slice(&0x0112u16.to_be_bytes()); // Orientation
+        payload.extend_from_slice(&3u16.to_be_bytes()); // SHORT
+        payload.extend_from_slice(&1u32.to_be_bytes()); // count
+        payload.extend_from_slice(&orientation.to_be_bytes());
+        payload.extend_from_slice(&[0, 0]);
+        payload.extend_from_slice(&0u32.to_be_bytes()); // no next IFD
+
+        let mut segment = vec![0xff, 0xe1];
+        segment.extend_from_slice(&(payload.len() as u16 + 2).to_be_bytes());
+        segment.extend(payload);
+        segment
+    }
+
+    fn jpeg(orientation: Option<u16>) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        DynamicImage::ImageRgb8(stored())
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Jpeg)
+            .unwrap();
+        if let Some(orientation) = orientation {
+            // Straight after SOI
+            bytes.splice(2..2, exif_segment(orientation));
+        }
+        bytes
+    }
+
+    fn assert_close(actual: Rgb<u8>, expected: Rgb<u8>, corner: &str) {
+        let close = actual
+            .0
+            .iter()
+            .zip(expected.0)
+            .all(|(a, e)| a.abs_diff(e) < 48);
+        assert!(close, "{corner}: expected {expected:?}, got {actual:?}");
+    }
+
+    /// Corner colours of the upright image: top-left, top-right, bottom-left, bottom-right.
+    fn assert_corners(image: &DynamicImage, expected: [Rgb<u8>; 4]) {
+        let (w, h) = image.dimensions();
+        let image = image.to_rgb8();
+        // Sample a few pixels in from the corners to stay clear of JPEG ringing
+        assert_close(*image.get_pixel(3, 3), expected[0], "top-left");
+        assert_close(*image.get_pixel(w - 4, 3), expected[1], "top-right");
+        assert_close(*image.get_pixel(3, h - 4), expected[2], "bottom-left");
+        assert_close(*image.get_pixel(w - 4, h - 4), expected[3], "bottom-right");
+    }
+
+    #[test_case(1, (32, 16), [RED, GREEN, BLUE, WHITE] ; "normal")]
+    #[test_case(2, (32, 16), [GREEN, RED, WHITE, BLUE] ; "mirrored")]
+    #[test_case(3, (32, 16), [WHITE, BLUE, GREEN, RED] ; "rotated 180")]
+    #[test_case(4, (32, 16), [BLUE, WHITE, RED, GREEN] ; "flipped")]
+    #[test_case(5, (16, 32), [RED, BLUE, GREEN, WHITE] ; "transposed")]
+    #[test_case(6, (16, 32), [BLUE, RED, WHITE, GREEN] ; "rotated 90")]
+    #[test_case(7, (16, 32), [WHITE, GREEN, BLUE, RED] ; "transversed")]
+    #[test_case(8, (16, 32), [GREEN, WHITE, RED, BLUE] ; "rotated 270")]
+    fn test_jpeg_orientation(orientation: u16, dimensions: (u32, u32), corners: [Rgb<u8>; 4]) {
+        let bytes = jpeg(Some(orientation));
+        assert_eq!(read_orientation(&bytes), Some(orientation as u32));
+
+        let image = load_from_memory(&bytes).unwrap();
+
+        assert_eq!(image.dimensions(), dimensions);
+        assert_corners(&image, corners);
+    }
+
+    #[test]
+    fn test_jpeg_without_exif_is_unchanged() {
+        let bytes = jpeg(None);
+        assert_eq!(read_orientation(&bytes), None);
+
+        let image = load_from_memory(&bytes).unwrap();
+
+        assert_eq!(image.dimensions(), (32, 16));
+        assert_corners(&image, [RED, GREEN, BLUE, WHITE]);
+    }
+
+    #[test]
+    fn test_png_is_not_reoriented() {
+        let mut bytes = Vec::new();
+        DynamicImage::ImageRgb8(stored())
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+
+        let image = load_from_memory(&bytes).unwrap();
+
+        assert_eq!(image.to_rgb8(), stored());
+    }
+
+    #[test]
+    fn test_reencoded_output_has_no_orientation() {
+        let image = load_from_memory(&jpeg(Some(6))).unwrap();
+        let mut reencoded = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut reencoded), ImageFormat::Jpeg)
+            .unwrap();
+
+        assert_eq!(read_orientation(&reencoded), None);
+        assert_eq!(load_from_memory(&reencoded).unwrap().dimensions(), (16, 32));
+    }
+
+    #[test]
+    fn test_file_is_oriented_like_memory() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("upload");
+        std::fs::write(&path, jpeg(Some(6))).unwrap();
+
+        let image = load_from_file(&path).unwrap();
+
+        assert_eq!(image.dimensions(), (16, 32));
+        assert_corners(&image, [BLUE, RED, WHITE, GREEN]);
+    }
+}
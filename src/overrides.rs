@@ -0,0 +1,240 @@
+//! Per-upload processing overrides (`lolcommits_upload --override key=value`).
+//!
+//! The client sends them with the upload, the server applies the ones listed in
+//! `ServerConfig::allow_overrides` to its config for that upload only.
+
+use crate::config::ServerConfig;
+use std::collections::BTreeMap;
+
+/// Override keys, each mapping onto a `ServerConfig` field.
+pub const KEYS: &[&str] = &["background", "center_person", "burned_in_chyron"];
+
+pub type Overrides = BTreeMap<String, String>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OverrideError {
+    NotAllowed { key: String, allowed: Vec<String> },
+    InvalidValue { key: String, value: String },
+}
+
+impl std::fmt::Display for OverrideError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            OverrideError::NotAllowed { key, allowed } if allowed.is_empty() => write!(
+                fmt,
+                "override {key:?} is not allowed, this server allows no overrides"
+            ),
+            OverrideError::NotAllowed { key, allowed } => write!(
+                fmt,
+                "override {key:?} is not allowed, allowed: {}",
+                allowed.join(", ")
+            ),
+            OverrideError::InvalidValue { key, value } => {
+                write!(fmt, "invalid value {value:?} for override {key:?}")
+            }
+        }
+    }
+}
+
+/// Parse a `key=value` command line argument.
+pub fn parse_arg(arg: &str) -> std::result::Result<(String, String), String> {
+    match arg.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.trim().to_string()))
+        }
+        _ => Err(format!("expected key=value, got {arg:?}")),
+    }
+}
+
+fn parse_bool(key: &str, value: &str) -> std::result::Result<bool, OverrideError> {
+    value.parse().map_err(|_| OverrideError::InvalidValue {
+        key: key.to_string(),
+        value: value.to_string(),
+    })
+}
+
+/// A `background` override, which may only name a background in the XDG data
+/// directories (see `background_path`), never a path: uploaders mustn't have the
+/// server read files of their choosing.
+fn parse_background(key: &str, value: &str) -> std::result::Result<String, OverrideError> {
+    if value.is_empty() || value.starts_with('.') || value.contains(['/', '\\']) {
+        return Err(OverrideError::InvalidValue {
+            key: key.to_string(),
+            value: value.to_string(),
+        });
+    }
+    Ok(value.to_string())
+}
+
+/// Apply `overrides` to `config`, rejecting keys not in `allowed` and unparseable
+/// values. `config` is left untouched on error.
+pub fn apply(
+    config: &mut ServerConfig,
+    overrides: &Overrides,
+    allowed: &[String],
+) -> std::result::Result<(), OverrideError> {
+    let mut updated = config.clone();
+
+    for (key, value) in overrides {
+        if !allowed.contains(key) || !KEYS.contains(&key.as_str()) {
+            return Err(OverrideError::NotAllowed {
+                key: key.clone(),
+                allowed: allowed
+                    .iter()
+                    .filter(|key| KEYS.contains(&key.as_str()))
+                    .cloned()
+                    .collect(),
+            });
+        }
+
+        match key.as_str() {
+            "background" => updated.background_path = parse_background(key, value)?,
+            "center_person" => updated.center_person = parse_bool(key, value)?,
+            "burned_in_chyron" => updated.burned_in_chyron = parse_bool(key, value)?,
+            _ => unreachable!("checked against KEYS above"),
+        }
+    }
+
+    *config = updated;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    fn overrides(pairs: &[(&str, &str)]) -> Overrides {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn allowed(keys: &[&str]) -> Vec<String> {
+        keys.iter().map(|k| k.to_string()).collect()
+    }
+
+    #[test_case("background=party", Some(("background", "party")) ; "simple")]
+    #[test_case("center_person = false", Some(("center_person", "false")) ; "spaces")]
+    #[test_case("background=a=b", Some(("background", "a=b")) ; "equals in value")]
+    #[test_case("background", None ; "missing value")]
+    #[test_case("=party", None ; "missing key")]
+    fn test_parse_arg(arg: &str, expected: Option<(&str, &str)>) {
+        assert_eq!(
+            parse_arg(arg).ok(),
+            expected.map(|(k, v)| (k.to_string(), v.to_string()))
+        );
+    }
+
+    #[test]
+    fn test_apply_overrides() {
+        let mut config = ServerConfig::default();
+
+        apply(
+            &mut config,
+            &overrides(&[("background", "party"), ("center_person", "false")]),
+            &allowed(&["background", "center_person"]),
+        )
+        .unwrap();
+
+        assert_eq!(config.background_path, "party");
+        assert!(!config.center_person);
+    }
+
+    #[test]
+    fn test_apply_rejects_non_whitelisted() {
+        let mut config = ServerConfig::default();
+
+        let error = apply(
+            &mut config,
+            &overrides(&[("background", "party"), ("center_person", "false")]),
+            &allowed(&["background"]),
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            error,
+            OverrideError::NotAllowed {
+                key: "center_person".to_string(),
+                allowed: allowed(&["background"]),
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "override \"center_person\" is not allowed, allowed: background"
+        );
+        // Nothing is applied when any override is rejected
+        assert_eq!(
+            config.background_path,
+            ServerConfig::default().background_path
+        );
+    }
+
+    #[test]
+    fn test_apply_rejects_everything_by_default() {
+        let error = apply(
+            &mut ServerConfig::default(),
+            &overrides(&[("background", "party")]),
+            &ServerConfig::default().allow_overrides,
+        )
+        .unwrap_err();
+
+        assert!(error.to_string().contains("allows no overrides"));
+    }
+
+    #[test]
+    fn test_apply_ignores_unknown_whitelisted_keys() {
+        let error = apply(
+            &mut ServerConfig::default(),
+            &overrides(&[("images_dir", "/tmp")]),
+            &allowed(&["images_dir", "background"]),
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            error,
+            OverrideError::NotAllowed {
+                key: "images_dir".to_string(),
+                allowed: allowed(&["background"]),
+            }
+        );
+    }
+
+    #[test_case("/etc/passwd" ; "absolute path")]
+    #[test_case("../../../etc/passwd" ; "parent")]
+    #[test_case("backgrounds/party" ; "relative path")]
+    #[test_case("..\\secret" ; "backslash")]
+    #[test_case("" ; "empty")]
+    fn test_apply_rejects_background_paths(value: &str) {
+        let mut config = ServerConfig::default();
+
+        let error = apply(
+            &mut config,
+            &overrides(&[("background", value)]),
+            &allowed(&["background"]),
+        )
+        .unwrap_err();
+
+        assert!(
+            matches!(error, OverrideError::InvalidValue { .. }),
+            "{error}"
+        );
+        assert_eq!(
+            config.background_path,
+            ServerConfig::default().background_path
+        );
+    }
+
+    #[test]
+    fn test_apply_rejects_invalid_bool() {
+        let error = apply(
+            &mut ServerConfig::default(),
+            &overrides(&[("center_person", "maybe")]),
+            &allowed(&["center_person"]),
+        )
+        .unwrap_err();
+
+        assert!(matches!(error, OverrideError::InvalidValue { .. }));
+    }
+}
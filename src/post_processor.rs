@@ -0,0 +1,578 @@
+//! Image post-processing pipeline run on every upload.
+//!
+//! Stages are named in `ServerConfig::post_processors` and run in that order, each
+//! taking the previous stage's output. What an upload gets is first resolved into a
+//! [`ProcessingPlan`], which `GET /api/pipeline/plan` also reports without an image.
+
+use crate::config::{AnimationChyron, BurnedInChyronConfig, Config, ServerConfig};
+use crate::error::{Error, Result};
+use crate::git::CommitMetadata;
+use crate::image_processor;
+use crate::overrides::{self, Overrides};
+use crate::segmentation::SegmentationModel;
+use image::DynamicImage;
+use serde::Serialize;
+use std::sync::Arc;
+
+/// Stage names accepted in `post_processors`.
+pub const STAGES: &[&str] = &["background", "chyron"];
+
+pub fn default_post_processors() -> Vec<String> {
+    STAGES.iter().map(|stage| stage.to_string()).collect()
+}
+
+/// A single step applied to an uploaded image.
+pub trait PostProcessor: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn apply(&self, image: DynamicImage, metadata: &CommitMetadata) -> Result<DynamicImage>;
+}
+
+/// Segment the person and composite them onto the configured background.
+pub struct Background {
+    config: ServerConfig,
+    model: Arc<SegmentationModel>,
+}
+
+impl PostProcessor for Background {
+    fn name(&self) -> &'static str {
+        "background"
+    }
+
+    fn apply(&self, image: DynamicImage, _metadata: &CommitMetadata) -> Result<DynamicImage> {
+        image_processor::replace_background(&self.config, &self.model, image)
+    }
+}
+
+/// Burn the commit information into the image.
+pub struct Chyron {
+    config: BurnedInChyronConfig,
+}
+
+impl PostProcessor for Chyron {
+    fn name(&self) -> &'static str {
+        "chyron"
+    }
+
+    fn apply(&self, image: DynamicImage, metadata: &CommitMetadata) -> Result<DynamicImage> {
+        crate::chyron::burn_in_chyron(&self.config, image, metadata)
+    }
+}
+
+/// Reject stage names that aren't in [`STAGES`].
+pub fn validate(names: &[String]) -> Result {
+    match names.iter().find(|name| !STAGES.contains(&name.as_str())) {
+        Some(name) => Err(Error::UnknownPostProcessor { name: name.clone() }),
+        None => Ok(()),
+    }
+}
+
+/// How an upload will be processed, resolved from the config and the upload's metadata
+/// and overrides.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessingPlan {
+    /// Stages that will run, in order.
+    pub stages: Vec<&'static str>,
+    /// `None` when the background stage doesn't run.
+    pub background: Option<BackgroundPlan>,
+    /// `None` when the chyron stage doesn't run.
+    pub chyron: Option<BurnedInChyronConfig>,
+    /// Overrides applied on top of the config.
+    pub overrides: Overrides,
+    /// Overrides uploads may request.
+    pub allowed_overrides: Vec<String>,
+    /// The server config with the overrides applied.
+    #[serde(skip)]
+    server: ServerConfig,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BackgroundPlan {
+    pub path: String,
+    /// As reported by `/api/health`: configured, embedded_default, missing or disabled.
+    pub status: &'static str,
+    pub center_person: bool,
+    pub center_person_max_off_frame: f32,
+}
+
+/// Resolve what processing an upload of `metadata` with `requested` overrides gets.
+/// `burned_in_chyron` is the upload's own choice of chyron, which needs no
+/// `allow_overrides` entry. Fails on unknown stage names and overrides the config doesn't
+/// allow.
+pub fn resolve_plan(
+    config: &Config,
+    metadata: &CommitMetadata,
+    requested: &Overrides,
+    burned_in_chyron: Option<bool>,
+) -> Result<ProcessingPlan> {
+    let mut server_config = config.server.clone().unwrap_or_default();
+    validate(&server_config.post_processors)?;
+
+    // A background requested for this upload wins over the one for its commit type
+    if !requested.contains_key("background")
+        && let Some(path_spec) = server_config.backgrounds.get(&metadata.commit_type)
+    {
+        if path_spec.eq_ignore_ascii_case(image_processor::NO_BACKGROUND) {
+            server_config.background_path = path_spec.clone();
+        } else {
+            match image_processor::resolve_background_path(path_spec) {
+                Ok(_) => server_config.background_path = path_spec.clone(),
+                Err(e) => tracing::warn!(
+                    commit_type = %metadata.commit_type,
+                    background = %path_spec,
+                    error = %e,
+                    "Background for commit type not found, using background_path"
+                ),
+            }
+        }
+    }
+
+    if !requested.is_empty() {
+        let allowed = server_config.allow_overrides.clone();
+        overrides::apply(&mut server_config, requested, &allowed)
+            .map_err(|e| std::io::Error::other(e.to_string()))?;
+    }
+    if let Some(burned_in_chyron) = burned_in_chyron {
+        server_config.burned_in_chyron = burned_in_chyron;
+    }
+
+    let mut stages = Vec::new();
+    for name in &server_config.post_processors {
+        match name.as_str() {
+            "background" => stages.push("background"),
+            "chyron" if server_config.burned_in_chyron => stages.push("chyron"),
+            "chyron" => tracing::debug!("Chyron disabled"),
+            _ => unreachable!("validated above"),
+        }
+    }
+
+    let background = stages.contains(&"background").then(|| BackgroundPlan {
+        path: server_config.background_path.clone(),
+        status: image_processor::Background::resolve(&server_config.background_path).status(),
+        center_person: server_config.center_person,
+        center_person_max_off_frame: server_config.center_person_max_off_frame,
+    });
+    let chyron = stages
+        .contains(&"chyron")
+        .then(|| config.burned_in_chyron.clone().unwrap_or_default());
+
+    tracing::debug!(
+        repo = %metadata.repo_name,
+        commit_type = %metadata.commit_type,
+        branch = %metadata.branch_name,
+        ?stages,
+        "Resolved processing plan"
+    );
+
+    Ok(ProcessingPlan {
+        stages,
+        background,
+        chyron,
+        overrides: requested.clone(),
+        allowed_overrides: server_config.allow_overrides.clone(),
+        server: server_config,
+    })
+}
+
+/// Build the stages of `plan`.
+pub fn build(plan: &ProcessingPlan, model: &Arc<SegmentationModel>) -> Vec<Box<dyn PostProcessor>> {
+    plan.stages
+        .iter()
+        .map(|&name| -> Box<dyn PostProcessor> {
+            match name {
+                "background" => Box::new(Background {
+                    config: plan.server.clone(),
+                    model: model.clone(),
+                }),
+                "chyron" => Box::new(Chyron {
+                    config: plan.chyron.clone().unwrap_or_default(),
+                }),
+                _ => unreachable!("resolve_plan only yields known stages"),
+            }
+        })
+        .collect()
+}
+
+/// Run `image` through every stage in order.
+pub fn run(
+    stages: &[Box<dyn PostProcessor>],
+    image: DynamicImage,
+    metadata: &CommitMetadata,
+) -> Result<DynamicImage> {
+    stages.iter().try_fold(image, |image, stage| {
+        let image = stage.apply(image, metadata)?;
+        tracing::debug!(stage = stage.name(), "Applied post-processor");
+        Ok(image)
+    })
+}
+
+/// Run each frame of an animation through every stage in order, leaving the chyron
+/// off all but the last frame unless `chyron` is [`AnimationChyron::All`]. A single
+/// frame is processed exactly like [`run`].
+pub fn run_frames(
+    stages: &[Box<dyn PostProcessor>],
+    frames: Vec<DynamicImage>,
+    metadata: &CommitMetadata,
+    chyron: AnimationChyron,
+) -> Result<Vec<DynamicImage>> {
+    let last = frames.len().saturating_sub(1);
+    frames
+        .into_iter()
+        .enumerate()
+        .map(|(index, frame)| {
+            stages
+                .iter()
+                .filter(|stage| {
+                    stage.name() != "chyron" || chyron == AnimationChyron::All || index == last
+                })
+                .try_fold(frame, |frame, stage| stage.apply(frame, metadata))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::DiffStats;
+    use std::sync::Mutex;
+    use test_case::test_case;
+
+    type Calls = Arc<Mutex<Vec<&'static str>>>;
+
+    struct Recording {
+        name: &'static str,
+        calls: Calls,
+        fail: bool,
+    }
+
+    impl PostProcessor for Recording {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn apply(&self, image: DynamicImage, _metadata: &CommitMetadata) -> Result<DynamicImage> {
+            self.calls.lock().unwrap().push(self.name);
+            if self.fail {
+                return Err(Error::GitCommandFailed);
+            }
+            // Grow the image so each stage's effect is visible downstream
+            Ok(DynamicImage::new_rgba8(image.width() + 1, image.height()))
+        }
+    }
+
+    fn recording(
+        names: &[&'static str],
+        fail: Option<&str>,
+    ) -> (Vec<Box<dyn PostProcessor>>, Calls) {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let stages = names
+            .iter()
+            .map(|&name| {
+                Box::new(Recording {
+                    name,
+                    calls: calls.clone(),
+                    fail: fail == Some(name),
+                }) as Box<dyn PostProcessor>
+            })
+            .collect();
+        (stages, calls)
+    }
+
+    fn metadata() -> CommitMetadata {
+        CommitMetadata {
+            path: std::path::PathBuf::new(),
+            revision: "abc1234".to_string(),
+            message: "feat: pipeline".to_string(),
+            commit_type: "feat".to_string(),
+            scope: String::new(),
+            timestamp: "2024-01-15 12:34:56".to_string(),
+            repo_name: "repo".to_string(),
+            branch_name: "main".to_string(),
+            author_name: String::new(),
+            author_email: String::new(),
+            breaking: false,
+            co_authors: Vec::new(),
+            stats: DiffStats {
+                files_changed: 0,
+                insertions: 0,
+                deletions: 0,
+            },
+        }
+    }
+
+    /// Never loaded, no test builds a pipeline that runs the background stage.
+    fn model() -> Arc<SegmentationModel> {
+        Arc::new(SegmentationModel::open(
+            "/nonexistent/models",
+            &Default::default(),
+            Default::default(),
+            Default::default(),
+        ))
+    }
+
+    fn pipeline(config: &Config) -> Result<Vec<Box<dyn PostProcessor>>> {
+        let plan = resolve_plan(config, &metadata(), &Overrides::new(), None)?;
+        Ok(build(&plan, &model()))
+    }
+
+    fn names(stages: &[Box<dyn PostProcessor>]) -> Vec<&'static str> {
+        stages.iter().map(|stage| stage.name()).collect()
+    }
+
+    fn config(post_processors: &[&str], burned_in_chyron: bool) -> Config {
+        Config {
+            server: Some(ServerConfig {
+                post_processors: post_processors.iter().map(|s| s.to_string()).collect(),
+                burned_in_chyron,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_run_applies_stages_in_order() -> Result {
+        let (stages, calls) = recording(&["second", "first", "third"], None);
+
+        let image = run(&stages, DynamicImage::new_rgba8(1, 1), &metadata())?;
+
+        assert_eq!(*calls.lock().unwrap(), vec!["second", "first", "third"]);
+        assert_eq!(image.width(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_stops_at_failing_stage() {
+        let (stages, calls) = recording(&["first", "broken", "never"], Some("broken"));
+
+        assert!(run(&stages, DynamicImage::new_rgba8(1, 1), &metadata()).is_err());
+        assert_eq!(*calls.lock().unwrap(), vec!["first", "broken"]);
+    }
+
+    #[test]
+    fn test_run_empty_pipeline_is_identity() -> Result {
+        let image = run(&[], DynamicImage::new_rgba8(3, 2), &metadata())?;
+        assert_eq!((image.width(), image.height()), (3, 2));
+        Ok(())
+    }
+
+    #[test_case(AnimationChyron::Last, vec!["background", "background", "background", "chyron"] ; "last frame")]
+    #[test_case(AnimationChyron::All, vec!["background", "chyron", "background", "chyron", "background", "chyron"] ; "all frames")]
+    fn test_run_frames_places_chyron(chyron: AnimationChyron, expected: Vec<&str>) -> Result {
+        let (stages, calls) = recording(&["background", "chyron"], None);
+        let frames = vec![DynamicImage::new_rgba8(1, 1); 3];
+
+        let processed = run_frames(&stages, frames, &metadata(), chyron)?;
+
+        assert_eq!(processed.len(), 3);
+        assert_eq!(*calls.lock().unwrap(), expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_default_order() -> Result {
+        let stages = pipeline(&Config::default())?;
+        assert_eq!(names(&stages), vec!["background", "chyron"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_respects_configured_order() -> Result {
+        let stages = pipeline(&config(&["chyron", "background"], true))?;
+        assert_eq!(names(&stages), vec!["chyron", "background"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_skips_disabled_chyron() -> Result {
+        let stages = pipeline(&config(&["background", "chyron"], false))?;
+        assert_eq!(names(&stages), vec!["background"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_rejects_unknown_stage() {
+        let result = pipeline(&config(&["background", "watermark"], true));
+        assert!(matches!(
+            result,
+            Err(Error::UnknownPostProcessor { name }) if name == "watermark"
+        ));
+    }
+
+    fn plan_config(server: ServerConfig) -> Config {
+        Config {
+            server: Some(server),
+            burned_in_chyron: Some(BurnedInChyronConfig {
+                title_font_size: 40.0,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn requested(pairs: &[(&str, &str)]) -> Overrides {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_plan_with_configured_background_and_chyron() -> Result {
+        let dir = tempfile::tempdir()?;
+        let background = dir.path().join("bg.png");
+        image::RgbImage::new(2, 2).save(&background)?;
+        let config = plan_config(ServerConfig {
+            background_path: background.display().to_string(),
+            center_person: false,
+            ..Default::default()
+        });
+
+        let plan = resolve_plan(&config, &metadata(), &Overrides::new(), None)?;
+
+        assert_eq!(plan.stages, vec!["background", "chyron"]);
+        let background_plan = plan.background.unwrap();
+        assert_eq!(background_plan.status, "configured");
+        assert!(!background_plan.center_person);
+        assert_eq!(plan.chyron.unwrap().title_font_size, 40.0);
+        assert!(plan.overrides.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan_disabled_background_and_chyron() -> Result {
+        let config = plan_config(ServerConfig {
+            background_path: "none".to_string(),
+            post_processors: vec!["background".to_string()],
+            burned_in_chyron: false,
+            ..Default::default()
+        });
+
+        let plan = resolve_plan(&config, &metadata(), &Overrides::new(), None)?;
+
+        assert_eq!(plan.stages, vec!["background"]);
+        assert_eq!(plan.background.unwrap().status, "disabled");
+        assert!(plan.chyron.is_none());
+        Ok(())
+    }
+
+    #[test_case(true, None, true ; "config default")]
+    #[test_case(true, Some(false), false ; "upload turns it off")]
+    #[test_case(false, Some(true), true ; "upload turns it on")]
+    fn test_plan_honours_upload_chyron_choice(
+        configured: bool,
+        requested: Option<bool>,
+        expected: bool,
+    ) -> Result {
+        let config = plan_config(ServerConfig {
+            background_path: "none".to_string(),
+            burned_in_chyron: configured,
+            ..Default::default()
+        });
+
+        let plan = resolve_plan(&config, &metadata(), &Overrides::new(), requested)?;
+
+        assert_eq!(plan.stages.contains(&"chyron"), expected);
+        assert_eq!(plan.chyron.is_some(), expected);
+        assert!(plan.stages.contains(&"background"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan_applies_allowed_overrides() -> Result {
+        let config = plan_config(ServerConfig {
+            allow_overrides: vec!["background".to_string(), "burned_in_chyron".to_string()],
+            ..Default::default()
+        });
+        let overrides = requested(&[("background", "none"), ("burned_in_chyron", "false")]);
+
+        let plan = resolve_plan(&config, &metadata(), &overrides, None)?;
+
+        assert_eq!(plan.stages, vec!["background"]);
+        assert_eq!(plan.background.unwrap().status, "disabled");
+        assert_eq!(plan.overrides, overrides);
+        assert_eq!(
+            plan.allowed_overrides,
+            vec!["background", "burned_in_chyron"]
+        );
+        Ok(())
+    }
+
+    #[test_case("feat", "feat.png" ; "matching type")]
+    #[test_case("docs", "default.png" ; "other type")]
+    #[test_case("fix", "default.png" ; "missing file falls back")]
+    fn test_plan_picks_background_by_commit_type(commit_type: &str, expected: &str) -> Result {
+        let dir = tempfile::tempdir()?;
+        for name in ["default.png", "feat.png"] {
+            image::RgbImage::new(2, 2).save(dir.path().join(name))?;
+        }
+        let path = |name: &str| dir.path().join(name).display().to_string();
+        let config = plan_config(ServerConfig {
+            background_path: path("default.png"),
+            backgrounds: [("feat", path("feat.png")), ("fix", path("missing.png"))]
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+            ..Default::default()
+        });
+        let metadata = CommitMetadata {
+            commit_type: commit_type.to_string(),
+            ..metadata()
+        };
+
+        let plan = resolve_plan(&config, &metadata, &Overrides::new(), None)?;
+
+        assert_eq!(plan.background.unwrap().path, path(expected));
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan_background_override_beats_commit_type() -> Result {
+        let config = plan_config(ServerConfig {
+            backgrounds: [("feat".to_string(), "none".to_string())].into(),
+            allow_overrides: vec!["background".to_string()],
+            ..Default::default()
+        });
+        let dir = tempfile::tempdir()?;
+        image::RgbImage::new(2, 2).save(dir.path().join("party.png"))?;
+        let overrides = requested(&[("background", "party")]);
+
+        let plan = temp_env::with_var("XDG_DATA_HOME", Some(dir.path()), || {
+            resolve_plan(&config, &metadata(), &overrides, None)
+        })?;
+
+        assert_eq!(plan.background.unwrap().status, "configured");
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan_rejects_disallowed_overrides() {
+        let config = plan_config(ServerConfig::default());
+
+        let result = resolve_plan(
+            &config,
+            &metadata(),
+            &requested(&[("background", "none")]),
+            None,
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_plan_serializes_without_server_config() -> Result {
+        let config = plan_config(ServerConfig {
+            background_path: "none".to_string(),
+            ..Default::default()
+        });
+
+        let plan = resolve_plan(&config, &metadata(), &Overrides::new(), None)?;
+        let json = serde_json::to_value(&plan)?;
+
+        assert_eq!(json["stages"], serde_json::json!(["background", "chyron"]));
+        assert_eq!(json["background"]["status"], "disabled");
+        assert_eq!(json["chyron"]["title_font_size"], 40.0);
+        assert!(json.get("server").is_none());
+        Ok(())
+    }
+}
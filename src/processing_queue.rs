@@ -0,0 +1,143 @@
+//! Admission control for processing uploads.
+//!
+//! Background replacement is CPU-heavy, so at most `processing_concurrency` uploads are
+//! processed at once and the rest wait their turn. Once `processing_backlog` are waiting,
+//! further uploads are turned away until the queue drains rather than piling up in memory.
+
+use crate::config::ServerConfig;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+pub struct ProcessingQueue {
+    slots: Arc<Semaphore>,
+    concurrency: usize,
+    /// Uploads accepted and not yet finished, whether waiting or being processed.
+    pending: AtomicUsize,
+    backlog: usize,
+}
+
+impl ProcessingQueue {
+    /// A concurrency of 0 is taken as 1, so uploads can't wait forever.
+    pub fn new(concurrency: usize, backlog: usize) -> Self {
+        let concurrency = concurrency.max(1);
+        Self {
+            slots: Arc::new(Semaphore::new(concurrency)),
+            concurrency,
+            pending: AtomicUsize::new(0),
+            backlog,
+        }
+    }
+
+    pub fn from_config(config: &ServerConfig) -> Self {
+        Self::new(config.processing_concurrency, config.processing_backlog)
+    }
+
+    /// Take a place in the queue for an upload, `None` when the backlog is full.
+    pub fn enqueue(self: &Arc<Self>) -> Option<Ticket> {
+        let capacity = self.concurrency + self.backlog;
+        self.pending
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |pending| {
+                (pending < capacity).then_some(pending + 1)
+            })
+            .ok()?;
+        self.report();
+        Some(Ticket {
+            queue: Arc::clone(self),
+            slot: None,
+        })
+    }
+
+    /// Uploads waiting for a slot.
+    pub fn waiting(&self) -> usize {
+        self.pending
+            .load(Ordering::Acquire)
+            .saturating_sub(self.active())
+    }
+
+    /// Uploads being processed.
+    pub fn active(&self) -> usize {
+        self.concurrency - self.slots.available_permits()
+    }
+
+    fn report(&self) {
+        crate::metrics::set_processing_queue(self.waiting(), self.active());
+    }
+}
+
+/// An upload's place in the [`ProcessingQueue`], given up when dropped.
+pub struct Ticket {
+    queue: Arc<ProcessingQueue>,
+    slot: Option<OwnedSemaphorePermit>,
+}
+
+impl Ticket {
+    /// Wait for a processing slot, held until the ticket is dropped.
+    pub async fn start(&mut self) {
+        if self.slot.is_some() {
+            return;
+        }
+        let slot = Arc::clone(&self.queue.slots)
+            .acquire_owned()
+            .await
+            .expect("processing slots are never closed");
+        self.slot = Some(slot);
+        self.queue.report();
+    }
+}
+
+impl Drop for Ticket {
+    fn drop(&mut self) {
+        drop(self.slot.take());
+        self.queue.pending.fetch_sub(1, Ordering::AcqRel);
+        self.queue.report();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_backlog_bounds_pending_uploads() {
+        let queue = Arc::new(ProcessingQueue::new(1, 2));
+
+        let mut first = queue.enqueue().unwrap();
+        let second = queue.enqueue().unwrap();
+        let third = queue.enqueue().unwrap();
+        assert!(queue.enqueue().is_none(), "one slot and two waiting");
+
+        first.start().await;
+        assert_eq!((queue.active(), queue.waiting()), (1, 2));
+
+        drop(second);
+        assert_eq!((queue.active(), queue.waiting()), (1, 1));
+        assert!(queue.enqueue().is_some());
+        drop(third);
+        drop(first);
+        assert_eq!((queue.active(), queue.waiting()), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn test_waits_for_a_free_slot() {
+        let queue = Arc::new(ProcessingQueue::new(1, 1));
+        let mut first = queue.enqueue().unwrap();
+        first.start().await;
+
+        let mut second = queue.enqueue().unwrap();
+        let waiting = tokio::time::timeout(std::time::Duration::from_millis(20), second.start());
+        assert!(waiting.await.is_err(), "started while the slot was taken");
+
+        drop(first);
+        tokio::time::timeout(std::time::Duration::from_secs(1), second.start())
+            .await
+            .expect("slot not handed on");
+        assert_eq!(queue.active(), 1);
+    }
+
+    #[test]
+    fn test_zero_concurrency_still_processes() {
+        let queue = Arc::new(ProcessingQueue::new(0, 0));
+        assert!(queue.enqueue().is_some());
+    }
+}
@@ -0,0 +1,114 @@
+//! Read-only mode for lolcommitsd.
+//!
+//! While enabled the gallery keeps serving images and events but refuses any request
+//! that would modify images_dir. The runtime toggle is persisted to a small state file
+//! so it survives restarts.
+
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Name of the persisted state file within the server's state directory.
+const STATE_FILE_NAME: &str = "read_only.json";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PersistedState {
+    enabled: bool,
+}
+
+#[derive(Debug)]
+pub struct ReadOnlyMode {
+    enabled: AtomicBool,
+    state_path: PathBuf,
+}
+
+impl ReadOnlyMode {
+    /// Restore the mode from the state file in `state_dir`, falling back to `default`
+    /// (the `read_only` config flag) when nothing usable has been persisted.
+    pub fn load(state_dir: impl AsRef<Path>, default: bool) -> Self {
+        let state_path = state_dir.as_ref().join(STATE_FILE_NAME);
+
+        let enabled = match std::fs::read_to_string(&state_path) {
+            Ok(contents) => match serde_json::from_str::<PersistedState>(&contents) {
+                Ok(state) => {
+                    tracing::info!(path = %state_path.display(), enabled = state.enabled, "Restored read-only mode");
+                    state.enabled
+                }
+                Err(e) => {
+                    tracing::warn!(path = %state_path.display(), error = %e, "Ignoring unreadable read-only state file");
+                    default
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => default,
+            Err(e) => {
+                tracing::warn!(path = %state_path.display(), error = %e, "Failed to read read-only state file");
+                default
+            }
+        };
+
+        Self {
+            enabled: AtomicBool::new(enabled),
+            state_path,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Change the mode and persist it. The in-memory flag is only updated once the
+    /// state file has been written.
+    pub fn set(&self, enabled: bool) -> Result {
+        let contents = serde_json::to_string(&PersistedState { enabled })?;
+        crate::storage::atomic_write(
+            self.state_path.parent().unwrap_or(Path::new(".")),
+            STATE_FILE_NAME,
+            contents,
+        )?;
+
+        self.enabled.store(enabled, Ordering::Relaxed);
+        tracing::info!(enabled, "Read-only mode changed");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_without_state_file_uses_default() -> Result {
+        let dir = tempfile::tempdir()?;
+
+        assert!(!ReadOnlyMode::load(dir.path(), false).is_enabled());
+        assert!(ReadOnlyMode::load(dir.path(), true).is_enabled());
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_persists_across_reload() -> Result {
+        let dir = tempfile::tempdir()?;
+        let state_dir = dir.path().join("state");
+
+        let mode = ReadOnlyMode::load(&state_dir, false);
+        mode.set(true)?;
+        assert!(mode.is_enabled());
+
+        // Persisted value wins over the config default
+        assert!(ReadOnlyMode::load(&state_dir, false).is_enabled());
+
+        mode.set(false)?;
+        assert!(!ReadOnlyMode::load(&state_dir, true).is_enabled());
+        Ok(())
+    }
+
+    #[test]
+    fn test_corrupt_state_file_uses_default() -> Result {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join(STATE_FILE_NAME), "not json")?;
+
+        assert!(ReadOnlyMode::load(dir.path(), true).is_enabled());
+        Ok(())
+    }
+}
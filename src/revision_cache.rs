@@ -0,0 +1,174 @@
+//! Revisions in the gallery, for rejecting duplicate uploads and `/api/exists`.
+//!
+//! Derived from the [`ImageIndex`] and rebuilt whenever it changes, so uploads, deletions
+//! and reprocessing are reflected without bookkeeping of their own. Lookups first check
+//! whether `images_dir` changed on disk, so images deleted by hand or by a cleanup job
+//! stop counting as duplicates, and images the index missed start to.
+
+use crate::image_index::ImageIndex;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedRevision {
+    pub repo_name: String,
+    pub filename: String,
+}
+
+pub struct RevisionCache {
+    index: Arc<ImageIndex>,
+    state: RwLock<State>,
+}
+
+#[derive(Default)]
+struct State {
+    revisions: HashMap<String, CachedRevision>,
+    /// Index generation the revisions were read from.
+    generation: Option<u64>,
+}
+
+impl RevisionCache {
+    pub fn new(index: Arc<ImageIndex>) -> Self {
+        let cache = Self {
+            index,
+            state: RwLock::new(State::default()),
+        };
+        cache.refresh();
+        cache
+    }
+
+    /// The gallery image of `revision`, the newest when it was uploaded more than once.
+    pub fn get(&self, revision: &str) -> Option<CachedRevision> {
+        self.index.rescan_if_changed();
+        self.refresh();
+        self.state
+            .read()
+            .expect("revision cache lock poisoned")
+            .revisions
+            .get(revision)
+            .cloned()
+    }
+
+    pub fn contains(&self, revision: &str) -> bool {
+        self.get(revision).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.state
+            .read()
+            .expect("revision cache lock poisoned")
+            .revisions
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Rebuild from the index if it changed since the last build.
+    fn refresh(&self) {
+        let generation = self.index.generation();
+        let state = self.state.read().expect("revision cache lock poisoned");
+        if state.generation == Some(generation) {
+            return;
+        }
+        drop(state);
+
+        // Oldest first so the newest file for a revision wins
+        let mut images = self.index.list();
+        images.reverse();
+        let revisions: HashMap<String, CachedRevision> = images
+            .into_iter()
+            .filter_map(|image| {
+                let filename = image.path.file_name()?.to_str()?.to_string();
+                let entry = CachedRevision {
+                    repo_name: image.repo_name,
+                    filename,
+                };
+                Some((image.revision, entry))
+            })
+            .collect();
+
+        tracing::debug!(
+            count = revisions.len(),
+            generation,
+            "Rebuilt revision cache"
+        );
+        crate::metrics::set_revision_cache_size(revisions.len());
+        let mut state = self.state.write().expect("revision cache lock poisoned");
+        state.revisions = revisions;
+        state.generation = Some(generation);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Result;
+    use std::path::{Path, PathBuf};
+
+    fn touch(dir: &Path, filename: &str) -> Result<PathBuf> {
+        let path = dir.join(filename);
+        std::fs::write(&path, b"not really a png")?;
+        Ok(path)
+    }
+
+    fn filename(cache: &RevisionCache, revision: &str) -> Option<String> {
+        cache.get(revision).map(|entry| entry.filename)
+    }
+
+    #[test]
+    fn test_newest_upload_of_a_revision_wins() -> Result {
+        let dir = tempfile::tempdir()?;
+        touch(dir.path(), "repo-20240101-120000-abc1234.png")?;
+        touch(dir.path(), "repo-20240201-120000-abc1234.png")?;
+
+        let cache = RevisionCache::new(Arc::new(ImageIndex::open(dir.path())));
+
+        assert_eq!(cache.len(), 1);
+        assert_eq!(
+            filename(&cache, "abc1234").as_deref(),
+            Some("repo-20240201-120000-abc1234.png")
+        );
+        assert_eq!(
+            cache.get("abc1234").map(|entry| entry.repo_name).as_deref(),
+            Some("repo")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_follows_the_index() -> Result {
+        let dir = tempfile::tempdir()?;
+        let index = Arc::new(ImageIndex::open(dir.path()));
+        let cache = RevisionCache::new(index.clone());
+        assert!(cache.is_empty());
+
+        let path = touch(dir.path(), "repo-20240101-120000-abc1234.png")?;
+        index.insert(&path);
+        assert!(cache.contains("abc1234"));
+
+        index.remove("repo-20240101-120000-abc1234.png");
+        std::fs::remove_file(path)?;
+        assert!(!cache.contains("abc1234"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_notices_images_deleted_and_added_by_hand() -> Result {
+        let dir = tempfile::tempdir()?;
+        let deleted = touch(dir.path(), "repo-20240101-120000-abc1234.png")?;
+        let cache = RevisionCache::new(Arc::new(ImageIndex::open(dir.path())));
+        assert!(cache.contains("abc1234"));
+
+        std::fs::remove_file(deleted)?;
+        touch(dir.path(), "repo-20240201-120000-def5678.png")?;
+        // Directory mtimes can be as coarse as a second
+        let later = std::time::SystemTime::now() + std::time::Duration::from_secs(2);
+        std::fs::File::open(dir.path())?.set_modified(later)?;
+
+        assert!(!cache.contains("abc1234"));
+        assert!(cache.contains("def5678"));
+        Ok(())
+    }
+}
@@ -1,77 +1,401 @@
+use crate::config::{DnnBackend, DnnTarget, SegmentationConfig, SegmentationModelKind};
 use crate::error::{Error::*, Result};
+use crate::model_cache::ModelCache;
+use opencv::dnn::{
+    DNN_BACKEND_CUDA, DNN_BACKEND_OPENCV, DNN_TARGET_CPU, DNN_TARGET_CUDA, DNN_TARGET_OPENCL, Net,
+    NetTrait, read_net_from_onnx,
+};
+use reqwest::StatusCode;
 use std::fs;
+use std::io::{IsTerminal, Read, Write};
 use std::path::{Path, PathBuf};
 
-// Using U2Net model for background segmentation
-// This model is well-tested with OpenCV DNN and provides good results
-const MODEL_URL: &str = "https://github.com/danielgatis/rembg/releases/download/v0.0.0/u2net.onnx";
-const MODEL_FILENAME: &str = "u2net.onnx";
-// MD5 checksum from rembg project: https://github.com/danielgatis/rembg/blob/main/rembg/sessions/u2net.py
-const MODEL_MD5: &str = "60024c5c889badc19c04ad937298a77b";
+// U2Net models for background segmentation, well-tested with OpenCV DNN. URLs and MD5
+// checksums from the rembg project: https://github.com/danielgatis/rembg/tree/main/rembg/sessions
+const U2NET_URL: &str = "https://github.com/danielgatis/rembg/releases/download/v0.0.0/u2net.onnx";
+const U2NET_MD5: &str = "60024c5c889badc19c04ad937298a77b";
+const U2NETP_URL: &str =
+    "https://github.com/danielgatis/rembg/releases/download/v0.0.0/u2netp.onnx";
+const U2NETP_MD5: &str = "8e83ca70e441ab06c318d82300c84806";
+const U2NET_INPUT_SIZE: i32 = 320;
+
+/// A segmentation model: where to download it and the input it expects. Defaults to
+/// U2Net.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelSpec {
+    pub url: String,
+    pub filename: String,
+    /// MD5 of the file, checked after downloading.
+    pub md5: String,
+    /// Side of the square input image.
+    pub input_size: i32,
+}
+
+impl Default for ModelSpec {
+    fn default() -> Self {
+        Self::new(U2NET_URL, U2NET_MD5, U2NET_INPUT_SIZE)
+    }
+}
+
+impl ModelSpec {
+    /// The model chosen by `[server.segmentation]`. A `model_url` needs a `model_checksum`.
+    pub fn from_config(config: &SegmentationConfig) -> Result<Self> {
+        let Some(url) = &config.model_url else {
+            let (url, md5) = match config.model {
+                SegmentationModelKind::U2net => (U2NET_URL, U2NET_MD5),
+                SegmentationModelKind::U2netp => (U2NETP_URL, U2NETP_MD5),
+            };
+            return Ok(Self::new(url, md5, U2NET_INPUT_SIZE));
+        };
+        let Some(md5) = &config.model_checksum else {
+            return Err(ModelChecksumRequired { url: url.clone() });
+        };
+        let input_size = config
+            .model_input_size
+            .map_or(U2NET_INPUT_SIZE, |size| size as i32);
+        Ok(Self::new(url, &md5.to_lowercase(), input_size))
+    }
+
+    /// Named after the last segment of `url`.
+    fn new(url: &str, md5: &str, input_size: i32) -> Self {
+        let path = url.split(['?', '#']).next().unwrap_or_default();
+        let filename = match path.rsplit('/').next() {
+            Some(name) if !name.is_empty() => name.to_string(),
+            _ => "model.onnx".to_string(),
+        };
+        Self {
+            url: url.to_string(),
+            filename,
+            md5: md5.to_string(),
+            input_size,
+        }
+    }
 
-pub fn get_model_path(models_dir: impl AsRef<Path>) -> Result<PathBuf> {
-    let models_path = models_dir.as_ref();
+    /// Where the model lives within `models_dir` (it may not be downloaded yet).
+    pub fn file(&self, models_dir: impl AsRef<Path>) -> PathBuf {
+        models_dir.as_ref().join(&self.filename)
+    }
+}
+
+/// The segmentation net, shared by every upload.
+pub type SegmentationModel = ModelCache<SegmentationNet>;
+
+impl SegmentationModel {
+    /// The `spec` model in `models_dir`, loaded on first use to run on `backend` and
+    /// `target`.
+    pub fn open(
+        models_dir: impl AsRef<Path>,
+        spec: &ModelSpec,
+        backend: DnnBackend,
+        target: DnnTarget,
+    ) -> Self {
+        let input_size = spec.input_size;
+        ModelCache::new(spec.file(models_dir), move |path| {
+            load_net(path, input_size, backend, target)
+        })
+    }
+}
+
+/// A loaded net and the backend it currently runs on.
+pub struct SegmentationNet {
+    pub net: Net,
+    /// Side of the square input image.
+    pub input_size: i32,
+    pub backend: DnnBackend,
+    pub target: DnnTarget,
+}
+
+impl SegmentationNet {
+    pub fn on_cpu(&self) -> bool {
+        self.backend == DnnBackend::Opencv && self.target == DnnTarget::Cpu
+    }
 
+    /// Move to the OpenCV backend on the CPU, after the configured one failed.
+    pub fn fall_back_to_cpu(&mut self) -> Result {
+        set_preference(&mut self.net, DnnBackend::Opencv, DnnTarget::Cpu)?;
+        self.backend = DnnBackend::Opencv;
+        self.target = DnnTarget::Cpu;
+        Ok(())
+    }
+}
+
+fn load_net(
+    path: &Path,
+    input_size: i32,
+    backend: DnnBackend,
+    target: DnnTarget,
+) -> Result<SegmentationNet> {
+    tracing::debug!(path = %path.display(), ?backend, ?target, "Loading segmentation model");
+    let net = read_net_from_onnx(&path.to_string_lossy())?;
+    let mut model = SegmentationNet {
+        net,
+        input_size,
+        backend,
+        target,
+    };
+    if let Err(e) = set_preference(&mut model.net, backend, target) {
+        if model.on_cpu() {
+            return Err(e);
+        }
+        tracing::warn!(?backend, ?target, error = %e, "DNN backend unavailable, falling back to CPU");
+        model.fall_back_to_cpu()?;
+    }
+    Ok(model)
+}
+
+fn set_preference(net: &mut Net, backend: DnnBackend, target: DnnTarget) -> Result {
+    net.set_preferable_backend(match backend {
+        DnnBackend::Opencv => DNN_BACKEND_OPENCV,
+        DnnBackend::Cuda => DNN_BACKEND_CUDA,
+    })?;
+    net.set_preferable_target(match target {
+        DnnTarget::Cpu => DNN_TARGET_CPU,
+        DnnTarget::Opencl => DNN_TARGET_OPENCL,
+        DnnTarget::Cuda => DNN_TARGET_CUDA,
+    })?;
+    Ok(())
+}
+
+/// The `spec` model in `models_dir`, downloading it first if it's missing or its
+/// checksum doesn't match (a download cut short, say).
+pub fn get_model_path(models_dir: impl AsRef<Path>, spec: &ModelSpec) -> Result<PathBuf> {
+    let models_dir = resolve_models_dir(models_dir.as_ref(), spec);
+    ensure_model(&models_dir, spec, download_model)
+}
+
+/// `models_dir` when it already holds the model or can take one, otherwise the XDG cache
+/// directory, so a service user without write access to a packaged directory that lacks
+/// the model can still download it.
+pub fn resolve_models_dir(models_dir: &Path, spec: &ModelSpec) -> PathBuf {
+    let cache_dir = xdg::BaseDirectories::with_prefix("lolcommits")
+        .get_cache_home()
+        .map(|cache| cache.join("models"));
+    choose_models_dir(models_dir, spec, cache_dir)
+}
+
+fn choose_models_dir(models_dir: &Path, spec: &ModelSpec, fallback: Option<PathBuf>) -> PathBuf {
+    if spec.file(models_dir).exists() {
+        return models_dir.to_path_buf();
+    }
+    let probe = fs::create_dir_all(models_dir).and_then(|()| tempfile::tempfile_in(models_dir));
+    match (probe, fallback) {
+        (Ok(_), _) | (Err(_), None) => models_dir.to_path_buf(),
+        (Err(e), Some(fallback)) => {
+            tracing::warn!(
+                models_dir = %models_dir.display(),
+                fallback = %fallback.display(),
+                error = %e,
+                "models_dir is not writable and has no model, using the cache directory instead"
+            );
+            fallback
+        }
+    }
+}
+
+fn ensure_model(
+    models_path: &Path,
+    spec: &ModelSpec,
+    download: impl FnOnce(&Path, &ModelSpec) -> Result,
+) -> Result<PathBuf> {
     // Ensure directory exists
     fs::create_dir_all(models_path).map_err(|source| ModelDirectoryCreate {
         path: models_path.to_path_buf(),
         source,
     })?;
 
-    let model_path = models_path.join(MODEL_FILENAME);
-
-    if !model_path.exists() {
-        tracing::info!("Downloading segmentation model (this happens once)...");
-        download_model(&model_path)?;
-        tracing::info!("Model downloaded successfully");
+    let model_path = spec.file(models_path);
+
+    if model_path.exists() {
+        match verify_checksum(&fs::read(&model_path)?, &spec.md5) {
+            Ok(()) => return Ok(model_path),
+            Err(e) => {
+                tracing::warn!(
+                    path = %model_path.display(),
+                    error = %e,
+                    "Cached segmentation model is corrupt, downloading it again"
+                );
+                fs::remove_file(&model_path)?;
+            }
+        }
     }
 
+    tracing::info!(
+        model = spec.filename,
+        "Downloading segmentation model (this happens once)..."
+    );
+    download(&model_path, spec)?;
+    tracing::info!("Model downloaded successfully");
+
     Ok(model_path)
 }
 
-fn download_model(path: impl AsRef<Path>) -> Result {
-    tracing::debug!(url = MODEL_URL, "Requesting model download");
-
-    let response = reqwest::blocking::get(MODEL_URL)?;
+/// Fetch the model to `path`, streaming it into a `.part` file alongside that a later
+/// attempt resumes from with an HTTP Range request. Only a complete file with the right
+/// checksum is renamed into place.
+fn download_model(path: &Path, spec: &ModelSpec) -> Result {
+    let partial = partial_file(path);
+    let resume_from = fs::metadata(&partial).map_or(0, |m| m.len());
+
+    tracing::debug!(url = spec.url, resume_from, "Requesting model download");
+    let mut request = reqwest::blocking::Client::new().get(&spec.url);
+    if resume_from > 0 {
+        request = request.header(reqwest::header::RANGE, format!("bytes={resume_from}-"));
+    }
+    let mut response = request.send()?;
 
     let status = response.status();
-    if !status.is_success() {
+    if status == StatusCode::RANGE_NOT_SATISFIABLE && resume_from > 0 {
+        // The previous attempt fetched everything but didn't get as far as verifying it
+        tracing::debug!(bytes = resume_from, "Partial model is already complete");
+    } else if !status.is_success() {
         return Err(HttpError {
             status: status.as_u16(),
         });
+    } else {
+        // A server ignoring the Range header sends the whole file again
+        let resumed = status == StatusCode::PARTIAL_CONTENT;
+        let offset = if resumed { resume_from } else { 0 };
+        if resumed {
+            tracing::info!(bytes = offset, "Resuming segmentation model download");
+        }
+        let total = response.content_length().map(|len| len + offset);
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(resumed)
+            .truncate(!resumed)
+            .open(&partial)
+            .map_err(|source| ModelFileWrite {
+                path: partial.clone(),
+                source,
+            })?;
+        copy_with_progress(&mut response, &mut file, offset, total)?;
     }
 
-    let content_length = response.content_length();
-    if let Some(len) = content_length {
-        tracing::debug!(bytes = len, "Downloading model");
-    }
+    finish_download(&partial, path, spec)
+}
 
-    let bytes = response.bytes()?;
+/// `path` with `.part` appended.
+fn partial_file(path: &Path) -> PathBuf {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".part");
+    PathBuf::from(partial)
+}
+
+/// Verify a fully downloaded `partial` and move it to `path`. A bad download is removed
+/// so the next attempt starts over.
+fn finish_download(partial: &Path, path: &Path, spec: &ModelSpec) -> Result {
+    let bytes = fs::read(partial)?;
 
     // Validate minimum size (ONNX models should be at least a few KB)
-    if bytes.len() < 1024 {
-        return Err(ModelFileTooSmall { size: bytes.len() });
+    let verified = if bytes.len() < 1024 {
+        Err(ModelFileTooSmall { size: bytes.len() })
+    } else {
+        verify_checksum(&bytes, &spec.md5)
+    };
+    if let Err(e) = verified {
+        let _ = fs::remove_file(partial);
+        return Err(e);
+    }
+
+    fs::rename(partial, path).map_err(|source| ModelFileWrite {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    tracing::debug!(path = ?path, size = bytes.len(), "Model saved successfully");
+
+    Ok(())
+}
+
+/// Report download progress every this many percent.
+const PROGRESS_STEP_PERCENT: u64 = 5;
+
+/// Copy `reader` to `writer`, logging progress towards `total` bytes (`offset` of which
+/// were fetched before) and keeping a line updated on stdout when it's a terminal.
+fn copy_with_progress(
+    reader: &mut impl Read,
+    writer: &mut impl Write,
+    offset: u64,
+    total: Option<u64>,
+) -> std::io::Result<u64> {
+    let interactive = std::io::stdout().is_terminal();
+    let mut progress = Progress::new(offset, total);
+    let mut downloaded = offset;
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let read = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        writer.write_all(&buf[..read])?;
+        downloaded += read as u64;
+
+        if let Some(percent) = progress.step(downloaded) {
+            tracing::info!(percent, downloaded, total, "Downloading segmentation model");
+            if interactive {
+                print!("\rDownloading segmentation model: {percent}%");
+                let _ = std::io::stdout().flush();
+            }
+        }
+    }
+    if interactive && progress.reported {
+        println!();
+    }
+    writer.flush()?;
+    Ok(downloaded)
+}
+
+/// Which download percentages have been reported.
+struct Progress {
+    total: Option<u64>,
+    next_percent: u64,
+    reported: bool,
+}
+
+impl Progress {
+    fn new(downloaded: u64, total: Option<u64>) -> Self {
+        let mut progress = Self {
+            total,
+            next_percent: 0,
+            reported: false,
+        };
+        progress.next_percent = progress.percent(downloaded).unwrap_or(0) / PROGRESS_STEP_PERCENT
+            * PROGRESS_STEP_PERCENT
+            + PROGRESS_STEP_PERCENT;
+        progress
+    }
+
+    fn percent(&self, downloaded: u64) -> Option<u64> {
+        let total = self.total.filter(|total| *total > 0)?;
+        Some((downloaded * 100 / total).min(100))
+    }
+
+    /// The percentage to report now `downloaded` bytes are in, once per step. Nothing
+    /// is reported when the total size is unknown.
+    fn step(&mut self, downloaded: u64) -> Option<u64> {
+        let percent = self.percent(downloaded)?;
+        if percent < self.next_percent {
+            return None;
+        }
+        self.next_percent =
+            percent / PROGRESS_STEP_PERCENT * PROGRESS_STEP_PERCENT + PROGRESS_STEP_PERCENT;
+        self.reported = true;
+        Some(percent)
     }
+}
 
-    // Verify MD5 checksum
-    let digest = md5::compute(&bytes);
-    let checksum = format!("{:x}", digest);
-    if checksum != MODEL_MD5 {
+fn verify_checksum(bytes: &[u8], expected: &str) -> Result {
+    let checksum = format!("{:x}", md5::compute(bytes));
+    if checksum != expected {
         return Err(ModelChecksumMismatch {
-            expected: MODEL_MD5.to_string(),
+            expected: expected.to_string(),
             actual: checksum,
         });
     }
     tracing::debug!(checksum, "Model checksum verified");
-
-    let path_ref = path.as_ref();
-    fs::write(path_ref, &bytes).map_err(|source| ModelFileWrite {
-        path: path_ref.to_path_buf(),
-        source,
-    })?;
-
-    tracing::debug!(path = ?path_ref, size = bytes.len(), "Model saved successfully");
-
     Ok(())
 }
 
@@ -80,6 +404,228 @@ mod tests {
     use super::*;
     use crate::error::Error;
     use std::env;
+    use test_case::test_case;
+
+    #[test_case(SegmentationModelKind::U2net, "u2net.onnx", U2NET_MD5 ; "u2net")]
+    #[test_case(SegmentationModelKind::U2netp, "u2netp.onnx", U2NETP_MD5 ; "u2netp")]
+    fn test_built_in_models(model: SegmentationModelKind, filename: &str, md5: &str) {
+        let spec = ModelSpec::from_config(&SegmentationConfig {
+            model,
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(spec.filename, filename);
+        assert_eq!(spec.md5, md5);
+        assert_eq!(spec.input_size, 320);
+        assert!(spec.url.ends_with(filename));
+    }
+
+    #[test]
+    fn test_custom_model() {
+        let spec = ModelSpec::from_config(&SegmentationConfig {
+            model: SegmentationModelKind::U2netp,
+            model_url: Some("https://example.com/models/silueta.onnx?download=1".to_string()),
+            model_checksum: Some("55E59E0D8062D2F5D013F4725EE84782".to_string()),
+            model_input_size: Some(256),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(
+            spec.url,
+            "https://example.com/models/silueta.onnx?download=1"
+        );
+        assert_eq!(spec.filename, "silueta.onnx");
+        assert_eq!(spec.md5, "55e59e0d8062d2f5d013f4725ee84782");
+        assert_eq!(spec.input_size, 256);
+    }
+
+    #[test]
+    fn test_custom_model_requires_checksum() {
+        let result = ModelSpec::from_config(&SegmentationConfig {
+            model_url: Some("https://example.com/model.onnx".to_string()),
+            ..Default::default()
+        });
+        assert!(matches!(result, Err(Error::ModelChecksumRequired { .. })));
+    }
+
+    /// A model whose checksum matches `contents`.
+    fn spec_for(contents: &[u8]) -> ModelSpec {
+        let md5 = format!("{:x}", md5::compute(contents));
+        ModelSpec::new("https://example.com/model.onnx", &md5, 320)
+    }
+
+    #[test]
+    fn test_ensure_model_keeps_valid_cached_model() -> Result {
+        let dir = tempfile::tempdir()?;
+        let spec = spec_for(b"good model");
+        fs::write(spec.file(dir.path()), b"good model")?;
+
+        let path = ensure_model(dir.path(), &spec, |_, _| {
+            panic!("a valid model should not be downloaded again")
+        })?;
+
+        assert_eq!(fs::read(path)?, b"good model");
+        Ok(())
+    }
+
+    #[test]
+    fn test_ensure_model_redownloads_corrupt_cached_model() -> Result {
+        let dir = tempfile::tempdir()?;
+        let spec = spec_for(b"good model");
+        fs::write(spec.file(dir.path()), b"good mo")?;
+
+        let path = ensure_model(dir.path(), &spec, |path, _| {
+            assert!(!path.exists(), "corrupt model should be removed first");
+            fs::write(path, b"good model")?;
+            Ok(())
+        })?;
+
+        assert_eq!(fs::read(path)?, b"good model");
+        Ok(())
+    }
+
+    #[test]
+    fn test_ensure_model_reports_failed_redownload() -> Result {
+        let dir = tempfile::tempdir()?;
+        let spec = spec_for(b"good model");
+        fs::write(spec.file(dir.path()), b"good mo")?;
+
+        let result = ensure_model(dir.path(), &spec, |_, _| Err(HttpError { status: 503 }));
+
+        assert!(matches!(result, Err(Error::HttpError { status: 503 })));
+        assert!(!spec.file(dir.path()).exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_choose_models_dir_uses_existing_model() -> Result {
+        let dir = tempfile::tempdir()?;
+        let spec = spec_for(b"model");
+        fs::write(spec.file(dir.path()), b"model")?;
+
+        let chosen = choose_models_dir(dir.path(), &spec, Some(PathBuf::from("/fallback")));
+
+        assert_eq!(chosen, dir.path());
+        Ok(())
+    }
+
+    #[test]
+    fn test_choose_models_dir_creates_writable_dir() -> Result {
+        let dir = tempfile::tempdir()?;
+        let models_dir = dir.path().join("models");
+
+        let chosen = choose_models_dir(
+            &models_dir,
+            &ModelSpec::default(),
+            Some(PathBuf::from("/fallback")),
+        );
+
+        assert_eq!(chosen, models_dir);
+        assert!(models_dir.is_dir());
+        Ok(())
+    }
+
+    #[test]
+    fn test_choose_models_dir_falls_back_when_unwritable() -> Result {
+        let dir = tempfile::tempdir()?;
+        // A directory can't be created beneath a file, whoever runs the test
+        let file = dir.path().join("not-a-dir");
+        fs::write(&file, b"")?;
+        let models_dir = file.join("models");
+        let fallback = dir.path().join("cache");
+
+        let chosen = choose_models_dir(&models_dir, &ModelSpec::default(), Some(fallback.clone()));
+
+        assert_eq!(chosen, fallback);
+        Ok(())
+    }
+
+    #[test]
+    fn test_progress_reports_each_step_once() {
+        let mut progress = Progress::new(0, Some(1000));
+        assert_eq!(progress.step(10), None);
+        assert_eq!(progress.step(50), Some(5));
+        assert_eq!(progress.step(60), None);
+        assert_eq!(progress.step(230), Some(23));
+        assert_eq!(progress.step(240), None);
+        assert_eq!(progress.step(250), Some(25));
+        assert_eq!(progress.step(1000), Some(100));
+    }
+
+    #[test]
+    fn test_progress_resumes_after_offset() {
+        let mut progress = Progress::new(420, Some(1000));
+        assert_eq!(progress.step(430), None);
+        assert_eq!(progress.step(450), Some(45));
+    }
+
+    #[test]
+    fn test_progress_without_total_is_silent() {
+        let mut progress = Progress::new(0, None);
+        assert_eq!(progress.step(1 << 20), None);
+    }
+
+    #[test]
+    fn test_copy_with_progress_appends_to_offset() -> Result {
+        let mut reader = std::io::Cursor::new(vec![7u8; 200_000]);
+        let mut written = Vec::new();
+
+        let downloaded = copy_with_progress(&mut reader, &mut written, 100, Some(200_100))?;
+
+        assert_eq!(downloaded, 200_100);
+        assert_eq!(written.len(), 200_000);
+        Ok(())
+    }
+
+    #[test]
+    fn test_finish_download_moves_verified_model() -> Result {
+        let dir = tempfile::tempdir()?;
+        let contents = vec![1u8; 2048];
+        let spec = spec_for(&contents);
+        let path = spec.file(dir.path());
+        let partial = partial_file(&path);
+        fs::write(&partial, &contents)?;
+
+        finish_download(&partial, &path, &spec)?;
+
+        assert_eq!(fs::read(&path)?, contents);
+        assert!(!partial.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_finish_download_discards_bad_download() -> Result {
+        let dir = tempfile::tempdir()?;
+        let spec = spec_for(&[1u8; 2048]);
+        let path = spec.file(dir.path());
+        let partial = partial_file(&path);
+        fs::write(&partial, [2u8; 2048])?;
+
+        let result = finish_download(&partial, &path, &spec);
+
+        assert!(matches!(result, Err(Error::ModelChecksumMismatch { .. })));
+        assert!(!partial.exists());
+        assert!(!path.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_partial_file_keeps_extension() {
+        assert_eq!(
+            partial_file(Path::new("/models/u2net.onnx")),
+            PathBuf::from("/models/u2net.onnx.part")
+        );
+    }
+
+    #[test]
+    fn test_verify_checksum() {
+        let bytes = b"onnx bytes";
+        let md5 = format!("{:x}", md5::compute(bytes));
+        assert!(verify_checksum(bytes, &md5).is_ok());
+
+        let result = verify_checksum(bytes, U2NET_MD5);
+        assert!(matches!(result, Err(Error::ModelChecksumMismatch { .. })));
+    }
 
     #[test]
     fn test_get_model_path_creates_directory() {
@@ -89,7 +635,7 @@ mod tests {
         let temp_dir = env::temp_dir().join("lolcommits-test-models");
         let models_dir = temp_dir.to_string_lossy().to_string();
 
-        let result = get_model_path(&models_dir);
+        let result = get_model_path(&models_dir, &ModelSpec::default());
 
         // If the test fails due to network issues, that's acceptable in CI/offline scenarios
         if let Err(err) = result {
@@ -106,7 +652,7 @@ mod tests {
 
         let path = result.unwrap();
         // Should end with the model filename
-        assert!(path.to_string_lossy().ends_with(MODEL_FILENAME));
+        assert!(path.to_string_lossy().ends_with("u2net.onnx"));
 
         // Parent directory should exist (created by get_model_path)
         assert!(path.parent().unwrap().exists());
@@ -121,7 +667,7 @@ mod tests {
         let temp_dir = env::temp_dir().join("lolcommits-test-models-2");
         let models_dir = temp_dir.to_string_lossy().to_string();
 
-        let result = get_model_path(&models_dir);
+        let result = get_model_path(&models_dir, &ModelSpec::default());
 
         // If the test fails due to network issues, that's acceptable
         if let Err(err) = result {
@@ -1,47 +1,291 @@
 use axum::{
-    Json, Router,
-    extract::{DefaultBodyLimit, Multipart, State},
-    http::StatusCode,
+    Extension, Json, Router,
+    body::Bytes,
+    extract::{
+        ConnectInfo, DefaultBodyLimit, Multipart, Path, Query, Request, State,
+        multipart::{Field, MultipartError},
+    },
+    http::{HeaderMap, StatusCode, header},
     response::{
-        Html, IntoResponse, Response,
+        IntoResponse, Response,
         sse::{Event, Sse},
     },
     routing::{get, post},
 };
 use futures::stream::Stream;
 use serde::{Deserialize, Serialize, Serializer};
-use std::collections::HashSet;
+use std::collections::HashMap;
 use std::convert::Infallible;
+use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::sync::{RwLock, broadcast};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use subtle::ConstantTimeEq;
+use tokio::sync::broadcast;
+use tokio_util::task::TaskTracker;
+use tower::ServiceExt;
 use tower_http::{
-    services::ServeDir,
+    services::ServeFile,
     trace::{DefaultMakeSpan, TraceLayer},
 };
 
-use crate::{config, error::Result, git, image_metadata, image_processor};
+use crate::{
+    api_error::ApiError,
+    best_of,
+    chyron::{self, ChyronFonts},
+    config,
+    disk_space::DiskSpace,
+    error::{Error, Result},
+    export, failed_uploads, feed, git,
+    image_cache::ImageCache,
+    image_index::{self, ImageIndex},
+    image_metadata,
+    image_processor::Background,
+    jobs::Jobs,
+    overrides::{self, Overrides},
+    post_processor,
+    processing_queue::ProcessingQueue,
+    read_only::ReadOnlyMode,
+    revision_cache::RevisionCache,
+    segmentation::SegmentationModel,
+    static_files, stats,
+    urls::ImageUrls,
+};
+
+/// Error code in the 503 body returned for mutating requests while read-only.
+pub const READ_ONLY_ERROR_CODE: &str = "read_only";
+
+/// Error code in the 507 body returned for uploads while low on disk space.
+pub const LOW_DISK_SPACE_ERROR_CODE: &str = "low_disk_space";
+
+/// Error code in the 409 body returned for uploads of a revision already in the gallery.
+pub const DUPLICATE_REVISION_ERROR_CODE: &str = "duplicate_revision";
+
+/// Error code in the 409 body returned when reprocessing an image without a kept original.
+pub const ORIGINAL_MISSING_ERROR_CODE: &str = "original_missing";
+
+/// Error code in the 413 body returned for uploads over `max_upload_bytes`.
+pub const UPLOAD_TOO_LARGE_ERROR_CODE: &str = "upload_too_large";
+
+/// Error code in the 503 body returned for uploads while the processing backlog is full.
+pub const BUSY_ERROR_CODE: &str = "busy";
+
+/// Error code in the 422 body returned for uploads whose metadata doesn't parse or holds
+/// values no commit has.
+pub const INVALID_METADATA_ERROR_CODE: &str = "invalid_metadata";
+
+/// How long clients are asked to wait before retrying an upload refused as busy.
+const BUSY_RETRY_AFTER_SECS: u64 = 10;
+
+/// Allowance on top of `max_upload_bytes` for the metadata part and multipart framing.
+const MULTIPART_OVERHEAD_BYTES: usize = 64 * 1024;
+
+/// Most a `/api/ping` test upload may carry, it only needs to prove uploads get through.
+const PING_MAX_BYTES: usize = 64 * 1024;
+
+/// Prefix of the temporary files uploaded images are streamed into within images_dir.
+/// Dotfiles are never served or indexed, and `--fsck` reports any left behind by a crash.
+pub const UPLOAD_TEMP_PREFIX: &str = ".upload";
+
+/// Directory within images_dir that rendered chyron overlays are cached in.
+pub const CHYRON_CACHE_DIR: &str = ".chyron";
+
+/// Directory within state_dir that uploads are kept in as received, with `keep_originals`.
+/// Outside images_dir so the unprocessed captures are never served.
+pub const ORIGINALS_DIR: &str = "originals";
+
+/// Counts an `/api/events` subscriber as connected for as long as it's alive.
+struct SseConnectionGuard {
+    clients: Arc<AtomicUsize>,
+    client_ip: String,
+    connected_at: std::time::Instant,
+}
 
-struct SseConnectionGuard;
+impl SseConnectionGuard {
+    fn new(clients: Arc<AtomicUsize>, client_ip: String) -> Self {
+        let connected = clients.fetch_add(1, Ordering::Relaxed) + 1;
+        crate::metrics::increment_sse_connections();
+        tracing::info!(client_ip, clients = connected, "SSE client connected");
+        Self {
+            clients,
+            client_ip,
+            connected_at: std::time::Instant::now(),
+        }
+    }
+}
 
 impl Drop for SseConnectionGuard {
     fn drop(&mut self) {
+        let connected = self.clients.fetch_sub(1, Ordering::Relaxed) - 1;
         crate::metrics::decrement_sse_connections();
+        tracing::info!(
+            client_ip = %self.client_ip,
+            clients = connected,
+            connected_secs = self.connected_at.elapsed().as_secs(),
+            "SSE client disconnected"
+        );
     }
 }
 
 #[derive(Debug, Serialize)]
 struct ConfigResponse {
     gallery_title: String,
+    /// Most image data an upload may carry, so clients can scale captures down first.
+    max_upload_bytes: usize,
+}
+
+/// What a `/api/ping` test upload contained.
+#[derive(Debug, Serialize)]
+struct PingResponse {
+    status: &'static str,
+    bytes: usize,
+    width: u32,
+    height: u32,
+    /// Real uploads would be refused.
+    read_only: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct HealthResponse {
+    status: &'static str,
+    read_only: bool,
+    /// How `background_path` resolved at startup, see [`Background::status`].
+    background: &'static str,
+    /// "low" while uploads are refused for lack of space, otherwise "ok".
+    disk_space: &'static str,
+    /// Total size of the images in the gallery.
+    gallery_bytes: u64,
+    /// Clients subscribed to `/api/events`.
+    sse_clients: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReadOnlyRequest {
+    enabled: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct ReadOnlyResponse {
+    read_only: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct ReloadConfigResponse {
+    reloaded: bool,
+    background: &'static str,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExistsQuery {
+    repo: Option<String>,
+    revision: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ExistsResponse {
+    exists: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    filename: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlanQuery {
+    #[serde(default)]
+    repo: String,
+    #[serde(rename = "type", default)]
+    commit_type: String,
+    #[serde(default)]
+    branch: String,
+}
+
+/// Filters and paging for `/api/images`. Numbers are parsed by hand so malformed ones
+/// get a JSON 400 rather than the extractor's plain-text rejection.
+#[derive(Debug, Default, Deserialize)]
+struct ImagesQuery {
+    limit: Option<String>,
+    offset: Option<String>,
+    #[serde(flatten)]
+    filter: ImageFilter,
+}
+
+/// Exact matches on an image's repo, branch and commit type, shared by the endpoints
+/// that select images. Empty values match everything.
+#[derive(Debug, Default, Clone, Deserialize)]
+struct ImageFilter {
+    repo: Option<String>,
+    branch: Option<String>,
+    #[serde(rename = "type")]
+    commit_type: Option<String>,
+}
+
+impl ImageFilter {
+    fn matches(&self, image: &git::CommitMetadata) -> bool {
+        let wanted = |filter: &Option<String>, value: &str| {
+            filter
+                .as_deref()
+                .is_none_or(|filter| filter.is_empty() || filter == value)
+        };
+        wanted(&self.repo, &image.repo_name)
+            && wanted(&self.branch, &image.branch_name)
+            && wanted(&self.commit_type, &image.commit_type)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ImagesResponse {
+    images: Vec<ImageMetadata>,
+    /// Images matching the filters, before `offset` and `limit` are applied.
+    total: usize,
+    offset: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BestQuery {
+    since: Option<String>,
+    until: Option<String>,
+    metric: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExportQuery {
+    #[serde(flatten)]
+    filter: ImageFilter,
+    since: Option<String>,
+    until: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StatsQuery {
+    repo: Option<String>,
+    since: Option<String>,
+    until: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct RankedImage {
+    score: u64,
+    #[serde(flatten)]
+    image: ImageMetadata,
+}
+
+#[derive(Debug, Serialize)]
+struct BestResponse {
+    metric: &'static str,
+    best: Option<RankedImage>,
+    runners_up: Vec<RankedImage>,
 }
 
 #[derive(Debug, Serialize)]
 struct UploadResponse {
     status: String,
     message: String,
+    /// Poll `/api/jobs/{job_id}` for the outcome of processing.
+    job_id: String,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct UploadMetadata {
     revision: String,
     message: String,
@@ -50,15 +294,88 @@ struct UploadMetadata {
     timestamp: String,
     repo_name: String,
     branch_name: String,
+    #[serde(default)]
+    author_name: String,
+    #[serde(default)]
+    author_email: String,
+    #[serde(default)]
+    breaking: bool,
+    #[serde(default)]
+    co_authors: Vec<String>,
     files_changed: u32,
     insertions: u32,
     deletions: u32,
     #[serde(default)]
     force: bool,
+    #[serde(default)]
+    processing_overrides: Overrides,
+    /// How long each frame of an animated upload is shown.
+    #[serde(default)]
+    frame_delay_ms: Option<u64>,
+    /// Whether to burn the chyron in, over `burned_in_chyron` in `[server]`. `false` when
+    /// the client already did.
+    #[serde(default)]
+    burned_in_chyron: Option<bool>,
+}
+
+impl UploadMetadata {
+    /// The first field holding a value no commit could have, and what's wrong with it.
+    fn problem(&self) -> Option<(&'static str, String)> {
+        use chrono::Datelike;
+
+        if self.revision.is_empty() {
+            return Some(("revision", "is empty".to_string()));
+        }
+        // The revision and repo name end up in the image's filename
+        if !image_metadata::is_revision(&self.revision) {
+            return Some((
+                "revision",
+                format!("{:?} isn't a commit hash", self.revision),
+            ));
+        }
+        if image_metadata::filename_repo_name(&self.repo_name).is_empty() {
+            return Some((
+                "repo_name",
+                format!("{:?} leaves nothing for a filename", self.repo_name),
+            ));
+        }
+
+        let Some(taken) = crate::parse_timestamp(&self.timestamp) else {
+            return Some((
+                "timestamp",
+                format!(
+                    "{:?} isn't RFC 3339 or {}",
+                    self.timestamp,
+                    crate::TIMESTAMP_FORMAT
+                ),
+            ));
+        };
+        let latest = chrono::Utc::now() + chrono::Duration::days(1);
+        if taken.year() < 1970 || taken > latest {
+            return Some(("timestamp", format!("{} is out of range", self.timestamp)));
+        }
+
+        // A count this big is a negative one wrapped around by the client
+        [
+            ("files_changed", self.files_changed),
+            ("insertions", self.insertions),
+            ("deletions", self.deletions),
+        ]
+        .into_iter()
+        .find(|&(_, count)| count > i32::MAX as u32)
+        .map(|(field, count)| (field, format!("{count} looks like a negative count")))
+    }
 }
 
 #[derive(Debug)]
-pub struct ImageMetadata(git::CommitMetadata);
+pub struct ImageMetadata(git::CommitMetadata, ImageUrls);
+
+impl ImageMetadata {
+    pub fn new(config: &config::ServerConfig, metadata: git::CommitMetadata) -> Self {
+        let urls = ImageUrls::for_metadata(config, &metadata);
+        Self(metadata, urls)
+    }
+}
 
 impl std::ops::Deref for ImageMetadata {
     type Target = git::CommitMetadata;
@@ -81,58 +398,380 @@ impl Serialize for ImageMetadata {
             .and_then(|s| s.to_str())
             .unwrap_or("");
 
-        let mut state = serializer.serialize_struct("ImageMetadata", 9)?;
+        let mut state = serializer.serialize_struct("ImageMetadata", 16)?;
         state.serialize_field("filename", &filename)?;
+        state.serialize_field("url", &self.1.url)?;
+        state.serialize_field("thumb_url", &self.1.thumb_url)?;
         state.serialize_field("revision", &self.0.revision)?;
         state.serialize_field("message", &self.0.message)?;
         state.serialize_field("commit_type", &self.0.commit_type)?;
         state.serialize_field("scope", &self.0.scope)?;
-        state.serialize_field("timestamp", &self.0.timestamp)?;
+        // Older images recorded local time without an offset
+        let timestamp = crate::parse_timestamp(&self.0.timestamp)
+            .map(crate::format_timestamp)
+            .unwrap_or_else(|| self.0.timestamp.clone());
+        state.serialize_field("timestamp", &timestamp)?;
         state.serialize_field("repo_name", &self.0.repo_name)?;
         state.serialize_field("branch_name", &self.0.branch_name)?;
+        state.serialize_field("author_name", &self.0.author_name)?;
+        state.serialize_field("author_email", &self.0.author_email)?;
+        state.serialize_field("breaking", &self.0.breaking)?;
+        state.serialize_field("co_authors", &self.0.co_authors)?;
         state.serialize_field("stats", &self.0.stats)?;
+        state.serialize_field("animated", &image_metadata::is_animated(&self.0.path))?;
         state.end()
     }
 }
 
+/// One image with everything the lightbox shows beyond the listing.
+#[derive(Debug, Serialize)]
+struct ImageDetail {
+    #[serde(flatten)]
+    image: ImageMetadata,
+    size_bytes: u64,
+    /// `None` when the image header can't be read.
+    width: Option<u32>,
+    height: Option<u32>,
+    /// Where the metadata came from: "embedded" PNG chunks, a "sidecar" or the "filename".
+    metadata_source: &'static str,
+    /// Every text chunk embedded in a PNG, by keyword, for debugging.
+    text_chunks: std::collections::BTreeMap<String, String>,
+}
+
+/// Pushed to `/api/events` subscribers.
+#[derive(Debug, Clone)]
+struct GalleryEvent {
+    name: &'static str,
+    /// JSON, sent as the SSE event data.
+    data: String,
+    /// The image the event is about, for subscribers filtering by repo or type. Events
+    /// without one go to every subscriber.
+    image: Option<Arc<git::CommitMetadata>>,
+}
+
+impl GalleryEvent {
+    fn new_image(image: &ImageMetadata) -> serde_json::Result<Self> {
+        Ok(Self {
+            name: "new_image",
+            data: serde_json::to_string(image)?,
+            image: Some(Arc::new(git::CommitMetadata::clone(image))),
+        })
+    }
+
+    fn image_updated(image: &ImageMetadata) -> serde_json::Result<Self> {
+        Ok(Self {
+            name: "image_updated",
+            data: serde_json::to_string(image)?,
+            image: Some(Arc::new(git::CommitMetadata::clone(image))),
+        })
+    }
+
+    /// `image` is what the index knew of the deleted file, if anything.
+    fn image_deleted(filename: &str, image: Option<git::CommitMetadata>) -> Self {
+        Self {
+            name: "image_deleted",
+            data: serde_json::json!({ "filename": filename }).to_string(),
+            image: image.map(Arc::new),
+        }
+    }
+
+    /// Sent first to each subscriber, with how many images it would list, so a client
+    /// reconnecting can tell whether it missed any.
+    fn hello(image_count: usize) -> Self {
+        Self {
+            name: "hello",
+            data: serde_json::json!({ "image_count": image_count }).to_string(),
+            image: None,
+        }
+    }
+
+    /// Sent in place of the `skipped` events a subscriber fell too far behind to get,
+    /// so it refetches the gallery.
+    fn resync(skipped: u64) -> Self {
+        Self {
+            name: "resync",
+            data: serde_json::json!({ "skipped": skipped }).to_string(),
+            image: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum EventFormat {
+    /// Named events with a JSON payload.
+    #[default]
+    Json,
+    /// Unnamed events whose data is just the event name, for clients predating the
+    /// JSON payload.
+    Legacy,
+}
+
+#[derive(Debug, Deserialize)]
+struct EventsQuery {
+    #[serde(default)]
+    format: EventFormat,
+    /// Only events about matching images, plus those about no image in particular.
+    #[serde(flatten)]
+    filter: ImageFilter,
+}
+
+impl EventsQuery {
+    fn wants(&self, event: &GalleryEvent) -> bool {
+        event
+            .image
+            .as_deref()
+            .is_none_or(|image| self.filter.matches(image))
+    }
+
+    fn to_sse(&self, event: GalleryEvent) -> Event {
+        match self.format {
+            EventFormat::Json => Event::default().event(event.name).data(event.data),
+            EventFormat::Legacy => Event::default().data(event.name),
+        }
+    }
+}
+
+/// The daemon's config, loaded once at startup and replaced as a whole by
+/// [`SharedConfig::reload`] (`POST /api/admin/reload-config` or SIGHUP).
+#[derive(Clone)]
+pub struct SharedConfig {
+    path: Option<PathBuf>,
+    current: Arc<std::sync::RwLock<Arc<LoadedConfig>>>,
+}
+
+/// A config and what is resolved from it up front.
+pub struct LoadedConfig {
+    pub config: config::Config,
+    pub server: config::ServerConfig,
+    /// Checked once rather than on every upload.
+    pub background: Background,
+}
+
+impl LoadedConfig {
+    pub fn new(config: config::Config) -> Self {
+        let server = config.server.clone().unwrap_or_default();
+
+        let background = Background::resolve(&server.background_path);
+        match &background {
+            Background::Missing(reason) => tracing::error!(
+                background_path = %server.background_path,
+                reason,
+                "Background not found, uploads will fail until background_path is fixed or set to \"none\""
+            ),
+            background => tracing::info!(status = background.status(), "Resolved background"),
+        }
+
+        Self {
+            config,
+            server,
+            background,
+        }
+    }
+}
+
+impl SharedConfig {
+    /// `config` as loaded from `path`, `None` meaning the default location.
+    pub fn new(config: config::Config, path: Option<PathBuf>) -> Self {
+        Self::with_loaded(LoadedConfig::new(config), path)
+    }
+
+    fn with_loaded(loaded: LoadedConfig, path: Option<PathBuf>) -> Self {
+        Self {
+            path,
+            current: Arc::new(std::sync::RwLock::new(Arc::new(loaded))),
+        }
+    }
+
+    pub fn get(&self) -> Arc<LoadedConfig> {
+        self.current.read().expect("config lock poisoned").clone()
+    }
+
+    /// Re-read the config file and swap it in. A missing, unreadable or invalid file is
+    /// an error and the current config stays in place.
+    pub fn reload(&self) -> Result<Arc<LoadedConfig>> {
+        let loaded = config::Config::resolve_path(self.path.clone()).and_then(|path| {
+            if !path.exists() {
+                return Err(crate::error::Error::ConfigFileRead {
+                    path,
+                    source: std::io::ErrorKind::NotFound.into(),
+                });
+            }
+            config::Config::load_from(Some(path))
+        });
+
+        match loaded {
+            Ok(config) => {
+                let loaded = Arc::new(LoadedConfig::new(config));
+                *self.current.write().expect("config lock poisoned") = loaded.clone();
+                tracing::info!("Reloaded config");
+                Ok(loaded)
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "Failed to reload config, keeping the current one");
+                Err(e)
+            }
+        }
+    }
+}
+
 #[derive(Clone)]
 struct AppState {
-    tx: broadcast::Sender<String>,
-    revision_cache: Arc<RwLock<HashSet<String>>>,
+    tx: broadcast::Sender<GalleryEvent>,
+    revision_cache: Arc<RevisionCache>,
+    image_index: Arc<ImageIndex>,
+    jobs: Arc<Jobs>,
+    read_only: Arc<ReadOnlyMode>,
+    admin_token: Option<Arc<str>>,
+    image_cache: Option<Arc<ImageCache>>,
+    config: SharedConfig,
+    disk_space: Arc<DiskSpace>,
+    segmentation_model: Arc<SegmentationModel>,
+    /// Uploads being processed in the background, drained on shutdown.
+    tasks: TaskTracker,
+    feed: Arc<std::sync::Mutex<Option<CachedFeed>>>,
+    /// `max_upload_bytes` as the router's body limit was built with.
+    max_upload_bytes: usize,
+    processing: Arc<ProcessingQueue>,
+    /// Clients subscribed to `/api/events`.
+    sse_clients: Arc<AtomicUsize>,
+}
+
+/// The last rendered feed, reused until the gallery or the config changes.
+struct CachedFeed {
+    generation: u64,
+    config: Arc<LoadedConfig>,
+    rendered_at: std::time::Instant,
+    body: Bytes,
+}
+
+impl CachedFeed {
+    fn is_current(&self, generation: u64, config: &Arc<LoadedConfig>) -> bool {
+        self.generation == generation
+            && Arc::ptr_eq(&self.config, config)
+            // Past this the index may be stale, and only a listing rescans it
+            && self.rendered_at.elapsed() < image_index::RESCAN_INTERVAL
+    }
+}
+
+/// State for serving gallery images, through the in-memory cache when there is one.
+#[derive(Clone)]
+struct ImageFiles {
+    images_dir: Arc<std::path::Path>,
+    cache: Option<Arc<ImageCache>>,
 }
 
 pub fn create_router(
     data_home: std::path::PathBuf,
     metrics_handle: metrics_exporter_prometheus::PrometheusHandle,
+    segmentation_model: Arc<SegmentationModel>,
+    config: SharedConfig,
+    tasks: TaskTracker,
 ) -> Router {
     // Create broadcast channel for SSE events (capacity of 100 events)
     let (tx, _rx) = broadcast::channel(100);
 
-    // Initialize revision cache from existing images
-    let (revision_cache, initial_cache_size) = match initialize_revision_cache() {
-        Ok(cache) => {
-            let len = cache.len();
-            tracing::info!(count = len, "Initialized revision cache");
-            (Arc::new(RwLock::new(cache)), len)
-        }
-        Err(e) => {
-            tracing::warn!(error = %e, "Failed to initialize revision cache, starting with empty cache");
-            (Arc::new(RwLock::new(HashSet::new())), 0)
-        }
-    };
+    let server_config = config.get().server.clone();
+
+    // Read the gallery's metadata once, or only what changed since the last run, then
+    // seed the revision cache from it
+    let image_index = Arc::new(ImageIndex::open_persistent(
+        &server_config.images_dir,
+        &server_config.state_dir,
+    ));
+    let revision_cache = Arc::new(RevisionCache::new(image_index.clone()));
+    tracing::info!(count = revision_cache.len(), "Initialized revision cache");
+
+    let read_only = Arc::new(ReadOnlyMode::load(
+        &server_config.state_dir,
+        server_config.read_only,
+    ));
+    if read_only.is_enabled() {
+        tracing::warn!("Server is in read-only mode, uploads will be refused");
+    }
 
-    crate::metrics::set_images_total(initial_cache_size);
-    crate::metrics::set_revision_cache_size(initial_cache_size);
+    let image_cache = ImageCache::from_megabytes(server_config.image_cache_mb).map(Arc::new);
+    if image_cache.is_some() {
+        tracing::info!(
+            size_mb = server_config.image_cache_mb,
+            "Serving images through in-memory cache"
+        );
+    }
+
+    let disk_space = DiskSpace::new(&server_config);
+    tracing::info!(
+        gallery_bytes = disk_space.gallery_bytes(),
+        "Measured gallery size"
+    );
+    if let Err(e) = disk_space.check() {
+        tracing::warn!(error = %e, "Low disk space, uploads will be refused");
+    }
+
+    let state = AppState {
+        tx,
+        revision_cache,
+        image_index,
+        jobs: Arc::new(Jobs::new(std::time::Duration::from_secs(
+            server_config.job_ttl_secs,
+        ))),
+        read_only,
+        admin_token: server_config.admin_token.as_deref().map(Arc::from),
+        image_cache: image_cache.clone(),
+        config,
+        disk_space: Arc::new(disk_space),
+        segmentation_model,
+        tasks,
+        feed: Arc::new(std::sync::Mutex::new(None)),
+        max_upload_bytes: server_config.max_upload_bytes,
+        processing: Arc::new(ProcessingQueue::from_config(&server_config)),
+        sse_clients: Arc::new(AtomicUsize::new(0)),
+    };
 
-    let state = AppState { tx, revision_cache };
+    let image_routes = Router::new()
+        .route("/{filename}", get(image_handler))
+        .with_state(ImageFiles {
+            images_dir: Arc::from(data_home.as_path()),
+            cache: image_cache,
+        });
 
     let app_routes = Router::new()
-        .route("/", get(index_handler))
+        .route("/", get(static_handler))
+        .route(feed::FEED_ROUTE, get(feed_handler))
         .route("/api/images", get(list_images))
+        .route("/api/best", get(best_images))
+        .route("/api/stats", get(stats_handler))
+        .route("/api/export", get(export_handler))
         .route("/api/config", get(get_config))
-        .route("/api/upload", post(upload_handler))
+        .route("/api/health", get(health_handler))
+        .route("/api/exists", get(exists_handler))
+        .route(
+            "/api/images/{filename}",
+            get(image_detail_handler).delete(delete_image),
+        )
+        .route("/api/images/{filename}/reprocess", post(reprocess_image))
+        .route(
+            "/api/images/{filename}/chyron.png",
+            get(chyron_overlay_handler),
+        )
+        .route("/api/admin/readonly", post(set_read_only))
+        .route("/api/admin/reload-config", post(reload_config))
+        .route("/api/pipeline/plan", get(pipeline_plan_handler))
+        .route(
+            "/api/upload",
+            post(upload_handler).layer(DefaultBodyLimit::max(
+                state
+                    .max_upload_bytes
+                    .saturating_add(MULTIPART_OVERHEAD_BYTES),
+            )),
+        )
+        .route(
+            "/api/ping",
+            post(ping_handler).layer(DefaultBodyLimit::max(PING_MAX_BYTES)),
+        )
+        .route("/api/jobs/{id}", get(job_handler))
         .route("/api/events", get(sse_handler))
-        .nest_service("/images", ServeDir::new(&data_home))
+        .nest("/images", image_routes)
+        .fallback(static_handler)
         .layer(DefaultBodyLimit::max(4 * 1024 * 1024)) // 4 MiB
         .layer(
             TraceLayer::new_for_http()
@@ -152,309 +791,3951 @@ pub fn create_router(
     app_routes.merge(metrics_routes)
 }
 
-async fn index_handler() -> Html<&'static str> {
-    Html(include_str!("static/index.html"))
+/// The gallery frontend, from `static_dir` when it has one, see [`static_files`].
+async fn static_handler(State(state): State<AppState>, request: Request) -> Response {
+    let static_dir = state.config.get().server.static_dir.clone();
+    static_files::serve(static_dir.as_deref().map(std::path::Path::new), request).await
 }
 
-async fn list_images() -> Response {
-    match config::Config::load() {
-        Ok(config) => {
-            let server_config = config.server.clone().unwrap_or_default();
-            match get_image_list(&server_config) {
-                Ok(images) => {
-                    let responses: Vec<ImageMetadata> =
-                        images.into_iter().map(ImageMetadata).collect();
-                    Json(responses).into_response()
-                }
-                Err(e) => {
-                    tracing::error!(error = %e, "Failed to list images");
-                    (
-                        axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                        format!("Failed to list images: {}", e),
-                    )
-                        .into_response()
-                }
-            }
-        }
-        Err(e) => {
-            tracing::error!(error = %e, "Failed to load config");
-            (
-                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Failed to load config: {}", e),
-            )
-                .into_response()
+async fn list_images(
+    State(state): State<AppState>,
+    Query(query): Query<ImagesQuery>,
+) -> std::result::Result<Json<ImagesResponse>, ApiError> {
+    let offset = parse_count("offset", query.offset.as_deref())
+        .map_err(|message| ApiError::bad_request("invalid_offset", message))?
+        .unwrap_or(0);
+    let limit = match parse_count("limit", query.limit.as_deref()) {
+        Ok(Some(0)) => {
+            return Err(ApiError::bad_request(
+                "invalid_limit",
+                "limit must be at least 1",
+            ));
         }
-    }
+        Ok(limit) => limit,
+        Err(message) => return Err(ApiError::bad_request("invalid_limit", message)),
+    };
+
+    let images = gallery_lookup(&state, |state| state.image_index.list()).await?;
+    let server_config = &state.config.get().server;
+    let (page, total) = select_page(images, &query, offset, limit);
+    Ok(Json(ImagesResponse {
+        images: page
+            .into_iter()
+            .map(|image| ImageMetadata::new(server_config, image))
+            .collect(),
+        total,
+        offset,
+        limit,
+    }))
 }
 
-async fn get_config() -> Response {
-    match config::Config::load() {
-        Ok(cfg) => {
-            let gallery_title = cfg
-                .server
-                .as_ref()
-                .map(|s| s.gallery_title.clone())
-                .unwrap_or_else(|| "Lolcommits Gallery".to_string());
-            Json(ConfigResponse { gallery_title }).into_response()
-        }
-        Err(e) => {
-            tracing::error!(error = %e, "Failed to load config");
-            (
-                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Failed to load config: {}", e),
-            )
-                .into_response()
-        }
-    }
+/// Run `lookup` on a blocking thread, as listing the gallery or looking up a revision
+/// may rescan `images_dir`.
+async fn gallery_lookup<T: Send + 'static>(
+    state: &AppState,
+    lookup: impl FnOnce(&AppState) -> T + Send + 'static,
+) -> std::result::Result<T, ApiError> {
+    let state = state.clone();
+    tokio::task::spawn_blocking(move || lookup(&state))
+        .await
+        .map_err(|e| {
+            tracing::error!(error = %e, "Gallery lookup task failed");
+            ApiError::internal("Gallery lookup task failed")
+        })
 }
 
-async fn sse_handler(
-    State(state): State<AppState>,
-) -> Sse<impl Stream<Item = std::result::Result<Event, Infallible>>> {
-    let rx = state.tx.subscribe();
+/// Atom feed of the newest lolcommits.
+async fn feed_handler(State(state): State<AppState>) -> Response {
+    let loaded = state.config.get();
+    // Read before listing, so an upload racing the render costs a re-render rather than
+    // a stale feed
+    let generation = state.image_index.generation();
+    if let Some(feed) = state
+        .feed
+        .lock()
+        .expect("feed cache lock poisoned")
+        .as_ref()
+        && feed.is_current(generation, &loaded)
+    {
+        return (
+            [(header::CONTENT_TYPE, feed::CONTENT_TYPE)],
+            feed.body.clone(),
+        )
+            .into_response();
+    }
 
-    let stream = async_stream::stream! {
-        crate::metrics::increment_sse_connections();
-        let _guard = SseConnectionGuard;
-        let mut rx = rx;
-        loop {
-            match rx.recv().await {
-                Ok(msg) => {
-                    yield Ok(Event::default().data(msg));
-                }
-                Err(broadcast::error::RecvError::Lagged(n)) => {
-                    tracing::warn!(skipped = n, "SSE client lagged, skipped messages");
-                    continue;
-                }
-                Err(broadcast::error::RecvError::Closed) => {
-                    break;
-                }
-            }
-        }
+    let images = match gallery_lookup(&state, |state| state.image_index.list()).await {
+        Ok(images) => images,
+        Err(e) => return e.into_response(),
     };
+    let newest = &images[..images.len().min(loaded.server.feed_entries)];
+    let body = Bytes::from(feed::render(&loaded.server, newest));
+    tracing::debug!(entries = newest.len(), generation, "Rendered feed");
+    *state.feed.lock().expect("feed cache lock poisoned") = Some(CachedFeed {
+        generation,
+        config: loaded.clone(),
+        rendered_at: std::time::Instant::now(),
+        body: body.clone(),
+    });
+    ([(header::CONTENT_TYPE, feed::CONTENT_TYPE)], body).into_response()
+}
 
-    Sse::new(stream).keep_alive(
-        axum::response::sse::KeepAlive::new()
-            .interval(std::time::Duration::from_secs(15))
-            .text("keepalive"),
-    )
+fn parse_count(name: &str, value: Option<&str>) -> std::result::Result<Option<usize>, String> {
+    value
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| format!("{name} must be a non-negative integer, got {value:?}"))
+        })
+        .transpose()
 }
 
-fn initialize_revision_cache() -> Result<HashSet<String>> {
-    let config = config::Config::load()?;
-    let server_config = config.server.clone().unwrap_or_default();
-    let images = get_image_list(&server_config)?;
-    Ok(images.into_iter().map(|img| img.revision).collect())
+/// The images matching `query`'s filters, newest first as listed, cut down to the
+/// requested page. Also returns how many matched in total.
+fn select_page(
+    images: Vec<git::CommitMetadata>,
+    query: &ImagesQuery,
+    offset: usize,
+    limit: Option<usize>,
+) -> (Vec<git::CommitMetadata>, usize) {
+    let matching: Vec<_> = images
+        .into_iter()
+        .filter(|image| query.filter.matches(image))
+        .collect();
+    let total = matching.len();
+
+    let page = matching
+        .into_iter()
+        .skip(offset)
+        .take(limit.unwrap_or(usize::MAX))
+        .collect();
+    (page, total)
+}
+
+/// The `since`/`until` window of a request, a 400 when either can't be parsed.
+fn parse_window(
+    since: Option<&str>,
+    until: Option<&str>,
+) -> std::result::Result<best_of::TimeWindow, ApiError> {
+    best_of::TimeWindow::parse(since, until)
+        .map_err(|message| ApiError::bad_request("invalid_date", message))
 }
 
-fn get_image_list(config: &config::ServerConfig) -> Result<Vec<git::CommitMetadata>> {
-    let images_dir = PathBuf::from(&config.images_dir);
+async fn best_images(
+    State(state): State<AppState>,
+    Query(query): Query<BestQuery>,
+) -> std::result::Result<Json<BestResponse>, ApiError> {
+    let window = parse_window(query.since.as_deref(), query.until.as_deref())?;
+    let metric = query
+        .metric
+        .as_deref()
+        .map(str::parse)
+        .transpose()
+        .map_err(|message| ApiError::bad_request("invalid_metric", message))?
+        .unwrap_or_default();
 
-    // Create directory if it doesn't exist
-    if !images_dir.exists() {
-        return Ok(Vec::new());
+    // Nothing records reactions yet, so only the diff metric can be answered
+    if metric == best_of::Metric::Reactions {
+        return Err(ApiError::bad_request(
+            "metric_unavailable",
+            "Reactions are not recorded by this server",
+        ));
     }
 
-    let mut images: Vec<git::CommitMetadata> = std::fs::read_dir(&images_dir)?
-        .filter_map(|entry| entry.ok())
-        .map(|entry| entry.path())
-        .filter(|path| path.extension().and_then(|s| s.to_str()) == Some("png"))
-        .filter_map(|path| image_metadata::parse_image_file(&path))
-        .collect();
+    let images = gallery_lookup(&state, |state| state.image_index.list()).await?;
+    let loaded = state.config.get();
+    let server_config = &loaded.server;
+
+    let rank = |ranked: best_of::Ranked| RankedImage {
+        score: ranked.score,
+        image: ImageMetadata::new(server_config, ranked.metadata),
+    };
 
-    // Sort by timestamp descending (newest first)
-    images.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+    let response = match best_of::select_best(images, &window, metric, &HashMap::new()) {
+        Some(best) => BestResponse {
+            metric: metric.as_str(),
+            best: Some(rank(best.best)),
+            runners_up: best.runners_up.into_iter().map(rank).collect(),
+        },
+        None => BestResponse {
+            metric: metric.as_str(),
+            best: None,
+            runners_up: Vec::new(),
+        },
+    };
 
-    Ok(images)
+    Ok(Json(response))
 }
 
-async fn upload_handler(State(state): State<AppState>, mut multipart: Multipart) -> Response {
-    let mut image_bytes: Option<Vec<u8>> = None;
-    let mut metadata: Option<UploadMetadata> = None;
+async fn stats_handler(
+    State(state): State<AppState>,
+    Query(query): Query<StatsQuery>,
+) -> std::result::Result<Json<stats::Stats>, ApiError> {
+    let window = parse_window(query.since.as_deref(), query.until.as_deref())?;
+    let filter = stats::Filter {
+        repo: query.repo.filter(|repo| !repo.is_empty()),
+        window,
+    };
 
-    // Parse multipart form
-    while let Ok(Some(field)) = multipart.next_field().await {
-        let name = field.name().map(|s| s.to_string()).unwrap_or_default();
-        tracing::debug!(field_name = %name, "Received field");
+    let images = gallery_lookup(&state, |state| state.image_index.list()).await?;
+    Ok(Json(stats::compute(&images, &filter)))
+}
 
-        match name.as_str() {
-            "image" => match field.bytes().await {
-                Ok(bytes) => {
-                    tracing::debug!(size = bytes.len(), "Received image");
-                    image_bytes = Some(bytes.to_vec());
-                }
-                Err(e) => {
-                    tracing::error!(error = %e, "Failed to read image bytes");
-                }
-            },
-            "metadata" => match field.bytes().await {
-                Ok(bytes) => {
-                    if let Ok(text) = String::from_utf8(bytes.to_vec())
-                        && let Ok(parsed) = serde_json::from_str::<UploadMetadata>(&text)
-                    {
-                        tracing::debug!(?parsed, "Received metadata");
-                        metadata = Some(parsed);
-                    }
-                }
-                Err(e) => {
-                    tracing::error!(error = %e, "Failed to read metadata bytes");
-                }
-            },
-            _ => {
-                tracing::debug!(field_name = %name, "Ignoring unknown field");
+/// Size of the chunks an export is streamed to the client in.
+const EXPORT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Stream a ZIP of the matching images with a manifest of their metadata. The archive
+/// is written on a blocking thread straight into the response body, a chunk at a time.
+async fn export_handler(
+    State(state): State<AppState>,
+    Query(query): Query<ExportQuery>,
+) -> std::result::Result<Response, ApiError> {
+    let window = parse_window(query.since.as_deref(), query.until.as_deref())?;
+    let images: Vec<_> = gallery_lookup(&state, |state| state.image_index.list())
+        .await?
+        .into_iter()
+        .filter(|image| query.filter.matches(image) && window.contains(&image.timestamp))
+        .collect();
+    let filename = export_filename(query.filter.repo.as_deref(), &window);
+    tracing::info!(filename, images = images.len(), "Exporting images");
+    let disposition = format!("attachment; filename=\"{filename}\"");
+
+    // A few chunks of slack, so reading the next image overlaps sending the last
+    let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+    let errors = tx.clone();
+    tokio::task::spawn_blocking(move || {
+        let writer = BodyWriter {
+            tx,
+            buffer: Vec::with_capacity(EXPORT_CHUNK_SIZE),
+        };
+        match export::write_archive(writer, &images) {
+            Ok(included) => tracing::info!(filename, included, "Exported images"),
+            Err(e) => {
+                tracing::warn!(filename, error = %e, "Export failed");
+                // Fail the body, so the client sees a broken download rather than a
+                // truncated archive
+                let _ = errors.blocking_send(Err(std::io::Error::other(e.to_string())));
             }
         }
-    }
-
-    let Some(image_bytes) = image_bytes else {
-        return (StatusCode::BAD_REQUEST, "Missing image field").into_response();
+    });
+    let body = async_stream::stream! {
+        while let Some(chunk) = rx.recv().await {
+            yield chunk;
+        }
     };
 
-    let Some(metadata) = metadata else {
-        return (StatusCode::BAD_REQUEST, "Missing metadata field").into_response();
-    };
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/zip".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        axum::body::Body::from_stream(body),
+    )
+        .into_response())
+}
 
-    tracing::info!(
-        revision = %metadata.revision,
-        repo = %metadata.repo_name,
-        "Received upload, spawning async processor"
-    );
-    crate::metrics::record_upload("accepted");
+/// `lolcommits[-{repo}][-{since}-to-{until}].zip`, naming an export after what it holds.
+fn export_filename(repo: Option<&str>, window: &best_of::TimeWindow) -> String {
+    let mut name = "lolcommits".to_string();
+    if let Some(repo) = repo.filter(|repo| !repo.is_empty()) {
+        name.push('-');
+        // Keep the header value plain
+        name.extend(repo.chars().map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        }));
+    }
+    match (window.since, window.until) {
+        (Some(since), Some(until)) => name.push_str(&format!("-{since}-to-{until}")),
+        (Some(since), None) => name.push_str(&format!("-since-{since}")),
+        (None, Some(until)) => name.push_str(&format!("-until-{until}")),
+        (None, None) => {}
+    }
+    name + ".zip"
+}
 
-    // Spawn async processing task
-    let tx = state.tx.clone();
-    let revision_cache = state.revision_cache.clone();
-    tokio::spawn(async move {
-        if let Err(e) = process_image_async(image_bytes, metadata, tx, revision_cache).await {
-            tracing::error!(error = %e, "Failed to process image");
-            crate::metrics::record_upload("failed");
+/// Hands what is written to it to a streamed response body, for producing a body on a
+/// blocking thread. Fails once the client has gone away.
+struct BodyWriter {
+    tx: tokio::sync::mpsc::Sender<std::io::Result<Bytes>>,
+    buffer: Vec<u8>,
+}
+
+impl std::io::Write for BodyWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        if self.buffer.len() >= EXPORT_CHUNK_SIZE {
+            self.flush()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
         }
+        let chunk = std::mem::replace(&mut self.buffer, Vec::with_capacity(EXPORT_CHUNK_SIZE));
+        self.tx
+            .blocking_send(Ok(Bytes::from(chunk)))
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::BrokenPipe))
+    }
+}
+
+async fn get_config(State(state): State<AppState>) -> Json<ConfigResponse> {
+    Json(ConfigResponse {
+        gallery_title: state.config.get().server.gallery_title.clone(),
+        max_upload_bytes: state.max_upload_bytes,
+    })
+}
+
+async fn health_handler(State(state): State<AppState>) -> Json<HealthResponse> {
+    let disk_space = match state.disk_space.check() {
+        Ok(()) => "ok",
+        Err(_) => "low",
+    };
+    let background = &state.config.get().background;
+    let status = match (background, disk_space) {
+        (Background::Missing(_), _) | (_, "low") => "degraded",
+        _ => "ok",
+    };
+    Json(HealthResponse {
+        status,
+        read_only: state.read_only.is_enabled(),
+        background: background.status(),
+        disk_space,
+        gallery_bytes: state.disk_space.gallery_bytes(),
+        sse_clients: state.sse_clients.load(Ordering::Relaxed),
+    })
+}
+
+/// Whether `revision` of `repo` is already in the gallery, so clients can skip the
+/// camera for uploads that would be dropped as duplicates.
+async fn exists_handler(
+    State(state): State<AppState>,
+    Query(query): Query<ExistsQuery>,
+) -> std::result::Result<Json<ExistsResponse>, ApiError> {
+    let revision = query.revision.clone();
+    let existing = gallery_lookup(&state, move |state| state.revision_cache.get(&revision));
+    let existing = existing.await?.filter(|entry| {
+        query
+            .repo
+            .as_ref()
+            .is_none_or(|repo| *repo == entry.repo_name)
     });
 
-    // Return 202 Accepted immediately
-    (
-        StatusCode::ACCEPTED,
-        Json(UploadResponse {
-            status: "accepted".to_string(),
-            message: "Processing in background".to_string(),
-        }),
+    Ok(Json(ExistsResponse {
+        exists: existing.is_some(),
+        filename: existing.map(|entry| entry.filename),
+    }))
+}
+
+/// Check the request carries the configured admin bearer token, failing with the
+/// rejection to send when it doesn't.
+fn require_admin(state: &AppState, headers: &HeaderMap) -> std::result::Result<(), ApiError> {
+    let Some(expected) = state.admin_token.as_deref() else {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            "admin_disabled",
+            "Admin API is disabled (no admin_token configured)",
+        ));
+    };
+
+    let provided = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    // In constant time, so response times don't give the token away a byte at a time
+    let matches =
+        provided.is_some_and(|provided| bool::from(provided.as_bytes().ct_eq(expected.as_bytes())));
+    if matches {
+        Ok(())
+    } else {
+        Err(ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            "unauthorized",
+            "Missing or invalid admin token",
+        ))
+    }
+}
+
+/// The processing an upload from `repo` of a `type` commit on `branch` would get with
+/// the current config, without an image.
+async fn pipeline_plan_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<PlanQuery>,
+) -> std::result::Result<Json<post_processor::ProcessingPlan>, ApiError> {
+    require_admin(&state, &headers)?;
+    plan_response(&state.config.get().config, query)
+}
+
+fn plan_response(
+    config: &config::Config,
+    query: PlanQuery,
+) -> std::result::Result<Json<post_processor::ProcessingPlan>, ApiError> {
+    let metadata = git::CommitMetadata {
+        path: PathBuf::new(),
+        revision: String::new(),
+        message: String::new(),
+        commit_type: query.commit_type,
+        scope: String::new(),
+        timestamp: String::new(),
+        repo_name: query.repo,
+        branch_name: query.branch,
+        author_name: String::new(),
+        author_email: String::new(),
+        breaking: false,
+        co_authors: Vec::new(),
+        stats: git::DiffStats {
+            files_changed: 0,
+            insertions: 0,
+            deletions: 0,
+        },
+    };
+
+    let plan = post_processor::resolve_plan(config, &metadata, &Overrides::new(), None)?;
+    Ok(Json(plan))
+}
+
+/// Re-read the config file. The current config is kept when the new one is invalid.
+async fn reload_config(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> std::result::Result<Json<ReloadConfigResponse>, ApiError> {
+    require_admin(&state, &headers)?;
+
+    let loaded = state.config.reload()?;
+    Ok(Json(ReloadConfigResponse {
+        reloaded: true,
+        background: loaded.background.status(),
+    }))
+}
+
+/// Refuse mutating requests while the server is read-only.
+fn check_writable(state: &AppState) -> std::result::Result<(), ApiError> {
+    if state.read_only.is_enabled() {
+        return Err(ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            READ_ONLY_ERROR_CODE,
+            "Server is in read-only mode, uploads are temporarily disabled",
+        ));
+    }
+    Ok(())
+}
+
+async fn set_read_only(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<ReadOnlyRequest>,
+) -> std::result::Result<Json<ReadOnlyResponse>, ApiError> {
+    require_admin(&state, &headers)?;
+
+    state.read_only.set(request.enabled).map_err(|e| {
+        tracing::error!(error = %e, "Failed to persist read-only mode");
+        ApiError::internal(format!("Failed to persist read-only mode: {e}"))
+    })?;
+    Ok(Json(ReadOnlyResponse {
+        read_only: request.enabled,
+    }))
+}
+
+/// Stream gallery events, starting with `hello` (JSON format only, legacy clients would
+/// take it for a change) and matching the query's filter. The client address comes from
+/// the connection when the server was started with it.
+async fn sse_handler(
+    State(state): State<AppState>,
+    Query(query): Query<EventsQuery>,
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
+) -> Sse<impl Stream<Item = std::result::Result<Event, Infallible>>> {
+    // Subscribed before counting, so nothing added in between is missed
+    let rx = state.tx.subscribe();
+    let filter = query.filter.clone();
+    let image_count = gallery_lookup(&state, move |state| {
+        state
+            .image_index
+            .list()
+            .iter()
+            .filter(|image| filter.matches(image))
+            .count()
+    })
+    .await
+    .unwrap_or_default();
+    let client_ip = connect_info.map_or_else(
+        || "unknown".to_string(),
+        |Extension(ConnectInfo(address))| address.ip().to_string(),
+    );
+    let clients = state.sse_clients.clone();
+
+    let stream = async_stream::stream! {
+        let _guard = SseConnectionGuard::new(clients, client_ip);
+        if matches!(query.format, EventFormat::Json) {
+            yield Ok(query.to_sse(GalleryEvent::hello(image_count)));
+        }
+        let mut rx = rx;
+        loop {
+            match rx.recv().await {
+                Ok(event) => {
+                    if query.wants(&event) {
+                        yield Ok(query.to_sse(event));
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    tracing::warn!(skipped = n, "SSE client lagged, telling it to resync");
+                    yield Ok(query.to_sse(GalleryEvent::resync(n)));
+                }
+                Err(broadcast::error::RecvError::Closed) => {
+                    break;
+                }
+            }
+        }
+    };
+
+    Sse::new(stream).keep_alive(
+        axum::response::sse::KeepAlive::new()
+            .interval(std::time::Duration::from_secs(15))
+            .text("keepalive"),
     )
-        .into_response()
 }
 
-async fn process_image_async(
-    image_bytes: Vec<u8>,
-    metadata: UploadMetadata,
-    tx: broadcast::Sender<String>,
-    revision_cache: Arc<RwLock<HashSet<String>>>,
-) -> Result<()> {
-    tracing::info!(revision = %metadata.revision, force = metadata.force, "Starting async image processing");
+/// How long clients may keep a gallery image. Filenames are unique per capture, so an
+/// image never changes under its name short of being reprocessed.
+const IMAGE_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
 
-    // Load config
-    let config = config::Config::load()?;
+/// Serve a gallery image. Only image files directly in images_dir are served: never
+/// dotfiles (which covers in-progress tempfiles), sidecars or anything else left in the
+/// directory. PNGs come from the in-memory cache when there is one, reading through to
+/// disk on a miss; range and `If-Modified-Since` requests and files too large to cache
+/// are served straight from disk.
+async fn image_handler(
+    State(images): State<ImageFiles>,
+    Path(filename): Path<String>,
+    request: Request,
+) -> Response {
+    if !is_plain_filename(&filename)
+        || !image_metadata::is_image_file(std::path::Path::new(&filename))
+    {
+        tracing::debug!(filename, "Not serving a non-image");
+        return StatusCode::NOT_FOUND.into_response();
+    }
 
-    // Check if revision already exists (unless force flag is set)
-    if !metadata.force {
-        let cache = revision_cache.read().await;
-        if cache.contains(&metadata.revision) {
-            tracing::info!(revision = %metadata.revision, "Revision already exists, skipping upload");
-            crate::metrics::record_upload("duplicate_skipped");
-            return Ok(());
+    let path = images.images_dir.join(&filename);
+    let file_metadata = match tokio::fs::metadata(&path).await {
+        Ok(file_metadata) if file_metadata.is_file() => file_metadata,
+        _ => {
+            tracing::debug!(filename, "Image not found");
+            return StatusCode::NOT_FOUND.into_response();
         }
+    };
+
+    let etag = file_etag(&file_metadata);
+    if etag_matches(request.headers(), &etag) {
+        return cacheable(StatusCode::NOT_MODIFIED.into_response(), &etag);
     }
 
-    // Decode image
-    let _timer = crate::metrics::ScopedTimer::image_processing();
-    let image = image::load_from_memory(&image_bytes)?;
-    tracing::debug!("Decoded image");
+    let response = match &images.cache {
+        Some(cache)
+            if filename.ends_with(".png")
+                && !request.headers().contains_key(header::RANGE)
+                && !request.headers().contains_key(header::IF_MODIFIED_SINCE) =>
+        {
+            cached_png(cache, &filename, &path, &file_metadata, request).await
+        }
+        _ => serve_from_disk(&path, request).await,
+    };
+    cacheable(response, &etag)
+}
 
-    // Get server config for processing
-    let server_config = config.server.clone().unwrap_or_default();
+async fn cached_png(
+    cache: &ImageCache,
+    filename: &str,
+    path: &std::path::Path,
+    file_metadata: &std::fs::Metadata,
+    request: Request,
+) -> Response {
+    let Ok(mtime) = file_metadata.modified() else {
+        return serve_from_disk(path, request).await;
+    };
 
-    // Background replacement
-    let processed_image = image_processor::replace_background(&server_config, image)?;
-    tracing::info!("Background replaced");
+    if let Some(bytes) = cache.get(filename, mtime) {
+        return last_modified(png_response(bytes), mtime);
+    }
 
-    // Create commit metadata
-    let commit_metadata = git::CommitMetadata {
-        path: PathBuf::new(),
-        revision: metadata.revision.clone(),
-        message: metadata.message,
-        commit_type: metadata.commit_type,
-        scope: metadata.scope,
-        timestamp: metadata.timestamp,
-        repo_name: metadata.repo_name.clone(),
-        branch_name: metadata.branch_name,
-        stats: git::DiffStats {
-            files_changed: metadata.files_changed,
-            insertions: metadata.insertions,
-            deletions: metadata.deletions,
+    if !cache.accepts(file_metadata.len()) {
+        return serve_from_disk(path, request).await;
+    }
+
+    match tokio::fs::read(path).await {
+        Ok(data) => {
+            let bytes = Bytes::from(data);
+            cache.insert(filename, mtime, bytes.clone());
+            last_modified(png_response(bytes), mtime)
+        }
+        Err(e) => {
+            tracing::warn!(path = %path.display(), error = %e, "Failed to read image for cache");
+            serve_from_disk(path, request).await
+        }
+    }
+}
+
+/// Add the `Last-Modified` header a response from disk would have had.
+fn last_modified(mut response: Response, mtime: std::time::SystemTime) -> Response {
+    let http_date = chrono::DateTime::<chrono::Utc>::from(mtime)
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string();
+    if let Ok(value) = header::HeaderValue::from_str(&http_date) {
+        response.headers_mut().insert(header::LAST_MODIFIED, value);
+    }
+    response
+}
+
+/// Add the caching headers to a successful image response. Errors such as an
+/// unsatisfiable range are left alone.
+fn cacheable(mut response: Response, etag: &str) -> Response {
+    let status = response.status();
+    if status.is_success() || status == StatusCode::NOT_MODIFIED {
+        let headers = response.headers_mut();
+        headers.insert(
+            header::CACHE_CONTROL,
+            header::HeaderValue::from_static(IMAGE_CACHE_CONTROL),
+        );
+        if let Ok(etag) = header::HeaderValue::from_str(etag) {
+            headers.insert(header::ETAG, etag);
+        }
+    }
+    response
+}
+
+async fn serve_from_disk(path: &std::path::Path, request: Request) -> Response {
+    match ServeFile::new(path).oneshot(request).await {
+        Ok(response) => response.into_response(),
+        Err(never) => match never {},
+    }
+}
+
+fn png_response(bytes: Bytes) -> Response {
+    ([(header::CONTENT_TYPE, "image/png")], bytes).into_response()
+}
+
+/// Only plain filenames within images_dir, never paths.
+fn is_plain_filename(filename: &str) -> bool {
+    !filename.starts_with('.') && !filename.contains(['/', '\\'])
+}
+
+/// Everything about one image. Answers 422 with what the filename gives when the image
+/// has no metadata of its own, and honours `If-None-Match` with an ETag of the file's
+/// modification time and size.
+async fn image_detail_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(filename): Path<String>,
+) -> std::result::Result<Response, ApiError> {
+    check_image_filename(&filename)?;
+
+    let loaded = state.config.get();
+    let path = PathBuf::from(&loaded.server.images_dir).join(&filename);
+    let file = match tokio::fs::metadata(&path).await {
+        Ok(file) if file.is_file() => file,
+        _ => return Err(image_not_found(&filename)),
+    };
+
+    let etag = file_etag(&file);
+    if etag_matches(&headers, &etag) {
+        return Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response());
+    }
+
+    let (status, detail) = gallery_lookup(&state, move |_| {
+        read_image_detail(&loaded.server, &filename, path, file.len())
+    })
+    .await??;
+    Ok((status, [(header::ETAG, etag)], Json(detail)).into_response())
+}
+
+/// Read everything [`image_detail_handler`] reports about the `size`-byte image at
+/// `path`, with the status to answer with.
+fn read_image_detail(
+    server: &config::ServerConfig,
+    filename: &str,
+    path: PathBuf,
+    size: u64,
+) -> std::result::Result<(StatusCode, ImageDetail), ApiError> {
+    let recorded = image_metadata::read_metadata(&path).unwrap_or_else(|e| {
+        tracing::warn!(path = %path.display(), error = %e, "Failed to read image metadata");
+        None
+    });
+    let (metadata, metadata_source, status) = match recorded {
+        Some(metadata) if image_metadata::is_png(&path) => (metadata, "embedded", StatusCode::OK),
+        Some(metadata) => (metadata, "sidecar", StatusCode::OK),
+        None => match image_metadata::parse_filename(&path) {
+            Some(metadata) => (metadata, "filename", StatusCode::UNPROCESSABLE_ENTITY),
+            None => {
+                return Err(ApiError::new(
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    "no_metadata",
+                    format!("{filename} has no metadata and an unrecognised name"),
+                ));
+            }
         },
     };
+    let dimensions = image_metadata::read_dimensions(&path)
+        .inspect_err(|e| {
+            tracing::warn!(path = %path.display(), error = %e, "Failed to read image dimensions")
+        })
+        .ok();
+    let text_chunks = image_metadata::read_text_chunks(&path).unwrap_or_default();
+
+    let detail = ImageDetail {
+        image: ImageMetadata::new(server, git::CommitMetadata { path, ..metadata }),
+        size_bytes: size,
+        width: dimensions.map(|(width, _)| width),
+        height: dimensions.map(|(_, height)| height),
+        metadata_source,
+        text_chunks,
+    };
+    Ok((status, detail))
+}
 
-    // Apply chyron if enabled in server config
-    let final_image = if server_config.burned_in_chyron {
-        let chyron_config = config.burned_in_chyron.clone().unwrap_or_default();
-        let image_with_chyron =
-            image_processor::burn_in_chyron(&chyron_config, processed_image, &commit_metadata)?;
-        tracing::debug!("Burned in chyron");
-        image_with_chyron
+/// Only plain image filenames name gallery images, anything else is a 404.
+fn check_image_filename(filename: &str) -> std::result::Result<(), ApiError> {
+    if is_plain_filename(filename) && image_metadata::is_image_file(std::path::Path::new(filename))
+    {
+        Ok(())
     } else {
-        tracing::debug!("Chyron disabled");
-        processed_image
+        Err(image_not_found(filename))
+    }
+}
+
+fn image_not_found(filename: &str) -> ApiError {
+    ApiError::not_found(format!("no image {filename} in the gallery"))
+}
+
+/// Whether the request's `If-None-Match` names `etag`, so a 304 will do.
+fn etag_matches(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            // If-None-Match compares weakly
+            value
+                .split(',')
+                .map(str::trim)
+                .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag)
+        })
+}
+
+/// A strong ETag from a file's modification time and size, which change whenever the
+/// image is replaced.
+fn file_etag(file: &std::fs::Metadata) -> String {
+    let modified = file
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+        .map_or(0, |since_epoch| since_epoch.as_nanos());
+    format!("\"{modified:x}-{:x}\"", file.len())
+}
+
+/// Serve the chyron for an image on its own, as a transparent PNG the size of the
+/// image, for compositing in other tools.
+async fn chyron_overlay_handler(
+    State(state): State<AppState>,
+    Path(filename): Path<String>,
+) -> std::result::Result<Response, ApiError> {
+    check_image_filename(&filename)?;
+
+    let loaded = state.config.get();
+
+    // Read-only mode promises not to touch images_dir, so render without caching
+    let write_cache = !state.read_only.is_enabled();
+    let rendered = {
+        let filename = filename.clone();
+        tokio::task::spawn_blocking(move || {
+            render_chyron_overlay(&loaded.config, &filename, write_cache)
+        })
+        .await
+    };
+
+    match rendered {
+        Ok(Ok(Some(bytes))) => Ok(png_response(Bytes::from(bytes))),
+        Ok(Ok(None)) => Err(image_not_found(&filename)),
+        Ok(Err(e)) => {
+            tracing::error!(error = %e, "Failed to render chyron overlay");
+            Err(ApiError::internal(format!(
+                "Failed to render chyron overlay: {e}"
+            )))
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "Chyron overlay task failed");
+            Err(ApiError::internal("Chyron overlay task failed"))
+        }
+    }
+}
+
+/// Remove an image from the gallery along with its cached overlay, its revision and
+/// its index entry, then tell SSE clients.
+async fn delete_image(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(filename): Path<String>,
+) -> std::result::Result<StatusCode, ApiError> {
+    require_admin(&state, &headers)?;
+    check_writable(&state)?;
+    check_image_filename(&filename)?;
+
+    let deleted = {
+        let state = state.clone();
+        let filename = filename.clone();
+        tokio::task::spawn_blocking(move || remove_image(&state, &filename)).await
+    };
+    let removed = match deleted {
+        Ok(result) => result?,
+        Err(e) => {
+            tracing::error!(error = %e, "Delete task failed");
+            return Err(ApiError::internal("Delete task failed"));
+        }
+    };
+
+    let _ = state
+        .tx
+        .send(GalleryEvent::image_deleted(&filename, removed));
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The blocking part of [`delete_image`]: remove the files and forget the image,
+/// returning what the index held for it.
+fn remove_image(
+    state: &AppState,
+    filename: &str,
+) -> std::result::Result<Option<git::CommitMetadata>, ApiError> {
+    let images_dir = PathBuf::from(&state.config.get().server.images_dir);
+    let path = images_dir.join(filename);
+    let size = match std::fs::metadata(&path) {
+        Ok(metadata) if metadata.is_file() => metadata.len(),
+        _ => return Err(image_not_found(filename)),
+    };
+    if let Err(e) = std::fs::remove_file(&path) {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            return Err(image_not_found(filename));
+        }
+        tracing::error!(path = %path.display(), error = %e, "Failed to delete image");
+        return Err(ApiError::internal(format!("Failed to delete image: {e}")));
+    }
+    tracing::info!(filename, "Deleted image");
+    state.disk_space.record_deleted(size);
+
+    let overlay = images_dir
+        .join(CHYRON_CACHE_DIR)
+        .join(chyron_overlay_name(filename));
+    if let Err(e) = std::fs::remove_file(&overlay)
+        && e.kind() != std::io::ErrorKind::NotFound
+    {
+        tracing::warn!(path = %overlay.display(), error = %e, "Failed to delete cached chyron overlay");
+    }
+    let sidecar = image_metadata::sidecar_path(&path);
+    if let Err(e) = std::fs::remove_file(&sidecar)
+        && e.kind() != std::io::ErrorKind::NotFound
+    {
+        tracing::warn!(path = %sidecar.display(), error = %e, "Failed to delete metadata sidecar");
+    }
+    let original = std::path::Path::new(&state.config.get().server.state_dir)
+        .join(ORIGINALS_DIR)
+        .join(filename);
+    if let Err(e) = std::fs::remove_file(&original)
+        && e.kind() != std::io::ErrorKind::NotFound
+    {
+        tracing::warn!(path = %original.display(), error = %e, "Failed to delete original upload");
+    }
+    if let Some(cache) = &state.image_cache {
+        cache.invalidate(filename);
+    }
+    // Which the revision cache follows, handing the revision to any earlier upload of it
+    Ok(state.image_index.remove(filename))
+}
+
+/// Redo an image's processing from its kept original with the current config, replacing
+/// the published file, then tell SSE clients.
+async fn reprocess_image(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(filename): Path<String>,
+) -> std::result::Result<Json<ImageMetadata>, ApiError> {
+    require_admin(&state, &headers)?;
+    check_writable(&state)?;
+    check_image_filename(&filename)?;
+    check_disk_space(&state.disk_space)?;
+
+    let loaded = state.config.get();
+    let path = PathBuf::from(&loaded.server.images_dir).join(&filename);
+    let Ok(previous_size) = tokio::fs::metadata(&path).await.map(|m| m.len()) else {
+        return Err(image_not_found(&filename));
+    };
+    let original = std::path::Path::new(&loaded.server.state_dir)
+        .join(ORIGINALS_DIR)
+        .join(&filename);
+    if !tokio::fs::metadata(&original)
+        .await
+        .is_ok_and(|m| m.is_file())
+    {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            ORIGINAL_MISSING_ERROR_CODE,
+            format!(
+                "no original kept for {filename}, only uploads received with keep_originals enabled can be reprocessed"
+            ),
+        ));
+    }
+
+    let model = state.segmentation_model.clone();
+    let server_config = loaded.server.clone();
+    let index = state.image_index.clone();
+    let reprocessed = tokio::task::spawn_blocking(move || {
+        reprocess(&loaded, &model, &original, &path).inspect(|saved| index.insert(&saved.path))
+    })
+    .await;
+
+    let saved = match reprocessed {
+        Ok(Ok(saved)) => saved,
+        Ok(Err(e)) => {
+            tracing::error!(filename, error = %e, "Failed to reprocess image");
+            return Err(ApiError::internal(format!(
+                "Failed to reprocess image: {e}"
+            )));
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "Reprocessing task failed");
+            return Err(ApiError::internal("Reprocessing task failed"));
+        }
+    };
+    tracing::info!(filename, "Reprocessed image");
+
+    state.disk_space.record_deleted(previous_size);
+    state.disk_space.record_saved(&saved.path);
+    if let Some(cache) = &state.image_cache {
+        cache.invalidate(&filename);
+    }
+
+    let image = ImageMetadata::new(&server_config, saved);
+    match GalleryEvent::image_updated(&image) {
+        Ok(event) => {
+            let _ = state.tx.send(event);
+        }
+        Err(e) => tracing::warn!(error = %e, "Failed to broadcast image_updated event"),
+    }
+    Ok(Json(image))
+}
+
+/// Process `original` again and atomically replace the image at `path` with the result,
+/// keeping its metadata and how it was processed.
+fn reprocess(
+    loaded: &LoadedConfig,
+    model: &Arc<SegmentationModel>,
+    original: &std::path::Path,
+    path: &std::path::Path,
+) -> Result<git::CommitMetadata> {
+    let metadata = image_metadata::read_metadata(path)?.ok_or_else(|| {
+        std::io::Error::other(format!("{} has no lolcommit metadata", path.display()))
+    })?;
+    let processing = image_metadata::read_processing_info(path)?;
+
+    let image = crate::orientation::load_from_memory(&std::fs::read(original)?)?;
+    let (mut processed, processing) =
+        run_pipeline(&loaded.config, model, vec![image], &metadata, processing)?;
+
+    let (Some(dir), Some(filename)) = (path.parent(), path.file_name().and_then(|s| s.to_str()))
+    else {
+        return Err(std::io::Error::other(format!("invalid image path {}", path.display())).into());
+    };
+    let saved = image_metadata::save_gallery_image(
+        dir,
+        filename,
+        &processed.remove(0),
+        &metadata,
+        &processing,
+        loaded.server.jpeg_quality,
+    )?;
+    Ok(git::CommitMetadata {
+        path: saved,
+        ..metadata
+    })
+}
+
+/// Run the post-processing the config and `processing` call for on an upload's frames,
+/// a single one for a still, after correcting their exposure if they're too dark. The
+/// upload's own choice of chyron wins over the config's. Returns the frames with how they
+/// were processed, to record with the image.
+fn run_pipeline(
+    config: &config::Config,
+    model: &Arc<SegmentationModel>,
+    mut frames: Vec<image::DynamicImage>,
+    metadata: &git::CommitMetadata,
+    processing: image_metadata::ProcessingInfo,
+) -> Result<(Vec<image::DynamicImage>, image_metadata::ProcessingInfo)> {
+    let plan = post_processor::resolve_plan(
+        config,
+        metadata,
+        &processing.overrides,
+        processing.burned_in_chyron,
+    )?;
+    if !plan.overrides.is_empty() {
+        tracing::info!(overrides = ?plan.overrides, "Applied processing overrides");
+    }
+    let server_config = config.server.clone().unwrap_or_default();
+    let exposure_corrected = crate::exposure::correct(
+        &mut frames,
+        server_config.low_light_threshold,
+        server_config.auto_exposure_correction,
+    );
+    let stages = post_processor::build(&plan, model);
+    let processed =
+        post_processor::run_frames(&stages, frames, metadata, server_config.animation_chyron)?;
+    tracing::info!(
+        stages = stages.len(),
+        frames = processed.len(),
+        exposure_corrected,
+        "Post-processing complete"
+    );
+    Ok((
+        processed,
+        image_metadata::ProcessingInfo {
+            exposure_corrected,
+            ..processing
+        },
+    ))
+}
+
+/// PNG bytes of the chyron overlay for `filename`, `None` when there is no such image.
+/// Overlays are cached in [`CHYRON_CACHE_DIR`] and re-rendered when the image changes.
+fn render_chyron_overlay(
+    config: &config::Config,
+    filename: &str,
+    write_cache: bool,
+) -> Result<Option<Vec<u8>>> {
+    let images_dir = PathBuf::from(&config.server.clone().unwrap_or_default().images_dir);
+    let path = images_dir.join(filename);
+    let Ok(image_mtime) = std::fs::metadata(&path).and_then(|m| m.modified()) else {
+        return Ok(None);
     };
 
-    // Get output path
-    let output_path = get_output_path(&server_config, &metadata.repo_name, &metadata.revision)?;
+    let cache_dir = images_dir.join(CHYRON_CACHE_DIR);
+    let cached_name = chyron_overlay_name(filename);
+    let cached_path = cache_dir.join(&cached_name);
+    if let Ok(cached_mtime) = std::fs::metadata(&cached_path).and_then(|m| m.modified())
+        && cached_mtime >= image_mtime
+    {
+        tracing::debug!(path = %cached_path.display(), "Serving cached chyron overlay");
+        return Ok(Some(std::fs::read(&cached_path)?));
+    }
+
+    let Some(metadata) = image_metadata::parse_image_file(&path) else {
+        return Ok(None);
+    };
+    let (width, height) = image::image_dimensions(&path)?;
+    let chyron_config = config.burned_in_chyron.clone().unwrap_or_default();
+    let fonts = ChyronFonts::from_config(&chyron_config)?;
+    let overlay = chyron::render_chyron_overlay(&chyron_config, &fonts, width, height, &metadata);
 
-    // Write to temporary file first, then atomically move to final destination
-    let temp_file = tempfile::NamedTempFile::new_in(
-        output_path
-            .parent()
-            .ok_or_else(|| std::io::Error::other("Invalid output path"))?,
+    let mut bytes = Vec::new();
+    overlay.write_to(
+        &mut std::io::Cursor::new(&mut bytes),
+        image::ImageFormat::Png,
     )?;
-    let temp_path = temp_file.path();
 
-    tracing::debug!(temp_path = %temp_path.display(), "Writing to temporary file");
-    image_metadata::save_png_with_metadata(&final_image, temp_path, &commit_metadata)?;
+    if write_cache {
+        match crate::storage::atomic_write(&cache_dir, &cached_name, &bytes) {
+            Ok(path) => tracing::debug!(path = %path.display(), "Cached chyron overlay"),
+            Err(e) => tracing::warn!(error = %e, "Failed to cache chyron overlay"),
+        }
+    }
+
+    Ok(Some(bytes))
+}
 
-    // Atomically move temp file to final destination
-    temp_file
-        .persist(&output_path)
-        .map_err(|e| std::io::Error::other(e.to_string()))?;
-    tracing::info!(path = %output_path.display(), "Saved lolcommit with metadata");
-    crate::metrics::record_upload("processed");
+/// Name of the cached chyron overlay for the image `filename`. Overlays are always PNGs,
+/// whatever the image's format.
+fn chyron_overlay_name(filename: &str) -> String {
+    std::path::Path::new(filename)
+        .with_extension("png")
+        .to_string_lossy()
+        .into_owned()
+}
 
-    // Add revision to cache
+/// Check the upload's processing overrides up front so the client gets a 400 rather
+/// than a background processing failure.
+fn check_overrides(allowed: &[String], requested: &Overrides) -> std::result::Result<(), ApiError> {
+    if requested.is_empty() {
+        return Ok(());
+    }
+
+    let mut scratch = config::ServerConfig::default();
+    overrides::apply(&mut scratch, requested, allowed).map_err(|error| {
+        tracing::info!(error = %error, "Rejecting upload with invalid overrides");
+        ApiError::bad_request("invalid_override", error.to_string())
+    })
+}
+
+/// Read an upload's metadata part: a 415 when it's sent as something other than JSON,
+/// and a 422 naming the field when it doesn't parse or holds values no commit has. A part
+/// without a content type is taken to be JSON, as `curl -F` sends them.
+fn parse_upload_metadata(
+    content_type: Option<&str>,
+    bytes: &[u8],
+) -> std::result::Result<UploadMetadata, ApiError> {
+    if let Some(content_type) = content_type
+        && !is_json(content_type)
     {
-        let mut cache = revision_cache.write().await;
-        cache.insert(metadata.revision.clone());
-        tracing::debug!(revision = %metadata.revision, "Added revision to cache");
-        crate::metrics::set_revision_cache_size(cache.len());
-        crate::metrics::increment_images_total();
+        tracing::info!(content_type, "Rejecting upload with non-JSON metadata");
+        return Err(ApiError::new(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "unsupported_media_type",
+            format!("metadata must be application/json, not {content_type}"),
+        ));
     }
 
-    // Broadcast new image event to SSE clients
-    let _ = tx.send("new_image".to_string());
-    tracing::debug!("Broadcasted new_image event to SSE clients");
+    let deserializer = &mut serde_json::Deserializer::from_slice(bytes);
+    let metadata: UploadMetadata = serde_path_to_error::deserialize(deserializer)
+        .map_err(|e| invalid_metadata(&e.path().to_string(), e.into_inner()))?;
+    match metadata.problem() {
+        Some((field, problem)) => Err(invalid_metadata(field, problem)),
+        None => Ok(metadata),
+    }
+}
 
-    Ok(())
+fn is_json(content_type: &str) -> bool {
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    essence == "application/json" || essence.ends_with("+json")
+}
+
+fn invalid_metadata(field: &str, problem: impl std::fmt::Display) -> ApiError {
+    tracing::info!(field, %problem, "Rejecting upload with invalid metadata");
+    ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        INVALID_METADATA_ERROR_CODE,
+        format!("metadata {field}: {problem}"),
+    )
+    .with_detail("field", field)
 }
 
-fn get_output_path(
-    config: &config::ServerConfig,
-    repo_name: &str,
-    commit_sha: &str,
-) -> Result<PathBuf> {
-    let images_dir = PathBuf::from(&config.images_dir);
+/// A 507 for uploads arriving while images_dir is low on space.
+fn check_disk_space(disk_space: &DiskSpace) -> std::result::Result<(), ApiError> {
+    disk_space.check().map_err(|e| {
+        tracing::warn!(error = %e, "Rejecting upload, low on disk space");
+        ApiError::from(e)
+    })
+}
 
-    // Ensure directory exists
-    std::fs::create_dir_all(&images_dir)?;
+async fn upload_handler(
+    State(state): State<AppState>,
+    mut multipart: Multipart,
+) -> std::result::Result<Response, ApiError> {
+    check_writable(&state).inspect_err(|_| {
+        tracing::info!("Rejecting upload, server is read-only");
+        crate::metrics::record_upload("rejected_read_only");
+    })?;
+    check_disk_space(&state.disk_space)
+        .inspect_err(|_| crate::metrics::record_upload("rejected_low_disk_space"))?;
 
-    let timestamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
-    let filename = format!("{}-{}-{}.png", repo_name, timestamp, commit_sha);
+    // Before reading the body, so a busy server doesn't take it in only to refuse it
+    let Some(mut ticket) = state.processing.enqueue() else {
+        tracing::warn!(
+            waiting = state.processing.waiting(),
+            "Rejecting upload, processing backlog is full"
+        );
+        crate::metrics::record_upload("rejected_busy");
+        return Err(busy_error());
+    };
 
-    let output_path = images_dir.join(filename);
+    // Several image parts are the frames of an animated capture, in order. Each is
+    // removed from disk when dropped, however the upload ends.
+    let images_dir = PathBuf::from(&state.config.get().server.images_dir);
+    let mut frames: Vec<tempfile::TempPath> = Vec::new();
+    let mut received = 0;
+    let mut metadata: Option<UploadMetadata> = None;
 
-    Ok(output_path)
+    // Parse multipart form
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| multipart_error(e, state.max_upload_bytes))?
+    {
+        let name = field.name().map(|s| s.to_string()).unwrap_or_default();
+        tracing::debug!(field_name = %name, "Received field");
+
+        match name.as_str() {
+            "image" => {
+                let frame =
+                    receive_frame(field, &images_dir, &mut received, state.max_upload_bytes)
+                        .await?;
+                tracing::debug!(received, frame = frames.len(), "Received image");
+                frames.push(frame);
+            }
+            "metadata" => {
+                let content_type = field.content_type().map(str::to_owned);
+                let bytes = field
+                    .bytes()
+                    .await
+                    .map_err(|e| multipart_error(e, state.max_upload_bytes))?;
+                let parsed = parse_upload_metadata(content_type.as_deref(), &bytes)
+                    .inspect_err(|_| crate::metrics::record_upload("rejected_metadata"))?;
+                tracing::debug!(?parsed, "Received metadata");
+                metadata = Some(parsed);
+            }
+            _ => {
+                tracing::debug!(field_name = %name, "Ignoring unknown field");
+            }
+        }
+    }
+
+    if frames.is_empty() {
+        return Err(ApiError::bad_request(
+            "missing_image",
+            "Missing image field",
+        ));
+    }
+
+    let Some(metadata) = metadata else {
+        return Err(ApiError::bad_request(
+            "missing_metadata",
+            "Missing metadata field",
+        ));
+    };
+
+    let config = state.config.get();
+    check_overrides(
+        &config.server.allow_overrides,
+        &metadata.processing_overrides,
+    )
+    .inspect_err(|_| crate::metrics::record_upload("rejected_override"))?;
+
+    let existing = if metadata.force {
+        None
+    } else {
+        let revision = metadata.revision.clone();
+        gallery_lookup(&state, move |state| state.revision_cache.get(&revision)).await?
+    };
+    if let Some(existing) = existing {
+        tracing::info!(revision = %metadata.revision, filename = %existing.filename, "Revision already exists, rejecting upload");
+        crate::metrics::record_upload("duplicate_rejected");
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            DUPLICATE_REVISION_ERROR_CODE,
+            format!(
+                "revision {} is already in the gallery, upload with force to replace it",
+                metadata.revision
+            ),
+        )
+        .with_detail("revision", &metadata.revision)
+        .with_detail("filename", &existing.filename));
+    }
+
+    tracing::info!(
+        revision = %metadata.revision,
+        repo = %metadata.repo_name,
+        waiting = state.processing.waiting(),
+        active = state.processing.active(),
+        "Received upload, spawning async processor"
+    );
+    crate::metrics::record_upload("accepted");
+
+    // Spawn async processing task
+    let job_id = state.jobs.create(&metadata.revision);
+    let id = job_id.clone();
+    let tasks = state.tasks.clone();
+    tasks.spawn(async move {
+        // Released once the job is over, including a timed out one whose blocking work
+        // is still winding down
+        ticket.start().await;
+        run_job(&id, frames, metadata, state, config).await;
+    });
+
+    // Return 202 Accepted immediately
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(UploadResponse {
+            status: "accepted".to_string(),
+            message: "Processing in background".to_string(),
+            job_id,
+        }),
+    )
+        .into_response())
+}
+
+/// Decode a test upload's image and describe it, keeping nothing, so
+/// `lolcommits_upload doctor` can check an upload gets through without adding to the
+/// gallery. Answers even while read-only, saying so.
+async fn ping_handler(
+    State(state): State<AppState>,
+    mut multipart: Multipart,
+) -> std::result::Result<Json<PingResponse>, ApiError> {
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| multipart_error(e, PING_MAX_BYTES))?
+    {
+        if field.name() != Some("image") {
+            continue;
+        }
+
+        let bytes = field
+            .bytes()
+            .await
+            .map_err(|e| multipart_error(e, PING_MAX_BYTES))?;
+        let image = image::load_from_memory(&bytes).map_err(|e| {
+            ApiError::bad_request("invalid_image", format!("Undecodable image: {e}"))
+        })?;
+        return Ok(Json(PingResponse {
+            status: "ok",
+            bytes: bytes.len(),
+            width: image.width(),
+            height: image.height(),
+            read_only: state.read_only.is_enabled(),
+        }));
+    }
+    Err(ApiError::bad_request(
+        "missing_image",
+        "Missing image field",
+    ))
+}
+
+/// Stream an image part into a temporary file in `images_dir`, so an upload is never
+/// held in memory whole and is renamed within one filesystem once processed. Adds the
+/// part's size to `received`, refusing the upload once that passes `limit`.
+async fn receive_frame(
+    mut field: Field<'_>,
+    images_dir: &std::path::Path,
+    received: &mut usize,
+    limit: usize,
+) -> std::result::Result<tempfile::TempPath, ApiError> {
+    use tokio::io::AsyncWriteExt;
+
+    let store_failed = |e: std::io::Error| {
+        tracing::error!(error = %e, "Failed to store uploaded image");
+        ApiError::internal("Failed to store upload")
+    };
+
+    tokio::fs::create_dir_all(images_dir)
+        .await
+        .map_err(store_failed)?;
+    let (file, path) = tempfile::Builder::new()
+        .prefix(UPLOAD_TEMP_PREFIX)
+        .tempfile_in(images_dir)
+        .map_err(store_failed)?
+        .into_parts();
+    let mut file = tokio::fs::File::from_std(file);
+
+    loop {
+        match field.chunk().await {
+            Ok(Some(chunk)) => {
+                *received += chunk.len();
+                if *received > limit {
+                    return Err(upload_too_large(limit));
+                }
+                file.write_all(&chunk).await.map_err(store_failed)?;
+            }
+            Ok(None) => break,
+            Err(e) => return Err(multipart_error(e, limit)),
+        }
+    }
+    file.flush().await.map_err(store_failed)?;
+    Ok(path)
+}
+
+fn busy_error() -> ApiError {
+    ApiError::new(
+        StatusCode::SERVICE_UNAVAILABLE,
+        BUSY_ERROR_CODE,
+        "too many uploads waiting to be processed, try again later",
+    )
+    .with_retry_after(BUSY_RETRY_AFTER_SECS)
+}
+
+fn upload_too_large(limit: usize) -> ApiError {
+    tracing::info!(limit, "Rejecting upload, too large");
+    crate::metrics::record_upload("rejected_too_large");
+    ApiError::new(
+        StatusCode::PAYLOAD_TOO_LARGE,
+        UPLOAD_TOO_LARGE_ERROR_CODE,
+        format!("uploads are limited to {limit} bytes of image data"),
+    )
+    .with_detail("max_upload_bytes", limit)
+}
+
+/// A malformed or truncated form is the client's doing; the request body passing the
+/// route's limit counts as too large.
+fn multipart_error(e: MultipartError, limit: usize) -> ApiError {
+    if e.status() == StatusCode::PAYLOAD_TOO_LARGE {
+        return upload_too_large(limit);
+    }
+    tracing::warn!(error = %e, "Failed to read upload");
+    ApiError::new(e.status(), "invalid_multipart", e.body_text())
+}
+
+async fn job_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> std::result::Result<Response, ApiError> {
+    match state.jobs.get(&id) {
+        Some(job) => Ok(Json(job).into_response()),
+        None => Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "unknown_job",
+            format!("no job {id}, it may have expired"),
+        )),
+    }
+}
+
+/// Process upload job `id`, recording how it went. An upload that fails or takes longer
+/// than `processing_timeout_secs` is kept in [`failed_uploads::FAILED_DIR`].
+async fn run_job(
+    id: &str,
+    frames: Vec<tempfile::TempPath>,
+    metadata: UploadMetadata,
+    state: AppState,
+    config: Arc<LoadedConfig>,
+) {
+    let jobs = state.jobs.clone();
+    jobs.start(id);
+
+    let frames = Arc::new(frames);
+    let received = metadata.clone();
+    let images_dir = PathBuf::from(&config.server.images_dir);
+    let secs = config.server.processing_timeout_secs;
+    let deadline = Arc::new(Deadline::default());
+    let processing = process_image_async(frames.clone(), metadata, state, config, deadline.clone());
+    tokio::pin!(processing);
+
+    match with_deadline(secs, &deadline, processing.as_mut()).await {
+        Ok(Some(filename)) => jobs.finish(id, filename),
+        Ok(None) => jobs.fail(id, "revision is already in the gallery".to_string()),
+        Err(e) => {
+            tracing::error!(job_id = %id, error = %e, "Failed to process image");
+            crate::metrics::record_upload(match e {
+                Error::ProcessingDeadlineExceeded { .. } => "timed_out",
+                _ => "failed",
+            });
+            match failed_uploads::keep(&images_dir, id, &frames, &received, &e.to_string()) {
+                Ok(dir) => {
+                    tracing::warn!(job_id = %id, dir = %dir.display(), "Kept failed upload")
+                }
+                Err(e) => tracing::error!(job_id = %id, error = %e, "Failed to keep failed upload"),
+            }
+            jobs.fail(id, e.to_string());
+        }
+    }
+
+    // Blocking work can't be interrupted, so a timed out job keeps its processing slot
+    // until that work gives up, which it does before saving anything
+    if deadline.expired() {
+        let _ = processing.await;
+        tracing::debug!(job_id = %id, "Timed out processing has stopped");
+    }
+}
+
+/// Whether an upload's processing may still save its image or has run out of time.
+/// Whichever of saving and timing out comes first wins, so a timed out upload never
+/// turns up in the gallery and one being saved is never reported as failed.
+#[derive(Debug, Default)]
+struct Deadline(std::sync::Mutex<DeadlineState>);
+
+#[derive(Debug, Default, PartialEq)]
+enum DeadlineState {
+    #[default]
+    Running,
+    Saving,
+    Expired,
+}
+
+impl Deadline {
+    /// Claim the right to save, `false` once the deadline has passed.
+    fn begin_save(&self) -> bool {
+        self.transition(DeadlineState::Saving)
+    }
+
+    /// Time out, `false` when saving has already begun.
+    fn expire(&self) -> bool {
+        self.transition(DeadlineState::Expired)
+    }
+
+    fn expired(&self) -> bool {
+        *self.0.lock().unwrap() == DeadlineState::Expired
+    }
+
+    fn transition(&self, to: DeadlineState) -> bool {
+        let mut state = self.0.lock().unwrap();
+        if *state != DeadlineState::Running {
+            return false;
+        }
+        *state = to;
+        true
+    }
+}
+
+/// Run `work` for at most `secs`. Past that the deadline expires, unless `work` has
+/// begun saving, in which case it's left to finish.
+async fn with_deadline<T>(
+    secs: u64,
+    deadline: &Deadline,
+    work: impl std::future::Future<Output = Result<T>>,
+) -> Result<T> {
+    tokio::pin!(work);
+    match tokio::time::timeout(std::time::Duration::from_secs(secs), &mut work).await {
+        Ok(outcome) => outcome,
+        Err(_) if deadline.expire() => Err(Error::ProcessingDeadlineExceeded { secs }),
+        Err(_) => work.await,
+    }
+}
+
+/// Process and save an upload, returning the saved filename, or `None` when the
+/// revision turned out to be a duplicate. More than one frame makes an animated GIF.
+/// Nothing is saved once `deadline` has expired.
+async fn process_image_async(
+    frames: Arc<Vec<tempfile::TempPath>>,
+    metadata: UploadMetadata,
+    AppState {
+        tx,
+        revision_cache,
+        image_index,
+        image_cache,
+        disk_space,
+        segmentation_model,
+        ..
+    }: AppState,
+    loaded: Arc<LoadedConfig>,
+    deadline: Arc<Deadline>,
+) -> Result<Option<String>> {
+    tracing::info!(revision = %metadata.revision, force = metadata.force, "Starting async image processing");
+
+    // Backstop for a duplicate uploaded while this one was queued
+    let duplicate = !metadata.force && {
+        let revision = metadata.revision.clone();
+        tokio::task::spawn_blocking(move || revision_cache.contains(&revision))
+            .await
+            .map_err(std::io::Error::other)?
+    };
+    if duplicate {
+        tracing::info!(revision = %metadata.revision, "Revision already exists, skipping upload");
+        crate::metrics::record_upload("duplicate_skipped");
+        return Ok(None);
+    }
+
+    let server_config = &loaded.server;
+    let animated = frames.len() > 1;
+
+    // Date the image by its commit, not by when the upload arrived
+    let taken = image_metadata::taken_at(&metadata.timestamp);
+
+    // Create commit metadata
+    let commit_metadata = git::CommitMetadata {
+        path: PathBuf::new(),
+        revision: metadata.revision.clone(),
+        message: metadata.message,
+        commit_type: metadata.commit_type,
+        scope: metadata.scope,
+        timestamp: crate::format_timestamp(taken),
+        repo_name: metadata.repo_name.clone(),
+        branch_name: metadata.branch_name,
+        author_name: metadata.author_name,
+        author_email: metadata.author_email,
+        breaking: metadata.breaking,
+        co_authors: metadata.co_authors,
+        stats: git::DiffStats {
+            files_changed: metadata.files_changed,
+            insertions: metadata.insertions,
+            deletions: metadata.deletions,
+        },
+    };
+
+    let extension = if animated {
+        crate::animation::EXTENSION
+    } else {
+        server_config.output_format.extension()
+    };
+    let filename =
+        image_metadata::output_filename(&metadata.repo_name, &metadata.revision, taken, extension)?;
+
+    // Decoding, inference and encoding are CPU-bound, keep them off the async workers
+    let output_path = {
+        let loaded = loaded.clone();
+        let commit_metadata = commit_metadata.clone();
+        let filename = filename.clone();
+        let processing = image_metadata::ProcessingInfo {
+            overrides: metadata.processing_overrides,
+            burned_in_chyron: metadata.burned_in_chyron,
+            ..Default::default()
+        };
+        let frame_delay_ms = metadata.frame_delay_ms;
+        tokio::task::spawn_blocking(move || -> Result<PathBuf> {
+            let config = &loaded.config;
+            let server_config = &loaded.server;
+
+            // Decode image
+            let _timer = crate::metrics::ScopedTimer::image_processing();
+            let images = frames
+                .iter()
+                .map(|path| {
+                    let image = crate::orientation::load_from_file(path)?;
+                    Ok(if animated {
+                        crate::animation::fit_width(image, server_config.animation_max_width)
+                    } else {
+                        image
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            tracing::debug!(frames = images.len(), "Decoded image");
+
+            let (mut processed, processing) = run_pipeline(
+                config,
+                &segmentation_model,
+                images,
+                &commit_metadata,
+                processing,
+            )?;
+
+            // A timed out upload has been kept as failed, it mustn't turn up after all
+            if !deadline.begin_save() {
+                return Err(Error::ProcessingDeadlineExceeded {
+                    secs: server_config.processing_timeout_secs,
+                });
+            }
+
+            // Space may have run out while this upload was queued and processed
+            disk_space.check()?;
+
+            let images_dir = std::path::Path::new(&server_config.images_dir);
+            let output_path = if animated {
+                image_metadata::save_gallery_animation(
+                    images_dir,
+                    &filename,
+                    &processed,
+                    frame_delay_ms.unwrap_or(crate::animation::DEFAULT_FRAME_DELAY_MS),
+                    &commit_metadata,
+                    &processing,
+                )?
+            } else {
+                image_metadata::save_gallery_image(
+                    images_dir,
+                    &filename,
+                    &processed.remove(0),
+                    &commit_metadata,
+                    &processing,
+                    server_config.jpeg_quality,
+                )?
+            };
+            tracing::info!(path = %output_path.display(), "Saved lolcommit with metadata");
+            disk_space.record_saved(&output_path);
+
+            // Reprocessing works from a single original, so animations can't be kept
+            if server_config.keep_originals && !animated {
+                let originals = std::path::Path::new(&server_config.state_dir).join(ORIGINALS_DIR);
+                let kept = crate::storage::atomic_save(&originals, &filename, |path| {
+                    std::fs::copy(&frames[0], path)?;
+                    Ok(())
+                });
+                match kept {
+                    Ok(path) => tracing::debug!(path = %path.display(), "Kept original upload"),
+                    Err(e) => tracing::warn!(error = %e, "Failed to keep original upload"),
+                }
+            }
+
+            // Before the broadcast below, so clients refreshing on it see the new image,
+            // and before the next upload of this revision checks the revision cache
+            image_index.insert(&output_path);
+            Ok(output_path)
+        })
+        .await
+        .map_err(std::io::Error::other)??
+    };
+
+    // A forced re-upload can land on a filename that is already cached
+    if let Some(cache) = &image_cache
+        && let Some(filename) = output_path.file_name().and_then(|s| s.to_str())
+    {
+        cache.invalidate(filename);
+    }
+    crate::metrics::record_upload("processed");
+
+    // Broadcast new image event to SSE clients
+    let saved = git::CommitMetadata {
+        path: output_path,
+        ..commit_metadata
+    };
+    let event = GalleryEvent::new_image(&ImageMetadata::new(server_config, saved))?;
+    let _ = tx.send(event);
+    tracing::debug!("Broadcasted new_image event to SSE clients");
+
+    Ok(Some(filename))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+    use test_case::test_case;
+
+    fn test_state(state_dir: &std::path::Path, admin_token: Option<&str>) -> AppState {
+        let (tx, _rx) = broadcast::channel(16);
+        let image_index = Arc::new(ImageIndex::open(state_dir.join("images")));
+        AppState {
+            tx,
+            revision_cache: Arc::new(RevisionCache::new(image_index.clone())),
+            image_index,
+            jobs: Arc::new(Jobs::new(std::time::Duration::from_secs(60))),
+            read_only: Arc::new(ReadOnlyMode::load(state_dir, false)),
+            admin_token: admin_token.map(Arc::from),
+            image_cache: None,
+            config: SharedConfig::new(
+                config::Config {
+                    server: Some(config::ServerConfig {
+                        images_dir: state_dir.join("images").display().to_string(),
+                        state_dir: state_dir.display().to_string(),
+                        background_path: "none".to_string(),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+                Some(state_dir.join("config.toml")),
+            ),
+            disk_space: Arc::new(DiskSpace::new(&config::ServerConfig {
+                images_dir: state_dir.join("images").display().to_string(),
+                min_free_space_mb: 0,
+                ..Default::default()
+            })),
+            segmentation_model: Arc::new(SegmentationModel::open(
+                state_dir.join("models"),
+                &Default::default(),
+                Default::default(),
+                Default::default(),
+            )),
+            tasks: TaskTracker::new(),
+            feed: Arc::new(std::sync::Mutex::new(None)),
+            max_upload_bytes: config::ServerConfig::default().max_upload_bytes,
+            processing: Arc::new(ProcessingQueue::new(1, 16)),
+            sse_clients: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    fn bearer(token: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+        );
+        headers
+    }
+
+    fn enable() -> Json<ReadOnlyRequest> {
+        Json(ReadOnlyRequest { enabled: true })
+    }
+
+    #[tokio::test]
+    async fn test_read_only_toggle_requires_token() -> Result {
+        let dir = tempfile::tempdir()?;
+        let state = test_state(dir.path(), Some("secret"));
+
+        let response = set_read_only(State(state.clone()), HeaderMap::new(), enable())
+            .await
+            .into_response();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = set_read_only(State(state.clone()), bearer("wrong"), enable())
+            .await
+            .into_response();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        assert!(!state.read_only.is_enabled());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_only_toggle_disabled_without_admin_token() -> Result {
+        let dir = tempfile::tempdir()?;
+        let state = test_state(dir.path(), None);
+
+        let response = set_read_only(State(state.clone()), bearer("anything"), enable())
+            .await
+            .into_response();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert!(!state.read_only.is_enabled());
+        Ok(())
+    }
+
+    #[test]
+    fn test_reload_swaps_config() -> Result {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("config.toml");
+        let shared = SharedConfig::new(config::Config::default(), Some(path.clone()));
+
+        std::fs::write(
+            &path,
+            "[server]\ngallery_title = \"Reloaded\"\nbackground_path = \"none\"\n",
+        )?;
+        let loaded = shared.reload()?;
+
+        assert_eq!(loaded.server.gallery_title, "Reloaded");
+        assert_eq!(shared.get().server.gallery_title, "Reloaded");
+        assert_eq!(shared.get().background, Background::Disabled);
+        Ok(())
+    }
+
+    #[test]
+    fn test_failed_reload_keeps_current_config() -> Result {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("config.toml");
+        let shared = SharedConfig::new(config::Config::default(), Some(path.clone()));
+        let title = shared.get().server.gallery_title.clone();
+
+        // Missing file is an error rather than a fresh default
+        assert!(shared.reload().is_err());
+        assert!(!path.exists());
+
+        std::fs::write(&path, "[server\ngallery_title = ")?;
+        assert!(shared.reload().is_err());
+
+        assert_eq!(shared.get().server.gallery_title, title);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reload_config_endpoint() -> Result {
+        let dir = tempfile::tempdir()?;
+        let state = test_state(dir.path(), Some("secret"));
+
+        let response = reload_config(State(state.clone()), HeaderMap::new())
+            .await
+            .into_response();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        // test_state points at a config.toml that doesn't exist yet
+        let response = reload_config(State(state.clone()), bearer("secret"))
+            .await
+            .into_response();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        std::fs::write(
+            dir.path().join("config.toml"),
+            "[server]\ngallery_title = \"Reloaded\"\nbackground_path = \"none\"\n",
+        )?;
+        let response = reload_config(State(state.clone()), bearer("secret"))
+            .await
+            .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let Json(config) = get_config(State(state)).await;
+        assert_eq!(config.gallery_title, "Reloaded");
+        Ok(())
+    }
+
+    fn plan_query(repo: &str) -> Query<PlanQuery> {
+        Query(PlanQuery {
+            repo: repo.to_string(),
+            commit_type: "fix".to_string(),
+            branch: "main".to_string(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_plan_requires_token() -> Result {
+        let dir = tempfile::tempdir()?;
+
+        let state = test_state(dir.path(), Some("secret"));
+        let response =
+            pipeline_plan_handler(State(state.clone()), HeaderMap::new(), plan_query("repo"))
+                .await
+                .into_response();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let state = test_state(dir.path(), None);
+        let response = pipeline_plan_handler(State(state), bearer("anything"), plan_query("repo"))
+            .await
+            .into_response();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_plan_response() -> Result {
+        let config = config::Config {
+            server: Some(config::ServerConfig {
+                background_path: "none".to_string(),
+                burned_in_chyron: false,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let response = plan_response(&config, plan_query("repo").0).into_response();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body)?;
+        assert_eq!(body["stages"], serde_json::json!(["background"]));
+        assert_eq!(body["background"]["status"], "disabled");
+        assert!(body["chyron"].is_null());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_plan_reports_invalid_config() -> Result {
+        let config = config::Config {
+            server: Some(config::ServerConfig {
+                post_processors: vec!["watermark".to_string()],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let response = plan_response(&config, plan_query("repo").0).into_response();
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_upload_overrides_checked_against_allow_list() -> Result {
+        let allowed = vec!["background".to_string()];
+        let requested =
+            |key: &str, value: &str| Overrides::from([(key.to_string(), value.to_string())]);
+
+        assert!(check_overrides(&allowed, &Overrides::new()).is_ok());
+        assert!(check_overrides(&allowed, &requested("background", "party")).is_ok());
+
+        let response = check_overrides(&allowed, &requested("center_person", "false"))
+            .expect_err("non-whitelisted override is rejected")
+            .into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body)?;
+        assert_eq!(body["error"]["code"], "invalid_override");
+        assert_eq!(
+            body["error"]["message"],
+            "override \"center_person\" is not allowed, allowed: background"
+        );
+        Ok(())
+    }
+
+    /// Save a gallery image with metadata parsed from `filename` into `images_dir`.
+    fn save_image(images_dir: &std::path::Path, filename: &str) -> Result<PathBuf> {
+        std::fs::create_dir_all(images_dir)?;
+        let path = images_dir.join(filename);
+        let metadata = image_metadata::parse_filename(&path).unwrap();
+        image_metadata::save_png_with_metadata(
+            &image::DynamicImage::new_rgb8(32, 24),
+            &path,
+            &metadata,
+        )?;
+        Ok(path)
+    }
+
+    async fn delete(state: &AppState, headers: HeaderMap, filename: &str) -> Response {
+        delete_image(State(state.clone()), headers, Path(filename.to_owned()))
+            .await
+            .into_response()
+    }
+
+    #[tokio::test]
+    async fn test_delete_image() -> Result {
+        let dir = tempfile::tempdir()?;
+        let images_dir = dir.path().join("images");
+        let older = save_image(&images_dir, "repo-20240101-120000-abc1234.png")?;
+        let newer = save_image(&images_dir, "repo-20240201-120000-abc1234.png")?;
+        let state = test_state(dir.path(), Some("secret"));
+        std::fs::create_dir_all(images_dir.join(CHYRON_CACHE_DIR))?;
+        std::fs::write(
+            images_dir
+                .join(CHYRON_CACHE_DIR)
+                .join("repo-20240201-120000-abc1234.png"),
+            b"overlay",
+        )?;
+        let mut events = state.tx.subscribe();
+
+        let response = delete(&state, bearer("secret"), "repo-20240201-120000-abc1234.png").await;
+
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(!newer.exists());
+        assert!(
+            !images_dir
+                .join(CHYRON_CACHE_DIR)
+                .join("repo-20240201-120000-abc1234.png")
+                .exists()
+        );
+        assert_eq!(state.image_index.len(), 1);
+        // The earlier upload of the revision is what /api/exists now finds
+        assert_eq!(
+            exists(&state, None, "abc1234").await.filename.as_deref(),
+            older.file_name().and_then(|s| s.to_str())
+        );
+
+        let event = events.try_recv().unwrap();
+        assert_eq!(event.name, "image_deleted");
+        assert_eq!(
+            event.data,
+            r#"{"filename":"repo-20240201-120000-abc1234.png"}"#
+        );
+
+        let response = delete(&state, bearer("secret"), "repo-20240101-120000-abc1234.png").await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(!exists(&state, None, "abc1234").await.exists);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_delete_image_rejections() -> Result {
+        let dir = tempfile::tempdir()?;
+        let images_dir = dir.path().join("images");
+        let path = save_image(&images_dir, "repo-20240101-120000-abc1234.png")?;
+        std::fs::write(dir.path().join("secret.png"), b"outside images_dir")?;
+        std::fs::write(images_dir.join("notes.txt"), b"not an image")?;
+        let state = test_state(dir.path(), Some("secret"));
+
+        let response = delete(&state, HeaderMap::new(), "repo-20240101-120000-abc1234.png").await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        for filename in ["../secret.png", ".hidden.png", "notes.txt", "missing.png"] {
+            let response = delete(&state, bearer("secret"), filename).await;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND, "{filename}");
+        }
+        assert!(dir.path().join("secret.png").exists());
+
+        state.read_only.set(true)?;
+        let response = delete(&state, bearer("secret"), "repo-20240101-120000-abc1234.png").await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert!(path.exists());
+        Ok(())
+    }
+
+    async fn detail(state: &AppState, headers: HeaderMap, filename: &str) -> Response {
+        image_detail_handler(State(state.clone()), headers, Path(filename.to_owned()))
+            .await
+            .into_response()
+    }
+
+    #[tokio::test]
+    async fn test_image_detail() -> Result {
+        let dir = tempfile::tempdir()?;
+        let filename = "repo-20240101-120000-abc1234.png";
+        let path = save_image(&dir.path().join("images"), filename)?;
+        let state = test_state(dir.path(), None);
+
+        let response = detail(&state, HeaderMap::new(), filename).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let etag = response.headers()[header::ETAG].clone();
+        let body = json_body(response).await;
+        assert_eq!(body["filename"], filename);
+        assert_eq!(body["revision"], "abc1234");
+        assert_eq!(body["thumb_url"], format!("/images/{filename}"));
+        assert_eq!(body["size_bytes"], std::fs::metadata(&path)?.len());
+        assert_eq!(
+            (body["width"].clone(), body["height"].clone()),
+            (32.into(), 24.into())
+        );
+        assert_eq!(body["metadata_source"], "embedded");
+        assert_eq!(body["text_chunks"]["lolcommit:Revision"], "abc1234");
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, etag.clone());
+        let response = detail(&state, headers, filename).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[header::ETAG], etag);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_image_detail_without_metadata_is_partial() -> Result {
+        let dir = tempfile::tempdir()?;
+        let images_dir = dir.path().join("images");
+        std::fs::create_dir_all(&images_dir)?;
+        std::fs::write(
+            images_dir.join("repo-20240101-120000-abc1234.png"),
+            b"not really a png",
+        )?;
+        std::fs::write(images_dir.join("holiday.png"), b"not really a png")?;
+        std::fs::write(dir.path().join("secret.png"), b"outside images_dir")?;
+        let state = test_state(dir.path(), None);
+
+        let response = detail(&state, HeaderMap::new(), "repo-20240101-120000-abc1234.png").await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = json_body(response).await;
+        assert_eq!(body["revision"], "abc1234");
+        assert_eq!(body["metadata_source"], "filename");
+        assert!(body["width"].is_null());
+
+        let response = detail(&state, HeaderMap::new(), "holiday.png").await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(json_body(response).await["error"]["code"], "no_metadata");
+
+        for filename in ["../secret.png", ".hidden.png", "notes.txt", "missing.png"] {
+            let response = detail(&state, HeaderMap::new(), filename).await;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND, "{filename}");
+        }
+        Ok(())
+    }
+
+    async fn reprocess(state: &AppState, headers: HeaderMap, filename: &str) -> Response {
+        reprocess_image(State(state.clone()), headers, Path(filename.to_owned()))
+            .await
+            .into_response()
+    }
+
+    #[tokio::test]
+    async fn test_reprocess_image_replaces_it_from_original() -> Result {
+        let dir = tempfile::tempdir()?;
+        let filename = "repo-20240101-120000-abc1234.png";
+        let path = save_image(&dir.path().join("images"), filename)?;
+        let originals = dir.path().join(ORIGINALS_DIR);
+        std::fs::create_dir_all(&originals)?;
+        image::DynamicImage::new_rgb8(320, 240).save(originals.join(filename))?;
+        let state = test_state(dir.path(), Some("secret"));
+        let mut events = state.tx.subscribe();
+
+        let response = reprocess(&state, bearer("secret"), filename).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = json_body(response).await;
+        assert_eq!(body["filename"], filename);
+        assert_eq!(body["revision"], "abc1234");
+        assert_eq!(image::image_dimensions(&path)?, (320, 240));
+        let metadata = image_metadata::parse_image_file(&path).unwrap();
+        assert_eq!(metadata.revision, "abc1234");
+
+        let event = events.try_recv().unwrap();
+        assert_eq!(event.name, "image_updated");
+        assert!(event.data.contains(filename), "{}", event.data);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reprocess_image_rejections() -> Result {
+        let dir = tempfile::tempdir()?;
+        let filename = "repo-20240101-120000-abc1234.png";
+        save_image(&dir.path().join("images"), filename)?;
+        let state = test_state(dir.path(), Some("secret"));
+
+        let response = reprocess(&state, HeaderMap::new(), filename).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        for filename in ["../secret.png", ".hidden.png", "missing.png"] {
+            let response = reprocess(&state, bearer("secret"), filename).await;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND, "{filename}");
+        }
+
+        let response = reprocess(&state, bearer("secret"), filename).await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        assert_eq!(
+            json_body(response).await["error"]["code"],
+            ORIGINAL_MISSING_ERROR_CODE
+        );
+
+        state.read_only.set(true)?;
+        let response = reprocess(&state, bearer("secret"), filename).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        Ok(())
+    }
+
+    #[test]
+    fn test_chyron_overlay_is_cached_beside_images() -> Result {
+        let dir = tempfile::tempdir()?;
+        let filename = "repo-20240115-123456-abc1234.png";
+        let metadata = image_metadata::parse_filename(&dir.path().join(filename)).unwrap();
+        image_metadata::save_png_with_metadata(
+            &image::DynamicImage::new_rgb8(320, 240),
+            dir.path().join(filename),
+            &metadata,
+        )?;
+        let config = config::Config {
+            server: Some(config::ServerConfig {
+                images_dir: dir.path().display().to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let cached_path = dir.path().join(CHYRON_CACHE_DIR).join(filename);
+
+        let uncached = render_chyron_overlay(&config, filename, false)?.unwrap();
+        assert!(!cached_path.exists());
+
+        let rendered = render_chyron_overlay(&config, filename, true)?.unwrap();
+        assert_eq!(rendered, uncached);
+        assert_eq!(std::fs::read(&cached_path)?, rendered);
+        assert_eq!(image::load_from_memory(&rendered)?.width(), 320);
+
+        // Served from the cache while it is newer than the image
+        std::fs::write(&cached_path, b"cached")?;
+        assert_eq!(
+            render_chyron_overlay(&config, filename, true)?.unwrap(),
+            b"cached"
+        );
+
+        assert!(render_chyron_overlay(&config, "missing.png", true)?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_chyron_overlay_of_jpeg_is_cached_as_png() -> Result {
+        let dir = tempfile::tempdir()?;
+        let images_dir = dir.path().join("images");
+        std::fs::create_dir_all(&images_dir)?;
+        let filename = "repo-20240115-123456-abc1234.jpg";
+        image::DynamicImage::new_rgb8(320, 240).save(images_dir.join(filename))?;
+        let state = test_state(dir.path(), Some("secret"));
+        let cache_dir = images_dir.join(CHYRON_CACHE_DIR);
+
+        let rendered = render_chyron_overlay(&state.config.get().config, filename, true)?.unwrap();
+
+        assert_eq!(
+            std::fs::read(cache_dir.join("repo-20240115-123456-abc1234.png"))?,
+            rendered
+        );
+        assert!(!cache_dir.join(filename).exists());
+
+        let response = delete(&state, bearer("secret"), filename).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(!cache_dir.join("repo-20240115-123456-abc1234.png").exists());
+        Ok(())
+    }
+
+    async fn exists(state: &AppState, repo: Option<&str>, revision: &str) -> ExistsResponse {
+        let Json(response) = exists_handler(
+            State(state.clone()),
+            Query(ExistsQuery {
+                repo: repo.map(String::from),
+                revision: revision.to_string(),
+            }),
+        )
+        .await
+        .unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_exists_reports_cached_revisions() -> Result {
+        let dir = tempfile::tempdir()?;
+        save_image(
+            &dir.path().join("images"),
+            "repo-20240115-123456-abc1234.png",
+        )?;
+        let state = test_state(dir.path(), None);
+
+        let hit = exists(&state, Some("repo"), "abc1234").await;
+        assert!(hit.exists);
+        assert_eq!(
+            hit.filename.as_deref(),
+            Some("repo-20240115-123456-abc1234.png")
+        );
+        assert!(exists(&state, None, "abc1234").await.exists);
+
+        let miss = exists(&state, Some("repo"), "def5678").await;
+        assert!(!miss.exists);
+        assert_eq!(serde_json::to_string(&miss)?, r#"{"exists":false}"#);
+
+        assert!(!exists(&state, Some("other"), "abc1234").await.exists);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_health_reports_background() -> Result {
+        let dir = tempfile::tempdir()?;
+        let mut state = test_state(dir.path(), None);
+
+        let Json(health) = health_handler(State(state.clone())).await;
+        assert_eq!((health.status, health.background), ("ok", "disabled"));
+
+        state.config = SharedConfig::with_loaded(
+            LoadedConfig {
+                config: config::Config::default(),
+                server: config::ServerConfig::default(),
+                background: Background::Missing("not found".to_string()),
+            },
+            None,
+        );
+        let Json(health) = health_handler(State(state)).await;
+        assert_eq!((health.status, health.background), ("degraded", "missing"));
+        Ok(())
+    }
+
+    struct NoSpace;
+
+    impl crate::disk_space::SpaceProbe for NoSpace {
+        fn available_bytes(&self, _path: &std::path::Path) -> std::io::Result<u64> {
+            Ok(0)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_low_disk_space_rejects_uploads_and_degrades_health() -> Result {
+        let dir = tempfile::tempdir()?;
+        let mut state = test_state(dir.path(), None);
+        assert!(check_disk_space(&state.disk_space).is_ok());
+
+        state.disk_space = Arc::new(DiskSpace::with_probe(
+            &config::ServerConfig {
+                images_dir: dir.path().display().to_string(),
+                ..Default::default()
+            },
+            Box::new(NoSpace),
+        ));
+
+        let response = check_disk_space(&state.disk_space)
+            .unwrap_err()
+            .into_response();
+        assert_eq!(response.status(), StatusCode::INSUFFICIENT_STORAGE);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body)?;
+        assert_eq!(body["error"]["code"], LOW_DISK_SPACE_ERROR_CODE);
+        assert_eq!(body["error"]["details"]["available_mb"], 0);
+
+        let Json(health) = health_handler(State(state)).await;
+        assert_eq!((health.status, health.disk_space), ("degraded", "low"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_only_toggle_persists_and_reports_health() -> Result {
+        let dir = tempfile::tempdir()?;
+        let state = test_state(dir.path(), Some("secret"));
+
+        let response = set_read_only(State(state.clone()), bearer("secret"), enable())
+            .await
+            .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(state.read_only.is_enabled());
+
+        let Json(health) = health_handler(State(state.clone())).await;
+        assert!(health.read_only);
+
+        // A restarted server picks the persisted mode back up
+        assert!(ReadOnlyMode::load(dir.path(), false).is_enabled());
+
+        let response = set_read_only(
+            State(state.clone()),
+            bearer("secret"),
+            Json(ReadOnlyRequest { enabled: false }),
+        )
+        .await
+        .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!ReadOnlyMode::load(dir.path(), true).is_enabled());
+        Ok(())
+    }
+
+    fn cached_images(dir: &std::path::Path, capacity_bytes: u64) -> ImageFiles {
+        ImageFiles {
+            images_dir: Arc::from(dir),
+            cache: Some(Arc::new(ImageCache::new(capacity_bytes))),
+        }
+    }
+
+    fn cached_bytes(images: &ImageFiles) -> u64 {
+        images.cache.as_ref().map_or(0, |cache| cache.used_bytes())
+    }
+
+    fn image_request(filename: &str, range: Option<&str>) -> Request {
+        let mut builder = Request::builder().uri(format!("/images/{filename}"));
+        if let Some(range) = range {
+            builder = builder.header(header::RANGE, range);
+        }
+        builder.body(axum::body::Body::empty()).unwrap()
+    }
+
+    async fn serve_image(images: &ImageFiles, filename: &str, request: Request) -> Response {
+        image_handler(State(images.clone()), Path(filename.to_owned()), request).await
+    }
+
+    async fn body_len(response: Response) -> usize {
+        axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap()
+            .len()
+    }
+
+    #[tokio::test]
+    async fn test_image_handler_populates_cache_on_miss() -> Result {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("a.png"), vec![1u8; 64])?;
+        let images = cached_images(dir.path(), 1024);
+
+        for _ in 0..2 {
+            let response = serve_image(&images, "a.png", image_request("a.png", None)).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()[header::CONTENT_TYPE], "image/png");
+            assert_eq!(
+                response.headers()[header::CACHE_CONTROL],
+                IMAGE_CACHE_CONTROL
+            );
+            assert_eq!(body_len(response).await, 64);
+            assert_eq!(cached_bytes(&images), 64);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_image_handler_bypasses_cache_for_ranges() -> Result {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("a.png"), vec![1u8; 64])?;
+        let images = cached_images(dir.path(), 1024);
+
+        let response =
+            serve_image(&images, "a.png", image_request("a.png", Some("bytes=0-9"))).await;
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            response.headers()[header::CACHE_CONTROL],
+            IMAGE_CACHE_CONTROL
+        );
+        assert_eq!(body_len(response).await, 10);
+        assert_eq!(cached_bytes(&images), 0);
+        Ok(())
+    }
+
+    #[test_case("a.png", true ; "cached png")]
+    #[test_case("a.png", false ; "uncached png")]
+    #[test_case("a.gif", true ; "gif")]
+    #[tokio::test]
+    async fn test_image_handler_revalidates(filename: &str, cached: bool) -> Result {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join(filename), vec![1u8; 64])?;
+        let images = ImageFiles {
+            cache: cached.then(|| Arc::new(ImageCache::new(1024))),
+            ..cached_images(dir.path(), 0)
+        };
+
+        let response = serve_image(&images, filename, image_request(filename, None)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let etag = response.headers()[header::ETAG].clone();
+        let last_modified = response.headers()[header::LAST_MODIFIED].clone();
+
+        let mut request = image_request(filename, None);
+        request
+            .headers_mut()
+            .insert(header::IF_NONE_MATCH, etag.clone());
+        let response = serve_image(&images, filename, request).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[header::ETAG], etag);
+
+        let mut request = image_request(filename, None);
+        request
+            .headers_mut()
+            .insert(header::IF_MODIFIED_SINCE, last_modified);
+        let response = serve_image(&images, filename, request).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        Ok(())
+    }
+
+    #[test_case(true ; "cached")]
+    #[test_case(false ; "uncached")]
+    #[tokio::test]
+    async fn test_image_handler_rejects_non_images(cached: bool) -> Result {
+        let dir = tempfile::tempdir()?;
+        let images_dir = dir.path().join("images");
+        std::fs::create_dir_all(&images_dir)?;
+        for stray in [
+            ".tmpAbC123",
+            ".hidden.png",
+            "notes.txt",
+            "a.jpg.json",
+            "a.png.part",
+        ] {
+            std::fs::write(images_dir.join(stray), b"stray")?;
+        }
+        std::fs::create_dir_all(images_dir.join("dir.png"))?;
+        let images = ImageFiles {
+            cache: cached.then(|| Arc::new(ImageCache::new(1024))),
+            ..cached_images(&images_dir, 0)
+        };
+
+        for filename in [
+            "../secret.png",
+            ".tmpAbC123",
+            ".hidden.png",
+            "notes.txt",
+            "a.jpg.json",
+            "a.png.part",
+            "dir.png",
+            "missing.png",
+        ] {
+            let response = serve_image(&images, filename, image_request("x.png", None)).await;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND, "{filename}");
+            assert!(!response.headers().contains_key(header::CACHE_CONTROL));
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_image_route_rejects_traversal() -> Result {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("config.toml"), b"admin_token = \"secret\"")?;
+        std::fs::create_dir_all(dir.path().join("images/.chyron"))?;
+        std::fs::write(dir.path().join("images/.chyron/a.png"), b"overlay")?;
+        std::fs::write(dir.path().join("images/a.png"), b"image")?;
+        let router = test_router(dir.path());
+
+        for uri in [
+            "/images/../config.toml",
+            "/images/%2e%2e/config.toml",
+            "/images/..%2fconfig.toml",
+            "/images/%2e%2e%2fconfig.toml",
+            "/images/.chyron/a.png",
+            "/images/.chyron%2fa.png",
+            "/images/",
+        ] {
+            let request = Request::builder()
+                .uri(uri)
+                .body(axum::body::Body::empty())
+                .unwrap();
+            let response = router.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND, "{uri}");
+        }
+
+        let response = router.oneshot(image_request("a.png", None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        Ok(())
+    }
+
+    fn listed(repo: &str, branch: &str, commit_type: &str, timestamp: &str) -> git::CommitMetadata {
+        git::CommitMetadata {
+            path: PathBuf::from(format!("{repo}-{timestamp}.png")),
+            revision: timestamp.to_string(),
+            message: String::new(),
+            commit_type: commit_type.to_string(),
+            scope: String::new(),
+            timestamp: timestamp.to_string(),
+            repo_name: repo.to_string(),
+            branch_name: branch.to_string(),
+            author_name: String::new(),
+            author_email: String::new(),
+            breaking: false,
+            co_authors: Vec::new(),
+            stats: git::DiffStats {
+                files_changed: 0,
+                insertions: 0,
+                deletions: 0,
+            },
+        }
+    }
+
+    fn gallery() -> Vec<git::CommitMetadata> {
+        vec![
+            listed("app", "main", "feat", "5"),
+            listed("lib", "main", "fix", "4"),
+            listed("app", "dev", "fix", "3"),
+            listed("app", "main", "fix", "2"),
+            listed("lib", "dev", "feat", "1"),
+        ]
+    }
+
+    fn revisions(images: &[git::CommitMetadata]) -> Vec<&str> {
+        images.iter().map(|image| image.revision.as_str()).collect()
+    }
+
+    fn events_query(query: &str) -> EventsQuery {
+        let uri: axum::http::Uri = format!("/api/events?{query}").parse().unwrap();
+        Query::try_from_uri(&uri).unwrap().0
+    }
+
+    #[test_case("", true ; "no filter")]
+    #[test_case("repo=app", true ; "same repo")]
+    #[test_case("repo=lib", false ; "other repo")]
+    #[test_case("repo=app&type=feat", true ; "same repo and type")]
+    #[test_case("repo=app&type=fix", false ; "other type")]
+    #[test_case("format=legacy&repo=", true ; "empty repo")]
+    fn test_events_filter(query: &str, expected: bool) {
+        let query = events_query(query);
+        let image = ImageMetadata::new(
+            &config::ServerConfig::default(),
+            listed("app", "main", "feat", "5"),
+        );
+
+        assert_eq!(
+            query.wants(&GalleryEvent::new_image(&image).unwrap()),
+            expected
+        );
+        assert_eq!(
+            query.wants(&GalleryEvent::image_deleted(
+                "app-5.png",
+                Some(listed("app", "main", "feat", "5"))
+            )),
+            expected
+        );
+        // Events about no image in particular reach everyone
+        assert!(query.wants(&GalleryEvent::image_deleted("app-5.png", None)));
+        assert!(query.wants(&GalleryEvent::resync(1)));
+    }
+
+    /// The event stream `/api/events?{query}` would send.
+    async fn event_stream(state: &AppState, query: &str) -> axum::body::BodyDataStream {
+        sse_handler(State(state.clone()), Query(events_query(query)), None)
+            .await
+            .into_response()
+            .into_body()
+            .into_data_stream()
+    }
+
+    async fn next_frame(events: &mut axum::body::BodyDataStream) -> String {
+        use futures::StreamExt;
+
+        let frame = tokio::time::timeout(std::time::Duration::from_secs(30), events.next())
+            .await
+            .expect("no event within 30s")
+            .unwrap()
+            .unwrap();
+        String::from_utf8(frame.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_events_start_with_hello() -> Result {
+        let dir = tempfile::tempdir()?;
+        let images_dir = dir.path().join("images");
+        save_image(&images_dir, "repo-20240101-120000-abc1234.png")?;
+        let state = test_state(dir.path(), None);
+
+        let mut all = event_stream(&state, "").await;
+        assert_eq!(
+            next_frame(&mut all).await,
+            "event: hello\ndata: {\"image_count\":1}\n\n"
+        );
+        let mut other = event_stream(&state, "repo=other").await;
+        assert_eq!(
+            next_frame(&mut other).await,
+            "event: hello\ndata: {\"image_count\":0}\n\n"
+        );
+        assert_eq!(state.sse_clients.load(Ordering::Relaxed), 2);
+
+        drop(other);
+        assert_eq!(state.sse_clients.load(Ordering::Relaxed), 1);
+        drop(all);
+        assert_eq!(state.sse_clients.load(Ordering::Relaxed), 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_lagging_client_is_told_to_resync() -> Result {
+        let dir = tempfile::tempdir()?;
+        let state = test_state(dir.path(), None);
+        let mut events = event_stream(&state, "format=legacy").await;
+
+        // Legacy clients get no hello, and the test channel holds 16 events
+        for i in 0..20 {
+            state
+                .tx
+                .send(GalleryEvent::image_deleted(&format!("{i}.png"), None))
+                .unwrap();
+        }
+
+        assert_eq!(next_frame(&mut events).await, "data: resync\n\n");
+        assert_eq!(next_frame(&mut events).await, "data: image_deleted\n\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_page_filters_before_paging() {
+        let query = ImagesQuery {
+            filter: ImageFilter {
+                repo: Some("app".to_string()),
+                commit_type: Some("fix".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (page, total) = select_page(gallery(), &query, 0, None);
+        assert_eq!((revisions(&page), total), (vec!["3", "2"], 2));
+
+        let query = ImagesQuery {
+            filter: ImageFilter {
+                branch: Some("main".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (page, total) = select_page(gallery(), &query, 1, Some(1));
+        assert_eq!((revisions(&page), total), (vec!["4"], 3));
+    }
+
+    #[test_case(0, None, &["5", "4", "3", "2", "1"] ; "everything")]
+    #[test_case(0, Some(2), &["5", "4"] ; "first page")]
+    #[test_case(4, Some(2), &["1"] ; "last page")]
+    #[test_case(9, Some(2), &[] ; "past the end")]
+    fn test_select_page(offset: usize, limit: Option<usize>, expected: &[&str]) {
+        let (page, total) = select_page(gallery(), &ImagesQuery::default(), offset, limit);
+        assert_eq!(revisions(&page), expected);
+        assert_eq!(total, 5);
+    }
+
+    async fn images(query: &str) -> Response {
+        let Query(query) =
+            Query::try_from_uri(&format!("/api/images?{query}").parse().unwrap()).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        list_images(State(test_state(dir.path(), None)), Query(query))
+            .await
+            .into_response()
+    }
+
+    #[tokio::test]
+    async fn test_list_images_rejects_bad_paging() {
+        for query in ["limit=0", "limit=ten", "limit=-1", "offset=1.5"] {
+            let response = images(query).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{query}");
+            assert_eq!(
+                response.headers()[header::CONTENT_TYPE],
+                "application/json",
+                "{query}"
+            );
+        }
+        assert_eq!(
+            images("limit=10&offset=20&repo=app").await.status(),
+            StatusCode::OK
+        );
+    }
+
+    async fn feed(state: &AppState) -> String {
+        let response = feed_handler(State(state.clone())).await;
+        assert_eq!(response.headers()[header::CONTENT_TYPE], feed::CONTENT_TYPE);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_feed_is_rerendered_when_the_gallery_changes() -> Result {
+        let dir = tempfile::tempdir()?;
+        let mut state = test_state(dir.path(), None);
+        let images_dir = dir.path().join("images");
+        std::fs::create_dir_all(&images_dir)?;
+
+        let empty = feed(&state).await;
+        assert!(!empty.contains("<entry>"));
+        assert_eq!(feed(&state).await, empty, "served from the cache");
+
+        let path = images_dir.join("repo-20240301-123000-abc1234.png");
+        std::fs::write(&path, b"not really a png")?;
+        state.image_index.insert(&path);
+        let one = feed(&state).await;
+        assert!(one.contains("<id>urn:lolcommits:image:repo-20240301-123000-abc1234.png</id>"));
+
+        state.config = SharedConfig::with_loaded(
+            LoadedConfig {
+                config: config::Config::default(),
+                server: config::ServerConfig {
+                    gallery_title: "Renamed".to_string(),
+                    feed_entries: 0,
+                    ..Default::default()
+                },
+                background: Background::Disabled,
+            },
+            None,
+        );
+        let renamed = feed(&state).await;
+        assert!(renamed.contains("<title>Renamed</title>"));
+        assert!(!renamed.contains("<entry>"), "feed_entries is honoured");
+        Ok(())
+    }
+
+    fn multipart_upload(boundary: &str, metadata: &str) -> Vec<u8> {
+        multipart_upload_frames(boundary, metadata, 1)
+    }
+
+    /// An upload with `frames` image parts, animated when there is more than one.
+    fn multipart_upload_frames(boundary: &str, metadata: &str, frames: usize) -> Vec<u8> {
+        let mut png = Vec::new();
+        image::DynamicImage::new_rgb8(64, 48)
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        multipart_upload_images(boundary, metadata, &vec![png; frames])
+    }
+
+    /// An upload with an image part for each of `images`.
+    fn multipart_upload_images(boundary: &str, metadata: &str, images: &[Vec<u8>]) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(
+            format!("--{boundary}\r\nContent-Disposition: form-data; name=\"metadata\"\r\n\r\n{metadata}\r\n")
+                .as_bytes(),
+        );
+        for (index, image) in images.iter().enumerate() {
+            body.extend_from_slice(
+                format!(
+                    "--{boundary}\r\nContent-Disposition: form-data; name=\"image\"; filename=\"frame-{index}.png\"\r\n\
+                     Content-Type: image/png\r\n\r\n"
+                )
+                .as_bytes(),
+            );
+            body.extend(image);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(format!("--{boundary}--\r\n").as_bytes());
+        body
+    }
+
+    /// A router over a fresh gallery in `dir`/images, without background replacement
+    /// or chyron so uploads process quickly.
+    fn test_router(dir: &std::path::Path) -> Router {
+        test_router_with(dir, |_| {})
+    }
+
+    /// [`test_router`] with the server config adjusted by `configure`.
+    fn test_router_with(
+        dir: &std::path::Path,
+        configure: impl FnOnce(&mut config::ServerConfig),
+    ) -> Router {
+        tracked_router(dir, TaskTracker::new(), configure)
+    }
+
+    /// [`test_router_with`], processing uploads on `tasks`.
+    fn tracked_router(
+        dir: &std::path::Path,
+        tasks: TaskTracker,
+        configure: impl FnOnce(&mut config::ServerConfig),
+    ) -> Router {
+        let mut server = config::ServerConfig {
+            images_dir: dir.join("images").display().to_string(),
+            state_dir: dir.join("state").display().to_string(),
+            background_path: "none".to_string(),
+            burned_in_chyron: false,
+            min_free_space_mb: 0,
+            ..Default::default()
+        };
+        configure(&mut server);
+        let config = config::Config {
+            server: Some(server),
+            ..Default::default()
+        };
+        create_router(
+            dir.join("images"),
+            metrics_exporter_prometheus::PrometheusBuilder::new()
+                .build_recorder()
+                .handle(),
+            Arc::new(SegmentationModel::open(
+                dir.join("models"),
+                &Default::default(),
+                Default::default(),
+                Default::default(),
+            )),
+            SharedConfig::new(config, None),
+            tasks,
+        )
+    }
+
+    fn upload_request(revision: &str, force: bool) -> Request {
+        Request::builder()
+            .method("POST")
+            .uri("/api/upload")
+            .header(
+                header::CONTENT_TYPE,
+                "multipart/form-data; boundary=lolcommits",
+            )
+            .body(axum::body::Body::from(multipart_upload(
+                "lolcommits",
+                &upload_metadata(revision, force).to_string(),
+            )))
+            .unwrap()
+    }
+
+    /// An upload of `image` as is, which need not be a valid image.
+    fn upload_request_with_image(revision: &str, image: Vec<u8>) -> Request {
+        Request::builder()
+            .method("POST")
+            .uri("/api/upload")
+            .header(
+                header::CONTENT_TYPE,
+                "multipart/form-data; boundary=lolcommits",
+            )
+            .body(axum::body::Body::from(multipart_upload_images(
+                "lolcommits",
+                &upload_metadata(revision, false).to_string(),
+                &[image],
+            )))
+            .unwrap()
+    }
+
+    fn upload_metadata(revision: &str, force: bool) -> serde_json::Value {
+        serde_json::json!({
+            "revision": revision,
+            "message": "feat: stream uploads",
+            "commit_type": "feat",
+            "scope": "",
+            "timestamp": "2024-01-02 03:04:05",
+            "repo_name": "repo",
+            "branch_name": "main",
+            "files_changed": 1,
+            "insertions": 2,
+            "deletions": 3,
+            "force": force,
+        })
+    }
+
+    /// Upload temp files left behind in `images_dir`.
+    fn upload_temp_files(images_dir: &std::path::Path) -> Vec<PathBuf> {
+        std::fs::read_dir(images_dir)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .map(|entry| entry.path())
+                    .filter(|path| {
+                        path.file_name()
+                            .and_then(|s| s.to_str())
+                            .is_some_and(|name| name.starts_with(UPLOAD_TEMP_PREFIX))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    #[tokio::test]
+    async fn test_upload_broadcasts_new_image_with_metadata() -> Result {
+        use futures::StreamExt;
+
+        let dir = tempfile::tempdir()?;
+        let images_dir = dir.path().join("images");
+        let router = test_router(dir.path());
+
+        let mut events = subscribe(&router).await;
+
+        let response = router
+            .oneshot(upload_request("abc1234def", false))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+        let frame = tokio::time::timeout(std::time::Duration::from_secs(30), events.next())
+            .await
+            .expect("no event within 30s")
+            .unwrap()
+            .unwrap();
+        let frame = String::from_utf8(frame.to_vec()).unwrap();
+        assert!(frame.starts_with("event: new_image\n"), "{frame}");
+
+        let data = frame
+            .lines()
+            .find_map(|line| line.strip_prefix("data: "))
+            .expect("event has data");
+        let image: serde_json::Value = serde_json::from_str(data)?;
+        assert_eq!(image["revision"], "abc1234def");
+        // Dated by the uploaded commit time, not by when it arrived
+        let timestamp = image["timestamp"].as_str().unwrap();
+        assert_eq!(
+            chrono::DateTime::parse_from_rfc3339(timestamp).ok(),
+            crate::parse_timestamp("2024-01-02 03:04:05")
+        );
+        let filename = image["filename"].as_str().unwrap();
+        assert_eq!(filename, "repo-20240102-030405-abc1234def.png");
+        assert!(images_dir.join(filename).exists());
+        Ok(())
+    }
+
+    fn ping_request(body: Vec<u8>) -> Request {
+        Request::builder()
+            .method("POST")
+            .uri("/api/ping")
+            .header(
+                header::CONTENT_TYPE,
+                "multipart/form-data; boundary=lolcommits",
+            )
+            .body(axum::body::Body::from(body))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_ping_reads_the_image_and_keeps_nothing() -> Result {
+        let dir = tempfile::tempdir()?;
+        let router = test_router(dir.path());
+
+        let response = router
+            .clone()
+            .oneshot(ping_request(multipart_upload("lolcommits", "{}")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body)?;
+        assert_eq!(
+            (body["width"].as_u64(), body["height"].as_u64()),
+            (Some(64), Some(48))
+        );
+        assert_eq!(body["read_only"], false);
+        let images_dir = dir.path().join("images");
+        assert!(
+            !images_dir.exists() || std::fs::read_dir(&images_dir)?.next().is_none(),
+            "ping left files behind"
+        );
+
+        let response = router
+            .oneshot(ping_request(multipart_upload_images(
+                "lolcommits",
+                "{}",
+                &[b"not a png".to_vec()],
+            )))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_traversal_in_repo_name_stays_in_images_dir() -> Result {
+        let dir = tempfile::tempdir()?;
+        let tasks = TaskTracker::new();
+        let router = tracked_router(dir.path(), tasks.clone(), |_| {});
+        let metadata = with("repo_name", serde_json::json!("../../escape"));
+
+        let response = router
+            .oneshot(metadata_upload_request(&metadata, None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+        tasks.close();
+        tokio::time::timeout(std::time::Duration::from_secs(30), tasks.wait())
+            .await
+            .expect("upload not processed within 30s");
+        assert!(
+            dir.path()
+                .join("images")
+                .join("_.._escape-20240102-030405-abc1234.png")
+                .exists()
+        );
+        let escaped: Vec<_> = std::fs::read_dir(dir.path())?
+            .map(|entry| entry.unwrap().file_name())
+            .filter(|name| name.to_string_lossy().starts_with("escape"))
+            .collect();
+        assert!(escaped.is_empty(), "{escaped:?}");
+        Ok(())
+    }
+
+    /// Upload `request` to `router` and wait for its processing on `tasks`, returning the
+    /// job's id and final status.
+    async fn finished_job(
+        router: Router,
+        tasks: TaskTracker,
+        request: Request,
+    ) -> (String, serde_json::Value) {
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let job_id = json_body(response).await["job_id"]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        tasks.close();
+        tokio::time::timeout(std::time::Duration::from_secs(30), tasks.wait())
+            .await
+            .expect("upload not processed within 30s");
+        let request = Request::builder()
+            .uri(format!("/api/jobs/{job_id}"))
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let job = json_body(router.oneshot(request).await.unwrap()).await;
+        (job_id, job)
+    }
+
+    #[tokio::test]
+    async fn test_failed_upload_is_kept() -> Result {
+        let dir = tempfile::tempdir()?;
+        let images_dir = dir.path().join("images");
+        let tasks = TaskTracker::new();
+        let router = tracked_router(dir.path(), tasks.clone(), |_| {});
+
+        let request = upload_request_with_image("abc1234def", b"not a png".to_vec());
+        let (job_id, job) = finished_job(router, tasks, request).await;
+
+        assert_eq!(job["status"], "failed", "{job}");
+        let kept = images_dir.join(failed_uploads::FAILED_DIR).join(&job_id);
+        assert_eq!(std::fs::read(kept.join("frame-0.bin"))?, b"not a png");
+        let metadata: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(kept.join(failed_uploads::METADATA_NAME))?)?;
+        assert_eq!(metadata["revision"], "abc1234def");
+        assert_eq!(
+            std::fs::read_to_string(kept.join(failed_uploads::ERROR_NAME))?,
+            job["error"].as_str().unwrap()
+        );
+        assert_eq!(upload_temp_files(&images_dir), Vec::<PathBuf>::new());
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_processing_past_deadline_is_cancelled() {
+        let deadline = Deadline::default();
+
+        let outcome = with_deadline(5, &deadline, std::future::pending::<Result<()>>()).await;
+
+        assert_eq!(
+            outcome.unwrap_err().to_string(),
+            "processing took longer than 5s (processing_timeout_secs)"
+        );
+        assert!(deadline.expired());
+        assert!(!deadline.begin_save(), "saving after the deadline");
+        let deadline = Deadline::default();
+        assert_eq!(
+            with_deadline(5, &deadline, async { Ok(1) }).await.unwrap(),
+            1
+        );
+        assert!(!deadline.expired());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_saving_past_deadline_is_finished() {
+        let deadline = Deadline::default();
+
+        let saving = async {
+            assert!(deadline.begin_save());
+            tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+            Ok("saved")
+        };
+
+        assert_eq!(with_deadline(5, &deadline, saving).await.unwrap(), "saved");
+        assert!(!deadline.expired());
+        assert!(!deadline.expire());
+    }
+
+    #[tokio::test]
+    async fn test_upload_processing_is_tracked() -> Result {
+        let dir = tempfile::tempdir()?;
+        let tasks = TaskTracker::new();
+        let router = tracked_router(dir.path(), tasks.clone(), |_| {});
+
+        let response = router
+            .oneshot(upload_request("abc1234def", false))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+        // What shutdown does once the listener has stopped
+        tasks.close();
+        tokio::time::timeout(std::time::Duration::from_secs(30), tasks.wait())
+            .await
+            .expect("upload not processed within 30s");
+        assert!(
+            dir.path()
+                .join("images")
+                .join("repo-20240102-030405-abc1234def.png")
+                .exists()
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_large_upload_is_streamed_through_a_temp_file() -> Result {
+        let dir = tempfile::tempdir()?;
+        let images_dir = dir.path().join("images");
+        let tasks = TaskTracker::new();
+        let router = tracked_router(dir.path(), tasks.clone(), |_| {});
+
+        // Noise doesn't compress, so this stays a few megabytes as a PNG
+        let mut seed = 0x2545_f491_u32;
+        let noise = image::RgbImage::from_fn(1024, 900, |_, _| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            image::Rgb([seed as u8, (seed >> 8) as u8, (seed >> 16) as u8])
+        });
+        let mut png = Vec::new();
+        image::DynamicImage::ImageRgb8(noise)
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)?;
+        assert!(
+            png.len() > 2 * 1024 * 1024
+                && png.len() < config::ServerConfig::default().max_upload_bytes
+        );
+
+        let response = router
+            .oneshot(upload_request_with_image("abc1234def", png))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(
+            upload_temp_files(&images_dir).len(),
+            1,
+            "awaiting processing"
+        );
+
+        tasks.close();
+        tokio::time::timeout(std::time::Duration::from_secs(60), tasks.wait())
+            .await
+            .expect("upload not processed within 60s");
+        assert!(
+            images_dir
+                .join("repo-20240102-030405-abc1234def.png")
+                .exists()
+        );
+        assert_eq!(upload_temp_files(&images_dir), Vec::<PathBuf>::new());
+        Ok(())
+    }
+
+    // Just over the limit is caught counting image bytes, far over it by the body limit
+    #[test_case(1025 ; "over the image limit")]
+    #[test_case(1024 * 1024 ; "over the body limit")]
+    #[tokio::test]
+    async fn test_oversized_upload_is_rejected_without_leftovers(size: usize) -> Result {
+        let dir = tempfile::tempdir()?;
+        let images_dir = dir.path().join("images");
+        let router = test_router_with(dir.path(), |server| server.max_upload_bytes = 1024);
+
+        let response = router
+            .oneshot(upload_request_with_image("abc1234def", vec![0; size]))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let body = json_body(response).await;
+        assert_eq!(body["error"]["code"], UPLOAD_TOO_LARGE_ERROR_CODE);
+        assert_eq!(body["error"]["details"]["max_upload_bytes"], 1024);
+        assert!(
+            body["error"]["message"]
+                .as_str()
+                .unwrap()
+                .contains("1024 bytes")
+        );
+        assert_eq!(upload_temp_files(&images_dir), Vec::<PathBuf>::new());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_config_reports_upload_limit() -> Result {
+        let dir = tempfile::tempdir()?;
+        let router = test_router_with(dir.path(), |server| server.max_upload_bytes = 8 << 20);
+
+        let response = router
+            .oneshot(
+                Request::builder()
+                    .uri("/api/config")
+                    .body(axum::body::Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(json_body(response).await["max_upload_bytes"], 8 << 20);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_undecodable_upload_fails_without_leftovers() -> Result {
+        let dir = tempfile::tempdir()?;
+        let images_dir = dir.path().join("images");
+        let tasks = TaskTracker::new();
+        let router = tracked_router(dir.path(), tasks.clone(), |_| {});
+
+        let response = router
+            .oneshot(upload_request_with_image(
+                "abc1234def",
+                b"not an image".to_vec(),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+        tasks.close();
+        tokio::time::timeout(std::time::Duration::from_secs(30), tasks.wait())
+            .await
+            .expect("upload not processed within 30s");
+        assert_eq!(upload_temp_files(&images_dir), Vec::<PathBuf>::new());
+        Ok(())
+    }
+
+    /// Upload `revision` through `router` and wait for it to be processed, returning the
+    /// upload's status.
+    async fn upload_and_process(
+        router: &Router,
+        tasks: &TaskTracker,
+        revision: &str,
+    ) -> StatusCode {
+        let response = router
+            .clone()
+            .oneshot(upload_request(revision, false))
+            .await
+            .unwrap();
+        tasks.close();
+        tokio::time::timeout(std::time::Duration::from_secs(30), tasks.wait())
+            .await
+            .expect("upload not processed within 30s");
+        tasks.reopen();
+        response.status()
+    }
+
+    #[tokio::test]
+    async fn test_deleted_revision_can_be_uploaded_again() -> Result {
+        let dir = tempfile::tempdir()?;
+        let images_dir = dir.path().join("images");
+        let tasks = TaskTracker::new();
+        let router = tracked_router(dir.path(), tasks.clone(), |server| {
+            server.admin_token = Some("secret".to_string());
+        });
+        let filename = "repo-20240102-030405-abc1234def.png";
+
+        assert_eq!(
+            upload_and_process(&router, &tasks, "abc1234def").await,
+            StatusCode::ACCEPTED
+        );
+        assert_eq!(
+            upload_and_process(&router, &tasks, "abc1234def").await,
+            StatusCode::CONFLICT
+        );
+
+        let mut request = Request::builder()
+            .method("DELETE")
+            .uri(format!("/api/images/{filename}"))
+            .body(axum::body::Body::empty())
+            .unwrap();
+        *request.headers_mut() = bearer("secret");
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        assert_eq!(
+            upload_and_process(&router, &tasks, "abc1234def").await,
+            StatusCode::ACCEPTED
+        );
+        assert!(images_dir.join(filename).exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_revision_deleted_by_hand_can_be_uploaded_again() -> Result {
+        let dir = tempfile::tempdir()?;
+        let images_dir = dir.path().join("images");
+        let tasks = TaskTracker::new();
+        let router = tracked_router(dir.path(), tasks.clone(), |_| {});
+        let filename = "repo-20240102-030405-abc1234def.png";
+
+        assert_eq!(
+            upload_and_process(&router, &tasks, "abc1234def").await,
+            StatusCode::ACCEPTED
+        );
+        std::fs::remove_file(images_dir.join(filename))?;
+        // Directory mtimes can be as coarse as a second
+        let later = std::time::SystemTime::now() + std::time::Duration::from_secs(2);
+        std::fs::File::open(&images_dir)?.set_modified(later)?;
+
+        assert_eq!(
+            upload_and_process(&router, &tasks, "abc1234def").await,
+            StatusCode::ACCEPTED
+        );
+        assert!(images_dir.join(filename).exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_gallery_index_survives_a_restart() -> Result {
+        let dir = tempfile::tempdir()?;
+        let tasks = TaskTracker::new();
+        let router = tracked_router(dir.path(), tasks.clone(), |_| {});
+        assert_eq!(
+            upload_and_process(&router, &tasks, "abc1234def").await,
+            StatusCode::ACCEPTED
+        );
+        drop(router);
+
+        let state_dir = dir.path().join("state");
+        assert!(state_dir.join(image_index::SNAPSHOT_FILE_NAME).exists());
+        let restarted = tracked_router(dir.path(), tasks.clone(), |_| {});
+        assert_eq!(
+            upload_and_process(&restarted, &tasks, "abc1234def").await,
+            StatusCode::CONFLICT
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_upload_refused_while_backlog_is_full() -> Result {
+        let dir = tempfile::tempdir()?;
+        let tasks = TaskTracker::new();
+        let router = tracked_router(dir.path(), tasks.clone(), |server| {
+            server.processing_concurrency = 1;
+            server.processing_backlog = 0;
+        });
+
+        let response = router
+            .clone()
+            .oneshot(upload_request("abc1234def", false))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+        // The first upload holds the only place until it has been processed
+        let response = router
+            .clone()
+            .oneshot(upload_request("def5678abc", false))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            response.headers()[header::RETRY_AFTER],
+            BUSY_RETRY_AFTER_SECS.to_string()
+        );
+        assert_eq!(json_body(response).await["error"]["code"], BUSY_ERROR_CODE);
+
+        tasks.close();
+        tokio::time::timeout(std::time::Duration::from_secs(30), tasks.wait())
+            .await
+            .expect("upload not processed within 30s");
+        let response = router
+            .oneshot(upload_request("def5678abc", false))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_upload_keeps_original_when_configured() -> Result {
+        use futures::StreamExt;
+
+        let dir = tempfile::tempdir()?;
+        let router = test_router_with(dir.path(), |server| server.keep_originals = true);
+
+        let mut events = subscribe(&router).await;
+
+        let response = router
+            .oneshot(upload_request("abc1234def", false))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        tokio::time::timeout(std::time::Duration::from_secs(30), events.next())
+            .await
+            .expect("no event within 30s");
+
+        let original = dir
+            .path()
+            .join("state")
+            .join(ORIGINALS_DIR)
+            .join("repo-20240102-030405-abc1234def.png");
+        assert!(original.is_file());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_upload_of_several_frames_saves_animation() -> Result {
+        use futures::StreamExt;
+
+        let dir = tempfile::tempdir()?;
+        let router = test_router_with(dir.path(), |server| server.animation_max_width = 32);
+
+        let mut events = subscribe(&router).await;
+
+        let metadata = serde_json::json!({
+            "revision": "abc1234def",
+            "message": "feat: animate",
+            "commit_type": "feat",
+            "scope": "",
+            "timestamp": "2024-01-02 03:04:05",
+            "repo_name": "repo",
+            "branch_name": "main",
+            "files_changed": 1,
+            "insertions": 2,
+            "deletions": 3,
+            "frame_delay_ms": 100,
+        });
+        let response = router
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/upload")
+                    .header(
+                        header::CONTENT_TYPE,
+                        "multipart/form-data; boundary=lolcommits",
+                    )
+                    .body(axum::body::Body::from(multipart_upload_frames(
+                        "lolcommits",
+                        &metadata.to_string(),
+                        3,
+                    )))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+        let frame = tokio::time::timeout(std::time::Duration::from_secs(30), events.next())
+            .await
+            .expect("no event within 30s")
+            .unwrap()
+            .unwrap();
+        let frame = String::from_utf8(frame.to_vec()).unwrap();
+        let data = frame
+            .lines()
+            .find_map(|line| line.strip_prefix("data: "))
+            .expect("event has data");
+        let image: serde_json::Value = serde_json::from_str(data)?;
+        assert_eq!(image["animated"], true);
+        assert_eq!(image["filename"], "repo-20240102-030405-abc1234def.gif");
+
+        let path = dir
+            .path()
+            .join("images")
+            .join("repo-20240102-030405-abc1234def.gif");
+        let gif = image::codecs::gif::GifDecoder::new(std::io::BufReader::new(
+            std::fs::File::open(&path)?,
+        ))?;
+        let frames = image::AnimationDecoder::into_frames(gif).collect_frames()?;
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].buffer().dimensions(), (32, 24));
+        assert_eq!(frames[0].delay().numer_denom_ms(), (100, 1));
+        Ok(())
+    }
+
+    #[test_case(false, None, false ; "config off")]
+    #[test_case(false, Some(true), true ; "upload turns it on")]
+    #[test_case(true, Some(false), false ; "upload turns it off")]
+    #[tokio::test]
+    async fn test_upload_chooses_chyron(
+        configured: bool,
+        requested: Option<bool>,
+        expected: bool,
+    ) -> Result {
+        use futures::StreamExt;
+
+        let dir = tempfile::tempdir()?;
+        let router = test_router_with(dir.path(), |server| server.burned_in_chyron = configured);
+        let mut events = subscribe(&router).await;
+
+        let mut metadata = upload_metadata("abc1234def", false);
+        if let Some(requested) = requested {
+            metadata["burned_in_chyron"] = requested.into();
+        }
+        let request = Request::builder()
+            .method("POST")
+            .uri("/api/upload")
+            .header(
+                header::CONTENT_TYPE,
+                "multipart/form-data; boundary=lolcommits",
+            )
+            .body(axum::body::Body::from(multipart_upload(
+                "lolcommits",
+                &metadata.to_string(),
+            )))
+            .unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        tokio::time::timeout(std::time::Duration::from_secs(30), events.next())
+            .await
+            .expect("no event within 30s");
+
+        // The upload is all black, so anything else is the chyron
+        let path = dir
+            .path()
+            .join("images")
+            .join("repo-20240102-030405-abc1234def.png");
+        let saved = image::open(&path)?.to_rgb8();
+        assert_eq!(saved.pixels().any(|p| p.0 != [0, 0, 0]), expected);
+        assert_eq!(
+            image_metadata::read_processing_info(&path)?.burned_in_chyron,
+            requested
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_upload_saves_configured_output_format() -> Result {
+        use futures::StreamExt;
+
+        let dir = tempfile::tempdir()?;
+        let router = test_router_with(dir.path(), |server| {
+            server.output_format = config::OutputFormat::Jpeg
+        });
+
+        let mut events = subscribe(&router).await;
+
+        let response = router
+            .oneshot(upload_request("abc1234def", false))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        tokio::time::timeout(std::time::Duration::from_secs(30), events.next())
+            .await
+            .expect("no event within 30s");
+
+        let path = dir
+            .path()
+            .join("images")
+            .join("repo-20240102-030405-abc1234def.jpg");
+        assert!(path.is_file());
+        let metadata = image_metadata::read_metadata(&path)?.expect("sidecar should be written");
+        assert_eq!(metadata.revision, "abc1234def");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_frontend_served_from_static_dir() -> Result {
+        let dir = tempfile::tempdir()?;
+        let static_dir = dir.path().join("gallery");
+        std::fs::create_dir_all(&static_dir)?;
+        std::fs::write(static_dir.join("index.html"), "<p>custom</p>")?;
+        std::fs::write(static_dir.join("logo.svg"), "<svg/>")?;
+        let router = test_router_with(dir.path(), |server| {
+            server.static_dir = Some(static_dir.display().to_string());
+        });
+
+        for (uri, status, body) in [
+            ("/", StatusCode::OK, "<p>custom</p>"),
+            ("/logo.svg", StatusCode::OK, "<svg/>"),
+            ("/api/no-such-route", StatusCode::NOT_FOUND, ""),
+        ] {
+            let response = router
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .uri(uri)
+                        .body(axum::body::Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), status, "{uri}");
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            if status == StatusCode::OK {
+                assert_eq!(bytes, body.as_bytes(), "{uri}");
+            }
+        }
+        Ok(())
+    }
+
+    /// Subscribe to `/api/events`, past its `hello`.
+    async fn subscribe(router: &Router) -> axum::body::BodyDataStream {
+        let response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/api/events")
+                    .body(axum::body::Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let mut events = response.into_body().into_data_stream();
+        let hello = next_frame(&mut events).await;
+        assert!(hello.starts_with("event: hello\n"), "{hello}");
+        events
+    }
+
+    async fn json_body(response: Response) -> serde_json::Value {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_upload_job_reports_saved_filename() -> Result {
+        let dir = tempfile::tempdir()?;
+        let router = test_router(dir.path());
+
+        let response = router
+            .clone()
+            .oneshot(upload_request("abc1234def", false))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let job_id = json_body(response).await["job_id"]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        let job = tokio::time::timeout(std::time::Duration::from_secs(30), async {
+            loop {
+                let request = Request::builder()
+                    .uri(format!("/api/jobs/{job_id}"))
+                    .body(axum::body::Body::empty())
+                    .unwrap();
+                let job = json_body(router.clone().oneshot(request).await.unwrap()).await;
+                if job["status"] != "queued" && job["status"] != "processing" {
+                    return job;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("job still running after 30s");
+
+        assert_eq!(job["status"], "done", "{job}");
+        assert_eq!(job["revision"], "abc1234def");
+        let filename = job["filename"].as_str().unwrap();
+        assert!(dir.path().join("images").join(filename).exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unknown_job_is_not_found() -> Result {
+        let dir = tempfile::tempdir()?;
+        let state = test_state(dir.path(), None);
+
+        let response = job_handler(State(state), Path("missing".to_owned()))
+            .await
+            .into_response();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(json_body(response).await["error"]["code"], "unknown_job");
+        Ok(())
+    }
+
+    /// An upload whose metadata part is `metadata` as is, sent as `content_type`.
+    fn metadata_upload_request(metadata: &str, content_type: Option<&str>) -> Request {
+        let part_type = content_type
+            .map(|content_type| format!("Content-Type: {content_type}\r\n"))
+            .unwrap_or_default();
+        let mut png = Vec::new();
+        image::DynamicImage::new_rgb8(64, 48)
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        let mut body = format!(
+            "--lolcommits\r\nContent-Disposition: form-data; name=\"metadata\"\r\n{part_type}\r\n{metadata}\r\n\
+             --lolcommits\r\nContent-Disposition: form-data; name=\"image\"; filename=\"image.png\"\r\n\
+             Content-Type: image/png\r\n\r\n"
+        )
+        .into_bytes();
+        body.extend(png);
+        body.extend_from_slice(b"\r\n--lolcommits--\r\n");
+
+        Request::builder()
+            .method("POST")
+            .uri("/api/upload")
+            .header(
+                header::CONTENT_TYPE,
+                "multipart/form-data; boundary=lolcommits",
+            )
+            .body(axum::body::Body::from(body))
+            .unwrap()
+    }
+
+    fn with(key: &str, value: serde_json::Value) -> String {
+        let mut metadata = upload_metadata("abc1234", false);
+        metadata[key] = value;
+        metadata.to_string()
+    }
+
+    #[test_case(r#"{"revision": "abc1234", "message": "feat: trunc"#.to_string(), "message" ; "truncated")]
+    #[test_case(with("files_changed", serde_json::json!("three")), "files_changed" ; "wrong type")]
+    #[test_case(with("insertions", serde_json::json!(-2)), "insertions" ; "negative stats")]
+    #[test_case(with("deletions", serde_json::json!(u32::MAX)), "deletions" ; "wrapped stats")]
+    #[test_case(with("revision", serde_json::json!("")), "revision" ; "empty revision")]
+    #[test_case(with("revision", serde_json::json!("../abc")), "revision" ; "path in revision")]
+    #[test_case(with("revision", serde_json::json!("main")), "revision" ; "revision not hex")]
+    #[test_case(with("repo_name", serde_json::json!("..")), "repo_name" ; "nothing left of repo name")]
+    #[test_case(with("timestamp", serde_json::json!("yesterday")), "timestamp" ; "unparseable timestamp")]
+    #[test_case(with("timestamp", serde_json::json!("1901-01-01 00:00:00")), "timestamp" ; "absurd timestamp")]
+    #[tokio::test]
+    async fn test_malformed_metadata_is_rejected(metadata: String, field: &str) -> Result {
+        let dir = tempfile::tempdir()?;
+        let images_dir = dir.path().join("images");
+
+        let response = test_router(dir.path())
+            .oneshot(metadata_upload_request(&metadata, Some("application/json")))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = json_body(response).await;
+        assert_eq!(body["error"]["code"], INVALID_METADATA_ERROR_CODE);
+        assert_eq!(body["error"]["details"]["field"], field);
+        assert!(
+            body["error"]["message"]
+                .as_str()
+                .unwrap()
+                .starts_with(&format!("metadata {field}: ")),
+            "{body}"
+        );
+        assert_eq!(upload_temp_files(&images_dir), Vec::<PathBuf>::new());
+        Ok(())
+    }
+
+    #[test_case(Some("text/plain"), StatusCode::UNSUPPORTED_MEDIA_TYPE ; "not json")]
+    #[test_case(Some("application/json; charset=utf-8"), StatusCode::ACCEPTED ; "json")]
+    #[test_case(None, StatusCode::ACCEPTED ; "untyped")]
+    #[tokio::test]
+    async fn test_metadata_content_type(content_type: Option<&str>, status: StatusCode) -> Result {
+        let dir = tempfile::tempdir()?;
+        let metadata = upload_metadata("abc1234", false).to_string();
+
+        let response = test_router(dir.path())
+            .oneshot(metadata_upload_request(&metadata, content_type))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), status);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_upload_is_a_conflict() -> Result {
+        let dir = tempfile::tempdir()?;
+        save_image(
+            &dir.path().join("images"),
+            "repo-20240101-120000-abc1234.png",
+        )?;
+        let router = test_router(dir.path());
+
+        let response = router
+            .clone()
+            .oneshot(upload_request("abc1234", false))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let body = json_body(response).await;
+        assert_eq!(body["error"]["code"], DUPLICATE_REVISION_ERROR_CODE);
+        assert_eq!(body["error"]["details"]["revision"], "abc1234");
+        assert_eq!(
+            body["error"]["details"]["filename"],
+            "repo-20240101-120000-abc1234.png"
+        );
+
+        let response = router
+            .oneshot(upload_request("abc1234", true))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        Ok(())
+    }
+
+    async fn best(query: &str) -> Response {
+        let Query(query) =
+            Query::try_from_uri(&format!("/api/best?{query}").parse().unwrap()).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        best_images(State(test_state(dir.path(), None)), Query(query))
+            .await
+            .into_response()
+    }
+
+    #[tokio::test]
+    async fn test_best_rejects_bad_dates_and_metrics() {
+        for query in [
+            "since=2024-13-01",
+            "until=last-week",
+            "since=2024-02-01&until=2024-01-01",
+            "metric=likes",
+            "metric=reactions",
+        ] {
+            assert_eq!(
+                best(query).await.status(),
+                StatusCode::BAD_REQUEST,
+                "{query}"
+            );
+        }
+    }
+
+    #[test_case(None, None, None, "lolcommits.zip" ; "everything")]
+    #[test_case(Some("app"), None, None, "lolcommits-app.zip" ; "repo")]
+    #[test_case(Some("my app/v2"), None, None, "lolcommits-my_app_v2.zip" ; "unsafe repo")]
+    #[test_case(Some(""), Some("2024-03-01"), None, "lolcommits-since-2024-03-01.zip" ; "since")]
+    #[test_case(None, None, Some("2024-03-31"), "lolcommits-until-2024-03-31.zip" ; "until")]
+    #[test_case(Some("app"), Some("2024-03-01"), Some("2024-03-31"), "lolcommits-app-2024-03-01-to-2024-03-31.zip" ; "repo and range")]
+    fn test_export_filename(
+        repo: Option<&str>,
+        since: Option<&str>,
+        until: Option<&str>,
+        expected: &str,
+    ) {
+        let window = best_of::TimeWindow::parse(since, until).unwrap();
+        assert_eq!(export_filename(repo, &window), expected);
+    }
+
+    #[tokio::test]
+    async fn test_export_streams_matching_images() -> Result {
+        let dir = tempfile::tempdir()?;
+        let images_dir = dir.path().join("images");
+        for filename in [
+            "app-20240301-120000-aaa1111.png",
+            "app-20240401-120000-bbb2222.png",
+            "lib-20240315-120000-ccc3333.png",
+        ] {
+            save_image(&images_dir, filename)?;
+        }
+        let state = test_state(dir.path(), None);
+        let export = |query: &str| {
+            let Query(query) =
+                Query::try_from_uri(&format!("/api/export?{query}").parse().unwrap()).unwrap();
+            export_handler(State(state.clone()), Query(query))
+        };
+
+        let response = export("repo=app&since=2024-03-15").await.into_response();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/zip");
+        assert_eq!(
+            response.headers()[header::CONTENT_DISPOSITION],
+            "attachment; filename=\"lolcommits-app-since-2024-03-15.zip\""
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let mut zip = zip::ZipArchive::new(std::io::Cursor::new(body))?;
+        let names: Vec<_> = zip.file_names().map(str::to_owned).collect();
+        assert_eq!(
+            names,
+            ["app-20240401-120000-bbb2222.png", export::MANIFEST_NAME]
+        );
+        let manifest: serde_json::Value =
+            serde_json::from_reader(zip.by_name(export::MANIFEST_NAME)?)?;
+        assert_eq!(manifest[0]["revision"], "bbb2222");
+
+        let response = export("until=someday").await.into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stats_filters_the_index() -> Result {
+        let dir = tempfile::tempdir()?;
+        let state = test_state(dir.path(), None);
+        let images_dir = dir.path().join("images");
+        std::fs::create_dir_all(&images_dir)?;
+        for filename in [
+            "app-20240301-120000-aaa1111.png",
+            "app-20240401-120000-bbb2222.png",
+            "lib-20240315-120000-ccc3333.png",
+        ] {
+            let path = images_dir.join(filename);
+            std::fs::write(&path, b"not really a png")?;
+            state.image_index.insert(&path);
+        }
+        let stats = |query: &str| {
+            let Query(query) =
+                Query::try_from_uri(&format!("/api/stats?{query}").parse().unwrap()).unwrap();
+            let state = state.clone();
+            async move {
+                stats_handler(State(state), Query(query))
+                    .await
+                    .into_response()
+            }
+        };
+
+        let body = json_body(stats("").await).await;
+        assert_eq!(body["totals"]["commits"], 3);
+        assert_eq!(body["by_repo"]["app"]["commits"], 2);
+        assert_eq!(body["per_day"]["2024-03-15"], 1);
+
+        let body = json_body(stats("repo=app&until=2024-03-31").await).await;
+        assert_eq!(body["totals"]["commits"], 1);
+
+        let response = stats("since=yesterday").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(json_body(response).await["error"]["code"], "invalid_date");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_only_response_is_503_with_code() -> Result {
+        let dir = tempfile::tempdir()?;
+        let state = test_state(dir.path(), None);
+        assert!(check_writable(&state).is_ok());
+
+        state.read_only.set(true)?;
+        let response = check_writable(&state).unwrap_err().into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            json_body(response).await["error"]["code"],
+            READ_ONLY_ERROR_CODE
+        );
+        Ok(())
+    }
 }
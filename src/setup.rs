@@ -0,0 +1,858 @@
+//! First-run provisioning behind `lolcommits setup` and `lolcommitsd setup`.
+//!
+//! Each step is a function over injected collaborators ([`Prompter`], [`ClientProbe`],
+//! a model fetcher) so it can be exercised without a camera, network or terminal. All
+//! steps are safe to rerun: existing config, hooks, directories and models are kept and
+//! reported as already in place.
+
+use crate::{
+    camera::{self, DetectedCamera},
+    config::{CameraDeviceConfig, ClientConfig, Config, ServerConfig},
+    error::{Error, Result},
+    hook, segmentation,
+};
+use owo_colors::OwoColorize;
+use std::io::{BufRead, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+const IMAGES_DIR_MODE: u32 = 0o755;
+const MODELS_DIR_MODE: u32 = 0o755;
+const STATE_DIR_MODE: u32 = 0o700;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The step changed something.
+    Done(String),
+    /// Nothing to do, an earlier run (or the user) already took care of it.
+    AlreadyDone(String),
+    /// The step was declined or doesn't apply here.
+    Skipped(String),
+    Failed(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepReport {
+    pub step: &'static str,
+    pub outcome: Outcome,
+}
+
+impl StepReport {
+    fn new(step: &'static str, outcome: Outcome) -> Self {
+        Self { step, outcome }
+    }
+
+    pub fn is_failure(&self) -> bool {
+        matches!(self.outcome, Outcome::Failed(_))
+    }
+
+    /// Print the step as a ✓/✗ line.
+    pub fn print(&self) {
+        match &self.outcome {
+            Outcome::Done(detail) | Outcome::AlreadyDone(detail) => {
+                println!("{} {}: {}", "✓".green(), self.step, detail)
+            }
+            Outcome::Skipped(detail) => {
+                println!("{} {}: {}", "-".yellow(), self.step, detail.dimmed())
+            }
+            Outcome::Failed(detail) => println!("{} {}: {}", "✗".red(), self.step, detail.red()),
+        }
+    }
+}
+
+/// Source of answers to setup questions.
+pub trait Prompter {
+    /// Ask a yes/no question.
+    fn confirm(&mut self, question: &str, default: bool) -> bool;
+
+    /// Ask for a value, returning `default` when nothing is entered.
+    fn ask(&mut self, question: &str, default: &str) -> String;
+}
+
+/// Answers every question with its default (`--yes`).
+pub struct AssumeDefaults;
+
+impl Prompter for AssumeDefaults {
+    fn confirm(&mut self, _question: &str, default: bool) -> bool {
+        default
+    }
+
+    fn ask(&mut self, _question: &str, default: &str) -> String {
+        default.to_string()
+    }
+}
+
+/// Asks on stdout and reads answers from stdin, using the default on EOF.
+pub struct TerminalPrompter;
+
+impl TerminalPrompter {
+    fn read_answer(prompt: &str) -> Option<String> {
+        print!("{prompt} ");
+        std::io::stdout().flush().ok()?;
+
+        let mut line = String::new();
+        match std::io::stdin().lock().read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line.trim().to_string()),
+        }
+    }
+}
+
+impl Prompter for TerminalPrompter {
+    fn confirm(&mut self, question: &str, default: bool) -> bool {
+        let hint = if default { "[Y/n]" } else { "[y/N]" };
+        match Self::read_answer(&format!("{question} {hint}"))
+            .map(|answer| answer.to_lowercase())
+            .as_deref()
+        {
+            Some("y" | "yes") => true,
+            Some("n" | "no") => false,
+            _ => default,
+        }
+    }
+
+    fn ask(&mut self, question: &str, default: &str) -> String {
+        match Self::read_answer(&format!("{question} [{default}]")) {
+            Some(answer) if !answer.is_empty() => answer,
+            _ => default.to_string(),
+        }
+    }
+}
+
+/// The parts of client setup that need a camera or the network.
+pub trait ClientProbe {
+    fn detect_cameras(&self) -> Result<Vec<DetectedCamera>>;
+
+    /// Capture a frame with the configured cameras, returning its dimensions.
+    fn capture(&self, config: &ClientConfig) -> Result<(u32, u32)>;
+
+    /// Check the server answers `/api/config`, returning its gallery title.
+    fn check_server(&self, config: &ClientConfig) -> Result<String>;
+}
+
+/// [`ClientProbe`] backed by the real camera and HTTP client.
+pub struct SystemProbe;
+
+impl ClientProbe for SystemProbe {
+    fn detect_cameras(&self) -> Result<Vec<DetectedCamera>> {
+        camera::detect_cameras()
+    }
+
+    fn capture(&self, config: &ClientConfig) -> Result<(u32, u32)> {
+        let image = camera::capture_image(config)?;
+        Ok((image.width(), image.height()))
+    }
+
+    fn check_server(&self, config: &ClientConfig) -> Result<String> {
+        let url = format!("{}/api/config", config.server_url);
+
+        let client = reqwest::blocking::Client::builder()
+            .timeout(std::time::Duration::from_secs(
+                config.server_upload_timeout_secs,
+            ))
+            .danger_accept_invalid_certs(config.tls_skip_verify)
+            .build()?;
+
+        let response = client
+            .get(&url)
+            .send()
+            .map_err(|source| Error::ServerConnectionFailed {
+                url: url.clone(),
+                source,
+                attempts: 1,
+            })?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(Error::HttpError {
+                status: status.as_u16(),
+            });
+        }
+
+        let body: serde_json::Value = serde_json::from_str(&response.text()?)?;
+        Ok(body
+            .get("gallery_title")
+            .and_then(|title| title.as_str())
+            .unwrap_or_default()
+            .to_string())
+    }
+}
+
+/// Run every client step in order, calling `on_step` as each one finishes.
+///
+/// The `[client]` section of the config at `config_path` is created or updated; other
+/// sections are preserved. `hooks_dir` is `None` when not run inside a git repository.
+pub fn run_client(
+    config_path: &Path,
+    hooks_dir: Option<&Path>,
+    probe: &impl ClientProbe,
+    prompter: &mut impl Prompter,
+    mut on_step: impl FnMut(&StepReport),
+) -> Vec<StepReport> {
+    let mut reports = Vec::new();
+    let mut record = |report: StepReport| {
+        on_step(&report);
+        reports.push(report);
+    };
+
+    let mut config = match load_existing(config_path) {
+        Ok(config) => config,
+        Err(e) => {
+            record(StepReport::new(
+                "config",
+                Outcome::Failed(format!("{}: {}", config_path.display(), e)),
+            ));
+            return reports;
+        }
+    };
+
+    let already_configured = config.client.is_some();
+    let mut client = config.client.take().unwrap_or_default();
+
+    record(configure_cameras(
+        &mut client,
+        already_configured,
+        probe,
+        prompter,
+    ));
+    record(configure_server_url(&mut client, prompter));
+
+    config.client = Some(client.clone());
+    record(write_config(&config, config_path));
+    record(install_hook(hooks_dir, prompter));
+    record(test_capture(&client, probe));
+    record(check_server(&client, probe));
+
+    reports
+}
+
+/// Run every server step in order, calling `on_step` as each one finishes.
+///
+/// `fetch_model` downloads the segmentation model into the given models directory.
+pub fn run_server(
+    config: &ServerConfig,
+    fetch_model: impl FnOnce(&Path, &segmentation::ModelSpec) -> Result<PathBuf>,
+    mut on_step: impl FnMut(&StepReport),
+) -> Vec<StepReport> {
+    let mut reports = Vec::new();
+    let mut record = |report: StepReport| {
+        on_step(&report);
+        reports.push(report);
+    };
+
+    record(create_dir(
+        "images dir",
+        &config.images_dir,
+        IMAGES_DIR_MODE,
+    ));
+    record(create_dir(
+        "models dir",
+        &config.models_dir,
+        MODELS_DIR_MODE,
+    ));
+    record(create_dir("state dir", &config.state_dir, STATE_DIR_MODE));
+    match segmentation::ModelSpec::from_config(&config.segmentation) {
+        Ok(spec) => record(download_model(&config.models_dir, &spec, fetch_model)),
+        Err(e) => record(StepReport::new(MODEL_STEP, Outcome::Failed(e.to_string()))),
+    }
+
+    reports
+}
+
+fn load_existing(config_path: &Path) -> Result<Config> {
+    if config_path.exists() {
+        Config::load_from(Some(config_path.to_path_buf()))
+    } else {
+        Ok(Config::default())
+    }
+}
+
+fn describe_camera(camera: &DetectedCamera) -> String {
+    format!("{} ({})", camera.device, camera.name)
+}
+
+/// Pick the camera to use. An existing `[client]` section is left alone (the capture
+/// step checks it still works) so reruns never lose hand-tuned device settings.
+pub fn configure_cameras(
+    client: &mut ClientConfig,
+    already_configured: bool,
+    probe: &impl ClientProbe,
+    prompter: &mut impl Prompter,
+) -> StepReport {
+    const STEP: &str = "camera";
+
+    if already_configured && !client.camera_devices.is_empty() {
+        let devices: Vec<&str> = client
+            .camera_devices
+            .iter()
+            .map(|d| d.device.as_str())
+            .collect();
+        return StepReport::new(
+            STEP,
+            Outcome::AlreadyDone(format!("using configured {}", devices.join(", "))),
+        );
+    }
+
+    let detected = match probe.detect_cameras() {
+        Ok(detected) => detected,
+        Err(e) => {
+            return StepReport::new(STEP, Outcome::Failed(format!("detection failed: {e}")));
+        }
+    };
+
+    let chosen = match detected.as_slice() {
+        [] => return StepReport::new(STEP, Outcome::Failed("no cameras detected".to_string())),
+        [only] => only,
+        cameras => {
+            let listing: Vec<String> = cameras
+                .iter()
+                .enumerate()
+                .map(|(i, camera)| format!("  {}) {}", i + 1, describe_camera(camera)))
+                .collect();
+            let answer = prompter.ask(
+                &format!("Cameras found:\n{}\nUse camera", listing.join("\n")),
+                "1",
+            );
+
+            match answer
+                .parse::<usize>()
+                .ok()
+                .and_then(|n| n.checked_sub(1))
+                .and_then(|i| cameras.get(i))
+            {
+                Some(camera) => camera,
+                None => {
+                    return StepReport::new(
+                        STEP,
+                        Outcome::Failed(format!("{answer} is not one of the listed cameras")),
+                    );
+                }
+            }
+        }
+    };
+
+    client.camera_devices = vec![CameraDeviceConfig::new(&chosen.device)];
+    StepReport::new(STEP, Outcome::Done(describe_camera(chosen)))
+}
+
+/// Ask for the server URL, defaulting to the configured one.
+pub fn configure_server_url(client: &mut ClientConfig, prompter: &mut impl Prompter) -> StepReport {
+    const STEP: &str = "server url";
+
+    let answer = prompter.ask("lolcommitsd server URL", &client.server_url);
+    let url = answer.trim().trim_end_matches('/');
+
+    if !(url.starts_with("http://") || url.starts_with("https://")) {
+        return StepReport::new(
+            STEP,
+            Outcome::Failed(format!("{url:?} is not an http(s) URL")),
+        );
+    }
+
+    if url == client.server_url {
+        StepReport::new(STEP, Outcome::AlreadyDone(url.to_string()))
+    } else {
+        client.server_url = url.to_string();
+        StepReport::new(STEP, Outcome::Done(url.to_string()))
+    }
+}
+
+pub fn write_config(config: &Config, config_path: &Path) -> StepReport {
+    const STEP: &str = "config";
+
+    match config.save_to(config_path) {
+        Ok(()) => StepReport::new(
+            STEP,
+            Outcome::Done(format!("wrote {}", config_path.display())),
+        ),
+        Err(e) => StepReport::new(STEP, Outcome::Failed(e.to_string())),
+    }
+}
+
+/// Install (or append to) the post-commit hook in `hooks_dir`.
+pub fn install_hook(hooks_dir: Option<&Path>, prompter: &mut impl Prompter) -> StepReport {
+    const STEP: &str = "post-commit hook";
+
+    let Some(hooks_dir) = hooks_dir else {
+        return StepReport::new(
+            STEP,
+            Outcome::Skipped("not in a git repository".to_string()),
+        );
+    };
+    let hook_path = hooks_dir.join("post-commit");
+
+    let existing = match std::fs::read_to_string(&hook_path) {
+        Ok(contents) => Some(contents),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => {
+            return StepReport::new(
+                STEP,
+                Outcome::Failed(format!("{}: {}", hook_path.display(), e)),
+            );
+        }
+    };
+
+    if existing.as_deref().is_some_and(hook::is_installed) {
+        return StepReport::new(
+            STEP,
+            Outcome::AlreadyDone(format!("{} already runs lolcommits", hook_path.display())),
+        );
+    }
+
+    if !prompter.confirm(
+        &format!("Install post-commit hook in {}?", hooks_dir.display()),
+        true,
+    ) {
+        return StepReport::new(STEP, Outcome::Skipped("declined".to_string()));
+    }
+
+    match hook::install(hooks_dir, false) {
+        Ok(change @ hook::Change::AlreadyInstalled(_)) => {
+            StepReport::new(STEP, Outcome::AlreadyDone(change.to_string()))
+        }
+        Ok(change) => StepReport::new(STEP, Outcome::Done(change.to_string())),
+        Err(e) => StepReport::new(STEP, Outcome::Failed(e.to_string())),
+    }
+}
+
+pub fn test_capture(client: &ClientConfig, probe: &impl ClientProbe) -> StepReport {
+    const STEP: &str = "test capture";
+
+    match probe.capture(client) {
+        Ok((width, height)) => StepReport::new(
+            STEP,
+            Outcome::Done(format!("captured a {width}x{height} frame")),
+        ),
+        Err(e) => StepReport::new(STEP, Outcome::Failed(e.to_string())),
+    }
+}
+
+/// Dry run against the server: fetch `/api/config` rather than uploading a test image.
+pub fn check_server(client: &ClientConfig, probe: &impl ClientProbe) -> StepReport {
+    const STEP: &str = "server";
+
+    match probe.check_server(client) {
+        Ok(title) => StepReport::new(
+            STEP,
+            Outcome::Done(format!("{} is serving {:?}", client.server_url, title)),
+        ),
+        Err(e) => StepReport::new(
+            STEP,
+            Outcome::Failed(format!("{}: {}", client.server_url, e)),
+        ),
+    }
+}
+
+/// Create `path` if needed and make sure it has `mode`.
+pub fn create_dir(step: &'static str, path: impl AsRef<Path>, mode: u32) -> StepReport {
+    let path = path.as_ref();
+    let current_mode = std::fs::metadata(path)
+        .ok()
+        .filter(|metadata| metadata.is_dir())
+        .map(|metadata| metadata.permissions().mode() & 0o777);
+
+    if current_mode == Some(mode) {
+        return StepReport::new(
+            step,
+            Outcome::AlreadyDone(format!("{} ({:o})", path.display(), mode)),
+        );
+    }
+
+    let created = std::fs::create_dir_all(path)
+        .and_then(|()| std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)));
+
+    match (created, current_mode) {
+        (Ok(()), None) => StepReport::new(
+            step,
+            Outcome::Done(format!("created {} ({:o})", path.display(), mode)),
+        ),
+        (Ok(()), Some(previous)) => StepReport::new(
+            step,
+            Outcome::Done(format!(
+                "changed {} from {:o} to {:o}",
+                path.display(),
+                previous,
+                mode
+            )),
+        ),
+        (Err(e), _) => StepReport::new(step, Outcome::Failed(format!("{}: {}", path.display(), e))),
+    }
+}
+
+const MODEL_STEP: &str = "segmentation model";
+
+/// Make sure the `spec` segmentation model is present in `models_dir`.
+pub fn download_model(
+    models_dir: impl AsRef<Path>,
+    spec: &segmentation::ModelSpec,
+    fetch_model: impl FnOnce(&Path, &segmentation::ModelSpec) -> Result<PathBuf>,
+) -> StepReport {
+    let model = spec.file(&models_dir);
+    if model.exists() {
+        return StepReport::new(
+            MODEL_STEP,
+            Outcome::AlreadyDone(model.display().to_string()),
+        );
+    }
+
+    match fetch_model(models_dir.as_ref(), spec) {
+        Ok(path) => StepReport::new(
+            MODEL_STEP,
+            Outcome::Done(format!("downloaded {}", path.display())),
+        ),
+        Err(e) => StepReport::new(MODEL_STEP, Outcome::Failed(e.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    /// Prompter that replays scripted answers, then falls back to defaults.
+    #[derive(Default)]
+    struct Scripted {
+        answers: Vec<String>,
+        confirms: Vec<bool>,
+        asked: usize,
+    }
+
+    impl Prompter for Scripted {
+        fn confirm(&mut self, _question: &str, default: bool) -> bool {
+            self.asked += 1;
+            if self.confirms.is_empty() {
+                default
+            } else {
+                self.confirms.remove(0)
+            }
+        }
+
+        fn ask(&mut self, _question: &str, default: &str) -> String {
+            self.asked += 1;
+            if self.answers.is_empty() {
+                default.to_string()
+            } else {
+                self.answers.remove(0)
+            }
+        }
+    }
+
+    struct StubProbe {
+        cameras: Vec<DetectedCamera>,
+        capture_ok: bool,
+        server_ok: bool,
+    }
+
+    impl Default for StubProbe {
+        fn default() -> Self {
+            Self {
+                cameras: vec![camera("0", "Integrated Camera")],
+                capture_ok: true,
+                server_ok: true,
+            }
+        }
+    }
+
+    impl ClientProbe for StubProbe {
+        fn detect_cameras(&self) -> Result<Vec<DetectedCamera>> {
+            Ok(self.cameras.clone())
+        }
+
+        fn capture(&self, _config: &ClientConfig) -> Result<(u32, u32)> {
+            if self.capture_ok {
+                Ok((640, 480))
+            } else {
+                Err(Error::CameraBusy {
+                    device: "0".to_string(),
+                })
+            }
+        }
+
+        fn check_server(&self, _config: &ClientConfig) -> Result<String> {
+            if self.server_ok {
+                Ok("Lolcommits Gallery".to_string())
+            } else {
+                Err(Error::HttpError { status: 502 })
+            }
+        }
+    }
+
+    fn camera(device: &str, name: &str) -> DetectedCamera {
+        DetectedCamera {
+            device: device.to_string(),
+            name: name.to_string(),
+        }
+    }
+
+    fn mode_of(path: &Path) -> u32 {
+        std::fs::metadata(path).unwrap().permissions().mode() & 0o777
+    }
+
+    #[test]
+    fn test_configure_cameras_single_camera() {
+        let mut client = ClientConfig::default();
+        let probe = StubProbe {
+            cameras: vec![camera("/dev/video2", "Logitech")],
+            ..Default::default()
+        };
+
+        let report = configure_cameras(&mut client, false, &probe, &mut AssumeDefaults);
+
+        assert!(matches!(report.outcome, Outcome::Done(_)));
+        assert_eq!(client.camera_devices[0].device, "/dev/video2");
+    }
+
+    #[test]
+    fn test_configure_cameras_prompts_between_several() {
+        let mut client = ClientConfig::default();
+        let probe = StubProbe {
+            cameras: vec![camera("0", "Integrated"), camera("2", "External")],
+            ..Default::default()
+        };
+        let mut prompter = Scripted {
+            answers: vec!["2".to_string()],
+            ..Default::default()
+        };
+
+        configure_cameras(&mut client, false, &probe, &mut prompter);
+        assert_eq!(client.camera_devices[0].device, "2");
+
+        let mut prompter = Scripted {
+            answers: vec!["7".to_string()],
+            ..Default::default()
+        };
+        let report = configure_cameras(&mut client, false, &probe, &mut prompter);
+        assert!(report.is_failure());
+    }
+
+    #[test]
+    fn test_configure_cameras_none_detected_fails() {
+        let mut client = ClientConfig::default();
+        let probe = StubProbe {
+            cameras: vec![],
+            ..Default::default()
+        };
+
+        let report = configure_cameras(&mut client, false, &probe, &mut AssumeDefaults);
+        assert!(report.is_failure());
+    }
+
+    #[test]
+    fn test_configure_cameras_keeps_existing_config() {
+        let mut client = ClientConfig {
+            camera_devices: vec![CameraDeviceConfig {
+                format: Some("MJPEG".to_string()),
+                ..CameraDeviceConfig::new("/dev/video-ugreen")
+            }],
+            ..Default::default()
+        };
+        let mut prompter = Scripted::default();
+
+        let report = configure_cameras(&mut client, true, &StubProbe::default(), &mut prompter);
+
+        assert!(matches!(report.outcome, Outcome::AlreadyDone(_)));
+        assert_eq!(client.camera_devices[0].device, "/dev/video-ugreen");
+        assert_eq!(client.camera_devices[0].format.as_deref(), Some("MJPEG"));
+        assert_eq!(prompter.asked, 0);
+    }
+
+    #[test]
+    fn test_configure_server_url() {
+        let mut client = ClientConfig::default();
+        let mut prompter = Scripted {
+            answers: vec![
+                "https://lol.example.com/".to_string(),
+                "lol.example.com".to_string(),
+            ],
+            ..Default::default()
+        };
+
+        let report = configure_server_url(&mut client, &mut prompter);
+        assert!(matches!(report.outcome, Outcome::Done(_)));
+        assert_eq!(client.server_url, "https://lol.example.com");
+
+        let report = configure_server_url(&mut client, &mut prompter);
+        assert!(report.is_failure());
+        assert_eq!(client.server_url, "https://lol.example.com");
+
+        let report = configure_server_url(&mut client, &mut AssumeDefaults);
+        assert!(matches!(report.outcome, Outcome::AlreadyDone(_)));
+    }
+
+    #[test]
+    fn test_install_hook_fresh_and_rerun() -> Result {
+        let dir = tempfile::tempdir()?;
+        let hooks_dir = dir.path().join("hooks");
+
+        let report = install_hook(Some(&hooks_dir), &mut AssumeDefaults);
+        assert!(matches!(report.outcome, Outcome::Done(_)));
+
+        let hook = hooks_dir.join("post-commit");
+        let contents = std::fs::read_to_string(&hook)?;
+        assert!(contents.starts_with("#!/bin/sh\n"));
+        assert!(hook::is_installed(&contents));
+        assert_eq!(mode_of(&hook), 0o755);
+
+        let report = install_hook(Some(&hooks_dir), &mut AssumeDefaults);
+        assert!(matches!(report.outcome, Outcome::AlreadyDone(_)));
+        assert_eq!(std::fs::read_to_string(&hook)?, contents);
+        Ok(())
+    }
+
+    #[test]
+    fn test_install_hook_appends_to_existing_hook() -> Result {
+        let dir = tempfile::tempdir()?;
+        let hook = dir.path().join("post-commit");
+        std::fs::write(&hook, "#!/bin/sh\necho committed")?;
+
+        install_hook(Some(dir.path()), &mut AssumeDefaults);
+
+        let contents = std::fs::read_to_string(&hook)?;
+        assert!(contents.starts_with("#!/bin/sh\necho committed\n"));
+        assert!(hook::is_installed(&contents));
+        Ok(())
+    }
+
+    #[test]
+    fn test_install_hook_declined_or_outside_repo() -> Result {
+        let dir = tempfile::tempdir()?;
+        let mut prompter = Scripted {
+            confirms: vec![false],
+            ..Default::default()
+        };
+
+        let report = install_hook(Some(dir.path()), &mut prompter);
+        assert!(matches!(report.outcome, Outcome::Skipped(_)));
+        assert!(!dir.path().join("post-commit").exists());
+
+        let report = install_hook(None, &mut AssumeDefaults);
+        assert!(matches!(report.outcome, Outcome::Skipped(_)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_client_preserves_other_sections() -> Result {
+        let dir = tempfile::tempdir()?;
+        let config_path = dir.path().join("config.toml");
+        std::fs::write(&config_path, "[server]\nbind_port = 8080\n")?;
+
+        let mut printed = 0;
+        let reports = run_client(
+            &config_path,
+            Some(&dir.path().join("hooks")),
+            &StubProbe::default(),
+            &mut AssumeDefaults,
+            |_| printed += 1,
+        );
+
+        assert_eq!(printed, reports.len());
+        assert!(reports.iter().all(|r| !r.is_failure()), "{reports:?}");
+
+        let config = Config::load_from(Some(config_path.clone()))?;
+        assert_eq!(config.server.unwrap().bind_port, 8080);
+        assert_eq!(config.client.unwrap().camera_devices[0].device, "0");
+
+        // A second run changes nothing it doesn't have to
+        let reports = run_client(
+            &config_path,
+            Some(&dir.path().join("hooks")),
+            &StubProbe::default(),
+            &mut AssumeDefaults,
+            |_| {},
+        );
+        let camera = reports.iter().find(|r| r.step == "camera").unwrap();
+        assert!(matches!(camera.outcome, Outcome::AlreadyDone(_)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_client_reports_capture_and_server_failures() -> Result {
+        let dir = tempfile::tempdir()?;
+        let probe = StubProbe {
+            capture_ok: false,
+            server_ok: false,
+            ..Default::default()
+        };
+
+        let reports = run_client(
+            &dir.path().join("config.toml"),
+            None,
+            &probe,
+            &mut AssumeDefaults,
+            |_| {},
+        );
+
+        let failed: Vec<&str> = reports
+            .iter()
+            .filter(|r| r.is_failure())
+            .map(|r| r.step)
+            .collect();
+        assert_eq!(failed, vec!["test capture", "server"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_dir_sets_mode_and_is_rerunnable() -> Result {
+        let dir = tempfile::tempdir()?;
+        let state_dir = dir.path().join("state");
+
+        let report = create_dir("state dir", &state_dir, 0o700);
+        assert!(matches!(report.outcome, Outcome::Done(_)));
+        assert_eq!(mode_of(&state_dir), 0o700);
+
+        let report = create_dir("state dir", &state_dir, 0o700);
+        assert!(matches!(report.outcome, Outcome::AlreadyDone(_)));
+
+        std::fs::set_permissions(&state_dir, std::fs::Permissions::from_mode(0o777))?;
+        let report = create_dir("state dir", &state_dir, 0o700);
+        assert!(matches!(report.outcome, Outcome::Done(_)));
+        assert_eq!(mode_of(&state_dir), 0o700);
+        Ok(())
+    }
+
+    #[test]
+    fn test_download_model_skips_existing_model() -> Result {
+        let dir = tempfile::tempdir()?;
+        let fetched = Cell::new(false);
+
+        let spec = segmentation::ModelSpec::default();
+
+        let report = download_model(dir.path(), &spec, |models_dir, spec| {
+            fetched.set(true);
+            let path = spec.file(models_dir);
+            std::fs::write(&path, b"onnx")?;
+            Ok(path)
+        });
+        assert!(matches!(report.outcome, Outcome::Done(_)));
+        assert!(fetched.get());
+
+        let report = download_model(dir.path(), &spec, |_, _| {
+            panic!("model should not be fetched again")
+        });
+        assert!(matches!(report.outcome, Outcome::AlreadyDone(_)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_server_reports_fetch_failure() -> Result {
+        let dir = tempfile::tempdir()?;
+        let config = ServerConfig {
+            images_dir: dir.path().join("images").display().to_string(),
+            models_dir: dir.path().join("models").display().to_string(),
+            state_dir: dir.path().join("state").display().to_string(),
+            ..Default::default()
+        };
+
+        let reports = run_server(
+            &config,
+            |_, _| Err(Error::HttpError { status: 404 }),
+            |_| {},
+        );
+
+        assert_eq!(reports.len(), 4);
+        assert!(reports[..3].iter().all(|r| !r.is_failure()));
+        assert!(reports[3].is_failure());
+        assert!(dir.path().join("images").is_dir());
+        Ok(())
+    }
+}
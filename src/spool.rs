@@ -0,0 +1,232 @@
+//! Local queue for captures that couldn't reach the server.
+//!
+//! When the upload fails to connect, the PNG and a JSON sidecar of its upload metadata are
+//! written to `spool_dir` and uploaded oldest-first by the next capture or
+//! `lolcommits_upload --flush-spool`. Entries beyond `spool_max_entries` or older than
+//! `spool_max_age_days` are dropped so the spool can't grow without bound.
+
+use crate::config::ClientConfig;
+use crate::error::Result;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+pub struct Spool {
+    dir: PathBuf,
+    max_entries: usize,
+    max_age: Duration,
+}
+
+/// A queued capture: `{millis}-{revision}.png` and its `.json` sidecar.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub image: PathBuf,
+    pub metadata: PathBuf,
+}
+
+impl Entry {
+    fn for_sidecar(metadata: PathBuf) -> Self {
+        Self {
+            image: metadata.with_extension("png"),
+            metadata,
+        }
+    }
+
+    /// The entry whose image is at `image`.
+    pub fn for_image(image: PathBuf) -> Self {
+        Self {
+            metadata: image.with_extension("json"),
+            image,
+        }
+    }
+
+    /// Remove both files, e.g. once uploaded.
+    pub fn remove(&self) {
+        for path in [&self.metadata, &self.image] {
+            if let Err(e) = std::fs::remove_file(path)
+                && e.kind() != std::io::ErrorKind::NotFound
+            {
+                tracing::warn!(path = %path.display(), error = %e, "Failed to remove spool entry");
+            }
+        }
+    }
+}
+
+impl Spool {
+    pub fn new(dir: impl Into<PathBuf>, max_entries: usize, max_age: Duration) -> Self {
+        Self {
+            dir: dir.into(),
+            max_entries,
+            max_age,
+        }
+    }
+
+    /// The spool configured for the client, `None` when spooling is off
+    /// (`spool_max_entries = 0`).
+    pub fn from_config(config: &ClientConfig) -> Option<Self> {
+        (config.spool_max_entries > 0).then(|| {
+            Self::new(
+                &config.spool_dir,
+                config.spool_max_entries,
+                Duration::from_secs(config.spool_max_age_days * 24 * 60 * 60),
+            )
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Queue `png` with its upload metadata, then prune.
+    pub fn push(&self, png: &[u8], metadata_json: &str, revision: &str) -> Result<Entry> {
+        let millis = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let revision: String = revision
+            .chars()
+            .filter(char::is_ascii_alphanumeric)
+            .collect();
+        let name = format!("{millis:013}-{revision}");
+
+        // The sidecar goes last, so an entry is only listed once its image is complete
+        let image = crate::storage::atomic_write(&self.dir, &format!("{name}.png"), png)?;
+        let metadata =
+            crate::storage::atomic_write(&self.dir, &format!("{name}.json"), metadata_json)?;
+        tracing::info!(path = %image.display(), "Spooled capture for later upload");
+
+        self.prune();
+        Ok(Entry { image, metadata })
+    }
+
+    /// Queued captures, oldest first.
+    pub fn entries(&self) -> Vec<Entry> {
+        let Ok(dir) = std::fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        let mut sidecars: Vec<PathBuf> = dir
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().and_then(|s| s.to_str()) == Some("json"))
+            .collect();
+        // Names start with a zero-padded timestamp
+        sidecars.sort();
+        sidecars.into_iter().map(Entry::for_sidecar).collect()
+    }
+
+    /// Drop entries older than the max age and the oldest beyond the max count,
+    /// returning how many were dropped.
+    pub fn prune(&self) -> usize {
+        let mut entries = self.entries();
+        let mut dropped = 0;
+
+        entries.retain(|entry| {
+            let expired = std::fs::metadata(&entry.metadata)
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|modified| modified.elapsed().ok())
+                .is_some_and(|age| age > self.max_age);
+            if expired {
+                tracing::warn!(path = %entry.image.display(), "Dropping expired spooled capture");
+                entry.remove();
+                dropped += 1;
+            }
+            !expired
+        });
+
+        let excess = entries.len().saturating_sub(self.max_entries);
+        for entry in &entries[..excess] {
+            tracing::warn!(path = %entry.image.display(), "Spool full, dropping oldest capture");
+            entry.remove();
+            dropped += 1;
+        }
+        dropped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(spool: &Spool) -> Vec<String> {
+        spool
+            .entries()
+            .iter()
+            .map(|entry| {
+                let name = entry.image.file_name().unwrap().to_string_lossy();
+                name.split_once('-').unwrap().1.to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_push_writes_image_and_sidecar() -> Result {
+        let dir = tempfile::tempdir()?;
+        let spool = Spool::new(dir.path().join("spool"), 10, Duration::from_secs(3600));
+
+        let entry = spool.push(b"png", r#"{"revision":"abc"}"#, "abc")?;
+
+        assert_eq!(std::fs::read(&entry.image)?, b"png");
+        assert_eq!(
+            std::fs::read_to_string(&entry.metadata)?,
+            r#"{"revision":"abc"}"#
+        );
+        assert_eq!(spool.entries(), vec![entry]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_entries_are_oldest_first() -> Result {
+        let dir = tempfile::tempdir()?;
+        let spool = Spool::new(dir.path(), 10, Duration::from_secs(3600));
+        std::fs::write(dir.path().join("0000000000002-bbb.json"), "{}")?;
+        std::fs::write(dir.path().join("0000000000001-aaa.json"), "{}")?;
+        std::fs::write(dir.path().join("0000000000003-ccc.json"), "{}")?;
+        // An image whose sidecar was never written isn't an entry
+        std::fs::write(dir.path().join("0000000000004-ddd.png"), "png")?;
+
+        assert_eq!(names(&spool), ["aaa.png", "bbb.png", "ccc.png"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_push_drops_oldest_beyond_max_entries() -> Result {
+        let dir = tempfile::tempdir()?;
+        let spool = Spool::new(dir.path(), 2, Duration::from_secs(3600));
+        std::fs::write(dir.path().join("0000000000001-aaa.json"), "{}")?;
+        std::fs::write(dir.path().join("0000000000001-aaa.png"), "png")?;
+        std::fs::write(dir.path().join("0000000000002-bbb.json"), "{}")?;
+
+        spool.push(b"png", "{}", "ccc")?;
+
+        assert_eq!(names(&spool), ["bbb.png", "ccc.png"]);
+        assert!(!dir.path().join("0000000000001-aaa.png").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_prune_drops_expired_entries() -> Result {
+        let dir = tempfile::tempdir()?;
+        let spool = Spool::new(dir.path(), 10, Duration::from_secs(3600));
+        let old = dir.path().join("0000000000001-aaa.json");
+        std::fs::write(&old, "{}")?;
+        std::fs::File::options()
+            .write(true)
+            .open(&old)?
+            .set_modified(SystemTime::now() - Duration::from_secs(7200))?;
+        std::fs::write(dir.path().join("0000000000002-bbb.json"), "{}")?;
+
+        assert_eq!(spool.prune(), 1);
+        assert_eq!(names(&spool), ["bbb.png"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_config_disabled_at_zero() {
+        let config = ClientConfig {
+            spool_max_entries: 0,
+            ..Default::default()
+        };
+        assert!(Spool::from_config(&config).is_none());
+        assert!(Spool::from_config(&ClientConfig::default()).is_some());
+    }
+}
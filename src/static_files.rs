@@ -0,0 +1,242 @@
+//! The gallery frontend, served at `/` and any other path no route claims.
+//!
+//! Its files are compiled in. With `static_dir` set to a directory holding an
+//! `index.html`, files there are served in their place, so the gallery can be restyled
+//! without a rebuild, and anything the directory lacks is still served built in. Only
+//! files within the directory are served, never dotfiles, nor anything a symlink in it
+//! points to outside it.
+
+use crate::api_error::ApiError;
+use axum::{
+    extract::Request,
+    http::{HeaderValue, Method, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use include_dir::{Dir, include_dir};
+use std::path::{Path, PathBuf};
+use tower::ServiceExt;
+use tower_http::services::ServeFile;
+
+/// The built-in frontend. Add CSS, scripts or images to `src/static` to ship them too.
+static EMBEDDED: Dir = include_dir!("$CARGO_MANIFEST_DIR/src/static");
+
+/// The page that marks a directory as a frontend, and what `/` serves.
+pub const INDEX_NAME: &str = "index.html";
+
+/// Pages are revalidated on every load, so a changed `static_dir` shows straight away.
+const PAGE_CACHE_CONTROL: &str = "no-cache";
+
+/// Other assets may be reused for a while.
+const ASSET_CACHE_CONTROL: &str = "public, max-age=3600";
+
+/// Answer `request` from `static_dir` when it has an `index.html`, otherwise, or when
+/// the file isn't there, from the built-in files.
+pub async fn serve(static_dir: Option<&Path>, request: Request) -> Response {
+    if !matches!(*request.method(), Method::GET | Method::HEAD) {
+        return not_found();
+    }
+    let Some(name) = asset_name(request.uri().path()) else {
+        return not_found();
+    };
+
+    if let Some(path) = static_dir
+        .filter(|dir| dir.join(INDEX_NAME).is_file())
+        .and_then(|dir| resolve(dir, &name))
+    {
+        let response = match ServeFile::new(path).oneshot(request).await {
+            Ok(response) => response.into_response(),
+            Err(never) => match never {},
+        };
+        if response.status() != StatusCode::NOT_FOUND {
+            return cacheable(response, &name);
+        }
+    }
+
+    match EMBEDDED.get_file(&name) {
+        Some(file) => cacheable(
+            (
+                [(header::CONTENT_TYPE, content_type(&name))],
+                file.contents(),
+            )
+                .into_response(),
+            &name,
+        ),
+        None => not_found(),
+    }
+}
+
+/// The file `name` names within `dir`, with any symlinks resolved, `None` when there's
+/// no such file or it lies outside `dir`.
+fn resolve(dir: &Path, name: &str) -> Option<PathBuf> {
+    let dir = dir.canonicalize().ok()?;
+    let path = dir.join(name).canonicalize().ok()?;
+    if !path.starts_with(&dir) {
+        tracing::warn!(path = %path.display(), static_dir = %dir.display(), "Refusing to serve a file outside static_dir");
+        return None;
+    }
+    path.is_file().then_some(path)
+}
+
+/// The file a request path names, relative to the frontend's root, `None` for paths
+/// that could leave it or name a dotfile. Directories name their `index.html`.
+/// Percent-encoded paths are refused outright, as no asset needs one.
+fn asset_name(path: &str) -> Option<String> {
+    let path = path.strip_prefix('/').unwrap_or(path);
+    if path.contains(['%', '\\']) {
+        return None;
+    }
+    let segments: Vec<&str> = path.split('/').collect();
+    let (last, dirs) = segments.split_last()?;
+    if dirs
+        .iter()
+        .any(|segment| segment.is_empty() || segment.starts_with('.'))
+        || last.starts_with('.')
+    {
+        return None;
+    }
+    Some(if last.is_empty() {
+        format!("{path}{INDEX_NAME}")
+    } else {
+        path.to_string()
+    })
+}
+
+fn content_type(name: &str) -> &'static str {
+    match Path::new(name).extension().and_then(|ext| ext.to_str()) {
+        Some("html") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js") => "text/javascript; charset=utf-8",
+        Some("json") => "application/json",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("ico") => "image/x-icon",
+        Some("woff2") => "font/woff2",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Add the caching header for `name` to a successful response.
+fn cacheable(mut response: Response, name: &str) -> Response {
+    let status = response.status();
+    if status.is_success() || status == StatusCode::NOT_MODIFIED {
+        let cache_control = if name.ends_with(".html") {
+            PAGE_CACHE_CONTROL
+        } else {
+            ASSET_CACHE_CONTROL
+        };
+        response.headers_mut().insert(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static(cache_control),
+        );
+    }
+    response
+}
+
+fn not_found() -> Response {
+    ApiError::not_found("no such page").into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Result;
+    use axum::http::HeaderMap;
+    use test_case::test_case;
+
+    async fn get(static_dir: Option<&Path>, uri: &str) -> (StatusCode, HeaderMap, String) {
+        let request = Request::builder()
+            .uri(uri)
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let response = serve(static_dir, request).await;
+        let (parts, body) = response.into_parts();
+        let body = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        (
+            parts.status,
+            parts.headers,
+            String::from_utf8_lossy(&body).into_owned(),
+        )
+    }
+
+    #[test_case("/", Some("index.html") ; "root")]
+    #[test_case("/css/site.css", Some("css/site.css") ; "nested")]
+    #[test_case("/theme/", Some("theme/index.html") ; "directory")]
+    #[test_case("/../etc/passwd", None ; "parent")]
+    #[test_case("/css/../../etc/passwd", None ; "nested parent")]
+    #[test_case("/%2e%2e/etc/passwd", None ; "encoded parent")]
+    #[test_case("/.env", None ; "dotfile")]
+    #[test_case("/css//site.css", None ; "empty segment")]
+    #[test_case("/css\\..\\secret", None ; "backslash")]
+    fn test_asset_name(path: &str, expected: Option<&str>) {
+        assert_eq!(asset_name(path).as_deref(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_embedded_page_without_static_dir() {
+        let (status, headers, body) = get(None, "/").await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers[header::CONTENT_TYPE], "text/html; charset=utf-8");
+        assert_eq!(headers[header::CACHE_CONTROL], PAGE_CACHE_CONTROL);
+        assert!(body.contains("<html"));
+        assert_eq!(get(None, "/logo.png").await.0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_static_dir_overrides_embedded_files() -> Result {
+        let dir = tempfile::tempdir()?;
+        let static_dir = dir.path().join("static");
+        std::fs::create_dir_all(static_dir.join("css"))?;
+        std::fs::write(static_dir.join(INDEX_NAME), "<p>custom</p>")?;
+        std::fs::write(static_dir.join("css/site.css"), "body {}")?;
+        std::fs::write(static_dir.join(".env"), "SECRET=1")?;
+        std::fs::write(dir.path().join("secret.txt"), "outside")?;
+        std::os::unix::fs::symlink(dir.path().join("secret.txt"), static_dir.join("linked.txt"))?;
+        std::os::unix::fs::symlink(dir.path(), static_dir.join("parent"))?;
+
+        let (status, headers, body) = get(Some(&static_dir), "/").await;
+        assert_eq!((status, body.as_str()), (StatusCode::OK, "<p>custom</p>"));
+        assert_eq!(headers[header::CACHE_CONTROL], PAGE_CACHE_CONTROL);
+
+        let (status, headers, body) = get(Some(&static_dir), "/css/site.css").await;
+        assert_eq!((status, body.as_str()), (StatusCode::OK, "body {}"));
+        assert!(
+            headers[header::CONTENT_TYPE]
+                .to_str()
+                .unwrap()
+                .starts_with("text/css")
+        );
+        assert_eq!(headers[header::CACHE_CONTROL], ASSET_CACHE_CONTROL);
+
+        for uri in [
+            "/.env",
+            "/../secret.txt",
+            "/%2e%2e/secret.txt",
+            "/linked.txt",
+            "/parent/secret.txt",
+            "/missing.js",
+        ] {
+            assert_eq!(
+                get(Some(&static_dir), uri).await.0,
+                StatusCode::NOT_FOUND,
+                "{uri}"
+            );
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_static_dir_without_index_is_ignored() -> Result {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("site.css"), "body {}")?;
+
+        let (status, _, body) = get(Some(dir.path()), "/").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("<html"));
+        assert_eq!(
+            get(Some(dir.path()), "/site.css").await.0,
+            StatusCode::NOT_FOUND
+        );
+        Ok(())
+    }
+}
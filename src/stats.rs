@@ -0,0 +1,170 @@
+//! Gallery statistics: totals and breakdowns of the indexed lolcommits, for
+//! `/api/stats`.
+
+use crate::best_of::TimeWindow;
+use crate::git::CommitMetadata;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Author key for images saved before authors were recorded.
+const UNKNOWN_AUTHOR: &str = "unknown";
+
+/// Which images the statistics cover.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Filter {
+    /// Only this repo, when set.
+    pub repo: Option<String>,
+    pub window: TimeWindow,
+}
+
+impl Filter {
+    fn matches(&self, image: &CommitMetadata) -> bool {
+        self.repo
+            .as_deref()
+            .is_none_or(|repo| repo == image.repo_name)
+            && self.window.contains(&image.timestamp)
+    }
+}
+
+/// Commit count and summed diff stats of a group of images.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Totals {
+    pub commits: usize,
+    pub files_changed: u64,
+    pub insertions: u64,
+    pub deletions: u64,
+}
+
+impl Totals {
+    fn add(&mut self, image: &CommitMetadata) {
+        self.commits += 1;
+        self.files_changed += u64::from(image.stats.files_changed);
+        self.insertions += u64::from(image.stats.insertions);
+        self.deletions += u64::from(image.stats.deletions);
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Stats {
+    pub totals: Totals,
+    pub by_repo: BTreeMap<String, Totals>,
+    pub by_author: BTreeMap<String, Totals>,
+    pub by_type: BTreeMap<String, Totals>,
+    pub by_branch: BTreeMap<String, Totals>,
+    /// Commits per `YYYY-MM-DD` day. Days without commits, and images with an
+    /// unparseable timestamp, are left out.
+    pub per_day: BTreeMap<String, usize>,
+}
+
+/// Aggregate the images matching `filter`.
+pub fn compute(images: &[CommitMetadata], filter: &Filter) -> Stats {
+    let mut stats = Stats::default();
+
+    for image in images.iter().filter(|image| filter.matches(image)) {
+        let author = if image.author_name.is_empty() {
+            UNKNOWN_AUTHOR
+        } else {
+            &image.author_name
+        };
+
+        stats.totals.add(image);
+        for (groups, key) in [
+            (&mut stats.by_repo, image.repo_name.as_str()),
+            (&mut stats.by_author, author),
+            (&mut stats.by_type, image.commit_type.as_str()),
+            (&mut stats.by_branch, image.branch_name.as_str()),
+        ] {
+            groups.entry(key.to_string()).or_default().add(image);
+        }
+
+        if let Some(taken) = crate::local_time(&image.timestamp) {
+            *stats.per_day.entry(taken.date().to_string()).or_default() += 1;
+        }
+    }
+
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::DiffStats;
+
+    fn image(repo: &str, author: &str, timestamp: &str, insertions: u32) -> CommitMetadata {
+        CommitMetadata {
+            path: Default::default(),
+            revision: "abc1234".to_string(),
+            message: "feat: stats".to_string(),
+            commit_type: "feat".to_string(),
+            scope: String::new(),
+            timestamp: timestamp.to_string(),
+            repo_name: repo.to_string(),
+            branch_name: "main".to_string(),
+            author_name: author.to_string(),
+            author_email: String::new(),
+            breaking: false,
+            co_authors: Vec::new(),
+            stats: DiffStats {
+                files_changed: 1,
+                insertions,
+                deletions: 1,
+            },
+        }
+    }
+
+    #[test]
+    fn test_empty_gallery() {
+        assert_eq!(compute(&[], &Filter::default()), Stats::default());
+    }
+
+    #[test]
+    fn test_groups_sum_to_the_totals() {
+        let images = [
+            image("app", "Ada", "2024-03-01 09:00:00", 10),
+            image("app", "", "2024-03-01 17:00:00", 5),
+            image("lib", "Ada", "2024-03-02 12:00:00", 1),
+        ];
+        let stats = compute(&images, &Filter::default());
+
+        assert_eq!(stats.totals.commits, 3);
+        assert_eq!(stats.totals.insertions, 16);
+        assert_eq!(stats.by_repo["app"].commits, 2);
+        assert_eq!(stats.by_author["Ada"].insertions, 11);
+        assert_eq!(stats.by_author[UNKNOWN_AUTHOR].commits, 1);
+        assert_eq!(stats.per_day["2024-03-01"], 2);
+        for groups in [
+            &stats.by_repo,
+            &stats.by_author,
+            &stats.by_type,
+            &stats.by_branch,
+        ] {
+            let commits: usize = groups.values().map(|totals| totals.commits).sum();
+            assert_eq!(commits, stats.totals.commits);
+        }
+    }
+
+    #[test]
+    fn test_filters_by_repo_and_window() {
+        let images = [
+            image("app", "Ada", "2024-03-01 09:00:00", 10),
+            image("app", "Ada", "2024-04-01 09:00:00", 20),
+            image("lib", "Ada", "2024-03-15 09:00:00", 30),
+            image("app", "Ada", "garbage", 40),
+        ];
+        let filter = Filter {
+            repo: Some("app".to_string()),
+            window: TimeWindow::parse(Some("2024-03-01"), Some("2024-03-31")).unwrap(),
+        };
+
+        let stats = compute(&images, &filter);
+
+        assert_eq!(stats.totals.commits, 1);
+        assert_eq!(stats.totals.insertions, 10);
+
+        // An unbounded window keeps images with unparseable timestamps out of the
+        // histogram only
+        let stats = compute(&images, &Filter::default());
+        assert_eq!(stats.totals.commits, 4);
+        assert_eq!(stats.per_day.values().sum::<usize>(), 3);
+    }
+}
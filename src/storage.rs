@@ -0,0 +1,236 @@
+//! Atomic file saves for everything written into `images_dir` and `state_dir`.
+//!
+//! Files are written to a temporary file beside the target, fsynced and renamed into
+//! place, so readers only ever see complete files.
+
+use crate::error::{Error, Result};
+use std::fs::File;
+use std::io::ErrorKind;
+use std::path::{Component, Path, PathBuf};
+
+/// Save `dir/filename` atomically, `write` filling in the temporary file at the path it
+/// is given. Creates `dir` if needed and returns the final path.
+pub fn atomic_save(
+    dir: &Path,
+    filename: &str,
+    write: impl FnOnce(&Path) -> Result,
+) -> Result<PathBuf> {
+    atomic_save_with(dir, filename, write, |from, to| std::fs::rename(from, to))
+}
+
+/// [`atomic_save`] for contents already in memory.
+pub fn atomic_write(dir: &Path, filename: &str, contents: impl AsRef<[u8]>) -> Result<PathBuf> {
+    atomic_save(dir, filename, |path| {
+        std::fs::write(path, contents.as_ref())?;
+        Ok(())
+    })
+}
+
+fn atomic_save_with(
+    dir: &Path,
+    filename: &str,
+    write: impl FnOnce(&Path) -> Result,
+    rename: impl Fn(&Path, &Path) -> std::io::Result<()>,
+) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    let target = within(dir, filename)?;
+
+    // Removed on drop if anything below fails
+    let temp_path = tempfile::NamedTempFile::new_in(dir)?.into_temp_path();
+    tracing::debug!(temp_path = %temp_path.display(), target = %target.display(), "Writing to temporary file");
+    write(&temp_path)?;
+    File::open(&temp_path)?.sync_all()?;
+
+    match rename(&temp_path, &target) {
+        Ok(()) => {}
+        // Some network filesystems refuse renames they consider cross-device even
+        // within a directory
+        Err(e) if is_cross_device(&e) => {
+            tracing::warn!(target = %target.display(), error = %e, "Rename crossed devices, copying instead");
+            copy_into_place(&temp_path, &target, dir, &rename)?;
+            temp_path.close()?;
+        }
+        Err(e) => return Err(e.into()),
+    }
+
+    sync_dir(dir);
+    Ok(target)
+}
+
+/// Put a copy of `source` at `target`, through a fresh temporary file in `dir` renamed
+/// into place. Should that rename cross devices too, `source` is copied over `target`
+/// directly, which readers could catch half written but is all that's left.
+fn copy_into_place(
+    source: &Path,
+    target: &Path,
+    dir: &Path,
+    rename: impl Fn(&Path, &Path) -> std::io::Result<()>,
+) -> Result {
+    let copy_path = tempfile::NamedTempFile::new_in(dir)?.into_temp_path();
+    std::fs::copy(source, &copy_path)?;
+    File::open(&copy_path)?.sync_all()?;
+    match rename(&copy_path, target) {
+        Ok(()) => Ok(()),
+        Err(e) if is_cross_device(&e) => {
+            tracing::warn!(target = %target.display(), error = %e, "Rename crossed devices again, copying over the target");
+            std::fs::copy(source, target)?;
+            File::open(target)?.sync_all()?;
+            Ok(())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// `dir/filename`, refusing a filename that would land anywhere but directly in `dir`
+/// once canonicalized.
+fn within(dir: &Path, filename: &str) -> Result<PathBuf> {
+    let target = dir.join(filename);
+    let mut components = Path::new(filename).components();
+    let plain =
+        matches!(components.next(), Some(Component::Normal(_))) && components.next().is_none();
+    let parent = target.parent().map(Path::canonicalize).transpose()?;
+    if !plain || parent != Some(dir.canonicalize()?) {
+        return Err(Error::PathOutsideDir {
+            path: target,
+            dir: dir.to_path_buf(),
+        });
+    }
+    Ok(target)
+}
+
+fn is_cross_device(error: &std::io::Error) -> bool {
+    /// `EXDEV` on Linux and macOS.
+    const EXDEV: i32 = 18;
+    error.kind() == ErrorKind::CrossesDevices || error.raw_os_error() == Some(EXDEV)
+}
+
+/// Persist the rename itself. Best effort, not every filesystem supports it.
+fn sync_dir(dir: &Path) {
+    if let Err(e) = File::open(dir).and_then(|dir| dir.sync_all()) {
+        tracing::debug!(dir = %dir.display(), error = %e, "Could not sync directory");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use test_case::test_case;
+
+    fn leftovers(dir: &Path) -> Vec<String> {
+        std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name != "out.png")
+            .collect()
+    }
+
+    #[test]
+    fn test_atomic_write_creates_directory() -> Result {
+        let root = tempfile::tempdir()?;
+        let dir = root.path().join("nested").join("thumbnails");
+
+        let path = atomic_write(&dir, "out.png", b"png bytes")?;
+
+        assert_eq!(path, dir.join("out.png"));
+        assert_eq!(std::fs::read(&path)?, b"png bytes");
+        assert!(leftovers(&dir).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_atomic_write_replaces_existing() -> Result {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("out.png"), b"old")?;
+
+        atomic_write(dir.path(), "out.png", b"new")?;
+
+        assert_eq!(std::fs::read(dir.path().join("out.png"))?, b"new");
+        Ok(())
+    }
+
+    #[test_case("../out.png" ; "parent")]
+    #[test_case("nested/out.png" ; "subdirectory")]
+    #[test_case("/tmp/out.png" ; "absolute")]
+    #[test_case("" ; "empty")]
+    fn test_refuses_paths_outside_dir(filename: &str) -> Result {
+        let root = tempfile::tempdir()?;
+        let dir = root.path().join("images");
+        std::fs::create_dir_all(dir.join("nested"))?;
+
+        let result = atomic_write(&dir, filename, b"escaped");
+
+        assert!(
+            matches!(result, Err(Error::PathOutsideDir { .. })),
+            "{result:?}"
+        );
+        assert!(!root.path().join("out.png").exists());
+        assert!(!dir.join("nested").join("out.png").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_failed_write_leaves_nothing_behind() -> Result {
+        let dir = tempfile::tempdir()?;
+
+        let result = atomic_save(dir.path(), "out.png", |_| {
+            Err(std::io::Error::other("encoder failed").into())
+        });
+
+        assert!(result.is_err());
+        assert!(!dir.path().join("out.png").exists());
+        assert!(leftovers(dir.path()).is_empty());
+        Ok(())
+    }
+
+    /// Renames `failures` times with `EXDEV`, then for real.
+    fn failing_rename(
+        failures: usize,
+        attempts: &Cell<usize>,
+    ) -> impl Fn(&Path, &Path) -> std::io::Result<()> {
+        move |from, to| {
+            attempts.set(attempts.get() + 1);
+            if attempts.get() <= failures {
+                return Err(std::io::Error::from_raw_os_error(18));
+            }
+            std::fs::rename(from, to)
+        }
+    }
+
+    #[test_case(1 ; "copy renamed into place")]
+    #[test_case(usize::MAX ; "copied over the target")]
+    fn test_cross_device_rename_falls_back_to_copy(failures: usize) -> Result {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("out.png"), b"old")?;
+        let attempts = Cell::new(0);
+
+        let path = atomic_save_with(
+            dir.path(),
+            "out.png",
+            |path| Ok(std::fs::write(path, b"copied")?),
+            failing_rename(failures, &attempts),
+        )?;
+
+        assert_eq!(attempts.get(), 2);
+        assert_eq!(std::fs::read(path)?, b"copied");
+        assert!(leftovers(dir.path()).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_other_rename_errors_are_returned() -> Result {
+        let dir = tempfile::tempdir()?;
+
+        let result = atomic_save_with(
+            dir.path(),
+            "out.png",
+            |path| Ok(std::fs::write(path, b"data")?),
+            |_, _| Err(std::io::Error::from(ErrorKind::PermissionDenied)),
+        );
+
+        assert!(result.is_err());
+        assert!(!dir.path().join("out.png").exists());
+        assert!(leftovers(dir.path()).is_empty());
+        Ok(())
+    }
+}
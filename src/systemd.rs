@@ -0,0 +1,56 @@
+//! systemd socket activation for lolcommitsd.
+//!
+//! With a `lolcommitsd.socket` unit, systemd opens the listening socket and passes it
+//! to the service as file descriptor 3, announcing it in `LISTEN_PID` and `LISTEN_FDS`
+//! (see sd_listen_fds(3)).
+
+use std::os::fd::{FromRawFd, RawFd};
+
+/// The first file descriptor systemd passes.
+const LISTEN_FDS_START: RawFd = 3;
+
+/// How many sockets systemd passed to the process `pid`, given `LISTEN_PID` and
+/// `LISTEN_FDS`. Variables meant for another process (a parent that didn't unset them)
+/// count as none.
+fn passed_fds(listen_pid: Option<&str>, listen_fds: Option<&str>, pid: u32) -> usize {
+    match (listen_pid.and_then(|p| p.parse::<u32>().ok()), listen_fds) {
+        (Some(listen_pid), Some(fds)) if listen_pid == pid => fds.parse().unwrap_or(0),
+        _ => 0,
+    }
+}
+
+/// The listening socket systemd passed, if started by socket activation. Only the
+/// first is used.
+pub fn listener() -> Option<std::net::TcpListener> {
+    let count = passed_fds(
+        std::env::var("LISTEN_PID").ok().as_deref(),
+        std::env::var("LISTEN_FDS").ok().as_deref(),
+        std::process::id(),
+    );
+    if count == 0 {
+        return None;
+    }
+    if count > 1 {
+        tracing::warn!(count, "systemd passed several sockets, using the first");
+    }
+    // SAFETY: systemd hands the process ownership of the descriptors from 3 on, and
+    // nothing else in lolcommitsd opens or claims descriptor 3 before this runs
+    let listener = unsafe { std::net::TcpListener::from_raw_fd(LISTEN_FDS_START) };
+    Some(listener)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(Some("42"), Some("1"), 42, 1 ; "for this process")]
+    #[test_case(Some("42"), Some("2"), 42, 2 ; "several")]
+    #[test_case(Some("41"), Some("1"), 42, 0 ; "for another process")]
+    #[test_case(None, Some("1"), 42, 0 ; "no pid")]
+    #[test_case(Some("42"), None, 42, 0 ; "no fds")]
+    #[test_case(Some("42"), Some("lots"), 42, 0 ; "malformed")]
+    fn test_passed_fds(pid_var: Option<&str>, fds_var: Option<&str>, pid: u32, expected: usize) {
+        assert_eq!(passed_fds(pid_var, fds_var, pid), expected);
+    }
+}
@@ -0,0 +1,121 @@
+//! HTTPS for lolcommitsd, with `tls_cert_path` and `tls_key_path` in `[server]`.
+//!
+//! The certificate and key are loaded once at startup, so a missing file or a key that
+//! doesn't belong to the certificate stops the daemon straight away rather than failing
+//! every connection.
+
+use crate::error::{Error, Result};
+use axum_server::tls_rustls::RustlsConfig;
+use std::path::Path;
+
+/// Load a PEM certificate chain and its private key.
+pub async fn load(cert_path: &Path, key_path: &Path) -> Result<RustlsConfig> {
+    let read = |path: &Path| {
+        std::fs::read(path).map_err(|source| Error::TlsFileRead {
+            path: path.to_path_buf(),
+            source,
+        })
+    };
+    let (cert, key) = (read(cert_path)?, read(key_path)?);
+    RustlsConfig::from_pem(cert, key)
+        .await
+        .map_err(|e| Error::TlsConfig {
+            reason: e.to_string(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, routing::get};
+
+    struct Pem {
+        cert: String,
+        key: String,
+    }
+
+    fn self_signed() -> Pem {
+        let rcgen::CertifiedKey { cert, signing_key } =
+            rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        Pem {
+            cert: cert.pem(),
+            key: signing_key.serialize_pem(),
+        }
+    }
+
+    fn write(dir: &Path, cert: &str, key: &str) -> (std::path::PathBuf, std::path::PathBuf) {
+        let (cert_path, key_path) = (dir.join("cert.pem"), dir.join("key.pem"));
+        std::fs::write(&cert_path, cert).unwrap();
+        std::fs::write(&key_path, key).unwrap();
+        (cert_path, key_path)
+    }
+
+    #[tokio::test]
+    async fn test_load_rejects_missing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let pem = self_signed();
+        let (cert_path, _) = write(dir.path(), &pem.cert, &pem.key);
+
+        let error = load(&cert_path, &dir.path().join("missing.pem"))
+            .await
+            .unwrap_err();
+
+        assert!(
+            matches!(&error, Error::TlsFileRead { path, .. } if path.ends_with("missing.pem")),
+            "{error}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_load_rejects_key_for_another_cert() {
+        let dir = tempfile::tempdir().unwrap();
+        let (cert_path, key_path) = write(dir.path(), &self_signed().cert, &self_signed().key);
+
+        let error = load(&cert_path, &key_path).await.unwrap_err();
+
+        assert!(matches!(error, Error::TlsConfig { .. }), "{error}");
+    }
+
+    #[tokio::test]
+    async fn test_load_rejects_garbage() {
+        let dir = tempfile::tempdir().unwrap();
+        let (cert_path, key_path) = write(dir.path(), "not a cert", &self_signed().key);
+
+        let error = load(&cert_path, &key_path).await.unwrap_err();
+
+        assert!(matches!(error, Error::TlsConfig { .. }), "{error}");
+    }
+
+    #[tokio::test]
+    async fn test_serves_https() {
+        let dir = tempfile::tempdir().unwrap();
+        let pem = self_signed();
+        let (cert_path, key_path) = write(dir.path(), &pem.cert, &pem.key);
+        let tls = load(&cert_path, &key_path).await.unwrap();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let url = format!(
+            "https://localhost:{}/",
+            listener.local_addr().unwrap().port()
+        );
+        let app = Router::new().route("/", get(|| async { "lol" }));
+        let server = axum_server::from_tcp_rustls(listener, tls).unwrap();
+        tokio::spawn(server.serve(app.into_make_service()));
+
+        let (verified, skipped) = tokio::task::spawn_blocking(move || {
+            let get = |skip_verify: bool| {
+                reqwest::blocking::Client::builder()
+                    .danger_accept_invalid_certs(skip_verify)
+                    .build()
+                    .and_then(|client| client.get(&url).send())
+                    .and_then(|response| response.text())
+            };
+            (get(false), get(true))
+        })
+        .await
+        .unwrap();
+
+        assert!(verified.is_err(), "self-signed certificate was trusted");
+        assert_eq!(skipped.unwrap(), "lol");
+    }
+}
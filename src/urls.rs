@@ -0,0 +1,126 @@
+//! Public URL construction for gallery images.
+//!
+//! Every place that hands an image location to a client (list endpoint, SSE events,
+//! feeds) goes through [`ImageUrls`] so deployments behind a reverse proxy or under a
+//! path prefix get consistent links.
+
+use crate::config::ServerConfig;
+use crate::git::CommitMetadata;
+use serde::Serialize;
+
+/// Route the gallery images are served under.
+const IMAGES_ROUTE: &str = "/images";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ImageUrls {
+    pub url: String,
+    pub thumb_url: String,
+}
+
+impl ImageUrls {
+    /// Build the URLs for an image from its metadata (the filename is taken from `path`).
+    pub fn for_metadata(config: &ServerConfig, metadata: &CommitMetadata) -> Self {
+        let filename = metadata
+            .path
+            .file_name()
+            .and_then(|s| s.to_str())
+            .unwrap_or("");
+        Self::for_filename(config, filename)
+    }
+
+    /// Build the URLs for an image filename within images_dir.
+    pub fn for_filename(config: &ServerConfig, filename: &str) -> Self {
+        let url = format!("{}{}/{}", base_url(config), IMAGES_ROUTE, filename);
+
+        // No thumbnails are generated yet, so the thumbnail is the full image
+        Self {
+            thumb_url: url.clone(),
+            url,
+        }
+    }
+}
+
+/// Base that public URLs are built on, never ending in '/'.
+///
+/// Uses `public_base_url` verbatim when set (it is expected to already include any
+/// path the gallery is mounted under), otherwise a root-relative `mount_prefix`.
+pub fn base_url(config: &ServerConfig) -> String {
+    match config
+        .public_base_url
+        .as_deref()
+        .map(|u| u.trim_end_matches('/'))
+    {
+        Some(base) if !base.is_empty() => base.to_string(),
+        _ => normalise_prefix(&config.mount_prefix),
+    }
+}
+
+/// Normalise a mount prefix to either "" or "/segment[/segment...]".
+fn normalise_prefix(prefix: &str) -> String {
+    let trimmed = prefix.trim_matches('/');
+    if trimmed.is_empty() {
+        String::new()
+    } else {
+        format!("/{}", trimmed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    fn server_config(public_base_url: Option<&str>, mount_prefix: &str) -> ServerConfig {
+        ServerConfig {
+            public_base_url: public_base_url.map(|s| s.to_owned()),
+            mount_prefix: mount_prefix.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    #[test_case(None,                                 "",              "/images/a.png" ; "defaults")]
+    #[test_case(None,                                 "/lol",          "/lol/images/a.png" ; "prefix")]
+    #[test_case(None,                                 "lol/",          "/lol/images/a.png" ; "prefix without leading slash")]
+    #[test_case(None,                                 "/",             "/images/a.png" ; "root prefix")]
+    #[test_case(None,                                 "/a/b/",         "/a/b/images/a.png" ; "nested prefix")]
+    #[test_case(Some("https://lol.example.com"),      "",              "https://lol.example.com/images/a.png" ; "base url")]
+    #[test_case(Some("https://lol.example.com/"),     "",              "https://lol.example.com/images/a.png" ; "base url trailing slash")]
+    #[test_case(Some("https://example.com/lol//"),    "",              "https://example.com/lol/images/a.png" ; "base url with path")]
+    #[test_case(Some("https://lol.example.com"),      "/ignored",      "https://lol.example.com/images/a.png" ; "base url wins over prefix")]
+    #[test_case(Some(""),                             "/lol",          "/lol/images/a.png" ; "empty base url uses prefix")]
+    fn test_image_urls(base: Option<&str>, prefix: &str, expected: &str) {
+        let config = server_config(base, prefix);
+        let urls = ImageUrls::for_filename(&config, "a.png");
+        assert_eq!(urls.url, expected);
+        assert_eq!(urls.thumb_url, expected);
+    }
+
+    #[test]
+    fn test_for_metadata_uses_path_filename() {
+        let config = server_config(None, "/lol");
+        let metadata = CommitMetadata {
+            path: std::path::PathBuf::from(
+                "/var/lib/lolcommits/images/repo-20240101-000000-abc.png",
+            ),
+            revision: "abc".to_owned(),
+            message: String::new(),
+            commit_type: String::new(),
+            scope: String::new(),
+            timestamp: String::new(),
+            repo_name: "repo".to_owned(),
+            branch_name: String::new(),
+            author_name: String::new(),
+            author_email: String::new(),
+            breaking: false,
+            co_authors: Vec::new(),
+            stats: crate::git::DiffStats {
+                files_changed: 0,
+                insertions: 0,
+                deletions: 0,
+            },
+        };
+
+        let urls = ImageUrls::for_metadata(&config, &metadata);
+        assert_eq!(urls.url, "/lol/images/repo-20240101-000000-abc.png");
+    }
+}
@@ -0,0 +1,301 @@
+//! Golden-image tests for the chyron renderer.
+//!
+//! Each case renders onto a deterministic synthetic image with the bundled DejaVu Sans
+//! Mono font, or DejaVu Sans for proportional layout (so fontconfig differences between
+//! machines can't change the output) and
+//! compares the result against `tests/goldens/chyron/<case>.png`.
+//!
+//! After an intentional rendering change, regenerate the references with:
+//!
+//! ```text
+//! UPDATE_GOLDENS=1 cargo test --test golden_chyron
+//! ```
+//!
+//! and review the changed PNGs before committing them. New cases are bootstrapped the
+//! same way; without `UPDATE_GOLDENS=1` a missing reference fails the test.
+
+use ab_glyph::FontArc;
+use image::{DynamicImage, Rgba, RgbaImage};
+use std::path::PathBuf;
+use sw1nn_lolcommits_rs::chyron::{
+    CHYRON_HEIGHT, ChyronFonts, overlay_chyron, render_chyron_overlay,
+};
+use sw1nn_lolcommits_rs::config::{
+    BurnedInChyronConfig, ChyronPosition, ChyronStyle, MessageOverflow,
+};
+use sw1nn_lolcommits_rs::git::{self, CommitMetadata, DiffStats};
+use test_case::test_case;
+
+const FONT: &[u8] = include_bytes!("fixtures/fonts/DejaVuSansMono.ttf");
+const PROPORTIONAL_FONT: &[u8] = include_bytes!("fixtures/fonts/DejaVuSans.ttf");
+
+/// Maximum per-channel difference before a pixel counts as changed.
+const CHANNEL_TOLERANCE: u8 = 8;
+
+/// Fraction of pixels allowed to exceed the channel tolerance (anti-aliasing jitter).
+const MAX_CHANGED_FRACTION: f64 = 0.001;
+
+fn fonts() -> ChyronFonts {
+    ChyronFonts::uniform(FontArc::try_from_slice(FONT).expect("bundled font should parse"))
+}
+
+/// Deterministic flat-coloured tiles: varied enough to check the chyron blending while
+/// keeping the reference PNGs small.
+fn synthetic_image(width: u32, height: u32) -> DynamicImage {
+    let image = RgbaImage::from_fn(width, height, |x, y| {
+        let (tx, ty) = (x / 32, y / 32);
+        Rgba([
+            (tx * 37 % 256) as u8,
+            (ty * 59 % 256) as u8,
+            ((tx + ty) * 23 % 256) as u8,
+            255,
+        ])
+    });
+    DynamicImage::ImageRgba8(image)
+}
+
+fn metadata(message: &str, revision: &str, stats: (u32, u32, u32)) -> CommitMetadata {
+    let (files_changed, insertions, deletions) = stats;
+    let first_line = message.lines().next().unwrap_or(message);
+
+    CommitMetadata {
+        path: PathBuf::new(),
+        revision: revision.to_owned(),
+        message: message.to_owned(),
+        commit_type: git::parse_commit_type(message),
+        scope: git::parse_commit_scope(first_line),
+        timestamp: "2024-01-15 12:34:56".to_owned(),
+        repo_name: "golden-repo".to_owned(),
+        branch_name: "main".to_owned(),
+        author_name: String::new(),
+        author_email: String::new(),
+        breaking: git::is_breaking_change(message),
+        co_authors: git::parse_co_authors(message),
+        stats: DiffStats {
+            files_changed,
+            insertions,
+            deletions,
+        },
+    }
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/goldens/chyron")
+        .join(format!("{name}.png"))
+}
+
+fn assert_matches_golden(name: &str, actual: &RgbaImage) {
+    let path = golden_path(name);
+    let update = std::env::var_os("UPDATE_GOLDENS").is_some_and(|v| v == "1");
+
+    if update {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        actual.save(&path).unwrap();
+        return;
+    }
+    assert!(
+        path.exists(),
+        "golden {name} is missing from {}; generate it with \
+         UPDATE_GOLDENS=1 cargo test --test golden_chyron",
+        path.display()
+    );
+
+    let expected = image::open(&path).unwrap().to_rgba8();
+    assert_eq!(
+        expected.dimensions(),
+        actual.dimensions(),
+        "golden {name} has different dimensions"
+    );
+
+    let changed = expected
+        .pixels()
+        .zip(actual.pixels())
+        .filter(|(e, a)| {
+            e.0.iter()
+                .zip(a.0.iter())
+                .any(|(x, y)| x.abs_diff(*y) > CHANNEL_TOLERANCE)
+        })
+        .count();
+    let total = (actual.width() * actual.height()) as f64;
+
+    if changed as f64 / total > MAX_CHANGED_FRACTION {
+        let actual_path = std::env::temp_dir().join(format!("golden-{name}.actual.png"));
+        actual.save(&actual_path).ok();
+        panic!(
+            "golden {name}: {changed} pixels differ (actual written to {}); \
+             rerun with UPDATE_GOLDENS=1 if the change is intended",
+            actual_path.display()
+        );
+    }
+}
+
+#[test_case("feat_with_scope", (640, 480), "feat(core): add golden tests", (3, 42, 7) ; "feat with scope")]
+#[test_case("empty_scope", (640, 480), "fix: handle the empty case", (1, 2, 1) ; "empty scope")]
+#[test_case("plain_message", (640, 480), "Update README", (1, 10, 0) ; "non conventional message")]
+#[test_case("no_stats", (640, 480), "chore: tidy up", (0, 0, 0) ; "no stats")]
+#[test_case("huge_stats", (640, 480), "chore(deps): bump everything", (1234, 1_567_890, 48_213) ; "huge stats")]
+#[test_case("long_message", (640, 480), "feat(render): this commit message is far too long to fit on the chyron without running into the revision", (2, 12, 3) ; "long message")]
+#[test_case("multiline", (640, 480), "fix(git): first line only\n\nThe body should never be drawn.", (1, 1, 1) ; "multiline body ignored")]
+#[test_case("unicode", (640, 480), "feat(i18n): naïve café — Ünïcödé ✓", (2, 5, 0) ; "unicode message")]
+#[test_case("breaking", (640, 480), "feat(api)!: drop the v1 endpoints", (5, 10, 250) ; "breaking change")]
+#[test_case("small_frame", (320, 240), "feat: tiny camera", (1, 3, 1) ; "small frame")]
+#[test_case("large_frame", (1280, 720), "feat: hd camera", (4, 120, 30) ; "large frame")]
+fn test_chyron_golden(name: &str, size: (u32, u32), message: &str, stats: (u32, u32, u32)) {
+    let config = BurnedInChyronConfig::default();
+    let image = synthetic_image(size.0, size.1);
+    let metadata = metadata(message, "0123456789abcdef0123456789abcdef01234567", stats);
+
+    let rendered = overlay_chyron(&config, &fonts(), image, &metadata).unwrap();
+
+    assert_matches_golden(name, &rendered.to_rgba8());
+}
+
+#[test]
+fn test_chyron_golden_empty_revision() {
+    let config = BurnedInChyronConfig::default();
+    let metadata = metadata("feat: no revision", "", (1, 1, 0));
+
+    let rendered = overlay_chyron(&config, &fonts(), synthetic_image(640, 480), &metadata).unwrap();
+
+    assert_matches_golden("empty_revision", &rendered.to_rgba8());
+}
+
+#[test]
+fn test_chyron_golden_opaque() {
+    let config = BurnedInChyronConfig {
+        chyron_opacity: 1.0,
+        ..Default::default()
+    };
+    let metadata = metadata("feat(style): opaque band", "abcdef0", (2, 8, 4));
+
+    let rendered = overlay_chyron(&config, &fonts(), synthetic_image(640, 480), &metadata).unwrap();
+
+    assert_matches_golden("opaque", &rendered.to_rgba8());
+}
+
+#[test]
+fn test_chyron_golden_locale() {
+    let config = BurnedInChyronConfig {
+        locale: Some("de-DE".to_string()),
+        ..Default::default()
+    };
+    let metadata = metadata(
+        "feat(i18n): localized chyron",
+        "abcdef0",
+        (1234, 1_567_890, 48_213),
+    );
+
+    let rendered = overlay_chyron(&config, &fonts(), synthetic_image(640, 480), &metadata).unwrap();
+
+    assert_matches_golden("locale_de", &rendered.to_rgba8());
+}
+
+#[test]
+fn test_chyron_golden_custom_layout() {
+    let config = BurnedInChyronConfig {
+        position: ChyronPosition::Top,
+        height_px: Some(100),
+        margin_px: Some(40),
+        background_color: "#003366".to_string(),
+        message_color: "#ffcc00".to_string(),
+        info_color: "#ddeeff".to_string(),
+        sha_color: "#ffffff".to_string(),
+        stats_color: Some("#ffffff".to_string()),
+        ..Default::default()
+    };
+    let metadata = metadata("feat(style): configurable chyron", "abcdef0", (2, 8, 4));
+
+    let rendered = overlay_chyron(&config, &fonts(), synthetic_image(640, 480), &metadata).unwrap();
+
+    assert_matches_golden("custom_layout", &rendered.to_rgba8());
+}
+
+#[test]
+fn test_chyron_golden_proportional_font() {
+    let config = BurnedInChyronConfig::default();
+    let metadata = metadata(
+        "feat(layout): measure proportional text instead of counting characters",
+        "abcdef0",
+        (12, 4567, 890),
+    );
+    let fonts = ChyronFonts::uniform(
+        FontArc::try_from_slice(PROPORTIONAL_FONT).expect("bundled font should parse"),
+    );
+
+    let rendered = overlay_chyron(&config, &fonts, synthetic_image(640, 480), &metadata).unwrap();
+
+    assert_matches_golden("proportional", &rendered.to_rgba8());
+}
+
+#[test_case("overflow_wrap", MessageOverflow::Wrap ; "wrap")]
+#[test_case("overflow_shrink", MessageOverflow::Shrink ; "shrink")]
+fn test_chyron_golden_message_overflow(name: &str, message_overflow: MessageOverflow) {
+    let config = BurnedInChyronConfig {
+        message_overflow,
+        ..Default::default()
+    };
+    let metadata = metadata(
+        "feat(render): this commit message is far too long to fit on the chyron without running into the revision",
+        "abcdef0",
+        (2, 12, 3),
+    );
+
+    let rendered = overlay_chyron(&config, &fonts(), synthetic_image(640, 480), &metadata).unwrap();
+
+    assert_matches_golden(name, &rendered.to_rgba8());
+}
+
+#[test_case("meme", (640, 480), "feat(memes): classic captions" ; "meme")]
+#[test_case("meme_long_message", (640, 480), "fix(render): a commit message long enough that it has to wrap onto a second line and still gets cut short at the end" ; "meme long message")]
+#[test_case("meme_small_frame", (320, 240), "feat: tiny camera" ; "meme small frame")]
+fn test_meme_golden(name: &str, size: (u32, u32), message: &str) {
+    let config = BurnedInChyronConfig {
+        style: ChyronStyle::Meme,
+        ..Default::default()
+    };
+    let metadata = metadata(message, "abcdef0", (2, 8, 4));
+    let fonts = ChyronFonts::uniform(
+        FontArc::try_from_slice(PROPORTIONAL_FONT).expect("bundled font should parse"),
+    );
+
+    let rendered =
+        overlay_chyron(&config, &fonts, synthetic_image(size.0, size.1), &metadata).unwrap();
+
+    assert_matches_golden(name, &rendered.to_rgba8());
+}
+
+#[test]
+fn test_chyron_overlay_is_transparent_outside_band() {
+    let config = BurnedInChyronConfig::default();
+    let metadata = metadata("feat(video): overlay only", "abcdef0", (3, 42, 7));
+
+    let overlay = render_chyron_overlay(&config, &fonts(), 640, 480, &metadata);
+
+    assert_eq!(overlay.dimensions(), (640, 480));
+    let band_top = 480 - CHYRON_HEIGHT;
+    assert!(
+        overlay
+            .enumerate_pixels()
+            .filter(|(_, y, _)| *y < band_top)
+            .all(|(_, _, pixel)| pixel.0 == [0, 0, 0, 0])
+    );
+
+    let band_alpha = (config.chyron_opacity * 255.0).round() as u8;
+    let band: Vec<_> = overlay
+        .enumerate_pixels()
+        .filter(|(_, y, _)| *y >= band_top)
+        .map(|(_, _, pixel)| pixel.0)
+        .collect();
+    assert!(band.iter().all(|[_, _, _, a]| *a >= band_alpha));
+
+    // Text is drawn opaque over the translucent band
+    let text_pixels = band
+        .iter()
+        .filter(|[r, g, b, a]| *a == 255 && (*r > 128 || *g > 128 || *b > 128))
+        .count();
+    assert!(
+        text_pixels > 100,
+        "only {text_pixels} text pixels in the band"
+    );
+}
@@ -0,0 +1,169 @@
+//! Golden-file tests for the gallery statistics behind `/api/stats`.
+//!
+//! Each case aggregates the same synthetic gallery with a different filter and compares
+//! the JSON against `tests/goldens/stats/<case>.json`.
+//!
+//! After an intentional change to the statistics, regenerate the references with:
+//!
+//! ```text
+//! UPDATE_GOLDENS=1 cargo test --test golden_stats
+//! ```
+//!
+//! and review the diff before committing them. Without `UPDATE_GOLDENS=1` a missing
+//! reference fails the test.
+
+use std::path::PathBuf;
+use sw1nn_lolcommits_rs::best_of::TimeWindow;
+use sw1nn_lolcommits_rs::git::{self, CommitMetadata, DiffStats};
+use sw1nn_lolcommits_rs::stats::{self, Filter};
+use test_case::test_case;
+
+/// (repo, branch, author, timestamp, message, (files changed, insertions, deletions))
+type Commit = (
+    &'static str,
+    &'static str,
+    &'static str,
+    &'static str,
+    &'static str,
+    (u32, u32, u32),
+);
+
+const GALLERY: &[Commit] = &[
+    (
+        "app",
+        "main",
+        "Ada",
+        "2024-03-01 09:15:00",
+        "feat(api): add stats",
+        (4, 120, 8),
+    ),
+    (
+        "app",
+        "main",
+        "Ada",
+        "2024-03-01 16:40:00",
+        "fix(api): off by one",
+        (1, 2, 2),
+    ),
+    (
+        "app",
+        "feature/feed",
+        "Grace",
+        "2024-03-02 11:00:00",
+        "feat(feed): atom feed",
+        (3, 210, 0),
+    ),
+    (
+        "app",
+        "main",
+        "",
+        "2023-12-24 08:00:00",
+        "Initial commit",
+        (12, 900, 0),
+    ),
+    (
+        "lib",
+        "main",
+        "Grace",
+        "2024-03-02 18:05:00",
+        "refactor!: drop v1",
+        (7, 30, 450),
+    ),
+    (
+        "lib",
+        "main",
+        "Linus",
+        "2024-03-10 23:59:59",
+        "docs: readme",
+        (1, 15, 3),
+    ),
+    (
+        "lib",
+        "release",
+        "Linus",
+        "2024-04-01 00:00:00",
+        "chore(deps): bump",
+        (2, 40, 40),
+    ),
+];
+
+fn gallery() -> Vec<CommitMetadata> {
+    GALLERY
+        .iter()
+        .enumerate()
+        .map(
+            |(
+                i,
+                &(repo, branch, author, timestamp, message, (files_changed, insertions, deletions)),
+            )| {
+                let first_line = message.lines().next().unwrap_or(message);
+                CommitMetadata {
+                    path: PathBuf::new(),
+                    revision: format!("{i:07x}"),
+                    message: message.to_owned(),
+                    commit_type: git::parse_commit_type(message),
+                    scope: git::parse_commit_scope(first_line),
+                    timestamp: timestamp.to_owned(),
+                    repo_name: repo.to_owned(),
+                    branch_name: branch.to_owned(),
+                    author_name: author.to_owned(),
+                    author_email: String::new(),
+                    breaking: git::is_breaking_change(message),
+                    co_authors: Vec::new(),
+                    stats: DiffStats {
+                        files_changed,
+                        insertions,
+                        deletions,
+                    },
+                }
+            },
+        )
+        .collect()
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/goldens/stats")
+        .join(format!("{name}.json"))
+}
+
+fn assert_matches_golden(name: &str, actual: &str) {
+    let path = golden_path(name);
+    let update = std::env::var_os("UPDATE_GOLDENS").is_some_and(|v| v == "1");
+
+    if update {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+    assert!(
+        path.exists(),
+        "golden {name} is missing from {}; generate it with \
+         UPDATE_GOLDENS=1 cargo test --test golden_stats",
+        path.display()
+    );
+
+    let expected = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(
+        expected, actual,
+        "golden {name} differs; rerun with UPDATE_GOLDENS=1 if the change is intended"
+    );
+}
+
+#[test_case("all", None, None, None ; "whole gallery")]
+#[test_case("repo_app", Some("app"), None, None ; "one repo")]
+#[test_case("march_2024", None, Some("2024-03-01"), Some("2024-03-31") ; "date window")]
+#[test_case("lib_since_march_10", Some("lib"), Some("2024-03-10"), None ; "repo and since")]
+#[test_case("no_match", Some("missing"), None, None ; "nothing matches")]
+fn test_stats_golden(name: &str, repo: Option<&str>, since: Option<&str>, until: Option<&str>) {
+    let filter = Filter {
+        repo: repo.map(str::to_owned),
+        window: TimeWindow::parse(since, until).unwrap(),
+    };
+
+    let stats = stats::compute(&gallery(), &filter);
+
+    let mut json = serde_json::to_string_pretty(&stats).unwrap();
+    json.push('\n');
+    assert_matches_golden(name, &json);
+}